@@ -9,6 +9,8 @@ struct Claims {
     roles: Vec<String>,
     iat: u64,
     exp: u64,
+    /// Token id, used to revoke the token before it expires.
+    jti: String,
 }
 
 pub fn generate(
@@ -33,6 +35,7 @@ pub fn generate(
         roles: roles.to_vec(),
         iat: now,
         exp: now + ttl_secs,
+        jti: uuid::Uuid::new_v4().to_string(),
     };
 
     encode(
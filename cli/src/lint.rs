@@ -0,0 +1,78 @@
+//! `config validate` and `config schema`: check a server config file before
+//! deploying it, and describe the format for editors.
+
+use colored::Colorize;
+use fs9_config::{ConfigError, ConfigLoader};
+use std::path::Path;
+
+/// One line per problem, prefixed with the field it is about when known.
+pub fn describe(errors: &[ConfigError]) -> Vec<String> {
+    errors
+        .iter()
+        .map(|e| match e {
+            ConfigError::Invalid { field, message } => format!("{field}: {message}"),
+            other => other.to_string(),
+        })
+        .collect()
+}
+
+/// Print every problem with the config at `path`; fails if there is any.
+pub fn validate(path: &Path) -> Result<(), String> {
+    match ConfigLoader::check_file(path) {
+        Ok(_) => {
+            println!("{} {} is valid", "✓".green(), path.display());
+            Ok(())
+        }
+        Err(errors) => {
+            println!("{} {}", "✗".red(), path.display());
+            for line in describe(&errors) {
+                println!("  {line}");
+            }
+            Err(format!("{} problem(s) in {}", errors.len(), path.display()))
+        }
+    }
+}
+
+/// Print the JSON Schema of the config file.
+pub fn schema() -> Result<(), String> {
+    let schema = serde_json::to_string_pretty(&fs9_config::json_schema())
+        .map_err(|e| format!("Failed to serialize schema: {e}"))?;
+    println!("{schema}");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_problem_is_reported_with_its_field() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("fs9.yaml");
+        std::fs::write(
+            &path,
+            "server:\n  port: 0\nmounts:\n  - path: /a\n    provider: memfs\n  \
+             - path: /a\n    provider: memfs\n    op_timeout_secs: 0\n",
+        )
+        .unwrap();
+
+        let errors = ConfigLoader::check_file(&path).unwrap_err();
+        assert_eq!(
+            describe(&errors),
+            [
+                "server.port: must be between 1 and 65535",
+                "mounts[1].path: '/a' is mounted more than once",
+                "mounts[1].op_timeout_secs: must be greater than 0",
+            ]
+        );
+        assert!(validate(&path).unwrap_err().starts_with("3 problem(s)"));
+    }
+
+    #[test]
+    fn valid_file_passes() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("fs9.yaml");
+        std::fs::write(&path, "mounts:\n  - path: /\n    provider: memfs\n").unwrap();
+        assert!(validate(&path).is_ok());
+    }
+}
@@ -4,6 +4,8 @@ use serde::{Deserialize, Serialize};
 
 mod config;
 mod jwt;
+mod lint;
+mod transfer;
 
 use config::Config;
 
@@ -52,11 +54,78 @@ enum Commands {
     #[command(subcommand)]
     Mount(MountCommands),
 
-    /// Show current configuration
-    Config,
+    /// Copy files through the server. Paths are [namespace:]/path
+    Cp {
+        /// Source path
+        src: String,
+        /// Destination path, or an existing directory to copy into
+        dst: String,
+        /// Copy directories recursively
+        #[arg(short, long)]
+        recursive: bool,
+        /// Namespace for paths without a namespace prefix
+        #[arg(short, long, default_value = "default")]
+        namespace: String,
+    },
+
+    /// Move files through the server. Paths are [namespace:]/path
+    Mv {
+        /// Source path
+        src: String,
+        /// Destination path, or an existing directory to move into
+        dst: String,
+        /// Namespace for paths without a namespace prefix
+        #[arg(short, long, default_value = "default")]
+        namespace: String,
+    },
+
+    /// Show current configuration, or check a server config file
+    Config {
+        #[command(subcommand)]
+        command: Option<ConfigCommands>,
+    },
 
     /// Check server health
     Health,
+
+    /// Query fs9-meta's audit log of administrative actions
+    Audit {
+        /// fs9-meta URL
+        #[arg(long, env = "FS9_META_URL", default_value = "http://localhost:9998")]
+        meta: String,
+        /// fs9-meta admin key
+        #[arg(long, env = "FS9_META_KEY")]
+        meta_key: Option<String>,
+        /// Only actions by this actor
+        #[arg(long)]
+        actor: Option<String>,
+        /// Only this action, e.g. namespace.create or apikey.revoke
+        #[arg(long)]
+        action: Option<String>,
+        /// Only actions in this namespace
+        #[arg(short, long)]
+        namespace: Option<String>,
+        /// Only actions on this target (namespace, mount path, user or key id)
+        #[arg(long)]
+        target: Option<String>,
+        /// Only actions at or after this RFC 3339 time
+        #[arg(long)]
+        since: Option<String>,
+        /// Most entries to show, newest first
+        #[arg(long, default_value = "100")]
+        limit: u32,
+    },
+}
+
+#[derive(Subcommand)]
+enum ConfigCommands {
+    /// Check a server config file, reporting every problem with it
+    Validate {
+        /// Config file (YAML, TOML or JSON)
+        file: std::path::PathBuf,
+    },
+    /// Print the JSON Schema of the server config file
+    Schema,
 }
 
 #[derive(Subcommand)]
@@ -85,6 +154,30 @@ enum MountCommands {
         #[arg(short, long)]
         namespace: String,
     },
+    /// Re-create a mount's provider from new configuration
+    Update {
+        /// Target namespace
+        #[arg(short, long)]
+        namespace: String,
+        /// Mount path
+        #[arg(short, long)]
+        path: String,
+        /// Provider configuration as JSON (e.g., '{"uid": 1000}')
+        #[arg(short, long)]
+        config: Option<String>,
+        /// Set config values (can be repeated). Format: key=value or key.subkey=value
+        #[arg(long = "set", value_name = "KEY=VALUE")]
+        sets: Vec<String>,
+    },
+    /// Unmount a provider; fails while files under it are open
+    Remove {
+        /// Target namespace
+        #[arg(short, long)]
+        namespace: String,
+        /// Mount path
+        #[arg(short, long)]
+        path: String,
+    },
 }
 
 #[derive(Subcommand)]
@@ -110,6 +203,23 @@ enum NsCommands {
         /// Namespace name
         name: String,
     },
+    /// Show a namespace's storage usage against its quota, counted since
+    /// the server loaded the namespace
+    Usage {
+        /// Namespace name
+        name: String,
+    },
+    /// Set the byte budget shared by all of a namespace's mounts
+    Quota {
+        /// Namespace name
+        name: String,
+        /// Quota in bytes
+        #[arg(required_unless_present = "unlimited")]
+        bytes: Option<u64>,
+        /// Remove the quota instead
+        #[arg(long, conflicts_with = "bytes")]
+        unlimited: bool,
+    },
     /// Delete a namespace (if supported)
     Delete {
         /// Namespace name
@@ -145,6 +255,13 @@ enum TokenCommands {
         /// JWT token
         token: String,
     },
+    /// Revoke a token on the server until it expires
+    Revoke {
+        /// JWT token, or the token id (`jti` claim) alone
+        token: String,
+    },
+    /// List token ids that are currently revoked
+    ListRevoked,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -160,6 +277,58 @@ struct CreateNamespaceRequest {
     name: String,
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+struct SetQuotaRequest {
+    quota_bytes: Option<u64>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct NamespaceUsage {
+    name: String,
+    used_bytes: u64,
+    quota_bytes: Option<u64>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct RevokeTokenRequest {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    token: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    jti: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct RevokedToken {
+    jti: String,
+    expires_at: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct AuditEntryInfo {
+    actor: Option<String>,
+    action: String,
+    namespace: Option<String>,
+    target: Option<String>,
+    result: String,
+    created_at: String,
+}
+
+/// Filters for `fs9-admin audit`, passed through as fs9-meta query params.
+#[derive(Debug, Serialize)]
+struct AuditFilter {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    actor: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    action: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    namespace: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    target: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    since: Option<String>,
+    limit: u32,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 struct ErrorResponse {
     error: String,
@@ -182,8 +351,44 @@ fn main() {
 
     let result = match cli.command {
         Commands::Init { server, secret } => cmd_init(server, secret),
-        Commands::Config => cmd_config(&config),
+        Commands::Cp {
+            src,
+            dst,
+            recursive,
+            namespace,
+        } => cmd_cp(&config, &src, &dst, &namespace, recursive),
+        Commands::Mv {
+            src,
+            dst,
+            namespace,
+        } => cmd_mv(&config, &src, &dst, &namespace),
+        Commands::Config { command } => match command {
+            None => cmd_config(&config),
+            Some(ConfigCommands::Validate { file }) => lint::validate(&file),
+            Some(ConfigCommands::Schema) => lint::schema(),
+        },
         Commands::Health => cmd_health(&config),
+        Commands::Audit {
+            meta,
+            meta_key,
+            actor,
+            action,
+            namespace,
+            target,
+            since,
+            limit,
+        } => cmd_audit(
+            &meta,
+            meta_key.as_deref(),
+            &AuditFilter {
+                actor,
+                action,
+                namespace,
+                target,
+                since,
+                limit,
+            },
+        ),
         Commands::Mount(mount_cmd) => match mount_cmd {
             MountCommands::Add {
                 provider,
@@ -193,6 +398,15 @@ fn main() {
                 sets,
             } => cmd_mount_add(&config, &namespace, &path, &provider, cfg, sets),
             MountCommands::List { namespace } => cmd_mount_list(&config, &namespace),
+            MountCommands::Update {
+                namespace,
+                path,
+                config: cfg,
+                sets,
+            } => cmd_mount_update(&config, &namespace, &path, cfg, &sets),
+            MountCommands::Remove { namespace, path } => {
+                cmd_mount_remove(&config, &namespace, &path)
+            }
         },
         Commands::Ns(ns_cmd) => match ns_cmd {
             NsCommands::Create {
@@ -203,6 +417,10 @@ fn main() {
             } => cmd_ns_create(&config, &cli.admin_ns, &name, mount, mount_config, sets),
             NsCommands::List => cmd_ns_list(&config, &cli.admin_ns),
             NsCommands::Get { name } => cmd_ns_get(&config, &cli.admin_ns, &name),
+            NsCommands::Usage { name } => cmd_ns_usage(&config, &cli.admin_ns, &name),
+            NsCommands::Quota { name, bytes, .. } => {
+                cmd_ns_quota(&config, &cli.admin_ns, &name, bytes)
+            }
             NsCommands::Delete { name, force } => {
                 cmd_ns_delete(&config, &cli.admin_ns, &name, force)
             }
@@ -216,6 +434,8 @@ fn main() {
                 quiet,
             } => cmd_token_generate(&config, &user, &namespace, roles, ttl, quiet),
             TokenCommands::Decode { token } => cmd_token_decode(&token),
+            TokenCommands::Revoke { token } => cmd_token_revoke(&config, &cli.admin_ns, &token),
+            TokenCommands::ListRevoked => cmd_token_list_revoked(&config, &cli.admin_ns),
         },
     };
 
@@ -451,7 +671,7 @@ fn do_mount(
         &config.jwt_secret,
         "admin",
         namespace,
-        &["operator".to_string()],
+        &["admin".to_string()],
         3600,
     )?;
     let client = reqwest::blocking::Client::new();
@@ -497,6 +717,85 @@ fn cmd_mount_add(
     do_mount(config, namespace, path, provider, config_json, &sets)
 }
 
+fn cmd_mount_update(
+    config: &Config,
+    namespace: &str,
+    path: &str,
+    config_json: Option<String>,
+    sets: &[String],
+) -> Result<(), String> {
+    let token = jwt::generate(
+        &config.jwt_secret,
+        "admin",
+        namespace,
+        &["admin".to_string()],
+        3600,
+    )?;
+    let client = reqwest::blocking::Client::new();
+
+    let mount_config = merge_config(config_json, sets)?;
+
+    let resp = client
+        .put(format!("{}/api/v1/mount", config.server))
+        .header("Authorization", format!("Bearer {}", token))
+        .json(&serde_json::json!({
+            "path": path,
+            "config": mount_config
+        }))
+        .send()
+        .map_err(|e| format!("Request failed: {}", e))?;
+
+    match resp.status().as_u16() {
+        200 => {
+            println!(
+                "{} Updated mount {} (namespace: {})",
+                "✓".green(),
+                path.cyan(),
+                namespace
+            );
+            Ok(())
+        }
+        404 => Err(format!("No mount at '{}'", path)),
+        409 => Err(format!("Mount '{}' has open files", path)),
+        403 => Err("Permission denied".to_string()),
+        _ => Err(format!("Failed: {}", resp.text().unwrap_or_default())),
+    }
+}
+
+fn cmd_mount_remove(config: &Config, namespace: &str, path: &str) -> Result<(), String> {
+    let token = jwt::generate(
+        &config.jwt_secret,
+        "admin",
+        namespace,
+        &["admin".to_string()],
+        3600,
+    )?;
+    let client = reqwest::blocking::Client::new();
+
+    let resp = client
+        .delete(format!("{}/api/v1/mount", config.server))
+        .query(&[("path", path)])
+        .header("Authorization", format!("Bearer {}", token))
+        .send()
+        .map_err(|e| format!("Request failed: {}", e))?;
+
+    match resp.status().as_u16() {
+        200 | 204 => {
+            println!(
+                "{} Unmounted {} (namespace: {})",
+                "✓".green(),
+                path.cyan(),
+                namespace
+            );
+            Ok(())
+        }
+        404 => Err(format!("No mount at '{}'", path)),
+        409 => Err(format!("Mount '{}' has open files", path)),
+        403 => Err("Permission denied".to_string()),
+        _ => Err(format!("Failed: {}", resp.text().unwrap_or_default())),
+    }
+}
+
 fn cmd_mount_list(config: &Config, namespace: &str) -> Result<(), String> {
     let token = jwt::generate(
         &config.jwt_secret,
@@ -621,6 +920,86 @@ fn cmd_ns_get(config: &Config, admin_ns: &str, name: &str) -> Result<(), String>
     }
 }
 
+fn cmd_ns_usage(config: &Config, admin_ns: &str, name: &str) -> Result<(), String> {
+    let token = jwt::generate(
+        &config.jwt_secret,
+        "admin",
+        admin_ns,
+        &["admin".to_string()],
+        3600,
+    )?;
+    let client = reqwest::blocking::Client::new();
+    let url = format!("{}/api/v1/namespaces/{}/usage", config.server, name);
+
+    let resp = client
+        .get(&url)
+        .header("Authorization", format!("Bearer {}", token))
+        .send()
+        .map_err(|e| format!("Request failed: {}", e))?;
+
+    let status = resp.status();
+    let body = resp.text().unwrap_or_default();
+
+    match status.as_u16() {
+        200 => {
+            let usage: NamespaceUsage = serde_json::from_str(&body)
+                .map_err(|e| format!("Failed to parse response: {}", e))?;
+            print_usage(&usage);
+            Ok(())
+        }
+        404 => Err(format!("Namespace '{}' not found or not loaded", name)),
+        _ => Err(format!("Request failed ({}): {}", status, body)),
+    }
+}
+
+fn cmd_ns_quota(
+    config: &Config,
+    admin_ns: &str,
+    name: &str,
+    quota_bytes: Option<u64>,
+) -> Result<(), String> {
+    let token = jwt::generate(
+        &config.jwt_secret,
+        "admin",
+        admin_ns,
+        &["admin".to_string()],
+        3600,
+    )?;
+    let client = reqwest::blocking::Client::new();
+    let url = format!("{}/api/v1/namespaces/{}/quota", config.server, name);
+
+    let resp = client
+        .put(&url)
+        .header("Authorization", format!("Bearer {}", token))
+        .json(&SetQuotaRequest { quota_bytes })
+        .send()
+        .map_err(|e| format!("Request failed: {}", e))?;
+
+    let status = resp.status();
+    let body = resp.text().unwrap_or_default();
+
+    match status.as_u16() {
+        200 => {
+            let usage: NamespaceUsage = serde_json::from_str(&body)
+                .map_err(|e| format!("Failed to parse response: {}", e))?;
+            println!("{} Updated quota for: {}", "✓".green(), name.cyan());
+            print_usage(&usage);
+            Ok(())
+        }
+        403 => Err("Permission denied. Admin role required.".to_string()),
+        404 => Err(format!("Namespace '{}' not found or not loaded", name)),
+        _ => Err(format!("Request failed ({}): {}", status, body)),
+    }
+}
+
+fn print_usage(usage: &NamespaceUsage) {
+    println!("  Used:  {} bytes", usage.used_bytes);
+    match usage.quota_bytes {
+        Some(quota) => println!("  Quota: {} bytes", quota),
+        None => println!("  Quota: {}", "unlimited".green()),
+    }
+}
+
 fn cmd_ns_delete(config: &Config, admin_ns: &str, name: &str, force: bool) -> Result<(), String> {
     if !force {
         println!(
@@ -662,6 +1041,24 @@ fn cmd_ns_delete(config: &Config, admin_ns: &str, name: &str, force: bool) -> Re
     }
 }
 
+fn cmd_cp(
+    config: &Config,
+    src: &str,
+    dst: &str,
+    namespace: &str,
+    recursive: bool,
+) -> Result<(), String> {
+    let src = transfer::Location::parse(src, namespace)?;
+    let dst = transfer::Location::parse(dst, namespace)?;
+    transfer::copy(config, &src, &dst, recursive)
+}
+
+fn cmd_mv(config: &Config, src: &str, dst: &str, namespace: &str) -> Result<(), String> {
+    let src = transfer::Location::parse(src, namespace)?;
+    let dst = transfer::Location::parse(dst, namespace)?;
+    transfer::rename(config, &src, &dst)
+}
+
 fn cmd_token_generate(
     config: &Config,
     user: &str,
@@ -738,6 +1135,142 @@ fn cmd_token_decode(token: &str) -> Result<(), String> {
     Ok(())
 }
 
+fn cmd_token_revoke(config: &Config, admin_ns: &str, token_or_id: &str) -> Result<(), String> {
+    // A JWT has three dot-separated parts; anything else is taken as a bare id.
+    let is_jwt = token_or_id.split('.').count() == 3;
+    let request = if is_jwt {
+        RevokeTokenRequest {
+            token: Some(token_or_id.to_string()),
+            jti: None,
+        }
+    } else {
+        RevokeTokenRequest {
+            token: None,
+            jti: Some(token_or_id.to_string()),
+        }
+    };
+
+    let token = jwt::generate(
+        &config.jwt_secret,
+        "admin",
+        admin_ns,
+        &["admin".to_string()],
+        3600,
+    )?;
+    let client = reqwest::blocking::Client::new();
+    let url = format!("{}/api/v1/auth/revoke", config.server);
+
+    let resp = client
+        .post(&url)
+        .header("Authorization", format!("Bearer {}", token))
+        .json(&request)
+        .send()
+        .map_err(|e| format!("Request failed: {}", e))?;
+
+    let status = resp.status();
+    let body = resp.text().unwrap_or_default();
+
+    match status.as_u16() {
+        200 | 204 => {
+            if is_jwt {
+                println!("{} Token revoked", "✓".green());
+            } else {
+                println!("{} Revoked token id: {}", "✓".green(), token_or_id.cyan());
+            }
+            Ok(())
+        }
+        _ => Err(format!("Request failed ({}): {}", status, body)),
+    }
+}
+
+fn cmd_token_list_revoked(config: &Config, admin_ns: &str) -> Result<(), String> {
+    let token = jwt::generate(
+        &config.jwt_secret,
+        "admin",
+        admin_ns,
+        &["admin".to_string()],
+        3600,
+    )?;
+    let client = reqwest::blocking::Client::new();
+    let url = format!("{}/api/v1/auth/revoked", config.server);
+
+    let resp = client
+        .get(&url)
+        .header("Authorization", format!("Bearer {}", token))
+        .send()
+        .map_err(|e| format!("Request failed: {}", e))?;
+
+    let status = resp.status();
+    let body = resp.text().unwrap_or_default();
+
+    if !status.is_success() {
+        return Err(format!("Request failed ({}): {}", status, body));
+    }
+
+    let revoked: Vec<RevokedToken> =
+        serde_json::from_str(&body).map_err(|e| format!("Failed to parse response: {}", e))?;
+
+    println!("{}", "Revoked tokens:".bold());
+    if revoked.is_empty() {
+        println!("  (none)");
+    } else {
+        for id in revoked {
+            let expires = chrono::DateTime::from_timestamp(id.expires_at, 0)
+                .map(|dt| dt.format("%Y-%m-%d %H:%M:%S UTC").to_string())
+                .unwrap_or_else(|| "invalid".to_string());
+            println!("  {} {}", "•".cyan(), id.jti.bold());
+            println!("      Until: {}", expires);
+        }
+    }
+    Ok(())
+}
+
+fn cmd_audit(meta: &str, meta_key: Option<&str>, filter: &AuditFilter) -> Result<(), String> {
+    let client = reqwest::blocking::Client::new();
+    let url = format!("{}/api/v1/audit", meta.trim_end_matches('/'));
+
+    let mut req = client.get(&url).query(filter);
+    if let Some(key) = meta_key {
+        req = req.header("x-fs9-meta-key", key);
+    }
+    let resp = req.send().map_err(|e| format!("Request failed: {}", e))?;
+
+    let status = resp.status();
+    let body = resp.text().unwrap_or_default();
+
+    if !status.is_success() {
+        return Err(format!("Request failed ({}): {}", status, body));
+    }
+
+    let entries: Vec<AuditEntryInfo> =
+        serde_json::from_str(&body).map_err(|e| format!("Failed to parse response: {}", e))?;
+
+    println!("{}", "Audit log:".bold());
+    if entries.is_empty() {
+        println!("  (none)");
+    } else {
+        for entry in entries {
+            let result = if entry.result == "ok" {
+                entry.result.green()
+            } else {
+                entry.result.red()
+            };
+            println!(
+                "  {} {} {} {} ({})",
+                entry.created_at,
+                entry.actor.as_deref().unwrap_or("-").cyan(),
+                entry.action.bold(),
+                entry.target.as_deref().unwrap_or("-"),
+                result
+            );
+            if let Some(ns) = entry.namespace {
+                println!("      Namespace: {}", ns);
+            }
+        }
+    }
+    Ok(())
+}
+
 fn base64_decode(input: &str) -> Result<Vec<u8>, String> {
     use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
     URL_SAFE_NO_PAD
@@ -0,0 +1,461 @@
+//! `cp` and `mv` through the server's HTTP file API.
+//!
+//! Paths are written `[namespace:]/path`; each namespace gets its own admin
+//! token. File contents stream from `/api/v1/download` straight into
+//! `/api/v1/upload` without being buffered here.
+
+use colored::Colorize;
+use serde::Deserialize;
+use std::io::{self, Read, Write};
+
+use crate::config::Config;
+use crate::jwt;
+
+/// Files at least this large report progress while they copy.
+const PROGRESS_THRESHOLD: u64 = 8 * 1024 * 1024;
+
+/// A path in a namespace, parsed from `[namespace:]/path`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Location {
+    pub namespace: String,
+    pub path: String,
+}
+
+impl Location {
+    pub fn parse(spec: &str, default_namespace: &str) -> Result<Self, String> {
+        let (namespace, path) = match spec.split_once(':') {
+            Some((ns, path)) if !ns.contains('/') => (ns, path),
+            _ => (default_namespace, spec),
+        };
+        if namespace.is_empty() {
+            return Err(format!("Missing namespace in '{}'", spec));
+        }
+        if !path.starts_with('/') {
+            return Err(format!("Path must be absolute: '{}'", spec));
+        }
+        let path = match path.trim_end_matches('/') {
+            "" => "/",
+            trimmed => trimmed,
+        };
+        Ok(Self {
+            namespace: namespace.to_string(),
+            path: path.to_string(),
+        })
+    }
+
+    fn join(&self, name: &str) -> Self {
+        let path = if self.path == "/" {
+            format!("/{}", name)
+        } else {
+            format!("{}/{}", self.path, name)
+        };
+        Self {
+            namespace: self.namespace.clone(),
+            path,
+        }
+    }
+
+    fn name(&self) -> &str {
+        self.path.rsplit('/').next().unwrap_or("")
+    }
+
+    /// Whether `self` is `other` or lies below it.
+    fn is_within(&self, other: &Self) -> bool {
+        self.namespace == other.namespace
+            && (self.path == other.path
+                || other.path == "/"
+                || self.path.starts_with(&format!("{}/", other.path)))
+    }
+}
+
+impl std::fmt::Display for Location {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.namespace, self.path)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct Entry {
+    path: String,
+    size: u64,
+    file_type: String,
+}
+
+impl Entry {
+    fn is_dir(&self) -> bool {
+        self.file_type == "directory"
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct Mount {
+    path: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct Opened {
+    handle_id: String,
+}
+
+/// The server's file API as seen from one namespace.
+struct Remote {
+    server: String,
+    token: String,
+    client: reqwest::blocking::Client,
+}
+
+impl Remote {
+    fn connect(config: &Config, namespace: &str) -> Result<Self, String> {
+        let token = jwt::generate(
+            &config.jwt_secret,
+            "admin",
+            namespace,
+            &["admin".to_string()],
+            3600,
+        )?;
+        // Large files outlive the client's default 30 second timeout.
+        let client = reqwest::blocking::Client::builder()
+            .timeout(None)
+            .build()
+            .map_err(|e| format!("Failed to build HTTP client: {}", e))?;
+        Ok(Self {
+            server: config.server.clone(),
+            token,
+            client,
+        })
+    }
+
+    fn url(&self, endpoint: &str) -> String {
+        format!("{}/api/v1/{}", self.server, endpoint)
+    }
+
+    fn send(
+        &self,
+        request: reqwest::blocking::RequestBuilder,
+    ) -> Result<reqwest::blocking::Response, String> {
+        let resp = request
+            .header("Authorization", format!("Bearer {}", self.token))
+            .send()
+            .map_err(|e| format!("Request failed: {}", e))?;
+        if resp.status().is_success() {
+            Ok(resp)
+        } else {
+            let status = resp.status();
+            Err(format!(
+                "Request failed ({}): {}",
+                status,
+                resp.text().unwrap_or_default()
+            ))
+        }
+    }
+
+    fn stat(&self, path: &str) -> Result<Option<Entry>, String> {
+        let resp = self
+            .client
+            .get(self.url("stat"))
+            .query(&[("path", path)])
+            .header("Authorization", format!("Bearer {}", self.token))
+            .send()
+            .map_err(|e| format!("Request failed: {}", e))?;
+        match resp.status().as_u16() {
+            200 => resp
+                .json()
+                .map(Some)
+                .map_err(|e| format!("Failed to parse response: {}", e)),
+            404 => Ok(None),
+            _ => Err(format!(
+                "Failed to stat '{}': {}",
+                path,
+                resp.text().unwrap_or_default()
+            )),
+        }
+    }
+
+    fn readdir(&self, path: &str) -> Result<Vec<Entry>, String> {
+        self.send(
+            self.client
+                .get(self.url("readdir"))
+                .query(&[("path", path)]),
+        )?
+        .json()
+        .map_err(|e| format!("Failed to parse response: {}", e))
+    }
+
+    fn mkdir(&self, path: &str) -> Result<(), String> {
+        let opened: Opened = self
+            .send(self.client.post(self.url("open")).json(&serde_json::json!({
+                "path": path,
+                "flags": { "create": true, "directory": true },
+            })))?
+            .json()
+            .map_err(|e| format!("Failed to parse response: {}", e))?;
+        self.send(
+            self.client
+                .post(self.url("close"))
+                .json(&serde_json::json!({ "handle_id": opened.handle_id })),
+        )?;
+        Ok(())
+    }
+
+    fn download(&self, path: &str) -> Result<reqwest::blocking::Response, String> {
+        self.send(
+            self.client
+                .get(self.url("download"))
+                .query(&[("path", path)]),
+        )
+    }
+
+    fn upload(&self, path: &str, body: reqwest::blocking::Body) -> Result<(), String> {
+        self.send(
+            self.client
+                .put(self.url("upload"))
+                .query(&[("path", path)])
+                .body(body),
+        )?;
+        Ok(())
+    }
+
+    fn rename(&self, path: &str, to: &str) -> Result<(), String> {
+        self.send(
+            self.client
+                .post(self.url("wstat"))
+                .json(&serde_json::json!({
+                    "path": path,
+                    "changes": { "name": to },
+                })),
+        )?;
+        Ok(())
+    }
+
+    fn remove(&self, path: &str) -> Result<(), String> {
+        self.send(
+            self.client
+                .delete(self.url("remove"))
+                .query(&[("path", path)]),
+        )?;
+        Ok(())
+    }
+
+    /// Remove `path` and, for a directory, everything below it.
+    fn remove_tree(&self, path: &str, is_dir: bool) -> Result<(), String> {
+        if is_dir {
+            for entry in self.readdir(path)? {
+                self.remove_tree(&entry.path, entry.is_dir())?;
+            }
+        }
+        self.remove(path)
+    }
+
+    /// The mount serving `path`: the longest mount path that contains it.
+    fn mount_of(&self, path: &str) -> Result<String, String> {
+        let mounts: Vec<Mount> = self
+            .send(self.client.get(self.url("mounts")))?
+            .json()
+            .map_err(|e| format!("Failed to parse response: {}", e))?;
+        mounts
+            .into_iter()
+            .map(|m| m.path)
+            .filter(|mount| {
+                mount == "/" || path == mount || path.starts_with(&format!("{}/", mount))
+            })
+            .max_by_key(String::len)
+            .ok_or_else(|| format!("No mount serves '{}'", path))
+    }
+}
+
+/// Counts bytes as they are read and redraws a progress line on stderr.
+struct Progress<R> {
+    inner: R,
+    label: String,
+    done: u64,
+    total: u64,
+}
+
+impl<R: Read> Read for Progress<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.done += n as u64;
+        let percent = (self.done * 100).checked_div(self.total).unwrap_or(100);
+        eprint!(
+            "\r  {} {:>3}% ({} / {} MiB)",
+            self.label,
+            percent,
+            self.done / (1024 * 1024),
+            self.total / (1024 * 1024)
+        );
+        if n == 0 {
+            eprintln!();
+        }
+        io::stderr().flush().ok();
+        Ok(n)
+    }
+}
+
+/// Where `src` lands when copied or moved to `dst`: inside `dst` if that
+/// is an existing directory, otherwise at `dst` itself.
+fn destination(dst_remote: &Remote, src: &Location, dst: &Location) -> Result<Location, String> {
+    match dst_remote.stat(&dst.path)? {
+        Some(entry) if entry.is_dir() => Ok(dst.join(src.name())),
+        _ => Ok(dst.clone()),
+    }
+}
+
+fn copy_file(
+    src_remote: &Remote,
+    src: &Location,
+    dst_remote: &Remote,
+    dst: &Location,
+    size: u64,
+) -> Result<(), String> {
+    let download = src_remote.download(&src.path)?;
+    let body = if size >= PROGRESS_THRESHOLD {
+        let progress = Progress {
+            inner: download,
+            label: src.path.clone(),
+            done: 0,
+            total: size,
+        };
+        reqwest::blocking::Body::sized(progress, size)
+    } else {
+        reqwest::blocking::Body::sized(download, size)
+    };
+    dst_remote.upload(&dst.path, body)
+}
+
+/// Copy `src` to exactly `dst`; returns the number of files copied.
+fn copy_tree(
+    src_remote: &Remote,
+    src: &Location,
+    dst_remote: &Remote,
+    dst: &Location,
+    entry: &Entry,
+) -> Result<usize, String> {
+    if !entry.is_dir() {
+        copy_file(src_remote, src, dst_remote, dst, entry.size)?;
+        return Ok(1);
+    }
+
+    if dst_remote.stat(&dst.path)?.is_none() {
+        dst_remote.mkdir(&dst.path)?;
+    }
+    let mut copied = 0;
+    for child in src_remote.readdir(&src.path)? {
+        let name = child.path.rsplit('/').next().unwrap_or("").to_string();
+        copied += copy_tree(
+            src_remote,
+            &src.join(&name),
+            dst_remote,
+            &dst.join(&name),
+            &child,
+        )?;
+    }
+    Ok(copied)
+}
+
+/// Look up `src` and work out where it goes, refusing to copy a
+/// directory into itself.
+fn prepare(
+    src_remote: &Remote,
+    src: &Location,
+    dst_remote: &Remote,
+    dst: &Location,
+) -> Result<(Entry, Location), String> {
+    let entry = src_remote
+        .stat(&src.path)?
+        .ok_or_else(|| format!("'{}' not found", src))?;
+    let target = destination(dst_remote, src, dst)?;
+    if target == *src {
+        return Err(format!("'{}' and '{}' are the same file", src, target));
+    }
+    if entry.is_dir() && target.is_within(src) {
+        return Err(format!("Cannot copy '{}' into itself", src));
+    }
+    Ok((entry, target))
+}
+
+pub fn copy(
+    config: &Config,
+    src: &Location,
+    dst: &Location,
+    recursive: bool,
+) -> Result<(), String> {
+    let src_remote = Remote::connect(config, &src.namespace)?;
+    let dst_remote = Remote::connect(config, &dst.namespace)?;
+    let (entry, target) = prepare(&src_remote, src, &dst_remote, dst)?;
+    if entry.is_dir() && !recursive {
+        return Err(format!("'{}' is a directory (use -r to copy it)", src));
+    }
+
+    let copied = copy_tree(&src_remote, src, &dst_remote, &target, &entry)?;
+    if entry.is_dir() {
+        println!(
+            "{} Copied {} → {} ({} files)",
+            "✓".green(),
+            src.to_string().cyan(),
+            target.to_string().cyan(),
+            copied
+        );
+    } else {
+        println!(
+            "{} Copied {} → {} ({} bytes)",
+            "✓".green(),
+            src.to_string().cyan(),
+            target.to_string().cyan(),
+            entry.size
+        );
+    }
+    Ok(())
+}
+
+pub fn rename(config: &Config, src: &Location, dst: &Location) -> Result<(), String> {
+    let src_remote = Remote::connect(config, &src.namespace)?;
+    let dst_remote = Remote::connect(config, &dst.namespace)?;
+    let (entry, target) = prepare(&src_remote, src, &dst_remote, dst)?;
+
+    // One provider can rename in place; anything else is copied and deleted.
+    let same_provider = src.namespace == target.namespace
+        && src_remote.mount_of(&src.path)? == src_remote.mount_of(&target.path)?;
+    if same_provider {
+        src_remote.rename(&src.path, &target.path)?;
+    } else {
+        copy_tree(&src_remote, src, &dst_remote, &target, &entry)?;
+        src_remote.remove_tree(&src.path, entry.is_dir())?;
+    }
+
+    println!(
+        "{} Moved {} → {}",
+        "✓".green(),
+        src.to_string().cyan(),
+        target.to_string().cyan()
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_locations() {
+        let loc = Location::parse("team-a:/data/file.txt", "default").unwrap();
+        assert_eq!(loc.namespace, "team-a");
+        assert_eq!(loc.path, "/data/file.txt");
+
+        let loc = Location::parse("/data/dir/", "default").unwrap();
+        assert_eq!(loc.namespace, "default");
+        assert_eq!(loc.path, "/data/dir");
+
+        assert_eq!(Location::parse("team-a:/", "default").unwrap().path, "/");
+        assert!(Location::parse("relative.txt", "default").is_err());
+        assert!(Location::parse(":/file", "default").is_err());
+    }
+
+    #[test]
+    fn nested_locations() {
+        let dir = Location::parse("/a", "ns").unwrap();
+        assert!(Location::parse("/a/b", "ns").unwrap().is_within(&dir));
+        assert!(!Location::parse("/ab", "ns").unwrap().is_within(&dir));
+        assert!(!Location::parse("other:/a/b", "ns").unwrap().is_within(&dir));
+        assert_eq!(dir.join("c").path, "/a/c");
+    }
+}
@@ -0,0 +1,128 @@
+//! A live fs9-server with auth disabled, driven through `fs9-admin`.
+
+#![allow(dead_code)]
+
+use std::net::TcpListener;
+use std::path::PathBuf;
+use std::process::{Child, Command, Output, Stdio};
+use std::time::Duration;
+
+use serde_json::Value;
+
+pub struct TestServer {
+    process: Child,
+    pub url: String,
+}
+
+impl TestServer {
+    pub fn start() -> Self {
+        let workspace_root = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .parent()
+            .unwrap()
+            .to_path_buf();
+        let server_bin = ["target/debug/fs9-server", "target/release/fs9-server"]
+            .iter()
+            .map(|candidate| workspace_root.join(candidate))
+            .find(|candidate| candidate.exists())
+            .expect("fs9-server binary not found. Run `cargo build -p fs9-server` first.");
+        let port = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let url = format!("http://127.0.0.1:{port}");
+
+        let process = Command::new(server_bin)
+            .env("FS9_PORT", port.to_string())
+            .env("FS9_HOST", "127.0.0.1")
+            .env("FS9_JWT_SECRET", "")
+            .env("FS9_META_ENDPOINTS", "")
+            .env("FS9_META_KEY", "")
+            .env("FS9_SKIP_META_CHECK", "1")
+            .env("RUST_LOG", "warn")
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .unwrap();
+        let server = Self { process, url };
+
+        let client = reqwest::blocking::Client::new();
+        for _ in 0..50 {
+            std::thread::sleep(Duration::from_millis(100));
+            if client.get(format!("{}/health", server.url)).send().is_ok() {
+                return server;
+            }
+        }
+        panic!("server failed to start within 5 seconds");
+    }
+
+    pub fn admin(&self, args: &[&str]) -> Output {
+        Command::new(env!("CARGO_BIN_EXE_fs9-admin"))
+            .args(["--server", &self.url, "--secret", "unused"])
+            .args(args)
+            .output()
+            .unwrap()
+    }
+
+    pub fn mounts(&self) -> String {
+        let output = self.admin(&["mount", "list", "-n", "default"]);
+        assert!(output.status.success(), "{output:?}");
+        String::from_utf8(output.stdout).unwrap()
+    }
+
+    pub fn exists(&self, path: &str) -> bool {
+        reqwest::blocking::get(format!("{}/api/v1/stat?path={path}", self.url))
+            .unwrap()
+            .status()
+            .is_success()
+    }
+
+    pub fn upload(&self, path: &str, data: &[u8]) {
+        reqwest::blocking::Client::new()
+            .put(format!("{}/api/v1/upload?path={path}", self.url))
+            .body(data.to_vec())
+            .send()
+            .unwrap()
+            .error_for_status()
+            .unwrap();
+    }
+
+    /// A file's contents, or `None` if it doesn't exist.
+    pub fn download(&self, path: &str) -> Option<Vec<u8>> {
+        let resp =
+            reqwest::blocking::get(format!("{}/api/v1/download?path={path}", self.url)).unwrap();
+        resp.status()
+            .is_success()
+            .then(|| resp.bytes().unwrap().to_vec())
+    }
+
+    pub fn mkdir(&self, path: &str) {
+        let opened = self.post(
+            "open",
+            &serde_json::json!({ "path": path, "flags": { "create": true, "directory": true } }),
+        );
+        self.post(
+            "close",
+            &serde_json::json!({ "handle_id": opened["handle_id"] }),
+        );
+    }
+
+    pub fn post(&self, endpoint: &str, body: &Value) -> Value {
+        reqwest::blocking::Client::new()
+            .post(format!("{}/api/v1/{endpoint}", self.url))
+            .json(body)
+            .send()
+            .unwrap()
+            .error_for_status()
+            .unwrap()
+            .json()
+            .unwrap_or(Value::Null)
+    }
+}
+
+impl Drop for TestServer {
+    fn drop(&mut self) {
+        let _ = self.process.kill();
+        let _ = self.process.wait();
+    }
+}
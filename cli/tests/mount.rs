@@ -0,0 +1,62 @@
+//! `fs9-admin mount` against a live fs9-server with auth disabled.
+
+mod common;
+
+use common::TestServer;
+use serde_json::json;
+
+#[test]
+fn add_update_and_remove_a_mount() {
+    let server = TestServer::start();
+    let first = tempfile::tempdir().unwrap();
+    let second = tempfile::tempdir().unwrap();
+    std::fs::write(first.path().join("first.txt"), "1").unwrap();
+    std::fs::write(second.path().join("second.txt"), "2").unwrap();
+    let root = |dir: &tempfile::TempDir| format!("root={}", dir.path().display());
+
+    let output = server.admin(&[
+        "mount",
+        "add",
+        "localfs",
+        "-n",
+        "default",
+        "-p",
+        "/scratch",
+        "--set",
+        &root(&first),
+    ]);
+    assert!(output.status.success(), "{output:?}");
+    assert!(server.mounts().contains("/scratch → localfs"));
+    assert!(server.exists("/scratch/first.txt"));
+
+    let output = server.admin(&[
+        "mount",
+        "update",
+        "-n",
+        "default",
+        "-p",
+        "/scratch",
+        "--set",
+        &root(&second),
+    ]);
+    assert!(output.status.success(), "{output:?}");
+    assert!(server.mounts().contains("/scratch → localfs"));
+    assert!(server.exists("/scratch/second.txt"));
+    assert!(!server.exists("/scratch/first.txt"));
+
+    // An open file keeps the mount in place.
+    let opened = server.post(
+        "open",
+        &json!({ "path": "/scratch/second.txt", "flags": { "read": true } }),
+    );
+    let output = server.admin(&["mount", "remove", "-n", "default", "-p", "/scratch"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("open files"));
+    assert!(server.mounts().contains("/scratch"));
+
+    server.post("close", &json!({ "handle_id": opened["handle_id"] }));
+    let output = server.admin(&["mount", "remove", "-n", "default", "-p", "/scratch"]);
+    assert!(output.status.success(), "{output:?}");
+    assert!(!server.mounts().contains("/scratch"));
+    assert!(!server.exists("/scratch/second.txt"));
+}
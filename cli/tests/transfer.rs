@@ -0,0 +1,53 @@
+//! `fs9-admin cp` and `mv` against a live fs9-server with auth disabled.
+
+mod common;
+
+use common::TestServer;
+
+fn assert_ok(output: &std::process::Output) {
+    assert!(output.status.success(), "{output:?}");
+}
+
+#[test]
+fn copy_then_move_a_file() {
+    let server = TestServer::start();
+    let data: Vec<u8> = (0..200_000u32).map(|i| (i % 251) as u8).collect();
+    server.upload("/source.bin", &data);
+
+    assert_ok(&server.admin(&["cp", "/source.bin", "default:/copy.bin"]));
+    assert_eq!(server.download("/copy.bin").as_deref(), Some(&data[..]));
+    assert_eq!(server.download("/source.bin").as_deref(), Some(&data[..]));
+
+    assert_ok(&server.admin(&["mv", "/copy.bin", "/moved.bin"]));
+    assert_eq!(server.download("/copy.bin"), None);
+    assert_eq!(server.download("/moved.bin").as_deref(), Some(&data[..]));
+}
+
+#[test]
+fn copy_and_move_directories_across_mounts() {
+    let server = TestServer::start();
+    server.mkdir("/tree");
+    server.mkdir("/tree/sub");
+    server.upload("/tree/a.txt", b"a");
+    server.upload("/tree/sub/b.txt", b"b");
+
+    let output = server.admin(&["cp", "/tree", "/copied"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("use -r"));
+
+    assert_ok(&server.admin(&["cp", "-r", "/tree", "/copied"]));
+    assert_eq!(server.download("/copied/a.txt").as_deref(), Some(&b"a"[..]));
+    assert_eq!(
+        server.download("/copied/sub/b.txt").as_deref(),
+        Some(&b"b"[..])
+    );
+
+    // Moving onto another provider falls back to copy and delete.
+    assert_ok(&server.admin(&["mount", "add", "memfs", "-n", "default", "-p", "/other"]));
+    assert_ok(&server.admin(&["mv", "/copied", "/other"]));
+    assert_eq!(server.download("/copied/a.txt"), None);
+    assert_eq!(
+        server.download("/other/copied/sub/b.txt").as_deref(),
+        Some(&b"b"[..])
+    );
+}
@@ -4,10 +4,12 @@ use std::time::Duration;
 
 use bytes::Bytes;
 use futures_core::Stream;
-use reqwest::Client;
+use futures_util::{stream, StreamExt};
+use reqwest::{Client, RequestBuilder};
 use serde::Serialize;
 
 use crate::error::{Fs9Error, Result};
+use crate::retry::{is_retryable_error, is_retryable_status, retry_after, RetryPolicy};
 use crate::types::*;
 
 /// A stream of byte chunks from a download response.
@@ -26,9 +28,28 @@ impl Stream for ByteStream {
     }
 }
 
+/// Clones are cheap and share one connection pool, so a single client can
+/// be handed to any number of concurrent tasks.
+#[derive(Clone)]
 pub struct Fs9Client {
     client: Client,
     base_url: String,
+    retry: RetryPolicy,
+    chunk_size: usize,
+}
+
+/// Work left in a [`Fs9Client::walk_with`] traversal.
+enum WalkStep {
+    List(String, usize),
+    Visit(FileInfo, usize),
+}
+
+/// Progress of a [`Fs9Client::read_stream`].
+enum ReadState {
+    Open,
+    Reading(FileHandle, u64),
+    Closing(FileHandle),
+    Done,
 }
 
 impl Fs9Client {
@@ -44,22 +65,22 @@ impl Fs9Client {
         &self.base_url
     }
 
+    pub fn retry_policy(&self) -> &RetryPolicy {
+        &self.retry
+    }
+
     pub async fn health(&self) -> Result<bool> {
-        let resp = self
-            .client
-            .get(format!("{}/health", self.base_url))
-            .send()
-            .await?;
+        let request = self.client.get(format!("{}/health", self.base_url));
+        let resp = self.send(request, true).await?;
         Ok(resp.status().is_success())
     }
 
     pub async fn stat(&self, path: &str) -> Result<FileInfo> {
-        let resp = self
+        let request = self
             .client
             .get(format!("{}/api/v1/stat", self.base_url))
-            .query(&[("path", path)])
-            .send()
-            .await?;
+            .query(&[("path", path)]);
+        let resp = self.send(request, true).await?;
 
         self.handle_response::<FileInfoResponse>(resp)
             .await
@@ -73,23 +94,21 @@ impl Fs9Client {
             changes: StatChanges,
         }
 
-        let resp = self
+        let request = self
             .client
             .post(format!("{}/api/v1/wstat", self.base_url))
-            .json(&WstatRequest { path, changes })
-            .send()
-            .await?;
+            .json(&WstatRequest { path, changes });
+        let resp = self.send(request, false).await?;
 
         self.handle_empty_response(resp).await
     }
 
     pub async fn statfs(&self, path: &str) -> Result<FsStats> {
-        let resp = self
+        let request = self
             .client
             .get(format!("{}/api/v1/statfs", self.base_url))
-            .query(&[("path", path)])
-            .send()
-            .await?;
+            .query(&[("path", path)]);
+        let resp = self.send(request, true).await?;
 
         self.handle_response::<FsStatsResponse>(resp)
             .await
@@ -103,12 +122,13 @@ impl Fs9Client {
             flags: OpenFlags,
         }
 
-        let resp = self
+        // Opening without write/create/truncate/append has no side effects.
+        let idempotent = !(flags.write || flags.create || flags.truncate || flags.append);
+        let request = self
             .client
             .post(format!("{}/api/v1/open", self.base_url))
-            .json(&OpenRequest { path, flags })
-            .send()
-            .await?;
+            .json(&OpenRequest { path, flags });
+        let resp = self.send(request, idempotent).await?;
 
         let open_resp: OpenResponse = self.handle_response(resp).await?;
         Ok(FileHandle {
@@ -126,32 +146,35 @@ impl Fs9Client {
             size: usize,
         }
 
-        let resp = self
+        let request = self
             .client
             .post(format!("{}/api/v1/read", self.base_url))
             .json(&ReadRequest {
                 handle_id: &handle.id,
                 offset,
                 size,
-            })
-            .send()
-            .await?;
+            });
+        let resp = self.send(request, true).await?;
 
         if !resp.status().is_success() {
-            return Err(self.extract_error(resp).await);
+            // A drained stream reports its end once; to the caller that is
+            // the same as reading past the end of a file.
+            return match self.extract_error(resp).await {
+                Fs9Error::EndOfStream(_) => Ok(Bytes::new()),
+                err => Err(err),
+            };
         }
 
         Ok(resp.bytes().await?)
     }
 
     pub async fn write(&self, handle: &FileHandle, offset: u64, data: &[u8]) -> Result<usize> {
-        let resp = self
+        let request = self
             .client
             .post(format!("{}/api/v1/write", self.base_url))
             .query(&[("handle_id", &handle.id), ("offset", &offset.to_string())])
-            .body(data.to_vec())
-            .send()
-            .await?;
+            .body(data.to_vec());
+        let resp = self.send(request, false).await?;
 
         let write_resp: WriteResponse = self.handle_response(resp).await?;
         Ok(write_resp.bytes_written)
@@ -168,49 +191,156 @@ impl Fs9Client {
             sync: bool,
         }
 
-        let resp = self
+        let request = self
             .client
             .post(format!("{}/api/v1/close", self.base_url))
             .json(&CloseRequest {
                 handle_id: handle.id,
                 sync,
-            })
-            .send()
-            .await?;
+            });
+        let resp = self.send(request, false).await?;
+
+        self.handle_empty_response(resp).await
+    }
+
+    pub async fn fsync(&self, handle: &FileHandle, data_only: bool) -> Result<()> {
+        #[derive(Serialize)]
+        struct FsyncRequest<'a> {
+            handle_id: &'a str,
+            data_only: bool,
+        }
+
+        let request = self
+            .client
+            .post(format!("{}/api/v1/fsync", self.base_url))
+            .json(&FsyncRequest {
+                handle_id: &handle.id,
+                data_only,
+            });
+        let resp = self.send(request, false).await?;
+
+        self.handle_empty_response(resp).await
+    }
+
+    pub async fn getxattr(&self, path: &str, name: &str) -> Result<Bytes> {
+        let request = self
+            .client
+            .get(format!("{}/api/v1/xattr", self.base_url))
+            .query(&[("path", path), ("name", name)]);
+        let resp = self.send(request, true).await?;
+
+        if !resp.status().is_success() {
+            return Err(self.extract_error(resp).await);
+        }
+
+        Ok(resp.bytes().await?)
+    }
+
+    pub async fn setxattr(&self, path: &str, name: &str, value: &[u8]) -> Result<()> {
+        let request = self
+            .client
+            .put(format!("{}/api/v1/xattr", self.base_url))
+            .query(&[("path", path), ("name", name)])
+            .body(value.to_vec());
+        let resp = self.send(request, false).await?;
+
+        self.handle_empty_response(resp).await
+    }
+
+    pub async fn listxattr(&self, path: &str) -> Result<Vec<String>> {
+        let request = self
+            .client
+            .get(format!("{}/api/v1/xattrs", self.base_url))
+            .query(&[("path", path)]);
+        let resp = self.send(request, true).await?;
+
+        self.handle_response(resp).await
+    }
+
+    pub async fn removexattr(&self, path: &str, name: &str) -> Result<()> {
+        let request = self
+            .client
+            .delete(format!("{}/api/v1/xattr", self.base_url))
+            .query(&[("path", path), ("name", name)]);
+        let resp = self.send(request, false).await?;
 
         self.handle_empty_response(resp).await
     }
 
     pub async fn readdir(&self, path: &str) -> Result<Vec<FileInfo>> {
-        let resp = self
+        let request = self
             .client
             .get(format!("{}/api/v1/readdir", self.base_url))
-            .query(&[("path", path)])
-            .send()
-            .await?;
+            .query(&[("path", path)]);
+        let resp = self.send(request, true).await?;
 
         let entries: Vec<FileInfoResponse> = self.handle_response(resp).await?;
         Ok(entries.into_iter().map(Into::into).collect())
     }
 
+    /// Depth-first walk of everything below `root`, see [`Self::walk_with`].
+    pub fn walk<'a>(&'a self, root: &str) -> impl Stream<Item = Result<FileInfo>> + 'a {
+        self.walk_with(root, WalkOptions::default())
+    }
+
+    /// Depth-first, pre-order walk of everything below `root` (the root itself
+    /// is not yielded). Siblings are visited in path order so the output is
+    /// deterministic. A directory that fails to list yields an `Err` item and
+    /// the walk continues with its siblings.
+    pub fn walk_with<'a>(
+        &'a self,
+        root: &str,
+        options: WalkOptions,
+    ) -> impl Stream<Item = Result<FileInfo>> + 'a {
+        let pending = vec![WalkStep::List(root.to_string(), 0)];
+        stream::unfold(
+            (pending, options),
+            move |(mut pending, options)| async move {
+                while let Some(step) = pending.pop() {
+                    match step {
+                        WalkStep::List(path, depth) => match self.readdir(&path).await {
+                            Ok(mut entries) => {
+                                entries.sort_by(|a, b| b.path.cmp(&a.path));
+                                pending.extend(
+                                    entries.into_iter().map(|e| WalkStep::Visit(e, depth + 1)),
+                                );
+                            }
+                            Err(e) => return Some((Err(e), (pending, options))),
+                        },
+                        WalkStep::Visit(info, depth) => {
+                            if info.is_dir() {
+                                if options.skip_dir.as_ref().is_some_and(|skip| skip(&info)) {
+                                    continue;
+                                }
+                                if !options.max_depth.is_some_and(|max| depth >= max) {
+                                    pending.push(WalkStep::List(info.path.clone(), depth));
+                                }
+                            }
+                            return Some((Ok(info), (pending, options)));
+                        }
+                    }
+                }
+                None
+            },
+        )
+    }
+
     pub async fn remove(&self, path: &str) -> Result<()> {
-        let resp = self
+        let request = self
             .client
             .delete(format!("{}/api/v1/remove", self.base_url))
-            .query(&[("path", path)])
-            .send()
-            .await?;
+            .query(&[("path", path)]);
+        let resp = self.send(request, false).await?;
 
         self.handle_empty_response(resp).await
     }
 
     pub async fn capabilities(&self, path: &str) -> Result<Capabilities> {
-        let resp = self
+        let request = self
             .client
             .get(format!("{}/api/v1/capabilities", self.base_url))
-            .query(&[("path", path)])
-            .send()
-            .await?;
+            .query(&[("path", path)]);
+        let resp = self.send(request, true).await?;
 
         self.handle_response::<CapabilitiesResponse>(resp)
             .await
@@ -218,11 +348,8 @@ impl Fs9Client {
     }
 
     pub async fn list_mounts(&self) -> Result<Vec<MountInfo>> {
-        let resp = self
-            .client
-            .get(format!("{}/api/v1/mounts", self.base_url))
-            .send()
-            .await?;
+        let request = self.client.get(format!("{}/api/v1/mounts", self.base_url));
+        let resp = self.send(request, true).await?;
 
         let mounts: Vec<MountResponse> = self.handle_response(resp).await?;
         Ok(mounts.into_iter().map(Into::into).collect())
@@ -243,13 +370,99 @@ impl Fs9Client {
         Ok(())
     }
 
+    /// Stream a file's contents through sequential handle reads of
+    /// `chunk_size` bytes, ending at the first short read. Failures are
+    /// yielded as `Err` items, after which the stream ends.
+    pub fn read_stream<'a>(&'a self, path: &'a str) -> impl Stream<Item = Result<Bytes>> + 'a {
+        stream::unfold(ReadState::Open, move |state| async move {
+            let mut state = state;
+            loop {
+                state = match state {
+                    ReadState::Open => match self.open(path, OpenFlags::read()).await {
+                        Ok(handle) => ReadState::Reading(handle, 0),
+                        Err(e) => return Some((Err(e), ReadState::Done)),
+                    },
+                    ReadState::Reading(handle, offset) => {
+                        match self.read(&handle, offset, self.chunk_size).await {
+                            Ok(data) if data.len() < self.chunk_size => {
+                                if data.is_empty() {
+                                    ReadState::Closing(handle)
+                                } else {
+                                    return Some((Ok(data), ReadState::Closing(handle)));
+                                }
+                            }
+                            Ok(data) => {
+                                let next = offset + data.len() as u64;
+                                return Some((Ok(data), ReadState::Reading(handle, next)));
+                            }
+                            Err(e) => {
+                                let _ = self.close(handle).await;
+                                return Some((Err(e), ReadState::Done));
+                            }
+                        }
+                    }
+                    ReadState::Closing(handle) => {
+                        return match self.close(handle).await {
+                            Ok(()) => None,
+                            Err(e) => Some((Err(e), ReadState::Done)),
+                        };
+                    }
+                    ReadState::Done => return None,
+                };
+            }
+        })
+    }
+
+    /// Create or truncate `path` and write the stream to it chunk by chunk at
+    /// increasing offsets, without buffering the whole payload. Items larger
+    /// than `chunk_size` are split. Returns the number of bytes written.
+    pub async fn write_stream<S>(&self, path: &str, data: S) -> Result<u64>
+    where
+        S: Stream<Item = Result<Bytes>>,
+    {
+        let handle = self.open(path, OpenFlags::create_truncate()).await?;
+        match self.write_chunks(&handle, data).await {
+            Ok(written) => {
+                self.close(handle).await?;
+                Ok(written)
+            }
+            Err(e) => {
+                let _ = self.close(handle).await;
+                Err(e)
+            }
+        }
+    }
+
+    async fn write_chunks<S>(&self, handle: &FileHandle, data: S) -> Result<u64>
+    where
+        S: Stream<Item = Result<Bytes>>,
+    {
+        let mut data = std::pin::pin!(data);
+        let mut offset = 0u64;
+        while let Some(item) = data.next().await {
+            let mut chunk = item?;
+            while !chunk.is_empty() {
+                let piece = chunk.split_to(chunk.len().min(self.chunk_size));
+                let mut sent = 0;
+                while sent < piece.len() {
+                    let n = self.write(handle, offset, &piece[sent..]).await?;
+                    if n == 0 {
+                        return Err(Fs9Error::Server("write accepted no bytes".to_string()));
+                    }
+                    sent += n;
+                    offset += n as u64;
+                }
+            }
+        }
+        Ok(offset)
+    }
+
     pub async fn download(&self, path: &str) -> Result<Bytes> {
-        let resp = self
+        let request = self
             .client
             .get(format!("{}/api/v1/download", self.base_url))
-            .query(&[("path", path)])
-            .send()
-            .await?;
+            .query(&[("path", path)]);
+        let resp = self.send(request, true).await?;
 
         if !resp.status().is_success() {
             return Err(self.extract_error(resp).await);
@@ -259,13 +472,12 @@ impl Fs9Client {
     }
 
     pub async fn download_range(&self, path: &str, start: u64, end: u64) -> Result<Bytes> {
-        let resp = self
+        let request = self
             .client
             .get(format!("{}/api/v1/download", self.base_url))
             .query(&[("path", path)])
-            .header("Range", format!("bytes={start}-{end}"))
-            .send()
-            .await?;
+            .header("Range", format!("bytes={start}-{end}"));
+        let resp = self.send(request, true).await?;
 
         if !resp.status().is_success() {
             return Err(self.extract_error(resp).await);
@@ -275,12 +487,11 @@ impl Fs9Client {
     }
 
     pub async fn download_stream(&self, path: &str) -> Result<ByteStream> {
-        let resp = self
+        let request = self
             .client
             .get(format!("{}/api/v1/download", self.base_url))
-            .query(&[("path", path)])
-            .send()
-            .await?;
+            .query(&[("path", path)]);
+        let resp = self.send(request, true).await?;
 
         if !resp.status().is_success() {
             return Err(self.extract_error(resp).await);
@@ -292,13 +503,12 @@ impl Fs9Client {
     }
 
     pub async fn upload(&self, path: &str, data: impl Into<reqwest::Body>) -> Result<usize> {
-        let resp = self
+        let request = self
             .client
             .put(format!("{}/api/v1/upload", self.base_url))
             .query(&[("path", path)])
-            .body(data)
-            .send()
-            .await?;
+            .body(data);
+        let resp = self.send(request, false).await?;
 
         let upload_resp: UploadResponse = self.handle_response(resp).await?;
         Ok(upload_resp.bytes_written)
@@ -349,8 +559,21 @@ impl Fs9Client {
         self.wstat(path, StatChanges::new().mode(mode)).await
     }
 
+    /// Set the size of the file at `path`, zero-filling any growth.
     pub async fn truncate(&self, path: &str, size: u64) -> Result<()> {
-        self.wstat(path, StatChanges::new().size(size)).await
+        #[derive(Serialize)]
+        struct TruncateRequest<'a> {
+            path: &'a str,
+            size: u64,
+        }
+
+        let request = self
+            .client
+            .post(format!("{}/api/v1/truncate", self.base_url))
+            .json(&TruncateRequest { path, size });
+        let resp = self.send(request, false).await?;
+
+        self.handle_empty_response(resp).await
     }
 
     pub async fn rename(&self, path: &str, new_name: &str) -> Result<()> {
@@ -364,12 +587,11 @@ impl Fs9Client {
             path: &'a str,
         }
 
-        let resp = self
+        let request = self
             .client
             .post(format!("{}/api/v1/plugin/load", self.base_url))
-            .json(&LoadPluginRequest { name, path })
-            .send()
-            .await?;
+            .json(&LoadPluginRequest { name, path });
+        let resp = self.send(request, false).await?;
 
         self.handle_response::<LoadPluginResponse>(resp)
             .await
@@ -382,22 +604,20 @@ impl Fs9Client {
             name: &'a str,
         }
 
-        let resp = self
+        let request = self
             .client
             .post(format!("{}/api/v1/plugin/unload", self.base_url))
-            .json(&UnloadPluginRequest { name })
-            .send()
-            .await?;
+            .json(&UnloadPluginRequest { name });
+        let resp = self.send(request, false).await?;
 
         self.handle_empty_response(resp).await
     }
 
     pub async fn list_plugins(&self) -> Result<Vec<String>> {
-        let resp = self
+        let request = self
             .client
-            .get(format!("{}/api/v1/plugin/list", self.base_url))
-            .send()
-            .await?;
+            .get(format!("{}/api/v1/plugin/list", self.base_url));
+        let resp = self.send(request, true).await?;
 
         self.handle_response(resp).await
     }
@@ -416,16 +636,15 @@ impl Fs9Client {
             config: Option<serde_json::Value>,
         }
 
-        let resp = self
+        let request = self
             .client
             .post(format!("{}/api/v1/mount", self.base_url))
             .json(&MountPluginRequest {
                 path: mount_path,
                 provider,
                 config,
-            })
-            .send()
-            .await?;
+            });
+        let resp = self.send(request, false).await?;
 
         self.handle_response::<MountResponse>(resp)
             .await
@@ -447,16 +666,42 @@ impl Fs9Client {
             params.push(("type", event_type.clone()));
         }
 
-        let resp = self
+        let request = self
             .client
             .get(format!("{}/api/v1/events", self.base_url))
-            .query(&params)
-            .send()
-            .await?;
+            .query(&params);
+        let resp = self.send(request, true).await?;
 
         self.handle_response(resp).await
     }
 
+    /// Send a request, retrying transient failures according to the retry
+    /// policy. Non-idempotent requests are only retried when the policy
+    /// opts in, and streaming bodies that cannot be cloned are sent once.
+    async fn send(&self, request: RequestBuilder, idempotent: bool) -> Result<reqwest::Response> {
+        if !self.retry.applies_to(idempotent) {
+            return Ok(request.send().await?);
+        }
+
+        let mut attempt = 1;
+        loop {
+            let Some(this_try) = request.try_clone() else {
+                return Ok(request.send().await?);
+            };
+            let last = attempt >= self.retry.max_attempts;
+            let delay = match this_try.send().await {
+                Ok(resp) if !last && is_retryable_status(resp.status()) => {
+                    retry_after(resp.headers()).unwrap_or_else(|| self.retry.backoff(attempt))
+                }
+                Err(err) if !last && is_retryable_error(&err) => self.retry.backoff(attempt),
+                result => return Ok(result?),
+            };
+            tracing::debug!(attempt, ?delay, "retrying fs9 request");
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        }
+    }
+
     async fn handle_response<T: serde::de::DeserializeOwned>(
         &self,
         resp: reqwest::Response,
@@ -474,15 +719,18 @@ impl Fs9Client {
         Ok(())
     }
 
+    /// Turn a failed response into an error. Most server errors carry an
+    /// `ErrorResponse` body; a plain-text body (such as the rate limiter's)
+    /// is taken as the message.
     async fn extract_error(&self, resp: reqwest::Response) -> Fs9Error {
         let status = resp.status().as_u16();
-        match resp.json::<ErrorResponse>().await {
-            Ok(err_resp) => Fs9Error::from_response(status, err_resp.error),
-            Err(_) => Fs9Error::Request {
-                status,
-                message: "unknown error".to_string(),
-            },
-        }
+        let body = resp.text().await.unwrap_or_default();
+        let message = match serde_json::from_str::<ErrorResponse>(&body) {
+            Ok(err_resp) => err_resp.error,
+            Err(_) if body.trim().is_empty() => "unknown error".to_string(),
+            Err(_) => body,
+        };
+        Fs9Error::from_response(status, message)
     }
 }
 
@@ -490,6 +738,11 @@ pub struct Fs9ClientBuilder {
     base_url: String,
     timeout: Duration,
     token: Option<String>,
+    retry: RetryPolicy,
+    chunk_size: usize,
+    pool_max_idle_per_host: Option<usize>,
+    http2_prior_knowledge: bool,
+    tcp_keepalive: Option<Duration>,
 }
 
 impl Fs9ClientBuilder {
@@ -498,21 +751,93 @@ impl Fs9ClientBuilder {
             base_url: base_url.trim_end_matches('/').to_string(),
             timeout: Duration::from_secs(30),
             token: None,
+            retry: RetryPolicy::default(),
+            chunk_size: 1024 * 1024,
+            pool_max_idle_per_host: None,
+            http2_prior_knowledge: false,
+            tcp_keepalive: None,
         }
     }
 
-    pub fn timeout(mut self, timeout: Duration) -> Self {
+    #[must_use]
+    pub const fn timeout(mut self, timeout: Duration) -> Self {
         self.timeout = timeout;
         self
     }
 
+    #[must_use]
     pub fn token(mut self, token: impl Into<String>) -> Self {
         self.token = Some(token.into());
         self
     }
 
+    /// Retry idempotent requests up to `max_attempts` times in total on
+    /// connection errors, timeouts, 5xx and 429 responses, with jittered
+    /// exponential backoff starting at `base_backoff`. A `Retry-After`
+    /// header on the response takes precedence over the computed backoff.
+    #[must_use]
+    pub fn retry(mut self, max_attempts: u32, base_backoff: Duration) -> Self {
+        self.retry.max_attempts = max_attempts.max(1);
+        self.retry.base_backoff = base_backoff;
+        self
+    }
+
+    /// Also retry non-idempotent requests (writes, removes, closes, ...).
+    /// A retried write may be applied twice if the first attempt reached the
+    /// server, so this is off by default.
+    #[must_use]
+    pub const fn retry_writes(mut self, enabled: bool) -> Self {
+        self.retry.retry_writes = enabled;
+        self
+    }
+
+    /// Bytes requested per call by `read_stream` and sent per call by
+    /// `write_stream`. Defaults to 1 MiB.
+    #[must_use]
+    pub fn chunk_size(mut self, chunk_size: usize) -> Self {
+        self.chunk_size = chunk_size.max(1);
+        self
+    }
+
+    /// Idle connections kept open to the server for reuse. Unlimited by
+    /// default; 0 closes every connection once its request is done.
+    #[must_use]
+    pub const fn pool_max_idle_per_host(mut self, max: usize) -> Self {
+        self.pool_max_idle_per_host = Some(max);
+        self
+    }
+
+    /// Speak HTTP/2 from the first byte instead of HTTP/1.1, multiplexing
+    /// every concurrent request over one connection. The server must accept
+    /// cleartext HTTP/2 (fs9-server does).
+    #[must_use]
+    pub const fn http2_prior_knowledge(mut self, enabled: bool) -> Self {
+        self.http2_prior_knowledge = enabled;
+        self
+    }
+
+    /// Send TCP keep-alive probes on idle connections at this interval, so
+    /// pooled connections dropped by middleboxes are noticed.
+    #[must_use]
+    pub const fn tcp_keepalive(mut self, interval: Duration) -> Self {
+        self.tcp_keepalive = Some(interval);
+        self
+    }
+
+    /// # Errors
+    ///
+    /// Fails if the token cannot be sent as a header or the HTTP client
+    /// cannot be set up.
     pub fn build(self) -> Result<Fs9Client> {
-        let mut builder = Client::builder().timeout(self.timeout);
+        let mut builder = Client::builder()
+            .timeout(self.timeout)
+            .tcp_keepalive(self.tcp_keepalive);
+        if let Some(max) = self.pool_max_idle_per_host {
+            builder = builder.pool_max_idle_per_host(max);
+        }
+        if self.http2_prior_knowledge {
+            builder = builder.http2_prior_knowledge();
+        }
 
         if let Some(token) = &self.token {
             let mut headers = reqwest::header::HeaderMap::new();
@@ -532,6 +857,8 @@ impl Fs9ClientBuilder {
         Ok(Fs9Client {
             client,
             base_url: self.base_url,
+            retry: self.retry,
+            chunk_size: self.chunk_size,
         })
     }
 }
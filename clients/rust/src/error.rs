@@ -29,9 +29,36 @@ pub enum Fs9Error {
     #[error("directory not empty: {0}")]
     DirectoryNotEmpty(String),
 
+    /// The file changed under a conditional write, such as an etag or
+    /// version mismatch.
+    #[error("conflict: {0}")]
+    Conflict(String),
+
+    #[error("rate limited: {0}")]
+    RateLimited(String),
+
+    #[error("not implemented: {0}")]
+    NotImplemented(String),
+
+    #[error("no space left: {0}")]
+    NoSpace(String),
+
     #[error("invalid handle")]
     InvalidHandle,
 
+    /// A closed stream has been read to the end. [`Fs9Client::read`]
+    /// reports this as an empty read, like the end of a file.
+    ///
+    /// [`Fs9Client::read`]: crate::Fs9Client::read
+    #[error("end of stream: {0}")]
+    EndOfStream(String),
+
+    /// The server failed with a 5xx status not covered above.
+    #[error("server error: {status} - {message}")]
+    ServerError { status: u16, message: String },
+
+    /// The server answered in a way the client cannot make sense of, such
+    /// as a write that accepted no bytes.
     #[error("server error: {0}")]
     Server(String),
 
@@ -45,7 +72,23 @@ pub enum Fs9Error {
 impl Fs9Error {
     pub(crate) fn from_response(status: u16, message: String) -> Self {
         let msg = message.trim().to_string();
+        let detail = |prefix: &str| msg.strip_prefix(prefix).map(|s| s.trim().to_string());
         match status {
+            // The server reports several distinct errors as 400; the message
+            // prefix (the SDK error's Display) tells them apart.
+            400 => {
+                if let Some(path) = detail("not a directory:") {
+                    Self::NotDirectory(path)
+                } else if let Some(path) = detail("is a directory:") {
+                    Self::IsDirectory(path)
+                } else if let Some(path) = detail("directory not empty:") {
+                    Self::DirectoryNotEmpty(path)
+                } else if msg.starts_with("invalid handle") {
+                    Self::InvalidHandle
+                } else {
+                    Self::InvalidArgument(detail("invalid argument:").unwrap_or(msg))
+                }
+            }
             404 => Self::NotFound(
                 msg.strip_prefix("not found:")
                     .map(|s| s.trim().to_string())
@@ -56,24 +99,56 @@ impl Fs9Error {
                     .map(|s| s.trim().to_string())
                     .unwrap_or(msg),
             ),
-            409 => Self::AlreadyExists(
-                msg.strip_prefix("already exists:")
+            409 => detail("already exists:").map_or_else(
+                || Self::Conflict(detail("conflict:").unwrap_or_else(|| msg.clone())),
+                Self::AlreadyExists,
+            ),
+            410 => Self::EndOfStream(detail("end of stream:").unwrap_or(msg)),
+            429 => Self::RateLimited(msg),
+            501 => Self::NotImplemented(
+                msg.strip_prefix("not implemented:")
                     .map(|s| s.trim().to_string())
                     .unwrap_or(msg),
             ),
-            400 => Self::InvalidArgument(
-                msg.strip_prefix("invalid argument:")
+            504 => Self::Timeout,
+            507 => Self::NoSpace(
+                msg.strip_prefix("no space left:")
                     .map(|s| s.trim().to_string())
                     .unwrap_or(msg),
             ),
-            504 => Self::Timeout,
-            500..=599 => Self::Server(msg),
+            500..=599 => Self::ServerError {
+                status,
+                message: msg,
+            },
             _ => Self::Request {
                 status,
                 message: msg,
             },
         }
     }
+
+    /// The HTTP status the server reports this kind of error with, for
+    /// logging alongside the message. `None` for errors raised by the client
+    /// itself, and for timeouts, which are usually the client's own deadline.
+    #[must_use]
+    pub fn status(&self) -> Option<u16> {
+        match self {
+            Self::NotFound(_) => Some(404),
+            Self::PermissionDenied(_) => Some(403),
+            Self::AlreadyExists(_) | Self::Conflict(_) => Some(409),
+            Self::InvalidArgument(_)
+            | Self::NotDirectory(_)
+            | Self::IsDirectory(_)
+            | Self::DirectoryNotEmpty(_)
+            | Self::InvalidHandle => Some(400),
+            Self::EndOfStream(_) => Some(410),
+            Self::RateLimited(_) => Some(429),
+            Self::NotImplemented(_) => Some(501),
+            Self::NoSpace(_) => Some(507),
+            Self::ServerError { status, .. } | Self::Request { status, .. } => Some(*status),
+            Self::Connection(_) | Self::Server(_) | Self::Timeout | Self::Serialization(_) => None,
+        }
+    }
 }
 
 impl From<reqwest::Error> for Fs9Error {
@@ -94,4 +169,106 @@ impl From<serde_json::Error> for Fs9Error {
     }
 }
 
+impl From<Fs9Error> for std::io::Error {
+    fn from(err: Fs9Error) -> Self {
+        use std::io::ErrorKind;
+        let kind = match &err {
+            Fs9Error::NotFound(_) => ErrorKind::NotFound,
+            Fs9Error::PermissionDenied(_) => ErrorKind::PermissionDenied,
+            Fs9Error::AlreadyExists(_) => ErrorKind::AlreadyExists,
+            Fs9Error::InvalidArgument(_) | Fs9Error::InvalidHandle => ErrorKind::InvalidInput,
+            Fs9Error::NotImplemented(_) => ErrorKind::Unsupported,
+            Fs9Error::Timeout => ErrorKind::TimedOut,
+            Fs9Error::EndOfStream(_) => ErrorKind::UnexpectedEof,
+            Fs9Error::Connection(_) => ErrorKind::ConnectionAborted,
+            _ => ErrorKind::Other,
+        };
+        Self::new(kind, err)
+    }
+}
+
 pub type Result<T> = std::result::Result<T, Fs9Error>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn from(status: u16, message: &str) -> Fs9Error {
+        Fs9Error::from_response(status, message.to_string())
+    }
+
+    #[test]
+    fn bad_request_is_split_by_message() {
+        assert!(matches!(from(400, "not a directory: /f"), Fs9Error::NotDirectory(p) if p == "/f"));
+        assert!(matches!(from(400, "is a directory: /d"), Fs9Error::IsDirectory(p) if p == "/d"));
+        assert!(matches!(
+            from(400, "directory not empty: /d"),
+            Fs9Error::DirectoryNotEmpty(p) if p == "/d"
+        ));
+        assert!(matches!(
+            from(400, "invalid handle: 7"),
+            Fs9Error::InvalidHandle
+        ));
+        assert!(matches!(
+            from(400, "invalid argument: bad offset"),
+            Fs9Error::InvalidArgument(m) if m == "bad offset"
+        ));
+        assert!(
+            matches!(from(400, "missing path"), Fs9Error::InvalidArgument(m) if m == "missing path")
+        );
+    }
+
+    #[test]
+    fn statuses_map_to_variants() {
+        assert!(matches!(from(404, "not found: /x"), Fs9Error::NotFound(p) if p == "/x"));
+        assert!(matches!(
+            from(403, "permission denied: ro"),
+            Fs9Error::PermissionDenied(_)
+        ));
+        assert!(matches!(
+            from(409, "already exists: /x"),
+            Fs9Error::AlreadyExists(_)
+        ));
+        assert!(matches!(
+            from(501, "not implemented: link"),
+            Fs9Error::NotImplemented(_)
+        ));
+        assert!(matches!(from(504, "timeout after 30s"), Fs9Error::Timeout));
+        assert!(matches!(
+            from(507, "no space left: 4 pages over quota"),
+            Fs9Error::NoSpace(m) if m == "4 pages over quota"
+        ));
+        assert!(matches!(
+            from(409, "conflict: ETag mismatch (expected a, got b)"),
+            Fs9Error::Conflict(m) if m == "ETag mismatch (expected a, got b)"
+        ));
+        assert!(matches!(
+            from(409, "version conflict: expected 1, got 2"),
+            Fs9Error::Conflict(m) if m == "version conflict: expected 1, got 2"
+        ));
+        assert!(matches!(
+            from(410, "end of stream: /logs/app"),
+            Fs9Error::EndOfStream(p) if p == "/logs/app"
+        ));
+        assert!(matches!(
+            from(429, "User rate limit exceeded"),
+            Fs9Error::RateLimited(_)
+        ));
+        assert!(matches!(
+            from(503, "unavailable"),
+            Fs9Error::ServerError { status: 503, message } if message == "unavailable"
+        ));
+        assert!(matches!(
+            from(413, "too large"),
+            Fs9Error::Request { status: 413, .. }
+        ));
+    }
+
+    #[test]
+    fn status_survives_the_mapping() {
+        for status in [400, 403, 404, 409, 410, 413, 429, 500, 501, 503, 507] {
+            assert_eq!(from(status, "x").status(), Some(status));
+        }
+        assert_eq!(Fs9Error::Connection("refused".into()).status(), None);
+    }
+}
@@ -0,0 +1,201 @@
+//! [`Fs9File`]: an open FS9 file behind the blocking `std::io` traits.
+
+use std::future::Future;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+
+use bytes::{Buf, Bytes};
+use tokio::runtime::Handle;
+
+use crate::client::Fs9Client;
+use crate::error::{Fs9Error, Result};
+use crate::types::{FileHandle, OpenFlags};
+
+/// Bytes fetched per read call and buffered before a write call.
+const BUFFER_SIZE: usize = 64 * 1024;
+
+/// A file opened with [`Fs9Client::open_file`], read and written through
+/// `Read`, `Write` and `Seek` at a position it keeps itself, so `io::copy`,
+/// `BufReader` and friends work over FS9.
+///
+/// Reads are fetched [`BUFFER_SIZE`] bytes at a time at least, and writes
+/// are held until that many have accumulated, a seek, a read, `flush` or
+/// `close`. Dropping the file flushes and closes it, ignoring errors, and
+/// only waits for that outside a runtime context; call
+/// [`close`](Self::close) to see errors.
+///
+/// Each call blocks on the runtime the file was opened on, so use it from
+/// a thread outside that runtime, such as `tokio::task::spawn_blocking`,
+/// not from async code.
+pub struct Fs9File {
+    client: Fs9Client,
+    runtime: Handle,
+    handle: FileHandle,
+    closed: bool,
+    /// Where the next read or write starts.
+    pos: u64,
+    /// Bytes of the file from `pos` on, fetched ahead of the caller.
+    read_buf: Bytes,
+    /// Bytes written by the caller that end at `pos` and are not sent yet.
+    write_buf: Vec<u8>,
+}
+
+impl Fs9Client {
+    /// Open `path` as an [`Fs9File`], positioned at the start of the file.
+    ///
+    /// # Errors
+    ///
+    /// Fails as [`open`](Self::open) does.
+    pub async fn open_file(&self, path: &str, flags: OpenFlags) -> Result<Fs9File> {
+        let handle = self.open(path, flags).await?;
+        Ok(Fs9File {
+            client: self.clone(),
+            runtime: Handle::current(),
+            handle,
+            closed: false,
+            pos: 0,
+            read_buf: Bytes::new(),
+            write_buf: Vec::new(),
+        })
+    }
+}
+
+impl Fs9File {
+    /// The handle the file was opened with.
+    pub const fn handle(&self) -> &FileHandle {
+        &self.handle
+    }
+
+    /// Send buffered writes and close the handle, reporting any failure.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the buffered writes or the close fail; the handle is
+    /// closed even if the writes do.
+    pub fn close(mut self) -> Result<()> {
+        let flushed = self.flush_writes();
+        self.closed = true;
+        let closed = self.block_on(self.client.close(self.handle.clone()));
+        flushed.and(closed)
+    }
+
+    fn block_on<F: Future>(&self, future: F) -> F::Output {
+        self.runtime.block_on(future)
+    }
+
+    /// Send `write_buf` to the file, at the offset it ends at `pos` from.
+    fn flush_writes(&mut self) -> Result<()> {
+        if self.write_buf.is_empty() {
+            return Ok(());
+        }
+        let data = std::mem::take(&mut self.write_buf);
+        let start = self.pos - data.len() as u64;
+        self.block_on(write_all(&self.client, &self.handle, start, &data))
+    }
+}
+
+/// Write all of `data` at `offset`, in as many calls as the server needs.
+async fn write_all(
+    client: &Fs9Client,
+    handle: &FileHandle,
+    offset: u64,
+    data: &[u8],
+) -> Result<()> {
+    let mut sent = 0;
+    while sent < data.len() {
+        let n = client
+            .write(handle, offset + sent as u64, &data[sent..])
+            .await?;
+        if n == 0 {
+            return Err(Fs9Error::Server("write accepted no bytes".to_string()));
+        }
+        sent += n;
+    }
+    Ok(())
+}
+
+impl Read for Fs9File {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        self.flush_writes()?;
+        if self.read_buf.is_empty() {
+            let size = buf.len().max(BUFFER_SIZE);
+            self.read_buf = self.block_on(self.client.read(&self.handle, self.pos, size))?;
+        }
+
+        // An empty read from the server is the end of the file.
+        let n = buf.len().min(self.read_buf.len());
+        buf[..n].copy_from_slice(&self.read_buf[..n]);
+        self.read_buf.advance(n);
+        self.pos += n as u64;
+        Ok(n)
+    }
+}
+
+impl Write for Fs9File {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        // Anything read ahead may be about to change.
+        self.read_buf.clear();
+        self.write_buf.extend_from_slice(buf);
+        self.pos += buf.len() as u64;
+        if self.write_buf.len() >= BUFFER_SIZE {
+            self.flush_writes()?;
+        }
+        Ok(buf.len())
+    }
+
+    /// Send buffered writes to the server. The handle stays open.
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(self.flush_writes()?)
+    }
+}
+
+impl Seek for Fs9File {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.flush_writes()?;
+        let (base, delta) = match pos {
+            SeekFrom::Start(offset) => (offset, 0),
+            SeekFrom::Current(delta) => (self.pos, delta),
+            SeekFrom::End(delta) => {
+                let info = self.block_on(self.client.stat(self.handle.path()))?;
+                (info.size, delta)
+            }
+        };
+        let target = base.checked_add_signed(delta).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "seek to a negative or overflowing position",
+            )
+        })?;
+
+        if target != self.pos {
+            self.read_buf.clear();
+            self.pos = target;
+        }
+        Ok(self.pos)
+    }
+}
+
+impl Drop for Fs9File {
+    fn drop(&mut self) {
+        if self.closed {
+            return;
+        }
+        let handle = self.handle.clone();
+        let client = self.client.clone();
+        let data = std::mem::take(&mut self.write_buf);
+        let start = self.pos - data.len() as u64;
+        let finish = async move {
+            let _ = write_all(&client, &handle, start, &data).await;
+            let _ = client.close(handle).await;
+        };
+        // Blocking may panic inside the runtime, so there the flush and
+        // close are left to a task.
+        if Handle::try_current().is_ok() {
+            self.runtime.spawn(finish);
+        } else {
+            self.runtime.block_on(finish);
+        }
+    }
+}
@@ -1,9 +1,13 @@
 mod client;
 mod error;
+mod file;
+mod retry;
 mod types;
 
-pub use client::{ByteStream, Fs9Client};
+pub use client::{ByteStream, Fs9Client, Fs9ClientBuilder};
 pub use error::{Fs9Error, Result};
+pub use file::Fs9File;
+pub use retry::RetryPolicy;
 pub use types::*;
 
 #[cfg(test)]
@@ -0,0 +1,116 @@
+use std::time::Duration;
+
+use rand::Rng;
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+use reqwest::StatusCode;
+
+/// Upper bound for a single computed backoff, regardless of attempt count.
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+/// How the client retries requests that fail transiently.
+///
+/// Only idempotent requests (stat, readdir, read, read-only open, downloads)
+/// are retried unless `retry_writes` is set.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Total attempts including the first one; `1` disables retries.
+    pub max_attempts: u32,
+    /// Backoff before the second attempt; doubled for each one after.
+    pub base_backoff: Duration,
+    /// Also retry non-idempotent requests such as writes and removes.
+    pub retry_writes: bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 1,
+            base_backoff: Duration::from_millis(100),
+            retry_writes: false,
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub(crate) fn applies_to(&self, idempotent: bool) -> bool {
+        self.max_attempts > 1 && (idempotent || self.retry_writes)
+    }
+
+    /// Jittered exponential backoff after the given (1-based) failed attempt:
+    /// a random delay between half and all of `base_backoff * 2^(attempt-1)`.
+    pub(crate) fn backoff(&self, attempt: u32) -> Duration {
+        let exp = self
+            .base_backoff
+            .saturating_mul(1u32 << attempt.saturating_sub(1).min(16))
+            .min(MAX_BACKOFF);
+        let half = exp / 2;
+        half + exp
+            .saturating_sub(half)
+            .mul_f64(rand::thread_rng().gen::<f64>())
+    }
+}
+
+pub(crate) fn is_retryable_status(status: StatusCode) -> bool {
+    status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS
+}
+
+pub(crate) fn is_retryable_error(err: &reqwest::Error) -> bool {
+    err.is_connect() || err.is_timeout()
+}
+
+/// Delay requested by a `Retry-After` header, in either delta-seconds or
+/// HTTP-date form.
+pub(crate) fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let value = headers.get(RETRY_AFTER)?.to_str().ok()?.trim();
+    if let Ok(secs) = value.parse::<u64>() {
+        return Some(Duration::from_secs(secs));
+    }
+    let at = httpdate::parse_http_date(value).ok()?;
+    Some(
+        at.duration_since(std::time::SystemTime::now())
+            .unwrap_or_default(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backoff_doubles_within_jitter_bounds() {
+        let policy = RetryPolicy {
+            max_attempts: 5,
+            base_backoff: Duration::from_millis(100),
+            retry_writes: false,
+        };
+        for (attempt, full) in [(1, 100), (2, 200), (3, 400)] {
+            let delay = policy.backoff(attempt);
+            assert!(delay >= Duration::from_millis(full / 2), "{delay:?}");
+            assert!(delay <= Duration::from_millis(full), "{delay:?}");
+        }
+        assert!(policy.backoff(40) <= MAX_BACKOFF);
+    }
+
+    #[test]
+    fn retries_only_apply_to_writes_when_opted_in() {
+        let mut policy = RetryPolicy {
+            max_attempts: 3,
+            ..RetryPolicy::default()
+        };
+        assert!(policy.applies_to(true));
+        assert!(!policy.applies_to(false));
+        policy.retry_writes = true;
+        assert!(policy.applies_to(false));
+        assert!(!RetryPolicy::default().applies_to(true));
+    }
+
+    #[test]
+    fn retry_after_accepts_seconds() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(retry_after(&headers), None);
+        headers.insert(RETRY_AFTER, "7".parse().unwrap());
+        assert_eq!(retry_after(&headers), Some(Duration::from_secs(7)));
+        headers.insert(RETRY_AFTER, "soon".parse().unwrap());
+        assert_eq!(retry_after(&headers), None);
+    }
+}
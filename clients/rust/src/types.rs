@@ -87,6 +87,9 @@ pub struct OpenFlags {
     pub append: bool,
     #[serde(default)]
     pub directory: bool,
+    /// With `create`, fail with `AlreadyExists` if the path exists.
+    #[serde(default)]
+    pub exclusive: bool,
 }
 
 impl OpenFlags {
@@ -166,6 +169,9 @@ pub struct StatChanges {
     pub name: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub symlink_target: Option<String>,
+    /// Seconds until the file expires; 0 clears an existing expiry.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ttl: Option<u64>,
 }
 
 impl StatChanges {
@@ -198,6 +204,11 @@ impl StatChanges {
         self
     }
 
+    pub fn ttl(mut self, secs: u64) -> Self {
+        self.ttl = Some(secs);
+        self
+    }
+
     pub fn symlink(mut self, target: impl Into<String>) -> Self {
         self.symlink_target = Some(target.into());
         self
@@ -392,6 +403,46 @@ pub struct EventsQuery {
     pub event_type: Option<String>,
 }
 
+type DirFilter = Box<dyn Fn(&FileInfo) -> bool + Send + Sync>;
+
+/// Options for [`crate::Fs9Client::walk_with`].
+///
+/// Symlinks are yielded as entries but never followed, so a link pointing
+/// back up the tree cannot cause an endless walk.
+#[derive(Default)]
+pub struct WalkOptions {
+    pub(crate) max_depth: Option<usize>,
+    pub(crate) skip_dir: Option<DirFilter>,
+}
+
+impl WalkOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only descend this many levels; `1` lists just the root's children.
+    pub fn max_depth(mut self, depth: usize) -> Self {
+        self.max_depth = Some(depth);
+        self
+    }
+
+    /// Directories for which `pred` returns true are neither yielded nor
+    /// descended into.
+    pub fn skip_dir(mut self, pred: impl Fn(&FileInfo) -> bool + Send + Sync + 'static) -> Self {
+        self.skip_dir = Some(Box::new(pred));
+        self
+    }
+}
+
+impl std::fmt::Debug for WalkOptions {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WalkOptions")
+            .field("max_depth", &self.max_depth)
+            .field("skip_dir", &self.skip_dir.is_some())
+            .finish()
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct PluginInfo {
     pub name: String,
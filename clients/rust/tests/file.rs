@@ -0,0 +1,228 @@
+//! `Fs9File`'s `std::io` traits against an in-process server that keeps
+//! files in memory and, like a real one, may return short reads. Files under
+//! `/streams` behave like closed streams and report their end as an error.
+
+use std::collections::HashMap;
+use std::io::{BufReader, Read, Seek, SeekFrom, Write};
+use std::sync::{Arc, Mutex};
+
+use axum::body::Bytes;
+use axum::extract::{Query, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use fs9_client::{Fs9Client, Fs9File, OpenFlags};
+use serde_json::{json, Value};
+use tokio::net::TcpListener;
+
+/// Most bytes the server returns from one read.
+const MAX_READ: usize = 50_000;
+
+#[derive(Default)]
+struct Files {
+    contents: HashMap<String, Vec<u8>>,
+    handles: HashMap<String, String>,
+}
+
+type Shared = Arc<Mutex<Files>>;
+
+fn info(path: &str, size: usize) -> Value {
+    json!({
+        "path": path, "size": size, "file_type": "regular", "mode": 420,
+        "uid": 0, "gid": 0, "atime": 0, "mtime": 0, "ctime": 0,
+        "etag": "", "symlink_target": null,
+    })
+}
+
+async fn server() -> String {
+    async fn open(State(files): State<Shared>, Json(req): Json<Value>) -> Json<Value> {
+        let mut files = files.lock().unwrap();
+        let path = req["path"].as_str().unwrap().to_string();
+        let content = files.contents.entry(path.clone()).or_default();
+        if req["flags"]["truncate"] == json!(true) {
+            content.clear();
+        }
+        let size = content.len();
+        let id = (files.handles.len() + 1).to_string();
+        files.handles.insert(id.clone(), path.clone());
+        Json(json!({ "handle_id": id, "metadata": info(&path, size) }))
+    }
+
+    async fn read(State(files): State<Shared>, Json(req): Json<Value>) -> Response {
+        let number = |field: &str| usize::try_from(req[field].as_u64().unwrap()).unwrap();
+        let files = files.lock().unwrap();
+        let path = &files.handles[req["handle_id"].as_str().unwrap()];
+        let content = &files.contents[path];
+        let offset = number("offset").min(content.len());
+        if offset == content.len() && path.starts_with("/streams/") {
+            let error = json!({ "error": format!("end of stream: {path}"), "code": 410 });
+            return (StatusCode::GONE, Json(error)).into_response();
+        }
+        let end = content.len().min(offset + number("size").min(MAX_READ));
+        Bytes::copy_from_slice(&content[offset..end]).into_response()
+    }
+
+    async fn write(
+        State(files): State<Shared>,
+        Query(query): Query<HashMap<String, String>>,
+        body: Bytes,
+    ) -> Json<Value> {
+        let mut files = files.lock().unwrap();
+        let path = files.handles[&query["handle_id"]].clone();
+        let offset: usize = query["offset"].parse().unwrap();
+        let content = files.contents.get_mut(&path).unwrap();
+        if content.len() < offset + body.len() {
+            content.resize(offset + body.len(), 0);
+        }
+        content[offset..offset + body.len()].copy_from_slice(&body);
+        Json(json!({ "bytes_written": body.len() }))
+    }
+
+    async fn stat(
+        State(files): State<Shared>,
+        Query(query): Query<HashMap<String, String>>,
+    ) -> Json<Value> {
+        let files = files.lock().unwrap();
+        Json(info(&query["path"], files.contents[&query["path"]].len()))
+    }
+
+    let app = Router::new()
+        .route("/api/v1/open", post(open))
+        .route("/api/v1/read", post(read))
+        .route("/api/v1/write", post(write))
+        .route("/api/v1/close", post(|| async {}))
+        .route("/api/v1/stat", get(stat))
+        .with_state(Shared::default());
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    url
+}
+
+/// Bytes that don't repeat on any power-of-two period.
+fn pattern(len: usize) -> Vec<u8> {
+    (0..len)
+        .map(|i| (i * 7 + i / 251).to_le_bytes()[0])
+        .collect()
+}
+
+/// Run blocking `std::io` code off the runtime the file was opened on.
+async fn blocking<T: Send + 'static>(f: impl FnOnce() -> T + Send + 'static) -> T {
+    tokio::task::spawn_blocking(f).await.unwrap()
+}
+
+#[tokio::test]
+async fn io_copy_in_and_bufreader_out_roundtrip() {
+    let client = Fs9Client::new(&server().await).unwrap();
+    let data = pattern(300_001);
+    let local = std::env::temp_dir().join(format!("fs9-file-{}", std::process::id()));
+    std::fs::write(&local, &data).unwrap();
+
+    let mut file = client
+        .open_file("/copy.bin", OpenFlags::create_truncate())
+        .await
+        .unwrap();
+    let source = local.clone();
+    let copied = blocking(move || {
+        let copied = std::io::copy(&mut std::fs::File::open(source).unwrap(), &mut file);
+        file.close().unwrap();
+        copied.unwrap()
+    })
+    .await;
+    std::fs::remove_file(&local).unwrap();
+    assert_eq!(copied, data.len() as u64);
+
+    let file = client
+        .open_file("/copy.bin", OpenFlags::read())
+        .await
+        .unwrap();
+    let read_back = blocking(move || {
+        let mut read_back = Vec::new();
+        BufReader::with_capacity(1000, file)
+            .read_to_end(&mut read_back)
+            .unwrap();
+        read_back
+    })
+    .await;
+    assert!(read_back == data, "contents differ after a roundtrip");
+}
+
+#[tokio::test]
+async fn seek_moves_reads_and_writes() {
+    let client = Fs9Client::new(&server().await).unwrap();
+    let mut file: Fs9File = client
+        .open_file("/seek.bin", OpenFlags::create_truncate())
+        .await
+        .unwrap();
+
+    let (tail, middle, end) = blocking(move || {
+        file.write_all(b"0123456789").unwrap();
+        assert_eq!(file.seek(SeekFrom::End(-4)).unwrap(), 6);
+        let mut tail = String::new();
+        (&mut file).take(10).read_to_string(&mut tail).unwrap();
+
+        file.seek(SeekFrom::Start(2)).unwrap();
+        file.write_all(b"ab").unwrap();
+        file.seek(SeekFrom::Current(-3)).unwrap();
+        let mut middle = [0u8; 4];
+        file.read_exact(&mut middle).unwrap();
+
+        assert!(file.seek(SeekFrom::Current(-100)).is_err());
+        let mut rest = Vec::new();
+        file.read_to_end(&mut rest).unwrap();
+        let end = file.stream_position().unwrap();
+        file.close().unwrap();
+        (tail, middle, end)
+    })
+    .await;
+
+    assert_eq!(tail, "6789");
+    assert_eq!(&middle, b"1ab4");
+    assert_eq!(end, 10);
+}
+
+#[tokio::test]
+async fn drop_flushes_buffered_writes() {
+    let client = Fs9Client::new(&server().await).unwrap();
+    let mut file = client
+        .open_file("/dropped.txt", OpenFlags::create_truncate())
+        .await
+        .unwrap();
+    // Outside any runtime context the drop blocks until the data is sent.
+    blocking(move || {
+        std::thread::spawn(move || file.write_all(b"kept").unwrap())
+            .join()
+            .unwrap();
+    })
+    .await;
+
+    assert_eq!(
+        &client.read_file("/dropped.txt").await.unwrap()[..],
+        b"kept"
+    );
+}
+
+#[tokio::test]
+async fn end_of_stream_reads_as_end_of_file() {
+    let client = Fs9Client::new(&server().await).unwrap();
+    let mut writer = client
+        .open_file("/streams/log", OpenFlags::create_truncate())
+        .await
+        .unwrap();
+    let mut reader = client
+        .open_file("/streams/log", OpenFlags::read())
+        .await
+        .unwrap();
+
+    let read = blocking(move || {
+        writer.write_all(b"last words").unwrap();
+        writer.close().unwrap();
+        let mut read = String::new();
+        reader.read_to_string(&mut read).unwrap();
+        read
+    })
+    .await;
+
+    assert_eq!(read, "last words");
+}
@@ -0,0 +1,99 @@
+//! Connection pooling against an in-process HTTP server that records which
+//! connections requests arrive on.
+
+use std::collections::HashSet;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+
+use axum::extract::{ConnectInfo, State};
+use axum::routing::get;
+use axum::Router;
+use fs9_client::{Fs9Client, Fs9ClientBuilder};
+use tokio::net::TcpListener;
+
+type Peers = Arc<Mutex<HashSet<SocketAddr>>>;
+
+/// Serve `/api/v1/download` over HTTP/1.1 and cleartext HTTP/2. Returns the
+/// base URL and the client addresses seen, one per connection.
+async fn server() -> (String, Peers) {
+    async fn download(
+        State(peers): State<Peers>,
+        ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    ) -> &'static str {
+        peers.lock().unwrap().insert(peer);
+        "hello"
+    }
+
+    let peers = Peers::default();
+    let app = Router::new()
+        .route("/api/v1/download", get(download))
+        .with_state(peers.clone());
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move {
+        axum::serve(
+            listener,
+            app.into_make_service_with_connect_info::<SocketAddr>(),
+        )
+        .await
+        .unwrap();
+    });
+    (url, peers)
+}
+
+async fn download_concurrently(client: &Fs9Client, count: usize) {
+    let tasks: Vec<_> = (0..count)
+        .map(|i| {
+            let client = client.clone();
+            tokio::spawn(async move { client.download(&format!("/file-{i}")).await })
+        })
+        .collect();
+    for task in tasks {
+        assert_eq!(&task.await.unwrap().unwrap()[..], b"hello");
+    }
+}
+
+fn builder(url: &str) -> Fs9ClientBuilder {
+    Fs9Client::builder(url).retry(1, std::time::Duration::ZERO)
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn many_concurrent_reads_through_one_client() {
+    let (url, peers) = server().await;
+    let client = builder(&url).pool_max_idle_per_host(16).build().unwrap();
+
+    for _ in 0..4 {
+        download_concurrently(&client, 250).await;
+    }
+
+    // Idle connections are reused rather than a socket opened per request.
+    assert!(peers.lock().unwrap().len() < 1000);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn http2_prior_knowledge_multiplexes_one_connection() {
+    let (url, peers) = server().await;
+    let client = builder(&url).http2_prior_knowledge(true).build().unwrap();
+
+    download_concurrently(&client, 200).await;
+
+    assert_eq!(peers.lock().unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn pool_max_idle_per_host_bounds_reuse() {
+    let (url, peers) = server().await;
+    let pooled = builder(&url).tcp_keepalive(std::time::Duration::from_secs(30));
+    let client = pooled.build().unwrap();
+    for _ in 0..3 {
+        client.download("/a").await.unwrap();
+    }
+    assert_eq!(peers.lock().unwrap().len(), 1);
+
+    peers.lock().unwrap().clear();
+    let client = builder(&url).pool_max_idle_per_host(0).build().unwrap();
+    for _ in 0..3 {
+        client.download("/a").await.unwrap();
+    }
+    assert_eq!(peers.lock().unwrap().len(), 3);
+}
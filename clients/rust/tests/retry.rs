@@ -0,0 +1,142 @@
+//! Retry behaviour against a scripted mock HTTP server.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use fs9_client::{Fs9Client, Fs9Error};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+
+const FILE_INFO: &str = r#"{"path":"/a.txt","size":3,"file_type":"regular","mode":420,"uid":0,"gid":0,"atime":0,"mtime":0,"ctime":0,"etag":"e","symlink_target":null}"#;
+
+fn response(status: &str, extra_headers: &str, body: &str) -> String {
+    format!(
+        "HTTP/1.1 {status}\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n{extra_headers}\r\n{body}",
+        body.len()
+    )
+}
+
+fn unavailable() -> String {
+    response(
+        "503 Service Unavailable",
+        "",
+        r#"{"error":"busy","code":503}"#,
+    )
+}
+
+/// Serve the scripted responses in order, one per connection, repeating the
+/// last one once the script runs out. Returns the base URL and a counter of
+/// requests received.
+async fn mock_server(script: Vec<String>) -> (String, Arc<AtomicUsize>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let hits = Arc::new(AtomicUsize::new(0));
+    let counter = hits.clone();
+    tokio::spawn(async move {
+        loop {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let n = counter.fetch_add(1, Ordering::SeqCst);
+            let reply = script[n.min(script.len() - 1)].clone();
+            let mut buf = [0u8; 4096];
+            let mut request = Vec::new();
+            while !request.windows(4).any(|w| w == b"\r\n\r\n") {
+                let read = socket.read(&mut buf).await.unwrap();
+                if read == 0 {
+                    break;
+                }
+                request.extend_from_slice(&buf[..read]);
+            }
+            socket.write_all(reply.as_bytes()).await.unwrap();
+            socket.shutdown().await.ok();
+        }
+    });
+    (url, hits)
+}
+
+#[tokio::test]
+async fn idempotent_request_succeeds_after_transient_failures() {
+    let (url, hits) = mock_server(vec![
+        unavailable(),
+        unavailable(),
+        response("200 OK", "", FILE_INFO),
+    ])
+    .await;
+    let client = Fs9Client::builder(&url)
+        .retry(3, Duration::from_millis(5))
+        .build()
+        .unwrap();
+
+    let info = client.stat("/a.txt").await.unwrap();
+    assert_eq!(info.size, 3);
+    assert_eq!(hits.load(Ordering::SeqCst), 3);
+}
+
+#[tokio::test]
+async fn gives_up_after_max_attempts() {
+    let (url, hits) = mock_server(vec![unavailable()]).await;
+    let client = Fs9Client::builder(&url)
+        .retry(2, Duration::from_millis(5))
+        .build()
+        .unwrap();
+
+    let err = client.stat("/a.txt").await.unwrap_err();
+    assert!(
+        matches!(err, Fs9Error::ServerError { status: 503, .. }),
+        "{err:?}"
+    );
+    assert_eq!(hits.load(Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+async fn writes_are_not_retried_unless_opted_in() {
+    let script = vec![unavailable(), response("200 OK", "", "")];
+
+    let (url, hits) = mock_server(script.clone()).await;
+    let client = Fs9Client::builder(&url)
+        .retry(3, Duration::from_millis(5))
+        .build()
+        .unwrap();
+    assert!(client.remove("/a.txt").await.is_err());
+    assert_eq!(hits.load(Ordering::SeqCst), 1);
+
+    let (url, hits) = mock_server(script).await;
+    let client = Fs9Client::builder(&url)
+        .retry(3, Duration::from_millis(5))
+        .retry_writes(true)
+        .build()
+        .unwrap();
+    client.remove("/a.txt").await.unwrap();
+    assert_eq!(hits.load(Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+async fn retry_after_overrides_backoff() {
+    let (url, hits) = mock_server(vec![
+        response(
+            "429 Too Many Requests",
+            "retry-after: 1\r\n",
+            r#"{"error":"slow down","code":429}"#,
+        ),
+        response("200 OK", "", FILE_INFO),
+    ])
+    .await;
+    let client = Fs9Client::builder(&url)
+        .retry(2, Duration::from_millis(1))
+        .build()
+        .unwrap();
+
+    let started = Instant::now();
+    client.stat("/a.txt").await.unwrap();
+    assert!(started.elapsed() >= Duration::from_secs(1));
+    assert_eq!(hits.load(Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+async fn retries_are_disabled_by_default() {
+    let (url, hits) = mock_server(vec![unavailable(), response("200 OK", "", FILE_INFO)]).await;
+    let client = Fs9Client::new(&url).unwrap();
+
+    assert!(client.stat("/a.txt").await.is_err());
+    assert_eq!(hits.load(Ordering::SeqCst), 1);
+}
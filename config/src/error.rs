@@ -15,6 +15,13 @@ pub enum ConfigError {
     #[error("Failed to parse JSON: {0}")]
     ParseJson(#[from] serde_json::Error),
 
+    #[error("Failed to parse {format} config {path}: {message}")]
+    ParseFile {
+        path: PathBuf,
+        format: &'static str,
+        message: String,
+    },
+
     #[error("Environment variable '{name}' not found")]
     EnvVarNotFound { name: String },
 
@@ -23,4 +30,31 @@ pub enum ConfigError {
 
     #[error("Invalid config value: {0}")]
     InvalidValue(String),
+
+    #[error("Config include cycle through {path}")]
+    IncludeCycle { path: PathBuf },
+
+    #[error("Invalid config at {field}: {message}")]
+    Invalid { field: String, message: String },
+}
+
+impl ConfigError {
+    pub(crate) fn parse_file(
+        path: &std::path::Path,
+        format: crate::loader::Format,
+        message: impl ToString,
+    ) -> Self {
+        Self::ParseFile {
+            path: path.to_path_buf(),
+            format: format.name(),
+            message: message.to_string(),
+        }
+    }
+
+    pub(crate) fn invalid(field: impl Into<String>, message: impl Into<String>) -> Self {
+        Self::Invalid {
+            field: field.into(),
+            message: message.into(),
+        }
+    }
 }
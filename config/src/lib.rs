@@ -1,6 +1,8 @@
 //! FS9 Configuration System
 //!
 //! Provides unified YAML-based configuration for all FS9 components.
+//! Files ending in `.toml` or `.json` are parsed as TOML or JSON instead;
+//! the default search paths are YAML.
 //!
 //! # Configuration Loading Priority
 //!
@@ -30,17 +32,48 @@
 //!       backend:
 //!         type: s3
 //!         bucket: "my-bucket"
+//!     op_timeout_secs: 10
+//!     retry:
+//!       max_attempts: 3
+//!     require_capabilities: [rename, truncate]
+//!   - path: "/archive"
+//!     provider: pagefs
+//!     read_only: true
+//! ```
+//!
+//! # Includes
+//!
+//! A top-level `include:` list pulls in other files before the including
+//! file's own keys are applied. Paths are relative to the including file;
+//! mappings merge key by key and lists (such as `mounts`) are appended.
+//!
+//! ```yaml
+//! include:
+//!   - fragments/mounts.yaml
+//! server:
+//!   port: 9999
 //! ```
+//!
+//! # Hot Reload
+//!
+//! [`ConfigLoader::watch`] re-runs loading and validation when a config file
+//! changes and publishes the result on a `tokio::sync::watch` channel.
+//! fs9-server applies logging, rate limits and new mounts live; `server.host`
+//! and `server.port` changes are flagged in [`ConfigReload::restart_required`].
 
 #![allow(missing_docs)]
 
 mod error;
 mod loader;
 mod types;
+mod validate;
+mod watch;
 
 pub use error::ConfigError;
 pub use loader::ConfigLoader;
 pub use types::*;
+pub use validate::KNOWN_PROVIDERS;
+pub use watch::{restart_required, ConfigReload, ConfigWatcher};
 
 /// Load configuration from default locations.
 ///
@@ -55,10 +88,28 @@ pub fn load_from_file(path: &str) -> Result<Fs9Config, ConfigError> {
     ConfigLoader::new().with_file(path).load()
 }
 
+/// JSON Schema of the config file, derived from [`Fs9Config`], for editor
+/// completion and validation of YAML, TOML and JSON configs alike.
+#[must_use]
+pub fn json_schema() -> serde_json::Value {
+    serde_json::to_value(schemars::schema_for!(Fs9Config)).unwrap_or_default()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn schema_describes_the_config_fields() {
+        let schema = json_schema();
+        assert_eq!(schema["title"], "Fs9Config");
+        assert!(schema["properties"]["mounts"].is_object());
+        let mount = &schema["definitions"]["MountConfig"];
+        let required = mount["required"].as_array().unwrap();
+        assert!(required.contains(&serde_json::json!("path")));
+        assert!(mount["properties"]["read_only"].is_object());
+    }
+
     #[test]
     fn default_config_is_valid() {
         let config = Fs9Config::default();
@@ -97,6 +148,10 @@ mounts:
         type: s3
         bucket: "test-bucket"
         prefix: "data"
+    read_only: true
+    require_capabilities: [rename]
+    mask_capabilities: [chmod, chown]
+    fail_fast: false
 
 logging:
   level: debug
@@ -106,6 +161,13 @@ logging:
         assert_eq!(config.server.port, 9000);
         assert!(config.server.auth.enabled);
         assert_eq!(config.mounts.len(), 2);
+        assert!(!config.mounts[0].read_only);
+        assert!(config.mounts[1].read_only);
+        assert!(config.mounts[0].require_capabilities.is_empty());
+        assert_eq!(config.mounts[1].require_capabilities, ["rename"]);
+        assert_eq!(config.mounts[1].mask_capabilities, ["chmod", "chown"]);
+        assert!(config.mounts[0].fail_fast);
+        assert!(!config.mounts[1].fail_fast);
         assert_eq!(config.logging.level, LogLevel::Debug);
     }
 }
@@ -1,6 +1,8 @@
-use crate::{ConfigError, Fs9Config};
+use crate::{ConfigError, ConfigWatcher, Fs9Config};
 use regex::Regex;
-use std::path::PathBuf;
+use serde_yaml::{Mapping, Value};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
 pub struct ConfigLoader {
     explicit_file: Option<PathBuf>,
@@ -37,6 +39,59 @@ impl ConfigLoader {
     }
 
     pub fn load(&self) -> Result<Fs9Config, ConfigError> {
+        self.load_tracked(&mut Vec::new())
+    }
+
+    /// Load `path` the way the server would, following includes and
+    /// expanding `${VAR}`s, and collect every problem with it, for linting a
+    /// file before it is deployed. A file that cannot be read or parsed
+    /// yields that one error; otherwise every broken validation rule is
+    /// reported. `FS9_*` overrides are not applied, so the result reflects
+    /// the file alone.
+    pub fn check_file(path: impl AsRef<Path>) -> Result<Fs9Config, Vec<ConfigError>> {
+        let path = path.as_ref();
+        let content = std::fs::read_to_string(path).map_err(|e| {
+            vec![ConfigError::ReadFile {
+                path: path.to_path_buf(),
+                source: e,
+            }]
+        })?;
+        let config = Self::new()
+            .parse_file(path, &content, &mut Vec::new())
+            .map_err(|e| vec![e])?;
+        let errors = config.validation_errors();
+        if errors.is_empty() {
+            Ok(config)
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Watch the resolved config files and publish every successful reload.
+    ///
+    /// Must be called from within a Tokio runtime. See [`ConfigWatcher`].
+    pub fn watch(self, interval: Duration) -> Result<ConfigWatcher, ConfigError> {
+        ConfigWatcher::spawn(self, interval)
+    }
+
+    /// Files consulted by [`load`](Self::load), whether or not they exist
+    /// yet: the explicit or `FS9_CONFIG` file, or every search path.
+    pub(crate) fn candidate_files(&self) -> Vec<PathBuf> {
+        if let Ok(env_path) = std::env::var("FS9_CONFIG") {
+            vec![PathBuf::from(env_path)]
+        } else if let Some(ref explicit) = self.explicit_file {
+            vec![explicit.clone()]
+        } else {
+            self.search_paths.clone()
+        }
+    }
+
+    /// Like [`load`](Self::load), additionally recording every file that was
+    /// read (including `include:` targets) in `sources`.
+    pub(crate) fn load_tracked(
+        &self,
+        sources: &mut Vec<PathBuf>,
+    ) -> Result<Fs9Config, ConfigError> {
         let mut config = Fs9Config::default();
 
         if let Ok(env_path) = std::env::var("FS9_CONFIG") {
@@ -45,38 +100,117 @@ impl ConfigLoader {
                     path: PathBuf::from(&env_path),
                     source: e,
                 })?;
-            config = self.parse_yaml(&content)?;
+            config = self.parse_file(Path::new(&env_path), &content, sources)?;
         } else if let Some(ref explicit) = self.explicit_file {
             let content = std::fs::read_to_string(explicit).map_err(|e| ConfigError::ReadFile {
                 path: explicit.clone(),
                 source: e,
             })?;
-            config = self.parse_yaml(&content)?;
+            config = self.parse_file(explicit, &content, sources)?;
         } else {
             for path in &self.search_paths {
                 if path.exists() {
                     if let Ok(content) = std::fs::read_to_string(path) {
-                        config = self.merge_yaml(&config, &content)?;
+                        config = self.merge_file(&config, path, &content, sources)?;
                     }
                 }
             }
         }
 
         self.apply_env_overrides(&mut config);
+        config.validate()?;
         Ok(config)
     }
 
-    fn parse_yaml(&self, content: &str) -> Result<Fs9Config, ConfigError> {
-        let expanded = self.expand_env_vars(content);
-        Ok(serde_yaml::from_str(&expanded)?)
+    fn parse_file(
+        &self,
+        path: &Path,
+        content: &str,
+        sources: &mut Vec<PathBuf>,
+    ) -> Result<Fs9Config, ConfigError> {
+        let mut chain = Vec::new();
+        let value = self.resolve_includes(path, content, &mut chain, sources)?;
+        serde_yaml::from_value(value)
+            .map_err(|e| ConfigError::parse_file(path, Format::from_path(path), e))
     }
 
-    fn merge_yaml(&self, base: &Fs9Config, content: &str) -> Result<Fs9Config, ConfigError> {
-        let expanded = self.expand_env_vars(content);
-        let overlay: Fs9Config = serde_yaml::from_str(&expanded)?;
+    fn merge_file(
+        &self,
+        base: &Fs9Config,
+        path: &Path,
+        content: &str,
+        sources: &mut Vec<PathBuf>,
+    ) -> Result<Fs9Config, ConfigError> {
+        let overlay = self.parse_file(path, content, sources)?;
         Ok(self.merge_configs(base, &overlay))
     }
 
+    /// Parse `content` (read from `path`) and fold in the files named by its
+    /// top-level `include:` list. Includes resolve against the including
+    /// file's directory and are merged in order, with the including file's
+    /// own keys applied last. `chain` holds the files currently being
+    /// resolved so that a file including itself, directly or not, is caught.
+    fn resolve_includes(
+        &self,
+        path: &Path,
+        content: &str,
+        chain: &mut Vec<PathBuf>,
+        sources: &mut Vec<PathBuf>,
+    ) -> Result<Value, ConfigError> {
+        let canonical = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+        if chain.contains(&canonical) {
+            return Err(ConfigError::IncludeCycle { path: canonical });
+        }
+        if !sources.contains(&canonical) {
+            sources.push(canonical.clone());
+        }
+
+        let expanded = self.expand_env_vars(content)?;
+        let mut value = Format::from_path(path).parse(path, &expanded)?;
+        if value.is_null() {
+            value = Value::Mapping(Mapping::new());
+        }
+
+        let includes = match value.as_mapping_mut().and_then(|m| m.remove("include")) {
+            None => return Ok(value),
+            Some(Value::String(single)) => vec![single],
+            Some(Value::Sequence(items)) => items
+                .into_iter()
+                .map(|item| match item {
+                    Value::String(s) => Ok(s),
+                    _ => Err(ConfigError::InvalidValue(format!(
+                        "include entries in {} must be paths",
+                        path.display()
+                    ))),
+                })
+                .collect::<Result<_, _>>()?,
+            Some(_) => {
+                return Err(ConfigError::InvalidValue(format!(
+                    "include in {} must be a path or a list of paths",
+                    path.display()
+                )))
+            }
+        };
+
+        chain.push(canonical);
+        let base_dir = path.parent().unwrap_or_else(|| Path::new("."));
+        let mut merged = Value::Mapping(Mapping::new());
+        for include in includes {
+            let include_path = base_dir.join(include);
+            let content =
+                std::fs::read_to_string(&include_path).map_err(|e| ConfigError::ReadFile {
+                    path: include_path.clone(),
+                    source: e,
+                })?;
+            let included = self.resolve_includes(&include_path, &content, chain, sources)?;
+            deep_merge(&mut merged, included);
+        }
+        chain.pop();
+
+        deep_merge(&mut merged, value);
+        Ok(merged)
+    }
+
     fn merge_configs(&self, base: &Fs9Config, overlay: &Fs9Config) -> Fs9Config {
         let mut result = base.clone();
 
@@ -111,13 +245,40 @@ impl ConfigLoader {
         result
     }
 
-    fn expand_env_vars(&self, content: &str) -> String {
-        let re = Regex::new(r"\$\{([^}]+)\}").unwrap();
-        re.replace_all(content, |caps: &regex::Captures| {
-            let var_name = &caps[1];
-            std::env::var(var_name).unwrap_or_default()
-        })
-        .to_string()
+    /// Replace `${VAR}` and `${VAR:-default}` with values from the
+    /// environment. The default applies when `VAR` is unset or empty; a
+    /// `${VAR}` with no default must be set. Comment lines are left alone so
+    /// commented-out examples do not need their variables defined.
+    fn expand_env_vars(&self, content: &str) -> Result<String, ConfigError> {
+        let re = Regex::new(r"\$\{([^}:]+)(?::-([^}]*))?\}").unwrap();
+        let mut expanded = String::with_capacity(content.len());
+        let mut missing = None;
+
+        for line in content.split_inclusive('\n') {
+            if line.trim_start().starts_with('#') {
+                expanded.push_str(line);
+                continue;
+            }
+            let line = re.replace_all(line, |caps: &regex::Captures| {
+                let var_name = &caps[1];
+                let value = std::env::var(var_name).ok();
+                match caps.get(2) {
+                    Some(default) => value
+                        .filter(|v| !v.is_empty())
+                        .unwrap_or_else(|| default.as_str().to_string()),
+                    None => value.unwrap_or_else(|| {
+                        missing.get_or_insert_with(|| var_name.to_string());
+                        String::new()
+                    }),
+                }
+            });
+            expanded.push_str(&line);
+        }
+
+        match missing {
+            Some(name) => Err(ConfigError::EnvVarNotFound { name }),
+            None => Ok(expanded),
+        }
     }
 
     fn apply_env_overrides(&self, config: &mut Fs9Config) {
@@ -208,9 +369,77 @@ impl ConfigLoader {
     }
 }
 
+/// On-disk config syntax, chosen by file extension. Anything that is not
+/// `.toml` or `.json` is read as YAML.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Format {
+    Yaml,
+    Toml,
+    Json,
+}
+
+impl Format {
+    fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("toml") => Self::Toml,
+            Some(ext) if ext.eq_ignore_ascii_case("json") => Self::Json,
+            _ => Self::Yaml,
+        }
+    }
+
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            Self::Yaml => "YAML",
+            Self::Toml => "TOML",
+            Self::Json => "JSON",
+        }
+    }
+
+    /// Parse `content` into a YAML value tree so that includes and merging
+    /// work the same whatever format each file is written in.
+    fn parse(self, path: &Path, content: &str) -> Result<Value, ConfigError> {
+        let value = match self {
+            Self::Yaml => serde_yaml::from_str(content).map_err(|e| e.to_string()),
+            Self::Toml => toml::from_str::<toml::Value>(content)
+                .map_err(|e| e.to_string())
+                .and_then(|v| serde_yaml::to_value(v).map_err(|e| e.to_string())),
+            Self::Json => serde_json::from_str::<serde_json::Value>(content)
+                .map_err(|e| e.to_string())
+                .and_then(|v| serde_yaml::to_value(v).map_err(|e| e.to_string())),
+        };
+        value.map_err(|e| ConfigError::parse_file(path, self, e))
+    }
+}
+
+/// Merge `overlay` into `base`: mappings merge key by key, sequences are
+/// appended, and any other value in `overlay` replaces the one in `base`.
+fn deep_merge(base: &mut Value, overlay: Value) {
+    match (base, overlay) {
+        (Value::Mapping(base), Value::Mapping(overlay)) => {
+            for (key, value) in overlay {
+                match base.get_mut(&key) {
+                    Some(existing) => deep_merge(existing, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (Value::Sequence(base), Value::Sequence(overlay)) => base.extend(overlay),
+        (base, overlay) => *base = overlay,
+    }
+}
+
 impl PartialEq for crate::MountConfig {
     fn eq(&self, other: &Self) -> bool {
-        self.path == other.path && self.provider == other.provider
+        self.path == other.path
+            && self.provider == other.provider
+            && self.read_only == other.read_only
+            && self.op_timeout_secs == other.op_timeout_secs
+            && self.retry == other.retry
+            && self.require_capabilities == other.require_capabilities
+            && self.mask_capabilities == other.mask_capabilities
+            && self.fail_fast == other.fail_fast
     }
 }
 
@@ -222,16 +451,245 @@ mod tests {
     fn expand_env_vars_works() {
         std::env::set_var("TEST_VAR_123", "hello");
         let loader = ConfigLoader::new();
-        let result = loader.expand_env_vars("value: ${TEST_VAR_123}");
+        let result = loader.expand_env_vars("value: ${TEST_VAR_123}").unwrap();
         assert_eq!(result, "value: hello");
         std::env::remove_var("TEST_VAR_123");
     }
 
     #[test]
-    fn missing_env_var_becomes_empty() {
+    fn missing_env_var_is_an_error() {
         let loader = ConfigLoader::new();
         let result = loader.expand_env_vars("value: ${NONEXISTENT_VAR_XYZ}");
-        assert_eq!(result, "value: ");
+        assert!(matches!(
+            result,
+            Err(ConfigError::EnvVarNotFound { name }) if name == "NONEXISTENT_VAR_XYZ"
+        ));
+    }
+
+    #[test]
+    fn env_var_default_applies_when_unset() {
+        let loader = ConfigLoader::new();
+        let result = loader
+            .expand_env_vars("port: ${NONEXISTENT_PORT_XYZ:-8080}\nhost: ${NONEXISTENT_HOST_XYZ:-}")
+            .unwrap();
+        assert_eq!(result, "port: 8080\nhost: ");
+
+        std::env::set_var("TEST_DEFAULTED_VAR_456", "9090");
+        let result = loader
+            .expand_env_vars("port: ${TEST_DEFAULTED_VAR_456:-8080}")
+            .unwrap();
+        assert_eq!(result, "port: 9090");
+        std::env::remove_var("TEST_DEFAULTED_VAR_456");
+    }
+
+    #[test]
+    fn env_vars_in_comments_are_ignored() {
+        let loader = ConfigLoader::new();
+        let result = loader
+            .expand_env_vars("# jwt_secret: ${NONEXISTENT_SECRET_XYZ}\nport: 1\n")
+            .unwrap();
+        assert_eq!(result, "# jwt_secret: ${NONEXISTENT_SECRET_XYZ}\nport: 1\n");
+    }
+
+    #[test]
+    fn shipped_configs_load_without_secrets_set() {
+        let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("..");
+        for name in ["fs9.yaml", "fs9.example.yaml"] {
+            let config = ConfigLoader::check_file(root.join(name))
+                .unwrap_or_else(|e| panic!("{name}: {e:?}"));
+            assert!(!config.mounts.is_empty(), "{name} lost its mounts");
+        }
+    }
+
+    #[test]
+    fn load_expands_env_vars_in_file() {
+        std::env::set_var("TEST_JWT_SECRET_789", "s3cret");
+        let path = std::env::temp_dir().join(format!("fs9-env-{}.yaml", std::process::id()));
+        std::fs::write(
+            &path,
+            "server:\n  auth:\n    enabled: true\n    jwt_secret: \"${TEST_JWT_SECRET_789}\"\n",
+        )
+        .unwrap();
+
+        let config = ConfigLoader::new()
+            .with_file(path.to_str().unwrap())
+            .load()
+            .unwrap();
+        assert_eq!(config.server.auth.jwt_secret, "s3cret");
+
+        std::fs::remove_file(&path).unwrap();
+        std::env::remove_var("TEST_JWT_SECRET_789");
+    }
+
+    #[test]
+    fn include_merges_fragment_relative_to_parent() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("fragments")).unwrap();
+        std::fs::write(
+            dir.path().join("fragments/mounts.yaml"),
+            "mounts:\n  - path: /data\n    provider: memfs\n  - path: /kv\n    provider: kv\n",
+        )
+        .unwrap();
+        let base = dir.path().join("base.yaml");
+        std::fs::write(
+            &base,
+            "include:\n  - fragments/mounts.yaml\nserver:\n  port: 9100\n",
+        )
+        .unwrap();
+
+        let config = ConfigLoader::new()
+            .with_file(base.to_str().unwrap())
+            .load()
+            .unwrap();
+        assert_eq!(config.server.port, 9100);
+        let mounts: Vec<_> = config.mounts.iter().map(|m| m.path.as_str()).collect();
+        assert_eq!(mounts, ["/data", "/kv"]);
+    }
+
+    #[test]
+    fn including_file_overrides_its_includes() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("defaults.yaml"),
+            "server:\n  host: 10.0.0.1\n  port: 7000\n",
+        )
+        .unwrap();
+        let base = dir.path().join("base.yaml");
+        std::fs::write(&base, "include: [defaults.yaml]\nserver:\n  port: 7001\n").unwrap();
+
+        let config = ConfigLoader::new()
+            .with_file(base.to_str().unwrap())
+            .load()
+            .unwrap();
+        assert_eq!(config.server.host, "10.0.0.1");
+        assert_eq!(config.server.port, 7001);
+    }
+
+    #[test]
+    fn include_cycle_is_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("a.yaml"), "include: [b.yaml]\n").unwrap();
+        std::fs::write(dir.path().join("b.yaml"), "include: [a.yaml]\n").unwrap();
+
+        let result = ConfigLoader::new()
+            .with_file(dir.path().join("a.yaml").to_str().unwrap())
+            .load();
+        assert!(matches!(
+            result,
+            Err(ConfigError::IncludeCycle { path }) if path.ends_with("a.yaml")
+        ));
+    }
+
+    fn load_in(dir: &Path, name: &str, content: &str) -> Result<Fs9Config, ConfigError> {
+        let path = dir.join(name);
+        std::fs::write(&path, content).unwrap();
+        ConfigLoader::new().with_file(path.to_str().unwrap()).load()
+    }
+
+    #[test]
+    fn yaml_toml_and_json_load_the_same_config() {
+        let dir = tempfile::tempdir().unwrap();
+        let yaml = load_in(
+            dir.path(),
+            "fs9.yaml",
+            "server:\n  port: 9200\n  rate_limit:\n    enabled: true\n    user_qps: 50\n\
+             logging:\n  level: debug\n\
+             mounts:\n  - path: /data\n    provider: pagefs\n    config:\n      uid: 7\n",
+        )
+        .unwrap();
+        let toml = load_in(
+            dir.path(),
+            "fs9.toml",
+            "[server]\nport = 9200\n\n[server.rate_limit]\nenabled = true\nuser_qps = 50\n\n\
+             [logging]\nlevel = \"debug\"\n\n\
+             [[mounts]]\npath = \"/data\"\nprovider = \"pagefs\"\nconfig = { uid = 7 }\n",
+        )
+        .unwrap();
+        let json = load_in(
+            dir.path(),
+            "fs9.json",
+            r#"{"server": {"port": 9200, "rate_limit": {"enabled": true, "user_qps": 50}},
+                "logging": {"level": "debug"},
+                "mounts": [{"path": "/data", "provider": "pagefs", "config": {"uid": 7}}]}"#,
+        )
+        .unwrap();
+
+        for config in [&toml, &json] {
+            assert_eq!(config.server.port, yaml.server.port);
+            assert_eq!(
+                config.server.rate_limit.user_qps,
+                yaml.server.rate_limit.user_qps
+            );
+            assert!(config.server.rate_limit.enabled);
+            assert_eq!(config.logging.level, yaml.logging.level);
+            assert_eq!(config.mounts, yaml.mounts);
+        }
+        assert_eq!(yaml.server.port, 9200);
+        assert_eq!(yaml.mounts[0].config, Some(serde_json::json!({"uid": 7})));
+    }
+
+    #[test]
+    fn parse_errors_name_the_format_and_file() {
+        let dir = tempfile::tempdir().unwrap();
+        for (name, content, format) in [
+            ("bad.yaml", "server: [", "YAML"),
+            ("bad.toml", "[server\n", "TOML"),
+            ("bad.json", "{\"server\": ", "JSON"),
+        ] {
+            let err = load_in(dir.path(), name, content).unwrap_err();
+            match &err {
+                ConfigError::ParseFile {
+                    path, format: f, ..
+                } => {
+                    assert!(path.ends_with(name));
+                    assert_eq!(*f, format);
+                }
+                other => panic!("unexpected error for {name}: {other}"),
+            }
+            assert!(err.to_string().contains(name));
+        }
+    }
+
+    #[test]
+    fn check_file_reports_every_broken_rule() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("fs9.yaml");
+        std::fs::write(
+            &path,
+            "server:\n  port: 0\n  auth:\n    enabled: true\n    jwt_secret: \"\"\n  \
+             plugins:\n    directories: []\n\
+             mounts:\n  - path: /\n    provider: memfs\n  - path: /x\n    provider: nosuchfs\n",
+        )
+        .unwrap();
+
+        let errors = ConfigLoader::check_file(&path).unwrap_err();
+        let fields: Vec<_> = errors
+            .iter()
+            .map(|e| match e {
+                ConfigError::Invalid { field, .. } => field.as_str(),
+                other => panic!("unexpected error: {other}"),
+            })
+            .collect();
+        assert_eq!(
+            fields,
+            [
+                "server.port",
+                "server.auth.jwt_secret",
+                "mounts[1].provider"
+            ]
+        );
+    }
+
+    #[test]
+    fn check_file_accepts_a_valid_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("fs9.yaml");
+        std::fs::write(&path, "server:\n  port: 9100\n").unwrap();
+        assert_eq!(ConfigLoader::check_file(&path).unwrap().server.port, 9100);
+
+        std::fs::write(&path, "server: [").unwrap();
+        let errors = ConfigLoader::check_file(&path).unwrap_err();
+        assert!(matches!(errors[..], [ConfigError::ParseFile { .. }]));
     }
 
     #[test]
@@ -1,6 +1,8 @@
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[serde(default)]
 pub struct Fs9Config {
     pub server: ServerConfig,
@@ -18,6 +20,12 @@ impl Default for Fs9Config {
                 path: "/".to_string(),
                 provider: "memfs".to_string(),
                 config: None,
+                read_only: false,
+                op_timeout_secs: None,
+                retry: None,
+                require_capabilities: Vec::new(),
+                mask_capabilities: Vec::new(),
+                fail_fast: true,
             }],
             fuse: FuseConfig::default(),
             shell: ShellConfig::default(),
@@ -26,7 +34,7 @@ impl Default for Fs9Config {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[serde(default)]
 pub struct ServerConfig {
     pub host: String,
@@ -52,12 +60,28 @@ pub struct ServerConfig {
     /// Prometheus metrics configuration.
     #[serde(default)]
     pub metrics: MetricsConfig,
+    /// In-memory stat/readdir cache in each namespace's VFS.
+    #[serde(default)]
+    pub metadata_cache: MetadataCacheConfig,
+    /// Per-mount circuit breakers that fail requests fast with 503 while a
+    /// mount's backend keeps erroring.
+    #[serde(default)]
+    pub mount_circuit_breaker: MountCircuitBreakerConfig,
     /// Default body size limit in bytes (for JSON API requests). Default: 2MB.
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub max_body_size_bytes: Option<usize>,
-    /// Write endpoint body size limit in bytes. Default: 256MB.
-    #[serde(default, skip_serializing_if = "Option::is_none")]
+    /// Body size limit in bytes for the data endpoints (`/write`,
+    /// `/upload` and `/dav`), including chunked uploads. Default: 256MB.
+    #[serde(
+        default,
+        alias = "max_write_bytes",
+        skip_serializing_if = "Option::is_none"
+    )]
     pub max_write_size_bytes: Option<usize>,
+    /// Body size limit in bytes for the admin endpoints (auth, namespaces
+    /// and mounts). Default: 64KB.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_admin_body_size_bytes: Option<usize>,
     /// Meta client resilience configuration.
     #[serde(default)]
     pub meta_resilience: MetaResilienceConfig,
@@ -84,8 +108,11 @@ impl Default for ServerConfig {
             shutdown_timeout_secs: None,
             rate_limit: RateLimitConfig::default(),
             metrics: MetricsConfig::default(),
+            metadata_cache: MetadataCacheConfig::default(),
+            mount_circuit_breaker: MountCircuitBreakerConfig::default(),
             max_body_size_bytes: None,
             max_write_size_bytes: None,
+            max_admin_body_size_bytes: None,
             meta_resilience: MetaResilienceConfig::default(),
             refresh_grace_period_hours: None,
             default_pagefs: None,
@@ -93,12 +120,26 @@ impl Default for ServerConfig {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[serde(default)]
 pub struct RateLimitConfig {
     pub enabled: bool,
     pub namespace_qps: u32,
     pub user_qps: u32,
+    /// Reads per second each namespace may make, on top of `namespace_qps`.
+    /// 0 leaves reads limited by `namespace_qps` alone.
+    pub read_qps: u32,
+    /// Writes per second each namespace may make; 0 is unlimited.
+    pub write_qps: u32,
+    /// Read and write limits for particular namespaces, replacing
+    /// `read_qps` and `write_qps` for them.
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    pub namespaces: HashMap<String, NamespaceRateLimit>,
+    /// Namespaces idle this long have their buckets dropped.
+    pub idle_secs: u64,
+    /// Most read/write buckets kept at once; the least recently used are
+    /// dropped beyond that.
+    pub max_buckets: usize,
 }
 
 impl Default for RateLimitConfig {
@@ -107,15 +148,35 @@ impl Default for RateLimitConfig {
             enabled: false,
             namespace_qps: 1000,
             user_qps: 100,
+            read_qps: 0,
+            write_qps: 0,
+            namespaces: HashMap::new(),
+            idle_secs: 600,
+            max_buckets: 10_000,
         }
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Per-namespace override of the read and write limits. Unset fields keep
+/// the server-wide value.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct NamespaceRateLimit {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub read_qps: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub write_qps: Option<u32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[serde(default)]
 pub struct MetricsConfig {
     pub enabled: bool,
     pub path: String,
+    /// When set, scrapes must send `Authorization: Bearer <admin_key>`
+    /// instead of a JWT. Applies even when JWT auth is disabled.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub admin_key: Option<String>,
 }
 
 impl Default for MetricsConfig {
@@ -123,11 +184,62 @@ impl Default for MetricsConfig {
         Self {
             enabled: true,
             path: "/metrics".to_string(),
+            admin_key: None,
         }
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct MetadataCacheConfig {
+    pub enabled: bool,
+    pub ttl_ms: u64,
+    /// Also remember failed lookups. Risky when files are created by other
+    /// servers sharing the same backend.
+    pub negative: bool,
+    /// Most `stat` results, and separately most listings, each namespace
+    /// holds; the oldest are dropped first.
+    pub max_entries: usize,
+}
+
+impl Default for MetadataCacheConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            ttl_ms: 1000,
+            negative: false,
+            max_entries: 100_000,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct MountCircuitBreakerConfig {
+    pub enabled: bool,
+    /// Length of the sliding window the failure rate is measured over.
+    pub window_secs: u64,
+    /// Calls a mount must see within the window before its breaker can open.
+    pub min_calls: u32,
+    /// Share of failed calls within the window, from 0 to 1, that opens it.
+    pub failure_rate: f64,
+    /// How long an open breaker rejects requests before probing the backend.
+    pub cooldown_secs: u64,
+}
+
+impl Default for MountCircuitBreakerConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            window_secs: 30,
+            min_calls: 10,
+            failure_rate: 0.5,
+            cooldown_secs: 30,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[serde(default)]
 pub struct MetaResilienceConfig {
     pub failure_threshold: u32,
@@ -147,11 +259,16 @@ impl Default for MetaResilienceConfig {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[serde(default)]
 pub struct AuthConfig {
     pub enabled: bool,
     pub jwt_secret: String,
+    /// Secrets `jwt_secret` replaced. Tokens signed with them are still
+    /// accepted until they expire, so the secret can be rotated without
+    /// logging everyone out; new tokens are signed with `jwt_secret` only.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub previous_secrets: Vec<String>,
     pub issuer: String,
     pub audience: String,
 }
@@ -161,17 +278,23 @@ impl Default for AuthConfig {
         Self {
             enabled: false,
             jwt_secret: String::new(),
+            previous_secrets: Vec::new(),
             issuer: "fs9".to_string(),
             audience: "fs9-clients".to_string(),
         }
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[serde(default)]
 pub struct PluginsConfig {
     pub directories: Vec<String>,
     pub preload: Vec<PluginEntry>,
+    /// Hex SHA-256 digests of the plugin files allowed to load. When set,
+    /// any other file found in `directories` or named by `preload` is
+    /// refused. Empty allows every plugin.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub allowed_sha256: Vec<String>,
 }
 
 impl Default for PluginsConfig {
@@ -179,11 +302,12 @@ impl Default for PluginsConfig {
         Self {
             directories: vec!["./plugins".to_string()],
             preload: Vec::new(),
+            allowed_sha256: Vec::new(),
         }
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct PluginEntry {
     pub name: String,
     pub path: String,
@@ -192,7 +316,7 @@ pub struct PluginEntry {
 /// Default pagefs configuration for auto-provisioning db9 tenant namespaces.
 /// When a db9-authenticated request targets a tenant without an fs9 namespace,
 /// the server auto-creates the namespace with a pagefs mount using this config.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct DefaultPagefsConfig {
     pub pd_endpoints: Vec<String>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
@@ -209,15 +333,66 @@ fn default_keyspace_prefix() -> String {
     "tipg_fs_".to_string()
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct MountConfig {
     pub path: String,
     pub provider: String,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub config: Option<serde_json::Value>,
+    /// Deny writes through this mount even if the provider allows them.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub read_only: bool,
+    /// Fail a call to the provider with 503 once it has run this many
+    /// seconds, instead of letting a hung backend hold the request. The
+    /// call itself keeps running in the background until it returns.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub op_timeout_secs: Option<u64>,
+    /// Retry reads, stats and listings that fail because the backend is
+    /// unavailable, such as when S3 throttles. Writes are never retried.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retry: Option<MountRetryConfig>,
+    /// Capability names (e.g. `rename`, `truncate`) the provider must
+    /// have; the server refuses to start if one is missing.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub require_capabilities: Vec<String>,
+    /// Capability names to switch off for this mount even if the provider
+    /// has them; operations that need them are refused.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub mask_capabilities: Vec<String>,
+    /// Refuse to start if the provider fails its health check when the
+    /// mount is set up. When off, the failure is logged and the mount is
+    /// added anyway.
+    #[serde(default = "default_fail_fast")]
+    pub fail_fast: bool,
+}
+
+const fn default_fail_fast() -> bool {
+    true
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct MountRetryConfig {
+    /// Total attempts including the first one.
+    pub max_attempts: u32,
+    /// Backoff before the second attempt, doubled for each one after and
+    /// jittered.
+    pub base_backoff_ms: u64,
+    /// Upper bound for a single backoff.
+    pub max_backoff_ms: u64,
+}
+
+impl Default for MountRetryConfig {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            base_backoff_ms: 50,
+            max_backoff_ms: 2000,
+        }
+    }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[serde(default)]
 pub struct FuseConfig {
     pub server: String,
@@ -237,13 +412,18 @@ impl Default for FuseConfig {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[serde(default)]
 pub struct FuseOptions {
     pub allow_other: bool,
     pub allow_root: bool,
     pub auto_unmount: bool,
     pub read_only: bool,
+    pub max_read: Option<u32>,
+    pub max_write: Option<u32>,
+    /// Buffer writes in the kernel page cache; see `fs9-fuse` for the
+    /// consistency trade-off.
+    pub writeback_cache: bool,
 }
 
 impl Default for FuseOptions {
@@ -253,11 +433,14 @@ impl Default for FuseOptions {
             allow_root: false,
             auto_unmount: true,
             read_only: false,
+            max_read: None,
+            max_write: None,
+            writeback_cache: false,
         }
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[serde(default)]
 pub struct CacheConfig {
     pub attr_ttl: String,
@@ -273,7 +456,7 @@ impl Default for CacheConfig {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[serde(default)]
 pub struct ShellConfig {
     pub server: String,
@@ -293,7 +476,7 @@ impl Default for ShellConfig {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[serde(default)]
 pub struct HistoryConfig {
     pub enabled: bool,
@@ -311,7 +494,7 @@ impl Default for HistoryConfig {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[serde(default)]
 pub struct LoggingConfig {
     pub level: LogLevel,
@@ -329,7 +512,7 @@ impl Default for LoggingConfig {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "lowercase")]
 pub enum LogLevel {
     Trace,
@@ -351,7 +534,7 @@ impl LogLevel {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "lowercase")]
 pub enum LogFormat {
     Pretty,
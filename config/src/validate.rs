@@ -0,0 +1,293 @@
+use std::collections::HashSet;
+
+use crate::{ConfigError, Fs9Config};
+
+/// Providers that are built into the server or shipped as bundled plugins.
+/// Mounts may also name any plugin listed under `server.plugins.preload`,
+/// or any plugin found in `server.plugins.directories` at startup.
+pub const KNOWN_PROVIDERS: &[&str] = &[
+    "memfs", "localfs", "proxyfs", "pagefs", "kv", "streamfs", "pubsubfs", "hellofs",
+];
+
+impl Fs9Config {
+    /// Check the semantic rules that deserialization alone cannot enforce,
+    /// stopping at the first one the config breaks.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        self.validation_errors()
+            .into_iter()
+            .next()
+            .map_or(Ok(()), Err)
+    }
+
+    /// Every rule checked by [`validate`](Self::validate) that the config
+    /// breaks, in the order the fields appear.
+    #[must_use]
+    pub fn validation_errors(&self) -> Vec<ConfigError> {
+        let mut errors = Vec::new();
+
+        if self.server.port == 0 {
+            errors.push(ConfigError::invalid(
+                "server.port",
+                "must be between 1 and 65535",
+            ));
+        }
+
+        if self.server.auth.enabled && self.server.auth.jwt_secret.trim().is_empty() {
+            errors.push(ConfigError::invalid(
+                "server.auth.jwt_secret",
+                "must be set when auth is enabled",
+            ));
+        }
+
+        if self.server.metadata_cache.enabled && self.server.metadata_cache.ttl_ms == 0 {
+            errors.push(ConfigError::invalid(
+                "server.metadata_cache.ttl_ms",
+                "must be greater than 0 when the cache is enabled",
+            ));
+        }
+        if self.server.metadata_cache.enabled && self.server.metadata_cache.max_entries == 0 {
+            errors.push(ConfigError::invalid(
+                "server.metadata_cache.max_entries",
+                "must be greater than 0 when the cache is enabled",
+            ));
+        }
+
+        let rate_limit = &self.server.rate_limit;
+        if rate_limit.enabled && (rate_limit.idle_secs == 0 || rate_limit.max_buckets == 0) {
+            errors.push(ConfigError::invalid(
+                "server.rate_limit",
+                "idle_secs and max_buckets must be greater than 0",
+            ));
+        }
+
+        let breaker = &self.server.mount_circuit_breaker;
+        if breaker.enabled {
+            if !(breaker.failure_rate > 0.0 && breaker.failure_rate <= 1.0) {
+                errors.push(ConfigError::invalid(
+                    "server.mount_circuit_breaker.failure_rate",
+                    "must be greater than 0 and at most 1",
+                ));
+            }
+            if breaker.window_secs == 0 || breaker.cooldown_secs == 0 {
+                errors.push(ConfigError::invalid(
+                    "server.mount_circuit_breaker",
+                    "window_secs and cooldown_secs must be greater than 0",
+                ));
+            }
+        }
+
+        for (i, digest) in self.server.plugins.allowed_sha256.iter().enumerate() {
+            let digest = digest.trim();
+            if digest.len() != 64 || !digest.bytes().all(|b| b.is_ascii_hexdigit()) {
+                errors.push(ConfigError::invalid(
+                    format!("server.plugins.allowed_sha256[{i}]"),
+                    "must be a SHA-256 digest written as 64 hex digits",
+                ));
+            }
+        }
+
+        let mut seen_paths = HashSet::new();
+        for (i, mount) in self.mounts.iter().enumerate() {
+            if !seen_paths.insert(mount.path.as_str()) {
+                errors.push(ConfigError::invalid(
+                    format!("mounts[{i}].path"),
+                    format!("'{}' is mounted more than once", mount.path),
+                ));
+            }
+
+            let preloaded = self
+                .server
+                .plugins
+                .preload
+                .iter()
+                .any(|p| p.name == mount.provider);
+            if !preloaded && !KNOWN_PROVIDERS.contains(&mount.provider.as_str()) {
+                // Plugins in the plugin directories are only known once the
+                // server scans them, so the name can't be checked here.
+                if self.server.plugins.directories.is_empty() {
+                    errors.push(ConfigError::invalid(
+                        format!("mounts[{i}].provider"),
+                        format!("unknown provider '{}'", mount.provider),
+                    ));
+                } else {
+                    tracing::warn!(
+                        mount = %mount.path,
+                        provider = %mount.provider,
+                        "provider is neither built in nor preloaded; expecting it in a plugin directory"
+                    );
+                }
+            }
+
+            if mount.op_timeout_secs == Some(0) {
+                errors.push(ConfigError::invalid(
+                    format!("mounts[{i}].op_timeout_secs"),
+                    "must be greater than 0",
+                ));
+            }
+
+            if mount.retry.as_ref().is_some_and(|r| r.max_attempts == 0) {
+                errors.push(ConfigError::invalid(
+                    format!("mounts[{i}].retry.max_attempts"),
+                    "must be greater than 0",
+                ));
+            }
+
+            let masked_required = mount.require_capabilities.iter().find(|name| {
+                mount
+                    .mask_capabilities
+                    .iter()
+                    .any(|masked| masked.eq_ignore_ascii_case(name))
+            });
+            if let Some(name) = masked_required {
+                errors.push(ConfigError::invalid(
+                    format!("mounts[{i}].mask_capabilities"),
+                    format!("'{name}' is also required"),
+                ));
+            }
+        }
+
+        errors
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{MountConfig, PluginEntry};
+
+    fn mount(path: &str, provider: &str) -> MountConfig {
+        MountConfig {
+            path: path.to_string(),
+            provider: provider.to_string(),
+            config: None,
+            read_only: false,
+            op_timeout_secs: None,
+            retry: None,
+            require_capabilities: Vec::new(),
+            mask_capabilities: Vec::new(),
+            fail_fast: true,
+        }
+    }
+
+    fn invalid_field(config: &Fs9Config) -> String {
+        match config.validate() {
+            Err(ConfigError::Invalid { field, .. }) => field,
+            other => panic!("expected an invalid config, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn valid_config_passes() {
+        let yaml = r#"
+server:
+  port: 9000
+  auth:
+    enabled: true
+    jwt_secret: "test-secret"
+  plugins:
+    preload:
+      - name: custom
+        path: /opt/fs9/libcustom.so
+
+mounts:
+  - path: "/"
+    provider: memfs
+  - path: "/data"
+    provider: pagefs
+  - path: "/custom"
+    provider: custom
+"#;
+        let config: Fs9Config = serde_yaml::from_str(yaml).unwrap();
+        config.validate().unwrap();
+        Fs9Config::default().validate().unwrap();
+    }
+
+    #[test]
+    fn duplicate_mount_path_is_rejected() {
+        let config = Fs9Config {
+            mounts: vec![
+                mount("/", "memfs"),
+                mount("/data", "memfs"),
+                mount("/data", "pagefs"),
+            ],
+            ..Fs9Config::default()
+        };
+        assert_eq!(invalid_field(&config), "mounts[2].path");
+    }
+
+    #[test]
+    fn zero_port_is_rejected() {
+        let mut config = Fs9Config::default();
+        config.server.port = 0;
+        assert_eq!(invalid_field(&config), "server.port");
+    }
+
+    #[test]
+    fn auth_without_secret_is_rejected() {
+        let mut config = Fs9Config::default();
+        config.server.auth.enabled = true;
+        config.server.auth.jwt_secret = "  ".to_string();
+        assert_eq!(invalid_field(&config), "server.auth.jwt_secret");
+    }
+
+    #[test]
+    fn unknown_provider_is_rejected() {
+        let mut config = Fs9Config::default();
+        config.mounts.push(mount("/x", "nosuchfs"));
+        // It may still turn up in a plugin directory.
+        config.validate().unwrap();
+
+        config.server.plugins.directories.clear();
+        assert_eq!(invalid_field(&config), "mounts[1].provider");
+
+        config.server.plugins.preload.push(PluginEntry {
+            name: "nosuchfs".to_string(),
+            path: "/opt/fs9/libnosuchfs.so".to_string(),
+        });
+        config.validate().unwrap();
+    }
+
+    #[test]
+    fn malformed_plugin_digest_is_rejected() {
+        let mut config = Fs9Config::default();
+        config.server.plugins.allowed_sha256 = vec!["ab".repeat(32), "not-a-digest".to_string()];
+        assert_eq!(invalid_field(&config), "server.plugins.allowed_sha256[1]");
+
+        config.server.plugins.allowed_sha256.pop();
+        config.validate().unwrap();
+    }
+
+    #[test]
+    fn zero_op_timeout_is_rejected() {
+        let mut config = Fs9Config::default();
+        config.mounts[0].op_timeout_secs = Some(0);
+        assert_eq!(invalid_field(&config), "mounts[0].op_timeout_secs");
+
+        config.mounts[0].op_timeout_secs = Some(5);
+        config.validate().unwrap();
+    }
+
+    #[test]
+    fn zero_retry_attempts_are_rejected() {
+        let mut config = Fs9Config::default();
+        config.mounts[0].retry = Some(crate::MountRetryConfig {
+            max_attempts: 0,
+            ..Default::default()
+        });
+        assert_eq!(invalid_field(&config), "mounts[0].retry.max_attempts");
+
+        config.mounts[0].retry = Some(crate::MountRetryConfig::default());
+        config.validate().unwrap();
+    }
+
+    #[test]
+    fn required_capability_cannot_be_masked() {
+        let mut config = Fs9Config::default();
+        config.mounts[0].require_capabilities = vec!["rename".to_string()];
+        config.mounts[0].mask_capabilities = vec!["RENAME".to_string()];
+        assert_eq!(invalid_field(&config), "mounts[0].mask_capabilities");
+
+        config.mounts[0].mask_capabilities = vec!["chown".to_string()];
+        config.validate().unwrap();
+    }
+}
@@ -0,0 +1,220 @@
+//! Hot reload: poll the resolved config files and republish on change.
+
+use crate::{ConfigError, ConfigLoader, Fs9Config};
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+use tokio::time::MissedTickBehavior;
+
+/// A config published by [`ConfigWatcher`].
+#[derive(Debug, Clone)]
+pub struct ConfigReload {
+    pub config: Fs9Config,
+    /// Fields that differ from the config the watcher started with but are
+    /// only read at startup, e.g. `server.port`. Consumers should surface
+    /// these instead of pretending they were applied.
+    pub restart_required: Vec<&'static str>,
+}
+
+/// Re-runs the load + validate pipeline whenever one of the config files
+/// changes and publishes the result on a `tokio::sync::watch` channel.
+///
+/// Files are polled for size and modification time rather than watched
+/// through inotify, which keeps working when editors replace the file
+/// instead of writing it in place. A reload that fails to parse or validate
+/// is logged and the previous config stays current. The background task
+/// stops when the watcher is dropped.
+pub struct ConfigWatcher {
+    receiver: watch::Receiver<ConfigReload>,
+    task: JoinHandle<()>,
+}
+
+impl ConfigWatcher {
+    pub(crate) fn spawn(loader: ConfigLoader, interval: Duration) -> Result<Self, ConfigError> {
+        let mut sources = Vec::new();
+        let config = loader.load_tracked(&mut sources)?;
+        // Stamp the files now rather than in the task, so that edits made
+        // before the task first runs are still noticed.
+        let watched = watched_files(&loader, &sources);
+        let stamps = snapshot(&watched);
+        let (sender, receiver) = watch::channel(ConfigReload {
+            config: config.clone(),
+            restart_required: Vec::new(),
+        });
+        let task = tokio::spawn(poll(loader, interval, config, watched, stamps, sender));
+        Ok(Self { receiver, task })
+    }
+
+    /// A receiver that observes every successful reload.
+    pub fn subscribe(&self) -> watch::Receiver<ConfigReload> {
+        self.receiver.clone()
+    }
+
+    /// The most recently loaded config.
+    pub fn current(&self) -> Fs9Config {
+        self.receiver.borrow().config.clone()
+    }
+}
+
+impl Drop for ConfigWatcher {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// Fields changed between `running` and `reloaded` that cannot be applied
+/// without restarting the server.
+pub fn restart_required(running: &Fs9Config, reloaded: &Fs9Config) -> Vec<&'static str> {
+    let mut fields = Vec::new();
+    if running.server.host != reloaded.server.host {
+        fields.push("server.host");
+    }
+    if running.server.port != reloaded.server.port {
+        fields.push("server.port");
+    }
+    fields
+}
+
+type Stamp = Option<(SystemTime, u64)>;
+
+async fn poll(
+    loader: ConfigLoader,
+    interval: Duration,
+    initial: Fs9Config,
+    mut watched: Vec<PathBuf>,
+    mut stamps: Vec<Stamp>,
+    sender: watch::Sender<ConfigReload>,
+) {
+    let mut sources = Vec::new();
+    let mut ticker = tokio::time::interval(interval);
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+    loop {
+        ticker.tick().await;
+        let current = snapshot(&watched);
+        if current == stamps {
+            continue;
+        }
+        stamps = current;
+
+        sources.clear();
+        match loader.load_tracked(&mut sources) {
+            Ok(config) => {
+                let restart_required = restart_required(&initial, &config);
+                if restart_required.is_empty() {
+                    tracing::info!("Config reloaded");
+                } else {
+                    tracing::warn!(
+                        fields = ?restart_required,
+                        "Config reloaded; some changes require a restart to take effect"
+                    );
+                }
+
+                // Includes may have been added or dropped.
+                let files = watched_files(&loader, &sources);
+                if files != watched {
+                    watched = files;
+                    stamps = snapshot(&watched);
+                }
+
+                if sender
+                    .send(ConfigReload {
+                        config,
+                        restart_required,
+                    })
+                    .is_err()
+                {
+                    break;
+                }
+            }
+            Err(e) => {
+                tracing::warn!(error = %e, "Config reload failed; keeping previous config");
+            }
+        }
+    }
+}
+
+fn watched_files(loader: &ConfigLoader, sources: &[PathBuf]) -> Vec<PathBuf> {
+    let mut files = loader.candidate_files();
+    for source in sources {
+        if !files.contains(source) {
+            files.push(source.clone());
+        }
+    }
+    files
+}
+
+fn snapshot(files: &[PathBuf]) -> Vec<Stamp> {
+    files
+        .iter()
+        .map(|path| {
+            let meta = std::fs::metadata(path).ok()?;
+            Some((meta.modified().ok()?, meta.len()))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::LogLevel;
+
+    #[tokio::test]
+    async fn watcher_publishes_logging_level_change() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("fs9.yaml");
+        std::fs::write(&path, "logging:\n  level: info\n").unwrap();
+
+        let watcher = ConfigLoader::new()
+            .with_file(path.to_str().unwrap())
+            .watch(Duration::from_millis(20))
+            .unwrap();
+        let mut updates = watcher.subscribe();
+        assert_eq!(updates.borrow().config.logging.level, LogLevel::Info);
+
+        std::fs::write(&path, "logging:\n  level: debug\n").unwrap();
+        tokio::time::timeout(Duration::from_secs(5), updates.changed())
+            .await
+            .expect("no reload within timeout")
+            .unwrap();
+
+        let reload = updates.borrow().clone();
+        assert_eq!(reload.config.logging.level, LogLevel::Debug);
+        assert!(reload.restart_required.is_empty());
+        assert_eq!(watcher.current().logging.level, LogLevel::Debug);
+    }
+
+    #[tokio::test]
+    async fn invalid_reload_keeps_previous_config() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("fs9.yaml");
+        std::fs::write(&path, "logging:\n  level: warn\n").unwrap();
+
+        let watcher = ConfigLoader::new()
+            .with_file(path.to_str().unwrap())
+            .watch(Duration::from_millis(20))
+            .unwrap();
+        let mut updates = watcher.subscribe();
+
+        std::fs::write(&path, "server:\n  port: 0\n").unwrap();
+        let changed = tokio::time::timeout(Duration::from_millis(200), updates.changed()).await;
+        assert!(changed.is_err(), "invalid config must not be published");
+        assert_eq!(watcher.current().logging.level, LogLevel::Warn);
+    }
+
+    #[test]
+    fn host_and_port_changes_require_restart() {
+        let running = Fs9Config::default();
+        let mut reloaded = running.clone();
+        reloaded.logging.level = LogLevel::Trace;
+        assert!(restart_required(&running, &reloaded).is_empty());
+
+        reloaded.server.host = "127.0.0.1".to_string();
+        reloaded.server.port = running.server.port + 1;
+        assert_eq!(
+            restart_required(&running, &reloaded),
+            ["server.host", "server.port"]
+        );
+    }
+}
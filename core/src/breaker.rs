@@ -0,0 +1,340 @@
+//! Per-mount circuit breakers used by [`VfsRouter`](crate::VfsRouter).
+//!
+//! A mount whose backend keeps failing is taken offline for a cool-down
+//! period, during which calls fail at once with
+//! [`FsError::CircuitBreakerOpen`] instead of each waiting on the backend.
+//! After the cool-down a single probe call is let through; its outcome
+//! decides whether the mount comes back or stays offline for another round.
+
+use fs9_sdk::{FsError, FsResult};
+use metrics::{counter, gauge};
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::mount::MountPoint;
+
+/// Settings for the router's per-mount circuit breakers.
+#[derive(Debug, Clone, Copy)]
+pub struct CircuitBreakerConfig {
+    /// How far back call outcomes count towards the failure rate.
+    pub window: Duration,
+    /// Calls needed within the window before the breaker may open, so that
+    /// a single failure on a quiet mount does not take it offline.
+    pub min_calls: u32,
+    /// Fraction of failed calls within the window, from 0 to 1, that opens
+    /// the breaker.
+    pub failure_rate: f64,
+    /// How long an open breaker rejects calls before letting a probe through.
+    pub cooldown: Duration,
+}
+
+impl CircuitBreakerConfig {
+    #[must_use]
+    pub fn new(window: Duration, cooldown: Duration) -> Self {
+        Self {
+            window,
+            min_calls: 10,
+            failure_rate: 0.5,
+            cooldown,
+        }
+    }
+
+    #[must_use]
+    pub fn with_min_calls(mut self, min_calls: u32) -> Self {
+        self.min_calls = min_calls.max(1);
+        self
+    }
+
+    #[must_use]
+    pub fn with_failure_rate(mut self, failure_rate: f64) -> Self {
+        self.failure_rate = failure_rate.clamp(0.0, 1.0);
+        self
+    }
+}
+
+/// Where a mount's breaker stands.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CircuitState {
+    /// Calls go through and their outcomes are counted.
+    Closed,
+    /// Calls are rejected until the cool-down ends.
+    Open,
+    /// The cool-down is over and one probe call is in flight.
+    HalfOpen,
+}
+
+enum State {
+    Closed,
+    Open { until: Instant },
+    HalfOpen { since: Instant },
+}
+
+#[derive(Default)]
+struct Window {
+    /// `(finished at, failed)` for recent calls, oldest first.
+    outcomes: VecDeque<(Instant, bool)>,
+    failures: usize,
+}
+
+impl Window {
+    fn push(&mut self, now: Instant, failed: bool, window: Duration) {
+        self.outcomes.push_back((now, failed));
+        self.failures += usize::from(failed);
+        while let Some(&(at, failed)) = self.outcomes.front() {
+            if now.duration_since(at) <= window {
+                break;
+            }
+            self.outcomes.pop_front();
+            self.failures -= usize::from(failed);
+        }
+    }
+
+    fn clear(&mut self) {
+        self.outcomes.clear();
+        self.failures = 0;
+    }
+}
+
+struct Breaker {
+    state: State,
+    window: Window,
+}
+
+/// Errors that say the backend itself is in trouble. Anything else, such
+/// as a missing file or a bad argument, means the backend answered.
+fn is_backend_failure(err: &FsError) -> bool {
+    matches!(
+        err,
+        FsError::BackendUnavailable(_)
+            | FsError::Transient(_)
+            | FsError::Timeout { .. }
+            | FsError::Internal(_)
+            | FsError::Remote { .. }
+    )
+}
+
+/// Report the breaker position under `fs9_vfs_circuit_open`, 1 while calls
+/// are being rejected, and count openings under
+/// `fs9_vfs_circuit_opened_total`.
+fn record_state(mount: &MountPoint, state: CircuitState) {
+    let labels = [
+        ("mount", mount.path.clone()),
+        ("provider", mount.provider_name.clone()),
+    ];
+    let open = if state == CircuitState::Closed {
+        0.0
+    } else {
+        1.0
+    };
+    gauge!("fs9_vfs_circuit_open", &labels).set(open);
+    if state == CircuitState::Open {
+        counter!("fs9_vfs_circuit_opened_total", &labels).increment(1);
+    }
+}
+
+pub(crate) struct MountBreakers {
+    config: CircuitBreakerConfig,
+    breakers: Mutex<HashMap<String, Breaker>>,
+}
+
+impl MountBreakers {
+    pub(crate) fn new(config: CircuitBreakerConfig) -> Self {
+        Self {
+            config,
+            breakers: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub(crate) fn state(&self, mount_path: &str) -> CircuitState {
+        let breakers = self.breakers.lock().expect("circuit breakers poisoned");
+        match breakers.get(mount_path).map(|b| &b.state) {
+            None | Some(State::Closed) => CircuitState::Closed,
+            Some(State::Open { until }) if Instant::now() < *until => CircuitState::Open,
+            Some(State::Open { .. } | State::HalfOpen { .. }) => CircuitState::HalfOpen,
+        }
+    }
+
+    /// Let a call to `mount` go ahead, or refuse it while the breaker is
+    /// open or its probe is still out.
+    pub(crate) fn admit(&self, mount: &MountPoint) -> FsResult<()> {
+        let now = Instant::now();
+        let mut breakers = self.breakers.lock().expect("circuit breakers poisoned");
+        let Some(breaker) = breakers.get_mut(&mount.path) else {
+            return Ok(());
+        };
+
+        let retry_at = match breaker.state {
+            State::Closed => return Ok(()),
+            State::Open { until } => until,
+            // A probe whose caller went away never reports back, so give up
+            // on it after one more cool-down and send another.
+            State::HalfOpen { since } => since + self.config.cooldown,
+        };
+        if now >= retry_at {
+            breaker.state = State::HalfOpen { since: now };
+            record_state(mount, CircuitState::HalfOpen);
+            return Ok(());
+        }
+
+        counter!(
+            "fs9_vfs_circuit_rejections_total",
+            "mount" => mount.path.clone(),
+            "provider" => mount.provider_name.clone()
+        )
+        .increment(1);
+        Err(FsError::circuit_breaker_open(
+            mount.path.clone(),
+            retry_at - now,
+        ))
+    }
+
+    /// Count the outcome of a call that [`admit`](Self::admit) let through.
+    pub(crate) fn record<T>(&self, mount: &MountPoint, result: &FsResult<T>) {
+        let failed = result.as_ref().err().is_some_and(is_backend_failure);
+        let now = Instant::now();
+        let mut breakers = self.breakers.lock().expect("circuit breakers poisoned");
+        let breaker = breakers
+            .entry(mount.path.clone())
+            .or_insert_with(|| Breaker {
+                state: State::Closed,
+                window: Window::default(),
+            });
+
+        match breaker.state {
+            State::Closed => {
+                breaker.window.push(now, failed, self.config.window);
+                let calls = breaker.window.outcomes.len();
+                #[allow(clippy::cast_precision_loss)]
+                let rate = breaker.window.failures as f64 / calls as f64;
+                if failed
+                    && calls >= self.config.min_calls as usize
+                    && rate >= self.config.failure_rate
+                {
+                    tracing::warn!(
+                        mount = %mount.path,
+                        calls,
+                        failures = breaker.window.failures,
+                        "circuit breaker opened"
+                    );
+                    breaker.state = State::Open {
+                        until: now + self.config.cooldown,
+                    };
+                    breaker.window.clear();
+                    record_state(mount, CircuitState::Open);
+                }
+            }
+            State::HalfOpen { .. } if failed => {
+                tracing::warn!(mount = %mount.path, "circuit breaker probe failed");
+                breaker.state = State::Open {
+                    until: now + self.config.cooldown,
+                };
+                record_state(mount, CircuitState::Open);
+            }
+            State::HalfOpen { .. } => {
+                tracing::info!(mount = %mount.path, "circuit breaker closed");
+                breaker.state = State::Closed;
+                breaker.window.clear();
+                record_state(mount, CircuitState::Closed);
+            }
+            // Calls admitted before the breaker opened; the window that
+            // opened it already accounts for the backend's state.
+            State::Open { .. } => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mount(path: &str) -> MountPoint {
+        MountPoint {
+            path: path.to_string(),
+            provider_name: "test".to_string(),
+            bind_source: None,
+            read_only: false,
+            op_timeout: None,
+            retry: None,
+            masked_capabilities: fs9_sdk::Capabilities::empty(),
+        }
+    }
+
+    fn breakers(cooldown: Duration) -> MountBreakers {
+        MountBreakers::new(
+            CircuitBreakerConfig::new(Duration::from_secs(60), cooldown).with_min_calls(3),
+        )
+    }
+
+    fn fail(breakers: &MountBreakers, mount: &MountPoint) {
+        breakers.admit(mount).unwrap();
+        breakers.record::<()>(mount, &Err(FsError::backend_unavailable("down")));
+    }
+
+    #[test]
+    fn opens_once_the_failure_rate_is_reached() {
+        let breakers = breakers(Duration::from_secs(30));
+        let data = mount("/data");
+
+        fail(&breakers, &data);
+        fail(&breakers, &data);
+        assert_eq!(breakers.state("/data"), CircuitState::Closed);
+        fail(&breakers, &data);
+        assert_eq!(breakers.state("/data"), CircuitState::Open);
+
+        let Err(FsError::CircuitBreakerOpen { retry_after, .. }) = breakers.admit(&data) else {
+            panic!("open breaker admitted a call");
+        };
+        assert!(retry_after <= Duration::from_secs(30));
+    }
+
+    #[test]
+    fn ordinary_errors_and_successes_keep_it_closed() {
+        let breakers = breakers(Duration::from_secs(30));
+        let data = mount("/data");
+
+        for _ in 0..5 {
+            breakers.record::<()>(&data, &Err(FsError::not_found("/x")));
+        }
+        breakers.record::<()>(&data, &Err(FsError::end_of_stream("/s")));
+        fail(&breakers, &data);
+        breakers.record(&data, &Ok(()));
+        fail(&breakers, &data);
+        // Two of eight calls failed: below the 50% rate.
+        assert_eq!(breakers.state("/data"), CircuitState::Closed);
+    }
+
+    #[test]
+    fn breakers_are_per_mount() {
+        let breakers = breakers(Duration::from_secs(30));
+        let (data, logs) = (mount("/data"), mount("/logs"));
+
+        for _ in 0..3 {
+            fail(&breakers, &data);
+        }
+        assert!(breakers.admit(&data).is_err());
+        assert!(breakers.admit(&logs).is_ok());
+    }
+
+    #[test]
+    fn probe_after_cooldown_closes_or_reopens() {
+        let breakers = breakers(Duration::from_millis(20));
+        let data = mount("/data");
+        for _ in 0..3 {
+            fail(&breakers, &data);
+        }
+
+        std::thread::sleep(Duration::from_millis(30));
+        assert_eq!(breakers.state("/data"), CircuitState::HalfOpen);
+        fail(&breakers, &data);
+        assert_eq!(breakers.state("/data"), CircuitState::Open);
+
+        std::thread::sleep(Duration::from_millis(30));
+        breakers.admit(&data).unwrap();
+        // Only one probe at a time.
+        assert!(breakers.admit(&data).is_err());
+        breakers.record(&data, &Ok(()));
+        assert_eq!(breakers.state("/data"), CircuitState::Closed);
+        assert!(breakers.admit(&data).is_ok());
+    }
+}
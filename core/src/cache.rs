@@ -0,0 +1,249 @@
+//! Short-lived `stat`/`readdir` cache used by [`VfsRouter`](crate::VfsRouter).
+
+use fs9_sdk::{normalize_path, FileInfo, FsError, FsResult};
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Settings for the router's metadata cache.
+#[derive(Debug, Clone, Copy)]
+pub struct MetadataCacheConfig {
+    /// How long a cached `stat` or `readdir` result is served.
+    pub ttl: Duration,
+    /// Also cache `NotFound` from `stat`. Off by default: a file created
+    /// behind the router's back stays invisible until the entry expires.
+    pub negative: bool,
+    /// Most `stat` results, and separately most listings, held at once.
+    /// Storing past it drops the oldest entries first.
+    pub max_entries: usize,
+}
+
+impl MetadataCacheConfig {
+    pub const DEFAULT_MAX_ENTRIES: usize = 100_000;
+
+    #[must_use]
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            negative: false,
+            max_entries: Self::DEFAULT_MAX_ENTRIES,
+        }
+    }
+
+    #[must_use]
+    pub fn with_negative_lookups(mut self, enabled: bool) -> Self {
+        self.negative = enabled;
+        self
+    }
+
+    #[must_use]
+    pub fn with_max_entries(mut self, max_entries: usize) -> Self {
+        self.max_entries = max_entries;
+        self
+    }
+}
+
+/// Hit and miss counts since the router was created.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+}
+
+struct Cached<T> {
+    value: T,
+    expires: Instant,
+}
+
+/// Cached values by path, with the order they were stored in. Every entry
+/// lives for the same TTL, so the oldest stored is the first to expire.
+struct Entries<T> {
+    map: HashMap<String, Cached<T>>,
+    /// Expiry and path of each store, oldest first. A store whose entry has
+    /// since been replaced or removed is skipped when reached.
+    order: VecDeque<(Instant, String)>,
+}
+
+impl<T> Default for Entries<T> {
+    fn default() -> Self {
+        Self {
+            map: HashMap::new(),
+            order: VecDeque::new(),
+        }
+    }
+}
+
+impl<T> Entries<T> {
+    fn insert(&mut self, path: String, value: T, expires: Instant, max_entries: usize) {
+        let now = Instant::now();
+        while let Some((stored, _)) = self.order.front() {
+            let full = self.map.len() >= max_entries && !self.map.contains_key(&path);
+            if *stored > now && !full {
+                break;
+            }
+            let (stored, key) = self.order.pop_front().unwrap();
+            if self
+                .map
+                .get(&key)
+                .is_some_and(|entry| entry.expires == stored)
+            {
+                self.map.remove(&key);
+            }
+        }
+        if max_entries == 0 {
+            return;
+        }
+        self.order.push_back((expires, path.clone()));
+        self.map.insert(path, Cached { value, expires });
+        // Paths stored over and over leave records behind; drop them before
+        // they outnumber the entries.
+        if self.order.len() > max_entries.saturating_mul(2) {
+            let map = &self.map;
+            self.order
+                .retain(|(stored, key)| map.get(key).is_some_and(|entry| entry.expires == *stored));
+        }
+    }
+}
+
+pub(crate) struct MetadataCache {
+    config: MetadataCacheConfig,
+    stats: Mutex<Entries<FsResult<FileInfo>>>,
+    dirs: Mutex<Entries<Vec<FileInfo>>>,
+    /// Bumped on every invalidation so that a lookup which raced with a
+    /// mutation does not store what it read from before the mutation.
+    generation: AtomicU64,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl MetadataCache {
+    pub(crate) fn new(config: MetadataCacheConfig) -> Self {
+        Self {
+            config,
+            stats: Mutex::new(Entries::default()),
+            dirs: Mutex::new(Entries::default()),
+            generation: AtomicU64::new(0),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    pub(crate) fn generation(&self) -> u64 {
+        self.generation.load(Ordering::Acquire)
+    }
+
+    pub(crate) fn stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+        }
+    }
+
+    pub(crate) fn get_stat(&self, path: &str) -> Option<FsResult<FileInfo>> {
+        let value = normalize_path(path)
+            .ok()
+            .and_then(|path| Self::lookup(&self.stats, &path));
+        self.record(value.is_some());
+        value
+    }
+
+    pub(crate) fn put_stat(&self, path: &str, result: &FsResult<FileInfo>, generation: u64) {
+        let cacheable = match result {
+            Ok(_) => true,
+            Err(FsError::NotFound(_)) => self.config.negative,
+            Err(_) => false,
+        };
+        if cacheable {
+            self.store(&self.stats, path, result.clone(), generation);
+        }
+    }
+
+    pub(crate) fn get_dir(&self, path: &str) -> Option<Vec<FileInfo>> {
+        let value = normalize_path(path)
+            .ok()
+            .and_then(|path| Self::lookup(&self.dirs, &path));
+        self.record(value.is_some());
+        value
+    }
+
+    pub(crate) fn put_dir(&self, path: &str, entries: &[FileInfo], generation: u64) {
+        self.store(&self.dirs, path, entries.to_vec(), generation);
+    }
+
+    /// Drop cached data for `path` and the listing of its parent.
+    pub(crate) fn invalidate(&self, path: &str) {
+        self.generation.fetch_add(1, Ordering::AcqRel);
+        // Paths that don't normalize are never cached.
+        let Ok(path) = normalize_path(path) else {
+            return;
+        };
+        self.stats.lock().unwrap().map.remove(&path);
+        let mut dirs = self.dirs.lock().unwrap();
+        dirs.map.remove(&path);
+        dirs.map.remove(parent(&path));
+    }
+
+    /// Like [`invalidate`](Self::invalidate), and also everything below `path`.
+    pub(crate) fn invalidate_tree(&self, path: &str) {
+        self.invalidate(path);
+        let Ok(path) = normalize_path(path) else {
+            return;
+        };
+        let prefix = if path == "/" {
+            path
+        } else {
+            format!("{path}/")
+        };
+        self.stats
+            .lock()
+            .unwrap()
+            .map
+            .retain(|key, _| !key.starts_with(&prefix));
+        self.dirs
+            .lock()
+            .unwrap()
+            .map
+            .retain(|key, _| !key.starts_with(&prefix));
+    }
+
+    fn lookup<T: Clone>(entries: &Mutex<Entries<T>>, key: &str) -> Option<T> {
+        let map = &mut entries.lock().unwrap().map;
+        match map.get(key) {
+            Some(entry) if entry.expires > Instant::now() => Some(entry.value.clone()),
+            Some(_) => {
+                map.remove(key);
+                None
+            }
+            None => None,
+        }
+    }
+
+    /// Cache `value` for `path`, first dropping expired entries and, when
+    /// full, the oldest ones.
+    fn store<T>(&self, entries: &Mutex<Entries<T>>, path: &str, value: T, generation: u64) {
+        let Ok(path) = normalize_path(path) else {
+            return;
+        };
+        let mut entries = entries.lock().unwrap();
+        // Checked under the map lock: invalidation bumps the generation
+        // before taking the same lock, so a racing mutation is never missed.
+        if self.generation() != generation {
+            return;
+        }
+        let expires = Instant::now() + self.config.ttl;
+        entries.insert(path, value, expires, self.config.max_entries);
+    }
+
+    fn record(&self, hit: bool) {
+        let counter = if hit { &self.hits } else { &self.misses };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+fn parent(path: &str) -> &str {
+    match path.rfind('/') {
+        Some(0) | None => "/",
+        Some(i) => &path[..i],
+    }
+}
@@ -0,0 +1,108 @@
+//! Change notifications published by [`VfsRouter`](crate::VfsRouter).
+//!
+//! Every mutation that succeeds through a router is sent on its broadcast
+//! channel. Sending never waits: a subscriber that falls more than
+//! [`CHANGE_BUFFER`] events behind misses the oldest ones and is told how
+//! many it skipped the next time it receives.
+
+use serde::Serialize;
+use tokio::sync::broadcast;
+
+/// Events a subscriber may fall behind by before it starts losing them.
+pub const CHANGE_BUFFER: usize = 1024;
+
+/// The kind of change an event reports.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChangeOp {
+    Create,
+    Write,
+    Remove,
+    Rename,
+}
+
+impl ChangeOp {
+    #[must_use]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Create => "create",
+            Self::Write => "write",
+            Self::Remove => "remove",
+            Self::Rename => "rename",
+        }
+    }
+}
+
+/// One successful mutation, with paths as seen through the router.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ChangeEvent {
+    pub op: ChangeOp,
+    pub path: String,
+    /// Where a renamed entry went.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub new_path: Option<String>,
+}
+
+impl ChangeEvent {
+    /// Whether the event touches `prefix` or anything below it.
+    #[must_use]
+    pub fn is_under(&self, prefix: &str) -> bool {
+        let under = |path: &str| {
+            let prefix = prefix.trim_end_matches('/');
+            prefix.is_empty()
+                || path == prefix
+                || path
+                    .strip_prefix(prefix)
+                    .is_some_and(|rest| rest.starts_with('/'))
+        };
+        under(&self.path) || self.new_path.as_deref().is_some_and(under)
+    }
+}
+
+pub(crate) struct ChangeFeed {
+    sender: broadcast::Sender<ChangeEvent>,
+}
+
+impl ChangeFeed {
+    pub(crate) fn new() -> Self {
+        Self {
+            sender: broadcast::channel(CHANGE_BUFFER).0,
+        }
+    }
+
+    pub(crate) fn subscribe(&self) -> broadcast::Receiver<ChangeEvent> {
+        self.sender.subscribe()
+    }
+
+    pub(crate) fn publish(&self, op: ChangeOp, path: &str, new_path: Option<&str>) {
+        if self.sender.receiver_count() == 0 {
+            return;
+        }
+        // Only fails when every receiver has gone in the meantime.
+        let _ = self.sender.send(ChangeEvent {
+            op,
+            path: path.to_string(),
+            new_path: new_path.map(str::to_string),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn is_under_matches_whole_path_components() {
+        let event = ChangeEvent {
+            op: ChangeOp::Rename,
+            path: "/docs/a.txt".to_string(),
+            new_path: Some("/archive/a.txt".to_string()),
+        };
+        assert!(event.is_under("/"));
+        assert!(event.is_under("/docs"));
+        assert!(event.is_under("/docs/"));
+        assert!(event.is_under("/archive"));
+        assert!(!event.is_under("/doc"));
+        assert!(!event.is_under("/other"));
+    }
+}
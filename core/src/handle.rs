@@ -219,6 +219,19 @@ impl HandleRegistry {
         total
     }
 
+    /// Number of open handles served by `provider`.
+    pub async fn count_for_provider(&self, provider: &Arc<dyn FsProvider>) -> usize {
+        let mut count = 0;
+        for shard in &self.shards {
+            let handles = shard.handles.read().await;
+            count += handles
+                .values()
+                .filter(|state| Arc::ptr_eq(&state.provider, provider))
+                .count();
+        }
+        count
+    }
+
     /// List information about all handles.
     ///
     /// Acquires read locks on all shards sequentially. O(shards) lock acquisitions.
@@ -339,6 +352,32 @@ mod tests {
         assert_eq!(registry.count().await, 1);
     }
 
+    #[tokio::test]
+    async fn count_handles_per_provider() {
+        let registry = HandleRegistry::new(Duration::from_secs(300));
+        let fs: Arc<dyn FsProvider> = Arc::new(MemoryFs::new());
+        let other: Arc<dyn FsProvider> = Arc::new(MemoryFs::new());
+
+        let (provider_handle, metadata) = fs
+            .open("/test.txt", OpenFlags::create_file())
+            .await
+            .unwrap();
+        let id = registry
+            .register(
+                fs.clone(),
+                "/test.txt".to_string(),
+                OpenFlags::create_file(),
+                metadata,
+                provider_handle,
+            )
+            .await;
+
+        assert_eq!(registry.count_for_provider(&fs).await, 1);
+        assert_eq!(registry.count_for_provider(&other).await, 0);
+        registry.close(id, false).await.unwrap();
+        assert_eq!(registry.count_for_provider(&fs).await, 0);
+    }
+
     #[tokio::test]
     async fn close_handle() {
         let registry = HandleRegistry::new(Duration::from_secs(300));
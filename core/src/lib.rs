@@ -1,18 +1,29 @@
 #![allow(missing_docs)]
 
+pub mod breaker;
+pub mod cache;
+pub mod events;
 pub mod handle;
 pub mod mount;
 pub mod plugin;
 pub mod providers;
+pub mod retry;
+#[cfg(any(test, feature = "test-util"))]
+pub mod testing;
 pub mod vfs;
 
+pub use breaker::{CircuitBreakerConfig, CircuitState};
+pub use cache::{CacheStats, MetadataCacheConfig};
+pub use events::{ChangeEvent, ChangeOp};
 pub use fs9_sdk;
 pub use handle::{
     start_cleanup_task, HandleId, HandleInfo, HandleRef, HandleRegistry, HandleState,
 };
-pub use mount::{MountEntry, MountPoint, MountTable};
-pub use plugin::{PluginError, PluginManager, PluginProvider};
+pub use mount::{MountEntry, MountPoint, MountTable, ResolvedPath};
+pub use plugin::{PluginError, PluginManager, PluginOps, PluginProvider};
 pub use providers::{
-    default_registry, LocalFs, MemoryFs, ProviderConfig, ProviderFactory, ProviderRegistry, ProxyFs,
+    default_registry, CacheFs, LocalFs, MemoryFs, NullFs, OverlayFs, ProviderConfig,
+    ProviderFactory, ProviderPool, ProviderRegistry, ProxyFs, RandomFs, WriteMode,
 };
+pub use retry::RetryPolicy;
 pub use vfs::VfsRouter;
@@ -1,12 +1,36 @@
 use fs9_sdk::{Capabilities, FsError, FsProvider, FsResult};
 use std::collections::BTreeMap;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::RwLock;
 
+use crate::retry::RetryPolicy;
+
 #[derive(Debug, Clone)]
 pub struct MountPoint {
     pub path: String,
     pub provider_name: String,
+    /// For a bind mount, the path of the mount whose provider it shares.
+    pub bind_source: Option<String>,
+    /// Reject every mutation through this path, whatever the provider
+    /// supports. Enforced by [`VfsRouter`](crate::VfsRouter).
+    pub read_only: bool,
+    /// Stop waiting for a provider call through this path after this long.
+    /// Enforced by [`VfsRouter`](crate::VfsRouter).
+    pub op_timeout: Option<Duration>,
+    /// Retry idempotent provider calls through this path that fail
+    /// transiently. Enforced by [`VfsRouter`](crate::VfsRouter).
+    pub retry: Option<RetryPolicy>,
+    /// Capabilities this path does not offer even though the provider has
+    /// them. Enforced by [`VfsRouter`](crate::VfsRouter).
+    pub masked_capabilities: Capabilities,
+}
+
+impl MountPoint {
+    /// The mount that owns the provider: the bind source, or this mount.
+    fn origin(&self) -> &str {
+        self.bind_source.as_deref().unwrap_or(&self.path)
+    }
 }
 
 pub struct MountEntry {
@@ -22,6 +46,27 @@ impl std::fmt::Debug for MountEntry {
     }
 }
 
+/// The outcome of resolving a path against the mount table.
+pub struct ResolvedPath {
+    pub provider: Arc<dyn FsProvider>,
+    /// The path as the provider sees it.
+    pub relative_path: String,
+    /// The path under the provider's original mount. Paths reached through
+    /// different bind mounts of the same provider share an origin path.
+    pub origin_path: String,
+    /// The mount the path was resolved through.
+    pub mount: MountPoint,
+}
+
+impl ResolvedPath {
+    /// What the provider supports through this mount: its capabilities
+    /// less the mount's masked ones.
+    #[must_use]
+    pub fn capabilities(&self) -> Capabilities {
+        self.provider.capabilities() - self.mount.masked_capabilities
+    }
+}
+
 pub struct MountTable {
     mounts: RwLock<BTreeMap<String, MountEntry>>,
 }
@@ -74,6 +119,11 @@ impl MountTable {
                 mount_point: MountPoint {
                     path,
                     provider_name: provider_name.to_string(),
+                    bind_source: None,
+                    read_only: false,
+                    op_timeout: None,
+                    retry: None,
+                    masked_capabilities: Capabilities::empty(),
                 },
                 provider,
             },
@@ -82,6 +132,230 @@ impl MountTable {
         Ok(())
     }
 
+    /// Make the provider mounted at `source` also appear at `alias`. Both
+    /// paths then share one provider instance, so handles and data are the
+    /// same whichever path is used. Binding a bind mount binds its origin.
+    pub async fn bind(&self, source: &str, alias: &str) -> FsResult<()> {
+        let source = Self::normalize_mount_path(source);
+        let alias = Self::normalize_mount_path(alias);
+        let mut mounts = self.mounts.write().await;
+
+        if mounts.contains_key(&alias) {
+            return Err(FsError::already_exists(&alias));
+        }
+        let origin = mounts
+            .get(&source)
+            .ok_or_else(|| FsError::not_found(&source))?;
+
+        let entry = MountEntry {
+            mount_point: MountPoint {
+                path: alias.clone(),
+                provider_name: origin.mount_point.provider_name.clone(),
+                bind_source: Some(origin.mount_point.origin().to_string()),
+                read_only: origin.mount_point.read_only,
+                op_timeout: origin.mount_point.op_timeout,
+                retry: origin.mount_point.retry,
+                masked_capabilities: origin.mount_point.masked_capabilities,
+            },
+            provider: origin.provider.clone(),
+        };
+        mounts.insert(alias, entry);
+
+        Ok(())
+    }
+
+    /// Mount an [`OverlayFs`](crate::OverlayFs) at `path`: writes land in
+    /// `upper`, reads fall through to `lowers` in order.
+    pub async fn mount_overlay(
+        &self,
+        path: &str,
+        upper: Arc<dyn FsProvider>,
+        lowers: Vec<Arc<dyn FsProvider>>,
+    ) -> FsResult<()> {
+        let overlay = crate::providers::OverlayFs::new(upper, lowers);
+        self.mount(path, "overlay", Arc::new(overlay)).await
+    }
+
+    /// Snapshot the tree at `path` through the provider it resolves to and
+    /// mount a read-only view of it at `at`. Writes through `path` carry on
+    /// without showing up at `at`. Returns the provider's snapshot id.
+    pub async fn snapshot(&self, path: &str, at: &str) -> FsResult<u64> {
+        let at = Self::normalize_mount_path(at);
+        if self.mounts.read().await.contains_key(&at) {
+            return Err(FsError::already_exists(&at));
+        }
+        let resolved = self.resolve_mount(path).await?;
+        if !resolved.capabilities().supports_snapshot() {
+            return Err(FsError::not_implemented(format!(
+                "snapshot ({})",
+                resolved.mount.provider_name
+            )));
+        }
+        let snapshot_id = resolved.provider.snapshot(&resolved.relative_path).await?;
+        self.mount_snapshot(&resolved.mount.path, snapshot_id, &at)
+            .await?;
+        Ok(snapshot_id)
+    }
+
+    /// Mount a read-only view of snapshot `snapshot_id`, taken earlier by
+    /// the provider mounted at `source`, at `at`.
+    pub async fn mount_snapshot(&self, source: &str, snapshot_id: u64, at: &str) -> FsResult<()> {
+        let source = Self::normalize_mount_path(source);
+        let at = Self::normalize_mount_path(at);
+        let (origin, provider) = {
+            let mounts = self.mounts.read().await;
+            let entry = mounts
+                .get(&source)
+                .ok_or_else(|| FsError::not_found(&source))?;
+            (entry.mount_point.clone(), entry.provider.clone())
+        };
+        if !(provider.capabilities() - origin.masked_capabilities).supports_snapshot() {
+            return Err(FsError::not_implemented(format!(
+                "snapshot ({})",
+                origin.provider_name
+            )));
+        }
+        let view = provider.snapshot_view(snapshot_id).await?;
+
+        let mut mounts = self.mounts.write().await;
+        if mounts.contains_key(&at) {
+            return Err(FsError::already_exists(&at));
+        }
+        let entry = MountEntry {
+            mount_point: MountPoint {
+                path: at.clone(),
+                provider_name: format!("{}@{snapshot_id}", origin.provider_name),
+                bind_source: None,
+                read_only: true,
+                op_timeout: origin.op_timeout,
+                retry: origin.retry,
+                masked_capabilities: origin.masked_capabilities,
+            },
+            provider: view,
+        };
+        mounts.insert(at, entry);
+        Ok(())
+    }
+
+    /// Mark the mount at `path` read-only, or writable again. A bind mount
+    /// can be made read-only on its own, leaving its source writable.
+    pub async fn set_read_only(&self, path: &str, read_only: bool) -> FsResult<()> {
+        let path = Self::normalize_mount_path(path);
+        let mut mounts = self.mounts.write().await;
+        let entry = mounts
+            .get_mut(&path)
+            .ok_or_else(|| FsError::not_found(&path))?;
+        entry.mount_point.read_only = read_only;
+        Ok(())
+    }
+
+    /// Fail calls through the mount at `path` with [`FsError::Timeout`]
+    /// once they have run for `timeout`, or
+    /// wait indefinitely again with `None`. Like the read-only flag, a bind
+    /// mount starts out with its source's timeout and can be changed alone.
+    ///
+    /// A timeout only stops the request from waiting; it cannot cancel the
+    /// provider call. Plugin calls run on Tokio's blocking pool, so each
+    /// abandoned call keeps a pool thread busy until the plugin returns, and
+    /// a backend that hangs for good can eventually exhaust the pool.
+    /// Providers that block the async worker instead of awaiting cannot be
+    /// timed out at all.
+    pub async fn set_op_timeout(&self, path: &str, timeout: Option<Duration>) -> FsResult<()> {
+        let path = Self::normalize_mount_path(path);
+        let mut mounts = self.mounts.write().await;
+        let entry = mounts
+            .get_mut(&path)
+            .ok_or_else(|| FsError::not_found(&path))?;
+        entry.mount_point.op_timeout = timeout;
+        Ok(())
+    }
+
+    /// Retry idempotent calls through the mount at `path` that fail
+    /// transiently, as `policy` says, or stop retrying with `None`. Like the
+    /// timeout, a bind mount starts out with its source's policy and can be
+    /// changed alone.
+    pub async fn set_retry_policy(&self, path: &str, policy: Option<RetryPolicy>) -> FsResult<()> {
+        let path = Self::normalize_mount_path(path);
+        let mut mounts = self.mounts.write().await;
+        let entry = mounts
+            .get_mut(&path)
+            .ok_or_else(|| FsError::not_found(&path))?;
+        entry.mount_point.retry = policy;
+        Ok(())
+    }
+
+    /// Fail with [`FsError::NotImplemented`] unless the provider mounted at
+    /// `path` has every capability in `required`, so a mount that depends
+    /// on them is refused up front rather than failing on first use.
+    pub async fn require_capabilities(&self, path: &str, required: Capabilities) -> FsResult<()> {
+        let path = Self::normalize_mount_path(path);
+        let (provider_name, caps) = self
+            .mounts
+            .read()
+            .await
+            .get(&path)
+            .map(|e| {
+                (
+                    e.mount_point.provider_name.clone(),
+                    e.provider.capabilities(),
+                )
+            })
+            .ok_or_else(|| FsError::not_found(&path))?;
+        let missing = required - caps;
+        if !missing.is_empty() {
+            return Err(FsError::not_implemented(format!(
+                "{path} ({provider_name}) lacks required capabilities: {}",
+                missing.names().join(", ")
+            )));
+        }
+        Ok(())
+    }
+
+    /// Hide `masked` capabilities of the provider at `path`: callers are
+    /// refused the operations they cover and the mount advertises the
+    /// reduced set. Like the read-only flag, a bind mount starts out with
+    /// its source's mask and can be changed alone.
+    pub async fn mask_capabilities(&self, path: &str, masked: Capabilities) -> FsResult<()> {
+        let path = Self::normalize_mount_path(path);
+        self.mounts
+            .write()
+            .await
+            .get_mut(&path)
+            .ok_or_else(|| FsError::not_found(&path))?
+            .mount_point
+            .masked_capabilities = masked;
+        Ok(())
+    }
+
+    /// Swap the provider mounted at `path` for `provider`, keeping the
+    /// mount's read-only flag, and return the one it replaces. Bind mounts
+    /// of `path` follow the new provider; replacing a bind mount itself is
+    /// refused, replace its source instead.
+    pub async fn replace(
+        &self,
+        path: &str,
+        provider_name: &str,
+        provider: Arc<dyn FsProvider>,
+    ) -> FsResult<Arc<dyn FsProvider>> {
+        let path = Self::normalize_mount_path(path);
+        let mut mounts = self.mounts.write().await;
+        let entry = mounts.get(&path).ok_or_else(|| FsError::not_found(&path))?;
+        if let Some(source) = &entry.mount_point.bind_source {
+            return Err(FsError::invalid_argument(format!(
+                "{path} is a bind mount of {source}"
+            )));
+        }
+
+        let old = entry.provider.clone();
+        for entry in mounts.values_mut() {
+            if entry.mount_point.origin() == path {
+                entry.mount_point.provider_name = provider_name.to_string();
+                entry.provider = provider.clone();
+            }
+        }
+        Ok(old)
+    }
+
     pub async fn unmount(&self, path: &str) -> FsResult<Arc<dyn FsProvider>> {
         let path = Self::normalize_mount_path(path);
         let mut mounts = self.mounts.write().await;
@@ -93,27 +367,62 @@ impl MountTable {
     }
 
     pub async fn resolve(&self, path: &str) -> FsResult<(Arc<dyn FsProvider>, String)> {
+        let resolved = self.resolve_mount(path).await?;
+        Ok((resolved.provider, resolved.relative_path))
+    }
+
+    /// Like [`resolve`](Self::resolve), also reporting the mount that
+    /// matched and the path under the provider's original mount.
+    pub async fn resolve_mount(&self, path: &str) -> FsResult<ResolvedPath> {
         let path = Self::normalize_mount_path(path);
         let mounts = self.mounts.read().await;
+        let (entry, relative_path) = Self::lookup(&mounts, path)?;
+
+        let origin = entry.mount_point.origin();
+        let origin_path = match (origin, relative_path.as_str()) {
+            (_, "/") => origin.to_string(),
+            ("/", _) => relative_path.clone(),
+            _ => format!("{origin}{relative_path}"),
+        };
+        Ok(ResolvedPath {
+            provider: entry.provider.clone(),
+            relative_path,
+            origin_path,
+            mount: entry.mount_point.clone(),
+        })
+    }
 
+    /// Whether `path` falls under a mount marked read-only. Paths outside
+    /// any mount are not read-only; resolving them fails elsewhere.
+    pub async fn is_read_only(&self, path: &str) -> bool {
+        let path = Self::normalize_mount_path(path);
+        let mounts = self.mounts.read().await;
+        Self::lookup(&mounts, path).is_ok_and(|(entry, _)| entry.mount_point.read_only)
+    }
+
+    /// The mount owning `path` and the path relative to it.
+    fn lookup(
+        mounts: &BTreeMap<String, MountEntry>,
+        path: String,
+    ) -> FsResult<(&MountEntry, String)> {
         // O(log n) resolution using BTreeMap ordering.
         // Iterate keys <= path in reverse to find longest prefix match first.
         for (mount_path, entry) in mounts.range(..=path.clone()).rev() {
             if path == *mount_path {
-                return Ok((entry.provider.clone(), "/".to_string()));
+                return Ok((entry, "/".to_string()));
             }
             if mount_path == "/" {
-                return Ok((entry.provider.clone(), path));
+                return Ok((entry, path));
             }
             if path.starts_with(mount_path) && path.as_bytes().get(mount_path.len()) == Some(&b'/')
             {
                 let relative_path = path[mount_path.len()..].to_string();
-                return Ok((entry.provider.clone(), relative_path));
+                return Ok((entry, relative_path));
             }
         }
 
         if let Some(entry) = mounts.get("/") {
-            return Ok((entry.provider.clone(), path));
+            return Ok((entry, path));
         }
 
         Err(FsError::not_found(&path))
@@ -132,14 +441,38 @@ impl MountTable {
         let path = Self::normalize_mount_path(path);
         let mounts = self.mounts.read().await;
 
-        mounts
-            .get(&path)
-            .map(|e| (e.mount_point.clone(), e.provider.capabilities()))
+        mounts.get(&path).map(|e| {
+            let caps = e.provider.capabilities() - e.mount_point.masked_capabilities;
+            (e.mount_point.clone(), caps)
+        })
     }
 
     pub async fn count(&self) -> usize {
         self.mounts.read().await.len()
     }
+
+    /// Call [`FsProvider::flush`] on every mounted provider, once each: bind
+    /// mounts share their source's provider and are skipped. Every provider
+    /// is flushed even if an earlier one fails; the failures are returned
+    /// with the mount path they belong to.
+    pub async fn flush_all(&self) -> Vec<(String, FsError)> {
+        let providers: Vec<(String, Arc<dyn FsProvider>)> = self
+            .mounts
+            .read()
+            .await
+            .values()
+            .filter(|e| e.mount_point.bind_source.is_none())
+            .map(|e| (e.mount_point.path.clone(), e.provider.clone()))
+            .collect();
+
+        let mut failures = Vec::new();
+        for (path, provider) in providers {
+            if let Err(e) = provider.flush().await {
+                failures.push((path, e));
+            }
+        }
+        failures
+    }
 }
 
 #[cfg(test)]
@@ -199,6 +532,36 @@ mod tests {
         assert_eq!(table.count().await, 0);
     }
 
+    #[tokio::test]
+    async fn replace_swaps_provider_and_its_binds() {
+        let table = MountTable::new();
+        let old: Arc<dyn FsProvider> = Arc::new(MemoryFs::new());
+        let new: Arc<dyn FsProvider> = Arc::new(MemoryFs::new());
+
+        table.mount("/data", "data", old.clone()).await.unwrap();
+        table.set_read_only("/data", true).await.unwrap();
+        table.bind("/data", "/alias").await.unwrap();
+
+        let replaced = table.replace("/data", "fresh", new.clone()).await.unwrap();
+        assert!(Arc::ptr_eq(&replaced, &old));
+        for path in ["/data/f", "/alias/f"] {
+            let (provider, _) = table.resolve(path).await.unwrap();
+            assert!(Arc::ptr_eq(&provider, &new));
+        }
+        let (mount, _) = table.get_mount_info("/data").await.unwrap();
+        assert_eq!(mount.provider_name, "fresh");
+        assert!(mount.read_only);
+
+        assert!(matches!(
+            table.replace("/alias", "fresh", new.clone()).await,
+            Err(FsError::InvalidArgument(_))
+        ));
+        assert!(matches!(
+            table.replace("/missing", "fresh", new).await,
+            Err(FsError::NotFound(_))
+        ));
+    }
+
     #[tokio::test]
     async fn cannot_mount_duplicate() {
         let table = MountTable::new();
@@ -232,6 +595,148 @@ mod tests {
         assert_eq!(mounts.len(), 3);
     }
 
+    #[tokio::test]
+    async fn mount_overlay_reads_through_to_lower() {
+        let table = MountTable::new();
+        let lower = Arc::new(MemoryFs::new());
+        let (handle, _) = lower
+            .open("/base.txt", fs9_sdk::OpenFlags::create_file())
+            .await
+            .unwrap();
+        lower.close(handle, false).await.unwrap();
+
+        table
+            .mount_overlay("/", Arc::new(MemoryFs::new()), vec![lower])
+            .await
+            .unwrap();
+
+        let (provider, relative) = table.resolve("/base.txt").await.unwrap();
+        assert!(provider.stat(&relative).await.is_ok());
+        assert_eq!(table.list_mounts().await[0].provider_name, "overlay");
+    }
+
+    #[tokio::test]
+    async fn bind_shares_provider_under_alias() {
+        let table = MountTable::new();
+        let fs = Arc::new(MemoryFs::new());
+        table.mount("/a", "mem", fs.clone()).await.unwrap();
+
+        table.bind("/a", "/b").await.unwrap();
+        table.bind("/b", "/legacy/b").await.unwrap();
+
+        let (provider, relative) = table.resolve("/b/x").await.unwrap();
+        assert_eq!(relative, "/x");
+        assert!(Arc::ptr_eq(&provider, &(fs as Arc<dyn FsProvider>)));
+
+        let resolved = table.resolve_mount("/legacy/b/x").await.unwrap();
+        assert_eq!(resolved.origin_path, "/a/x");
+        assert_eq!(resolved.mount.path, "/legacy/b");
+        let resolved = table.resolve_mount("/b").await.unwrap();
+        assert_eq!(resolved.origin_path, "/a");
+
+        let mounts = table.list_mounts().await;
+        let legacy = mounts.iter().find(|m| m.path == "/legacy/b").unwrap();
+        assert_eq!(legacy.bind_source.as_deref(), Some("/a"));
+        assert_eq!(legacy.provider_name, "mem");
+    }
+
+    #[tokio::test]
+    async fn bind_requires_existing_source_and_free_alias() {
+        let table = MountTable::new();
+        table
+            .mount("/a", "a", Arc::new(MemoryFs::new()))
+            .await
+            .unwrap();
+        table
+            .mount("/c", "c", Arc::new(MemoryFs::new()))
+            .await
+            .unwrap();
+
+        assert!(matches!(
+            table.bind("/missing", "/b").await,
+            Err(FsError::NotFound(_))
+        ));
+        assert!(matches!(
+            table.bind("/a", "/c").await,
+            Err(FsError::AlreadyExists(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn read_only_flag_follows_longest_prefix() {
+        let table = MountTable::new();
+        table
+            .mount("/", "root", Arc::new(MemoryFs::new()))
+            .await
+            .unwrap();
+        table
+            .mount("/ro", "ro", Arc::new(MemoryFs::new()))
+            .await
+            .unwrap();
+        table.set_read_only("/ro", true).await.unwrap();
+        table.bind("/ro", "/ro-alias").await.unwrap();
+
+        assert!(table.is_read_only("/ro/file").await);
+        assert!(table.is_read_only("/ro-alias/file").await);
+        assert!(!table.is_read_only("/rw/file").await);
+        assert!(matches!(
+            table.set_read_only("/missing", true).await,
+            Err(FsError::NotFound(_))
+        ));
+
+        table.set_read_only("/ro-alias", false).await.unwrap();
+        assert!(!table.is_read_only("/ro-alias/file").await);
+        assert!(table.is_read_only("/ro/file").await);
+    }
+
+    #[tokio::test]
+    async fn missing_required_capability_refuses_the_mount() {
+        let table = MountTable::new();
+        table
+            .mount("/data", "mem", Arc::new(MemoryFs::new()))
+            .await
+            .unwrap();
+
+        table
+            .require_capabilities("/data", Capabilities::RENAME | Capabilities::TRUNCATE)
+            .await
+            .unwrap();
+        let err = table
+            .require_capabilities("/data", Capabilities::RENAME | Capabilities::XATTR)
+            .await
+            .unwrap_err();
+        assert!(matches!(&err, FsError::NotImplemented(msg) if msg.contains("xattr")));
+        assert!(matches!(
+            table
+                .require_capabilities("/missing", Capabilities::READ)
+                .await,
+            Err(FsError::NotFound(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn masked_capabilities_are_not_advertised() {
+        let table = MountTable::new();
+        table
+            .mount("/data", "mem", Arc::new(MemoryFs::new()))
+            .await
+            .unwrap();
+        table
+            .mask_capabilities("/data", Capabilities::RENAME)
+            .await
+            .unwrap();
+        table.bind("/data", "/alias").await.unwrap();
+
+        for path in ["/data", "/alias"] {
+            let (mount, caps) = table.get_mount_info(path).await.unwrap();
+            assert!(!caps.supports_rename());
+            assert!(caps.supports_write());
+            assert_eq!(mount.masked_capabilities, Capabilities::RENAME);
+        }
+        let resolved = table.resolve_mount("/data/file").await.unwrap();
+        assert!(!resolved.capabilities().supports_rename());
+    }
+
     #[tokio::test]
     async fn resolve_without_root_mount() {
         let table = MountTable::new();
@@ -273,4 +778,19 @@ mod tests {
         let (_, relative) = table.resolve("/a/file.txt").await.unwrap();
         assert_eq!(relative, "/file.txt");
     }
+
+    #[tokio::test]
+    async fn snapshot_needs_provider_support() {
+        let table = MountTable::new();
+        table
+            .mount("/data", "memfs", Arc::new(MemoryFs::new()))
+            .await
+            .unwrap();
+
+        let result = table.snapshot("/data", "/data-snap").await;
+        assert!(matches!(result, Err(FsError::NotImplemented(_))));
+        let result = table.mount_snapshot("/data", 1, "/data-snap").await;
+        assert!(matches!(result, Err(FsError::NotImplemented(_))));
+        assert!(table.resolve("/data-snap/file").await.is_err());
+    }
 }
@@ -3,7 +3,7 @@
 //! This module provides the ability to load filesystem providers from dynamic
 //! libraries (.so on Linux, .dylib on macOS, .dll on Windows).
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::ffi::CString;
 use std::path::Path;
 use std::ptr;
@@ -15,18 +15,21 @@ use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use async_trait::async_trait;
 use bytes::Bytes;
 use fs9_sdk::{
-    Capabilities, FileInfo, FileType, FsError, FsResult, FsStats, Handle, OpenFlags, StatChanges,
+    Capabilities, DirPage, FileInfo, FileType, FsError, FsResult, FsStats, Handle, OpenFlags,
+    StatChanges,
 };
 use fs9_sdk_ffi::{
-    CBytes, CFileInfo, CFsStats, COpenFlags, CResult, CStatChanges, PluginVTable,
+    CBytes, CFileInfo, CFsStats, COpenFlags, CResult, CStatChanges, LegacyFsyncFn, PluginVTable,
     FILE_TYPE_DIRECTORY, FILE_TYPE_REGULAR, FILE_TYPE_SYMLINK, FS9_ERR_ALREADY_EXISTS,
-    FS9_ERR_DIRECTORY_NOT_EMPTY, FS9_ERR_INTERNAL, FS9_ERR_INVALID_ARGUMENT,
-    FS9_ERR_INVALID_HANDLE, FS9_ERR_IS_DIRECTORY, FS9_ERR_NOT_DIRECTORY, FS9_ERR_NOT_FOUND,
-    FS9_ERR_NOT_IMPLEMENTED, FS9_ERR_PERMISSION_DENIED, FS9_OK, FS9_SDK_VERSION,
+    FS9_ERR_BACKEND_UNAVAILABLE, FS9_ERR_DIRECTORY_NOT_EMPTY, FS9_ERR_END_OF_STREAM,
+    FS9_ERR_INTERNAL, FS9_ERR_INVALID_ARGUMENT, FS9_ERR_INVALID_HANDLE, FS9_ERR_IS_DIRECTORY,
+    FS9_ERR_NOT_DIRECTORY, FS9_ERR_NOT_FOUND, FS9_ERR_NOT_IMPLEMENTED, FS9_ERR_NO_SPACE,
+    FS9_ERR_PERMISSION_DENIED, FS9_MIN_SDK_VERSION, FS9_OK, FS9_SDK_VERSION,
 };
-use libc::c_void;
+use libc::{c_char, c_void, size_t};
 use libloading::{Library, Symbol};
-use tracing::debug;
+use sha2::{Digest, Sha256};
+use tracing::{debug, warn};
 
 use fs9_sdk::FsProvider;
 
@@ -49,17 +52,239 @@ pub enum PluginError {
 
     #[error("plugin already loaded: {0}")]
     AlreadyLoaded(String),
+
+    #[error("plugin {path} is not allowed: its sha256 {sha256} is not in the allowlist")]
+    NotAllowed { path: String, sha256: String },
+}
+
+/// The vtable operations a loaded plugin provides beyond the ones every
+/// supported SDK version has.
+///
+/// Plugins built against an older SDK lack the slots added since; calls to
+/// those fail with `NotImplemented`, except `rename`, which falls back to a
+/// `wstat` as it did before the slot existed, `flush`, which does nothing,
+/// and `health_check`, which probes the root like the trait default.
+/// Snapshots are only available from plugins with the `snapshot` slots.
+/// Exclusive opens on plugins that predate `COpenFlags::excl` are
+/// emulated with a `stat` first, which is not atomic. Etags are only read
+/// from plugins whose `CFileInfo` has them. Plugins whose `fsync` predates
+/// its `data_only` flag are called without it and always sync in full.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[allow(clippy::struct_excessive_bools)]
+pub struct PluginOps {
+    pub fsync: bool,
+    pub fsync_data_only: bool,
+    pub link: bool,
+    pub rename: bool,
+    pub readdir_at: bool,
+    pub flush: bool,
+    pub xattr: bool,
+    pub open_excl: bool,
+    pub health_check: bool,
+    pub snapshot: bool,
+    pub etag: bool,
+}
+
+impl PluginOps {
+    #[must_use]
+    pub const fn for_sdk_version(sdk_version: u32) -> Self {
+        Self {
+            fsync: sdk_version >= fs9_sdk_ffi::FSYNC_SINCE,
+            fsync_data_only: sdk_version >= fs9_sdk_ffi::FSYNC_DATA_ONLY_SINCE,
+            link: sdk_version >= fs9_sdk_ffi::LINK_SINCE,
+            rename: sdk_version >= fs9_sdk_ffi::RENAME_SINCE,
+            readdir_at: sdk_version >= fs9_sdk_ffi::READDIR_AT_SINCE,
+            flush: sdk_version >= fs9_sdk_ffi::FLUSH_SINCE,
+            xattr: sdk_version >= fs9_sdk_ffi::XATTR_SINCE,
+            open_excl: sdk_version >= fs9_sdk_ffi::OPEN_EXCL_SINCE,
+            health_check: sdk_version >= fs9_sdk_ffi::HEALTH_CHECK_SINCE,
+            snapshot: sdk_version >= fs9_sdk_ffi::SNAPSHOT_SINCE,
+            etag: sdk_version >= fs9_sdk_ffi::ETAG_SINCE,
+        }
+    }
 }
 
 struct LoadedPlugin {
+    /// `None` for vtables registered without a library, as in tests.
     #[allow(dead_code)]
-    library: Library,
+    library: Option<Library>,
     vtable: PluginVTable,
+    ops: PluginOps,
     name: String,
 }
 
+fn unsupported_slot(op: &'static [u8]) -> CResult {
+    CResult::err(FS9_ERR_NOT_IMPLEMENTED, op.as_ptr().cast(), op.len())
+}
+
+unsafe extern "C" fn unsupported_fsync(_: *mut c_void, _: u64, _: u8) -> CResult {
+    unsupported_slot(b"fsync")
+}
+
+unsafe extern "C" fn unsupported_link(
+    _: *mut c_void,
+    _: *const c_char,
+    _: size_t,
+    _: *const c_char,
+    _: size_t,
+) -> CResult {
+    unsupported_slot(b"link")
+}
+
+unsafe extern "C" fn unsupported_rename(
+    _: *mut c_void,
+    _: *const c_char,
+    _: size_t,
+    _: *const c_char,
+    _: size_t,
+) -> CResult {
+    unsupported_slot(b"rename")
+}
+
+#[allow(clippy::too_many_arguments)]
+unsafe extern "C" fn unsupported_readdir_at(
+    _: *mut c_void,
+    _: *const c_char,
+    _: size_t,
+    _: *const c_char,
+    _: size_t,
+    _: size_t,
+    _: fs9_sdk_ffi::ReaddirCallback,
+    _: *mut c_void,
+    _: *mut CBytes,
+) -> CResult {
+    unsupported_slot(b"readdir_at")
+}
+
+unsafe extern "C" fn unsupported_flush(_: *mut c_void) -> CResult {
+    unsupported_slot(b"flush")
+}
+
+unsafe extern "C" fn unsupported_getxattr(
+    _: *mut c_void,
+    _: *const c_char,
+    _: size_t,
+    _: *const c_char,
+    _: size_t,
+    _: *mut CBytes,
+) -> CResult {
+    unsupported_slot(b"getxattr")
+}
+
+unsafe extern "C" fn unsupported_setxattr(
+    _: *mut c_void,
+    _: *const c_char,
+    _: size_t,
+    _: *const c_char,
+    _: size_t,
+    _: *const u8,
+    _: size_t,
+) -> CResult {
+    unsupported_slot(b"setxattr")
+}
+
+unsafe extern "C" fn unsupported_listxattr(
+    _: *mut c_void,
+    _: *const c_char,
+    _: size_t,
+    _: *mut CBytes,
+) -> CResult {
+    unsupported_slot(b"listxattr")
+}
+
+unsafe extern "C" fn unsupported_removexattr(
+    _: *mut c_void,
+    _: *const c_char,
+    _: size_t,
+    _: *const c_char,
+    _: size_t,
+) -> CResult {
+    unsupported_slot(b"removexattr")
+}
+
+unsafe extern "C" fn unsupported_health_check(_: *mut c_void) -> CResult {
+    unsupported_slot(b"health_check")
+}
+
+unsafe extern "C" fn unsupported_snapshot(
+    _: *mut c_void,
+    _: *const c_char,
+    _: size_t,
+    _: *mut u64,
+) -> CResult {
+    unsupported_slot(b"snapshot")
+}
+
+unsafe extern "C" fn unsupported_open_snapshot(
+    _: *mut c_void,
+    _: u64,
+    _: *mut *mut c_void,
+) -> CResult {
+    unsupported_slot(b"snapshot")
+}
+
+/// Copy a plugin's vtable, reading only the slots its SDK version has and
+/// filling the newer ones with stubs that fail with `NotImplemented`.
+///
+/// # Safety
+/// `vtable_ptr` must point to a vtable at least as long as its
+/// `sdk_version` implies.
+unsafe fn read_vtable(
+    vtable_ptr: *const PluginVTable,
+) -> Result<(PluginVTable, PluginOps), PluginError> {
+    // `sdk_version` leads every version of the vtable.
+    let sdk_version = ptr::read(vtable_ptr.cast::<u32>());
+    if !(FS9_MIN_SDK_VERSION..=FS9_SDK_VERSION).contains(&sdk_version) {
+        return Err(PluginError::VersionMismatch {
+            plugin: sdk_version,
+            sdk: FS9_SDK_VERSION,
+        });
+    }
+
+    let mut vtable = std::mem::MaybeUninit::<PluginVTable>::uninit();
+    let out = vtable.as_mut_ptr();
+    ptr::copy_nonoverlapping(
+        vtable_ptr.cast::<u8>(),
+        out.cast::<u8>(),
+        fs9_sdk_ffi::vtable_len(sdk_version),
+    );
+    let ops = PluginOps::for_sdk_version(sdk_version);
+    if !ops.fsync {
+        ptr::addr_of_mut!((*out).fsync).write(unsupported_fsync);
+    }
+    if !ops.link {
+        ptr::addr_of_mut!((*out).link).write(unsupported_link);
+    }
+    if !ops.rename {
+        ptr::addr_of_mut!((*out).rename).write(unsupported_rename);
+    }
+    if !ops.readdir_at {
+        ptr::addr_of_mut!((*out).readdir_at).write(unsupported_readdir_at);
+    }
+    if !ops.flush {
+        ptr::addr_of_mut!((*out).flush).write(unsupported_flush);
+    }
+    if !ops.xattr {
+        ptr::addr_of_mut!((*out).getxattr).write(unsupported_getxattr);
+        ptr::addr_of_mut!((*out).setxattr).write(unsupported_setxattr);
+        ptr::addr_of_mut!((*out).listxattr).write(unsupported_listxattr);
+        ptr::addr_of_mut!((*out).removexattr).write(unsupported_removexattr);
+    }
+    if !ops.health_check {
+        ptr::addr_of_mut!((*out).health_check).write(unsupported_health_check);
+    }
+    if !ops.snapshot {
+        ptr::addr_of_mut!((*out).snapshot).write(unsupported_snapshot);
+        ptr::addr_of_mut!((*out).open_snapshot).write(unsupported_open_snapshot);
+    }
+    Ok((vtable.assume_init(), ops))
+}
+
 pub struct PluginManager {
     plugins: Mutex<HashMap<String, Arc<LoadedPlugin>>>,
+    /// Lowercase hex SHA-256 digests of the plugin files that may be
+    /// loaded, or `None` to load any file.
+    allowed_sha256: Mutex<Option<HashSet<String>>>,
 }
 
 impl PluginManager {
@@ -67,7 +292,54 @@ impl PluginManager {
     pub fn new() -> Self {
         Self {
             plugins: Mutex::new(HashMap::new()),
+            allowed_sha256: Mutex::new(None),
+        }
+    }
+
+    /// Only load plugin files whose SHA-256 digest is one of `digests`,
+    /// given in hex. The file is hashed just before it is opened, so this
+    /// guards against a swapped or tampered binary, not against one
+    /// replaced in between by someone who can already write to it.
+    pub fn set_allowed_sha256<I, S>(&self, digests: I)
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let digests = digests
+            .into_iter()
+            .map(|d| d.as_ref().trim().to_ascii_lowercase())
+            .collect();
+        *self.allowed_sha256.lock().unwrap() = Some(digests);
+    }
+
+    /// Refuse `library_path` unless its digest is allowed, when an
+    /// allowlist is set.
+    fn verify_digest(&self, library_path: &Path) -> Result<(), PluginError> {
+        if self.allowed_sha256.lock().unwrap().is_none() {
+            return Ok(());
+        }
+
+        let contents =
+            std::fs::read(library_path).map_err(|e| PluginError::LoadError(e.to_string()))?;
+        let sha256: String = Sha256::digest(&contents)
+            .iter()
+            .map(|b| format!("{b:02x}"))
+            .collect();
+        let allowed = self
+            .allowed_sha256
+            .lock()
+            .unwrap()
+            .as_ref()
+            .is_some_and(|allowed| allowed.contains(&sha256));
+        if allowed {
+            return Ok(());
         }
+
+        warn!(path = ?library_path, %sha256, "Refusing plugin not in the sha256 allowlist");
+        Err(PluginError::NotAllowed {
+            path: library_path.display().to_string(),
+            sha256,
+        })
     }
 
     /// Load a plugin from a dynamic library path.
@@ -99,6 +371,7 @@ impl PluginManager {
     ) -> Result<String, PluginError> {
         debug!(path = ?library_path, "Loading plugin");
 
+        self.verify_digest(library_path)?;
         let library = unsafe { Library::new(library_path) }
             .map_err(|e| PluginError::LoadError(e.to_string()))?;
 
@@ -107,7 +380,7 @@ impl PluginManager {
                 .map_err(|_| PluginError::SymbolNotFound("fs9_plugin_version".to_string()))?;
 
         let plugin_version = unsafe { get_version() };
-        if plugin_version != FS9_SDK_VERSION {
+        if !(FS9_MIN_SDK_VERSION..=FS9_SDK_VERSION).contains(&plugin_version) {
             return Err(PluginError::VersionMismatch {
                 plugin: plugin_version,
                 sdk: FS9_SDK_VERSION,
@@ -125,14 +398,7 @@ impl PluginManager {
             ));
         }
 
-        let vtable = unsafe { ptr::read(vtable_ptr) };
-
-        if vtable.sdk_version != FS9_SDK_VERSION {
-            return Err(PluginError::VersionMismatch {
-                plugin: vtable.sdk_version,
-                sdk: FS9_SDK_VERSION,
-            });
-        }
+        let (vtable, ops) = unsafe { read_vtable(vtable_ptr) }?;
 
         let name = if let Some(n) = name_override {
             n.to_string()
@@ -172,17 +438,18 @@ impl PluginManager {
         }
 
         let loaded = Arc::new(LoadedPlugin {
-            library,
+            library: Some(library),
             vtable,
+            ops,
             name: name.clone(),
         });
 
         plugins.insert(name.clone(), loaded);
 
         if let Some(ver) = version_str {
-            debug!(name = %name, version = %ver, "Plugin loaded successfully");
+            debug!(name = %name, version = %ver, sdk_version = vtable.sdk_version, ?ops, "Plugin loaded successfully");
         } else {
-            debug!(name = %name, "Plugin loaded successfully");
+            debug!(name = %name, sdk_version = vtable.sdk_version, ?ops, "Plugin loaded successfully");
         }
 
         Ok(name)
@@ -236,6 +503,12 @@ impl PluginManager {
         self.plugins.lock().unwrap().contains_key(name)
     }
 
+    /// The optional operations the loaded plugin `name` provides.
+    #[must_use]
+    pub fn supported_ops(&self, name: &str) -> Option<PluginOps> {
+        self.plugins.lock().unwrap().get(name).map(|p| p.ops)
+    }
+
     #[must_use]
     pub fn loaded_plugins(&self) -> Vec<String> {
         self.plugins.lock().unwrap().keys().cloned().collect()
@@ -339,6 +612,45 @@ impl SendablePtr {
 
 unsafe impl Send for SendablePtr {}
 
+/// Data read from a plugin, passed on without copying. Holds on to the
+/// plugin so that its code, which may have to release the buffer, stays
+/// loaded until then.
+struct PluginBuffer {
+    bytes: CBytes,
+    _plugin: Arc<LoadedPlugin>,
+}
+
+// Safety: the buffer is never written through and is freed exactly once,
+// on drop, from whichever thread drops it.
+unsafe impl Send for PluginBuffer {}
+
+impl AsRef<[u8]> for PluginBuffer {
+    fn as_ref(&self) -> &[u8] {
+        unsafe { slice::from_raw_parts(self.bytes.data, self.bytes.len) }
+    }
+}
+
+impl Drop for PluginBuffer {
+    fn drop(&mut self) {
+        unsafe { fs9_sdk_ffi::fs9_bytes_free(&mut self.bytes) };
+    }
+}
+
+/// Copy a plugin-allocated buffer out and release it.
+///
+/// # Safety
+/// `bytes` must be empty or have been filled by `vec_to_cbytes` or
+/// `bytes_to_cbytes`.
+unsafe fn take_cbytes(bytes: &mut CBytes) -> Vec<u8> {
+    let data = if bytes.data.is_null() {
+        Vec::new()
+    } else {
+        slice::from_raw_parts(bytes.data, bytes.len).to_vec()
+    };
+    fs9_sdk_ffi::fs9_bytes_free(bytes);
+    data
+}
+
 fn cresult_to_fserror(result: CResult) -> FsError {
     let msg = if !result.error_msg.is_null() && result.error_msg_len > 0 {
         unsafe {
@@ -357,8 +669,11 @@ fn cresult_to_fserror(result: CResult) -> FsError {
         FS9_ERR_NOT_DIRECTORY => FsError::not_directory(msg),
         FS9_ERR_IS_DIRECTORY => FsError::is_directory(msg),
         FS9_ERR_DIRECTORY_NOT_EMPTY => FsError::directory_not_empty(msg),
-        FS9_ERR_INVALID_HANDLE => FsError::invalid_handle(0),
+        FS9_ERR_INVALID_HANDLE => FsError::invalid_handle(msg.parse().unwrap_or(0)),
         FS9_ERR_NOT_IMPLEMENTED => FsError::not_implemented(msg),
+        FS9_ERR_BACKEND_UNAVAILABLE => FsError::backend_unavailable(msg),
+        FS9_ERR_NO_SPACE => FsError::no_space(msg),
+        FS9_ERR_END_OF_STREAM => FsError::end_of_stream(msg),
         FS9_ERR_INTERNAL | _ => FsError::internal(if msg.is_empty() {
             format!("plugin error code: {}", result.code)
         } else {
@@ -367,7 +682,9 @@ fn cresult_to_fserror(result: CResult) -> FsError {
     }
 }
 
-fn cfileinfo_to_fileinfo(info: &CFileInfo) -> FileInfo {
+/// Convert a `CFileInfo`, taking its etag only if `etag` says the plugin
+/// built it with one.
+fn cfileinfo_to_fileinfo(info: &CFileInfo, etag: bool) -> FileInfo {
     let path = if !info.path.is_null() && info.path_len > 0 {
         unsafe {
             let bytes = slice::from_raw_parts(info.path as *const u8, info.path_len);
@@ -397,11 +714,34 @@ fn cfileinfo_to_fileinfo(info: &CFileInfo) -> FileInfo {
         atime,
         mtime,
         ctime,
-        etag: String::new(),
+        etag: if etag { info.etag() } else { String::new() },
         symlink_target: None,
     }
 }
 
+/// Where `collect_entry` puts the entries of a readdir.
+struct EntrySink {
+    entries: Mutex<Vec<FileInfo>>,
+    /// Whether the plugin's `CFileInfo` has an etag to read.
+    etag: bool,
+}
+
+/// `ReaddirCallback` that appends each entry to the `EntrySink` passed as
+/// `user_data`.
+unsafe extern "C" fn collect_entry(info: *const CFileInfo, user_data: *mut c_void) -> i32 {
+    if info.is_null() || user_data.is_null() {
+        return -1;
+    }
+    let sink = &*(user_data as *const EntrySink);
+    let file_info = cfileinfo_to_fileinfo(&*info, sink.etag);
+    if let Ok(mut guard) = sink.entries.lock() {
+        guard.push(file_info);
+        0
+    } else {
+        -1
+    }
+}
+
 fn timestamp_to_systemtime(timestamp: i64) -> SystemTime {
     if timestamp >= 0 {
         UNIX_EPOCH + Duration::from_secs(timestamp as u64)
@@ -417,7 +757,7 @@ fn systemtime_to_timestamp(time: SystemTime) -> i64 {
     }
 }
 
-fn openflags_to_copenflags(flags: &OpenFlags) -> COpenFlags {
+fn openflags_to_copenflags(flags: OpenFlags) -> COpenFlags {
     COpenFlags {
         read: u8::from(flags.read),
         write: u8::from(flags.write),
@@ -425,6 +765,8 @@ fn openflags_to_copenflags(flags: &OpenFlags) -> COpenFlags {
         truncate: u8::from(flags.truncate),
         append: u8::from(flags.append),
         directory: u8::from(flags.directory),
+        symlink: u8::from(flags.symlink),
+        excl: u8::from(flags.exclusive),
     }
 }
 
@@ -459,6 +801,8 @@ fn statchanges_to_cstatchanges(
         has_symlink_target: u8::from(symlink_cstr.is_some()),
         symlink_target: symlink_cstr.as_ref().map_or(ptr::null(), |s| s.as_ptr()),
         symlink_target_len: symlink_cstr.as_ref().map_or(0, |s| s.as_bytes().len()),
+        has_ttl: u8::from(changes.ttl.is_some()),
+        ttl_secs: changes.ttl.map_or(0, |ttl| ttl.as_secs()),
     };
 
     (c_changes, name_cstr, symlink_cstr)
@@ -482,6 +826,7 @@ impl FsProvider for PluginProvider {
         let path_len = path.len();
         let provider = SendablePtr::new(self.provider);
         let vtable = self.plugin.vtable;
+        let etag = self.plugin.ops.etag;
 
         tokio::task::spawn_blocking(move || {
             let mut out_info = CFileInfo::default();
@@ -494,7 +839,7 @@ impl FsProvider for PluginProvider {
                 )
             };
             if result.code == FS9_OK {
-                Ok(cfileinfo_to_fileinfo(&out_info))
+                Ok(cfileinfo_to_fileinfo(&out_info, etag))
             } else {
                 Err(cresult_to_fserror(result))
             }
@@ -551,11 +896,19 @@ impl FsProvider for PluginProvider {
     }
 
     async fn open(&self, path: &str, flags: OpenFlags) -> FsResult<(Handle, FileInfo)> {
+        if flags.create && flags.exclusive && !self.plugin.ops.open_excl {
+            match self.stat(path).await {
+                Ok(_) => return Err(FsError::already_exists(path)),
+                Err(FsError::NotFound(_)) => {}
+                Err(e) => return Err(e),
+            }
+        }
         let path_cstr = CString::new(path).map_err(|e| FsError::invalid_argument(e.to_string()))?;
         let path_len = path.len();
-        let c_flags = openflags_to_copenflags(&flags);
+        let c_flags = openflags_to_copenflags(flags);
         let provider = SendablePtr::new(self.provider);
         let vtable = self.plugin.vtable;
+        let etag = self.plugin.ops.etag;
 
         tokio::task::spawn_blocking(move || {
             let mut out_handle: u64 = 0;
@@ -571,7 +924,10 @@ impl FsProvider for PluginProvider {
                 )
             };
             if result.code == FS9_OK {
-                Ok((Handle::new(out_handle), cfileinfo_to_fileinfo(&out_info)))
+                Ok((
+                    Handle::new(out_handle),
+                    cfileinfo_to_fileinfo(&out_info, etag),
+                ))
             } else {
                 Err(cresult_to_fserror(result))
             }
@@ -583,23 +939,23 @@ impl FsProvider for PluginProvider {
     async fn read(&self, handle: &Handle, offset: u64, size: usize) -> FsResult<Bytes> {
         let handle_id = handle.id();
         let provider = SendablePtr::new(self.provider);
-        let vtable = self.plugin.vtable;
+        let plugin = Arc::clone(&self.plugin);
 
         tokio::task::spawn_blocking(move || {
             let mut out_data = CBytes::default();
-            let result =
-                unsafe { (vtable.read)(provider.as_ptr(), handle_id, offset, size, &mut out_data) };
+            let result = unsafe {
+                (plugin.vtable.read)(provider.as_ptr(), handle_id, offset, size, &mut out_data)
+            };
 
             if result.code == FS9_OK {
                 if out_data.data.is_null() || out_data.len == 0 {
+                    unsafe { fs9_sdk_ffi::fs9_bytes_free(&mut out_data) };
                     return Ok(Bytes::new());
                 }
-                let bytes = unsafe {
-                    let data = slice::from_raw_parts(out_data.data, out_data.len);
-                    Bytes::copy_from_slice(data)
-                };
-                unsafe { fs9_sdk_ffi::fs9_bytes_free(&mut out_data) };
-                Ok(bytes)
+                Ok(Bytes::from_owner(PluginBuffer {
+                    bytes: out_data,
+                    _plugin: plugin,
+                }))
             } else {
                 Err(cresult_to_fserror(result))
             }
@@ -653,32 +1009,258 @@ impl FsProvider for PluginProvider {
         .map_err(|e| FsError::internal(e.to_string()))?
     }
 
-    async fn readdir(&self, path: &str) -> FsResult<Vec<FileInfo>> {
+    async fn fsync(&self, handle: &Handle, data_only: bool) -> FsResult<()> {
+        let handle_id = handle.id();
+        let data_only_flag = u8::from(data_only);
+        let legacy = self.plugin.ops.fsync && !self.plugin.ops.fsync_data_only;
+        let provider = SendablePtr::new(self.provider);
+        let vtable = self.plugin.vtable;
+
+        tokio::task::spawn_blocking(move || {
+            let result = if legacy {
+                // SAFETY: plugins older than FSYNC_DATA_ONLY_SINCE put a
+                // LegacyFsyncFn in the slot.
+                let fsync = unsafe {
+                    std::mem::transmute::<fs9_sdk_ffi::FsyncFn, LegacyFsyncFn>(vtable.fsync)
+                };
+                unsafe { fsync(provider.as_ptr(), handle_id) }
+            } else {
+                unsafe { (vtable.fsync)(provider.as_ptr(), handle_id, data_only_flag) }
+            };
+            if result.code == FS9_OK {
+                Ok(())
+            } else {
+                Err(cresult_to_fserror(result))
+            }
+        })
+        .await
+        .map_err(|e| FsError::internal(e.to_string()))?
+    }
+
+    async fn flush(&self) -> FsResult<()> {
+        if !self.plugin.ops.flush {
+            return Ok(());
+        }
+        let provider = SendablePtr::new(self.provider);
+        let vtable = self.plugin.vtable;
+
+        tokio::task::spawn_blocking(move || {
+            let result = unsafe { (vtable.flush)(provider.as_ptr()) };
+            if result.code == FS9_OK {
+                Ok(())
+            } else {
+                Err(cresult_to_fserror(result))
+            }
+        })
+        .await
+        .map_err(|e| FsError::internal(e.to_string()))?
+    }
+
+    async fn health_check(&self) -> FsResult<()> {
+        if !self.plugin.ops.health_check {
+            self.stat("/").await?;
+            return match self.statfs("/").await {
+                Ok(_) | Err(FsError::NotImplemented(_)) => Ok(()),
+                Err(e) => Err(e),
+            };
+        }
+        let provider = SendablePtr::new(self.provider);
+        let vtable = self.plugin.vtable;
+
+        tokio::task::spawn_blocking(move || {
+            let result = unsafe { (vtable.health_check)(provider.as_ptr()) };
+            if result.code == FS9_OK {
+                Ok(())
+            } else {
+                Err(cresult_to_fserror(result))
+            }
+        })
+        .await
+        .map_err(|e| FsError::internal(e.to_string()))?
+    }
+
+    async fn snapshot(&self, path: &str) -> FsResult<u64> {
         let path_cstr = CString::new(path).map_err(|e| FsError::invalid_argument(e.to_string()))?;
         let path_len = path.len();
         let provider = SendablePtr::new(self.provider);
         let vtable = self.plugin.vtable;
 
         tokio::task::spawn_blocking(move || {
-            let entries: Arc<Mutex<Vec<FileInfo>>> = Arc::new(Mutex::new(Vec::new()));
-            let entries_ptr = Arc::into_raw(entries.clone()) as *mut c_void;
-
-            unsafe extern "C" fn collect_entry(
-                info: *const CFileInfo,
-                user_data: *mut c_void,
-            ) -> i32 {
-                if info.is_null() || user_data.is_null() {
-                    return -1;
-                }
-                let entries = &*(user_data as *const Mutex<Vec<FileInfo>>);
-                let file_info = cfileinfo_to_fileinfo(&*info);
-                if let Ok(mut guard) = entries.lock() {
-                    guard.push(file_info);
-                    0
-                } else {
-                    -1
-                }
+            let mut snapshot_id = 0u64;
+            let result = unsafe {
+                (vtable.snapshot)(
+                    provider.as_ptr(),
+                    path_cstr.as_ptr(),
+                    path_len,
+                    &mut snapshot_id,
+                )
+            };
+            if result.code == FS9_OK {
+                Ok(snapshot_id)
+            } else {
+                Err(cresult_to_fserror(result))
+            }
+        })
+        .await
+        .map_err(|e| FsError::internal(e.to_string()))?
+    }
+
+    async fn snapshot_view(&self, snapshot_id: u64) -> FsResult<Arc<dyn FsProvider>> {
+        let provider = SendablePtr::new(self.provider);
+        let vtable = self.plugin.vtable;
+
+        let view = tokio::task::spawn_blocking(move || {
+            let mut view = ptr::null_mut();
+            let result =
+                unsafe { (vtable.open_snapshot)(provider.as_ptr(), snapshot_id, &mut view) };
+            if result.code != FS9_OK {
+                return Err(cresult_to_fserror(result));
+            }
+            if view.is_null() {
+                return Err(FsError::internal("plugin returned a null snapshot view"));
+            }
+            Ok(SendablePtr::new(view))
+        })
+        .await
+        .map_err(|e| FsError::internal(e.to_string()))??;
+
+        Ok(Arc::new(Self {
+            plugin: self.plugin.clone(),
+            provider: view.as_ptr(),
+        }))
+    }
+
+    async fn getxattr(&self, path: &str, name: &str) -> FsResult<Bytes> {
+        let path_cstr = CString::new(path).map_err(|e| FsError::invalid_argument(e.to_string()))?;
+        let path_len = path.len();
+        let name_cstr = CString::new(name).map_err(|e| FsError::invalid_argument(e.to_string()))?;
+        let name_len = name.len();
+        let provider = SendablePtr::new(self.provider);
+        let vtable = self.plugin.vtable;
+
+        tokio::task::spawn_blocking(move || {
+            let mut out_value = CBytes::default();
+            let result = unsafe {
+                (vtable.getxattr)(
+                    provider.as_ptr(),
+                    path_cstr.as_ptr(),
+                    path_len,
+                    name_cstr.as_ptr(),
+                    name_len,
+                    &mut out_value,
+                )
+            };
+            if result.code == FS9_OK {
+                Ok(Bytes::from(unsafe { take_cbytes(&mut out_value) }))
+            } else {
+                Err(cresult_to_fserror(result))
+            }
+        })
+        .await
+        .map_err(|e| FsError::internal(e.to_string()))?
+    }
+
+    async fn setxattr(&self, path: &str, name: &str, value: Bytes) -> FsResult<()> {
+        let path_cstr = CString::new(path).map_err(|e| FsError::invalid_argument(e.to_string()))?;
+        let path_len = path.len();
+        let name_cstr = CString::new(name).map_err(|e| FsError::invalid_argument(e.to_string()))?;
+        let name_len = name.len();
+        let provider = SendablePtr::new(self.provider);
+        let vtable = self.plugin.vtable;
+
+        tokio::task::spawn_blocking(move || {
+            let result = unsafe {
+                (vtable.setxattr)(
+                    provider.as_ptr(),
+                    path_cstr.as_ptr(),
+                    path_len,
+                    name_cstr.as_ptr(),
+                    name_len,
+                    value.as_ptr(),
+                    value.len(),
+                )
+            };
+            if result.code == FS9_OK {
+                Ok(())
+            } else {
+                Err(cresult_to_fserror(result))
+            }
+        })
+        .await
+        .map_err(|e| FsError::internal(e.to_string()))?
+    }
+
+    async fn listxattr(&self, path: &str) -> FsResult<Vec<String>> {
+        let path_cstr = CString::new(path).map_err(|e| FsError::invalid_argument(e.to_string()))?;
+        let path_len = path.len();
+        let provider = SendablePtr::new(self.provider);
+        let vtable = self.plugin.vtable;
+
+        tokio::task::spawn_blocking(move || {
+            let mut out_names = CBytes::default();
+            let result = unsafe {
+                (vtable.listxattr)(
+                    provider.as_ptr(),
+                    path_cstr.as_ptr(),
+                    path_len,
+                    &mut out_names,
+                )
+            };
+            if result.code != FS9_OK {
+                return Err(cresult_to_fserror(result));
+            }
+            let names = unsafe { take_cbytes(&mut out_names) };
+            Ok(names
+                .split(|&b| b == 0)
+                .filter(|name| !name.is_empty())
+                .map(|name| String::from_utf8_lossy(name).into_owned())
+                .collect())
+        })
+        .await
+        .map_err(|e| FsError::internal(e.to_string()))?
+    }
+
+    async fn removexattr(&self, path: &str, name: &str) -> FsResult<()> {
+        let path_cstr = CString::new(path).map_err(|e| FsError::invalid_argument(e.to_string()))?;
+        let path_len = path.len();
+        let name_cstr = CString::new(name).map_err(|e| FsError::invalid_argument(e.to_string()))?;
+        let name_len = name.len();
+        let provider = SendablePtr::new(self.provider);
+        let vtable = self.plugin.vtable;
+
+        tokio::task::spawn_blocking(move || {
+            let result = unsafe {
+                (vtable.removexattr)(
+                    provider.as_ptr(),
+                    path_cstr.as_ptr(),
+                    path_len,
+                    name_cstr.as_ptr(),
+                    name_len,
+                )
+            };
+            if result.code == FS9_OK {
+                Ok(())
+            } else {
+                Err(cresult_to_fserror(result))
             }
+        })
+        .await
+        .map_err(|e| FsError::internal(e.to_string()))?
+    }
+
+    async fn readdir(&self, path: &str) -> FsResult<Vec<FileInfo>> {
+        let path_cstr = CString::new(path).map_err(|e| FsError::invalid_argument(e.to_string()))?;
+        let path_len = path.len();
+        let provider = SendablePtr::new(self.provider);
+        let vtable = self.plugin.vtable;
+        let etag = self.plugin.ops.etag;
+
+        tokio::task::spawn_blocking(move || {
+            let sink = Arc::new(EntrySink {
+                entries: Mutex::new(Vec::new()),
+                etag,
+            });
+            let sink_ptr = Arc::into_raw(sink.clone()) as *mut c_void;
 
             let result = unsafe {
                 (vtable.readdir)(
@@ -686,14 +1268,14 @@ impl FsProvider for PluginProvider {
                     path_cstr.as_ptr(),
                     path_len,
                     collect_entry,
-                    entries_ptr,
+                    sink_ptr,
                 )
             };
 
-            let entries = unsafe { Arc::from_raw(entries_ptr as *const Mutex<Vec<FileInfo>>) };
+            let sink = unsafe { Arc::from_raw(sink_ptr as *const EntrySink) };
 
             if result.code == FS9_OK {
-                let guard = entries.lock().unwrap();
+                let guard = sink.entries.lock().unwrap();
                 Ok(guard.clone())
             } else {
                 Err(cresult_to_fserror(result))
@@ -703,6 +1285,69 @@ impl FsProvider for PluginProvider {
         .map_err(|e| FsError::internal(e.to_string()))?
     }
 
+    async fn readdir_at(&self, path: &str, cursor: Option<&str>, max: usize) -> FsResult<DirPage> {
+        let path_cstr = CString::new(path).map_err(|e| FsError::invalid_argument(e.to_string()))?;
+        let path_len = path.len();
+        let cursor_bytes = cursor.unwrap_or_default().as_bytes().to_vec();
+        let provider = SendablePtr::new(self.provider);
+        let vtable = self.plugin.vtable;
+        let etag = self.plugin.ops.etag;
+
+        let page = tokio::task::spawn_blocking(move || {
+            let sink = Arc::new(EntrySink {
+                entries: Mutex::new(Vec::new()),
+                etag,
+            });
+            let sink_ptr = Arc::into_raw(sink.clone()) as *mut c_void;
+            let mut out_next_cursor = CBytes::default();
+
+            let result = unsafe {
+                (vtable.readdir_at)(
+                    provider.as_ptr(),
+                    path_cstr.as_ptr(),
+                    path_len,
+                    cursor_bytes.as_ptr().cast(),
+                    cursor_bytes.len(),
+                    max,
+                    collect_entry,
+                    sink_ptr,
+                    &mut out_next_cursor,
+                )
+            };
+
+            let sink = unsafe { Arc::from_raw(sink_ptr as *const EntrySink) };
+            let next_cursor = if out_next_cursor.data.is_null() || out_next_cursor.len == 0 {
+                None
+            } else {
+                let bytes =
+                    unsafe { slice::from_raw_parts(out_next_cursor.data, out_next_cursor.len) };
+                Some(String::from_utf8_lossy(bytes).into_owned())
+            };
+            unsafe { fs9_sdk_ffi::fs9_bytes_free(&mut out_next_cursor) };
+
+            if result.code == FS9_OK {
+                let entries = sink.entries.lock().unwrap().clone();
+                Ok(DirPage {
+                    entries,
+                    next_cursor,
+                })
+            } else {
+                Err(cresult_to_fserror(result))
+            }
+        })
+        .await
+        .map_err(|e| FsError::internal(e.to_string()))?;
+
+        match page {
+            // Plugins without native pagination still page correctly, just
+            // without the memory savings.
+            Err(FsError::NotImplemented(_)) => {
+                DirPage::from_listing(self.readdir(path).await?, cursor, max)
+            }
+            page => page,
+        }
+    }
+
     async fn remove(&self, path: &str) -> FsResult<()> {
         let path_cstr = CString::new(path).map_err(|e| FsError::invalid_argument(e.to_string()))?;
         let path_len = path.len();
@@ -722,6 +1367,69 @@ impl FsProvider for PluginProvider {
         .map_err(|e| FsError::internal(e.to_string()))?
     }
 
+    async fn link(&self, existing_path: &str, new_path: &str) -> FsResult<()> {
+        let existing_cstr =
+            CString::new(existing_path).map_err(|e| FsError::invalid_argument(e.to_string()))?;
+        let existing_len = existing_path.len();
+        let new_cstr =
+            CString::new(new_path).map_err(|e| FsError::invalid_argument(e.to_string()))?;
+        let new_len = new_path.len();
+        let provider = SendablePtr::new(self.provider);
+        let vtable = self.plugin.vtable;
+
+        tokio::task::spawn_blocking(move || {
+            let result = unsafe {
+                (vtable.link)(
+                    provider.as_ptr(),
+                    existing_cstr.as_ptr(),
+                    existing_len,
+                    new_cstr.as_ptr(),
+                    new_len,
+                )
+            };
+            if result.code == FS9_OK {
+                Ok(())
+            } else {
+                Err(cresult_to_fserror(result))
+            }
+        })
+        .await
+        .map_err(|e| FsError::internal(e.to_string()))?
+    }
+
+    async fn rename(&self, old_path: &str, new_path: &str) -> FsResult<()> {
+        if !self.plugin.ops.rename {
+            return self.wstat(old_path, StatChanges::rename(new_path)).await;
+        }
+        let old_cstr =
+            CString::new(old_path).map_err(|e| FsError::invalid_argument(e.to_string()))?;
+        let old_len = old_path.len();
+        let new_cstr =
+            CString::new(new_path).map_err(|e| FsError::invalid_argument(e.to_string()))?;
+        let new_len = new_path.len();
+        let provider = SendablePtr::new(self.provider);
+        let vtable = self.plugin.vtable;
+
+        tokio::task::spawn_blocking(move || {
+            let result = unsafe {
+                (vtable.rename)(
+                    provider.as_ptr(),
+                    old_cstr.as_ptr(),
+                    old_len,
+                    new_cstr.as_ptr(),
+                    new_len,
+                )
+            };
+            if result.code == FS9_OK {
+                Ok(())
+            } else {
+                Err(cresult_to_fserror(result))
+            }
+        })
+        .await
+        .map_err(|e| FsError::internal(e.to_string()))?
+    }
+
     fn capabilities(&self) -> Capabilities {
         let caps_bits = unsafe { (self.plugin.vtable.get_capabilities)(self.provider) };
         Capabilities::from_bits_truncate(caps_bits)
@@ -745,6 +1453,52 @@ mod tests {
         assert!(matches!(result, Err(PluginError::NotFound(_))));
     }
 
+    fn fake_plugin(dir: &tempfile::TempDir) -> (std::path::PathBuf, String) {
+        let path = dir.path().join("libfake.so");
+        let contents = b"not really a shared object";
+        std::fs::write(&path, contents).unwrap();
+        let sha256 = Sha256::digest(contents)
+            .iter()
+            .map(|b| format!("{b:02x}"))
+            .collect();
+        (path, sha256)
+    }
+
+    #[test]
+    fn allowlisted_plugin_passes_verification() {
+        let dir = tempfile::tempdir().unwrap();
+        let (path, sha256) = fake_plugin(&dir);
+        let manager = PluginManager::new();
+        manager.set_allowed_sha256(["00".repeat(32), sha256.to_uppercase()]);
+
+        // Past the allowlist, the file goes on to dlopen, which it fails.
+        assert!(matches!(
+            manager.load_from_path(&path),
+            Err(PluginError::LoadError(_))
+        ));
+    }
+
+    #[test]
+    fn plugin_missing_from_allowlist_is_refused() {
+        let dir = tempfile::tempdir().unwrap();
+        let (path, sha256) = fake_plugin(&dir);
+        let manager = PluginManager::new();
+        manager.set_allowed_sha256(["00".repeat(32)]);
+
+        let err = manager.load("fake", &path).unwrap_err();
+        assert!(
+            matches!(&err, PluginError::NotAllowed { path: p, sha256: s } if *p == path.display().to_string() && *s == sha256)
+        );
+        assert!(err.to_string().contains("libfake.so"));
+
+        manager.set_allowed_sha256(Vec::<String>::new());
+        assert!(matches!(
+            manager.load("fake", &path),
+            Err(PluginError::NotAllowed { .. })
+        ));
+        assert!(!manager.is_loaded("fake"));
+    }
+
     #[test]
     fn plugin_manager_create_provider_not_found() {
         let manager = PluginManager::new();
@@ -770,13 +1524,14 @@ mod tests {
     #[test]
     fn openflags_conversion() {
         let flags = OpenFlags::create_file();
-        let c_flags = openflags_to_copenflags(&flags);
+        let c_flags = openflags_to_copenflags(flags);
         assert_eq!(c_flags.read, 1);
         assert_eq!(c_flags.write, 1);
         assert_eq!(c_flags.create, 1);
         assert_eq!(c_flags.truncate, 0);
         assert_eq!(c_flags.append, 0);
         assert_eq!(c_flags.directory, 0);
+        assert_eq!(c_flags.excl, 0);
     }
 
     #[test]
@@ -806,6 +1561,47 @@ mod tests {
         };
         let err = cresult_to_fserror(result);
         assert!(err.is_permission_denied());
+
+        let result = CResult {
+            code: FS9_ERR_BACKEND_UNAVAILABLE,
+            error_msg: ptr::null(),
+            error_msg_len: 0,
+        };
+        let err = cresult_to_fserror(result);
+        assert!(matches!(err, FsError::BackendUnavailable(_)));
+
+        let result = CResult {
+            code: FS9_ERR_END_OF_STREAM,
+            error_msg: ptr::null(),
+            error_msg_len: 0,
+        };
+        let err = cresult_to_fserror(result);
+        assert!(err.is_end_of_stream());
+
+        let result = CResult {
+            code: FS9_ERR_NO_SPACE,
+            error_msg: ptr::null(),
+            error_msg_len: 0,
+        };
+        let err = cresult_to_fserror(result);
+        assert!(matches!(err, FsError::NoSpace(_)));
+    }
+
+    #[test]
+    fn cresult_message_is_kept() {
+        let missing = FsError::not_found("/some/path");
+        let result = CResult::from_error(FS9_ERR_NOT_FOUND, &missing);
+        assert_eq!(
+            cresult_to_fserror(result).to_string(),
+            "not found: /some/path"
+        );
+
+        let stale = FsError::invalid_handle(42);
+        let result = CResult::from_error(FS9_ERR_INVALID_HANDLE, &stale);
+        assert!(matches!(
+            cresult_to_fserror(result),
+            FsError::InvalidHandle(42)
+        ));
     }
 
     #[test]
@@ -838,11 +1634,421 @@ mod tests {
             atime: 1_700_000_000,
             mtime: 1_700_000_100,
             ctime: 1_700_000_050,
+            ..CFileInfo::default()
         };
-        let info = cfileinfo_to_fileinfo(&c_info);
+        let info = cfileinfo_to_fileinfo(&c_info, true);
         assert_eq!(info.path, "/test/file.txt");
         assert_eq!(info.size, 1024);
         assert_eq!(info.file_type, FileType::Regular);
         assert_eq!(info.mode, 0o644);
+        assert_eq!(info.etag, "");
+    }
+
+    #[test]
+    fn cfileinfo_etag_needs_a_new_enough_plugin() {
+        let mut c_info = CFileInfo::default();
+        c_info.set_etag("W/400-1");
+        assert_eq!(cfileinfo_to_fileinfo(&c_info, true).etag, "W/400-1");
+        assert_eq!(cfileinfo_to_fileinfo(&c_info, false).etag, "");
+        assert!(!PluginOps::for_sdk_version(fs9_sdk_ffi::ETAG_SINCE - 1).etag);
+    }
+
+    // A plugin built against SDK version 2, before any of the optional
+    // slots existed. Its vtable is given every slot anyway, each answering
+    // with success, so that a call reaching one shows up as a wrong result.
+
+    use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+    static WSTAT_RENAMED: AtomicBool = AtomicBool::new(false);
+
+    unsafe extern "C" fn old_create(_: *const c_char, _: size_t) -> *mut c_void {
+        ptr::NonNull::<u8>::dangling().as_ptr().cast()
+    }
+    unsafe extern "C" fn old_destroy(_: *mut c_void) {}
+    unsafe extern "C" fn old_capabilities(_: *mut c_void) -> u64 {
+        0
+    }
+    /// Only `/exists` exists.
+    unsafe extern "C" fn old_stat(
+        _: *mut c_void,
+        path: *const c_char,
+        path_len: size_t,
+        _: *mut CFileInfo,
+    ) -> CResult {
+        if fs9_sdk_ffi::str_from_c(path, path_len) == Some("/exists") {
+            CResult::ok()
+        } else {
+            CResult::err(FS9_ERR_NOT_FOUND, ptr::null(), 0)
+        }
+    }
+    unsafe extern "C" fn old_wstat(
+        _: *mut c_void,
+        _: *const c_char,
+        _: size_t,
+        changes: *const CStatChanges,
+    ) -> CResult {
+        if (*changes).has_name != 0 {
+            WSTAT_RENAMED.store(true, Ordering::SeqCst);
+        }
+        CResult::ok()
+    }
+    unsafe extern "C" fn old_statfs(
+        _: *mut c_void,
+        _: *const c_char,
+        _: size_t,
+        _: *mut CFsStats,
+    ) -> CResult {
+        CResult::ok()
+    }
+    unsafe extern "C" fn old_open(
+        _: *mut c_void,
+        _: *const c_char,
+        _: size_t,
+        _: *const COpenFlags,
+        _: *mut u64,
+        _: *mut CFileInfo,
+    ) -> CResult {
+        CResult::err(FS9_ERR_NOT_FOUND, ptr::null(), 0)
+    }
+    unsafe extern "C" fn old_read(
+        _: *mut c_void,
+        _: u64,
+        _: u64,
+        _: size_t,
+        _: *mut CBytes,
+    ) -> CResult {
+        CResult::ok()
+    }
+    unsafe extern "C" fn old_write(
+        _: *mut c_void,
+        _: u64,
+        _: u64,
+        _: *const u8,
+        _: size_t,
+        _: *mut size_t,
+    ) -> CResult {
+        CResult::ok()
+    }
+    unsafe extern "C" fn old_close(_: *mut c_void, _: u64, _: u8) -> CResult {
+        CResult::ok()
+    }
+    unsafe extern "C" fn old_readdir(
+        _: *mut c_void,
+        _: *const c_char,
+        _: size_t,
+        _: fs9_sdk_ffi::ReaddirCallback,
+        _: *mut c_void,
+    ) -> CResult {
+        CResult::ok()
+    }
+    unsafe extern "C" fn old_remove(_: *mut c_void, _: *const c_char, _: size_t) -> CResult {
+        CResult::ok()
+    }
+
+    unsafe extern "C" fn newer_fsync(_: *mut c_void, _: u64, _: u8) -> CResult {
+        CResult::ok()
+    }
+
+    static LEGACY_SYNCED: AtomicU64 = AtomicU64::new(0);
+
+    unsafe extern "C" fn legacy_fsync(_: *mut c_void, handle: u64) -> CResult {
+        LEGACY_SYNCED.store(handle, Ordering::SeqCst);
+        CResult::ok()
+    }
+    unsafe extern "C" fn newer_paths(
+        _: *mut c_void,
+        _: *const c_char,
+        _: size_t,
+        _: *const c_char,
+        _: size_t,
+    ) -> CResult {
+        CResult::ok()
+    }
+    #[allow(clippy::too_many_arguments)]
+    unsafe extern "C" fn newer_readdir_at(
+        _: *mut c_void,
+        _: *const c_char,
+        _: size_t,
+        _: *const c_char,
+        _: size_t,
+        _: size_t,
+        _: fs9_sdk_ffi::ReaddirCallback,
+        _: *mut c_void,
+        _: *mut CBytes,
+    ) -> CResult {
+        CResult::err(FS9_ERR_INTERNAL, ptr::null(), 0)
+    }
+    unsafe extern "C" fn newer_flush(_: *mut c_void) -> CResult {
+        CResult::err(FS9_ERR_INTERNAL, ptr::null(), 0)
+    }
+    unsafe extern "C" fn newer_getxattr(
+        _: *mut c_void,
+        _: *const c_char,
+        _: size_t,
+        _: *const c_char,
+        _: size_t,
+        _: *mut CBytes,
+    ) -> CResult {
+        CResult::ok()
+    }
+    unsafe extern "C" fn newer_setxattr(
+        _: *mut c_void,
+        _: *const c_char,
+        _: size_t,
+        _: *const c_char,
+        _: size_t,
+        _: *const u8,
+        _: size_t,
+    ) -> CResult {
+        CResult::ok()
+    }
+    unsafe extern "C" fn newer_listxattr(
+        _: *mut c_void,
+        _: *const c_char,
+        _: size_t,
+        _: *mut CBytes,
+    ) -> CResult {
+        CResult::ok()
+    }
+
+    unsafe extern "C" fn newer_health_check(_: *mut c_void) -> CResult {
+        CResult::err(FS9_ERR_BACKEND_UNAVAILABLE, ptr::null(), 0)
+    }
+
+    unsafe extern "C" fn newer_snapshot(
+        _: *mut c_void,
+        _: *const c_char,
+        _: size_t,
+        out_id: *mut u64,
+    ) -> CResult {
+        *out_id = 7;
+        CResult::ok()
+    }
+    unsafe extern "C" fn newer_open_snapshot(
+        _: *mut c_void,
+        _: u64,
+        out_view: *mut *mut c_void,
+    ) -> CResult {
+        *out_view = old_create(ptr::null(), 0);
+        CResult::ok()
+    }
+
+    fn vtable_for(sdk_version: u32) -> PluginVTable {
+        PluginVTable {
+            sdk_version,
+            name: ptr::null(),
+            name_len: 0,
+            version: ptr::null(),
+            version_len: 0,
+            create: old_create,
+            destroy: old_destroy,
+            get_capabilities: old_capabilities,
+            stat: old_stat,
+            wstat: old_wstat,
+            statfs: old_statfs,
+            open: old_open,
+            read: old_read,
+            write: old_write,
+            close: old_close,
+            readdir: old_readdir,
+            remove: old_remove,
+            fsync: newer_fsync,
+            link: newer_paths,
+            rename: newer_paths,
+            readdir_at: newer_readdir_at,
+            flush: newer_flush,
+            getxattr: newer_getxattr,
+            setxattr: newer_setxattr,
+            listxattr: newer_listxattr,
+            removexattr: newer_paths,
+            health_check: newer_health_check,
+            snapshot: newer_snapshot,
+            open_snapshot: newer_open_snapshot,
+        }
+    }
+
+    fn provider_for(vtable: &PluginVTable) -> PluginProvider {
+        let (vtable, ops) = unsafe { read_vtable(vtable) }.unwrap();
+        PluginProvider {
+            plugin: Arc::new(LoadedPlugin {
+                library: None,
+                vtable,
+                ops,
+                name: "old".to_string(),
+            }),
+            provider: unsafe { (vtable.create)(ptr::null(), 0) },
+        }
+    }
+
+    #[test]
+    fn ops_follow_the_plugin_sdk_version() {
+        let v2 = PluginOps::for_sdk_version(FS9_MIN_SDK_VERSION);
+        assert!(!(v2.fsync || v2.link || v2.rename || v2.readdir_at || v2.flush || v2.xattr));
+        let v7 = PluginOps::for_sdk_version(7);
+        assert!(v7.fsync && v7.link && v7.rename);
+        assert!(!(v7.fsync_data_only || v7.readdir_at || v7.flush || v7.xattr));
+        assert_eq!(
+            PluginOps::for_sdk_version(FS9_SDK_VERSION),
+            PluginOps {
+                fsync: true,
+                fsync_data_only: true,
+                link: true,
+                rename: true,
+                readdir_at: true,
+                flush: true,
+                xattr: true,
+                open_excl: true,
+                health_check: true,
+                snapshot: true,
+                etag: true,
+            }
+        );
+    }
+
+    #[test]
+    fn unsupported_sdk_versions_are_rejected() {
+        for version in [FS9_MIN_SDK_VERSION - 1, FS9_SDK_VERSION + 1] {
+            let vtable = vtable_for(version);
+            assert!(matches!(
+                unsafe { read_vtable(&vtable) },
+                Err(PluginError::VersionMismatch { plugin, .. }) if plugin == version
+            ));
+        }
+    }
+
+    #[tokio::test]
+    async fn old_plugins_report_newer_ops_as_not_implemented() {
+        let fs = provider_for(&vtable_for(FS9_MIN_SDK_VERSION));
+        let handle = Handle::new(1);
+        let not_implemented = |r: FsResult<()>| matches!(r, Err(FsError::NotImplemented(_)));
+
+        assert!(not_implemented(fs.fsync(&handle, false).await));
+        assert!(not_implemented(fs.link("/a", "/b").await));
+        assert!(not_implemented(
+            fs.setxattr("/a", "user.k", Bytes::new()).await
+        ));
+        assert!(not_implemented(fs.removexattr("/a", "user.k").await));
+        assert!(matches!(
+            fs.snapshot("/").await,
+            Err(FsError::NotImplemented(_))
+        ));
+        assert!(matches!(
+            fs.snapshot_view(0).await,
+            Err(FsError::NotImplemented(_))
+        ));
+        assert!(matches!(
+            fs.getxattr("/a", "user.k").await,
+            Err(FsError::NotImplemented(_))
+        ));
+        assert!(matches!(
+            fs.listxattr("/a").await,
+            Err(FsError::NotImplemented(_))
+        ));
+
+        // What a plugin of that age did before the slots existed.
+        fs.flush().await.unwrap();
+        assert!(fs
+            .readdir_at("/", None, 10)
+            .await
+            .unwrap()
+            .entries
+            .is_empty());
+        fs.rename("/a", "/b").await.unwrap();
+        assert!(WSTAT_RENAMED.load(Ordering::SeqCst));
+        // The root stat fails here, as it would for an unreachable backend.
+        assert!(matches!(fs.health_check().await, Err(FsError::NotFound(_))));
+    }
+
+    #[tokio::test]
+    async fn fsync_from_before_data_only_is_called_without_it() {
+        let mut vtable = vtable_for(fs9_sdk_ffi::FSYNC_DATA_ONLY_SINCE - 1);
+        vtable.fsync =
+            unsafe { std::mem::transmute::<LegacyFsyncFn, fs9_sdk_ffi::FsyncFn>(legacy_fsync) };
+        let fs = provider_for(&vtable);
+        fs.fsync(&Handle::new(9), true).await.unwrap();
+        assert_eq!(LEGACY_SYNCED.load(Ordering::SeqCst), 9);
+    }
+
+    #[tokio::test]
+    async fn current_plugins_reach_every_slot() {
+        let fs = provider_for(&vtable_for(FS9_SDK_VERSION));
+        fs.fsync(&Handle::new(1), false).await.unwrap();
+        fs.link("/a", "/b").await.unwrap();
+        assert!(matches!(fs.flush().await, Err(FsError::Internal(_))));
+        assert!(matches!(
+            fs.health_check().await,
+            Err(FsError::BackendUnavailable(_))
+        ));
+        assert_eq!(fs.snapshot("/").await.unwrap(), 7);
+        fs.snapshot_view(7).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn exclusive_open_is_checked_by_host_for_old_plugins() {
+        let exclusive = OpenFlags {
+            exclusive: true,
+            ..OpenFlags::create_file()
+        };
+
+        // The old plugin would ignore `excl`, so the host stats first.
+        let fs = provider_for(&vtable_for(FS9_MIN_SDK_VERSION));
+        assert!(matches!(
+            fs.open("/exists", exclusive).await,
+            Err(FsError::AlreadyExists(_))
+        ));
+
+        // A current plugin gets the flag and decides itself.
+        let fs = provider_for(&vtable_for(FS9_SDK_VERSION));
+        assert!(matches!(
+            fs.open("/exists", exclusive).await,
+            Err(FsError::NotFound(_))
+        ));
+    }
+
+    static READ_DATA: &[u8] = b"served straight from the plugin";
+
+    unsafe extern "C" fn shared_read(
+        _: *mut c_void,
+        _: u64,
+        _: u64,
+        _: size_t,
+        out: *mut CBytes,
+    ) -> CResult {
+        *out = fs9_sdk_ffi::bytes_to_cbytes(Bytes::from_static(READ_DATA));
+        CResult::ok()
+    }
+
+    unsafe extern "C" fn vec_read(
+        _: *mut c_void,
+        _: u64,
+        _: u64,
+        _: size_t,
+        out: *mut CBytes,
+    ) -> CResult {
+        *out = fs9_sdk_ffi::vec_to_cbytes(READ_DATA.to_vec());
+        CResult::ok()
+    }
+
+    #[tokio::test]
+    async fn reads_pass_plugin_buffers_on_without_copying() {
+        let fs = provider_for(&PluginVTable {
+            read: shared_read,
+            ..vtable_for(FS9_SDK_VERSION)
+        });
+        let data = fs.read(&Handle::new(1), 0, 64).await.unwrap();
+        assert_eq!(&data[..], READ_DATA);
+        assert_eq!(data.as_ptr(), READ_DATA.as_ptr());
+        // Slices and clones keep the plugin buffer alive until the last one.
+        let tail = data.slice(7..);
+        drop(data);
+        assert_eq!(&tail[..], &READ_DATA[7..]);
+
+        // Buffers from plugins that only know `vec_to_cbytes` still work.
+        let fs = provider_for(&PluginVTable {
+            read: vec_read,
+            ..vtable_for(FS9_MIN_SDK_VERSION)
+        });
+        let data = fs.read(&Handle::new(1), 0, 64).await.unwrap();
+        assert_eq!(&data[..], READ_DATA);
     }
 }
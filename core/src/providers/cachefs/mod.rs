@@ -0,0 +1,991 @@
+//! Whole-file cache of a slow backing provider, kept in a fast one.
+//!
+//! Opening a regular file for reading copies it into the cache provider
+//! unless it is there already, and reads are then served from the copy.
+//! Cached bytes are bounded: the least recently opened files are evicted
+//! first, skipping files that have a handle open. Writes either go to the
+//! backing provider and the cached copy together ([`WriteMode::Through`]),
+//! or to the cached copy alone ([`WriteMode::Back`]), which reaches the
+//! backing provider on `fsync`, `flush` or eviction. Metadata and listings
+//! always come from the backing provider, except for the size and times of
+//! files with writes not yet written back.
+//!
+//! Each open checks the backing file's size and modification time against
+//! those the cached copy was taken from, and fetches the file again when
+//! they differ. A copy with handles open or writes not yet written back is
+//! kept until they are done.
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use fs9_sdk::{
+    normalize_path, Capabilities, FileInfo, FileType, FsError, FsProvider, FsResult, FsStats,
+    Handle, OpenFlags, StatChanges,
+};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::SystemTime;
+
+const COPY_CHUNK: usize = 1024 * 1024;
+
+/// Where writes to cached files go.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WriteMode {
+    /// To the backing provider and the cached copy before returning.
+    #[default]
+    Through,
+    /// To the cached copy only; the backing provider is updated when the
+    /// file is fsynced, the cache is flushed or the file is evicted.
+    Back,
+}
+
+struct Entry {
+    size: u64,
+    /// Size and modification time of the backing file the copy matches.
+    source: (u64, SystemTime),
+    /// Position in the LRU order; larger is more recent.
+    tick: u64,
+    /// Holds writes the backing provider has not seen yet.
+    dirty: bool,
+    /// Open handles using the cached copy. Such entries are never evicted.
+    open: usize,
+}
+
+/// Cached files and their LRU order.
+#[derive(Default)]
+struct Index {
+    entries: HashMap<String, Entry>,
+    lru: BTreeMap<u64, String>,
+    bytes: u64,
+    tick: u64,
+}
+
+impl Index {
+    fn next_tick(&mut self) -> u64 {
+        self.tick += 1;
+        self.tick
+    }
+
+    /// Mark `path` as used by a new handle, if it is cached and still a copy
+    /// of the backing file described by `info`. `None` means the copy is
+    /// stale and unused, for the caller to discard.
+    fn pin_current(&mut self, path: &str, info: &FileInfo) -> Option<bool> {
+        let entry = self.entries.get(path)?;
+        let stale = !entry.dirty && entry.open == 0 && entry.source != (info.size, info.mtime);
+        (!stale).then(|| self.pin(path))
+    }
+
+    /// Mark `path` as used by a new handle, if it is cached.
+    fn pin(&mut self, path: &str) -> bool {
+        let tick = self.next_tick();
+        let Some(entry) = self.entries.get_mut(path) else {
+            return false;
+        };
+        self.lru.remove(&entry.tick);
+        self.lru.insert(tick, path.to_string());
+        entry.tick = tick;
+        entry.open += 1;
+        true
+    }
+
+    fn unpin(&mut self, path: &str) {
+        if let Some(entry) = self.entries.get_mut(path) {
+            entry.open = entry.open.saturating_sub(1);
+        }
+    }
+
+    /// Add `path`, copied from the backing file described by `info`, with
+    /// one open handle. An entry already there keeps its handles and gains
+    /// this one.
+    fn insert_pinned(&mut self, path: &str, info: &FileInfo) {
+        if self.pin(path) {
+            self.resize(path, info.size);
+            self.set_source(path, info);
+            return;
+        }
+        let tick = self.next_tick();
+        self.lru.insert(tick, path.to_string());
+        self.bytes += info.size;
+        self.entries.insert(
+            path.to_string(),
+            Entry {
+                size: info.size,
+                source: (info.size, info.mtime),
+                tick,
+                dirty: false,
+                open: 1,
+            },
+        );
+    }
+
+    fn set_source(&mut self, path: &str, info: &FileInfo) {
+        if let Some(entry) = self.entries.get_mut(path) {
+            entry.source = (info.size, info.mtime);
+        }
+    }
+
+    fn remove(&mut self, path: &str) -> Option<Entry> {
+        let entry = self.entries.remove(path)?;
+        self.lru.remove(&entry.tick);
+        self.bytes -= entry.size;
+        Some(entry)
+    }
+
+    fn resize(&mut self, path: &str, size: u64) {
+        if let Some(entry) = self.entries.get_mut(path) {
+            self.bytes = self.bytes - entry.size + size;
+            entry.size = size;
+        }
+    }
+
+    fn set_dirty(&mut self, path: &str, dirty: bool) {
+        if let Some(entry) = self.entries.get_mut(path) {
+            entry.dirty = dirty;
+        }
+    }
+
+    fn is_dirty(&self, path: &str) -> bool {
+        self.entries.get(path).is_some_and(|entry| entry.dirty)
+    }
+
+    /// Least recently used files to evict so `incoming` more bytes fit in
+    /// `max_bytes`.
+    fn victims(&self, incoming: u64, max_bytes: u64) -> Vec<String> {
+        let mut excess = (self.bytes + incoming).saturating_sub(max_bytes);
+        let mut victims = Vec::new();
+        for path in self.lru.values() {
+            if excess == 0 {
+                break;
+            }
+            let entry = &self.entries[path];
+            if entry.open == 0 {
+                excess = excess.saturating_sub(entry.size);
+                victims.push(path.clone());
+            }
+        }
+        victims
+    }
+
+    /// `path` and every cached path below it.
+    fn tree(&self, path: &str) -> Vec<String> {
+        let prefix = if path == "/" {
+            "/".to_string()
+        } else {
+            format!("{path}/")
+        };
+        self.entries
+            .keys()
+            .filter(|p| *p == path || p.starts_with(&prefix))
+            .cloned()
+            .collect()
+    }
+
+    fn dirty_paths(&self) -> Vec<String> {
+        self.entries
+            .iter()
+            .filter(|(_, entry)| entry.dirty)
+            .map(|(path, _)| path.clone())
+            .collect()
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Target {
+    Cached(Handle),
+    Backing(Handle),
+    /// A write-through handle on a cached file.
+    Both {
+        backing: Handle,
+        cached: Handle,
+    },
+}
+
+struct CacheHandle {
+    path: String,
+    flags: OpenFlags,
+    target: Target,
+}
+
+/// Per-path locks taken around looking a file up and filling it, so that
+/// concurrent first opens copy it once.
+#[derive(Default)]
+struct FillLocks(Mutex<HashMap<String, Arc<tokio::sync::Mutex<()>>>>);
+
+impl FillLocks {
+    async fn lock(&self, path: &str) -> FillGuard<'_> {
+        let lock = self
+            .0
+            .lock()
+            .unwrap()
+            .entry(path.to_string())
+            .or_default()
+            .clone();
+        FillGuard {
+            locks: self,
+            path: path.to_string(),
+            guard: Some(lock.lock_owned().await),
+        }
+    }
+}
+
+struct FillGuard<'a> {
+    locks: &'a FillLocks,
+    path: String,
+    guard: Option<tokio::sync::OwnedMutexGuard<()>>,
+}
+
+impl Drop for FillGuard<'_> {
+    fn drop(&mut self) {
+        self.guard = None;
+        let mut locks = self.locks.0.lock().unwrap();
+        if locks
+            .get(&self.path)
+            .is_some_and(|lock| Arc::strong_count(lock) == 1)
+        {
+            locks.remove(&self.path);
+        }
+    }
+}
+
+pub struct CacheFs {
+    backing: Arc<dyn FsProvider>,
+    cache: Arc<dyn FsProvider>,
+    max_bytes: u64,
+    mode: WriteMode,
+    index: Mutex<Index>,
+    fills: FillLocks,
+    handles: RwLock<HashMap<u64, CacheHandle>>,
+    next_handle: AtomicU64,
+}
+
+impl CacheFs {
+    /// Cache up to `max_bytes` of `backing`'s files in `cache`, which
+    /// should start out empty and not be used by anything else.
+    #[must_use]
+    pub fn new(backing: Arc<dyn FsProvider>, cache: Arc<dyn FsProvider>, max_bytes: u64) -> Self {
+        Self {
+            backing,
+            cache,
+            max_bytes,
+            mode: WriteMode::default(),
+            index: Mutex::new(Index::default()),
+            fills: FillLocks::default(),
+            handles: RwLock::new(HashMap::new()),
+            next_handle: AtomicU64::new(1),
+        }
+    }
+
+    #[must_use]
+    pub fn with_write_mode(mut self, mode: WriteMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Bytes of file data currently held in the cache provider.
+    pub fn cached_bytes(&self) -> u64 {
+        self.index.lock().unwrap().bytes
+    }
+
+    pub fn is_cached(&self, path: &str) -> bool {
+        normalize_path(path)
+            .is_ok_and(|path| self.index.lock().unwrap().entries.contains_key(&path))
+    }
+
+    async fn stat_opt(provider: &Arc<dyn FsProvider>, path: &str) -> FsResult<Option<FileInfo>> {
+        match provider.stat(path).await {
+            Ok(info) => Ok(Some(info)),
+            Err(e) if e.is_not_found() => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Create the directories above `path` in the cache provider.
+    async fn prepare_parent(&self, path: &str) -> FsResult<()> {
+        let mut dir = String::new();
+        let parts: Vec<&str> = path.split('/').filter(|p| !p.is_empty()).collect();
+        for part in parts.iter().take(parts.len().saturating_sub(1)) {
+            dir.push('/');
+            dir.push_str(part);
+            if Self::stat_opt(&self.cache, &dir).await?.is_none() {
+                let (handle, _) = self.cache.open(&dir, OpenFlags::create_dir()).await?;
+                self.cache.close(handle, false).await?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn copy_file(
+        from: &Arc<dyn FsProvider>,
+        to: &Arc<dyn FsProvider>,
+        path: &str,
+    ) -> FsResult<()> {
+        let (src, _) = from.open(path, OpenFlags::read()).await?;
+        let dst = match to.open(path, OpenFlags::create_truncate()).await {
+            Ok((dst, _)) => dst,
+            Err(e) => {
+                let _ = from.close(src, false).await;
+                return Err(e);
+            }
+        };
+        let mut offset = 0u64;
+        let result = loop {
+            let chunk = match from.read(&src, offset, COPY_CHUNK).await {
+                Ok(chunk) => chunk,
+                Err(e) => break Err(e),
+            };
+            if chunk.is_empty() {
+                break Ok(());
+            }
+            let len = chunk.len() as u64;
+            if let Err(e) = to.write(&dst, offset, chunk).await {
+                break Err(e);
+            }
+            offset += len;
+        };
+        let _ = from.close(src, false).await;
+        let closed = to.close(dst, true).await;
+        result?;
+        closed
+    }
+
+    /// Pin the cached copy of `path` if it still matches the backing file
+    /// described by `info`, discarding it if it is stale. Call with the
+    /// path's fill lock held.
+    async fn pin_current(&self, path: &str, info: &FileInfo) -> FsResult<bool> {
+        let pinned = self.index.lock().unwrap().pin_current(path, info);
+        let Some(pinned) = pinned else {
+            self.discard(path).await?;
+            return Ok(false);
+        };
+        Ok(pinned)
+    }
+
+    /// Copy `path`, described by `info`, into the cache and pin it,
+    /// evicting older files to make room. Returns false, caching nothing,
+    /// for files larger than the whole cache. Call with the path's fill
+    /// lock held.
+    async fn fill(&self, path: &str, info: &FileInfo) -> FsResult<bool> {
+        if info.size > self.max_bytes {
+            return Ok(false);
+        }
+        self.make_room(info.size).await?;
+        self.prepare_parent(path).await?;
+        Self::copy_file(&self.backing, &self.cache, path).await?;
+        self.index.lock().unwrap().insert_pinned(path, info);
+        Ok(true)
+    }
+
+    /// Record the backing file's current size and modification time as the
+    /// ones the cached copy of `path` matches, after writing both.
+    async fn resync_source(&self, path: &str) {
+        if let Ok(info) = self.backing.stat(path).await {
+            self.index.lock().unwrap().set_source(path, &info);
+        }
+    }
+
+    async fn make_room(&self, incoming: u64) -> FsResult<()> {
+        let victims = self.index.lock().unwrap().victims(incoming, self.max_bytes);
+        for path in victims {
+            self.evict(&path).await?;
+        }
+        Ok(())
+    }
+
+    /// Drop `path` from the cache, writing it back first if it is dirty.
+    async fn evict(&self, path: &str) -> FsResult<()> {
+        self.write_back(path).await?;
+        self.discard(path).await
+    }
+
+    /// Drop `path` from the cache without writing anything back.
+    async fn discard(&self, path: &str) -> FsResult<()> {
+        if self.index.lock().unwrap().remove(path).is_none() {
+            return Ok(());
+        }
+        match self.cache.remove(path).await {
+            Err(e) if !e.is_not_found() => Err(e),
+            _ => Ok(()),
+        }
+    }
+
+    /// Drop `path` and everything cached below it.
+    async fn discard_tree(&self, path: &str) -> FsResult<()> {
+        let paths = self.index.lock().unwrap().tree(path);
+        for path in paths {
+            self.discard(&path).await?;
+        }
+        Ok(())
+    }
+
+    /// Copy the cached `path` to the backing provider if it is dirty.
+    async fn write_back(&self, path: &str) -> FsResult<()> {
+        if !self.index.lock().unwrap().is_dirty(path) {
+            return Ok(());
+        }
+        Self::copy_file(&self.cache, &self.backing, path).await?;
+        self.index.lock().unwrap().set_dirty(path, false);
+        self.resync_source(path).await;
+        Ok(())
+    }
+
+    async fn write_back_tree(&self, path: &str) -> FsResult<()> {
+        let paths = self.index.lock().unwrap().tree(path);
+        for path in paths {
+            self.write_back(&path).await?;
+        }
+        Ok(())
+    }
+
+    /// Open the cached copy of `path`, which the caller has pinned.
+    async fn open_cached(&self, path: &str, flags: OpenFlags) -> FsResult<(Handle, FileInfo)> {
+        match self.cache.open(path, flags).await {
+            Ok((handle, info)) => {
+                self.index.lock().unwrap().resize(path, info.size);
+                Ok((handle, info))
+            }
+            Err(e) => {
+                self.index.lock().unwrap().unpin(path);
+                Err(e)
+            }
+        }
+    }
+
+    async fn open_for_read(&self, path: &str, flags: OpenFlags) -> FsResult<(Target, FileInfo)> {
+        let info = self.backing.stat(path).await?;
+        let filling = self.fills.lock(path).await;
+        if !self.pin_current(path, &info).await?
+            && (info.file_type != FileType::Regular || !self.fill(path, &info).await?)
+        {
+            drop(filling);
+            let (handle, info) = self.backing.open(path, flags).await?;
+            return Ok((Target::Backing(handle), info));
+        }
+        drop(filling);
+        let (handle, mut info) = self.open_cached(path, flags).await?;
+        info.path = path.to_string();
+        Ok((Target::Cached(handle), info))
+    }
+
+    async fn open_for_write(&self, path: &str, flags: OpenFlags) -> FsResult<(Target, FileInfo)> {
+        let (backing, info) = self.backing.open(path, flags).await?;
+        if info.file_type != FileType::Regular {
+            return Ok((Target::Backing(backing), info));
+        }
+
+        let filling = self.fills.lock(path).await;
+        let pinned = match self.pin_current(path, &info).await {
+            Ok(pinned) => pinned,
+            Err(e) => {
+                let _ = self.backing.close(backing, false).await;
+                return Err(e);
+            }
+        };
+        if self.mode == WriteMode::Through {
+            drop(filling);
+            if !pinned {
+                return Ok((Target::Backing(backing), info));
+            }
+            return match self.open_cached(path, flags).await {
+                Ok((cached, _)) => Ok((Target::Both { backing, cached }, info)),
+                Err(e) => {
+                    let _ = self.backing.close(backing, false).await;
+                    Err(e)
+                }
+            };
+        }
+
+        // Write-back: the backing handle only created or truncated the file.
+        if !pinned && info.size > self.max_bytes {
+            return Ok((Target::Backing(backing), info));
+        }
+        self.backing.close(backing, false).await?;
+        if !pinned && !self.fill(path, &info).await? {
+            drop(filling);
+            let (backing, info) = self.backing.open(path, flags).await?;
+            return Ok((Target::Backing(backing), info));
+        }
+        drop(filling);
+        let (cached, mut info) = self.open_cached(path, flags).await?;
+        info.path = path.to_string();
+        Ok((Target::Cached(cached), info))
+    }
+
+    fn register(&self, path: String, flags: OpenFlags, target: Target) -> Handle {
+        let id = self.next_handle.fetch_add(1, Ordering::SeqCst);
+        self.handles.write().unwrap().insert(
+            id,
+            CacheHandle {
+                path,
+                flags,
+                target,
+            },
+        );
+        Handle::new(id)
+    }
+
+    fn lookup_handle<T>(&self, handle: &Handle, f: impl FnOnce(&CacheHandle) -> T) -> FsResult<T> {
+        self.handles
+            .read()
+            .unwrap()
+            .get(&handle.id())
+            .map(f)
+            .ok_or_else(|| FsError::invalid_handle(handle.id()))
+    }
+
+    /// Record a write of `len` bytes at `offset` to the cached `path`.
+    async fn note_write(&self, path: &str, flags: OpenFlags, offset: u64, len: usize, dirty: bool) {
+        let end = if flags.append {
+            match self.cache.stat(path).await {
+                Ok(info) => info.size,
+                Err(_) => return,
+            }
+        } else {
+            offset + len as u64
+        };
+        let mut index = self.index.lock().unwrap();
+        if let Some(entry) = index.entries.get(path) {
+            if end > entry.size {
+                index.resize(path, end);
+            }
+        }
+        if dirty {
+            index.set_dirty(path, true);
+        }
+    }
+
+    /// `info` with the size and times of the cached copy, for files whose
+    /// latest writes have not reached the backing provider.
+    async fn patch_dirty(&self, mut info: FileInfo) -> FileInfo {
+        if self.index.lock().unwrap().is_dirty(&info.path) {
+            if let Ok(cached) = self.cache.stat(&info.path).await {
+                info.size = cached.size;
+                info.mtime = cached.mtime;
+                info.ctime = cached.ctime;
+            }
+        }
+        info
+    }
+}
+
+#[async_trait]
+impl FsProvider for CacheFs {
+    async fn stat(&self, path: &str) -> FsResult<FileInfo> {
+        let path = normalize_path(path)?;
+        let info = self.backing.stat(&path).await?;
+        Ok(self.patch_dirty(info).await)
+    }
+
+    async fn wstat(&self, path: &str, changes: StatChanges) -> FsResult<()> {
+        let path = normalize_path(path)?;
+        let renamed = changes.name.is_some();
+        let resized = changes.size.is_some() || changes.symlink_target.is_some();
+
+        if renamed {
+            self.write_back_tree(&path).await?;
+        } else {
+            self.write_back(&path).await?;
+        }
+        self.backing.wstat(&path, changes).await?;
+        if renamed {
+            self.discard_tree(&path).await?;
+        } else if resized {
+            self.discard(&path).await?;
+        }
+        Ok(())
+    }
+
+    async fn statfs(&self, path: &str) -> FsResult<FsStats> {
+        self.backing.statfs(path).await
+    }
+
+    async fn open(&self, path: &str, flags: OpenFlags) -> FsResult<(Handle, FileInfo)> {
+        let path = normalize_path(path)?;
+        let mutating = flags.write || flags.create || flags.truncate || flags.append;
+        let (target, info) = if flags.directory || flags.symlink {
+            let (handle, info) = self.backing.open(&path, flags).await?;
+            (Target::Backing(handle), info)
+        } else if mutating {
+            self.open_for_write(&path, flags).await?
+        } else {
+            self.open_for_read(&path, flags).await?
+        };
+        Ok((self.register(path, flags, target), info))
+    }
+
+    async fn read(&self, handle: &Handle, offset: u64, size: usize) -> FsResult<Bytes> {
+        let (provider, inner) = self.lookup_handle(handle, |h| match h.target {
+            Target::Cached(cached) | Target::Both { cached, .. } => (self.cache.clone(), cached),
+            Target::Backing(backing) => (self.backing.clone(), backing),
+        })?;
+        provider.read(&inner, offset, size).await
+    }
+
+    async fn write(&self, handle: &Handle, offset: u64, data: Bytes) -> FsResult<usize> {
+        let (path, flags, target) =
+            self.lookup_handle(handle, |h| (h.path.clone(), h.flags, h.target))?;
+
+        match target {
+            Target::Backing(backing) => self.backing.write(&backing, offset, data).await,
+            Target::Cached(cached) => {
+                let written = self.cache.write(&cached, offset, data).await?;
+                self.note_write(&path, flags, offset, written, true).await;
+                Ok(written)
+            }
+            Target::Both { backing, cached } => {
+                let written = self.backing.write(&backing, offset, data.clone()).await?;
+                match self
+                    .cache
+                    .write(&cached, offset, data.slice(..written))
+                    .await
+                {
+                    Ok(_) => self.note_write(&path, flags, offset, written, false).await,
+                    // The backing provider has the data; forget the stale
+                    // copy so the next open fetches the file again.
+                    Err(_) => {
+                        self.index.lock().unwrap().remove(&path);
+                    }
+                }
+                Ok(written)
+            }
+        }
+    }
+
+    async fn close(&self, handle: Handle, sync: bool) -> FsResult<()> {
+        let entry = self
+            .handles
+            .write()
+            .unwrap()
+            .remove(&handle.id())
+            .ok_or_else(|| FsError::invalid_handle(handle.id()))?;
+        match entry.target {
+            Target::Backing(backing) => self.backing.close(backing, sync).await,
+            Target::Cached(cached) => {
+                self.index.lock().unwrap().unpin(&entry.path);
+                self.cache.close(cached, false).await?;
+                if sync {
+                    self.write_back(&entry.path).await?;
+                }
+                Ok(())
+            }
+            Target::Both { backing, cached } => {
+                self.index.lock().unwrap().unpin(&entry.path);
+                let closed = self.cache.close(cached, false).await;
+                self.backing.close(backing, sync).await?;
+                self.resync_source(&entry.path).await;
+                closed
+            }
+        }
+    }
+
+    async fn fsync(&self, handle: &Handle, data_only: bool) -> FsResult<()> {
+        let (path, target) = self.lookup_handle(handle, |h| {
+            let backing = match h.target {
+                Target::Backing(backing) | Target::Both { backing, .. } => Some(backing),
+                Target::Cached(_) => None,
+            };
+            (h.path.clone(), backing)
+        })?;
+        match target {
+            Some(backing) => self.backing.fsync(&backing, data_only).await,
+            None => self.write_back(&path).await,
+        }
+    }
+
+    async fn flush(&self) -> FsResult<()> {
+        let dirty = self.index.lock().unwrap().dirty_paths();
+        for path in dirty {
+            self.write_back(&path).await?;
+        }
+        self.backing.flush().await
+    }
+
+    async fn health_check(&self) -> FsResult<()> {
+        self.backing.health_check().await
+    }
+
+    async fn snapshot(&self, path: &str) -> FsResult<u64> {
+        // Dirty files would otherwise be captured as the backing store
+        // last saw them.
+        self.flush().await?;
+        self.backing.snapshot(&normalize_path(path)?).await
+    }
+
+    async fn snapshot_view(&self, snapshot_id: u64) -> FsResult<Arc<dyn FsProvider>> {
+        self.backing.snapshot_view(snapshot_id).await
+    }
+
+    async fn readdir(&self, path: &str) -> FsResult<Vec<FileInfo>> {
+        let path = normalize_path(path)?;
+        let entries = self.backing.readdir(&path).await?;
+        let mut patched = Vec::with_capacity(entries.len());
+        for entry in entries {
+            patched.push(self.patch_dirty(entry).await);
+        }
+        Ok(patched)
+    }
+
+    async fn remove(&self, path: &str) -> FsResult<()> {
+        let path = normalize_path(path)?;
+        self.backing.remove(&path).await?;
+        self.discard_tree(&path).await
+    }
+
+    async fn rename(&self, old_path: &str, new_path: &str) -> FsResult<()> {
+        let old_path = normalize_path(old_path)?;
+        let new_path = normalize_path(new_path)?;
+        self.write_back_tree(&old_path).await?;
+        self.backing.rename(&old_path, &new_path).await?;
+        self.discard_tree(&old_path).await?;
+        self.discard_tree(&new_path).await
+    }
+
+    async fn link(&self, existing_path: &str, new_path: &str) -> FsResult<()> {
+        let existing_path = normalize_path(existing_path)?;
+        // Both names share the backing file; a cached copy of either could
+        // miss writes made through the other.
+        self.write_back(&existing_path).await?;
+        self.backing.link(&existing_path, new_path).await?;
+        self.discard(&existing_path).await
+    }
+
+    async fn getxattr(&self, path: &str, name: &str) -> FsResult<Bytes> {
+        self.backing.getxattr(path, name).await
+    }
+
+    async fn setxattr(&self, path: &str, name: &str, value: Bytes) -> FsResult<()> {
+        self.backing.setxattr(path, name, value).await
+    }
+
+    async fn listxattr(&self, path: &str) -> FsResult<Vec<String>> {
+        self.backing.listxattr(path).await
+    }
+
+    async fn removexattr(&self, path: &str, name: &str) -> FsResult<()> {
+        self.backing.removexattr(path, name).await
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        self.backing.capabilities()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{HookedFs, Op};
+    use crate::MemoryFs;
+
+    async fn write_file(fs: &dyn FsProvider, path: &str, data: &str) {
+        let (handle, _) = fs.open(path, OpenFlags::create_truncate()).await.unwrap();
+        fs.write(&handle, 0, Bytes::from(data.to_string()))
+            .await
+            .unwrap();
+        fs.close(handle, false).await.unwrap();
+    }
+
+    async fn read_file(fs: &dyn FsProvider, path: &str) -> String {
+        let (handle, _) = fs.open(path, OpenFlags::read()).await.unwrap();
+        let data = fs.read(&handle, 0, 4096).await.unwrap();
+        fs.close(handle, false).await.unwrap();
+        String::from_utf8(data.to_vec()).unwrap()
+    }
+
+    async fn cache_fs(max_bytes: u64) -> (CacheFs, Arc<HookedFs>, Arc<MemoryFs>) {
+        let backing = Arc::new(HookedFs::new());
+        let (h, _) = backing
+            .open("/docs", OpenFlags::create_dir())
+            .await
+            .unwrap();
+        backing.close(h, false).await.unwrap();
+        write_file(backing.as_ref(), "/docs/a.txt", "aaaaaaaaaa").await;
+        write_file(backing.as_ref(), "/docs/b.txt", "bbbbbbbbbb").await;
+        write_file(backing.as_ref(), "/docs/c.txt", "cccccccccc").await;
+
+        let cache = Arc::new(MemoryFs::new());
+        let fs = CacheFs::new(backing.clone(), cache.clone(), max_bytes);
+        (fs, backing, cache)
+    }
+
+    #[tokio::test]
+    async fn cache_hit_skips_backing() {
+        let (fs, backing, cache) = cache_fs(1024).await;
+
+        assert_eq!(read_file(&fs, "/docs/a.txt").await, "aaaaaaaaaa");
+        let after_miss = backing.calls(Op::Read);
+        assert!(after_miss > 0);
+        assert!(fs.is_cached("/docs/a.txt"));
+        assert_eq!(read_file(cache.as_ref(), "/docs/a.txt").await, "aaaaaaaaaa");
+
+        assert_eq!(read_file(&fs, "/docs/a.txt").await, "aaaaaaaaaa");
+        assert_eq!(
+            backing.calls(Op::Read),
+            after_miss,
+            "hit must not read backing"
+        );
+        assert_eq!(fs.cached_bytes(), 10);
+    }
+
+    #[tokio::test]
+    async fn eviction_refetches_from_backing() {
+        let (fs, backing, cache) = cache_fs(25).await;
+
+        read_file(&fs, "/docs/a.txt").await;
+        read_file(&fs, "/docs/b.txt").await;
+        // Touch a so that b is the least recently used.
+        read_file(&fs, "/docs/a.txt").await;
+        read_file(&fs, "/docs/c.txt").await;
+
+        assert!(fs.is_cached("/docs/a.txt"));
+        assert!(!fs.is_cached("/docs/b.txt"));
+        assert!(fs.is_cached("/docs/c.txt"));
+        assert!(cache.stat("/docs/b.txt").await.is_err());
+        assert_eq!(fs.cached_bytes(), 20);
+
+        let before = backing.calls(Op::Read);
+        assert_eq!(read_file(&fs, "/docs/b.txt").await, "bbbbbbbbbb");
+        assert!(
+            backing.calls(Op::Read) > before,
+            "evicted file is fetched again"
+        );
+        assert!(!fs.is_cached("/docs/a.txt"));
+    }
+
+    #[tokio::test]
+    async fn open_files_are_not_evicted() {
+        let (fs, _, _) = cache_fs(15).await;
+
+        let (held, _) = fs.open("/docs/a.txt", OpenFlags::read()).await.unwrap();
+        read_file(&fs, "/docs/b.txt").await;
+        assert!(fs.is_cached("/docs/a.txt"));
+        assert!(fs.is_cached("/docs/b.txt"));
+
+        let data = fs.read(&held, 0, 100).await.unwrap();
+        assert_eq!(&data[..], b"aaaaaaaaaa");
+        fs.close(held, false).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn concurrent_first_opens_fill_once() {
+        // Slow backing reads widen the window in which both opens miss.
+        let backing = Arc::new(HookedFs::new().before_async(Op::Read, |_| async {
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+            Ok(())
+        }));
+        write_file(backing.as_ref(), "/a.txt", "aaaaaaaaaa").await;
+        write_file(backing.as_ref(), "/b.txt", "bbbbbbbbbb").await;
+        let fs = CacheFs::new(backing.clone(), Arc::new(MemoryFs::new()), 15);
+
+        let opens = backing.calls(Op::Open);
+        let (first, second) = tokio::join!(
+            fs.open("/a.txt", OpenFlags::read()),
+            fs.open("/a.txt", OpenFlags::read())
+        );
+        let (first, second) = (first.unwrap().0, second.unwrap().0);
+        assert_eq!(backing.calls(Op::Open), opens + 1, "one copy is made");
+
+        // The handle still open keeps the copy from being evicted.
+        fs.close(first, false).await.unwrap();
+        read_file(&fs, "/b.txt").await;
+        assert!(fs.is_cached("/a.txt"));
+        let data = fs.read(&second, 0, 100).await.unwrap();
+        assert_eq!(&data[..], b"aaaaaaaaaa");
+        fs.close(second, false).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn changed_backing_files_are_fetched_again() {
+        let (fs, backing, _) = cache_fs(1024).await;
+        assert_eq!(read_file(&fs, "/docs/a.txt").await, "aaaaaaaaaa");
+
+        // Changed behind the cache's back.
+        write_file(backing.as_ref(), "/docs/a.txt", "changed").await;
+        assert_eq!(read_file(&fs, "/docs/a.txt").await, "changed");
+        assert_eq!(fs.cached_bytes(), 7);
+
+        // Writes made through the cache don't make its copy look stale.
+        let (handle, _) = fs.open("/docs/a.txt", OpenFlags::write()).await.unwrap();
+        fs.write(&handle, 0, Bytes::from("C")).await.unwrap();
+        fs.close(handle, false).await.unwrap();
+        let reads = backing.calls(Op::Read);
+        assert_eq!(read_file(&fs, "/docs/a.txt").await, "Changed");
+        assert_eq!(backing.calls(Op::Read), reads);
+    }
+
+    #[tokio::test]
+    async fn files_larger_than_cache_bypass_it() {
+        let (fs, _, _) = cache_fs(5).await;
+        assert_eq!(read_file(&fs, "/docs/a.txt").await, "aaaaaaaaaa");
+        assert!(!fs.is_cached("/docs/a.txt"));
+        assert_eq!(fs.cached_bytes(), 0);
+    }
+
+    #[tokio::test]
+    async fn write_through_updates_both() {
+        let (fs, backing, cache) = cache_fs(1024).await;
+        read_file(&fs, "/docs/a.txt").await;
+
+        let (handle, _) = fs.open("/docs/a.txt", OpenFlags::write()).await.unwrap();
+        fs.write(&handle, 0, Bytes::from("AAAA")).await.unwrap();
+        fs.close(handle, false).await.unwrap();
+
+        assert_eq!(
+            read_file(backing.as_ref(), "/docs/a.txt").await,
+            "AAAAaaaaaa"
+        );
+        assert_eq!(read_file(cache.as_ref(), "/docs/a.txt").await, "AAAAaaaaaa");
+        assert_eq!(read_file(&fs, "/docs/a.txt").await, "AAAAaaaaaa");
+    }
+
+    #[tokio::test]
+    async fn write_back_defers_until_flush() {
+        let (fs, backing, _) = cache_fs(1024).await;
+        let fs = fs.with_write_mode(WriteMode::Back);
+
+        write_file(&fs, "/docs/a.txt", "rewritten, longer").await;
+        write_file(&fs, "/docs/new.txt", "fresh").await;
+        assert_eq!(read_file(backing.as_ref(), "/docs/a.txt").await, "");
+        assert_eq!(fs.stat("/docs/a.txt").await.unwrap().size, 17);
+        assert_eq!(read_file(&fs, "/docs/new.txt").await, "fresh");
+
+        fs.flush().await.unwrap();
+        assert_eq!(
+            read_file(backing.as_ref(), "/docs/a.txt").await,
+            "rewritten, longer"
+        );
+        assert_eq!(read_file(backing.as_ref(), "/docs/new.txt").await, "fresh");
+    }
+
+    #[tokio::test]
+    async fn write_back_flushes_on_eviction() {
+        let (fs, backing, _) = cache_fs(12).await;
+        let fs = fs.with_write_mode(WriteMode::Back);
+
+        write_file(&fs, "/docs/a.txt", "dirty").await;
+        read_file(&fs, "/docs/b.txt").await;
+
+        assert!(!fs.is_cached("/docs/a.txt"));
+        assert_eq!(read_file(backing.as_ref(), "/docs/a.txt").await, "dirty");
+    }
+
+    #[tokio::test]
+    async fn remove_and_rename_invalidate() {
+        let (fs, _, cache) = cache_fs(1024).await;
+        read_file(&fs, "/docs/a.txt").await;
+        read_file(&fs, "/docs/b.txt").await;
+
+        fs.remove("/docs/a.txt").await.unwrap();
+        assert!(!fs.is_cached("/docs/a.txt"));
+        assert!(cache.stat("/docs/a.txt").await.is_err());
+        assert!(fs.stat("/docs/a.txt").await.is_err());
+
+        fs.rename("/docs/b.txt", "/docs/b.bak").await.unwrap();
+        assert!(!fs.is_cached("/docs/b.txt"));
+        assert_eq!(fs.cached_bytes(), 0);
+        assert_eq!(read_file(&fs, "/docs/b.bak").await, "bbbbbbbbbb");
+
+        // A rename over a cached file drops the replaced copy.
+        read_file(&fs, "/docs/c.txt").await;
+        fs.rename("/docs/b.bak", "/docs/c.txt").await.unwrap();
+        assert_eq!(read_file(&fs, "/docs/c.txt").await, "bbbbbbbbbb");
+    }
+}
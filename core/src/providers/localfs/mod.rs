@@ -20,8 +20,19 @@ struct LocalHandle {
     flags: OpenFlags,
 }
 
+/// Serves a directory of the host filesystem.
+///
+/// Every path is resolved under `root`. `..` may not climb above it, and
+/// neither may a symlink unless
+/// [`with_follow_symlinks_outside_root`](Self::with_follow_symlinks_outside_root)
+/// is set. Escapes fail with `PermissionDenied`.
+///
+/// The checks run before each syscall, so a process on the host that swaps
+/// a directory under `root` for a symlink in between can still slip past
+/// them. Do not share `root` with writers you do not trust.
 pub struct LocalFs {
     root: PathBuf,
+    follow_symlinks_outside_root: bool,
     handles: RwLock<HashMap<u64, LocalHandle>>,
     next_handle: AtomicU64,
 }
@@ -43,26 +54,67 @@ impl LocalFs {
 
         Ok(Self {
             root,
+            follow_symlinks_outside_root: false,
             handles: RwLock::new(HashMap::new()),
             next_handle: AtomicU64::new(1),
         })
     }
 
-    fn resolve_path(&self, path: &str) -> FsResult<PathBuf> {
-        let path = path.trim_start_matches('/');
-        let full_path = if path.is_empty() {
-            self.root.clone()
-        } else {
-            self.root.join(path)
-        };
+    /// Let symlinks under the root point anywhere on the host. `..` still
+    /// cannot climb above the root.
+    #[must_use]
+    pub fn with_follow_symlinks_outside_root(mut self, follow: bool) -> Self {
+        self.follow_symlinks_outside_root = follow;
+        self
+    }
 
-        let canonical = full_path
-            .canonicalize()
-            .or_else(|_| Ok::<_, std::io::Error>(full_path.clone()))
-            .map_err(|e| FsError::internal(e.to_string()))?;
+    /// Map `path` to a host path under the root. With `follow_last` unset
+    /// the final component is not followed if it is a symlink, for calls
+    /// such as `remove` that act on the link itself.
+    fn resolve_path(&self, path: &str, follow_last: bool) -> FsResult<PathBuf> {
+        let escape = || FsError::permission_denied(format!("{path} escapes the root"));
+
+        let mut full_path = self.root.clone();
+        let mut depth = 0usize;
+        for part in path.split('/') {
+            match part {
+                "" | "." => {}
+                ".." => {
+                    depth = depth.checked_sub(1).ok_or_else(escape)?;
+                    full_path.pop();
+                }
+                part => {
+                    depth += 1;
+                    full_path.push(part);
+                }
+            }
+        }
+        if self.follow_symlinks_outside_root || full_path == self.root {
+            return Ok(full_path);
+        }
+
+        // Symlinks among the existing parents resolve through canonicalize;
+        // parents that do not exist yet cannot be symlinks.
+        let parent = full_path.parent().unwrap_or(&self.root);
+        let existing = parent
+            .ancestors()
+            .find_map(|p| p.canonicalize().ok())
+            .ok_or_else(escape)?;
+        if !existing.starts_with(&self.root) {
+            return Err(escape());
+        }
 
-        if !canonical.starts_with(&self.root) && canonical != self.root {
-            return Err(FsError::permission_denied("path escapes root"));
+        if follow_last {
+            let is_symlink =
+                fs::symlink_metadata(&full_path).is_ok_and(|meta| meta.file_type().is_symlink());
+            if is_symlink {
+                // A dangling link cannot be checked, and creating through it
+                // could put a file anywhere.
+                let target = full_path.canonicalize().map_err(|_| escape())?;
+                if !target.starts_with(&self.root) {
+                    return Err(escape());
+                }
+            }
         }
 
         Ok(full_path)
@@ -111,7 +163,7 @@ impl LocalFs {
 #[async_trait]
 impl FsProvider for LocalFs {
     async fn stat(&self, path: &str) -> FsResult<FileInfo> {
-        let full_path = self.resolve_path(path)?;
+        let full_path = self.resolve_path(path, false)?;
 
         let symlink_meta = fs::symlink_metadata(&full_path).map_err(|e| map_io_error(e, path))?;
 
@@ -133,7 +185,10 @@ impl FsProvider for LocalFs {
     }
 
     async fn wstat(&self, path: &str, changes: StatChanges) -> FsResult<()> {
-        let full_path = self.resolve_path(path)?;
+        // Creating or renaming a symlink acts on the link, anything else on
+        // the file it points to.
+        let follow = changes.symlink_target.is_none() && changes.name.is_none();
+        let full_path = self.resolve_path(path, follow)?;
 
         if let Some(target) = changes.symlink_target {
             std::os::unix::fs::symlink(&target, &full_path).map_err(|e| map_io_error(e, path))?;
@@ -142,9 +197,13 @@ impl FsProvider for LocalFs {
 
         if let Some(new_name) = changes.name {
             let new_path = if new_name.starts_with('/') {
-                self.resolve_path(&new_name)?
+                self.resolve_path(&new_name, false)?
             } else {
-                full_path.parent().unwrap_or(&self.root).join(&new_name)
+                let dir = path
+                    .trim_end_matches('/')
+                    .rsplit_once('/')
+                    .map_or("", |(dir, _)| dir);
+                self.resolve_path(&format!("{dir}/{new_name}"), false)?
             };
             fs::rename(&full_path, &new_path).map_err(|e| map_io_error(e, path))?;
             return Ok(());
@@ -236,7 +295,7 @@ impl FsProvider for LocalFs {
     }
 
     async fn open(&self, path: &str, flags: OpenFlags) -> FsResult<(Handle, FileInfo)> {
-        let full_path = self.resolve_path(path)?;
+        let full_path = self.resolve_path(path, true)?;
 
         if flags.create && flags.directory {
             fs::create_dir(&full_path).map_err(|e| map_io_error(e, path))?;
@@ -350,8 +409,26 @@ impl FsProvider for LocalFs {
         Ok(())
     }
 
+    async fn fsync(&self, handle: &Handle, data_only: bool) -> FsResult<()> {
+        let handles = self.handles.read().unwrap();
+        let local_handle = handles
+            .get(&handle.id())
+            .ok_or_else(|| FsError::invalid_handle(handle.id()))?;
+
+        if let Some(file) = &local_handle.file {
+            let result = if data_only {
+                file.sync_data()
+            } else {
+                file.sync_all()
+            };
+            result.map_err(|e| FsError::internal(e.to_string()))?;
+        }
+
+        Ok(())
+    }
+
     async fn readdir(&self, path: &str) -> FsResult<Vec<FileInfo>> {
-        let full_path = self.resolve_path(path)?;
+        let full_path = self.resolve_path(path, true)?;
 
         let entries = fs::read_dir(&full_path).map_err(|e| map_io_error(e, path))?;
 
@@ -384,7 +461,7 @@ impl FsProvider for LocalFs {
     }
 
     async fn remove(&self, path: &str) -> FsResult<()> {
-        let full_path = self.resolve_path(path)?;
+        let full_path = self.resolve_path(path, false)?;
 
         let meta = fs::symlink_metadata(&full_path).map_err(|e| map_io_error(e, path))?;
 
@@ -402,6 +479,7 @@ impl FsProvider for LocalFs {
             | Capabilities::ETAG
             | Capabilities::ATOMIC_RENAME
             | Capabilities::HARDLINK
+            | Capabilities::FSYNC
     }
 }
 
@@ -545,4 +623,106 @@ mod tests {
         let result = fs.stat("/../../../etc/passwd").await;
         assert!(result.is_err());
     }
+
+    fn denied<T>(result: &FsResult<T>) -> bool {
+        matches!(result, Err(FsError::PermissionDenied(_)))
+    }
+
+    #[tokio::test]
+    async fn dotdot_cannot_climb_above_root() {
+        let (temp, fs) = setup().await;
+        fs.open("/sub", OpenFlags::create_dir()).await.unwrap();
+
+        for path in [
+            "/..",
+            "/../etc/passwd",
+            "/sub/../../etc/passwd",
+            "/sub/./../..",
+        ] {
+            assert!(denied(&fs.stat(path).await), "{path}");
+        }
+        assert!(denied(
+            &fs.open("/sub/../../escaped.txt", OpenFlags::create_file())
+                .await
+        ));
+        assert!(denied(
+            &fs.wstat("/sub", StatChanges::rename("../escaped")).await
+        ));
+        assert!(!temp.path().parent().unwrap().join("escaped.txt").exists());
+
+        // `..` that stays inside the root is fine.
+        let (handle, _) = fs
+            .open("/sub/../inside.txt", OpenFlags::create_file())
+            .await
+            .unwrap();
+        fs.close(handle, false).await.unwrap();
+        assert!(temp.path().join("inside.txt").exists());
+    }
+
+    #[tokio::test]
+    async fn absolute_paths_resolve_under_root() {
+        let (temp, fs) = setup().await;
+
+        assert!(matches!(
+            fs.stat("/etc/passwd").await,
+            Err(FsError::NotFound(_))
+        ));
+        assert!(matches!(
+            fs.stat("//etc//passwd").await,
+            Err(FsError::NotFound(_))
+        ));
+        fs.open("/etc", OpenFlags::create_dir()).await.unwrap();
+        let (handle, _) = fs
+            .open("/etc/passwd", OpenFlags::create_file())
+            .await
+            .unwrap();
+        fs.close(handle, false).await.unwrap();
+        assert!(temp.path().join("etc/passwd").exists());
+    }
+
+    #[tokio::test]
+    async fn symlinks_out_of_root_are_rejected() {
+        let (temp, fs) = setup().await;
+        let outside = TempDir::new().unwrap();
+        fs::write(outside.path().join("secret.txt"), "secret").unwrap();
+        let link = |target: &Path, name: &str| {
+            std::os::unix::fs::symlink(target, temp.path().join(name)).unwrap();
+        };
+        link(&outside.path().join("secret.txt"), "secret");
+        link(outside.path(), "out");
+        link(&outside.path().join("new.txt"), "dangling");
+        fs::write(temp.path().join("inside.txt"), "inside").unwrap();
+        link(&temp.path().join("inside.txt"), "inside-link");
+
+        assert!(denied(&fs.open("/secret", OpenFlags::read()).await));
+        assert!(denied(&fs.open("/out/secret.txt", OpenFlags::read()).await));
+        assert!(denied(&fs.readdir("/out").await));
+        assert!(denied(
+            &fs.open("/out/new.txt", OpenFlags::create_file()).await
+        ));
+        assert!(denied(
+            &fs.open("/dangling", OpenFlags::create_file()).await
+        ));
+        assert!(denied(
+            &fs.wstat("/secret", StatChanges::chmod(0o777)).await
+        ));
+        assert!(!outside.path().join("new.txt").exists());
+
+        // Links inside the root work, and escaping links can be inspected
+        // and removed without being followed.
+        let (handle, _) = fs.open("/inside-link", OpenFlags::read()).await.unwrap();
+        assert_eq!(&fs.read(&handle, 0, 64).await.unwrap()[..], b"inside");
+        fs.close(handle, false).await.unwrap();
+        assert!(fs.stat("/secret").await.unwrap().is_symlink());
+        fs.remove("/secret").await.unwrap();
+        assert!(outside.path().join("secret.txt").exists());
+
+        let fs = LocalFs::new(temp.path())
+            .unwrap()
+            .with_follow_symlinks_outside_root(true);
+        let (handle, _) = fs.open("/out/secret.txt", OpenFlags::read()).await.unwrap();
+        assert_eq!(&fs.read(&handle, 0, 64).await.unwrap()[..], b"secret");
+        fs.close(handle, false).await.unwrap();
+        assert!(denied(&fs.stat("/../etc/passwd").await));
+    }
 }
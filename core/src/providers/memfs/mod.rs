@@ -6,12 +6,13 @@ use fs9_sdk::{
 };
 use std::collections::HashMap;
 use std::sync::atomic::{AtomicU64, Ordering};
-use std::sync::RwLock;
+use std::sync::{Arc, RwLock};
 use std::time::SystemTime;
 
 #[derive(Debug, Clone)]
 struct MemFile {
-    content: Vec<u8>,
+    /// Shared with snapshots until either side writes to it.
+    content: Arc<Vec<u8>>,
     mode: u32,
     uid: u32,
     gid: u32,
@@ -24,7 +25,7 @@ impl Default for MemFile {
     fn default() -> Self {
         let now = SystemTime::now();
         Self {
-            content: Vec::new(),
+            content: Arc::default(),
             mode: 0o644,
             uid: 0,
             gid: 0,
@@ -178,8 +179,18 @@ struct OpenHandle {
     flags: OpenFlags,
 }
 
+/// A frozen copy of a [`MemoryFs`] tree, taken with [`MemoryFs::snapshot`].
+///
+/// Taking one and restoring it with
+/// [`MemoryFs::from_snapshot`] share the tree and file contents rather than
+/// copying them; whichever side changes something first gets its own copy.
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    entries: Arc<HashMap<String, MemEntry>>,
+}
+
 pub struct MemoryFs {
-    entries: RwLock<HashMap<String, MemEntry>>,
+    entries: RwLock<Arc<HashMap<String, MemEntry>>>,
     handles: RwLock<HashMap<u64, OpenHandle>>,
     next_handle: AtomicU64,
 }
@@ -193,16 +204,29 @@ impl Default for MemoryFs {
 impl MemoryFs {
     #[must_use]
     pub fn new() -> Self {
-        let fs = Self {
-            entries: RwLock::new(HashMap::new()),
+        let root = HashMap::from([("/".to_string(), MemEntry::Dir(MemDir::default()))]);
+        Self::from_snapshot(&Snapshot {
+            entries: Arc::new(root),
+        })
+    }
+
+    /// Capture the whole tree as it is now. Open handles are not part of it.
+    #[must_use]
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            entries: self.entries.read().unwrap().clone(),
+        }
+    }
+
+    /// A new filesystem holding the tree captured in `snapshot`, with no
+    /// open handles. Changes to it never show up in the snapshot.
+    #[must_use]
+    pub fn from_snapshot(snapshot: &Snapshot) -> Self {
+        Self {
+            entries: RwLock::new(snapshot.entries.clone()),
             handles: RwLock::new(HashMap::new()),
             next_handle: AtomicU64::new(1),
-        };
-        fs.entries
-            .write()
-            .unwrap()
-            .insert("/".to_string(), MemEntry::Dir(MemDir::default()));
-        fs
+        }
     }
 
     fn normalize_path(path: &str) -> String {
@@ -251,7 +275,8 @@ impl FsProvider for MemoryFs {
 
     async fn wstat(&self, path: &str, changes: StatChanges) -> FsResult<()> {
         let path = Self::normalize_path(path);
-        let mut entries = self.entries.write().unwrap();
+        let mut guard = self.entries.write().unwrap();
+        let entries = Arc::make_mut(&mut guard);
 
         if changes.symlink_target.is_some() {
             let target = changes.symlink_target.unwrap();
@@ -340,7 +365,7 @@ impl FsProvider for MemoryFs {
                     f.gid = gid;
                 }
                 if let Some(size) = changes.size {
-                    f.content.resize(size as usize, 0);
+                    Arc::make_mut(&mut f.content).resize(size as usize, 0);
                     f.mtime = now;
                 }
                 if let Some(atime) = changes.atime {
@@ -420,6 +445,7 @@ impl FsProvider for MemoryFs {
 
         let entry = {
             let mut entries = self.entries.write().unwrap();
+            // Lookups alone must not unshare the tree from a snapshot.
 
             if flags.create && flags.directory {
                 if entries.contains_key(&path) {
@@ -430,7 +456,7 @@ impl FsProvider for MemoryFs {
                 if !entries.contains_key(&parent) {
                     return Err(FsError::not_found(&parent));
                 }
-                entries.insert(path.clone(), MemEntry::Dir(MemDir::default()));
+                Arc::make_mut(&mut entries).insert(path.clone(), MemEntry::Dir(MemDir::default()));
             } else if flags.create {
                 if !entries.contains_key(&path) {
                     let parent = Self::parent_path(&path)
@@ -438,10 +464,11 @@ impl FsProvider for MemoryFs {
                     if !entries.contains_key(&parent) {
                         return Err(FsError::not_found(&parent));
                     }
-                    entries.insert(path.clone(), MemEntry::File(MemFile::default()));
+                    Arc::make_mut(&mut entries)
+                        .insert(path.clone(), MemEntry::File(MemFile::default()));
                 } else if flags.truncate {
-                    if let Some(MemEntry::File(f)) = entries.get_mut(&path) {
-                        f.content.clear();
+                    if let Some(MemEntry::File(f)) = Arc::make_mut(&mut entries).get_mut(&path) {
+                        f.content = Arc::default();
                         f.mtime = SystemTime::now();
                     }
                 }
@@ -505,24 +532,25 @@ impl FsProvider for MemoryFs {
         drop(handles);
 
         let mut entries = self.entries.write().unwrap();
-        let entry = entries
+        let entry = Arc::make_mut(&mut entries)
             .get_mut(&path)
             .ok_or_else(|| FsError::not_found(&path))?;
 
         match entry {
             MemEntry::File(f) => {
+                let content = Arc::make_mut(&mut f.content);
                 let write_offset = if is_append {
-                    f.content.len()
+                    content.len()
                 } else {
                     offset as usize
                 };
 
                 let required_len = write_offset + data.len();
-                if f.content.len() < required_len {
-                    f.content.resize(required_len, 0);
+                if content.len() < required_len {
+                    content.resize(required_len, 0);
                 }
 
-                f.content[write_offset..write_offset + data.len()].copy_from_slice(&data);
+                content[write_offset..write_offset + data.len()].copy_from_slice(&data);
                 f.mtime = SystemTime::now();
 
                 Ok(data.len())
@@ -596,7 +624,7 @@ impl FsProvider for MemoryFs {
             }
         }
 
-        entries.remove(&path);
+        Arc::make_mut(&mut entries).remove(&path);
         Ok(())
     }
 
@@ -709,6 +737,28 @@ mod tests {
         assert_eq!(&data[..], b"hello");
     }
 
+    #[tokio::test]
+    async fn truncate_down_then_up_zero_fills() {
+        let fs = MemoryFs::new();
+        let (handle, _) = fs
+            .open("/test.txt", OpenFlags::create_file())
+            .await
+            .unwrap();
+        fs.write(&handle, 0, Bytes::from("hello world"))
+            .await
+            .unwrap();
+        fs.close(handle, false).await.unwrap();
+
+        fs.truncate("/test.txt", 5).await.unwrap();
+        assert_eq!(fs.stat("/test.txt").await.unwrap().size, 5);
+        fs.truncate("/test.txt", 8).await.unwrap();
+        assert_eq!(fs.stat("/test.txt").await.unwrap().size, 8);
+
+        let (handle, _) = fs.open("/test.txt", OpenFlags::read()).await.unwrap();
+        let data = fs.read(&handle, 0, 1024).await.unwrap();
+        assert_eq!(&data[..], b"hello\0\0\0");
+    }
+
     #[tokio::test]
     async fn create_symlink() {
         let fs = MemoryFs::new();
@@ -784,4 +834,84 @@ mod tests {
         let data = fs.read(&handle, 0, 1024).await.unwrap();
         assert_eq!(&data[..], b"hello world");
     }
+
+    async fn write_file(fs: &MemoryFs, path: &str, data: &'static [u8]) {
+        let (handle, _) = fs.open(path, OpenFlags::create_file()).await.unwrap();
+        fs.write(&handle, 0, Bytes::from_static(data))
+            .await
+            .unwrap();
+        fs.close(handle, false).await.unwrap();
+    }
+
+    /// Every entry's metadata, and the contents of every file, in path order.
+    async fn dump(fs: &MemoryFs) -> Vec<(String, Vec<u8>)> {
+        let mut out = Vec::new();
+        let mut pending = vec![fs.stat("/").await.unwrap()];
+        while let Some(info) = pending.pop() {
+            let mut content = Vec::new();
+            match info.file_type {
+                FileType::Directory => pending.extend(fs.readdir(&info.path).await.unwrap()),
+                FileType::Regular => {
+                    let (handle, _) = fs.open(&info.path, OpenFlags::read()).await.unwrap();
+                    content = fs.read(&handle, 0, usize::MAX).await.unwrap().to_vec();
+                    fs.close(handle, false).await.unwrap();
+                }
+                FileType::Symlink => {}
+            }
+            out.push((format!("{info:?}"), content));
+        }
+        out.sort();
+        out
+    }
+
+    #[tokio::test]
+    async fn snapshot_restores_the_tree_exactly() {
+        let fixture = MemoryFs::new();
+        fixture
+            .open("/docs", OpenFlags::create_dir())
+            .await
+            .unwrap();
+        write_file(&fixture, "/docs/a.txt", b"alpha").await;
+        write_file(&fixture, "/b.txt", b"bravo").await;
+        fixture
+            .wstat("/link", StatChanges::symlink("/b.txt"))
+            .await
+            .unwrap();
+        let snapshot = fixture.snapshot();
+        let expected = dump(&fixture).await;
+
+        // Changes after the snapshot, to the original or to a restored copy,
+        // stay out of it.
+        write_file(&fixture, "/docs/a.txt", b"ALPHA").await;
+        fixture.remove("/b.txt").await.unwrap();
+
+        let restored = MemoryFs::from_snapshot(&snapshot);
+        assert_eq!(dump(&restored).await, expected);
+
+        let (handle, _) = restored
+            .open("/docs/a.txt", OpenFlags::append())
+            .await
+            .unwrap();
+        restored
+            .write(&handle, 0, Bytes::from_static(b"!"))
+            .await
+            .unwrap();
+        restored.close(handle, false).await.unwrap();
+        restored
+            .open("/docs/new", OpenFlags::create_dir())
+            .await
+            .unwrap();
+        restored
+            .open("/b.txt", OpenFlags::create_truncate())
+            .await
+            .unwrap();
+        restored
+            .wstat("/link", StatChanges::rename("moved"))
+            .await
+            .unwrap();
+
+        assert_eq!(dump(&MemoryFs::from_snapshot(&snapshot)).await, expected);
+        let a = restored.stat("/docs/a.txt").await.unwrap();
+        assert_eq!(a.size, 6);
+    }
 }
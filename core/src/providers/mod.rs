@@ -1,9 +1,19 @@
+pub mod cachefs;
 pub mod localfs;
 pub mod memfs;
+pub mod nullfs;
+pub mod overlayfs;
+pub mod pool;
 pub mod proxyfs;
+pub mod randomfs;
 pub mod registry;
 
+pub use cachefs::{CacheFs, WriteMode};
 pub use localfs::LocalFs;
 pub use memfs::MemoryFs;
+pub use nullfs::NullFs;
+pub use overlayfs::OverlayFs;
+pub use pool::ProviderPool;
 pub use proxyfs::ProxyFs;
+pub use randomfs::RandomFs;
 pub use registry::{default_registry, ProviderConfig, ProviderFactory, ProviderRegistry};
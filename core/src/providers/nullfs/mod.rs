@@ -0,0 +1,259 @@
+//! `/null` and `/zero`, as in `/dev`: a sink that discards every write and
+//! reads empty, and a source of endless zeros. Useful as benchmark
+//! endpoints and in shell pipelines.
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use fs9_sdk::{
+    Capabilities, FileInfo, FileType, FsError, FsProvider, FsResult, FsStats, Handle, OpenFlags,
+    StatChanges,
+};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::RwLock;
+use std::time::SystemTime;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Device {
+    Null,
+    Zero,
+}
+
+impl Device {
+    fn from_path(path: &str) -> Option<Self> {
+        match path.trim_end_matches('/') {
+            "/null" => Some(Self::Null),
+            "/zero" => Some(Self::Zero),
+            _ => None,
+        }
+    }
+
+    const fn path(self) -> &'static str {
+        match self {
+            Self::Null => "/null",
+            Self::Zero => "/zero",
+        }
+    }
+}
+
+pub struct NullFs {
+    handles: RwLock<HashMap<u64, Device>>,
+    next_handle: AtomicU64,
+    created: SystemTime,
+}
+
+impl Default for NullFs {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl NullFs {
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            handles: RwLock::new(HashMap::new()),
+            next_handle: AtomicU64::new(1),
+            created: SystemTime::now(),
+        }
+    }
+
+    fn info(&self, path: &str, file_type: FileType, mode: u32) -> FileInfo {
+        FileInfo {
+            path: path.to_string(),
+            size: 0,
+            file_type,
+            mode,
+            uid: 0,
+            gid: 0,
+            atime: self.created,
+            mtime: self.created,
+            ctime: self.created,
+            etag: String::new(),
+            symlink_target: None,
+        }
+    }
+
+    fn device(&self, id: u64) -> FsResult<Device> {
+        self.handles
+            .read()
+            .unwrap()
+            .get(&id)
+            .copied()
+            .ok_or_else(|| FsError::invalid_handle(id))
+    }
+}
+
+fn is_root(path: &str) -> bool {
+    path.trim_end_matches('/').is_empty()
+}
+
+#[async_trait]
+impl FsProvider for NullFs {
+    async fn stat(&self, path: &str) -> FsResult<FileInfo> {
+        if is_root(path) {
+            return Ok(self.info("/", FileType::Directory, 0o555));
+        }
+        let device = Device::from_path(path).ok_or_else(|| FsError::not_found(path))?;
+        Ok(self.info(device.path(), FileType::Regular, 0o666))
+    }
+
+    async fn wstat(&self, path: &str, changes: StatChanges) -> FsResult<()> {
+        if Device::from_path(path).is_none() && !is_root(path) {
+            return Err(FsError::not_found(path));
+        }
+        // Truncating or touching a device is a no-op, as with `/dev/null`.
+        let fixed = changes.mode.is_some()
+            || changes.uid.is_some()
+            || changes.gid.is_some()
+            || changes.name.is_some()
+            || changes.symlink_target.is_some();
+        if fixed {
+            return Err(FsError::permission_denied("device attributes are fixed"));
+        }
+        Ok(())
+    }
+
+    async fn statfs(&self, _path: &str) -> FsResult<FsStats> {
+        Ok(FsStats {
+            total_bytes: 0,
+            free_bytes: 0,
+            total_inodes: 3,
+            free_inodes: 0,
+            block_size: 4096,
+            max_name_len: 255,
+        })
+    }
+
+    async fn open(&self, path: &str, flags: OpenFlags) -> FsResult<(Handle, FileInfo)> {
+        let Some(device) = Device::from_path(path) else {
+            if is_root(path) {
+                return Err(FsError::is_directory(path));
+            }
+            if flags.create {
+                return Err(FsError::permission_denied("cannot create files in nullfs"));
+            }
+            return Err(FsError::not_found(path));
+        };
+        if flags.create && flags.exclusive {
+            return Err(FsError::already_exists(path));
+        }
+        if flags.directory {
+            return Err(FsError::not_directory(path));
+        }
+
+        let id = self.next_handle.fetch_add(1, Ordering::Relaxed);
+        self.handles.write().unwrap().insert(id, device);
+        Ok((
+            Handle::new(id),
+            self.info(device.path(), FileType::Regular, 0o666),
+        ))
+    }
+
+    async fn read(&self, handle: &Handle, _offset: u64, size: usize) -> FsResult<Bytes> {
+        Ok(match self.device(handle.id())? {
+            Device::Null => Bytes::new(),
+            Device::Zero => Bytes::from(vec![0u8; size]),
+        })
+    }
+
+    async fn write(&self, handle: &Handle, _offset: u64, data: Bytes) -> FsResult<usize> {
+        self.device(handle.id())?;
+        Ok(data.len())
+    }
+
+    async fn close(&self, handle: Handle, _sync: bool) -> FsResult<()> {
+        self.handles
+            .write()
+            .unwrap()
+            .remove(&handle.id())
+            .map(|_| ())
+            .ok_or_else(|| FsError::invalid_handle(handle.id()))
+    }
+
+    async fn readdir(&self, path: &str) -> FsResult<Vec<FileInfo>> {
+        if !is_root(path) {
+            return Err(match Device::from_path(path) {
+                Some(_) => FsError::not_directory(path),
+                None => FsError::not_found(path),
+            });
+        }
+        Ok([Device::Null, Device::Zero]
+            .into_iter()
+            .map(|d| self.info(d.path(), FileType::Regular, 0o666))
+            .collect())
+    }
+
+    async fn remove(&self, path: &str) -> FsResult<()> {
+        if Device::from_path(path).is_none() && !is_root(path) {
+            return Err(FsError::not_found(path));
+        }
+        Err(FsError::permission_denied("cannot remove a device"))
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities::READ
+            | Capabilities::WRITE
+            | Capabilities::TRUNCATE
+            | Capabilities::DIRECTORY
+            | Capabilities::SYNTHETIC
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn null_swallows_writes_and_reads_empty() {
+        let fs = NullFs::new();
+        let (handle, _) = fs.open("/null", OpenFlags::write()).await.unwrap();
+
+        let chunk = Bytes::from(vec![7u8; 4 * 1024 * 1024]);
+        let mut offset = 0;
+        for _ in 0..16 {
+            let written = fs.write(&handle, offset, chunk.clone()).await.unwrap();
+            assert_eq!(written, chunk.len());
+            offset += written as u64;
+        }
+        assert!(fs.read(&handle, 0, 4096).await.unwrap().is_empty());
+        fs.close(handle, true).await.unwrap();
+        assert_eq!(fs.stat("/null").await.unwrap().size, 0);
+    }
+
+    #[tokio::test]
+    async fn zero_reads_as_many_zeros_as_asked() {
+        let fs = NullFs::new();
+        let (handle, _) = fs.open("/zero", OpenFlags::read()).await.unwrap();
+
+        for (offset, size) in [(0, 1), (0, 65536), (u64::MAX / 2, 1 << 20)] {
+            let data = fs.read(&handle, offset, size).await.unwrap();
+            assert_eq!(data.len(), size);
+            assert!(data.iter().all(|&b| b == 0));
+        }
+        fs.close(handle, false).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn only_the_devices_exist() {
+        let fs = NullFs::new();
+
+        let names: Vec<_> = fs
+            .readdir("/")
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|f| f.path)
+            .collect();
+        assert_eq!(names, ["/null", "/zero"]);
+        assert!(fs.stat("/other").await.unwrap_err().is_not_found());
+        assert!(fs
+            .open("/other", OpenFlags::create_file())
+            .await
+            .unwrap_err()
+            .is_permission_denied());
+        assert!(fs.remove("/null").await.unwrap_err().is_permission_denied());
+        fs.truncate("/null", 0).await.unwrap();
+        fs.open("/null", OpenFlags::create_file()).await.unwrap();
+    }
+}
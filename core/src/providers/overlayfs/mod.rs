@@ -0,0 +1,735 @@
+//! Union of a writable upper provider over read-only lower providers.
+//!
+//! Lookups try the upper layer first and fall through to the lowers in
+//! order. Anything that modifies a lower file first copies the whole file
+//! into the upper layer. Deleting something that exists in a lower layer
+//! leaves a whiteout marker (`.wh.<name>`) in the upper directory, and a
+//! directory recreated or renamed over a lower one gets an opaque marker
+//! (`.wh..wh..opq`) so the lower directory's old contents stay hidden.
+//! Directories that exist in a lower layer cannot be renamed. Markers are
+//! never listed or resolvable through the overlay itself.
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use fs9_sdk::{
+    normalize_path, rename_target, Capabilities, FileInfo, FileType, FsError, FsProvider, FsResult,
+    FsStats, Handle, OpenFlags, StatChanges,
+};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+
+const WHITEOUT_PREFIX: &str = ".wh.";
+const OPAQUE_MARKER: &str = ".wh..wh..opq";
+const COPY_CHUNK: usize = 1024 * 1024;
+
+struct OverlayHandle {
+    provider: Arc<dyn FsProvider>,
+    handle: Handle,
+}
+
+pub struct OverlayFs {
+    upper: Arc<dyn FsProvider>,
+    lowers: Vec<Arc<dyn FsProvider>>,
+    handles: RwLock<HashMap<u64, OverlayHandle>>,
+    next_handle: AtomicU64,
+}
+
+impl OverlayFs {
+    /// `lowers` are searched in order, so put the highest-priority layer first.
+    #[must_use]
+    pub fn new(upper: Arc<dyn FsProvider>, lowers: Vec<Arc<dyn FsProvider>>) -> Self {
+        Self {
+            upper,
+            lowers,
+            handles: RwLock::new(HashMap::new()),
+            next_handle: AtomicU64::new(1),
+        }
+    }
+
+    /// Split into parent directory and final component. The root has no name.
+    fn split(path: &str) -> (&str, &str) {
+        match path.rfind('/') {
+            Some(0) => ("/", &path[1..]),
+            Some(i) => (&path[..i], &path[i + 1..]),
+            None => ("/", path),
+        }
+    }
+
+    fn join(dir: &str, name: &str) -> String {
+        if dir == "/" {
+            format!("/{name}")
+        } else {
+            format!("{dir}/{name}")
+        }
+    }
+
+    fn whiteout_path(path: &str) -> String {
+        let (parent, name) = Self::split(path);
+        Self::join(parent, &format!("{WHITEOUT_PREFIX}{name}"))
+    }
+
+    fn is_marker(path: &str) -> bool {
+        Self::split(path).1.starts_with(WHITEOUT_PREFIX)
+    }
+
+    /// Every directory from the top down to and including `path`, without `/`.
+    fn ancestors(path: &str) -> Vec<String> {
+        let mut out = Vec::new();
+        let mut current = String::new();
+        for part in path.split('/').filter(|p| !p.is_empty()) {
+            current.push('/');
+            current.push_str(part);
+            out.push(current.clone());
+        }
+        out
+    }
+
+    async fn stat_opt(provider: &Arc<dyn FsProvider>, path: &str) -> FsResult<Option<FileInfo>> {
+        match provider.stat(path).await {
+            Ok(info) => Ok(Some(info)),
+            Err(e) if e.is_not_found() => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Whether lower layers may still contribute `path`: no component of it
+    /// is whited out and no ancestor directory in the upper layer is opaque.
+    async fn lower_visible(&self, path: &str) -> FsResult<bool> {
+        for prefix in Self::ancestors(path) {
+            if Self::stat_opt(&self.upper, &Self::whiteout_path(&prefix))
+                .await?
+                .is_some()
+            {
+                return Ok(false);
+            }
+            if prefix != path
+                && Self::stat_opt(&self.upper, &Self::join(&prefix, OPAQUE_MARKER))
+                    .await?
+                    .is_some()
+            {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    async fn lookup_lower(&self, path: &str) -> FsResult<Option<(Arc<dyn FsProvider>, FileInfo)>> {
+        if !self.lower_visible(path).await? {
+            return Ok(None);
+        }
+        for lower in &self.lowers {
+            if let Some(info) = Self::stat_opt(lower, path).await? {
+                return Ok(Some((lower.clone(), info)));
+            }
+        }
+        Ok(None)
+    }
+
+    async fn create_upper(&self, path: &str, flags: OpenFlags) -> FsResult<()> {
+        let (handle, _) = self.upper.open(path, flags).await?;
+        self.upper.close(handle, false).await
+    }
+
+    /// Remove the whiteout for `path`, returning whether there was one.
+    async fn clear_whiteout(&self, path: &str) -> FsResult<bool> {
+        match self.upper.remove(&Self::whiteout_path(path)).await {
+            Ok(()) => Ok(true),
+            Err(e) if e.is_not_found() => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Make sure every directory above `path` exists in the upper layer,
+    /// copying directories up from the lowers as needed.
+    async fn prepare_parent(&self, path: &str) -> FsResult<()> {
+        let (parent, _) = Self::split(path);
+        for dir in Self::ancestors(parent) {
+            match Self::stat_opt(&self.upper, &dir).await? {
+                Some(info) if info.is_dir() => continue,
+                Some(_) => return Err(FsError::not_directory(&dir)),
+                None => {}
+            }
+            match self.lookup_lower(&dir).await? {
+                Some((_, info)) if info.is_dir() => {
+                    self.create_upper(&dir, OpenFlags::create_dir()).await?;
+                    self.copy_mode(&dir, info.mode).await;
+                }
+                Some(_) => return Err(FsError::not_directory(&dir)),
+                None => return Err(FsError::not_found(&dir)),
+            }
+        }
+        Ok(())
+    }
+
+    async fn copy_mode(&self, path: &str, mode: u32) {
+        if self.upper.capabilities().contains(Capabilities::CHMOD) {
+            let _ = self.upper.wstat(path, StatChanges::chmod(mode)).await;
+        }
+    }
+
+    /// Copy `path` from `lower` into the upper layer. With `with_data` unset
+    /// only an empty file is created, for callers about to truncate it.
+    async fn copy_up(
+        &self,
+        path: &str,
+        lower: &Arc<dyn FsProvider>,
+        info: &FileInfo,
+        with_data: bool,
+    ) -> FsResult<()> {
+        self.prepare_parent(path).await?;
+        match info.file_type {
+            FileType::Directory => {
+                self.create_upper(path, OpenFlags::create_dir()).await?;
+            }
+            FileType::Symlink => {
+                let target = info.symlink_target.clone().unwrap_or_default();
+                self.upper.wstat(path, StatChanges::symlink(target)).await?;
+                return Ok(());
+            }
+            FileType::Regular => {
+                let (dst, _) = self.upper.open(path, OpenFlags::create_truncate()).await?;
+                let copied = if with_data {
+                    self.copy_data(lower, path, &dst).await
+                } else {
+                    Ok(())
+                };
+                let closed = self.upper.close(dst, false).await;
+                copied?;
+                closed?;
+            }
+        }
+        self.copy_mode(path, info.mode).await;
+        Ok(())
+    }
+
+    async fn copy_data(
+        &self,
+        lower: &Arc<dyn FsProvider>,
+        path: &str,
+        dst: &Handle,
+    ) -> FsResult<()> {
+        let (src, _) = lower.open(path, OpenFlags::read()).await?;
+        let mut offset = 0u64;
+        let result = loop {
+            let chunk = match lower.read(&src, offset, COPY_CHUNK).await {
+                Ok(chunk) => chunk,
+                Err(e) => break Err(e),
+            };
+            if chunk.is_empty() {
+                break Ok(());
+            }
+            let len = chunk.len() as u64;
+            if let Err(e) = self.upper.write(dst, offset, chunk).await {
+                break Err(e);
+            }
+            offset += len;
+        };
+        let _ = lower.close(src, false).await;
+        result
+    }
+
+    /// Ensure `path` exists in the upper layer, copying it up if it only
+    /// exists below.
+    async fn ensure_upper(&self, path: &str, with_data: bool) -> FsResult<FileInfo> {
+        if let Some(info) = Self::stat_opt(&self.upper, path).await? {
+            return Ok(info);
+        }
+        let (lower, info) = self
+            .lookup_lower(path)
+            .await?
+            .ok_or_else(|| FsError::not_found(path))?;
+        self.copy_up(path, &lower, &info, with_data).await?;
+        Ok(info)
+    }
+
+    async fn write_whiteout(&self, path: &str) -> FsResult<()> {
+        self.prepare_parent(path).await?;
+        self.create_upper(&Self::whiteout_path(path), OpenFlags::create_file())
+            .await
+    }
+
+    fn register(&self, provider: Arc<dyn FsProvider>, handle: Handle) -> Handle {
+        let id = self.next_handle.fetch_add(1, Ordering::SeqCst);
+        self.handles
+            .write()
+            .unwrap()
+            .insert(id, OverlayHandle { provider, handle });
+        Handle::new(id)
+    }
+
+    fn lookup_handle(&self, handle: &Handle) -> FsResult<(Arc<dyn FsProvider>, Handle)> {
+        self.handles
+            .read()
+            .unwrap()
+            .get(&handle.id())
+            .map(|h| (h.provider.clone(), h.handle))
+            .ok_or_else(|| FsError::invalid_handle(handle.id()))
+    }
+}
+
+#[async_trait]
+impl FsProvider for OverlayFs {
+    async fn stat(&self, path: &str) -> FsResult<FileInfo> {
+        let path = normalize_path(path)?;
+        if Self::is_marker(&path) {
+            return Err(FsError::not_found(&path));
+        }
+        if let Some(info) = Self::stat_opt(&self.upper, &path).await? {
+            return Ok(info);
+        }
+        self.lookup_lower(&path)
+            .await?
+            .map(|(_, info)| info)
+            .ok_or_else(|| FsError::not_found(&path))
+    }
+
+    async fn wstat(&self, path: &str, mut changes: StatChanges) -> FsResult<()> {
+        let path = normalize_path(path)?;
+        if Self::is_marker(&path) {
+            return Err(FsError::not_found(&path));
+        }
+
+        if let Some(new_name) = changes.name.take() {
+            let new_path = rename_target(&path, &new_name)?;
+            self.rename(&path, &new_path).await?;
+            if changes.is_empty() {
+                return Ok(());
+            }
+            return self.wstat(&new_path, changes).await;
+        }
+
+        if changes.symlink_target.is_some() {
+            if self.stat(&path).await.is_ok() {
+                return Err(FsError::already_exists(&path));
+            }
+            self.prepare_parent(&path).await?;
+            self.clear_whiteout(&path).await?;
+            return self.upper.wstat(&path, changes).await;
+        }
+
+        self.ensure_upper(&path, changes.size != Some(0)).await?;
+        self.upper.wstat(&path, changes).await
+    }
+
+    async fn statfs(&self, path: &str) -> FsResult<FsStats> {
+        self.upper.statfs(path).await
+    }
+
+    async fn open(&self, path: &str, flags: OpenFlags) -> FsResult<(Handle, FileInfo)> {
+        let path = normalize_path(path)?;
+        if Self::is_marker(&path) {
+            return Err(FsError::permission_denied(format!(
+                "{path} is reserved for overlay whiteouts"
+            )));
+        }
+        let mutating = flags.write || flags.create || flags.truncate || flags.append;
+
+        if !mutating {
+            let provider = if Self::stat_opt(&self.upper, &path).await?.is_some() {
+                self.upper.clone()
+            } else {
+                self.lookup_lower(&path)
+                    .await?
+                    .map(|(lower, _)| lower)
+                    .ok_or_else(|| FsError::not_found(&path))?
+            };
+            let (inner, info) = provider.open(&path, flags).await?;
+            return Ok((self.register(provider, inner), info));
+        }
+
+        let exists_upper = Self::stat_opt(&self.upper, &path).await?.is_some();
+        let lower = if exists_upper {
+            None
+        } else {
+            self.lookup_lower(&path).await?
+        };
+
+        if (exists_upper || lower.is_some()) && flags.create && flags.directory {
+            return Err(FsError::already_exists(&path));
+        }
+
+        let mut opaque = false;
+        if let Some((lower, info)) = lower {
+            self.copy_up(&path, &lower, &info, !flags.truncate).await?;
+        } else if !exists_upper {
+            if !flags.create {
+                return Err(FsError::not_found(&path));
+            }
+            self.prepare_parent(&path).await?;
+            opaque = self.clear_whiteout(&path).await? && flags.directory;
+        }
+
+        let (inner, info) = self.upper.open(&path, flags).await?;
+        if opaque {
+            self.create_upper(&Self::join(&path, OPAQUE_MARKER), OpenFlags::create_file())
+                .await?;
+        }
+        Ok((self.register(self.upper.clone(), inner), info))
+    }
+
+    async fn read(&self, handle: &Handle, offset: u64, size: usize) -> FsResult<Bytes> {
+        let (provider, inner) = self.lookup_handle(handle)?;
+        provider.read(&inner, offset, size).await
+    }
+
+    async fn write(&self, handle: &Handle, offset: u64, data: Bytes) -> FsResult<usize> {
+        let (provider, inner) = self.lookup_handle(handle)?;
+        provider.write(&inner, offset, data).await
+    }
+
+    async fn close(&self, handle: Handle, sync: bool) -> FsResult<()> {
+        let entry = self
+            .handles
+            .write()
+            .unwrap()
+            .remove(&handle.id())
+            .ok_or_else(|| FsError::invalid_handle(handle.id()))?;
+        entry.provider.close(entry.handle, sync).await
+    }
+
+    async fn fsync(&self, handle: &Handle, data_only: bool) -> FsResult<()> {
+        let (provider, inner) = self.lookup_handle(handle)?;
+        provider.fsync(&inner, data_only).await
+    }
+
+    async fn flush(&self) -> FsResult<()> {
+        self.upper.flush().await?;
+        for lower in &self.lowers {
+            lower.flush().await?;
+        }
+        Ok(())
+    }
+
+    async fn health_check(&self) -> FsResult<()> {
+        self.upper.health_check().await?;
+        for lower in &self.lowers {
+            lower.health_check().await?;
+        }
+        Ok(())
+    }
+
+    async fn readdir(&self, path: &str) -> FsResult<Vec<FileInfo>> {
+        let path = normalize_path(path)?;
+        if Self::is_marker(&path) {
+            return Err(FsError::not_found(&path));
+        }
+
+        let mut merged = BTreeMap::new();
+        let mut hidden = HashSet::new();
+        let mut opaque = false;
+
+        let upper = match self.upper.readdir(&path).await {
+            Ok(entries) => Some(entries),
+            Err(e) if e.is_not_found() => None,
+            Err(e) => return Err(e),
+        };
+        let mut found = upper.is_some();
+        for entry in upper.into_iter().flatten() {
+            let name = Self::split(&entry.path).1.to_string();
+            if name == OPAQUE_MARKER {
+                opaque = true;
+            } else if let Some(target) = name.strip_prefix(WHITEOUT_PREFIX) {
+                hidden.insert(target.to_string());
+            } else {
+                merged.insert(name, entry);
+            }
+        }
+
+        if !opaque && self.lower_visible(&path).await? {
+            for lower in &self.lowers {
+                let entries = match lower.readdir(&path).await {
+                    Ok(entries) => entries,
+                    Err(e) if e.is_not_found() => continue,
+                    // The upper layer shadows whatever the lower has here.
+                    Err(FsError::NotDirectory(_)) if found => continue,
+                    Err(e) => return Err(e),
+                };
+                found = true;
+                for entry in entries {
+                    let name = Self::split(&entry.path).1.to_string();
+                    if !hidden.contains(&name) {
+                        merged.entry(name).or_insert(entry);
+                    }
+                }
+            }
+        }
+
+        if !found {
+            return Err(FsError::not_found(&path));
+        }
+        Ok(merged.into_values().collect())
+    }
+
+    async fn remove(&self, path: &str) -> FsResult<()> {
+        let path = normalize_path(path)?;
+        if path == "/" {
+            return Err(FsError::permission_denied("cannot remove root"));
+        }
+        if Self::is_marker(&path) {
+            return Err(FsError::not_found(&path));
+        }
+
+        let upper = Self::stat_opt(&self.upper, &path).await?;
+        let lower = self.lookup_lower(&path).await?;
+        let info = match (&upper, &lower) {
+            (Some(info), _) | (None, Some((_, info))) => info.clone(),
+            (None, None) => return Err(FsError::not_found(&path)),
+        };
+
+        if info.is_dir() {
+            if !self.readdir(&path).await?.is_empty() {
+                return Err(FsError::directory_not_empty(&path));
+            }
+            if upper.is_some() {
+                // Only markers are left; clear them so the upper rmdir succeeds.
+                for entry in self.upper.readdir(&path).await? {
+                    self.upper.remove(&entry.path).await?;
+                }
+            }
+        }
+
+        if upper.is_some() {
+            self.upper.remove(&path).await?;
+        }
+        if lower.is_some() {
+            self.write_whiteout(&path).await?;
+        }
+        Ok(())
+    }
+
+    async fn rename(&self, old_path: &str, new_path: &str) -> FsResult<()> {
+        let old_path = normalize_path(old_path)?;
+        let new_path = normalize_path(new_path)?;
+        if Self::is_marker(&old_path) || Self::is_marker(&new_path) {
+            return Err(FsError::invalid_argument(
+                "overlay whiteout names cannot be renamed",
+            ));
+        }
+
+        let lower = self.lookup_lower(&old_path).await?;
+        // Moving a directory that is merged with, or only in, a lower layer
+        // would leave its lower contents behind; callers copy instead, as
+        // for EXDEV.
+        if lower.as_ref().is_some_and(|(_, info)| info.is_dir()) {
+            return Err(FsError::not_implemented(
+                "rename of a directory from a lower overlay layer",
+            ));
+        }
+        let is_dir = match Self::stat_opt(&self.upper, &old_path).await? {
+            Some(info) => info.is_dir(),
+            None => match &lower {
+                Some((provider, info)) => {
+                    self.copy_up(&old_path, provider, info, true).await?;
+                    false
+                }
+                None => return Err(FsError::not_found(&old_path)),
+            },
+        };
+
+        self.prepare_parent(&new_path).await?;
+        let covers_lower = self.lookup_lower(&new_path).await?.is_some();
+        let cleared = self.clear_whiteout(&new_path).await?;
+        self.upper.rename(&old_path, &new_path).await?;
+        if lower.is_some() {
+            self.write_whiteout(&old_path).await?;
+        }
+        // A directory landing where a lower one was, or still is, must not
+        // show that directory's contents.
+        if is_dir && (cleared || covers_lower) {
+            self.create_upper(
+                &Self::join(&new_path, OPAQUE_MARKER),
+                OpenFlags::create_file(),
+            )
+            .await?;
+        }
+        Ok(())
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        self.upper
+            .capabilities()
+            .difference(Capabilities::HARDLINK | Capabilities::SNAPSHOT)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MemoryFs;
+
+    async fn write_file(fs: &dyn FsProvider, path: &str, data: &str) {
+        let (handle, _) = fs.open(path, OpenFlags::create_truncate()).await.unwrap();
+        fs.write(&handle, 0, Bytes::from(data.to_string()))
+            .await
+            .unwrap();
+        fs.close(handle, false).await.unwrap();
+    }
+
+    async fn read_file(fs: &dyn FsProvider, path: &str) -> String {
+        let (handle, _) = fs.open(path, OpenFlags::read()).await.unwrap();
+        let data = fs.read(&handle, 0, 4096).await.unwrap();
+        fs.close(handle, false).await.unwrap();
+        String::from_utf8(data.to_vec()).unwrap()
+    }
+
+    async fn names(fs: &dyn FsProvider, path: &str) -> Vec<String> {
+        fs.readdir(path)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|e| e.path.rsplit('/').next().unwrap().to_string())
+            .collect()
+    }
+
+    /// Lower layer with `/etc/hosts`, `/etc/motd` and `/bin/sh`.
+    async fn overlay() -> (OverlayFs, Arc<MemoryFs>, Arc<MemoryFs>) {
+        let lower = Arc::new(MemoryFs::new());
+        for dir in ["/etc", "/bin"] {
+            let (h, _) = lower.open(dir, OpenFlags::create_dir()).await.unwrap();
+            lower.close(h, false).await.unwrap();
+        }
+        write_file(lower.as_ref(), "/etc/hosts", "127.0.0.1 localhost").await;
+        write_file(lower.as_ref(), "/etc/motd", "welcome").await;
+        write_file(lower.as_ref(), "/bin/sh", "#!").await;
+
+        let upper = Arc::new(MemoryFs::new());
+        let fs = OverlayFs::new(upper.clone(), vec![lower.clone() as Arc<dyn FsProvider>]);
+        (fs, upper, lower)
+    }
+
+    #[tokio::test]
+    async fn reads_fall_through_to_lower() {
+        let (fs, upper, _) = overlay().await;
+
+        assert_eq!(read_file(&fs, "/etc/hosts").await, "127.0.0.1 localhost");
+        assert!(fs.stat("/bin").await.unwrap().is_dir());
+        assert!(upper.stat("/etc").await.is_err(), "reads must not copy up");
+
+        write_file(upper.as_ref(), "/motd-upper", "x").await;
+        assert_eq!(read_file(&fs, "/motd-upper").await, "x");
+    }
+
+    #[tokio::test]
+    async fn write_copies_whole_file_up() {
+        let (fs, upper, lower) = overlay().await;
+
+        let (handle, _) = fs.open("/etc/hosts", OpenFlags::write()).await.unwrap();
+        fs.write(&handle, 0, Bytes::from("127.0.0.2"))
+            .await
+            .unwrap();
+        fs.close(handle, false).await.unwrap();
+
+        assert_eq!(read_file(&fs, "/etc/hosts").await, "127.0.0.2 localhost");
+        assert_eq!(
+            read_file(upper.as_ref(), "/etc/hosts").await,
+            "127.0.0.2 localhost"
+        );
+        assert_eq!(
+            read_file(lower.as_ref(), "/etc/hosts").await,
+            "127.0.0.1 localhost",
+            "lower layer must stay untouched"
+        );
+    }
+
+    #[tokio::test]
+    async fn readdir_merges_layers_with_upper_shadowing() {
+        let (fs, upper, _) = overlay().await;
+        write_file(&fs, "/etc/motd", "upper motd").await;
+        write_file(&fs, "/etc/resolv.conf", "nameserver 1.1.1.1").await;
+
+        assert_eq!(names(&fs, "/etc").await, ["hosts", "motd", "resolv.conf"]);
+        assert_eq!(names(&fs, "/").await, ["bin", "etc"]);
+
+        let motd = fs
+            .readdir("/etc")
+            .await
+            .unwrap()
+            .into_iter()
+            .find(|e| e.path.ends_with("/motd"))
+            .unwrap();
+        assert_eq!(motd.size, "upper motd".len() as u64);
+        assert_eq!(names(upper.as_ref(), "/etc").await, ["motd", "resolv.conf"]);
+    }
+
+    #[tokio::test]
+    async fn remove_leaves_whiteout_hiding_lower_entry() {
+        let (fs, upper, lower) = overlay().await;
+
+        fs.remove("/etc/motd").await.unwrap();
+        assert!(fs.stat("/etc/motd").await.unwrap_err().is_not_found());
+        assert_eq!(names(&fs, "/etc").await, ["hosts"]);
+        assert!(lower.stat("/etc/motd").await.is_ok());
+        assert!(upper.stat("/etc/.wh.motd").await.is_ok());
+        assert!(
+            fs.stat("/etc/.wh.motd").await.is_err(),
+            "markers stay hidden"
+        );
+
+        // Recreating the file drops the whiteout and starts from scratch.
+        write_file(&fs, "/etc/motd", "new").await;
+        assert_eq!(read_file(&fs, "/etc/motd").await, "new");
+        assert!(upper.stat("/etc/.wh.motd").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn removed_directory_recreated_is_opaque() {
+        let (fs, _, _) = overlay().await;
+
+        assert!(matches!(
+            fs.remove("/bin").await,
+            Err(FsError::DirectoryNotEmpty(_))
+        ));
+        fs.remove("/bin/sh").await.unwrap();
+        fs.remove("/bin").await.unwrap();
+        assert!(fs.stat("/bin/sh").await.is_err());
+
+        let (h, _) = fs.open("/bin", OpenFlags::create_dir()).await.unwrap();
+        fs.close(h, false).await.unwrap();
+        assert!(names(&fs, "/bin").await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn rename_copies_up_and_whites_out_source() {
+        let (fs, _, _) = overlay().await;
+
+        fs.rename("/etc/hosts", "/etc/hosts.bak").await.unwrap();
+        assert!(fs.stat("/etc/hosts").await.is_err());
+        assert_eq!(
+            read_file(&fs, "/etc/hosts.bak").await,
+            "127.0.0.1 localhost"
+        );
+        assert!(matches!(
+            fs.rename("/bin", "/sbin").await,
+            Err(FsError::NotImplemented(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn rename_of_a_merged_directory_is_refused() {
+        let (fs, upper, _) = overlay().await;
+        // /etc now exists in both layers.
+        write_file(&fs, "/etc/extra", "x").await;
+        assert!(upper.stat("/etc").await.unwrap().is_dir());
+
+        assert!(matches!(
+            fs.rename("/etc", "/etc2").await,
+            Err(FsError::NotImplemented(_))
+        ));
+        assert_eq!(read_file(&fs, "/etc/motd").await, "welcome");
+    }
+
+    #[tokio::test]
+    async fn directory_renamed_over_a_whiteout_is_opaque() {
+        let (fs, _, _) = overlay().await;
+        fs.remove("/etc/hosts").await.unwrap();
+        fs.remove("/etc/motd").await.unwrap();
+        fs.remove("/etc").await.unwrap();
+        let (h, _) = fs.open("/new", OpenFlags::create_dir()).await.unwrap();
+        fs.close(h, false).await.unwrap();
+
+        fs.rename("/new", "/etc").await.unwrap();
+        assert!(names(&fs, "/etc").await.is_empty());
+        assert!(fs.stat("/etc/hosts").await.is_err());
+    }
+}
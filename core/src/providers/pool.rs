@@ -0,0 +1,213 @@
+//! Shares one provider instance between mounts of the same configuration.
+//!
+//! Mounting the same backend twice (say, one S3 bucket at two paths, or
+//! again after an unmount) would otherwise build two providers with
+//! separate caches and handle spaces that disagree with each other. The
+//! pool keys live instances by provider name plus canonical config and
+//! hands out clones of the same `Arc`. It only keeps weak references, so
+//! the mounts holding an instance are its reference count: it is dropped
+//! (and a plugin provider destroyed) once the last of them is unmounted.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex, Weak};
+
+use fs9_sdk::FsProvider;
+
+/// Providers whose every instance is a separate, in-process filesystem.
+const NEVER_SHARED: &[&str] = &["memfs", "kv", "pubsubfs", "streamfs"];
+
+pub struct ProviderPool {
+    instances: Mutex<HashMap<(String, String), Weak<dyn FsProvider>>>,
+    never_shared: HashSet<String>,
+}
+
+impl Default for ProviderPool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ProviderPool {
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            instances: Mutex::new(HashMap::new()),
+            never_shared: NEVER_SHARED.iter().map(|s| (*s).to_string()).collect(),
+        }
+    }
+
+    /// Always create fresh instances of `provider`, for providers that keep
+    /// their data in memory and so are distinct filesystems per instance.
+    #[must_use]
+    pub fn never_share(mut self, provider: &str) -> Self {
+        self.never_shared.insert(provider.to_string());
+        self
+    }
+
+    /// The live instance of `provider` built from an equal `config`, or a
+    /// new one from `create`. Key order in `config` does not matter.
+    pub fn get_or_create<F, E>(
+        &self,
+        provider: &str,
+        config: &serde_json::Value,
+        create: F,
+    ) -> Result<Arc<dyn FsProvider>, E>
+    where
+        F: FnOnce() -> Result<Arc<dyn FsProvider>, E>,
+    {
+        if self.never_shared.contains(provider) || in_memory_backend(provider, config) {
+            return create();
+        }
+
+        let key = (provider.to_string(), canonical(config));
+        let mut instances = self.instances.lock().unwrap();
+        if let Some(existing) = instances.get(&key).and_then(Weak::upgrade) {
+            tracing::debug!(provider, "Reusing provider instance");
+            return Ok(existing);
+        }
+
+        // Creating under the lock keeps two racing mounts from building
+        // two instances of the same config.
+        let created = create()?;
+        instances.retain(|_, weak| weak.strong_count() > 0);
+        instances.insert(key, Arc::downgrade(&created));
+        Ok(created)
+    }
+
+    /// Number of distinct instances still referenced by a mount.
+    #[must_use]
+    pub fn live_instances(&self) -> usize {
+        self.instances
+            .lock()
+            .unwrap()
+            .values()
+            .filter(|weak| weak.strong_count() > 0)
+            .count()
+    }
+}
+
+/// Whether `config` selects an in-process backend, as `pagefs` does with a
+/// `memory` backend (also its default when none is given).
+fn in_memory_backend(provider: &str, config: &serde_json::Value) -> bool {
+    config.get("backend").map_or_else(
+        || provider == "pagefs",
+        |backend| backend.get("type").and_then(|t| t.as_str()) == Some("memory"),
+    )
+}
+
+/// `config` serialized with object keys sorted at every level.
+fn canonical(config: &serde_json::Value) -> String {
+    fn sorted(value: &serde_json::Value) -> serde_json::Value {
+        match value {
+            serde_json::Value::Object(obj) => {
+                let mut keys: Vec<_> = obj.keys().collect();
+                keys.sort();
+                serde_json::Value::Object(
+                    keys.into_iter()
+                        .map(|k| (k.clone(), sorted(&obj[k])))
+                        .collect(),
+                )
+            }
+            serde_json::Value::Array(items) => {
+                serde_json::Value::Array(items.iter().map(sorted).collect())
+            }
+            other => other.clone(),
+        }
+    }
+    sorted(config).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::providers::MemoryFs;
+    use fs9_sdk::FsResult;
+    use serde_json::json;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn counting(created: &AtomicUsize) -> impl FnOnce() -> FsResult<Arc<dyn FsProvider>> + '_ {
+        move || {
+            created.fetch_add(1, Ordering::SeqCst);
+            Ok(Arc::new(MemoryFs::new()))
+        }
+    }
+
+    #[test]
+    fn identical_configs_share_one_instance() {
+        let pool = ProviderPool::new();
+        let created = AtomicUsize::new(0);
+
+        let a = pool
+            .get_or_create(
+                "s3",
+                &json!({"bucket": "b", "region": "r"}),
+                counting(&created),
+            )
+            .unwrap();
+        let b = pool
+            .get_or_create(
+                "s3",
+                &json!({"region": "r", "bucket": "b"}),
+                counting(&created),
+            )
+            .unwrap();
+        let other = pool
+            .get_or_create(
+                "s3",
+                &json!({"bucket": "c", "region": "r"}),
+                counting(&created),
+            )
+            .unwrap();
+
+        assert!(Arc::ptr_eq(&a, &b));
+        assert!(!Arc::ptr_eq(&a, &other));
+        assert_eq!(created.load(Ordering::SeqCst), 2);
+        assert_eq!(pool.live_instances(), 2);
+    }
+
+    #[test]
+    fn instance_is_dropped_with_its_last_mount() {
+        let pool = ProviderPool::new();
+        let created = AtomicUsize::new(0);
+        let config = json!({"root": "/data"});
+
+        let a = pool
+            .get_or_create("localfs", &config, counting(&created))
+            .unwrap();
+        let b = pool
+            .get_or_create("localfs", &config, counting(&created))
+            .unwrap();
+        let weak = Arc::downgrade(&a);
+        drop(a);
+        assert!(weak.upgrade().is_some(), "still mounted through b");
+        drop(b);
+        assert!(weak.upgrade().is_none());
+        assert_eq!(pool.live_instances(), 0);
+
+        pool.get_or_create("localfs", &config, counting(&created))
+            .unwrap();
+        assert_eq!(created.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn in_memory_providers_are_never_shared() {
+        let pool = ProviderPool::new().never_share("hello");
+        let created = AtomicUsize::new(0);
+
+        let memory = json!({"backend": {"type": "memory"}});
+        for (provider, config) in [
+            ("memfs", json!({})),
+            ("memfs", json!({})),
+            ("pagefs", json!({})),
+            ("pagefs", json!({})),
+            ("pagefs", memory.clone()),
+            ("pagefs", memory),
+            ("hello", json!({})),
+            ("hello", json!({})),
+        ] {
+            pool.get_or_create(provider, &config, counting(&created))
+                .unwrap();
+        }
+        assert_eq!(created.load(Ordering::SeqCst), 8);
+    }
+}
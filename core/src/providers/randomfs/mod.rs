@@ -0,0 +1,220 @@
+//! `/random`, as in `/dev`: a source of cryptographically random bytes.
+//!
+//! Every read returns fresh bytes from the operating system's generator and
+//! writes are discarded. A benchmark source that compression and dedup
+//! can't shortcut.
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use fs9_sdk::{
+    Capabilities, FileInfo, FileType, FsError, FsProvider, FsResult, FsStats, Handle, OpenFlags,
+    StatChanges,
+};
+use rand::rngs::OsRng;
+use rand::RngCore;
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::RwLock;
+use std::time::SystemTime;
+
+const DEVICE: &str = "/random";
+
+pub struct RandomFs {
+    handles: RwLock<HashSet<u64>>,
+    next_handle: AtomicU64,
+    created: SystemTime,
+}
+
+impl Default for RandomFs {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RandomFs {
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            handles: RwLock::new(HashSet::new()),
+            next_handle: AtomicU64::new(1),
+            created: SystemTime::now(),
+        }
+    }
+
+    fn info(&self, path: &str, file_type: FileType, mode: u32) -> FileInfo {
+        FileInfo {
+            path: path.to_string(),
+            size: 0,
+            file_type,
+            mode,
+            uid: 0,
+            gid: 0,
+            atime: self.created,
+            mtime: self.created,
+            ctime: self.created,
+            etag: String::new(),
+            symlink_target: None,
+        }
+    }
+
+    fn check_handle(&self, id: u64) -> FsResult<()> {
+        if self.handles.read().unwrap().contains(&id) {
+            Ok(())
+        } else {
+            Err(FsError::invalid_handle(id))
+        }
+    }
+}
+
+fn is_root(path: &str) -> bool {
+    path.trim_end_matches('/').is_empty()
+}
+
+fn is_device(path: &str) -> bool {
+    path.trim_end_matches('/') == DEVICE
+}
+
+#[async_trait]
+impl FsProvider for RandomFs {
+    async fn stat(&self, path: &str) -> FsResult<FileInfo> {
+        if is_root(path) {
+            Ok(self.info("/", FileType::Directory, 0o555))
+        } else if is_device(path) {
+            Ok(self.info(DEVICE, FileType::Regular, 0o666))
+        } else {
+            Err(FsError::not_found(path))
+        }
+    }
+
+    async fn wstat(&self, path: &str, changes: StatChanges) -> FsResult<()> {
+        if !is_device(path) && !is_root(path) {
+            return Err(FsError::not_found(path));
+        }
+        let fixed = changes.mode.is_some()
+            || changes.uid.is_some()
+            || changes.gid.is_some()
+            || changes.name.is_some()
+            || changes.symlink_target.is_some();
+        if fixed {
+            return Err(FsError::permission_denied("device attributes are fixed"));
+        }
+        Ok(())
+    }
+
+    async fn statfs(&self, _path: &str) -> FsResult<FsStats> {
+        Ok(FsStats {
+            total_bytes: 0,
+            free_bytes: 0,
+            total_inodes: 2,
+            free_inodes: 0,
+            block_size: 4096,
+            max_name_len: 255,
+        })
+    }
+
+    async fn open(&self, path: &str, flags: OpenFlags) -> FsResult<(Handle, FileInfo)> {
+        if is_root(path) {
+            return Err(FsError::is_directory(path));
+        }
+        if !is_device(path) {
+            if flags.create {
+                return Err(FsError::permission_denied(
+                    "cannot create files in randomfs",
+                ));
+            }
+            return Err(FsError::not_found(path));
+        }
+        if flags.create && flags.exclusive {
+            return Err(FsError::already_exists(path));
+        }
+        if flags.directory {
+            return Err(FsError::not_directory(path));
+        }
+
+        let id = self.next_handle.fetch_add(1, Ordering::Relaxed);
+        self.handles.write().unwrap().insert(id);
+        Ok((Handle::new(id), self.info(DEVICE, FileType::Regular, 0o666)))
+    }
+
+    async fn read(&self, handle: &Handle, _offset: u64, size: usize) -> FsResult<Bytes> {
+        self.check_handle(handle.id())?;
+        let mut data = vec![0u8; size];
+        OsRng
+            .try_fill_bytes(&mut data)
+            .map_err(|e| FsError::internal(format!("random source failed: {e}")))?;
+        Ok(Bytes::from(data))
+    }
+
+    async fn write(&self, handle: &Handle, _offset: u64, data: Bytes) -> FsResult<usize> {
+        self.check_handle(handle.id())?;
+        Ok(data.len())
+    }
+
+    async fn close(&self, handle: Handle, _sync: bool) -> FsResult<()> {
+        if self.handles.write().unwrap().remove(&handle.id()) {
+            Ok(())
+        } else {
+            Err(FsError::invalid_handle(handle.id()))
+        }
+    }
+
+    async fn readdir(&self, path: &str) -> FsResult<Vec<FileInfo>> {
+        if is_root(path) {
+            Ok(vec![self.info(DEVICE, FileType::Regular, 0o666)])
+        } else if is_device(path) {
+            Err(FsError::not_directory(path))
+        } else {
+            Err(FsError::not_found(path))
+        }
+    }
+
+    async fn remove(&self, path: &str) -> FsResult<()> {
+        if !is_device(path) && !is_root(path) {
+            return Err(FsError::not_found(path));
+        }
+        Err(FsError::permission_denied("cannot remove a device"))
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities::READ
+            | Capabilities::WRITE
+            | Capabilities::TRUNCATE
+            | Capabilities::DIRECTORY
+            | Capabilities::SYNTHETIC
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn reads_are_random_and_differ() {
+        let fs = RandomFs::new();
+        let (handle, _) = fs.open("/random", OpenFlags::read()).await.unwrap();
+
+        let first = fs.read(&handle, 0, 4096).await.unwrap();
+        let second = fs.read(&handle, 0, 4096).await.unwrap();
+        assert_eq!(first.len(), 4096);
+        assert_eq!(second.len(), 4096);
+        assert_ne!(first, second);
+        assert!(first.iter().any(|&b| b != 0));
+
+        assert!(fs.read(&handle, 0, 0).await.unwrap().is_empty());
+        fs.close(handle, false).await.unwrap();
+        assert!(fs.read(&handle, 0, 1).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn writes_are_discarded() {
+        let fs = RandomFs::new();
+        let (handle, _) = fs.open("/random", OpenFlags::write()).await.unwrap();
+        let written = fs
+            .write(&handle, 0, Bytes::from_static(b"seed"))
+            .await
+            .unwrap();
+        assert_eq!(written, 4);
+        assert_eq!(fs.stat("/random").await.unwrap().size, 0);
+        assert!(fs.stat("/urandom").await.unwrap_err().is_not_found());
+    }
+}
@@ -89,9 +89,20 @@ pub fn default_registry() -> ProviderRegistry {
         Ok(Arc::new(super::memfs::MemoryFs::new()))
     });
 
+    registry.register("nullfs", |_config| {
+        Ok(Arc::new(super::nullfs::NullFs::new()))
+    });
+
+    registry.register("randomfs", |_config| {
+        Ok(Arc::new(super::randomfs::RandomFs::new()))
+    });
+
     registry.register("localfs", |config| {
         let root = config.get_str("root").unwrap_or_else(|| "/tmp".to_string());
-        let fs = super::localfs::LocalFs::new(root)?;
+        let follow = config
+            .get::<bool>("follow_symlinks_outside_root")
+            .unwrap_or(false);
+        let fs = super::localfs::LocalFs::new(root)?.with_follow_symlinks_outside_root(follow);
         Ok(Arc::new(fs))
     });
 
@@ -134,6 +145,8 @@ mod tests {
         assert!(providers.contains(&"memfs"));
         assert!(providers.contains(&"localfs"));
         assert!(providers.contains(&"proxyfs"));
+        assert!(providers.contains(&"nullfs"));
+        assert!(providers.contains(&"randomfs"));
     }
 
     #[test]
@@ -0,0 +1,74 @@
+//! Per-mount retries of idempotent provider calls, used by
+//! [`VfsRouter`](crate::VfsRouter).
+//!
+//! A backend that is briefly unreachable or throttling fails with an error
+//! for which [`FsError::is_retryable`](fs9_sdk::FsError::is_retryable)
+//! holds, such as `BackendUnavailable`. Calls that can safely run twice
+//! (stat, statfs, read, readdir and the xattr reads) are tried again after
+//! a jittered, capped exponential backoff; writes, creates and other
+//! mutations are never retried, and neither are reads from providers that
+//! serve streams, which consume what they return. A call that ran into the
+//! mount's `op_timeout` is abandoned rather than retried. Each attempt goes
+//! through the mount's circuit breaker, so retries stop as soon as it opens.
+
+use rand::Rng;
+use std::time::Duration;
+
+/// How the router retries idempotent calls to one mount.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Total attempts including the first one; `1` disables retries.
+    pub max_attempts: u32,
+    /// Backoff before the second attempt; doubled for each one after.
+    pub base_backoff: Duration,
+    /// Upper bound for a single backoff, regardless of attempt count.
+    pub max_backoff: Duration,
+}
+
+impl RetryPolicy {
+    #[must_use]
+    pub fn new(max_attempts: u32) -> Self {
+        Self {
+            max_attempts: max_attempts.max(1),
+            base_backoff: Duration::from_millis(50),
+            max_backoff: Duration::from_secs(2),
+        }
+    }
+
+    #[must_use]
+    pub fn with_backoff(mut self, base_backoff: Duration, max_backoff: Duration) -> Self {
+        self.base_backoff = base_backoff;
+        self.max_backoff = max_backoff.max(base_backoff);
+        self
+    }
+
+    /// Jittered exponential backoff after the given (1-based) failed attempt:
+    /// a random delay between half and all of `base_backoff * 2^(attempt-1)`,
+    /// capped at `max_backoff`.
+    pub(crate) fn backoff(&self, attempt: u32) -> Duration {
+        let exp = self
+            .base_backoff
+            .saturating_mul(1u32 << attempt.saturating_sub(1).min(16))
+            .min(self.max_backoff);
+        let half = exp / 2;
+        half + exp
+            .saturating_sub(half)
+            .mul_f64(rand::thread_rng().gen::<f64>())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backoff_doubles_within_jitter_bounds_up_to_the_cap() {
+        let policy = RetryPolicy::new(5)
+            .with_backoff(Duration::from_millis(100), Duration::from_millis(300));
+        for (attempt, full) in [(1, 100), (2, 200), (3, 300), (40, 300)] {
+            let delay = policy.backoff(attempt);
+            assert!(delay >= Duration::from_millis(full / 2), "{delay:?}");
+            assert!(delay <= Duration::from_millis(full), "{delay:?}");
+        }
+    }
+}
@@ -0,0 +1,215 @@
+//! Test doubles for code that drives an [`FsProvider`].
+//!
+//! [`HookedFs`] is a [`MemoryFs`] that counts the calls reaching it and can
+//! run a hook before each kind of call, to make a backend fail, hang or
+//! record what it was asked. Built for this crate's tests and, through the
+//! `test-util` feature, for those of crates depending on it.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Mutex;
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use fs9_sdk::{
+    Capabilities, FileInfo, FsProvider, FsResult, FsStats, Handle, OpenFlags, StatChanges,
+};
+
+use crate::MemoryFs;
+
+/// The kind of provider call a [`HookedFs`] hook runs before.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Op {
+    Stat,
+    Wstat,
+    Statfs,
+    Open,
+    Read,
+    Write,
+    Close,
+    Fsync,
+    Readdir,
+    Remove,
+    Rename,
+}
+
+/// A call as seen by a [`HookedFs`] hook, with the arguments it identifies
+/// its target by.
+#[derive(Debug, Clone, Copy)]
+pub enum Call<'a> {
+    Stat(&'a str),
+    Wstat(&'a str),
+    Statfs(&'a str),
+    Open(&'a str, OpenFlags),
+    Read(&'a Handle),
+    Write(&'a Handle),
+    Close(&'a Handle),
+    Fsync(&'a Handle, bool),
+    Readdir(&'a str),
+    Remove(&'a str),
+    Rename(&'a str, &'a str),
+}
+
+impl Call<'_> {
+    #[must_use]
+    pub const fn op(&self) -> Op {
+        match self {
+            Self::Stat(_) => Op::Stat,
+            Self::Wstat(_) => Op::Wstat,
+            Self::Statfs(_) => Op::Statfs,
+            Self::Open(..) => Op::Open,
+            Self::Read(_) => Op::Read,
+            Self::Write(_) => Op::Write,
+            Self::Close(_) => Op::Close,
+            Self::Fsync(..) => Op::Fsync,
+            Self::Readdir(_) => Op::Readdir,
+            Self::Remove(_) => Op::Remove,
+            Self::Rename(..) => Op::Rename,
+        }
+    }
+}
+
+type HookFuture = Pin<Box<dyn Future<Output = FsResult<()>> + Send>>;
+type Hook = Box<dyn Fn(Call<'_>) -> HookFuture + Send + Sync>;
+
+/// A [`MemoryFs`] with per-operation hooks. A hook that fails stops the
+/// call before it reaches the filesystem. `fsync` has nothing to flush and
+/// only runs its hook.
+pub struct HookedFs {
+    inner: MemoryFs,
+    hooks: HashMap<Op, Hook>,
+    calls: Mutex<HashMap<Op, usize>>,
+    extra_capabilities: Capabilities,
+}
+
+impl Default for HookedFs {
+    fn default() -> Self {
+        Self {
+            inner: MemoryFs::new(),
+            hooks: HashMap::new(),
+            calls: Mutex::new(HashMap::new()),
+            extra_capabilities: Capabilities::empty(),
+        }
+    }
+}
+
+impl HookedFs {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Run `hook` before every `op` call, replacing any earlier hook.
+    #[must_use]
+    pub fn before(
+        self,
+        op: Op,
+        hook: impl Fn(Call<'_>) -> FsResult<()> + Send + Sync + 'static,
+    ) -> Self {
+        self.before_async(op, move |call| std::future::ready(hook(call)))
+    }
+
+    /// Like [`Self::before`], for hooks that need to wait.
+    #[must_use]
+    pub fn before_async<F>(
+        mut self,
+        op: Op,
+        hook: impl Fn(Call<'_>) -> F + Send + Sync + 'static,
+    ) -> Self
+    where
+        F: Future<Output = FsResult<()>> + Send + 'static,
+    {
+        self.hooks
+            .insert(op, Box::new(move |call| Box::pin(hook(call))));
+        self
+    }
+
+    /// Report `capabilities` on top of the ones of [`MemoryFs`].
+    #[must_use]
+    pub fn with_capabilities(mut self, capabilities: Capabilities) -> Self {
+        self.extra_capabilities = capabilities;
+        self
+    }
+
+    /// How many `op` calls have reached this provider, including ones its
+    /// hook failed.
+    pub fn calls(&self, op: Op) -> usize {
+        self.calls
+            .lock()
+            .unwrap()
+            .get(&op)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    async fn hook(&self, call: Call<'_>) -> FsResult<()> {
+        let op = call.op();
+        *self.calls.lock().unwrap().entry(op).or_default() += 1;
+        match self.hooks.get(&op) {
+            Some(hook) => hook(call).await,
+            None => Ok(()),
+        }
+    }
+}
+
+#[async_trait]
+impl FsProvider for HookedFs {
+    async fn stat(&self, path: &str) -> FsResult<FileInfo> {
+        self.hook(Call::Stat(path)).await?;
+        self.inner.stat(path).await
+    }
+
+    async fn wstat(&self, path: &str, changes: StatChanges) -> FsResult<()> {
+        self.hook(Call::Wstat(path)).await?;
+        self.inner.wstat(path, changes).await
+    }
+
+    async fn statfs(&self, path: &str) -> FsResult<FsStats> {
+        self.hook(Call::Statfs(path)).await?;
+        self.inner.statfs(path).await
+    }
+
+    async fn open(&self, path: &str, flags: OpenFlags) -> FsResult<(Handle, FileInfo)> {
+        self.hook(Call::Open(path, flags)).await?;
+        self.inner.open(path, flags).await
+    }
+
+    async fn read(&self, handle: &Handle, offset: u64, size: usize) -> FsResult<Bytes> {
+        self.hook(Call::Read(handle)).await?;
+        self.inner.read(handle, offset, size).await
+    }
+
+    async fn write(&self, handle: &Handle, offset: u64, data: Bytes) -> FsResult<usize> {
+        self.hook(Call::Write(handle)).await?;
+        self.inner.write(handle, offset, data).await
+    }
+
+    async fn close(&self, handle: Handle, sync: bool) -> FsResult<()> {
+        self.hook(Call::Close(&handle)).await?;
+        self.inner.close(handle, sync).await
+    }
+
+    async fn fsync(&self, handle: &Handle, data_only: bool) -> FsResult<()> {
+        self.hook(Call::Fsync(handle, data_only)).await
+    }
+
+    async fn readdir(&self, path: &str) -> FsResult<Vec<FileInfo>> {
+        self.hook(Call::Readdir(path)).await?;
+        self.inner.readdir(path).await
+    }
+
+    async fn remove(&self, path: &str) -> FsResult<()> {
+        self.hook(Call::Remove(path)).await?;
+        self.inner.remove(path).await
+    }
+
+    async fn rename(&self, old_path: &str, new_path: &str) -> FsResult<()> {
+        self.hook(Call::Rename(old_path, new_path)).await?;
+        self.inner.rename(old_path, new_path).await
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        self.inner.capabilities() | self.extra_capabilities
+    }
+}
@@ -1,16 +1,28 @@
 use async_trait::async_trait;
 use bytes::Bytes;
 use fs9_sdk::{
-    Capabilities, FileInfo, FsError, FsProvider, FsResult, FsStats, Handle, OpenFlags, StatChanges,
+    normalize_path, rename_target, Capabilities, DirPage, FileInfo, FileType, FsError, FsProvider,
+    FsResult, FsStats, Handle, OpenFlags, StatChanges,
 };
+use metrics::{counter, histogram};
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
+use std::time::Instant;
+use tokio::sync::broadcast;
 
+use crate::breaker::{CircuitBreakerConfig, CircuitState, MountBreakers};
+use crate::cache::{CacheStats, MetadataCache, MetadataCacheConfig};
+use crate::events::{ChangeEvent, ChangeFeed, ChangeOp};
 use crate::handle::HandleRegistry;
-use crate::mount::MountTable;
+use crate::mount::{MountPoint, MountTable, ResolvedPath};
 
 pub struct VfsRouter {
     mount_table: Arc<MountTable>,
     handle_registry: Arc<HandleRegistry>,
+    cache: Option<MetadataCache>,
+    breakers: Option<MountBreakers>,
+    changes: ChangeFeed,
 }
 
 impl VfsRouter {
@@ -19,9 +31,51 @@ impl VfsRouter {
         Self {
             mount_table,
             handle_registry,
+            cache: None,
+            breakers: None,
+            changes: ChangeFeed::new(),
         }
     }
 
+    /// Serve repeated `stat` and `readdir` calls from memory for up to
+    /// `config.ttl`. Entries are dropped whenever a mutation for the same
+    /// path goes through this router; changes made directly on a provider,
+    /// or by mounting over a cached path, show up once the TTL runs out.
+    #[must_use]
+    pub fn with_metadata_cache(mut self, config: MetadataCacheConfig) -> Self {
+        self.cache = Some(MetadataCache::new(config));
+        self
+    }
+
+    /// Give every mount its own circuit breaker: once calls to a mount fail
+    /// at `config.failure_rate` or worse, further calls fail straight away
+    /// with [`FsError::CircuitBreakerOpen`] until `config.cooldown` has
+    /// passed. Closing handles is never refused.
+    #[must_use]
+    pub fn with_circuit_breaker(mut self, config: CircuitBreakerConfig) -> Self {
+        self.breakers = Some(MountBreakers::new(config));
+        self
+    }
+
+    /// The breaker position for the mount at `mount_path`. Always
+    /// [`CircuitState::Closed`] when breakers are not enabled.
+    pub fn circuit_state(&self, mount_path: &str) -> CircuitState {
+        self.breakers
+            .as_ref()
+            .map_or(CircuitState::Closed, |b| b.state(mount_path))
+    }
+
+    /// Hit/miss counters of the metadata cache, if it is enabled.
+    pub fn cache_stats(&self) -> Option<CacheStats> {
+        self.cache.as_ref().map(MetadataCache::stats)
+    }
+
+    /// Receive a [`ChangeEvent`] for every create, write, remove and rename
+    /// that succeeds through this router from now on.
+    pub fn subscribe_changes(&self) -> broadcast::Receiver<ChangeEvent> {
+        self.changes.subscribe()
+    }
+
     pub fn mount_table(&self) -> &Arc<MountTable> {
         &self.mount_table
     }
@@ -30,23 +84,307 @@ impl VfsRouter {
         &self.handle_registry
     }
 
-    async fn resolve(&self, path: &str) -> FsResult<(Arc<dyn FsProvider>, String)> {
-        self.mount_table.resolve(path).await
+    async fn resolve(&self, path: &str) -> FsResult<ResolvedPath> {
+        self.mount_table.resolve_mount(path).await
+    }
+
+    /// Resolve `path` for the xattr operation `op`, which the provider must
+    /// advertise with `Capabilities::XATTR`.
+    async fn resolve_xattr(&self, path: &str, op: &str) -> FsResult<ResolvedPath> {
+        let r = self.resolve(path).await?;
+        if !r.capabilities().contains(Capabilities::XATTR) {
+            return Err(FsError::not_implemented(op));
+        }
+        Ok(r)
+    }
+
+    /// The mount a handle's path resolves through now, for metric labels and
+    /// the read-only check. `None` once the mount has gone away.
+    async fn handle_mount(&self, path: Option<&str>) -> Option<MountPoint> {
+        let resolved = self.mount_table.resolve_mount(path?).await.ok()?;
+        Some(resolved.mount)
+    }
+
+    /// The metadata cache is keyed by origin path, so that a change made
+    /// through one bind mount is not masked by entries cached under another.
+    async fn cache_key(&self, path: &str) -> Option<String> {
+        self.cache.as_ref()?;
+        Some(self.mount_table.resolve_mount(path).await.ok()?.origin_path)
+    }
+
+    async fn invalidate(&self, path: &str) {
+        if let (Some(cache), Some(key)) = (&self.cache, self.cache_key(path).await) {
+            cache.invalidate(&key);
+        }
+    }
+
+    async fn invalidate_tree(&self, path: &str) {
+        if let (Some(cache), Some(key)) = (&self.cache, self.cache_key(path).await) {
+            cache.invalidate_tree(&key);
+        }
+    }
+}
+
+/// Mounts flagged read-only are enforced here rather than by providers,
+/// so that the flag means the same thing for every provider.
+fn ensure_writable(mount: &MountPoint, path: &str) -> FsResult<()> {
+    if mount.read_only {
+        return Err(FsError::permission_denied(format!(
+            "{path} is on a read-only mount"
+        )));
+    }
+    Ok(())
+}
+
+/// Give up on a provider call once it has run for its mount's `op_timeout`.
+/// See [`MountTable::set_op_timeout`] for what happens to the call itself.
+async fn with_deadline<T>(
+    mount: Option<&MountPoint>,
+    call: impl Future<Output = FsResult<T>>,
+) -> FsResult<T> {
+    let Some((mount, timeout)) = mount.and_then(|m| Some((m, m.op_timeout?))) else {
+        return call.await;
+    };
+    tokio::time::timeout(timeout, call)
+        .await
+        .unwrap_or_else(|_| {
+            tracing::warn!(mount = %mount.path, ?timeout, "provider call timed out");
+            Err(FsError::timeout(timeout))
+        })
+}
+
+/// Time one provider call and count its outcome under `fs9_vfs_*`, labelled
+/// by operation, mount path and provider name. Calls answered from the
+/// metadata cache never reach this.
+async fn observe<T>(
+    op: &'static str,
+    mount: Option<&MountPoint>,
+    call: impl Future<Output = FsResult<T>>,
+) -> FsResult<T> {
+    let started = Instant::now();
+    let result = call.await;
+
+    let labels = [
+        ("op", op.to_string()),
+        (
+            "mount",
+            mount.map_or_else(|| "unknown".to_string(), |m| m.path.clone()),
+        ),
+        (
+            "provider",
+            mount.map_or_else(|| "unknown".to_string(), |m| m.provider_name.clone()),
+        ),
+    ];
+    histogram!("fs9_vfs_op_duration_seconds", &labels).record(started.elapsed().as_secs_f64());
+    counter!("fs9_vfs_ops_total", &labels).increment(1);
+    if let Err(e) = &result {
+        let [op, mount, provider] = labels;
+        let labels = [op, mount, provider, ("code", e.kind().to_string())];
+        counter!("fs9_vfs_errors_total", &labels).increment(1);
+    }
+    result
+}
+
+impl VfsRouter {
+    /// Run a provider call through the mount's circuit breaker, if any, and
+    /// [`observe`] it under the mount's deadline.
+    async fn call<T>(
+        &self,
+        op: &'static str,
+        mount: Option<&MountPoint>,
+        call: impl Future<Output = FsResult<T>>,
+    ) -> FsResult<T> {
+        let (Some(breakers), Some(mount)) = (&self.breakers, mount) else {
+            return observe(op, mount, with_deadline(mount, call)).await;
+        };
+        breakers.admit(mount)?;
+        let result = observe(op, Some(mount), with_deadline(Some(mount), call)).await;
+        breakers.record(mount, &result);
+        result
+    }
+
+    /// [`call`](Self::call) for operations that are safe to repeat, trying
+    /// again after a backoff while the failure is transient and the mount's
+    /// [`RetryPolicy`](crate::RetryPolicy) allows. A call that ran into the
+    /// mount's deadline is not repeated: it may still be running, and every
+    /// retry would leave another one behind.
+    async fn call_idempotent<T, F, Fut>(
+        &self,
+        op: &'static str,
+        mount: Option<&MountPoint>,
+        call: F,
+    ) -> FsResult<T>
+    where
+        F: Fn() -> Fut,
+        Fut: Future<Output = FsResult<T>>,
+    {
+        let Some((mount, policy)) = mount.and_then(|m| Some((m, m.retry?))) else {
+            return self.call(op, mount, call()).await;
+        };
+        let mut attempt = 1;
+        loop {
+            match self.call(op, Some(mount), call()).await {
+                Err(e)
+                    if attempt < policy.max_attempts
+                        && e.is_retryable()
+                        && !matches!(e, FsError::Timeout { .. }) =>
+                {
+                    let delay = policy.backoff(attempt);
+                    tracing::debug!(mount = %mount.path, op, attempt, ?delay, error = %e, "retrying provider call");
+                    counter!(
+                        "fs9_vfs_retries_total",
+                        "op" => op,
+                        "mount" => mount.path.clone(),
+                        "provider" => mount.provider_name.clone()
+                    )
+                    .increment(1);
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Rename between mounts whose providers can't rename into each other:
+    /// stream the file into a temporary sibling of `new_path`, carry over
+    /// mode and mtime where the target supports them, rename the copy into
+    /// place natively and only then remove the source. A target that can't
+    /// rename gets the copy written straight to `new_path` instead.
+    ///
+    /// A failed copy is cleaned up and leaves the source as it was; a
+    /// staged copy also leaves the target as it was, a direct one removes
+    /// whatever it wrote over. If removing the source fails the copy stays
+    /// at `new_path`, the source is left intact and the error is returned.
+    async fn move_across_mounts(&self, old_path: &str, new_path: &str) -> FsResult<()> {
+        let info = self.stat(old_path).await?;
+        if info.file_type != FileType::Regular {
+            return Err(FsError::invalid_argument(
+                "only files can be moved across mount points",
+            ));
+        }
+
+        let target_caps = self.resolve(new_path).await?.capabilities();
+        let written = if target_caps.contains(Capabilities::RENAME) {
+            staging_path(new_path)
+        } else {
+            new_path.to_string()
+        };
+        let copied = match self.copy_file(old_path, &written, &info).await {
+            Ok(()) if written != new_path => self.rename(&written, new_path).await,
+            result => result,
+        };
+        if let Err(e) = copied {
+            let _ = self.remove(&written).await;
+            return Err(e);
+        }
+
+        self.remove(old_path).await.map_err(|e| {
+            tracing::warn!(from = old_path, to = new_path, error = %e, "moved file but could not remove the source");
+            e
+        })
+    }
+
+    async fn copy_file(&self, from: &str, to: &str, info: &FileInfo) -> FsResult<()> {
+        let (dst, _) = self.open(to, OpenFlags::create_truncate()).await?;
+        let copied = self.copy_data(from, &dst).await;
+        let closed = self.close(dst, true).await;
+        copied?;
+        closed?;
+
+        let caps = self.resolve(to).await?.capabilities();
+        let changes = StatChanges {
+            mode: caps.contains(Capabilities::CHMOD).then_some(info.mode),
+            mtime: caps.contains(Capabilities::UTIME).then_some(info.mtime),
+            ..Default::default()
+        };
+        if changes.is_empty() {
+            return Ok(());
+        }
+        self.wstat(to, changes).await
+    }
+
+    async fn copy_data(&self, from: &str, dst: &Handle) -> FsResult<()> {
+        let (src, _) = self.open(from, OpenFlags::read()).await?;
+        let mut offset = 0u64;
+        let result = loop {
+            let chunk = match self.read(&src, offset, COPY_CHUNK).await {
+                Ok(chunk) => chunk,
+                Err(e) => break Err(e),
+            };
+            if chunk.is_empty() {
+                break Ok(());
+            }
+            let len = chunk.len() as u64;
+            if let Err(e) = self.write(dst, offset, chunk).await {
+                break Err(e);
+            }
+            offset += len;
+        };
+        let _ = self.close(src, false).await;
+        result
     }
 }
 
+/// Read size for copies made by cross-mount renames.
+const COPY_CHUNK: usize = 1024 * 1024;
+
+/// A hidden name next to `path` for a cross-mount rename to copy into
+/// before renaming it over `path`.
+fn staging_path(path: &str) -> String {
+    static NEXT: AtomicU64 = AtomicU64::new(0);
+    let (parent, name) = path.rsplit_once('/').unwrap_or(("", path));
+    format!(
+        "{parent}/.{name}.fs9-move-{}-{}",
+        std::process::id(),
+        NEXT.fetch_add(1, Ordering::Relaxed)
+    )
+}
+
+/// Every path is put through [`normalize_path`] before it is resolved, so
+/// providers only ever see canonical mount-relative paths.
 #[async_trait]
 impl FsProvider for VfsRouter {
     async fn stat(&self, path: &str) -> FsResult<FileInfo> {
-        let (provider, relative_path) = self.resolve(path).await?;
-        let mut info = provider.stat(&relative_path).await?;
-        info.path = path.to_string();
-        Ok(info)
+        let path: &str = &normalize_path(path)?;
+        let r = self.resolve(path).await?;
+        let with_path = |mut info: FileInfo| {
+            info.path = path.to_string();
+            info
+        };
+        let call = || r.provider.stat(&r.relative_path);
+
+        let Some(cache) = &self.cache else {
+            return self
+                .call_idempotent("stat", Some(&r.mount), call)
+                .await
+                .map(with_path);
+        };
+        if let Some(cached) = cache.get_stat(&r.origin_path) {
+            return cached.map(with_path);
+        }
+        let generation = cache.generation();
+        let result = self.call_idempotent("stat", Some(&r.mount), call).await;
+        cache.put_stat(&r.origin_path, &result, generation);
+        result.map(with_path)
     }
 
+    /// A change of `name` is carried out by [`rename`](Self::rename), after
+    /// any other changes, so it can cross mount points too.
     async fn wstat(&self, path: &str, mut changes: StatChanges) -> FsResult<()> {
-        let (provider, relative_path) = self.resolve(path).await?;
-        let caps = provider.capabilities();
+        let path: &str = &normalize_path(path)?;
+        if let Some(name) = changes.name.take() {
+            let new_path = rename_target(path, &name)?;
+            if !changes.is_empty() {
+                self.wstat(path, changes).await?;
+            }
+            return self.rename(path, &new_path).await;
+        }
+        let r = self.resolve(path).await?;
+        let caps = r.capabilities();
+        if !changes.is_empty() {
+            ensure_writable(&r.mount, path)?;
+        }
 
         if changes.mode.is_some() && !caps.contains(Capabilities::CHMOD) {
             return Err(FsError::not_implemented("chmod"));
@@ -62,35 +400,43 @@ impl FsProvider for VfsRouter {
         {
             return Err(FsError::not_implemented("utime"));
         }
-        if changes.name.is_some() && !caps.contains(Capabilities::RENAME) {
-            return Err(FsError::not_implemented("rename"));
-        }
         if changes.symlink_target.is_some() && !caps.contains(Capabilities::SYMLINK) {
             return Err(FsError::not_implemented("symlink"));
         }
 
-        // Translate absolute VFS rename target to mount-relative path
-        if let Some(ref new_name) = changes.name {
-            let (target_provider, target_relative) = self.resolve(new_name).await?;
-            if !Arc::ptr_eq(&provider, &target_provider) {
-                return Err(FsError::invalid_argument(
-                    "cannot rename across mount points",
-                ));
-            }
-            changes.name = Some(target_relative);
-        }
+        let change = if changes.symlink_target.is_some() {
+            Some(ChangeOp::Create)
+        } else {
+            changes.size.map(|_| ChangeOp::Write)
+        };
 
-        provider.wstat(&relative_path, changes).await
+        let result = self
+            .call(
+                "wstat",
+                Some(&r.mount),
+                r.provider.wstat(&r.relative_path, changes),
+            )
+            .await;
+        if let (Ok(()), Some(op)) = (&result, change) {
+            self.changes.publish(op, path, None);
+        }
+        self.invalidate(path).await;
+        result
     }
 
     async fn statfs(&self, path: &str) -> FsResult<FsStats> {
-        let (provider, relative_path) = self.resolve(path).await?;
-        provider.statfs(&relative_path).await
+        let path: &str = &normalize_path(path)?;
+        let r = self.resolve(path).await?;
+        self.call_idempotent("statfs", Some(&r.mount), || {
+            r.provider.statfs(&r.relative_path)
+        })
+        .await
     }
 
     async fn open(&self, path: &str, flags: OpenFlags) -> FsResult<(Handle, FileInfo)> {
-        let (provider, relative_path) = self.resolve(path).await?;
-        let caps = provider.capabilities();
+        let path: &str = &normalize_path(path)?;
+        let r = self.resolve(path).await?;
+        let caps = r.capabilities();
 
         if flags.read && !caps.contains(Capabilities::READ) {
             return Err(FsError::not_implemented("read"));
@@ -101,8 +447,24 @@ impl FsProvider for VfsRouter {
         if flags.create && !caps.contains(Capabilities::CREATE) {
             return Err(FsError::not_implemented("create"));
         }
+        let mutating = flags.write || flags.create || flags.truncate || flags.append;
+        if mutating {
+            ensure_writable(&r.mount, path)?;
+        }
 
-        let (provider_handle, mut metadata) = provider.open(&relative_path, flags).await?;
+        let (provider_handle, mut metadata) = self
+            .call(
+                "open",
+                Some(&r.mount),
+                r.provider.open(&r.relative_path, flags),
+            )
+            .await?;
+        if mutating {
+            self.invalidate(path).await;
+        }
+        if flags.create {
+            self.changes.publish(ChangeOp::Create, path, None);
+        }
 
         // Rewrite path to absolute VFS path
         metadata.path = path.to_string();
@@ -110,7 +472,7 @@ impl FsProvider for VfsRouter {
         let handle_id = self
             .handle_registry
             .register(
-                provider.clone(),
+                r.provider,
                 path.to_string(),
                 flags,
                 metadata.clone(),
@@ -130,8 +492,23 @@ impl FsProvider for VfsRouter {
 
         let provider = handle_ref.provider().await?;
         let provider_handle = handle_ref.provider_handle().await?;
+        let mount = self
+            .handle_mount(handle_ref.path().await.ok().as_deref())
+            .await;
 
-        provider.read(&provider_handle, offset, size).await
+        // Reading a stream consumes it, so a retry could skip data that the
+        // failed attempt already took.
+        if provider
+            .capabilities()
+            .intersects(Capabilities::STREAMING | Capabilities::STATEFUL_READ)
+        {
+            let read = provider.read(&provider_handle, offset, size);
+            return self.call("read", mount.as_ref(), read).await;
+        }
+        self.call_idempotent("read", mount.as_ref(), || {
+            provider.read(&provider_handle, offset, size)
+        })
+        .await
     }
 
     async fn write(&self, handle: &Handle, offset: u64, data: Bytes) -> FsResult<usize> {
@@ -143,40 +520,280 @@ impl FsProvider for VfsRouter {
 
         let provider = handle_ref.provider().await?;
         let provider_handle = handle_ref.provider_handle().await?;
+        let path = handle_ref.path().await?;
+        let mount = self.handle_mount(Some(&path)).await;
+        // The mount may have been made read-only after the handle was opened.
+        if let Some(mount) = &mount {
+            ensure_writable(mount, &path)?;
+        }
 
-        provider.write(&provider_handle, offset, data).await
+        let written = self
+            .call(
+                "write",
+                mount.as_ref(),
+                provider.write(&provider_handle, offset, data),
+            )
+            .await;
+        self.invalidate(&path).await;
+        if written.is_ok() {
+            self.changes.publish(ChangeOp::Write, &path, None);
+        }
+        written
     }
 
     async fn close(&self, handle: Handle, sync: bool) -> FsResult<()> {
-        self.handle_registry.close(handle.id(), sync).await
+        let (path, flags) = match self.handle_registry.get(handle.id()).await {
+            Some(handle_ref) => (handle_ref.path().await.ok(), handle_ref.flags().await.ok()),
+            None => (None, None),
+        };
+        let mount = self.handle_mount(path.as_deref()).await;
+
+        let result = observe(
+            "close",
+            mount.as_ref(),
+            with_deadline(
+                mount.as_ref(),
+                self.handle_registry.close(handle.id(), sync),
+            ),
+        )
+        .await;
+        // Providers may only persist buffered writes on close.
+        if let (Some(path), Some(flags)) = (&path, flags) {
+            if flags.write {
+                self.invalidate(path).await;
+            }
+        }
+        result
+    }
+
+    async fn fsync(&self, handle: &Handle, data_only: bool) -> FsResult<()> {
+        let handle_ref = self
+            .handle_registry
+            .get(handle.id())
+            .await
+            .ok_or_else(|| FsError::invalid_handle(handle.id()))?;
+
+        let provider = handle_ref.provider().await?;
+        let mount = self
+            .handle_mount(handle_ref.path().await.ok().as_deref())
+            .await;
+        let masked = mount
+            .as_ref()
+            .map_or(Capabilities::empty(), |m| m.masked_capabilities);
+        if !(provider.capabilities() - masked).contains(Capabilities::FSYNC) {
+            return Err(FsError::not_implemented("fsync"));
+        }
+        let provider_handle = handle_ref.provider_handle().await?;
+
+        self.call(
+            "fsync",
+            mount.as_ref(),
+            provider.fsync(&provider_handle, data_only),
+        )
+        .await
+    }
+
+    async fn flush(&self) -> FsResult<()> {
+        match self.mount_table.flush_all().await.into_iter().next() {
+            Some((_, e)) => Err(e),
+            None => Ok(()),
+        }
     }
 
     async fn readdir(&self, path: &str) -> FsResult<Vec<FileInfo>> {
-        let (provider, relative_path) = self.resolve(path).await?;
-        let entries = provider.readdir(&relative_path).await?;
+        let path: &str = &normalize_path(path)?;
+        let r = self.resolve(path).await?;
+        let call = || r.provider.readdir(&r.relative_path);
 
-        let base_path = if path == "/" { "" } else { path };
-        let entries = entries
-            .into_iter()
-            .map(|mut info| {
-                let name = info.path.rsplit('/').next().unwrap_or(&info.path);
-                info.path = format!("{base_path}/{name}");
-                info
+        let Some(cache) = &self.cache else {
+            let entries = self
+                .call_idempotent("readdir", Some(&r.mount), call)
+                .await?;
+            return Ok(rebase_entries(path, entries));
+        };
+        if let Some(entries) = cache.get_dir(&r.origin_path) {
+            return Ok(rebase_entries(path, entries));
+        }
+        let generation = cache.generation();
+        let entries = self
+            .call_idempotent("readdir", Some(&r.mount), call)
+            .await?;
+        cache.put_dir(&r.origin_path, &entries, generation);
+        Ok(rebase_entries(path, entries))
+    }
+
+    async fn readdir_at(&self, path: &str, cursor: Option<&str>, max: usize) -> FsResult<DirPage> {
+        let path: &str = &normalize_path(path)?;
+        let r = self.resolve(path).await?;
+        let page = self
+            .call_idempotent("readdir_at", Some(&r.mount), || {
+                r.provider.readdir_at(&r.relative_path, cursor, max)
             })
-            .collect();
+            .await?;
 
-        Ok(entries)
+        Ok(DirPage {
+            entries: rebase_entries(path, page.entries),
+            next_cursor: page.next_cursor,
+        })
     }
 
     async fn remove(&self, path: &str) -> FsResult<()> {
-        let (provider, relative_path) = self.resolve(path).await?;
-        let caps = provider.capabilities();
+        let path: &str = &normalize_path(path)?;
+        let r = self.resolve(path).await?;
+        let caps = r.capabilities();
 
         if !caps.contains(Capabilities::DELETE) {
             return Err(FsError::not_implemented("delete"));
         }
+        ensure_writable(&r.mount, path)?;
+
+        let result = self
+            .call(
+                "remove",
+                Some(&r.mount),
+                r.provider.remove(&r.relative_path),
+            )
+            .await;
+        self.invalidate_tree(path).await;
+        if result.is_ok() {
+            self.changes.publish(ChangeOp::Remove, path, None);
+        }
+        result
+    }
+
+    async fn link(&self, existing_path: &str, new_path: &str) -> FsResult<()> {
+        let existing_path: &str = &normalize_path(existing_path)?;
+        let new_path: &str = &normalize_path(new_path)?;
+        let existing = self.resolve(existing_path).await?;
+        if !existing.capabilities().contains(Capabilities::HARDLINK) {
+            return Err(FsError::not_implemented("link"));
+        }
+
+        let target = self.resolve(new_path).await?;
+        if !Arc::ptr_eq(&existing.provider, &target.provider) {
+            return Err(FsError::invalid_argument("cannot link across mount points"));
+        }
+        ensure_writable(&target.mount, new_path)?;
+
+        let result = self
+            .call(
+                "link",
+                Some(&existing.mount),
+                existing
+                    .provider
+                    .link(&existing.relative_path, &target.relative_path),
+            )
+            .await;
+        self.invalidate(existing_path).await;
+        self.invalidate(new_path).await;
+        if result.is_ok() {
+            self.changes.publish(ChangeOp::Create, new_path, None);
+        }
+        result
+    }
+
+    async fn rename(&self, old_path: &str, new_path: &str) -> FsResult<()> {
+        let old_path: &str = &normalize_path(old_path)?;
+        let new_path: &str = &normalize_path(new_path)?;
+        let old = self.resolve(old_path).await?;
+        let target = self.resolve(new_path).await?;
+        ensure_writable(&old.mount, old_path)?;
+        ensure_writable(&target.mount, new_path)?;
+        if !Arc::ptr_eq(&old.provider, &target.provider) {
+            self.move_across_mounts(old_path, new_path).await?;
+            self.changes
+                .publish(ChangeOp::Rename, old_path, Some(new_path));
+            return Ok(());
+        }
+        if !old.capabilities().contains(Capabilities::RENAME) {
+            return Err(FsError::not_implemented("rename"));
+        }
+
+        let result = self
+            .call(
+                "rename",
+                Some(&old.mount),
+                old.provider
+                    .rename(&old.relative_path, &target.relative_path),
+            )
+            .await;
+        self.invalidate_tree(old_path).await;
+        self.invalidate_tree(new_path).await;
+        if result.is_ok() {
+            self.changes
+                .publish(ChangeOp::Rename, old_path, Some(new_path));
+        }
+        result
+    }
+
+    async fn truncate(&self, path: &str, size: u64) -> FsResult<()> {
+        let path: &str = &normalize_path(path)?;
+        let r = self.resolve(path).await?;
+        if !r.capabilities().contains(Capabilities::TRUNCATE) {
+            return Err(FsError::not_implemented("truncate"));
+        }
+        ensure_writable(&r.mount, path)?;
+
+        let result = self
+            .call(
+                "truncate",
+                Some(&r.mount),
+                r.provider.truncate(&r.relative_path, size),
+            )
+            .await;
+        self.invalidate(path).await;
+        if result.is_ok() {
+            self.changes.publish(ChangeOp::Write, path, None);
+        }
+        result
+    }
+
+    async fn getxattr(&self, path: &str, name: &str) -> FsResult<Bytes> {
+        let path: &str = &normalize_path(path)?;
+        let r = self.resolve_xattr(path, "getxattr").await?;
+        self.call_idempotent("getxattr", Some(&r.mount), || {
+            r.provider.getxattr(&r.relative_path, name)
+        })
+        .await
+    }
+
+    async fn setxattr(&self, path: &str, name: &str, value: Bytes) -> FsResult<()> {
+        let path: &str = &normalize_path(path)?;
+        let r = self.resolve_xattr(path, "setxattr").await?;
+        ensure_writable(&r.mount, path)?;
+        let result = self
+            .call(
+                "setxattr",
+                Some(&r.mount),
+                r.provider.setxattr(&r.relative_path, name, value),
+            )
+            .await;
+        self.invalidate(path).await;
+        result
+    }
+
+    async fn listxattr(&self, path: &str) -> FsResult<Vec<String>> {
+        let path: &str = &normalize_path(path)?;
+        let r = self.resolve_xattr(path, "listxattr").await?;
+        self.call_idempotent("listxattr", Some(&r.mount), || {
+            r.provider.listxattr(&r.relative_path)
+        })
+        .await
+    }
 
-        provider.remove(&relative_path).await
+    async fn removexattr(&self, path: &str, name: &str) -> FsResult<()> {
+        let path: &str = &normalize_path(path)?;
+        let r = self.resolve_xattr(path, "removexattr").await?;
+        ensure_writable(&r.mount, path)?;
+        let result = self
+            .call(
+                "removexattr",
+                Some(&r.mount),
+                r.provider.removexattr(&r.relative_path, name),
+            )
+            .await;
+        self.invalidate(path).await;
+        result
     }
 
     fn capabilities(&self) -> Capabilities {
@@ -184,11 +801,25 @@ impl FsProvider for VfsRouter {
     }
 }
 
+/// Re-root provider-relative directory entries under the requested `path`.
+fn rebase_entries(path: &str, entries: Vec<FileInfo>) -> Vec<FileInfo> {
+    let base_path = if path == "/" { "" } else { path };
+    entries
+        .into_iter()
+        .map(|mut info| {
+            let name = info.path.rsplit('/').next().unwrap_or(&info.path);
+            info.path = format!("{base_path}/{name}");
+            info
+        })
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::MemoryFs;
-    use std::time::Duration;
+    use crate::testing::{Call, HookedFs, Op};
+    use crate::{CacheFs, LocalFs, MemoryFs, OverlayFs, RetryPolicy};
+    use std::time::{Duration, UNIX_EPOCH};
 
     fn create_vfs() -> VfsRouter {
         VfsRouter::new(
@@ -280,6 +911,73 @@ mod tests {
         assert!(entries.iter().any(|e| e.path == "/data/dir/b.txt"));
     }
 
+    /// However a path is spelled, every kind of provider sees the same
+    /// canonical one.
+    #[tokio::test]
+    async fn paths_are_normalized_before_dispatch() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let providers: Vec<(&str, Arc<dyn FsProvider>)> = vec![
+            ("memfs", Arc::new(MemoryFs::new())),
+            ("localfs", Arc::new(LocalFs::new(temp.path()).unwrap())),
+            (
+                "overlayfs",
+                Arc::new(OverlayFs::new(
+                    Arc::new(MemoryFs::new()),
+                    vec![Arc::new(MemoryFs::new())],
+                )),
+            ),
+            (
+                "cachefs",
+                Arc::new(CacheFs::new(
+                    Arc::new(MemoryFs::new()),
+                    Arc::new(MemoryFs::new()),
+                    1 << 20,
+                )),
+            ),
+        ];
+
+        for (name, provider) in providers {
+            let vfs = create_vfs();
+            vfs.mount_table().mount("/m", name, provider).await.unwrap();
+
+            vfs.open("/m//dir/", OpenFlags::create_dir()).await.unwrap();
+            let (handle, info) = vfs
+                .open("/m/./dir/../dir//file.txt", OpenFlags::create_file())
+                .await
+                .unwrap();
+            assert_eq!(info.path, "/m/dir/file.txt", "{name}");
+            vfs.write(&handle, 0, Bytes::from("hello")).await.unwrap();
+            vfs.close(handle, false).await.unwrap();
+
+            for spelling in [
+                "/m/dir/file.txt",
+                "//m/dir//file.txt/",
+                "/m/dir/./file.txt",
+                "/m/other/../dir/file.txt",
+                "/elsewhere/../m/dir/file.txt",
+            ] {
+                let info = vfs
+                    .stat(spelling)
+                    .await
+                    .unwrap_or_else(|e| panic!("{name}: {spelling}: {e}"));
+                assert_eq!(info.path, "/m/dir/file.txt", "{name}: {spelling}");
+                assert_eq!(info.size, 5, "{name}: {spelling}");
+            }
+
+            let entries = vfs.readdir("/m/dir/.").await.unwrap();
+            let paths: Vec<&str> = entries.iter().map(|e| e.path.as_str()).collect();
+            assert_eq!(paths, ["/m/dir/file.txt"], "{name}");
+
+            for escaping in ["/..", "/m/../../etc", "/m/dir/../../.."] {
+                let err = vfs.stat(escaping).await.unwrap_err();
+                assert!(
+                    matches!(err, FsError::InvalidArgument(_)),
+                    "{name}: {escaping}: {err}"
+                );
+            }
+        }
+    }
+
     #[tokio::test]
     async fn handle_isolation() {
         let vfs = create_vfs();
@@ -339,4 +1037,790 @@ mod tests {
         let info = vfs.stat("/renamed.txt").await.unwrap();
         assert_eq!(info.size, 5);
     }
+
+    #[tokio::test]
+    async fn link_requires_hardlink_capability() {
+        let vfs = create_vfs();
+        let fs = Arc::new(MemoryFs::new());
+
+        vfs.mount_table().mount("/", "root", fs).await.unwrap();
+
+        let (handle, _) = vfs
+            .open("/test.txt", OpenFlags::create_file())
+            .await
+            .unwrap();
+        vfs.close(handle, false).await.unwrap();
+
+        let result = vfs.link("/test.txt", "/alias.txt").await;
+        assert!(matches!(result, Err(FsError::NotImplemented(_))));
+    }
+
+    #[tokio::test]
+    async fn fsync_reaches_provider_flush_hook() {
+        let vfs = create_vfs();
+        let synced = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let recorded = synced.clone();
+        let fs = Arc::new(
+            HookedFs::new()
+                .with_capabilities(Capabilities::FSYNC)
+                .before(Op::Fsync, move |call| {
+                    if let Call::Fsync(handle, data_only) = call {
+                        recorded.lock().unwrap().push((handle.id(), data_only));
+                    }
+                    Ok(())
+                }),
+        );
+
+        vfs.mount_table()
+            .mount("/", "root", fs.clone())
+            .await
+            .unwrap();
+
+        let (handle, _) = vfs
+            .open("/test.txt", OpenFlags::create_file())
+            .await
+            .unwrap();
+        vfs.write(&handle, 0, Bytes::from_static(b"durable"))
+            .await
+            .unwrap();
+        vfs.fsync(&handle, true).await.unwrap();
+
+        let synced = synced.lock().unwrap().clone();
+        assert_eq!(synced.len(), 1);
+        assert!(synced[0].1);
+
+        vfs.close(handle, false).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn fsync_requires_fsync_capability() {
+        let vfs = create_vfs();
+        let fs = Arc::new(MemoryFs::new());
+
+        vfs.mount_table().mount("/", "root", fs).await.unwrap();
+
+        let (handle, _) = vfs
+            .open("/test.txt", OpenFlags::create_file())
+            .await
+            .unwrap();
+
+        let result = vfs.fsync(&handle, false).await;
+        assert!(matches!(result, Err(FsError::NotImplemented(_))));
+
+        vfs.close(handle, false).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn readdir_at_pages_through_mount() {
+        let vfs = create_vfs();
+        let fs = Arc::new(MemoryFs::new());
+
+        vfs.mount_table().mount("/data", "data", fs).await.unwrap();
+
+        for i in 0..250 {
+            let (handle, _) = vfs
+                .open(&format!("/data/f{i:03}"), OpenFlags::create_file())
+                .await
+                .unwrap();
+            vfs.close(handle, false).await.unwrap();
+        }
+
+        let mut seen = Vec::new();
+        let mut cursor: Option<String> = None;
+        loop {
+            let page = vfs
+                .readdir_at("/data", cursor.as_deref(), 100)
+                .await
+                .unwrap();
+            assert!(page.entries.len() <= 100);
+            seen.extend(page.entries.into_iter().map(|e| e.path));
+            match page.next_cursor {
+                Some(next) => cursor = Some(next),
+                None => break,
+            }
+        }
+
+        let expected: Vec<_> = (0..250).map(|i| format!("/data/f{i:03}")).collect();
+        assert_eq!(seen, expected);
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn async_provider_serves_concurrent_reads_on_one_thread() {
+        let vfs = Arc::new(create_vfs());
+        let fs = Arc::new(MemoryFs::new());
+
+        vfs.mount_table().mount("/", "root", fs).await.unwrap();
+
+        let (handle, _) = vfs
+            .open("/shared.txt", OpenFlags::create_file())
+            .await
+            .unwrap();
+        vfs.write(&handle, 0, Bytes::from_static(b"0123456789"))
+            .await
+            .unwrap();
+        vfs.close(handle, false).await.unwrap();
+
+        // A single-threaded runtime has no spare workers: any provider that
+        // blocked or spun up a runtime per call would stall or panic here.
+        let mut readers = tokio::task::JoinSet::new();
+        for i in 0..32u8 {
+            let vfs = vfs.clone();
+            readers.spawn(async move {
+                let (handle, _) = vfs.open("/shared.txt", OpenFlags::read()).await.unwrap();
+                tokio::task::yield_now().await;
+                let digit = i % 10;
+                let data = vfs.read(&handle, u64::from(digit), 1).await.unwrap();
+                vfs.close(handle, false).await.unwrap();
+                (digit, data)
+            });
+        }
+
+        let mut completed = 0;
+        while let Some(result) = readers.join_next().await {
+            let (digit, data) = result.unwrap();
+            assert_eq!(data[0], b'0' + digit);
+            completed += 1;
+        }
+        assert_eq!(completed, 32);
+    }
+
+    #[tokio::test]
+    async fn rename_across_directories() {
+        let vfs = create_vfs();
+        let fs = Arc::new(MemoryFs::new());
+
+        vfs.mount_table().mount("/", "root", fs).await.unwrap();
+
+        vfs.open("/a", OpenFlags::create_dir()).await.unwrap();
+        vfs.open("/b", OpenFlags::create_dir()).await.unwrap();
+        let (handle, _) = vfs
+            .open("/a/file.txt", OpenFlags::create_file())
+            .await
+            .unwrap();
+        vfs.close(handle, false).await.unwrap();
+
+        vfs.rename("/a/file.txt", "/b/file.txt").await.unwrap();
+
+        assert!(vfs.stat("/a/file.txt").await.is_err());
+        assert!(vfs.stat("/b/file.txt").await.is_ok());
+    }
+
+    async fn write_file(vfs: &VfsRouter, path: &str, data: &'static [u8]) {
+        let (handle, _) = vfs.open(path, OpenFlags::create_truncate()).await.unwrap();
+        vfs.write(&handle, 0, Bytes::from_static(data))
+            .await
+            .unwrap();
+        vfs.close(handle, false).await.unwrap();
+    }
+
+    async fn read_file(vfs: &VfsRouter, path: &str) -> Bytes {
+        let (handle, _) = vfs.open(path, OpenFlags::read()).await.unwrap();
+        let data = vfs.read(&handle, 0, 1024).await.unwrap();
+        vfs.close(handle, false).await.unwrap();
+        data
+    }
+
+    #[tokio::test]
+    async fn rename_across_mounts_copies_then_removes_source() {
+        let vfs = create_vfs();
+        vfs.mount_table()
+            .mount("/", "root", Arc::new(MemoryFs::new()))
+            .await
+            .unwrap();
+        vfs.mount_table()
+            .mount("/other", "other", Arc::new(MemoryFs::new()))
+            .await
+            .unwrap();
+
+        write_file(&vfs, "/test.txt", b"cross-mount").await;
+        let mtime = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        vfs.wstat(
+            "/test.txt",
+            StatChanges {
+                mode: Some(0o600),
+                mtime: Some(mtime),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+
+        vfs.rename("/test.txt", "/other/moved.txt").await.unwrap();
+
+        assert!(matches!(
+            vfs.stat("/test.txt").await,
+            Err(FsError::NotFound(_))
+        ));
+        assert_eq!(read_file(&vfs, "/other/moved.txt").await, "cross-mount");
+        let info = vfs.stat("/other/moved.txt").await.unwrap();
+        assert_eq!(info.mode & 0o777, 0o600);
+        assert_eq!(info.mtime, mtime);
+        // Nothing is left behind from staging the copy.
+        let names: Vec<_> = vfs
+            .readdir("/other")
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|e| e.path)
+            .collect();
+        assert_eq!(names, ["/other/moved.txt"]);
+    }
+
+    #[tokio::test]
+    async fn wstat_rename_crosses_mounts() {
+        let vfs = create_vfs();
+        vfs.mount_table()
+            .mount("/", "root", Arc::new(MemoryFs::new()))
+            .await
+            .unwrap();
+        vfs.mount_table()
+            .mount("/other", "other", Arc::new(MemoryFs::new()))
+            .await
+            .unwrap();
+        write_file(&vfs, "/test.txt", b"via wstat").await;
+
+        vfs.wstat(
+            "/test.txt",
+            StatChanges {
+                mode: Some(0o640),
+                ..StatChanges::rename("/other/moved.txt")
+            },
+        )
+        .await
+        .unwrap();
+
+        assert!(vfs.stat("/test.txt").await.is_err());
+        assert_eq!(read_file(&vfs, "/other/moved.txt").await, "via wstat");
+        let info = vfs.stat("/other/moved.txt").await.unwrap();
+        assert_eq!(info.mode & 0o777, 0o640);
+    }
+
+    #[tokio::test]
+    async fn rename_across_mounts_writes_directly_to_targets_without_rename() {
+        let vfs = create_vfs();
+        vfs.mount_table()
+            .mount("/", "root", Arc::new(MemoryFs::new()))
+            .await
+            .unwrap();
+        vfs.mount_table()
+            .mount("/other", "other", Arc::new(MemoryFs::new()))
+            .await
+            .unwrap();
+        vfs.mount_table()
+            .mask_capabilities("/other", Capabilities::RENAME)
+            .await
+            .unwrap();
+        write_file(&vfs, "/test.txt", b"no rename").await;
+
+        vfs.wstat("/test.txt", StatChanges::rename("/other/test.txt"))
+            .await
+            .unwrap();
+
+        assert!(vfs.stat("/test.txt").await.is_err());
+        assert_eq!(read_file(&vfs, "/other/test.txt").await, "no rename");
+        let names: Vec<_> = vfs
+            .readdir("/other")
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|e| e.path)
+            .collect();
+        assert_eq!(names, ["/other/test.txt"]);
+    }
+
+    #[tokio::test]
+    async fn rename_across_mounts_keeps_source_when_removal_fails() {
+        let vfs = create_vfs();
+        let fail_removes = Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let refuse = fail_removes.clone();
+        let source = Arc::new(HookedFs::new().before(Op::Remove, move |call| match call {
+            Call::Remove(path) if refuse.load(Ordering::SeqCst) => {
+                Err(FsError::permission_denied(path))
+            }
+            _ => Ok(()),
+        }));
+        vfs.mount_table()
+            .mount("/", "root", source.clone())
+            .await
+            .unwrap();
+        vfs.mount_table()
+            .mount("/other", "other", Arc::new(MemoryFs::new()))
+            .await
+            .unwrap();
+        write_file(&vfs, "/test.txt", b"kept").await;
+
+        fail_removes.store(true, Ordering::SeqCst);
+        let result = vfs.rename("/test.txt", "/other/test.txt").await;
+
+        assert!(matches!(result, Err(FsError::PermissionDenied(_))));
+        assert_eq!(read_file(&vfs, "/test.txt").await, "kept");
+    }
+
+    #[tokio::test]
+    async fn rename_across_mounts_refuses_directories() {
+        let vfs = create_vfs();
+        vfs.mount_table()
+            .mount("/", "root", Arc::new(MemoryFs::new()))
+            .await
+            .unwrap();
+        vfs.mount_table()
+            .mount("/other", "other", Arc::new(MemoryFs::new()))
+            .await
+            .unwrap();
+        let (handle, _) = vfs.open("/dir", OpenFlags::create_dir()).await.unwrap();
+        vfs.close(handle, false).await.unwrap();
+
+        let result = vfs.rename("/dir", "/other/dir").await;
+        assert!(matches!(result, Err(FsError::InvalidArgument(_))));
+        vfs.stat("/dir").await.unwrap();
+    }
+
+    async fn cached_vfs(config: MetadataCacheConfig) -> (VfsRouter, Arc<HookedFs>) {
+        let vfs = create_vfs().with_metadata_cache(config);
+        let fs = Arc::new(HookedFs::new());
+        vfs.mount_table()
+            .mount("/", "root", fs.clone())
+            .await
+            .unwrap();
+        let (handle, _) = vfs
+            .open("/file.txt", OpenFlags::create_file())
+            .await
+            .unwrap();
+        vfs.close(handle, false).await.unwrap();
+        (vfs, fs)
+    }
+
+    #[tokio::test]
+    async fn cached_stat_skips_provider_within_ttl() {
+        let (vfs, fs) = cached_vfs(MetadataCacheConfig::new(Duration::from_secs(60))).await;
+
+        vfs.stat("/file.txt").await.unwrap();
+        vfs.stat("/file.txt").await.unwrap();
+        vfs.readdir("/").await.unwrap();
+        vfs.readdir("/").await.unwrap();
+
+        assert_eq!(fs.calls(Op::Stat), 1);
+        assert_eq!(fs.calls(Op::Readdir), 1);
+        assert_eq!(vfs.cache_stats(), Some(CacheStats { hits: 2, misses: 2 }));
+    }
+
+    #[tokio::test]
+    async fn cached_stat_expires_after_ttl() {
+        let (vfs, fs) = cached_vfs(MetadataCacheConfig::new(Duration::from_millis(10))).await;
+
+        vfs.stat("/file.txt").await.unwrap();
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        vfs.stat("/file.txt").await.unwrap();
+
+        assert_eq!(fs.calls(Op::Stat), 2);
+    }
+
+    #[tokio::test]
+    async fn cache_holds_at_most_max_entries() {
+        let config = MetadataCacheConfig::new(Duration::from_secs(60)).with_max_entries(2);
+        let (vfs, fs) = cached_vfs(config).await;
+        for name in ["/a", "/b"] {
+            let (handle, _) = vfs.open(name, OpenFlags::create_file()).await.unwrap();
+            vfs.close(handle, false).await.unwrap();
+        }
+
+        for path in ["/file.txt", "/a", "/b"] {
+            vfs.stat(path).await.unwrap();
+        }
+        let stats = fs.calls(Op::Stat);
+        // The oldest entry made room for the newest.
+        vfs.stat("/b").await.unwrap();
+        vfs.stat("/a").await.unwrap();
+        assert_eq!(fs.calls(Op::Stat), stats);
+        vfs.stat("/file.txt").await.unwrap();
+        assert_eq!(fs.calls(Op::Stat), stats + 1);
+    }
+
+    #[tokio::test]
+    async fn write_invalidates_cached_entry() {
+        let (vfs, fs) = cached_vfs(MetadataCacheConfig::new(Duration::from_secs(60))).await;
+        assert_eq!(vfs.stat("/file.txt").await.unwrap().size, 0);
+        assert_eq!(vfs.readdir("/").await.unwrap()[0].size, 0);
+
+        let (handle, _) = vfs.open("/file.txt", OpenFlags::write()).await.unwrap();
+        vfs.write(&handle, 0, Bytes::from_static(b"fresh"))
+            .await
+            .unwrap();
+        vfs.close(handle, false).await.unwrap();
+
+        assert_eq!(vfs.stat("/file.txt").await.unwrap().size, 5);
+        assert_eq!(vfs.readdir("/").await.unwrap()[0].size, 5);
+        assert_eq!(fs.calls(Op::Stat), 2);
+        assert_eq!(fs.calls(Op::Readdir), 2);
+    }
+
+    #[tokio::test]
+    async fn remove_and_rename_invalidate_cached_entries() {
+        let (vfs, _) = cached_vfs(MetadataCacheConfig::new(Duration::from_secs(60))).await;
+        vfs.stat("/file.txt").await.unwrap();
+
+        vfs.rename("/file.txt", "/moved.txt").await.unwrap();
+        assert!(vfs.stat("/file.txt").await.unwrap_err().is_not_found());
+        vfs.stat("/moved.txt").await.unwrap();
+
+        vfs.remove("/moved.txt").await.unwrap();
+        assert!(vfs.stat("/moved.txt").await.unwrap_err().is_not_found());
+        assert!(vfs.readdir("/").await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn negative_lookups_are_cached_only_when_enabled() {
+        let (vfs, fs) = cached_vfs(MetadataCacheConfig::new(Duration::from_secs(60))).await;
+        vfs.stat("/missing").await.unwrap_err();
+        vfs.stat("/missing").await.unwrap_err();
+        assert_eq!(fs.calls(Op::Stat), 2);
+
+        let config = MetadataCacheConfig::new(Duration::from_secs(60)).with_negative_lookups(true);
+        let (vfs, fs) = cached_vfs(config).await;
+        vfs.stat("/missing").await.unwrap_err();
+        vfs.stat("/missing").await.unwrap_err();
+        assert_eq!(fs.calls(Op::Stat), 1);
+
+        // Creating the file through the router clears the negative entry.
+        let (handle, _) = vfs
+            .open("/missing", OpenFlags::create_file())
+            .await
+            .unwrap();
+        vfs.close(handle, false).await.unwrap();
+        vfs.stat("/missing").await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn bind_mount_serves_same_files() {
+        let vfs = create_vfs();
+        vfs.mount_table()
+            .mount("/a", "mem", Arc::new(MemoryFs::new()))
+            .await
+            .unwrap();
+        vfs.mount_table().bind("/a", "/b").await.unwrap();
+
+        let (handle, _) = vfs.open("/a/x", OpenFlags::create_file()).await.unwrap();
+        vfs.write(&handle, 0, Bytes::from_static(b"shared"))
+            .await
+            .unwrap();
+        vfs.close(handle, false).await.unwrap();
+
+        let (handle, info) = vfs.open("/b/x", OpenFlags::read()).await.unwrap();
+        assert_eq!(info.path, "/b/x");
+        assert_eq!(&vfs.read(&handle, 0, 64).await.unwrap()[..], b"shared");
+        vfs.close(handle, false).await.unwrap();
+
+        let entries = vfs.readdir("/b").await.unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].path, "/b/x");
+        assert_eq!(vfs.stat("/b/x").await.unwrap().path, "/b/x");
+
+        // Renames between the two paths stay inside one provider.
+        vfs.rename("/b/x", "/a/y").await.unwrap();
+        assert!(vfs.stat("/b/y").await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn bind_mount_shares_cache_invalidation() {
+        let vfs =
+            create_vfs().with_metadata_cache(MetadataCacheConfig::new(Duration::from_secs(60)));
+        vfs.mount_table()
+            .mount("/a", "mem", Arc::new(MemoryFs::new()))
+            .await
+            .unwrap();
+        vfs.mount_table().bind("/a", "/b").await.unwrap();
+
+        let (handle, _) = vfs.open("/a/x", OpenFlags::create_file()).await.unwrap();
+        vfs.close(handle, false).await.unwrap();
+        assert_eq!(vfs.stat("/b/x").await.unwrap().size, 0);
+
+        let (handle, _) = vfs.open("/a/x", OpenFlags::write()).await.unwrap();
+        vfs.write(&handle, 0, Bytes::from_static(b"abc"))
+            .await
+            .unwrap();
+        vfs.close(handle, false).await.unwrap();
+
+        assert_eq!(vfs.stat("/b/x").await.unwrap().size, 3);
+    }
+
+    #[tokio::test]
+    async fn masked_capabilities_are_refused() {
+        let vfs = create_vfs();
+        vfs.mount_table()
+            .mount("/", "root", Arc::new(MemoryFs::new()))
+            .await
+            .unwrap();
+        vfs.mount_table()
+            .mask_capabilities("/", Capabilities::RENAME | Capabilities::TRUNCATE)
+            .await
+            .unwrap();
+        write_file(&vfs, "/file.txt", b"data").await;
+
+        let not_implemented = |r: FsResult<()>| matches!(r, Err(FsError::NotImplemented(_)));
+        assert!(not_implemented(vfs.rename("/file.txt", "/moved.txt").await));
+        assert!(not_implemented(vfs.truncate("/file.txt", 0).await));
+        assert!(not_implemented(
+            vfs.wstat("/file.txt", StatChanges::rename("moved.txt"))
+                .await
+        ));
+        // What is not masked keeps working.
+        assert_eq!(read_file(&vfs, "/file.txt").await, "data");
+        vfs.wstat("/file.txt", StatChanges::chmod(0o600))
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn read_only_mount_denies_mutations() {
+        let vfs = create_vfs();
+        let fs = Arc::new(MemoryFs::new());
+        let (h, _) = fs
+            .open("/file.txt", OpenFlags::create_file())
+            .await
+            .unwrap();
+        fs.write(&h, 0, Bytes::from_static(b"data")).await.unwrap();
+        fs.close(h, false).await.unwrap();
+        fs.open("/dir", OpenFlags::create_dir()).await.unwrap();
+        vfs.mount_table().mount("/ro", "mem", fs).await.unwrap();
+        vfs.mount_table().set_read_only("/ro", true).await.unwrap();
+
+        let (handle, _) = vfs.open("/ro/file.txt", OpenFlags::read()).await.unwrap();
+        assert_eq!(&vfs.read(&handle, 0, 64).await.unwrap()[..], b"data");
+        vfs.close(handle, false).await.unwrap();
+        assert_eq!(vfs.stat("/ro/file.txt").await.unwrap().size, 4);
+        assert_eq!(vfs.readdir("/ro").await.unwrap().len(), 2);
+
+        let denied = |r: FsResult<()>| matches!(r, Err(FsError::PermissionDenied(_)));
+        for flags in [
+            OpenFlags::write(),
+            OpenFlags::create_file(),
+            OpenFlags::create_truncate(),
+            OpenFlags::append(),
+            OpenFlags::create_dir(),
+        ] {
+            assert!(denied(vfs.open("/ro/file.txt", flags).await.map(|_| ())));
+        }
+        assert!(denied(vfs.remove("/ro/file.txt").await));
+        assert!(denied(
+            vfs.wstat("/ro/file.txt", StatChanges::chmod(0o600)).await
+        ));
+        assert!(denied(vfs.rename("/ro/file.txt", "/ro/moved.txt").await));
+        assert!(vfs
+            .wstat("/ro/file.txt", StatChanges::default())
+            .await
+            .is_ok());
+        assert_eq!(vfs.stat("/ro/file.txt").await.unwrap().size, 4);
+    }
+
+    #[tokio::test]
+    async fn read_only_applies_to_already_open_handles() {
+        let vfs = create_vfs();
+        vfs.mount_table()
+            .mount("/data", "mem", Arc::new(MemoryFs::new()))
+            .await
+            .unwrap();
+        let (handle, _) = vfs
+            .open("/data/file.txt", OpenFlags::create_file())
+            .await
+            .unwrap();
+
+        vfs.mount_table()
+            .set_read_only("/data", true)
+            .await
+            .unwrap();
+        let result = vfs.write(&handle, 0, Bytes::from_static(b"late")).await;
+        assert!(matches!(result, Err(FsError::PermissionDenied(_))));
+        vfs.close(handle, false).await.unwrap();
+        assert_eq!(vfs.stat("/data/file.txt").await.unwrap().size, 0);
+    }
+
+    #[tokio::test]
+    async fn hung_provider_call_times_out_at_the_mount_deadline() {
+        let (release, hang) = std::sync::mpsc::channel::<()>();
+        let hang = Arc::new(std::sync::Mutex::new(hang));
+        let vfs = create_vfs();
+        // Answers `stat` from a blocking-pool thread, the way plugin calls
+        // run, that waits until the test drops `release`.
+        let fs = Arc::new(HookedFs::new().before_async(Op::Stat, move |_| {
+            let hang = hang.clone();
+            async move {
+                tokio::task::spawn_blocking(move || {
+                    let _ = hang.lock().unwrap().recv();
+                })
+                .await
+                .unwrap();
+                Ok(())
+            }
+        }));
+        vfs.mount_table()
+            .mount("/slow", "hanging", fs.clone())
+            .await
+            .unwrap();
+        vfs.mount_table()
+            .set_op_timeout("/slow", Some(Duration::from_millis(50)))
+            .await
+            .unwrap();
+        vfs.mount_table()
+            .set_retry_policy("/slow", Some(RetryPolicy::new(3)))
+            .await
+            .unwrap();
+        vfs.mount_table().bind("/slow", "/alias").await.unwrap();
+        let (handle, _) = vfs
+            .open("/slow/file.txt", OpenFlags::create_file())
+            .await
+            .unwrap();
+        vfs.close(handle, false).await.unwrap();
+
+        for path in ["/slow/file.txt", "/alias/file.txt"] {
+            let started = Instant::now();
+            let result = vfs.stat(path).await;
+            assert!(matches!(result, Err(FsError::Timeout { .. })));
+            assert!(started.elapsed() < Duration::from_secs(2));
+        }
+        // Timed-out calls are abandoned, not retried.
+        assert_eq!(fs.calls(Op::Stat), 2);
+
+        drop(release);
+        vfs.mount_table()
+            .set_op_timeout("/slow", None)
+            .await
+            .unwrap();
+        assert!(vfs.stat("/slow/file.txt").await.is_ok());
+    }
+
+    /// Fails the next `failures` reads and writes with `BackendUnavailable`.
+    fn flaky_fs(failures: &Arc<std::sync::atomic::AtomicU32>) -> HookedFs {
+        let fail = |failures: Arc<std::sync::atomic::AtomicU32>| {
+            move |_: Call<'_>| match failures.fetch_update(
+                Ordering::SeqCst,
+                Ordering::SeqCst,
+                |n| n.checked_sub(1),
+            ) {
+                Ok(_) => Err(FsError::backend_unavailable("throttled")),
+                Err(_) => Ok(()),
+            }
+        };
+        HookedFs::new()
+            .before(Op::Read, fail(failures.clone()))
+            .before(Op::Write, fail(failures.clone()))
+    }
+
+    /// Reads and writes that reached `fs`, failed or not.
+    fn attempts(fs: &HookedFs) -> usize {
+        fs.calls(Op::Read) + fs.calls(Op::Write)
+    }
+
+    async fn flaky_vfs(vfs: VfsRouter, fs: Arc<HookedFs>) -> (VfsRouter, Handle) {
+        vfs.mount_table().mount("/s3", "flaky", fs).await.unwrap();
+        vfs.mount_table()
+            .set_retry_policy(
+                "/s3",
+                Some(
+                    RetryPolicy::new(3)
+                        .with_backoff(Duration::from_millis(1), Duration::from_millis(5)),
+                ),
+            )
+            .await
+            .unwrap();
+        let (handle, _) = vfs
+            .open("/s3/file.txt", OpenFlags::create_file())
+            .await
+            .unwrap();
+        (vfs, handle)
+    }
+
+    #[tokio::test]
+    async fn transient_read_failures_are_retried() {
+        let failures = Arc::new(std::sync::atomic::AtomicU32::new(0));
+        let fs = Arc::new(flaky_fs(&failures));
+        let (vfs, handle) = flaky_vfs(create_vfs(), fs.clone()).await;
+        vfs.write(&handle, 0, Bytes::from_static(b"data"))
+            .await
+            .unwrap();
+
+        failures.store(2, Ordering::SeqCst);
+        let before = attempts(&fs);
+        assert_eq!(vfs.read(&handle, 0, 4).await.unwrap(), "data");
+        assert_eq!(attempts(&fs) - before, 3);
+
+        failures.store(3, Ordering::SeqCst);
+        let result = vfs.read(&handle, 0, 4).await;
+        assert!(matches!(result, Err(FsError::BackendUnavailable(_))));
+
+        vfs.mount_table()
+            .set_retry_policy("/s3", None)
+            .await
+            .unwrap();
+        failures.store(1, Ordering::SeqCst);
+        assert!(vfs.read(&handle, 0, 4).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn writes_are_never_retried() {
+        let failures = Arc::new(std::sync::atomic::AtomicU32::new(1));
+        let fs = Arc::new(flaky_fs(&failures));
+        let (vfs, handle) = flaky_vfs(create_vfs(), fs.clone()).await;
+        let result = vfs.write(&handle, 0, Bytes::from_static(b"data")).await;
+        assert!(matches!(result, Err(FsError::BackendUnavailable(_))));
+        assert_eq!(attempts(&fs), 1);
+    }
+
+    #[tokio::test]
+    async fn stream_reads_are_never_retried() {
+        let failures = Arc::new(std::sync::atomic::AtomicU32::new(0));
+        let fs = Arc::new(flaky_fs(&failures).with_capabilities(Capabilities::STREAMING));
+        let (vfs, handle) = flaky_vfs(create_vfs(), fs.clone()).await;
+        failures.store(1, Ordering::SeqCst);
+        let result = vfs.read(&handle, 0, 4).await;
+        assert!(matches!(result, Err(FsError::BackendUnavailable(_))));
+        assert_eq!(attempts(&fs), 1);
+    }
+
+    #[tokio::test]
+    async fn retries_stop_once_the_breaker_opens() {
+        let failures = Arc::new(std::sync::atomic::AtomicU32::new(u32::MAX));
+        let fs = Arc::new(flaky_fs(&failures));
+        let breaker = CircuitBreakerConfig::new(Duration::from_secs(60), Duration::from_secs(60))
+            .with_min_calls(1);
+        let (vfs, handle) = flaky_vfs(create_vfs().with_circuit_breaker(breaker), fs.clone()).await;
+        let result = vfs.read(&handle, 0, 4).await;
+        assert!(matches!(result, Err(FsError::CircuitBreakerOpen { .. })));
+        assert_eq!(attempts(&fs), 1);
+    }
+
+    #[tokio::test]
+    async fn successful_mutations_are_published() {
+        let vfs = create_vfs();
+        vfs.mount_table()
+            .mount("/", "root", Arc::new(MemoryFs::new()))
+            .await
+            .unwrap();
+        let mut changes = vfs.subscribe_changes();
+
+        let (handle, _) = vfs.open("/a.txt", OpenFlags::create_file()).await.unwrap();
+        vfs.write(&handle, 0, Bytes::from("hi")).await.unwrap();
+        vfs.close(handle, false).await.unwrap();
+        vfs.stat("/a.txt").await.unwrap();
+        vfs.rename("/a.txt", "/b.txt").await.unwrap();
+        assert!(vfs.remove("/missing.txt").await.is_err());
+        vfs.remove("/b.txt").await.unwrap();
+
+        let mut seen = Vec::new();
+        while let Ok(event) = changes.try_recv() {
+            seen.push((event.op, event.path, event.new_path));
+        }
+        assert_eq!(
+            seen,
+            vec![
+                (ChangeOp::Create, "/a.txt".to_string(), None),
+                (ChangeOp::Write, "/a.txt".to_string(), None),
+                (
+                    ChangeOp::Rename,
+                    "/a.txt".to_string(),
+                    Some("/b.txt".to_string())
+                ),
+                (ChangeOp::Remove, "/b.txt".to_string(), None),
+            ]
+        );
+    }
 }
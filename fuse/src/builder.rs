@@ -16,6 +16,20 @@ pub struct MountOptions {
     pub allow_root: bool,
     pub auto_unmount: bool,
     pub read_only: bool,
+    /// Largest read request the kernel may send, in bytes.
+    pub max_read: Option<u32>,
+    /// Largest write request the kernel may send, in bytes. Negotiated during
+    /// FUSE init; kernels without large-page support cap it at 128 KiB.
+    pub max_write: Option<u32>,
+    /// Let the kernel cache writes and flush them in batches.
+    ///
+    /// Writes return as soon as they reach the page cache and are pushed to
+    /// the provider later, coalesced into large sequential extents. Data is
+    /// only guaranteed to reach FS9 on `fsync`, `close` or unmount, so other
+    /// clients may see stale contents until then, and write errors surface on
+    /// a later `write`, `fsync` or `close` rather than the call that caused
+    /// them.
+    pub writeback_cache: bool,
 }
 
 impl MountOptions {
@@ -42,6 +56,9 @@ impl MountOptions {
         if self.read_only {
             options.push(MountOption::RO);
         }
+        if let Some(max_read) = self.max_read {
+            options.push(MountOption::CUSTOM(format!("max_read={max_read}")));
+        }
         options
     }
 }
@@ -136,6 +153,24 @@ impl Fs9FuseBuilder {
         self
     }
 
+    #[must_use]
+    pub const fn max_read(mut self, bytes: u32) -> Self {
+        self.mount_options.max_read = Some(bytes);
+        self
+    }
+
+    #[must_use]
+    pub const fn max_write(mut self, bytes: u32) -> Self {
+        self.mount_options.max_write = Some(bytes);
+        self
+    }
+
+    #[must_use]
+    pub const fn writeback_cache(mut self, enabled: bool) -> Self {
+        self.mount_options.writeback_cache = enabled;
+        self
+    }
+
     /// Build the [`Fs9Fuse`] instance and mount options without mounting.
     ///
     /// For advanced use cases where you want to control the FUSE session
@@ -148,6 +183,10 @@ impl Fs9FuseBuilder {
             self.uid,
             self.gid,
             self.cache_ttl,
+        )
+        .io_options(
+            self.mount_options.max_write,
+            self.mount_options.writeback_cache,
         );
         let options = self.mount_options.to_fuser_options();
         (fs, options)
@@ -202,6 +241,9 @@ mod tests {
         assert!(!opts.allow_root);
         assert!(!opts.auto_unmount);
         assert!(!opts.read_only);
+        assert!(opts.max_read.is_none());
+        assert!(opts.max_write.is_none());
+        assert!(!opts.writeback_cache);
     }
 
     #[test]
@@ -219,12 +261,27 @@ mod tests {
             allow_root: true,
             auto_unmount: true,
             read_only: true,
+            ..MountOptions::default()
         };
         let fuser_opts = opts.to_fuser_options();
         // Base (3) + AllowOther + AllowRoot + AutoUnmount + RO = 7
         assert_eq!(fuser_opts.len(), 7);
     }
 
+    #[test]
+    fn test_mount_options_max_read() {
+        let opts = MountOptions {
+            max_read: Some(1 << 20),
+            max_write: Some(1 << 20),
+            writeback_cache: true,
+            ..MountOptions::default()
+        };
+        let fuser_opts = opts.to_fuser_options();
+        // max_write and writeback are negotiated in init, not passed to mount.
+        assert_eq!(fuser_opts.len(), 4);
+        assert!(fuser_opts.contains(&MountOption::CUSTOM("max_read=1048576".to_string())));
+    }
+
     #[test]
     fn test_builder_setters() {
         // We can't fully test without a real client, but we can verify
@@ -238,6 +295,9 @@ mod tests {
                 .allow_root(false)
                 .auto_unmount(true)
                 .read_only(false)
+                .max_read(1 << 20)
+                .max_write(1 << 20)
+                .writeback_cache(true)
         };
     }
 }
@@ -1,20 +1,27 @@
+use std::collections::HashMap;
 use std::ffi::OsStr;
 use std::sync::Arc;
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
-use fs9_client::{Fs9Client, OpenFlags};
+use fs9_client::{FileHandle, Fs9Client, FsStats, OpenFlags};
 use fuser::{
-    FileAttr, FileType, Filesystem, ReplyAttr, ReplyCreate, ReplyData, ReplyDirectory, ReplyEmpty,
-    ReplyEntry, ReplyOpen, ReplyStatfs, ReplyWrite, Request,
+    FileAttr, FileType, Filesystem, KernelConfig, ReplyAttr, ReplyCreate, ReplyData,
+    ReplyDirectory, ReplyEmpty, ReplyEntry, ReplyOpen, ReplyStatfs, ReplyWrite, ReplyXattr,
+    Request,
 };
 use tokio::runtime::Handle as TokioHandle;
 use tracing::{debug, error, warn};
 
 use crate::handle::HandleTable;
 use crate::inode::{InodeTable, ROOT_INO};
+use crate::writeback::{WriteBuffer, WRITEBACK_FLUSH_BYTES};
 
 const BLOCK_SIZE: u32 = 4096;
 
+/// `FUSE_WRITEBACK_CACHE` init capability; fuser only exports it behind the
+/// `abi-7-23` feature.
+const FUSE_WRITEBACK_CACHE: u32 = 1 << 16;
+
 pub struct Fs9Fuse {
     client: Arc<Fs9Client>,
     inodes: InodeTable,
@@ -23,6 +30,10 @@ pub struct Fs9Fuse {
     uid: u32,
     gid: u32,
     ttl: Duration,
+    max_write: Option<u32>,
+    writeback_cache: bool,
+    /// Dirty data per open handle (`fh`), with the inode it belongs to.
+    dirty: HashMap<u64, (u64, WriteBuffer)>,
 }
 
 impl Fs9Fuse {
@@ -41,9 +52,21 @@ impl Fs9Fuse {
             uid,
             gid,
             ttl: cache_ttl,
+            max_write: None,
+            writeback_cache: false,
+            dirty: HashMap::new(),
         }
     }
 
+    /// Set the write size requested from the kernel and whether writes go
+    /// through the kernel's writeback cache.
+    #[must_use]
+    pub fn io_options(mut self, max_write: Option<u32>, writeback_cache: bool) -> Self {
+        self.max_write = max_write;
+        self.writeback_cache = writeback_cache;
+        self
+    }
+
     fn block_on<F: std::future::Future>(&self, f: F) -> F::Output {
         self.runtime.block_on(f)
     }
@@ -101,12 +124,65 @@ impl Fs9Fuse {
         }
     }
 
+    /// Write all of `data` at `offset`, looping over short writes.
+    fn write_all(&self, handle: &FileHandle, offset: u64, data: &[u8]) -> Result<(), i32> {
+        let mut done = 0;
+        while done < data.len() {
+            let at = offset + done as u64;
+            match self.block_on(self.client.write(handle, at, &data[done..])) {
+                Ok(0) => return Err(libc::EIO),
+                Ok(n) => done += n,
+                Err(e) => {
+                    error!("write failed: {}", e);
+                    return Err(error_to_errno(&e));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Push any data buffered for `fh` to the provider.
+    fn flush_handle(&mut self, fh: u64) -> Result<(), i32> {
+        let Some((offset, data)) = self.dirty.get_mut(&fh).and_then(|(_, buf)| buf.take()) else {
+            return Ok(());
+        };
+        let handle = self.handles.get(fh).ok_or(libc::EBADF)?;
+        self.write_all(&handle, offset, &data)
+    }
+
+    /// Push data buffered by every handle open on `ino`, so metadata calls
+    /// see the latest size.
+    fn flush_inode(&mut self, ino: u64) -> Result<(), i32> {
+        let fhs: Vec<u64> = self
+            .dirty
+            .iter()
+            .filter(|(_, (owner, buf))| *owner == ino && !buf.is_empty())
+            .map(|(fh, _)| *fh)
+            .collect();
+        if fhs.is_empty() {
+            return Ok(());
+        }
+        for fh in fhs {
+            self.flush_handle(fh)?;
+        }
+        self.inodes.invalidate_attr(ino);
+        Ok(())
+    }
+
+    /// Resolve `ino` and an attribute name for the xattr calls.
+    fn xattr_target(&self, ino: u64, name: &OsStr) -> Result<(String, String), i32> {
+        let path = self.inodes.get_path(ino).ok_or(libc::ENOENT)?;
+        let name = name.to_str().ok_or(libc::EINVAL)?;
+        Ok((path, name.to_string()))
+    }
+
     fn flags_to_open_flags(flags: i32) -> OpenFlags {
         let read = (flags & libc::O_ACCMODE) != libc::O_WRONLY;
         let write = (flags & libc::O_ACCMODE) != libc::O_RDONLY;
         let create = (flags & libc::O_CREAT) != 0;
         let truncate = (flags & libc::O_TRUNC) != 0;
         let append = (flags & libc::O_APPEND) != 0;
+        let exclusive = (flags & libc::O_EXCL) != 0;
 
         OpenFlags {
             read,
@@ -115,11 +191,32 @@ impl Fs9Fuse {
             truncate,
             append,
             directory: false,
+            symlink: false,
+            exclusive,
         }
     }
 }
 
 impl Filesystem for Fs9Fuse {
+    fn init(&mut self, _req: &Request<'_>, config: &mut KernelConfig) -> Result<(), libc::c_int> {
+        if let Some(max_write) = self.max_write {
+            if let Err(nearest) = config.set_max_write(max_write) {
+                warn!("max_write {} not supported, using {}", max_write, nearest);
+                let _ = config.set_max_write(nearest);
+            }
+        }
+        if self.writeback_cache {
+            if let Err(unsupported) = config.add_capabilities(FUSE_WRITEBACK_CACHE) {
+                warn!(
+                    "kernel does not support writeback cache (capabilities {:#x})",
+                    unsupported
+                );
+                self.writeback_cache = false;
+            }
+        }
+        Ok(())
+    }
+
     fn lookup(&mut self, _req: &Request<'_>, parent: u64, name: &OsStr, reply: ReplyEntry) {
         let name = match name.to_str() {
             Some(n) => n,
@@ -166,6 +263,11 @@ impl Filesystem for Fs9Fuse {
             }
         };
 
+        if let Err(e) = self.flush_inode(ino) {
+            reply.error(e);
+            return;
+        }
+
         match self.fetch_attr(&path, ino) {
             Ok(attr) => reply.attr(&self.ttl, &attr),
             Err(e) => reply.error(e),
@@ -198,6 +300,11 @@ impl Filesystem for Fs9Fuse {
             }
         };
 
+        if let Err(e) = self.flush_inode(ino) {
+            reply.error(e);
+            return;
+        }
+
         let changes = fs9_client::StatChanges {
             mode,
             uid,
@@ -320,6 +427,11 @@ impl Filesystem for Fs9Fuse {
             }
         };
 
+        if let Err(e) = self.flush_handle(fh) {
+            reply.error(e);
+            return;
+        }
+
         match self.block_on(self.client.read(&handle, offset as u64, size as usize)) {
             Ok(data) => reply.data(&data),
             Err(e) => {
@@ -349,6 +461,24 @@ impl Filesystem for Fs9Fuse {
             }
         };
 
+        if self.writeback_cache {
+            let pending = self
+                .dirty
+                .entry(fh)
+                .or_insert_with(|| (ino, WriteBuffer::new(WRITEBACK_FLUSH_BYTES)))
+                .1
+                .push(offset as u64, data);
+            if let Some((at, extent)) = pending {
+                if let Err(e) = self.write_all(&handle, at, &extent) {
+                    reply.error(e);
+                    return;
+                }
+            }
+            self.inodes.invalidate_attr(ino);
+            reply.written(data.len() as u32);
+            return;
+        }
+
         match self.block_on(self.client.write(&handle, offset as u64, data)) {
             Ok(written) => {
                 self.inodes.invalidate_attr(ino);
@@ -371,6 +501,10 @@ impl Filesystem for Fs9Fuse {
         _flush: bool,
         reply: ReplyEmpty,
     ) {
+        if let Err(e) = self.flush_handle(fh) {
+            warn!("flushing buffered writes on release failed: errno {}", e);
+        }
+        self.dirty.remove(&fh);
         if let Some(handle) = self.handles.remove(fh) {
             if let Err(e) = self.block_on(self.client.close(handle)) {
                 warn!("close failed: {}", e);
@@ -605,20 +739,18 @@ impl Filesystem for Fs9Fuse {
     fn statfs(&mut self, _req: &Request<'_>, _ino: u64, reply: ReplyStatfs) {
         match self.block_on(self.client.statfs("/")) {
             Ok(stats) => {
+                let s = StatfsReply::from_stats(&stats);
                 reply.statfs(
-                    stats.total_bytes / BLOCK_SIZE as u64,
-                    stats.free_bytes / BLOCK_SIZE as u64,
-                    stats.free_bytes / BLOCK_SIZE as u64,
-                    stats.total_inodes,
-                    stats.free_inodes,
-                    BLOCK_SIZE,
-                    stats.max_name_len,
-                    BLOCK_SIZE,
+                    s.blocks, s.bfree, s.bavail, s.files, s.ffree, s.bsize, s.namelen, s.bsize,
                 );
             }
+            // Nothing to report; an empty filesystem beats failing `df`.
+            Err(fs9_client::Fs9Error::NotImplemented(_)) => {
+                reply.statfs(0, 0, 0, 0, 0, BLOCK_SIZE, 255, BLOCK_SIZE);
+            }
             Err(e) => {
                 warn!("statfs failed: {}", e);
-                reply.statfs(0, 0, 0, 0, 0, BLOCK_SIZE, 255, BLOCK_SIZE);
+                reply.error(error_to_errno(&e));
             }
         }
     }
@@ -627,22 +759,161 @@ impl Filesystem for Fs9Fuse {
         &mut self,
         _req: &Request<'_>,
         _ino: u64,
-        _fh: u64,
+        fh: u64,
         _lock_owner: u64,
         reply: ReplyEmpty,
     ) {
-        reply.ok();
+        // Called on every close(2); reporting here is the last chance for
+        // deferred writeback errors to reach the application.
+        match self.flush_handle(fh) {
+            Ok(()) => reply.ok(),
+            Err(e) => reply.error(e),
+        }
     }
 
-    fn fsync(
+    fn fsync(&mut self, _req: &Request<'_>, _ino: u64, fh: u64, datasync: bool, reply: ReplyEmpty) {
+        let handle = match self.handles.get(fh) {
+            Some(h) => h,
+            None => {
+                reply.error(libc::EBADF);
+                return;
+            }
+        };
+
+        if let Err(e) = self.flush_handle(fh) {
+            reply.error(e);
+            return;
+        }
+
+        // ENOSYS tells the kernel this mount has nothing to flush, after
+        // which it treats fsync as a successful no-op.
+        match self.block_on(self.client.fsync(&handle, datasync)) {
+            Ok(()) => reply.ok(),
+            Err(e) => {
+                warn!("fsync failed: {}", e);
+                reply.error(error_to_errno(&e));
+            }
+        }
+    }
+
+    fn getxattr(
         &mut self,
         _req: &Request<'_>,
-        _ino: u64,
-        _fh: u64,
-        _datasync: bool,
+        ino: u64,
+        name: &OsStr,
+        size: u32,
+        reply: ReplyXattr,
+    ) {
+        let (path, name) = match self.xattr_target(ino, name) {
+            Ok(target) => target,
+            Err(e) => {
+                reply.error(e);
+                return;
+            }
+        };
+
+        match self.block_on(self.client.getxattr(&path, &name)) {
+            Ok(value) => reply_xattr(reply, size, &value),
+            Err(e) => {
+                debug!("getxattr {} on {} failed: {}", name, path, e);
+                reply.error(xattr_errno(&e));
+            }
+        }
+    }
+
+    fn setxattr(
+        &mut self,
+        _req: &Request<'_>,
+        ino: u64,
+        name: &OsStr,
+        value: &[u8],
+        flags: i32,
+        _position: u32,
         reply: ReplyEmpty,
     ) {
-        reply.ok();
+        let (path, name) = match self.xattr_target(ino, name) {
+            Ok(target) => target,
+            Err(e) => {
+                reply.error(e);
+                return;
+            }
+        };
+
+        if flags & (libc::XATTR_CREATE | libc::XATTR_REPLACE) != 0 {
+            let exists = match self.block_on(self.client.getxattr(&path, &name)) {
+                Ok(_) => true,
+                Err(fs9_client::Fs9Error::NotFound(_)) => false,
+                Err(e) => {
+                    reply.error(xattr_errno(&e));
+                    return;
+                }
+            };
+            if exists && flags & libc::XATTR_CREATE != 0 {
+                reply.error(libc::EEXIST);
+                return;
+            }
+            if !exists && flags & libc::XATTR_REPLACE != 0 {
+                reply.error(libc::ENODATA);
+                return;
+            }
+        }
+
+        match self.block_on(self.client.setxattr(&path, &name, value)) {
+            Ok(()) => {
+                self.inodes.invalidate_attr(ino);
+                reply.ok();
+            }
+            Err(e) => {
+                warn!("setxattr {} on {} failed: {}", name, path, e);
+                reply.error(xattr_errno(&e));
+            }
+        }
+    }
+
+    fn listxattr(&mut self, _req: &Request<'_>, ino: u64, size: u32, reply: ReplyXattr) {
+        let path = match self.inodes.get_path(ino) {
+            Some(p) => p,
+            None => {
+                reply.error(libc::ENOENT);
+                return;
+            }
+        };
+
+        match self.block_on(self.client.listxattr(&path)) {
+            Ok(names) => {
+                let mut list = Vec::new();
+                for name in names {
+                    list.extend_from_slice(name.as_bytes());
+                    list.push(0);
+                }
+                reply_xattr(reply, size, &list);
+            }
+            Err(e) => {
+                debug!("listxattr on {} failed: {}", path, e);
+                reply.error(xattr_errno(&e));
+            }
+        }
+    }
+
+    fn removexattr(&mut self, _req: &Request<'_>, ino: u64, name: &OsStr, reply: ReplyEmpty) {
+        let (path, name) = match self.xattr_target(ino, name) {
+            Ok(target) => target,
+            Err(e) => {
+                reply.error(e);
+                return;
+            }
+        };
+
+        match self.block_on(self.client.removexattr(&path, &name)) {
+            Ok(()) => {
+                self.inodes.invalidate_attr(ino);
+                reply.ok();
+            }
+            Err(e) => {
+                debug!("removexattr {} on {} failed: {}", name, path, e);
+                reply.error(xattr_errno(&e));
+            }
+        }
     }
 }
 
@@ -657,6 +928,71 @@ fn parent_path(path: &str) -> String {
     }
 }
 
+/// Answer an xattr read: a zero `size` is the caller probing for the
+/// buffer length it needs.
+fn reply_xattr(reply: ReplyXattr, size: u32, data: &[u8]) {
+    if size == 0 {
+        reply.size(data.len() as u32);
+    } else if data.len() > size as usize {
+        reply.error(libc::ERANGE);
+    } else {
+        reply.data(data);
+    }
+}
+
+/// A provider's [`FsStats`] in the units of a FUSE `statfs` reply: byte
+/// counts become whole blocks of the provider's block size.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct StatfsReply {
+    blocks: u64,
+    bfree: u64,
+    bavail: u64,
+    files: u64,
+    ffree: u64,
+    bsize: u32,
+    namelen: u32,
+}
+
+impl StatfsReply {
+    fn from_stats(stats: &FsStats) -> Self {
+        let bsize = if stats.block_size == 0 {
+            BLOCK_SIZE
+        } else {
+            stats.block_size
+        };
+        // Dividing never overflows, and clamping free to total keeps `df`
+        // from showing negative usage.
+        let blocks = stats.total_bytes / u64::from(bsize);
+        let bfree = stats.free_bytes.min(stats.total_bytes) / u64::from(bsize);
+        Self {
+            blocks,
+            bfree,
+            bavail: bfree,
+            files: stats.total_inodes,
+            ffree: stats.free_inodes.min(stats.total_inodes),
+            bsize,
+            namelen: if stats.max_name_len == 0 {
+                255
+            } else {
+                stats.max_name_len
+            },
+        }
+    }
+}
+
+/// Like [`error_to_errno`], but a missing attribute is `ENODATA` and an
+/// unsupported provider is `ENOTSUP`, as xattr(7) callers expect.
+fn xattr_errno(e: &fs9_client::Fs9Error) -> i32 {
+    use fs9_client::Fs9Error;
+    match e {
+        Fs9Error::NotFound(_) => libc::ENODATA,
+        Fs9Error::NotImplemented(_) => libc::ENOTSUP,
+        _ => error_to_errno(e),
+    }
+}
+
+/// Translate a client error into the errno FUSE replies with. Anything
+/// without a closer match is reported as `EIO`.
 fn error_to_errno(e: &fs9_client::Fs9Error) -> i32 {
     use fs9_client::Fs9Error;
     match e {
@@ -667,8 +1003,24 @@ fn error_to_errno(e: &fs9_client::Fs9Error) -> i32 {
         Fs9Error::NotDirectory(_) => libc::ENOTDIR,
         Fs9Error::IsDirectory(_) => libc::EISDIR,
         Fs9Error::DirectoryNotEmpty(_) => libc::ENOTEMPTY,
+        Fs9Error::RateLimited(_) => libc::EAGAIN,
         Fs9Error::InvalidHandle => libc::EBADF,
-        _ => libc::EIO,
+        Fs9Error::NotImplemented(_) => libc::ENOSYS,
+        Fs9Error::NoSpace(_) => libc::ENOSPC,
+        Fs9Error::Timeout => libc::ETIMEDOUT,
+        Fs9Error::Request { status, .. } => match status {
+            401 => libc::EACCES,
+            413 => libc::EFBIG,
+            416 => libc::EINVAL,
+            429 => libc::EAGAIN,
+            _ => libc::EIO,
+        },
+        Fs9Error::Conflict(_)
+        | Fs9Error::EndOfStream(_)
+        | Fs9Error::Connection(_)
+        | Fs9Error::Server(_)
+        | Fs9Error::ServerError { .. }
+        | Fs9Error::Serialization(_) => libc::EIO,
     }
 }
 
@@ -684,3 +1036,119 @@ fn time_or_now_to_secs(t: fuser::TimeOrNow) -> u64 {
             .unwrap_or(0),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fs9_client::Fs9Error;
+
+    #[test]
+    fn fs_errors_map_to_matching_errno() {
+        let p = || "/p".to_string();
+        let cases = [
+            (Fs9Error::NotFound(p()), libc::ENOENT),
+            (Fs9Error::PermissionDenied(p()), libc::EACCES),
+            (Fs9Error::AlreadyExists(p()), libc::EEXIST),
+            (Fs9Error::InvalidArgument(p()), libc::EINVAL),
+            (Fs9Error::NotDirectory(p()), libc::ENOTDIR),
+            (Fs9Error::IsDirectory(p()), libc::EISDIR),
+            (Fs9Error::DirectoryNotEmpty(p()), libc::ENOTEMPTY),
+            (Fs9Error::InvalidHandle, libc::EBADF),
+            (Fs9Error::NotImplemented(p()), libc::ENOSYS),
+            (Fs9Error::NoSpace(p()), libc::ENOSPC),
+            (Fs9Error::RateLimited(p()), libc::EAGAIN),
+            (Fs9Error::Timeout, libc::ETIMEDOUT),
+        ];
+        for (err, errno) in cases {
+            assert_eq!(error_to_errno(&err), errno, "{err:?}");
+        }
+    }
+
+    fn stats(total_bytes: u64, free_bytes: u64, block_size: u32) -> FsStats {
+        FsStats {
+            total_bytes,
+            free_bytes,
+            total_inodes: 1000,
+            free_inodes: 900,
+            block_size,
+            max_name_len: 255,
+        }
+    }
+
+    #[test]
+    fn statfs_counts_blocks_of_the_provider_block_size() {
+        // A PageFS with 64 KiB pages, 100 pages used of 1000.
+        let page = 64 * 1024;
+        let reply = StatfsReply::from_stats(&stats(1000 * page, 900 * page, 64 * 1024));
+        assert_eq!(
+            reply,
+            StatfsReply {
+                blocks: 1000,
+                bfree: 900,
+                bavail: 900,
+                files: 1000,
+                ffree: 900,
+                bsize: 64 * 1024,
+                namelen: 255,
+            }
+        );
+        // What `df` shows: size, used and available in bytes.
+        assert_eq!(reply.blocks * u64::from(reply.bsize), 1000 * page);
+        assert_eq!(
+            (reply.blocks - reply.bfree) * u64::from(reply.bsize),
+            100 * page
+        );
+    }
+
+    #[test]
+    fn statfs_handles_extreme_values() {
+        let reply = StatfsReply::from_stats(&stats(u64::MAX, u64::MAX, 1));
+        assert_eq!(reply.blocks, u64::MAX);
+        assert_eq!(reply.bfree, u64::MAX);
+
+        // Partial blocks are not counted, and free never exceeds total.
+        let reply = StatfsReply::from_stats(&stats(10_000, 20_000, 4096));
+        assert_eq!(reply.blocks, 2);
+        assert_eq!(reply.bfree, 2);
+
+        // Unknown block size and name length fall back to the defaults.
+        let mut unknown = stats(8192, 4096, 0);
+        unknown.max_name_len = 0;
+        let reply = StatfsReply::from_stats(&unknown);
+        assert_eq!(reply.bsize, BLOCK_SIZE);
+        assert_eq!((reply.blocks, reply.bfree), (2, 1));
+        assert_eq!(reply.namelen, 255);
+    }
+
+    #[test]
+    fn unmapped_errors_fall_back_to_eio() {
+        let request = |status| Fs9Error::Request {
+            status,
+            message: String::new(),
+        };
+        assert_eq!(error_to_errno(&request(401)), libc::EACCES);
+        assert_eq!(error_to_errno(&request(413)), libc::EFBIG);
+        assert_eq!(error_to_errno(&request(429)), libc::EAGAIN);
+        assert_eq!(error_to_errno(&request(418)), libc::EIO);
+        assert_eq!(error_to_errno(&Fs9Error::Server("boom".into())), libc::EIO);
+        assert_eq!(
+            error_to_errno(&Fs9Error::ServerError {
+                status: 500,
+                message: "boom".into()
+            }),
+            libc::EIO
+        );
+        assert_eq!(
+            error_to_errno(&Fs9Error::Conflict("etag".into())),
+            libc::EIO
+        );
+        assert_eq!(
+            error_to_errno(&Fs9Error::Connection("refused".into())),
+            libc::EIO
+        );
+        assert_eq!(
+            error_to_errno(&Fs9Error::Serialization("bad json".into())),
+            libc::EIO
+        );
+    }
+}
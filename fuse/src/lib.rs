@@ -4,6 +4,7 @@ pub mod builder;
 pub mod fs;
 pub mod handle;
 pub mod inode;
+pub mod writeback;
 
 pub use builder::{Fs9FuseBuilder, Fs9FuseMount, MountOptions};
 pub use fs::Fs9Fuse;
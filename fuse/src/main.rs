@@ -5,7 +5,6 @@ use std::time::Duration;
 
 use clap::Parser;
 use fs9_client::Fs9Client;
-use fs9_config::Fs9Config;
 use tracing::{error, info};
 
 use fs9_fuse::Fs9FuseBuilder;
@@ -40,6 +39,18 @@ struct Args {
     #[arg(short = 'r', long)]
     read_only: Option<bool>,
 
+    /// Largest read request the kernel may send, in bytes
+    #[arg(long)]
+    max_read: Option<u32>,
+
+    /// Largest write request the kernel may send, in bytes
+    #[arg(long)]
+    max_write: Option<u32>,
+
+    /// Cache writes in the kernel and flush them in batches
+    #[arg(long)]
+    writeback_cache: Option<bool>,
+
     #[arg(short, long)]
     debug: bool,
 
@@ -52,10 +63,13 @@ fn main() {
 
     let config = match &args.config {
         Some(path) => fs9_config::load_from_file(path).unwrap_or_else(|e| {
-            eprintln!("Warning: Failed to load config: {e}, using defaults");
-            Fs9Config::default()
+            eprintln!("Error: Failed to load config from {path}: {e}");
+            std::process::exit(1);
+        }),
+        None => fs9_config::load().unwrap_or_else(|e| {
+            eprintln!("Error: Failed to load config: {e}");
+            std::process::exit(1);
         }),
-        None => fs9_config::load().unwrap_or_default(),
     };
 
     let log_level = if args.debug {
@@ -84,6 +98,11 @@ fn main() {
         .auto_unmount
         .unwrap_or(config.fuse.options.auto_unmount);
     let read_only = args.read_only.unwrap_or(config.fuse.options.read_only);
+    let max_read = args.max_read.or(config.fuse.options.max_read);
+    let max_write = args.max_write.or(config.fuse.options.max_write);
+    let writeback_cache = args
+        .writeback_cache
+        .unwrap_or(config.fuse.options.writeback_cache);
     let cache_ttl = args
         .cache_ttl
         .unwrap_or_else(|| parse_duration(&config.fuse.cache.attr_ttl));
@@ -135,7 +154,16 @@ fn main() {
         .cache_ttl(Duration::from_secs(cache_ttl))
         .allow_other(allow_other)
         .allow_root(allow_root)
-        .read_only(read_only);
+        .read_only(read_only)
+        .writeback_cache(writeback_cache);
+    let builder = match max_read {
+        Some(bytes) => builder.max_read(bytes),
+        None => builder,
+    };
+    let builder = match max_write {
+        Some(bytes) => builder.max_write(bytes),
+        None => builder,
+    };
 
     let builder = if auto_unmount {
         if !allow_other && !allow_root && !fuse_conf_allows_other() {
@@ -0,0 +1,133 @@
+//! Coalescing of dirty data for writeback-cached mounts.
+//!
+//! With `writeback_cache` the kernel acknowledges writes from its page cache
+//! and hands them to us later, in whatever order and size it likes. Instead
+//! of issuing one provider `write` per FUSE request, each open handle keeps a
+//! single contiguous dirty extent and only sends it once it stops being
+//! contiguous, grows past the flush threshold, or the handle is flushed,
+//! fsynced or released.
+
+/// Dirty extents are written out once they reach this many bytes.
+pub const WRITEBACK_FLUSH_BYTES: usize = 4 * 1024 * 1024;
+
+/// A contiguous range of not-yet-written data: `(offset, bytes)`.
+pub type Extent = (u64, Vec<u8>);
+
+pub struct WriteBuffer {
+    offset: u64,
+    data: Vec<u8>,
+    limit: usize,
+}
+
+impl WriteBuffer {
+    pub fn new(limit: usize) -> Self {
+        Self {
+            offset: 0,
+            data: Vec::new(),
+            limit,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    /// Buffer `data` at `offset`.
+    ///
+    /// Returns the previously buffered extent when it has to be written out
+    /// first, i.e. when the new data does not directly follow it or would
+    /// push it past the flush threshold.
+    pub fn push(&mut self, offset: u64, data: &[u8]) -> Option<Extent> {
+        let end = self.offset + self.data.len() as u64;
+        let flushed =
+            if !self.is_empty() && (offset != end || self.data.len() + data.len() > self.limit) {
+                self.take()
+            } else {
+                None
+            };
+        if self.is_empty() {
+            self.offset = offset;
+        }
+        self.data.extend_from_slice(data);
+        flushed
+    }
+
+    /// Remove and return everything buffered so far.
+    pub fn take(&mut self) -> Option<Extent> {
+        if self.is_empty() {
+            return None;
+        }
+        Some((self.offset, std::mem::take(&mut self.data)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FILE_SIZE: usize = 16 * 1024 * 1024;
+
+    /// Number of provider `write` calls for a sequential write of
+    /// `FILE_SIZE` bytes that the kernel splits into `max_write` requests.
+    fn provider_writes(max_write: usize, writeback: bool) -> usize {
+        let requests = (0..FILE_SIZE).step_by(max_write).map(|off| {
+            let len = max_write.min(FILE_SIZE - off);
+            (off as u64, vec![0u8; len])
+        });
+        if !writeback {
+            return requests.count();
+        }
+
+        let mut buffer = WriteBuffer::new(WRITEBACK_FLUSH_BYTES);
+        let mut writes = 0;
+        let mut written = 0;
+        for (off, data) in requests {
+            if let Some((_, extent)) = buffer.push(off, &data) {
+                writes += 1;
+                written += extent.len();
+            }
+        }
+        if let Some((_, extent)) = buffer.take() {
+            writes += 1;
+            written += extent.len();
+        }
+        assert_eq!(written, FILE_SIZE);
+        writes
+    }
+
+    #[test]
+    fn larger_max_write_issues_fewer_provider_writes() {
+        assert_eq!(provider_writes(4096, false), 4096);
+        assert_eq!(provider_writes(1024 * 1024, false), 16);
+        assert_eq!(provider_writes(4096, true), 4);
+        assert_eq!(provider_writes(1024 * 1024, true), 4);
+    }
+
+    #[test]
+    fn contiguous_writes_coalesce_into_one_extent() {
+        let mut buffer = WriteBuffer::new(1024);
+        assert!(buffer.push(100, b"hello ").is_none());
+        assert!(buffer.push(106, b"world").is_none());
+        assert_eq!(buffer.take(), Some((100, b"hello world".to_vec())));
+        assert!(buffer.is_empty());
+        assert!(buffer.take().is_none());
+    }
+
+    #[test]
+    fn non_contiguous_write_flushes_previous_extent() {
+        let mut buffer = WriteBuffer::new(1024);
+        buffer.push(0, b"abc");
+        assert_eq!(buffer.push(10, b"xyz"), Some((0, b"abc".to_vec())));
+        // Rewriting earlier data is not contiguous either.
+        assert_eq!(buffer.push(0, b"A"), Some((10, b"xyz".to_vec())));
+        assert_eq!(buffer.take(), Some((0, b"A".to_vec())));
+    }
+
+    #[test]
+    fn extent_is_flushed_before_exceeding_limit() {
+        let mut buffer = WriteBuffer::new(8);
+        buffer.push(0, b"12345");
+        assert_eq!(buffer.push(5, b"6789"), Some((0, b"12345".to_vec())));
+        assert_eq!(buffer.take(), Some((5, b"6789".to_vec())));
+    }
+}
@@ -346,3 +346,28 @@ fn test_fuse_overwrite_larger_with_smaller() {
 
     fs::remove_file(&test_file).expect("Failed to remove file");
 }
+
+#[test]
+#[ignore]
+fn test_fuse_statfs_matches_server() {
+    let mountpoint = "/tmp/fs9-fuse-test-statfs";
+    let _mount = MountedFs::mount(&get_server_url(), mountpoint).expect("Failed to mount");
+
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let client = fs9_client::Fs9Client::new(&get_server_url()).unwrap();
+    let stats = runtime.block_on(client.statfs("/")).expect("statfs failed");
+
+    let path = std::ffi::CString::new(mountpoint).unwrap();
+    let mut vfs = std::mem::MaybeUninit::<libc::statvfs>::uninit();
+    assert_eq!(unsafe { libc::statvfs(path.as_ptr(), vfs.as_mut_ptr()) }, 0);
+    let vfs = unsafe { vfs.assume_init() };
+
+    let block_size = u64::from(stats.block_size);
+    assert_eq!(vfs.f_frsize as u64, block_size);
+    assert_eq!(vfs.f_blocks as u64, stats.total_bytes / block_size);
+    assert_eq!(vfs.f_bfree as u64, stats.free_bytes / block_size);
+    assert_eq!(vfs.f_bavail, vfs.f_bfree);
+    assert_eq!(vfs.f_files as u64, stats.total_inodes);
+    assert_eq!(vfs.f_ffree as u64, stats.free_inodes);
+    assert_eq!(vfs.f_namemax as u64, u64::from(stats.max_name_len));
+}
@@ -6,8 +6,10 @@ use axum::{
 };
 use chrono::{Duration, Utc};
 
+use super::audited;
+use crate::auth::{validate_grants, Actor, PathGrant};
 use crate::db::models::{
-    ApiKeyResponse, CreateApiKeyRequest, CreateApiKeyResponse, ValidateApiKeyRequest,
+    ApiKeyResponse, AuditEntry, CreateApiKeyRequest, CreateApiKeyResponse, ValidateApiKeyRequest,
 };
 use crate::error::MetaError;
 use crate::AppState;
@@ -15,26 +17,46 @@ use crate::AppState;
 /// Create a new API key.
 pub async fn create(
     State(state): State<AppState>,
+    Actor(actor): Actor,
     Json(req): Json<CreateApiKeyRequest>,
 ) -> Result<Json<CreateApiKeyResponse>, MetaError> {
-    // Get namespace
-    let ns = state
-        .store
-        .get_namespace(&req.namespace)
-        .await?
-        .ok_or_else(|| MetaError::NotFound(format!("Namespace '{}' not found", req.namespace)))?;
-
-    // TODO: Get user from auth context
-    let user_id = "system";
-
-    let expires_at = req
-        .expires_in_days
-        .map(|days| Utc::now() + Duration::days(days));
-
-    let (api_key, raw_key) = state
-        .store
-        .create_api_key(user_id, &ns.id, &req.name, &req.roles, expires_at)
-        .await?;
+    let audit = AuditEntry::new(&actor, "apikey.create", &req.user_id).in_namespace(&req.namespace);
+    let (api_key, raw_key) = audited(&state, &audit, async {
+        // Get namespace
+        let ns = state
+            .store
+            .get_namespace(&req.namespace)
+            .await?
+            .ok_or_else(|| {
+                MetaError::NotFound(format!("Namespace '{}' not found", req.namespace))
+            })?;
+
+        validate_grants(&req.scopes)?;
+
+        let user = state
+            .store
+            .get_user_by_id(&req.user_id)
+            .await?
+            .ok_or_else(|| MetaError::NotFound(format!("User '{}' not found", req.user_id)))?;
+
+        let expires_at = req
+            .expires_in_days
+            .map(|days| Utc::now() + Duration::days(days));
+
+        state
+            .store
+            .create_api_key(
+                &user.id,
+                &ns.id,
+                &req.name,
+                &req.roles,
+                &req.scopes,
+                expires_at,
+                &audit,
+            )
+            .await
+    })
+    .await?;
 
     let roles: Vec<String> = serde_json::from_str(&api_key.roles).unwrap_or_default();
 
@@ -44,6 +66,7 @@ pub async fn create(
         name: api_key.name,
         namespace: req.namespace,
         roles,
+        scopes: req.scopes,
         expires_at: api_key.expires_at,
         created_at: api_key.created_at,
     }))
@@ -67,12 +90,14 @@ pub async fn list(State(state): State<AppState>) -> Result<Json<Vec<ApiKeyRespon
         };
 
         let roles: Vec<String> = serde_json::from_str(&key.roles).unwrap_or_default();
+        let scopes: Vec<PathGrant> = serde_json::from_str(&key.scopes).unwrap_or_default();
 
         responses.push(ApiKeyResponse {
             id: key.id,
             name: key.name,
             namespace,
             roles,
+            scopes,
             expires_at: key.expires_at,
             last_used_at: key.last_used_at,
             created_at: key.created_at,
@@ -104,12 +129,14 @@ pub async fn validate(
                 };
 
             let roles: Vec<String> = serde_json::from_str(&key.roles).unwrap_or_default();
+            let scopes: Vec<PathGrant> = serde_json::from_str(&key.scopes).unwrap_or_default();
 
             Ok(Json(serde_json::json!({
                 "valid": true,
                 "user_id": key.user_id,
                 "namespace": namespace,
                 "roles": roles,
+                "scopes": scopes,
                 "expires_at": key.expires_at,
             })))
         }
@@ -123,8 +150,10 @@ pub async fn validate(
 /// Revoke an API key.
 pub async fn revoke(
     State(state): State<AppState>,
+    Actor(actor): Actor,
     Path(id): Path<String>,
 ) -> Result<Json<serde_json::Value>, MetaError> {
-    state.store.revoke_api_key(&id).await?;
+    let audit = AuditEntry::new(&actor, "apikey.revoke", &id);
+    audited(&state, &audit, state.store.revoke_api_key(&id, &audit)).await?;
     Ok(Json(serde_json::json!({"revoked": true})))
 }
@@ -0,0 +1,19 @@
+//! Audit log API handlers.
+
+use axum::{
+    extract::{Query, State},
+    Json,
+};
+
+use crate::db::models::{AuditLogResponse, AuditQuery};
+use crate::error::MetaError;
+use crate::AppState;
+
+/// Query the audit log, newest entries first.
+pub async fn list(
+    State(state): State<AppState>,
+    Query(query): Query<AuditQuery>,
+) -> Result<Json<Vec<AuditLogResponse>>, MetaError> {
+    let entries = state.store.query_audit(&query).await?;
+    Ok(Json(entries.into_iter().map(Into::into).collect()))
+}
@@ -1,16 +1,21 @@
 //! REST API handlers for fs9-meta service.
 
 mod apikey;
+mod audit;
 mod mount;
 mod namespace;
 mod token;
 mod user;
 
+use std::future::Future;
+
 use axum::{
-    routing::{delete, get, post},
+    routing::{delete, get, post, put},
     Router,
 };
 
+use crate::db::AuditEntry;
+use crate::error::MetaError;
 use crate::AppState;
 
 /// Create the API router.
@@ -21,6 +26,7 @@ pub fn router() -> Router<AppState> {
         .route("/namespaces", get(namespace::list))
         .route("/namespaces/:name", get(namespace::get))
         .route("/namespaces/:name", delete(namespace::delete))
+        .route("/namespaces/:name/quota", put(namespace::set_quota))
         // Mount routes
         .route("/namespaces/:namespace/mounts", post(mount::create))
         .route("/namespaces/:namespace/mounts", get(mount::list))
@@ -46,4 +52,27 @@ pub fn router() -> Router<AppState> {
         .route("/apikeys", get(apikey::list))
         .route("/apikeys/validate", post(apikey::validate))
         .route("/apikeys/:id", delete(apikey::revoke))
+        // Audit log
+        .route("/audit", get(audit::list))
+}
+
+/// Run an administrative action described by `entry`. Store operations
+/// record their own success inside the action's transaction; a failed
+/// action is recorded here once it has been rolled back.
+async fn audited<T>(
+    state: &AppState,
+    entry: &AuditEntry,
+    action: impl Future<Output = Result<T, MetaError>>,
+) -> Result<T, MetaError> {
+    let result = action.await;
+    if let Err(err) = &result {
+        if let Err(audit_err) = state
+            .store
+            .append_audit(entry, &format!("error: {err}"))
+            .await
+        {
+            tracing::warn!(action = %entry.action, error = %audit_err, "failed to record failed action");
+        }
+    }
+    result
 }
@@ -5,30 +5,34 @@ use axum::{
     Json,
 };
 
-use crate::db::models::{CreateMountRequest, MountResponse};
+use super::audited;
+use crate::auth::Actor;
+use crate::db::models::{AuditEntry, CreateMountRequest, MountResponse};
 use crate::error::MetaError;
 use crate::AppState;
 
 /// Create a new mount.
 pub async fn create(
     State(state): State<AppState>,
+    Actor(actor): Actor,
     Path(namespace): Path<String>,
     Json(req): Json<CreateMountRequest>,
 ) -> Result<Json<MountResponse>, MetaError> {
-    // Get namespace ID
-    let ns = state
-        .store
-        .get_namespace(&namespace)
-        .await?
-        .ok_or_else(|| MetaError::NotFound(format!("Namespace '{namespace}' not found")))?;
-
-    // TODO: Get creator from auth context
-    let created_by = "system";
+    let audit = AuditEntry::new(&actor, "mount.create", &req.path).in_namespace(&namespace);
+    let mount = audited(&state, &audit, async {
+        // Get namespace ID
+        let ns = state
+            .store
+            .get_namespace(&namespace)
+            .await?
+            .ok_or_else(|| MetaError::NotFound(format!("Namespace '{namespace}' not found")))?;
 
-    let mount = state
-        .store
-        .create_mount(&ns.id, &req.path, &req.provider, req.config, created_by)
-        .await?;
+        state
+            .store
+            .create_mount(&ns.id, &req.path, &req.provider, req.config, &audit)
+            .await
+    })
+    .await?;
     Ok(Json(mount.into()))
 }
 
@@ -70,15 +74,20 @@ pub async fn get(
 /// Delete a mount.
 pub async fn delete(
     State(state): State<AppState>,
+    Actor(actor): Actor,
     Path((namespace, path)): Path<(String, String)>,
 ) -> Result<Json<serde_json::Value>, MetaError> {
-    let ns = state
-        .store
-        .get_namespace(&namespace)
-        .await?
-        .ok_or_else(|| MetaError::NotFound(format!("Namespace '{namespace}' not found")))?;
-
     let mount_path = format!("/{path}");
-    state.store.delete_mount(&ns.id, &mount_path).await?;
+    let audit = AuditEntry::new(&actor, "mount.delete", &mount_path).in_namespace(&namespace);
+    audited(&state, &audit, async {
+        let ns = state
+            .store
+            .get_namespace(&namespace)
+            .await?
+            .ok_or_else(|| MetaError::NotFound(format!("Namespace '{namespace}' not found")))?;
+
+        state.store.delete_mount(&ns.id, &mount_path, &audit).await
+    })
+    .await?;
     Ok(Json(serde_json::json!({"deleted": true})))
 }
@@ -5,19 +5,25 @@ use axum::{
     Json,
 };
 
-use crate::db::models::{CreateNamespaceRequest, NamespaceResponse};
+use super::audited;
+use crate::auth::Actor;
+use crate::db::models::{AuditEntry, CreateNamespaceRequest, NamespaceResponse, SetQuotaRequest};
 use crate::error::MetaError;
 use crate::AppState;
 
 /// Create a new namespace.
 pub async fn create(
     State(state): State<AppState>,
+    Actor(actor): Actor,
     Json(req): Json<CreateNamespaceRequest>,
 ) -> Result<Json<NamespaceResponse>, MetaError> {
-    // TODO: Get creator from auth context
-    let created_by = "system";
-
-    let ns = state.store.create_namespace(&req.name, created_by).await?;
+    let audit = AuditEntry::new(&actor, "namespace.create", &req.name).in_namespace(&req.name);
+    let ns = audited(
+        &state,
+        &audit,
+        state.store.create_namespace(&req.name, &audit),
+    )
+    .await?;
     Ok(Json(ns.into()))
 }
 
@@ -42,11 +48,36 @@ pub async fn get(
     Ok(Json(ns.into()))
 }
 
+/// Set or lift a namespace's byte budget.
+pub async fn set_quota(
+    State(state): State<AppState>,
+    Actor(actor): Actor,
+    Path(name): Path<String>,
+    Json(req): Json<SetQuotaRequest>,
+) -> Result<Json<NamespaceResponse>, MetaError> {
+    let audit = AuditEntry::new(&actor, "namespace.set_quota", &name).in_namespace(&name);
+    let ns = audited(&state, &audit, async {
+        let quota_bytes = req
+            .quota_bytes
+            .map(i64::try_from)
+            .transpose()
+            .map_err(|_| MetaError::InvalidInput("quota_bytes is too large".to_string()))?;
+        state
+            .store
+            .set_namespace_quota(&name, quota_bytes, &audit)
+            .await
+    })
+    .await?;
+    Ok(Json(ns.into()))
+}
+
 /// Delete a namespace.
 pub async fn delete(
     State(state): State<AppState>,
+    Actor(actor): Actor,
     Path(name): Path<String>,
 ) -> Result<Json<serde_json::Value>, MetaError> {
-    state.store.delete_namespace(&name).await?;
+    let audit = AuditEntry::new(&actor, "namespace.delete", &name).in_namespace(&name);
+    audited(&state, &audit, state.store.delete_namespace(&name, &audit)).await?;
     Ok(Json(serde_json::json!({"deleted": true})))
 }
@@ -5,8 +5,11 @@ use chrono::{Duration, Utc};
 use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, Validation};
 use serde::{Deserialize, Serialize};
 
+use super::audited;
+use crate::auth::{validate_grants, Actor, PathGrant};
 use crate::db::models::{
-    GenerateTokenRequest, GenerateTokenResponse, ValidateTokenRequest, ValidateTokenResponse,
+    AuditEntry, GenerateTokenRequest, GenerateTokenResponse, ValidateTokenRequest,
+    ValidateTokenResponse,
 };
 use crate::error::MetaError;
 use crate::AppState;
@@ -22,6 +25,9 @@ struct Claims {
     ns: String,
     /// Roles
     roles: Vec<String>,
+    /// Path-prefix grants; empty means unrestricted within the namespace
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    scopes: Vec<PathGrant>,
     /// Expiration time (Unix timestamp)
     exp: i64,
     /// Issued at (Unix timestamp)
@@ -29,37 +35,53 @@ struct Claims {
 }
 
 /// Generate a new JWT token.
+///
+/// Tokens aren't stored, so the audit record is written on its own; the
+/// token is only handed out once it has been.
 pub async fn generate(
     State(state): State<AppState>,
+    Actor(actor): Actor,
     Json(req): Json<GenerateTokenRequest>,
 ) -> Result<Json<GenerateTokenResponse>, MetaError> {
-    // Validate namespace exists
-    state
-        .store
-        .get_namespace(&req.namespace)
-        .await?
-        .ok_or_else(|| MetaError::NotFound(format!("Namespace '{}' not found", req.namespace)))?;
-
-    let ttl = req.ttl_seconds.unwrap_or(86400); // Default 24 hours
-    let now = Utc::now();
-    #[allow(clippy::cast_possible_wrap)]
-    let expires_at = now + Duration::seconds(ttl as i64);
-
-    let claims = Claims {
-        sub: req.user_id.clone(),
-        ns: req.namespace.clone(),
-        roles: req.roles.clone(),
-        exp: expires_at.timestamp(),
-        iat: now.timestamp(),
-    };
-
-    let token = encode(
-        &Header::default(),
-        &claims,
-        &EncodingKey::from_secret(state.jwt_secret.as_bytes()),
-    )?;
-
-    Ok(Json(GenerateTokenResponse { token, expires_at }))
+    let audit =
+        AuditEntry::new(&actor, "token.generate", &req.user_id).in_namespace(&req.namespace);
+    let response = audited(&state, &audit, async {
+        // Validate namespace exists
+        state
+            .store
+            .get_namespace(&req.namespace)
+            .await?
+            .ok_or_else(|| {
+                MetaError::NotFound(format!("Namespace '{}' not found", req.namespace))
+            })?;
+        validate_grants(&req.scopes)?;
+
+        let ttl = req.ttl_seconds.unwrap_or(86400); // Default 24 hours
+        let now = Utc::now();
+        #[allow(clippy::cast_possible_wrap)]
+        let expires_at = now + Duration::seconds(ttl as i64);
+
+        let claims = Claims {
+            sub: req.user_id.clone(),
+            ns: req.namespace.clone(),
+            roles: req.roles.clone(),
+            scopes: req.scopes.clone(),
+            exp: expires_at.timestamp(),
+            iat: now.timestamp(),
+        };
+
+        let token = encode(
+            &Header::default(),
+            &claims,
+            &EncodingKey::from_secret(state.jwt_secret.as_bytes()),
+        )?;
+
+        Ok(GenerateTokenResponse { token, expires_at })
+    })
+    .await?;
+
+    state.store.append_audit(&audit, "ok").await?;
+    Ok(Json(response))
 }
 
 /// Validate a JWT token.
@@ -82,6 +104,7 @@ pub async fn validate(
                 user_id: Some(claims.sub),
                 namespace: Some(claims.ns),
                 roles: claims.roles,
+                scopes: claims.scopes,
                 expires_at: Some(
                     chrono::DateTime::from_timestamp(claims.exp, 0).unwrap_or_else(Utc::now),
                 ),
@@ -93,6 +116,7 @@ pub async fn validate(
             user_id: None,
             namespace: None,
             roles: vec![],
+            scopes: vec![],
             expires_at: None,
             error: Some(e.to_string()),
         })),
@@ -109,6 +133,7 @@ pub struct RefreshTokenRequest {
 /// Refresh a JWT token (extend expiration).
 pub async fn refresh(
     State(state): State<AppState>,
+    Actor(actor): Actor,
     Json(req): Json<RefreshTokenRequest>,
 ) -> Result<Json<GenerateTokenResponse>, MetaError> {
     let mut validation = Validation::default();
@@ -133,10 +158,12 @@ pub async fn refresh(
     #[allow(clippy::cast_possible_wrap)]
     let expires_at = now + Duration::seconds(ttl as i64);
 
+    let audit = AuditEntry::new(&actor, "token.refresh", &claims.sub).in_namespace(&claims.ns);
     let new_claims = Claims {
         sub: claims.sub,
         ns: claims.ns,
         roles: claims.roles,
+        scopes: claims.scopes,
         exp: expires_at.timestamp(),
         iat: now.timestamp(),
     };
@@ -147,5 +174,6 @@ pub async fn refresh(
         &EncodingKey::from_secret(state.jwt_secret.as_bytes()),
     )?;
 
+    state.store.append_audit(&audit, "ok").await?;
     Ok(Json(GenerateTokenResponse { token, expires_at }))
 }
@@ -1,17 +1,100 @@
 //! Authentication / authorization helpers for fs9-meta.
 
 use axum::{
+    async_trait,
     body::Body,
-    extract::State,
-    http::{header, Request, StatusCode},
+    extract::{FromRequestParts, State},
+    http::{header, request::Parts, Request, StatusCode},
     middleware::Next,
     response::{IntoResponse, Response},
     Json,
 };
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
 
+use crate::error::MetaError;
 use crate::AppState;
 
+/// Access level of a [`PathGrant`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Access {
+    #[serde(rename = "r")]
+    Read,
+    #[serde(rename = "rw")]
+    ReadWrite,
+}
+
+/// Access to everything under a path prefix within the credential's
+/// namespace, e.g. `{"prefix":"/uploads","access":"rw"}`.
+///
+/// A token or API key without grants is limited only by its roles; one with
+/// grants may touch nothing outside them.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PathGrant {
+    pub prefix: String,
+    pub access: Access,
+}
+
+/// Reject grants whose prefix is not an absolute, `..`-free path.
+pub fn validate_grants(grants: &[PathGrant]) -> Result<(), MetaError> {
+    for grant in grants {
+        if !grant.prefix.starts_with('/') || grant.prefix.split('/').any(|c| c == "..") {
+            return Err(MetaError::InvalidInput(format!(
+                "Invalid grant prefix '{}': must be an absolute path",
+                grant.prefix
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Prefix of every API key, which lets fs9-server tell keys apart from JWTs.
+pub const API_KEY_PREFIX: &str = "fs9_";
+
+/// Generate a new random API key. Only its hash is ever stored.
+#[must_use]
+pub fn generate_api_key() -> String {
+    format!("{API_KEY_PREFIX}{}", Uuid::new_v4().simple())
+}
+
+/// Hash an API key for storage and lookup (hex-encoded SHA-256).
+#[must_use]
+pub fn hash_api_key(key: &str) -> String {
+    use sha2::{Digest, Sha256};
+    use std::fmt::Write;
+
+    let digest = Sha256::digest(key.as_bytes());
+    let mut out = String::with_capacity(digest.len() * 2);
+    for b in digest {
+        write!(&mut out, "{b:02x}").expect("writing into String shouldn't fail");
+    }
+    out
+}
+
+/// Header naming who is making an admin request, for the audit log.
+pub const ACTOR_HEADER: &str = "x-fs9-actor";
+
+/// Who is making an admin request: the [`ACTOR_HEADER`] value, or `system`
+/// when the caller doesn't say.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Actor(pub String);
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for Actor {
+    type Rejection = std::convert::Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let actor = parts
+            .headers
+            .get(ACTOR_HEADER)
+            .and_then(|v| v.to_str().ok())
+            .map(str::trim)
+            .filter(|v| !v.is_empty())
+            .unwrap_or("system");
+        Ok(Self(actor.to_string()))
+    }
+}
+
 #[derive(Debug, Serialize)]
 struct ErrorResponse {
     error: String,
@@ -59,3 +142,52 @@ pub async fn require_admin_key(
         _ => unauthorized("missing or invalid admin key"),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn generated_keys_are_prefixed_and_unique() {
+        let a = generate_api_key();
+        let b = generate_api_key();
+        assert!(a.starts_with(API_KEY_PREFIX));
+        assert_eq!(a.len(), API_KEY_PREFIX.len() + 32);
+        assert_ne!(a, b);
+    }
+
+    #[test]
+    fn grants_use_short_access_names() {
+        let grant: PathGrant =
+            serde_json::from_str(r#"{"prefix":"/uploads","access":"rw"}"#).unwrap();
+        assert_eq!(grant.access, Access::ReadWrite);
+        assert_eq!(
+            serde_json::to_string(&PathGrant {
+                prefix: "/".to_string(),
+                access: Access::Read,
+            })
+            .unwrap(),
+            r#"{"prefix":"/","access":"r"}"#
+        );
+    }
+
+    #[test]
+    fn grant_prefixes_must_be_absolute() {
+        let grant = |prefix: &str| PathGrant {
+            prefix: prefix.to_string(),
+            access: Access::Read,
+        };
+        assert!(validate_grants(&[grant("/"), grant("/uploads")]).is_ok());
+        assert!(validate_grants(&[grant("uploads")]).is_err());
+        assert!(validate_grants(&[grant("/uploads/../etc")]).is_err());
+    }
+
+    #[test]
+    fn hash_is_stable_and_not_the_key() {
+        let key = "fs9_0123456789abcdef0123456789abcdef";
+        assert_eq!(hash_api_key(key), hash_api_key(key));
+        assert_eq!(hash_api_key(key).len(), 64);
+        assert!(!hash_api_key(key).contains(key));
+        assert_ne!(hash_api_key(key), hash_api_key("fs9_other"));
+    }
+}
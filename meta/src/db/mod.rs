@@ -16,6 +16,7 @@ pub use sqlite::SqliteStore;
 #[cfg(feature = "postgres")]
 pub use postgres::PostgresStore;
 
+use crate::auth::PathGrant;
 use crate::error::MetaError;
 pub use models::*;
 
@@ -74,12 +75,12 @@ impl MetaStore {
     // Namespace operations
     // ========================================================================
 
-    pub async fn create_namespace(&self, name: &str, created_by: &str) -> Result<Namespace> {
+    pub async fn create_namespace(&self, name: &str, audit: &AuditEntry) -> Result<Namespace> {
         match self {
             #[cfg(feature = "sqlite")]
-            Self::Sqlite(store) => store.create_namespace(name, created_by).await,
+            Self::Sqlite(store) => store.create_namespace(name, audit).await,
             #[cfg(feature = "postgres")]
-            Self::Postgres(store) => store.create_namespace(name, created_by).await,
+            Self::Postgres(store) => store.create_namespace(name, audit).await,
         }
     }
 
@@ -110,12 +111,26 @@ impl MetaStore {
         }
     }
 
-    pub async fn delete_namespace(&self, name: &str) -> Result<()> {
+    pub async fn delete_namespace(&self, name: &str, audit: &AuditEntry) -> Result<()> {
         match self {
             #[cfg(feature = "sqlite")]
-            Self::Sqlite(store) => store.delete_namespace(name).await,
+            Self::Sqlite(store) => store.delete_namespace(name, audit).await,
             #[cfg(feature = "postgres")]
-            Self::Postgres(store) => store.delete_namespace(name).await,
+            Self::Postgres(store) => store.delete_namespace(name, audit).await,
+        }
+    }
+
+    pub async fn set_namespace_quota(
+        &self,
+        name: &str,
+        quota_bytes: Option<i64>,
+        audit: &AuditEntry,
+    ) -> Result<Namespace> {
+        match self {
+            #[cfg(feature = "sqlite")]
+            Self::Sqlite(store) => store.set_namespace_quota(name, quota_bytes, audit).await,
+            #[cfg(feature = "postgres")]
+            Self::Postgres(store) => store.set_namespace_quota(name, quota_bytes, audit).await,
         }
     }
 
@@ -129,19 +144,19 @@ impl MetaStore {
         path: &str,
         provider: &str,
         config: Option<serde_json::Value>,
-        created_by: &str,
+        audit: &AuditEntry,
     ) -> Result<Mount> {
         match self {
             #[cfg(feature = "sqlite")]
             Self::Sqlite(store) => {
                 store
-                    .create_mount(namespace_id, path, provider, config, created_by)
+                    .create_mount(namespace_id, path, provider, config, audit)
                     .await
             }
             #[cfg(feature = "postgres")]
             Self::Postgres(store) => {
                 store
-                    .create_mount(namespace_id, path, provider, config, created_by)
+                    .create_mount(namespace_id, path, provider, config, audit)
                     .await
             }
         }
@@ -165,12 +180,17 @@ impl MetaStore {
         }
     }
 
-    pub async fn delete_mount(&self, namespace_id: &str, path: &str) -> Result<()> {
+    pub async fn delete_mount(
+        &self,
+        namespace_id: &str,
+        path: &str,
+        audit: &AuditEntry,
+    ) -> Result<()> {
         match self {
             #[cfg(feature = "sqlite")]
-            Self::Sqlite(store) => store.delete_mount(namespace_id, path).await,
+            Self::Sqlite(store) => store.delete_mount(namespace_id, path, audit).await,
             #[cfg(feature = "postgres")]
-            Self::Postgres(store) => store.delete_mount(namespace_id, path).await,
+            Self::Postgres(store) => store.delete_mount(namespace_id, path, audit).await,
         }
     }
 
@@ -298,25 +318,44 @@ impl MetaStore {
     // API Key operations
     // ========================================================================
 
+    #[allow(clippy::too_many_arguments)]
     pub async fn create_api_key(
         &self,
         user_id: &str,
         namespace_id: &str,
         name: &str,
         roles: &[String],
+        scopes: &[PathGrant],
         expires_at: Option<chrono::DateTime<chrono::Utc>>,
+        audit: &AuditEntry,
     ) -> Result<(ApiKey, String)> {
         match self {
             #[cfg(feature = "sqlite")]
             Self::Sqlite(store) => {
                 store
-                    .create_api_key(user_id, namespace_id, name, roles, expires_at)
+                    .create_api_key(
+                        user_id,
+                        namespace_id,
+                        name,
+                        roles,
+                        scopes,
+                        expires_at,
+                        audit,
+                    )
                     .await
             }
             #[cfg(feature = "postgres")]
             Self::Postgres(store) => {
                 store
-                    .create_api_key(user_id, namespace_id, name, roles, expires_at)
+                    .create_api_key(
+                        user_id,
+                        namespace_id,
+                        name,
+                        roles,
+                        scopes,
+                        expires_at,
+                        audit,
+                    )
                     .await
             }
         }
@@ -340,12 +379,12 @@ impl MetaStore {
         }
     }
 
-    pub async fn revoke_api_key(&self, key_id: &str) -> Result<()> {
+    pub async fn revoke_api_key(&self, key_id: &str, audit: &AuditEntry) -> Result<()> {
         match self {
             #[cfg(feature = "sqlite")]
-            Self::Sqlite(store) => store.revoke_api_key(key_id).await,
+            Self::Sqlite(store) => store.revoke_api_key(key_id, audit).await,
             #[cfg(feature = "postgres")]
-            Self::Postgres(store) => store.revoke_api_key(key_id).await,
+            Self::Postgres(store) => store.revoke_api_key(key_id, audit).await,
         }
     }
 
@@ -357,4 +396,29 @@ impl MetaStore {
             Self::Postgres(store) => store.touch_api_key(key_id).await,
         }
     }
+
+    // ========================================================================
+    // Audit log operations
+    // ========================================================================
+
+    /// Record an action outside of any store transaction, e.g. one that
+    /// failed or that changes nothing in the database.
+    pub async fn append_audit(&self, entry: &AuditEntry, result: &str) -> Result<()> {
+        match self {
+            #[cfg(feature = "sqlite")]
+            Self::Sqlite(store) => store.append_audit(entry, result).await,
+            #[cfg(feature = "postgres")]
+            Self::Postgres(store) => store.append_audit(entry, result).await,
+        }
+    }
+
+    /// Audit log entries matching `query`, newest first.
+    pub async fn query_audit(&self, query: &AuditQuery) -> Result<Vec<AuditLog>> {
+        match self {
+            #[cfg(feature = "sqlite")]
+            Self::Sqlite(store) => store.query_audit(query).await,
+            #[cfg(feature = "postgres")]
+            Self::Postgres(store) => store.query_audit(query).await,
+        }
+    }
 }
@@ -3,6 +3,8 @@
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 
+use crate::auth::PathGrant;
+
 /// Namespace (tenant) record.
 #[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
 pub struct Namespace {
@@ -13,6 +15,8 @@ pub struct Namespace {
     pub created_by: String,
     pub updated_at: Option<DateTime<Utc>>,
     pub deleted_at: Option<DateTime<Utc>>,
+    /// Byte budget across all of the namespace's mounts; `None` is unlimited.
+    pub quota_bytes: Option<i64>,
 }
 
 /// Mount configuration record.
@@ -58,7 +62,8 @@ pub struct ApiKey {
     pub namespace_id: String,
     pub name: String,
     pub key_hash: String,
-    pub roles: String, // JSON array
+    pub roles: String,  // JSON array
+    pub scopes: String, // JSON array of PathGrant
     pub expires_at: Option<DateTime<Utc>>,
     pub last_used_at: Option<DateTime<Utc>>,
     pub created_at: DateTime<Utc>,
@@ -70,14 +75,75 @@ pub struct ApiKey {
 pub struct AuditLog {
     pub id: String,
     pub namespace: Option<String>,
+    /// Actor who performed the action.
     pub user_id: Option<String>,
     pub action: String,
+    /// Target of the action: a namespace, mount path, user or key id.
     pub resource: Option<String>,
     pub details: Option<String>,
     pub ip_address: Option<String>,
+    /// `ok`, or `error: ...` for actions that failed.
+    pub result: String,
     pub created_at: DateTime<Utc>,
 }
 
+/// An administrative action to record in the audit log.
+///
+/// Mutating store operations take one and write it in the same transaction
+/// as the change, so an action whose record can't be written doesn't happen.
+#[derive(Debug, Clone)]
+pub struct AuditEntry {
+    pub actor: String,
+    pub action: String,
+    pub namespace: Option<String>,
+    pub target: String,
+}
+
+impl AuditEntry {
+    #[must_use]
+    pub fn new(actor: &str, action: &str, target: &str) -> Self {
+        Self {
+            actor: actor.to_string(),
+            action: action.to_string(),
+            namespace: None,
+            target: target.to_string(),
+        }
+    }
+
+    #[must_use]
+    pub fn in_namespace(mut self, namespace: &str) -> Self {
+        self.namespace = Some(namespace.to_string());
+        self
+    }
+}
+
+/// Filters for querying the audit log; unset fields match everything.
+#[derive(Debug, Default, Deserialize)]
+pub struct AuditQuery {
+    pub actor: Option<String>,
+    pub action: Option<String>,
+    pub namespace: Option<String>,
+    pub target: Option<String>,
+    /// Only entries at or after this time.
+    pub since: Option<DateTime<Utc>>,
+    /// Most entries to return, newest first (default 100, at most 1000).
+    pub limit: Option<u32>,
+}
+
+impl AuditQuery {
+    pub const DEFAULT_LIMIT: u32 = 100;
+    pub const MAX_LIMIT: u32 = 1000;
+
+    #[must_use]
+    pub fn limit(&self) -> i64 {
+        i64::from(
+            self.limit
+                .unwrap_or(Self::DEFAULT_LIMIT)
+                .min(Self::MAX_LIMIT),
+        )
+    }
+}
+
 // ============================================================================
 // Request/Response DTOs
 // ============================================================================
@@ -87,6 +153,12 @@ pub struct CreateNamespaceRequest {
     pub name: String,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct SetQuotaRequest {
+    /// `None` removes the limit.
+    pub quota_bytes: Option<u64>,
+}
+
 #[derive(Debug, Serialize)]
 pub struct NamespaceResponse {
     pub id: String,
@@ -94,6 +166,7 @@ pub struct NamespaceResponse {
     pub status: String,
     pub created_at: DateTime<Utc>,
     pub created_by: String,
+    pub quota_bytes: Option<i64>,
 }
 
 impl From<Namespace> for NamespaceResponse {
@@ -104,6 +177,7 @@ impl From<Namespace> for NamespaceResponse {
             status: ns.status,
             created_at: ns.created_at,
             created_by: ns.created_by,
+            quota_bytes: ns.quota_bytes,
         }
     }
 }
@@ -184,10 +258,14 @@ pub struct UserRoleResponse {
 
 #[derive(Debug, Deserialize)]
 pub struct CreateApiKeyRequest {
+    /// User the key acts as.
+    pub user_id: String,
     pub namespace: String,
     pub name: String,
     #[serde(default)]
     pub roles: Vec<String>,
+    #[serde(default)]
+    pub scopes: Vec<PathGrant>,
     pub expires_in_days: Option<i64>,
 }
 
@@ -198,6 +276,7 @@ pub struct CreateApiKeyResponse {
     pub name: String,
     pub namespace: String,
     pub roles: Vec<String>,
+    pub scopes: Vec<PathGrant>,
     pub expires_at: Option<DateTime<Utc>>,
     pub created_at: DateTime<Utc>,
 }
@@ -208,6 +287,7 @@ pub struct ApiKeyResponse {
     pub name: String,
     pub namespace: String,
     pub roles: Vec<String>,
+    pub scopes: Vec<PathGrant>,
     pub expires_at: Option<DateTime<Utc>>,
     pub last_used_at: Option<DateTime<Utc>>,
     pub created_at: DateTime<Utc>,
@@ -225,6 +305,7 @@ pub struct ValidateTokenResponse {
     pub user_id: Option<String>,
     pub namespace: Option<String>,
     pub roles: Vec<String>,
+    pub scopes: Vec<PathGrant>,
     pub expires_at: Option<DateTime<Utc>>,
     pub error: Option<String>,
 }
@@ -235,6 +316,8 @@ pub struct GenerateTokenRequest {
     pub namespace: String,
     #[serde(default)]
     pub roles: Vec<String>,
+    #[serde(default)]
+    pub scopes: Vec<PathGrant>,
     pub ttl_seconds: Option<u64>,
 }
 
@@ -248,3 +331,28 @@ pub struct GenerateTokenResponse {
 pub struct ValidateApiKeyRequest {
     pub key: String,
 }
+
+#[derive(Debug, Serialize)]
+pub struct AuditLogResponse {
+    pub id: String,
+    pub actor: Option<String>,
+    pub action: String,
+    pub namespace: Option<String>,
+    pub target: Option<String>,
+    pub result: String,
+    pub created_at: DateTime<Utc>,
+}
+
+impl From<AuditLog> for AuditLogResponse {
+    fn from(entry: AuditLog) -> Self {
+        Self {
+            id: entry.id,
+            actor: entry.user_id,
+            action: entry.action,
+            namespace: entry.namespace,
+            target: entry.resource,
+            result: entry.result,
+            created_at: entry.created_at,
+        }
+    }
+}
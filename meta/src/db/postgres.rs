@@ -3,11 +3,12 @@
 #![allow(clippy::missing_errors_doc)]
 
 use chrono::{DateTime, Utc};
-use sqlx::{postgres::PgPoolOptions, PgPool};
+use sqlx::{postgres::PgPoolOptions, PgConnection, PgPool};
 use uuid::Uuid;
 
-use super::models::{ApiKey, Mount, Namespace, User, UserRole};
+use super::models::{ApiKey, AuditEntry, AuditLog, AuditQuery, Mount, Namespace, User, UserRole};
 use super::Result;
+use crate::auth::{generate_api_key, hash_api_key, PathGrant};
 use crate::error::MetaError;
 
 /// PostgreSQL-backed metadata store.
@@ -132,6 +133,21 @@ impl PostgresStore {
         .execute(&self.pool)
         .await?;
 
+        // Columns added after the initial schema.
+        sqlx::query(
+            "ALTER TABLE api_keys ADD COLUMN IF NOT EXISTS scopes JSONB NOT NULL DEFAULT '[]'",
+        )
+        .execute(&self.pool)
+        .await?;
+        sqlx::query("ALTER TABLE namespaces ADD COLUMN IF NOT EXISTS quota_bytes BIGINT")
+            .execute(&self.pool)
+            .await?;
+        sqlx::query(
+            "ALTER TABLE audit_logs ADD COLUMN IF NOT EXISTS result TEXT NOT NULL DEFAULT 'ok'",
+        )
+        .execute(&self.pool)
+        .await?;
+
         // Create indexes (IF NOT EXISTS is PG 9.5+)
         let indexes = [
             "CREATE INDEX IF NOT EXISTS idx_pg_namespaces_name ON namespaces(name)",
@@ -142,6 +158,7 @@ impl PostgresStore {
             "CREATE INDEX IF NOT EXISTS idx_pg_api_keys_user ON api_keys(user_id)",
             "CREATE INDEX IF NOT EXISTS idx_pg_api_keys_hash ON api_keys(key_hash)",
             "CREATE INDEX IF NOT EXISTS idx_pg_audit_logs_namespace ON audit_logs(namespace)",
+            "CREATE INDEX IF NOT EXISTS idx_pg_audit_logs_created ON audit_logs(created_at)",
         ];
 
         for idx in indexes {
@@ -155,10 +172,12 @@ impl PostgresStore {
     // Namespace operations
     // ========================================================================
 
-    pub async fn create_namespace(&self, name: &str, created_by: &str) -> Result<Namespace> {
+    pub async fn create_namespace(&self, name: &str, audit: &AuditEntry) -> Result<Namespace> {
         let id = Uuid::new_v4().to_string();
         let now = Utc::now();
+        let created_by = audit.actor.as_str();
 
+        let mut tx = self.pool.begin().await?;
         sqlx::query(
             r#"
             INSERT INTO namespaces (id, name, status, created_at, created_by)
@@ -169,8 +188,10 @@ impl PostgresStore {
         .bind(name)
         .bind(now)
         .bind(created_by)
-        .execute(&self.pool)
+        .execute(&mut *tx)
         .await?;
+        insert_audit(&mut tx, audit, "ok").await?;
+        tx.commit().await?;
 
         Ok(Namespace {
             id,
@@ -180,13 +201,14 @@ impl PostgresStore {
             created_by: created_by.to_string(),
             updated_at: None,
             deleted_at: None,
+            quota_bytes: None,
         })
     }
 
     pub async fn get_namespace(&self, name: &str) -> Result<Option<Namespace>> {
         let row: Option<Namespace> = sqlx::query_as(
             r#"
-            SELECT id, name, status, created_at, created_by, updated_at, deleted_at
+            SELECT id, name, status, created_at, created_by, updated_at, deleted_at, quota_bytes
             FROM namespaces
             WHERE name = $1 AND deleted_at IS NULL
             "#,
@@ -201,7 +223,7 @@ impl PostgresStore {
     pub async fn get_namespace_by_id(&self, id: &str) -> Result<Option<Namespace>> {
         let row: Option<Namespace> = sqlx::query_as(
             r#"
-            SELECT id, name, status, created_at, created_by, updated_at, deleted_at
+            SELECT id, name, status, created_at, created_by, updated_at, deleted_at, quota_bytes
             FROM namespaces
             WHERE id = $1 AND deleted_at IS NULL
             "#,
@@ -216,7 +238,7 @@ impl PostgresStore {
     pub async fn list_namespaces(&self) -> Result<Vec<Namespace>> {
         let rows: Vec<Namespace> = sqlx::query_as(
             r#"
-            SELECT id, name, status, created_at, created_by, updated_at, deleted_at
+            SELECT id, name, status, created_at, created_by, updated_at, deleted_at, quota_bytes
             FROM namespaces
             WHERE deleted_at IS NULL
             ORDER BY name
@@ -228,9 +250,10 @@ impl PostgresStore {
         Ok(rows)
     }
 
-    pub async fn delete_namespace(&self, name: &str) -> Result<()> {
+    pub async fn delete_namespace(&self, name: &str, audit: &AuditEntry) -> Result<()> {
         let now = Utc::now();
 
+        let mut tx = self.pool.begin().await?;
         let result = sqlx::query(
             r#"
             UPDATE namespaces
@@ -240,16 +263,45 @@ impl PostgresStore {
         )
         .bind(now)
         .bind(name)
-        .execute(&self.pool)
+        .execute(&mut *tx)
         .await?;
 
         if result.rows_affected() == 0 {
             return Err(MetaError::NotFound(format!("Namespace '{name}' not found")));
         }
+        insert_audit(&mut tx, audit, "ok").await?;
+        tx.commit().await?;
 
         Ok(())
     }
 
+    pub async fn set_namespace_quota(
+        &self,
+        name: &str,
+        quota_bytes: Option<i64>,
+        audit: &AuditEntry,
+    ) -> Result<Namespace> {
+        let mut tx = self.pool.begin().await?;
+        let row: Option<Namespace> = sqlx::query_as(
+            r#"
+            UPDATE namespaces
+            SET quota_bytes = $1, updated_at = $2
+            WHERE name = $3 AND deleted_at IS NULL
+            RETURNING id, name, status, created_at, created_by, updated_at, deleted_at, quota_bytes
+            "#,
+        )
+        .bind(quota_bytes)
+        .bind(Utc::now())
+        .bind(name)
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        let ns = row.ok_or_else(|| MetaError::NotFound(format!("Namespace '{name}' not found")))?;
+        insert_audit(&mut tx, audit, "ok").await?;
+        tx.commit().await?;
+        Ok(ns)
+    }
+
     // ========================================================================
     // Mount operations
     // ========================================================================
@@ -260,12 +312,14 @@ impl PostgresStore {
         path: &str,
         provider: &str,
         config: Option<serde_json::Value>,
-        created_by: &str,
+        audit: &AuditEntry,
     ) -> Result<Mount> {
         let id = Uuid::new_v4().to_string();
         let now = Utc::now();
         let config_str = config.as_ref().map(ToString::to_string);
+        let created_by = audit.actor.as_str();
 
+        let mut tx = self.pool.begin().await?;
         sqlx::query(
             r#"
             INSERT INTO mounts (id, namespace_id, path, provider, config, created_at, created_by)
@@ -279,8 +333,10 @@ impl PostgresStore {
         .bind(&config_str)
         .bind(now)
         .bind(created_by)
-        .execute(&self.pool)
+        .execute(&mut *tx)
         .await?;
+        insert_audit(&mut tx, audit, "ok").await?;
+        tx.commit().await?;
 
         Ok(Mount {
             id,
@@ -325,7 +381,13 @@ impl PostgresStore {
         Ok(rows.into_iter().map(Into::into).collect())
     }
 
-    pub async fn delete_mount(&self, namespace_id: &str, path: &str) -> Result<()> {
+    pub async fn delete_mount(
+        &self,
+        namespace_id: &str,
+        path: &str,
+        audit: &AuditEntry,
+    ) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
         let result = sqlx::query(
             r#"
             DELETE FROM mounts
@@ -334,7 +396,7 @@ impl PostgresStore {
         )
         .bind(namespace_id)
         .bind(path)
-        .execute(&self.pool)
+        .execute(&mut *tx)
         .await?;
 
         if result.rows_affected() == 0 {
@@ -342,6 +404,8 @@ impl PostgresStore {
                 "Mount '{path}' not found in namespace"
             )));
         }
+        insert_audit(&mut tx, audit, "ok").await?;
+        tx.commit().await?;
 
         Ok(())
     }
@@ -551,24 +615,29 @@ impl PostgresStore {
     // API Key operations
     // ========================================================================
 
+    #[allow(clippy::too_many_arguments)]
     pub async fn create_api_key(
         &self,
         user_id: &str,
         namespace_id: &str,
         name: &str,
         roles: &[String],
+        scopes: &[PathGrant],
         expires_at: Option<DateTime<Utc>>,
+        audit: &AuditEntry,
     ) -> Result<(ApiKey, String)> {
         let id = Uuid::new_v4().to_string();
-        let raw_key = format!("fs9_{}", Uuid::new_v4().to_string().replace('-', ""));
+        let raw_key = generate_api_key();
         let key_hash = hash_api_key(&raw_key);
         let now = Utc::now();
         let roles_json = serde_json::to_string(roles).unwrap_or_else(|_| "[]".to_string());
+        let scopes_json = serde_json::to_string(scopes).unwrap_or_else(|_| "[]".to_string());
 
+        let mut tx = self.pool.begin().await?;
         sqlx::query(
             r#"
-            INSERT INTO api_keys (id, user_id, namespace_id, name, key_hash, roles, expires_at, created_at)
-            VALUES ($1, $2, $3, $4, $5, $6::jsonb, $7, $8)
+            INSERT INTO api_keys (id, user_id, namespace_id, name, key_hash, roles, scopes, expires_at, created_at)
+            VALUES ($1, $2, $3, $4, $5, $6::jsonb, $7::jsonb, $8, $9)
             "#,
         )
         .bind(&id)
@@ -577,10 +646,13 @@ impl PostgresStore {
         .bind(name)
         .bind(&key_hash)
         .bind(&roles_json)
+        .bind(&scopes_json)
         .bind(expires_at)
         .bind(now)
-        .execute(&self.pool)
+        .execute(&mut *tx)
         .await?;
+        insert_audit(&mut tx, audit, "ok").await?;
+        tx.commit().await?;
 
         let api_key = ApiKey {
             id,
@@ -589,6 +661,7 @@ impl PostgresStore {
             name: name.to_string(),
             key_hash,
             roles: roles_json,
+            scopes: scopes_json,
             expires_at,
             last_used_at: None,
             created_at: now,
@@ -603,7 +676,7 @@ impl PostgresStore {
 
         let row: Option<PgApiKeyRow> = sqlx::query_as(
             r#"
-            SELECT id, user_id, namespace_id, name, key_hash, roles::text, expires_at, last_used_at, created_at, revoked_at
+            SELECT id, user_id, namespace_id, name, key_hash, roles::text, scopes::text, expires_at, last_used_at, created_at, revoked_at
             FROM api_keys
             WHERE key_hash = $1 AND revoked_at IS NULL
             "#,
@@ -631,7 +704,7 @@ impl PostgresStore {
     pub async fn list_api_keys(&self, user_id: &str) -> Result<Vec<ApiKey>> {
         let rows: Vec<PgApiKeyRow> = sqlx::query_as(
             r#"
-            SELECT id, user_id, namespace_id, name, key_hash, roles::text, expires_at, last_used_at, created_at, revoked_at
+            SELECT id, user_id, namespace_id, name, key_hash, roles::text, scopes::text, expires_at, last_used_at, created_at, revoked_at
             FROM api_keys
             WHERE user_id = $1
             ORDER BY created_at DESC
@@ -644,9 +717,10 @@ impl PostgresStore {
         Ok(rows.into_iter().map(Into::into).collect())
     }
 
-    pub async fn revoke_api_key(&self, key_id: &str) -> Result<()> {
+    pub async fn revoke_api_key(&self, key_id: &str, audit: &AuditEntry) -> Result<()> {
         let now = Utc::now();
 
+        let mut tx = self.pool.begin().await?;
         let result = sqlx::query(
             r#"
             UPDATE api_keys
@@ -656,12 +730,14 @@ impl PostgresStore {
         )
         .bind(now)
         .bind(key_id)
-        .execute(&self.pool)
+        .execute(&mut *tx)
         .await?;
 
         if result.rows_affected() == 0 {
             return Err(MetaError::NotFound(format!("API key '{key_id}' not found")));
         }
+        insert_audit(&mut tx, audit, "ok").await?;
+        tx.commit().await?;
 
         Ok(())
     }
@@ -683,22 +759,63 @@ impl PostgresStore {
 
         Ok(())
     }
-}
 
-/// Hash function for API keys (SHA-256).
-fn hash_api_key(key: &str) -> String {
-    use sha2::{Digest, Sha256};
-    use std::fmt::Write;
+    // ========================================================================
+    // Audit log operations
+    // ========================================================================
+
+    pub async fn append_audit(&self, entry: &AuditEntry, result: &str) -> Result<()> {
+        let mut conn = self.pool.acquire().await?;
+        insert_audit(&mut conn, entry, result).await
+    }
 
-    let mut hasher = Sha256::new();
-    hasher.update(key.as_bytes());
-    let digest = hasher.finalize();
+    pub async fn query_audit(&self, query: &AuditQuery) -> Result<Vec<AuditLog>> {
+        let rows: Vec<AuditLog> = sqlx::query_as(
+            r#"
+            SELECT id, namespace, user_id, action, resource, details::text AS details,
+                   ip_address, result, created_at
+            FROM audit_logs
+            WHERE ($1::text IS NULL OR user_id = $1)
+              AND ($2::text IS NULL OR action = $2)
+              AND ($3::text IS NULL OR namespace = $3)
+              AND ($4::text IS NULL OR resource = $4)
+              AND ($5::timestamptz IS NULL OR created_at >= $5)
+            ORDER BY created_at DESC
+            LIMIT $6
+            "#,
+        )
+        .bind(&query.actor)
+        .bind(&query.action)
+        .bind(&query.namespace)
+        .bind(&query.target)
+        .bind(query.since)
+        .bind(query.limit())
+        .fetch_all(&self.pool)
+        .await?;
 
-    let mut out = String::with_capacity(digest.len() * 2);
-    for b in digest {
-        write!(&mut out, "{b:02x}").expect("writing into String shouldn't fail");
+        Ok(rows)
     }
-    out
+}
+
+/// Write an audit record on `conn`, which is the transaction of the action
+/// being recorded when there is one.
+async fn insert_audit(conn: &mut PgConnection, entry: &AuditEntry, result: &str) -> Result<()> {
+    sqlx::query(
+        r#"
+        INSERT INTO audit_logs (id, namespace, user_id, action, resource, result, created_at)
+        VALUES ($1, $2, $3, $4, $5, $6, $7)
+        "#,
+    )
+    .bind(Uuid::new_v4().to_string())
+    .bind(&entry.namespace)
+    .bind(&entry.actor)
+    .bind(&entry.action)
+    .bind(&entry.target)
+    .bind(result)
+    .bind(Utc::now())
+    .execute(conn)
+    .await?;
+    Ok(())
 }
 
 // ============================================================================
@@ -740,6 +857,7 @@ struct PgApiKeyRow {
     name: String,
     key_hash: String,
     roles: String,
+    scopes: String,
     expires_at: Option<DateTime<Utc>>,
     last_used_at: Option<DateTime<Utc>>,
     created_at: DateTime<Utc>,
@@ -755,6 +873,7 @@ impl From<PgApiKeyRow> for ApiKey {
             name: row.name,
             key_hash: row.key_hash,
             roles: row.roles,
+            scopes: row.scopes,
             expires_at: row.expires_at,
             last_used_at: row.last_used_at,
             created_at: row.created_at,
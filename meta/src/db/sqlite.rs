@@ -3,11 +3,12 @@
 #![allow(clippy::missing_errors_doc)]
 
 use chrono::{DateTime, Utc};
-use sqlx::{sqlite::SqlitePoolOptions, SqlitePool};
+use sqlx::{sqlite::SqlitePoolOptions, SqliteConnection, SqlitePool};
 use uuid::Uuid;
 
-use super::models::{ApiKey, Mount, Namespace, User, UserRole};
+use super::models::{ApiKey, AuditEntry, AuditLog, AuditQuery, Mount, Namespace, User, UserRole};
 use super::Result;
+use crate::auth::{generate_api_key, hash_api_key, PathGrant};
 use crate::error::MetaError;
 
 /// SQLite-backed metadata store.
@@ -132,12 +133,40 @@ impl SqliteStore {
             "CREATE INDEX IF NOT EXISTS idx_api_keys_user ON api_keys(user_id)",
             "CREATE INDEX IF NOT EXISTS idx_api_keys_hash ON api_keys(key_hash)",
             "CREATE INDEX IF NOT EXISTS idx_audit_logs_namespace ON audit_logs(namespace)",
+            "CREATE INDEX IF NOT EXISTS idx_audit_logs_created ON audit_logs(created_at)",
         ];
 
         for stmt in stmts {
             sqlx::query(stmt).execute(&self.pool).await?;
         }
 
+        // Columns added after the initial schema.
+        self.add_column("api_keys", "scopes", "TEXT NOT NULL DEFAULT '[]'")
+            .await?;
+        self.add_column("namespaces", "quota_bytes", "INTEGER")
+            .await?;
+        self.add_column("audit_logs", "result", "TEXT NOT NULL DEFAULT 'ok'")
+            .await?;
+
+        Ok(())
+    }
+
+    /// `ALTER TABLE ... ADD COLUMN` unless the column is already there;
+    /// `SQLite` has no `ADD COLUMN IF NOT EXISTS`.
+    async fn add_column(&self, table: &str, column: &str, definition: &str) -> Result<()> {
+        let exists: i64 =
+            sqlx::query_scalar("SELECT COUNT(*) FROM pragma_table_info(?) WHERE name = ?")
+                .bind(table)
+                .bind(column)
+                .fetch_one(&self.pool)
+                .await?;
+        if exists == 0 {
+            sqlx::query(&format!(
+                "ALTER TABLE {table} ADD COLUMN {column} {definition}"
+            ))
+            .execute(&self.pool)
+            .await?;
+        }
         Ok(())
     }
 
@@ -145,10 +174,12 @@ impl SqliteStore {
     // Namespace operations
     // ========================================================================
 
-    pub async fn create_namespace(&self, name: &str, created_by: &str) -> Result<Namespace> {
+    pub async fn create_namespace(&self, name: &str, audit: &AuditEntry) -> Result<Namespace> {
         let id = Uuid::new_v4().to_string();
         let now = Utc::now();
+        let created_by = audit.actor.as_str();
 
+        let mut tx = self.pool.begin().await?;
         sqlx::query(
             r"
             INSERT INTO namespaces (id, name, status, created_at, created_by)
@@ -159,8 +190,10 @@ impl SqliteStore {
         .bind(name)
         .bind(now.to_rfc3339())
         .bind(created_by)
-        .execute(&self.pool)
+        .execute(&mut *tx)
         .await?;
+        insert_audit(&mut tx, audit, "ok").await?;
+        tx.commit().await?;
 
         Ok(Namespace {
             id,
@@ -170,13 +203,14 @@ impl SqliteStore {
             created_by: created_by.to_string(),
             updated_at: None,
             deleted_at: None,
+            quota_bytes: None,
         })
     }
 
     pub async fn get_namespace(&self, name: &str) -> Result<Option<Namespace>> {
         let row: Option<NamespaceRow> = sqlx::query_as(
             r"
-            SELECT id, name, status, created_at, created_by, updated_at, deleted_at
+            SELECT id, name, status, created_at, created_by, updated_at, deleted_at, quota_bytes
             FROM namespaces
             WHERE name = ? AND deleted_at IS NULL
             ",
@@ -191,7 +225,7 @@ impl SqliteStore {
     pub async fn get_namespace_by_id(&self, id: &str) -> Result<Option<Namespace>> {
         let row: Option<NamespaceRow> = sqlx::query_as(
             r"
-            SELECT id, name, status, created_at, created_by, updated_at, deleted_at
+            SELECT id, name, status, created_at, created_by, updated_at, deleted_at, quota_bytes
             FROM namespaces
             WHERE id = ? AND deleted_at IS NULL
             ",
@@ -206,7 +240,7 @@ impl SqliteStore {
     pub async fn list_namespaces(&self) -> Result<Vec<Namespace>> {
         let rows: Vec<NamespaceRow> = sqlx::query_as(
             r"
-            SELECT id, name, status, created_at, created_by, updated_at, deleted_at
+            SELECT id, name, status, created_at, created_by, updated_at, deleted_at, quota_bytes
             FROM namespaces
             WHERE deleted_at IS NULL
             ORDER BY name
@@ -218,9 +252,10 @@ impl SqliteStore {
         Ok(rows.into_iter().map(Into::into).collect())
     }
 
-    pub async fn delete_namespace(&self, name: &str) -> Result<()> {
+    pub async fn delete_namespace(&self, name: &str, audit: &AuditEntry) -> Result<()> {
         let now = Utc::now();
 
+        let mut tx = self.pool.begin().await?;
         let result = sqlx::query(
             r"
             UPDATE namespaces
@@ -230,16 +265,49 @@ impl SqliteStore {
         )
         .bind(now.to_rfc3339())
         .bind(name)
-        .execute(&self.pool)
+        .execute(&mut *tx)
         .await?;
 
         if result.rows_affected() == 0 {
             return Err(MetaError::NotFound(format!("Namespace '{name}' not found")));
         }
+        insert_audit(&mut tx, audit, "ok").await?;
+        tx.commit().await?;
 
         Ok(())
     }
 
+    pub async fn set_namespace_quota(
+        &self,
+        name: &str,
+        quota_bytes: Option<i64>,
+        audit: &AuditEntry,
+    ) -> Result<Namespace> {
+        let mut tx = self.pool.begin().await?;
+        let result = sqlx::query(
+            r"
+            UPDATE namespaces
+            SET quota_bytes = ?, updated_at = ?
+            WHERE name = ? AND deleted_at IS NULL
+            ",
+        )
+        .bind(quota_bytes)
+        .bind(Utc::now().to_rfc3339())
+        .bind(name)
+        .execute(&mut *tx)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(MetaError::NotFound(format!("Namespace '{name}' not found")));
+        }
+        insert_audit(&mut tx, audit, "ok").await?;
+        tx.commit().await?;
+
+        self.get_namespace(name)
+            .await?
+            .ok_or_else(|| MetaError::NotFound(format!("Namespace '{name}' not found")))
+    }
+
     // ========================================================================
     // Mount operations
     // ========================================================================
@@ -250,12 +318,14 @@ impl SqliteStore {
         path: &str,
         provider: &str,
         config: Option<serde_json::Value>,
-        created_by: &str,
+        audit: &AuditEntry,
     ) -> Result<Mount> {
         let id = Uuid::new_v4().to_string();
         let now = Utc::now();
         let config_str = config.as_ref().map(ToString::to_string);
+        let created_by = audit.actor.as_str();
 
+        let mut tx = self.pool.begin().await?;
         sqlx::query(
             r"
             INSERT INTO mounts (id, namespace_id, path, provider, config, created_at, created_by)
@@ -269,8 +339,10 @@ impl SqliteStore {
         .bind(&config_str)
         .bind(now.to_rfc3339())
         .bind(created_by)
-        .execute(&self.pool)
+        .execute(&mut *tx)
         .await?;
+        insert_audit(&mut tx, audit, "ok").await?;
+        tx.commit().await?;
 
         Ok(Mount {
             id,
@@ -315,7 +387,13 @@ impl SqliteStore {
         Ok(rows.into_iter().map(Into::into).collect())
     }
 
-    pub async fn delete_mount(&self, namespace_id: &str, path: &str) -> Result<()> {
+    pub async fn delete_mount(
+        &self,
+        namespace_id: &str,
+        path: &str,
+        audit: &AuditEntry,
+    ) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
         let result = sqlx::query(
             r"
             DELETE FROM mounts
@@ -324,7 +402,7 @@ impl SqliteStore {
         )
         .bind(namespace_id)
         .bind(path)
-        .execute(&self.pool)
+        .execute(&mut *tx)
         .await?;
 
         if result.rows_affected() == 0 {
@@ -332,6 +410,8 @@ impl SqliteStore {
                 "Mount '{path}' not found in namespace"
             )));
         }
+        insert_audit(&mut tx, audit, "ok").await?;
+        tx.commit().await?;
 
         Ok(())
     }
@@ -541,24 +621,29 @@ impl SqliteStore {
     // API Key operations
     // ========================================================================
 
+    #[allow(clippy::too_many_arguments)]
     pub async fn create_api_key(
         &self,
         user_id: &str,
         namespace_id: &str,
         name: &str,
         roles: &[String],
+        scopes: &[PathGrant],
         expires_at: Option<DateTime<Utc>>,
+        audit: &AuditEntry,
     ) -> Result<(ApiKey, String)> {
         let id = Uuid::new_v4().to_string();
-        let raw_key = format!("fs9_{}", Uuid::new_v4().to_string().replace('-', ""));
+        let raw_key = generate_api_key();
         let key_hash = hash_api_key(&raw_key);
         let now = Utc::now();
         let roles_json = serde_json::to_string(roles).unwrap_or_else(|_| "[]".to_string());
+        let scopes_json = serde_json::to_string(scopes).unwrap_or_else(|_| "[]".to_string());
 
+        let mut tx = self.pool.begin().await?;
         sqlx::query(
             r"
-            INSERT INTO api_keys (id, user_id, namespace_id, name, key_hash, roles, expires_at, created_at)
-            VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+            INSERT INTO api_keys (id, user_id, namespace_id, name, key_hash, roles, scopes, expires_at, created_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)
             ",
         )
         .bind(&id)
@@ -567,10 +652,13 @@ impl SqliteStore {
         .bind(name)
         .bind(&key_hash)
         .bind(&roles_json)
+        .bind(&scopes_json)
         .bind(expires_at.map(|d| d.to_rfc3339()))
         .bind(now.to_rfc3339())
-        .execute(&self.pool)
+        .execute(&mut *tx)
         .await?;
+        insert_audit(&mut tx, audit, "ok").await?;
+        tx.commit().await?;
 
         let api_key = ApiKey {
             id,
@@ -579,6 +667,7 @@ impl SqliteStore {
             name: name.to_string(),
             key_hash,
             roles: roles_json,
+            scopes: scopes_json,
             expires_at,
             last_used_at: None,
             created_at: now,
@@ -593,7 +682,7 @@ impl SqliteStore {
 
         let row: Option<ApiKeyRow> = sqlx::query_as(
             r"
-            SELECT id, user_id, namespace_id, name, key_hash, roles, expires_at, last_used_at, created_at, revoked_at
+            SELECT id, user_id, namespace_id, name, key_hash, roles, scopes, expires_at, last_used_at, created_at, revoked_at
             FROM api_keys
             WHERE key_hash = ? AND revoked_at IS NULL
             ",
@@ -621,7 +710,7 @@ impl SqliteStore {
     pub async fn list_api_keys(&self, user_id: &str) -> Result<Vec<ApiKey>> {
         let rows: Vec<ApiKeyRow> = sqlx::query_as(
             r"
-            SELECT id, user_id, namespace_id, name, key_hash, roles, expires_at, last_used_at, created_at, revoked_at
+            SELECT id, user_id, namespace_id, name, key_hash, roles, scopes, expires_at, last_used_at, created_at, revoked_at
             FROM api_keys
             WHERE user_id = ?
             ORDER BY created_at DESC
@@ -634,9 +723,10 @@ impl SqliteStore {
         Ok(rows.into_iter().map(Into::into).collect())
     }
 
-    pub async fn revoke_api_key(&self, key_id: &str) -> Result<()> {
+    pub async fn revoke_api_key(&self, key_id: &str, audit: &AuditEntry) -> Result<()> {
         let now = Utc::now();
 
+        let mut tx = self.pool.begin().await?;
         let result = sqlx::query(
             r"
             UPDATE api_keys
@@ -646,12 +736,14 @@ impl SqliteStore {
         )
         .bind(now.to_rfc3339())
         .bind(key_id)
-        .execute(&self.pool)
+        .execute(&mut *tx)
         .await?;
 
         if result.rows_affected() == 0 {
             return Err(MetaError::NotFound(format!("API key '{key_id}' not found")));
         }
+        insert_audit(&mut tx, audit, "ok").await?;
+        tx.commit().await?;
 
         Ok(())
     }
@@ -673,22 +765,62 @@ impl SqliteStore {
 
         Ok(())
     }
-}
 
-/// Hash function for API keys (SHA-256).
-fn hash_api_key(key: &str) -> String {
-    use sha2::{Digest, Sha256};
-    use std::fmt::Write;
+    // ========================================================================
+    // Audit log operations
+    // ========================================================================
+
+    pub async fn append_audit(&self, entry: &AuditEntry, result: &str) -> Result<()> {
+        let mut conn = self.pool.acquire().await?;
+        insert_audit(&mut conn, entry, result).await
+    }
 
-    let mut hasher = Sha256::new();
-    hasher.update(key.as_bytes());
-    let digest = hasher.finalize();
+    pub async fn query_audit(&self, query: &AuditQuery) -> Result<Vec<AuditLog>> {
+        let rows: Vec<AuditLogRow> = sqlx::query_as(
+            r"
+            SELECT id, namespace, user_id, action, resource, details, ip_address, result, created_at
+            FROM audit_logs
+            WHERE (?1 IS NULL OR user_id = ?1)
+              AND (?2 IS NULL OR action = ?2)
+              AND (?3 IS NULL OR namespace = ?3)
+              AND (?4 IS NULL OR resource = ?4)
+              AND (?5 IS NULL OR created_at >= ?5)
+            ORDER BY created_at DESC, rowid DESC
+            LIMIT ?6
+            ",
+        )
+        .bind(&query.actor)
+        .bind(&query.action)
+        .bind(&query.namespace)
+        .bind(&query.target)
+        .bind(query.since.map(|d| d.to_rfc3339()))
+        .bind(query.limit())
+        .fetch_all(&self.pool)
+        .await?;
 
-    let mut out = String::with_capacity(digest.len() * 2);
-    for b in digest {
-        write!(&mut out, "{b:02x}").expect("writing into String shouldn't fail");
+        Ok(rows.into_iter().map(Into::into).collect())
     }
-    out
+}
+
+/// Write an audit record on `conn`, which is the transaction of the action
+/// being recorded when there is one.
+async fn insert_audit(conn: &mut SqliteConnection, entry: &AuditEntry, result: &str) -> Result<()> {
+    sqlx::query(
+        r"
+        INSERT INTO audit_logs (id, namespace, user_id, action, resource, result, created_at)
+        VALUES (?, ?, ?, ?, ?, ?, ?)
+        ",
+    )
+    .bind(Uuid::new_v4().to_string())
+    .bind(&entry.namespace)
+    .bind(&entry.actor)
+    .bind(&entry.action)
+    .bind(&entry.target)
+    .bind(result)
+    .bind(Utc::now().to_rfc3339())
+    .execute(conn)
+    .await?;
+    Ok(())
 }
 
 // ============================================================================
@@ -704,6 +836,7 @@ struct NamespaceRow {
     created_by: String,
     updated_at: Option<String>,
     deleted_at: Option<String>,
+    quota_bytes: Option<i64>,
 }
 
 impl From<NamespaceRow> for Namespace {
@@ -716,6 +849,7 @@ impl From<NamespaceRow> for Namespace {
             created_by: row.created_by,
             updated_at: row.updated_at.as_ref().map(|s| parse_datetime(s)),
             deleted_at: row.deleted_at.as_ref().map(|s| parse_datetime(s)),
+            quota_bytes: row.quota_bytes,
         }
     }
 }
@@ -801,6 +935,7 @@ struct ApiKeyRow {
     name: String,
     key_hash: String,
     roles: String,
+    scopes: String,
     expires_at: Option<String>,
     last_used_at: Option<String>,
     created_at: String,
@@ -816,6 +951,7 @@ impl From<ApiKeyRow> for ApiKey {
             name: row.name,
             key_hash: row.key_hash,
             roles: row.roles,
+            scopes: row.scopes,
             expires_at: row.expires_at.as_ref().map(|s| parse_datetime(s)),
             last_used_at: row.last_used_at.as_ref().map(|s| parse_datetime(s)),
             created_at: parse_datetime(&row.created_at),
@@ -824,6 +960,35 @@ impl From<ApiKeyRow> for ApiKey {
     }
 }
 
+#[derive(sqlx::FromRow)]
+struct AuditLogRow {
+    id: String,
+    namespace: Option<String>,
+    user_id: Option<String>,
+    action: String,
+    resource: Option<String>,
+    details: Option<String>,
+    ip_address: Option<String>,
+    result: String,
+    created_at: String,
+}
+
+impl From<AuditLogRow> for AuditLog {
+    fn from(row: AuditLogRow) -> Self {
+        Self {
+            id: row.id,
+            namespace: row.namespace,
+            user_id: row.user_id,
+            action: row.action,
+            resource: row.resource,
+            details: row.details,
+            ip_address: row.ip_address,
+            result: row.result,
+            created_at: parse_datetime(&row.created_at),
+        }
+    }
+}
+
 /// Parse ISO 8601 datetime string to `DateTime<Utc>`.
 fn parse_datetime(s: &str) -> DateTime<Utc> {
     DateTime::parse_from_rfc3339(s).map_or_else(|_| Utc::now(), |dt| dt.with_timezone(&Utc))
@@ -839,12 +1004,19 @@ mod tests {
         store
     }
 
+    fn by_admin() -> AuditEntry {
+        AuditEntry::new("admin", "test", "test")
+    }
+
     #[tokio::test]
     async fn test_namespace_crud() {
         let store = setup_test_db().await;
 
         // Create
-        let ns = store.create_namespace("test-ns", "admin").await.unwrap();
+        let ns = store
+            .create_namespace("test-ns", &by_admin())
+            .await
+            .unwrap();
         assert_eq!(ns.name, "test-ns");
         assert_eq!(ns.status, "active");
 
@@ -861,7 +1033,10 @@ mod tests {
         assert_eq!(list.len(), 1);
 
         // Delete
-        store.delete_namespace("test-ns").await.unwrap();
+        store
+            .delete_namespace("test-ns", &by_admin())
+            .await
+            .unwrap();
         let deleted = store.get_namespace("test-ns").await.unwrap();
         assert!(deleted.is_none());
     }
@@ -870,8 +1045,11 @@ mod tests {
     async fn test_namespace_already_exists() {
         let store = setup_test_db().await;
 
-        store.create_namespace("test-ns", "admin").await.unwrap();
-        let result = store.create_namespace("test-ns", "admin").await;
+        store
+            .create_namespace("test-ns", &by_admin())
+            .await
+            .unwrap();
+        let result = store.create_namespace("test-ns", &by_admin()).await;
         assert!(result.is_err());
     }
 
@@ -879,7 +1057,10 @@ mod tests {
     async fn test_mount_crud() {
         let store = setup_test_db().await;
 
-        let ns = store.create_namespace("test-ns", "admin").await.unwrap();
+        let ns = store
+            .create_namespace("test-ns", &by_admin())
+            .await
+            .unwrap();
 
         // Create mount
         let mount = store
@@ -888,7 +1069,7 @@ mod tests {
                 "/data",
                 "pagefs",
                 Some(serde_json::json!({"uid": 1000})),
-                "admin",
+                &by_admin(),
             )
             .await
             .unwrap();
@@ -904,7 +1085,10 @@ mod tests {
         assert_eq!(list.len(), 1);
 
         // Delete mount
-        store.delete_mount(&ns.id, "/data").await.unwrap();
+        store
+            .delete_mount(&ns.id, "/data", &by_admin())
+            .await
+            .unwrap();
         let deleted = store.get_mount(&ns.id, "/data").await.unwrap();
         assert!(deleted.is_none());
     }
@@ -942,7 +1126,10 @@ mod tests {
     async fn test_role_operations() {
         let store = setup_test_db().await;
 
-        let ns = store.create_namespace("test-ns", "admin").await.unwrap();
+        let ns = store
+            .create_namespace("test-ns", &by_admin())
+            .await
+            .unwrap();
         let user = store.create_user("alice", None, None).await.unwrap();
 
         // Assign role
@@ -976,20 +1163,36 @@ mod tests {
     async fn test_api_key_operations() {
         let store = setup_test_db().await;
 
-        let ns = store.create_namespace("test-ns", "admin").await.unwrap();
+        let ns = store
+            .create_namespace("test-ns", &by_admin())
+            .await
+            .unwrap();
         let user = store.create_user("alice", None, None).await.unwrap();
 
         // Create API key
         let (api_key, raw_key) = store
-            .create_api_key(&user.id, &ns.id, "my-key", &["read-only".to_string()], None)
+            .create_api_key(
+                &user.id,
+                &ns.id,
+                "my-key",
+                &["read-only".to_string()],
+                &[PathGrant {
+                    prefix: "/uploads".to_string(),
+                    access: crate::auth::Access::ReadWrite,
+                }],
+                None,
+                &by_admin(),
+            )
             .await
             .unwrap();
         assert_eq!(api_key.name, "my-key");
         assert!(raw_key.starts_with("fs9_"));
+        assert_ne!(api_key.key_hash, raw_key, "keys must be stored hashed");
 
         // Validate API key
         let validated = store.validate_api_key(&raw_key).await.unwrap().unwrap();
         assert_eq!(validated.id, api_key.id);
+        assert_eq!(validated.scopes, r#"[{"prefix":"/uploads","access":"rw"}]"#);
 
         // Touch API key
         store.touch_api_key(&api_key.id).await.unwrap();
@@ -999,8 +1202,51 @@ mod tests {
         assert_eq!(list.len(), 1);
 
         // Revoke API key
-        store.revoke_api_key(&api_key.id).await.unwrap();
+        store
+            .revoke_api_key(&api_key.id, &by_admin())
+            .await
+            .unwrap();
         let revoked = store.validate_api_key(&raw_key).await.unwrap();
         assert!(revoked.is_none());
     }
+
+    #[tokio::test]
+    async fn test_audit_records_commit_with_the_action() {
+        let store = setup_test_db().await;
+
+        let create =
+            AuditEntry::new("alice", "namespace.create", "audited").in_namespace("audited");
+        store.create_namespace("audited", &create).await.unwrap();
+        let missing = AuditEntry::new("alice", "namespace.delete", "missing");
+        assert!(store.delete_namespace("missing", &missing).await.is_err());
+
+        let entries = store.query_audit(&AuditQuery::default()).await.unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].user_id.as_deref(), Some("alice"));
+        assert_eq!(entries[0].action, "namespace.create");
+        assert_eq!(entries[0].namespace.as_deref(), Some("audited"));
+        assert_eq!(entries[0].resource.as_deref(), Some("audited"));
+        assert_eq!(entries[0].result, "ok");
+
+        let query = AuditQuery {
+            actor: Some("bob".to_string()),
+            ..AuditQuery::default()
+        };
+        assert!(store.query_audit(&query).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_failed_audit_write_aborts_the_action() {
+        let store = setup_test_db().await;
+        sqlx::query("DROP TABLE audit_logs")
+            .execute(&store.pool)
+            .await
+            .unwrap();
+
+        assert!(store
+            .create_namespace("test-ns", &by_admin())
+            .await
+            .is_err());
+        assert!(store.get_namespace("test-ns").await.unwrap().is_none());
+    }
 }
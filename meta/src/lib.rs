@@ -14,6 +14,8 @@ pub mod error;
 
 use std::sync::Arc;
 
+use axum::Router;
+
 pub use db::MetaStore;
 pub use error::MetaError;
 
@@ -41,3 +43,26 @@ impl AppState {
         }
     }
 }
+
+/// Build the full service router: `/health` plus `/api/v1/*` behind the
+/// admin key check.
+pub fn app(state: AppState) -> Router {
+    let api_router = api::router().layer(axum::middleware::from_fn_with_state(
+        state.clone(),
+        auth::require_admin_key,
+    ));
+    Router::new()
+        .nest("/api/v1", api_router)
+        .route("/health", axum::routing::get(health))
+        .with_state(state)
+}
+
+/// Serve `app` on an already bound listener until the server stops.
+pub async fn serve(listener: tokio::net::TcpListener, app: Router) -> std::io::Result<()> {
+    axum::serve(listener, app).await
+}
+
+/// Health check endpoint.
+async fn health() -> &'static str {
+    "ok"
+}
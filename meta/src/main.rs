@@ -6,13 +6,12 @@
 use std::net::SocketAddr;
 use std::path::Path;
 
-use axum::Router;
 use clap::Parser;
 use serde::Deserialize;
 use tower_http::trace::TraceLayer;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
-use fs9_meta::{api, auth, AppState, MetaStore};
+use fs9_meta::{AppState, MetaStore};
 
 /// Configuration file structure for fs9-meta.
 #[derive(Debug, Deserialize, Default)]
@@ -175,28 +174,14 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Create app state
     let state = AppState::new(store, jwt_secret, admin_key);
 
-    // Build router
-    let api_router = api::router().layer(axum::middleware::from_fn_with_state(
-        state.clone(),
-        auth::require_admin_key,
-    ));
-    let app = Router::new()
-        .nest("/api/v1", api_router)
-        .route("/health", axum::routing::get(health))
-        .layer(TraceLayer::new_for_http())
-        .with_state(state);
+    let app = fs9_meta::app(state).layer(TraceLayer::new_for_http());
 
     // Start server
     let addr: SocketAddr = format!("{host}:{port}").parse()?;
     tracing::info!(%addr, "Starting fs9-meta server");
 
     let listener = tokio::net::TcpListener::bind(addr).await?;
-    axum::serve(listener, app).await?;
+    fs9_meta::serve(listener, app).await?;
 
     Ok(())
 }
-
-/// Health check endpoint.
-async fn health() -> &'static str {
-    "ok"
-}
@@ -24,11 +24,22 @@ async fn request_json(
     method: &str,
     uri: &str,
     body: Option<Value>,
+) -> (StatusCode, Value) {
+    request_json_as(app, "system", method, uri, body).await
+}
+
+async fn request_json_as(
+    app: Router,
+    actor: &str,
+    method: &str,
+    uri: &str,
+    body: Option<Value>,
 ) -> (StatusCode, Value) {
     let req = Request::builder()
         .method(method)
         .uri(uri)
-        .header("content-type", "application/json");
+        .header("content-type", "application/json")
+        .header(fs9_meta::auth::ACTOR_HEADER, actor);
 
     let body = body.map_or_else(Body::empty, |body| {
         Body::from(serde_json::to_string(&body).unwrap())
@@ -115,6 +126,54 @@ async fn test_namespace_duplicate() {
     assert_eq!(status, StatusCode::CONFLICT);
 }
 
+#[tokio::test]
+async fn test_namespace_quota() {
+    let app = create_test_app().await;
+
+    let (status, body) = request_json(
+        app.clone(),
+        "POST",
+        "/api/v1/namespaces",
+        Some(json!({"name": "quota-ns"})),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["quota_bytes"], Value::Null);
+
+    let (status, body) = request_json(
+        app.clone(),
+        "PUT",
+        "/api/v1/namespaces/quota-ns/quota",
+        Some(json!({"quota_bytes": 1024})),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["quota_bytes"], 1024);
+
+    let (_, body) = request_json(app.clone(), "GET", "/api/v1/namespaces/quota-ns", None).await;
+    assert_eq!(body["quota_bytes"], 1024);
+
+    // Lifting the quota
+    let (status, body) = request_json(
+        app.clone(),
+        "PUT",
+        "/api/v1/namespaces/quota-ns/quota",
+        Some(json!({"quota_bytes": null})),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["quota_bytes"], Value::Null);
+
+    let (status, _) = request_json(
+        app,
+        "PUT",
+        "/api/v1/namespaces/missing/quota",
+        Some(json!({"quota_bytes": 1})),
+    )
+    .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
 #[tokio::test]
 async fn test_user_crud() {
     let app = create_test_app().await;
@@ -314,3 +373,92 @@ async fn test_mount_crud() {
     assert_eq!(status, StatusCode::OK);
     assert_eq!(body["deleted"], true);
 }
+
+#[tokio::test]
+async fn test_audit_log_records_admin_actions() {
+    let app = create_test_app().await;
+
+    let (status, _) = request_json_as(
+        app.clone(),
+        "alice",
+        "POST",
+        "/api/v1/namespaces",
+        Some(json!({"name": "audit-ns"})),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (_, user) = request_json(
+        app.clone(),
+        "POST",
+        "/api/v1/users",
+        Some(json!({"username": "carol"})),
+    )
+    .await;
+    let (status, key) = request_json(
+        app.clone(),
+        "POST",
+        "/api/v1/apikeys",
+        Some(json!({
+            "user_id": user["id"],
+            "namespace": "audit-ns",
+            "name": "ci"
+        })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let key_id = key["id"].as_str().unwrap();
+
+    let (status, _) = request_json_as(
+        app.clone(),
+        "bob",
+        "DELETE",
+        &format!("/api/v1/apikeys/{key_id}"),
+        None,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+
+    // A failed action is recorded too.
+    let (status, _) = request_json_as(
+        app.clone(),
+        "bob",
+        "DELETE",
+        "/api/v1/namespaces/missing",
+        None,
+    )
+    .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let (status, body) = request_json(app.clone(), "GET", "/api/v1/audit", None).await;
+    assert_eq!(status, StatusCode::OK);
+    let entries = body.as_array().unwrap();
+    let find = |action: &str| {
+        entries
+            .iter()
+            .find(|e| e["action"] == action)
+            .unwrap_or_else(|| panic!("no {action} entry in {body}"))
+    };
+
+    let created = find("namespace.create");
+    assert_eq!(created["actor"], "alice");
+    assert_eq!(created["target"], "audit-ns");
+    assert_eq!(created["namespace"], "audit-ns");
+    assert_eq!(created["result"], "ok");
+
+    let revoked = find("apikey.revoke");
+    assert_eq!(revoked["actor"], "bob");
+    assert_eq!(revoked["target"], key_id);
+    assert_eq!(revoked["result"], "ok");
+
+    let failed = find("namespace.delete");
+    assert_eq!(failed["target"], "missing");
+    assert!(failed["result"].as_str().unwrap().starts_with("error:"));
+
+    // Filters narrow the result.
+    let (status, body) = request_json(app, "GET", "/api/v1/audit?actor=alice", None).await;
+    assert_eq!(status, StatusCode::OK);
+    let entries = body.as_array().unwrap();
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0]["action"], "namespace.create");
+}
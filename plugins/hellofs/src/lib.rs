@@ -17,8 +17,15 @@ use serde::Deserialize;
 
 #[derive(Debug, Clone, Deserialize)]
 struct HelloConfig {
+    /// Template for `/hello`. `{{hostname}}` and `{{time}}` are filled in
+    /// each time the file is read.
     #[serde(default = "default_greeting")]
     greeting: String,
+    /// Also expose `/greeting`, a writable file holding the template, so
+    /// the greeting can be changed at runtime. Changes are kept in memory
+    /// only.
+    #[serde(default)]
+    writable_greeting: bool,
 }
 
 fn default_greeting() -> String {
@@ -29,18 +36,91 @@ impl Default for HelloConfig {
     fn default() -> Self {
         Self {
             greeting: default_greeting(),
+            writable_greeting: false,
         }
     }
 }
 
+const HELLO: &str = "/hello";
+const GREETING: &str = "/greeting";
+
 #[derive(Debug, Clone)]
 struct HelloFile {
     data: Bytes,
     mtime: SystemTime,
 }
 
+impl HelloFile {
+    fn new(data: impl Into<Bytes>) -> Self {
+        Self {
+            data: data.into(),
+            mtime: SystemTime::now(),
+        }
+    }
+
+    fn info(&self, path: &str) -> FileInfo {
+        FileInfo {
+            path: path.to_string(),
+            size: self.data.len() as u64,
+            file_type: FileType::Regular,
+            mode: 0o644,
+            uid: 0,
+            gid: 0,
+            atime: self.mtime,
+            mtime: self.mtime,
+            ctime: self.mtime,
+            etag: String::new(),
+            symlink_target: None,
+        }
+    }
+
+    fn read_at(&self, offset: u64, size: usize) -> Bytes {
+        let start = (offset as usize).min(self.data.len());
+        let end = (start + size).min(self.data.len());
+        self.data.slice(start..end)
+    }
+
+    fn write_at(&mut self, offset: u64, data: &[u8], append: bool) {
+        let offset = if append {
+            self.data.len()
+        } else {
+            offset as usize
+        };
+
+        let mut buf = self.data.to_vec();
+        if offset + data.len() > buf.len() {
+            buf.resize(offset + data.len(), 0);
+        }
+        buf[offset..offset + data.len()].copy_from_slice(data);
+        self.data = Bytes::from(buf);
+        self.mtime = SystemTime::now();
+    }
+}
+
+/// The machine's name for `{{hostname}}`, looked up once per provider.
+#[allow(unsafe_code)]
+fn hostname() -> String {
+    #[cfg(unix)]
+    {
+        let mut buf = [0u8; 256];
+        // SAFETY: gethostname writes at most `buf.len()` bytes into `buf`.
+        let rc = unsafe { libc::gethostname(buf.as_mut_ptr().cast(), buf.len()) };
+        if rc == 0 {
+            let len = buf.iter().position(|&b| b == 0).unwrap_or(buf.len());
+            return String::from_utf8_lossy(&buf[..len]).into_owned();
+        }
+    }
+    "localhost".to_string()
+}
+
+type Clock = Box<dyn Fn() -> SystemTime + Send + Sync>;
+
 struct HelloProvider {
-    greeting: String,
+    /// The template behind `/hello`, stored as the contents of `/greeting`.
+    greeting: RwLock<HelloFile>,
+    writable_greeting: bool,
+    hostname: String,
+    clock: Clock,
     files: RwLock<HashMap<String, HelloFile>>,
     handles: Mutex<HashMap<u64, (String, OpenFlags)>>,
     next_handle: Mutex<u64>,
@@ -48,8 +128,17 @@ struct HelloProvider {
 
 impl HelloProvider {
     fn new(config: HelloConfig) -> Self {
+        Self::with_host_and_clock(config, hostname(), Box::new(SystemTime::now))
+    }
+
+    fn with_host_and_clock(config: HelloConfig, hostname: String, clock: Clock) -> Self {
+        let mut greeting = config.greeting;
+        greeting.push('\n');
         Self {
-            greeting: config.greeting,
+            greeting: RwLock::new(HelloFile::new(greeting)),
+            writable_greeting: config.writable_greeting,
+            hostname,
+            clock,
             files: RwLock::new(HashMap::new()),
             handles: Mutex::new(HashMap::new()),
             next_handle: Mutex::new(1),
@@ -65,6 +154,43 @@ impl HelloProvider {
         }
     }
 
+    /// Whether `path` is the `/greeting` control file rather than an
+    /// ordinary file that happens to have that name.
+    fn is_greeting(&self, path: &str) -> bool {
+        self.writable_greeting && path == GREETING
+    }
+
+    /// Render `/hello` from the current template. This happens on every
+    /// stat and read, so the file never goes stale.
+    fn hello(&self) -> Bytes {
+        let template = self.greeting.read().unwrap();
+        let template = String::from_utf8_lossy(&template.data);
+        let time = chrono::DateTime::<chrono::Utc>::from((self.clock)())
+            .to_rfc3339_opts(chrono::SecondsFormat::Secs, true);
+        let text = template
+            .trim_end()
+            .replace("{{hostname}}", &self.hostname)
+            .replace("{{time}}", &time);
+        Bytes::from(format!("{text}\n"))
+    }
+
+    fn hello_info(&self) -> FileInfo {
+        let now = (self.clock)();
+        FileInfo {
+            path: HELLO.to_string(),
+            size: self.hello().len() as u64,
+            file_type: FileType::Regular,
+            mode: 0o444,
+            uid: 0,
+            gid: 0,
+            atime: now,
+            mtime: now,
+            ctime: now,
+            etag: "virtual".to_string(),
+            symlink_target: None,
+        }
+    }
+
     fn stat(&self, path: &str) -> FsResult<FileInfo> {
         let path = self.normalize_path(path);
 
@@ -84,39 +210,18 @@ impl HelloProvider {
             });
         }
 
-        if path == "/hello" {
-            let msg = format!("{}\n", self.greeting);
-            return Ok(FileInfo {
-                path: "/hello".to_string(),
-                size: msg.len() as u64,
-                file_type: FileType::Regular,
-                mode: 0o444,
-                uid: 0,
-                gid: 0,
-                atime: SystemTime::now(),
-                mtime: SystemTime::now(),
-                ctime: SystemTime::now(),
-                etag: "virtual".to_string(),
-                symlink_target: None,
-            });
+        if path == HELLO {
+            return Ok(self.hello_info());
+        }
+
+        if self.is_greeting(&path) {
+            return Ok(self.greeting.read().unwrap().info(&path));
         }
 
         let files = self.files.read().unwrap();
         files
             .get(&path)
-            .map(|f| FileInfo {
-                path: path.clone(),
-                size: f.data.len() as u64,
-                file_type: FileType::Regular,
-                mode: 0o644,
-                uid: 0,
-                gid: 0,
-                atime: f.mtime,
-                mtime: f.mtime,
-                ctime: f.mtime,
-                etag: String::new(),
-                symlink_target: None,
-            })
+            .map(|f| f.info(&path))
             .ok_or_else(|| FsError::not_found(&path))
     }
 
@@ -127,20 +232,18 @@ impl HelloProvider {
             if !flags.directory {
                 return Err(FsError::is_directory(&path));
             }
-        } else if path == "/hello" {
+        } else if path == HELLO {
             if flags.write {
                 return Err(FsError::permission_denied("cannot write to /hello"));
             }
+        } else if self.is_greeting(&path) {
+            if flags.truncate {
+                *self.greeting.write().unwrap() = HelloFile::new(Bytes::new());
+            }
         } else if flags.create {
             let mut files = self.files.write().unwrap();
             if !files.contains_key(&path) {
-                files.insert(
-                    path.clone(),
-                    HelloFile {
-                        data: Bytes::new(),
-                        mtime: SystemTime::now(),
-                    },
-                );
+                files.insert(path.clone(), HelloFile::new(Bytes::new()));
             }
         } else {
             let files = self.files.read().unwrap();
@@ -172,20 +275,20 @@ impl HelloProvider {
             return Err(FsError::is_directory(path));
         }
 
-        if path == "/hello" {
-            let msg = format!("{}\n", self.greeting);
-            let data = Bytes::from(msg);
+        if path == HELLO {
+            let data = self.hello();
             let start = (offset as usize).min(data.len());
             let end = (start + size).min(data.len());
             return Ok(data.slice(start..end));
         }
 
+        if self.is_greeting(path) {
+            return Ok(self.greeting.read().unwrap().read_at(offset, size));
+        }
+
         let files = self.files.read().unwrap();
         let file = files.get(path).ok_or_else(|| FsError::not_found(path))?;
-
-        let start = (offset as usize).min(file.data.len());
-        let end = (start + size).min(file.data.len());
-        Ok(file.data.slice(start..end))
+        Ok(file.read_at(offset, size))
     }
 
     fn write(&self, handle: u64, offset: u64, data: &[u8]) -> FsResult<usize> {
@@ -200,28 +303,23 @@ impl HelloProvider {
             return Err(FsError::is_directory(&path));
         }
 
-        if path == "/hello" {
+        if path == HELLO {
             return Err(FsError::permission_denied("cannot write to /hello"));
         }
 
+        if self.is_greeting(&path) {
+            self.greeting
+                .write()
+                .unwrap()
+                .write_at(offset, data, flags.append);
+            return Ok(data.len());
+        }
+
         let mut files = self.files.write().unwrap();
         let file = files
             .get_mut(&path)
             .ok_or_else(|| FsError::not_found(&path))?;
-
-        let offset = if flags.append {
-            file.data.len()
-        } else {
-            offset as usize
-        };
-
-        let mut buf = file.data.to_vec();
-        if offset + data.len() > buf.len() {
-            buf.resize(offset + data.len(), 0);
-        }
-        buf[offset..offset + data.len()].copy_from_slice(data);
-        file.data = Bytes::from(buf);
-        file.mtime = SystemTime::now();
+        file.write_at(offset, data, flags.append);
 
         Ok(data.len())
     }
@@ -242,35 +340,14 @@ impl HelloProvider {
             return Err(FsError::not_directory(&path));
         }
 
-        let files = self.files.read().unwrap();
-        let mut entries = vec![FileInfo {
-            path: "/hello".to_string(),
-            size: self.greeting.len() as u64 + 1,
-            file_type: FileType::Regular,
-            mode: 0o444,
-            uid: 0,
-            gid: 0,
-            atime: SystemTime::now(),
-            mtime: SystemTime::now(),
-            ctime: SystemTime::now(),
-            etag: "virtual".to_string(),
-            symlink_target: None,
-        }];
+        let mut entries = vec![self.hello_info()];
+        if self.writable_greeting {
+            entries.push(self.greeting.read().unwrap().info(GREETING));
+        }
 
+        let files = self.files.read().unwrap();
         for (path, file) in files.iter() {
-            entries.push(FileInfo {
-                path: path.clone(),
-                size: file.data.len() as u64,
-                file_type: FileType::Regular,
-                mode: 0o644,
-                uid: 0,
-                gid: 0,
-                atime: file.mtime,
-                mtime: file.mtime,
-                ctime: file.mtime,
-                etag: String::new(),
-                symlink_target: None,
-            });
+            entries.push(file.info(path));
         }
 
         Ok(entries)
@@ -283,8 +360,8 @@ impl HelloProvider {
             return Err(FsError::permission_denied("cannot remove root"));
         }
 
-        if path == "/hello" {
-            return Err(FsError::permission_denied("cannot remove /hello"));
+        if path == HELLO || self.is_greeting(&path) {
+            return Err(FsError::permission_denied(format!("cannot remove {path}")));
         }
 
         self.files
@@ -310,6 +387,10 @@ fn make_cresult_err(code: i32) -> CResult {
     }
 }
 
+fn fserror_to_cresult(err: &FsError) -> CResult {
+    CResult::from_error(fserror_to_code(err), err)
+}
+
 fn fserror_to_code(err: &FsError) -> i32 {
     match err {
         FsError::NotFound(_) => FS9_ERR_NOT_FOUND,
@@ -317,6 +398,7 @@ fn fserror_to_code(err: &FsError) -> i32 {
         FsError::NotDirectory(_) => FS9_ERR_NOT_DIRECTORY,
         FsError::InvalidHandle(_) => FS9_ERR_INVALID_HANDLE,
         FsError::PermissionDenied(_) => fs9_sdk_ffi::FS9_ERR_PERMISSION_DENIED,
+        FsError::NoSpace(_) => fs9_sdk_ffi::FS9_ERR_NO_SPACE,
         _ => fs9_sdk_ffi::FS9_ERR_INTERNAL,
     }
 }
@@ -369,13 +451,14 @@ unsafe extern "C" fn stat_fn(
             (*out_info).mtime = systemtime_to_timestamp(info.mtime);
             (*out_info).atime = systemtime_to_timestamp(info.atime);
             (*out_info).ctime = systemtime_to_timestamp(info.ctime);
+            (*out_info).set_etag(&info.etag);
             CResult {
                 code: FS9_OK,
                 error_msg: ptr::null(),
                 error_msg_len: 0,
             }
         }
-        Err(e) => make_cresult_err(fserror_to_code(&e)),
+        Err(e) => fserror_to_cresult(&e),
     }
 }
 
@@ -437,6 +520,8 @@ unsafe extern "C" fn open_fn(
         truncate: flags.truncate != 0,
         append: flags.append != 0,
         directory: flags.directory != 0,
+        symlink: flags.symlink != 0,
+        exclusive: flags.excl != 0,
     };
 
     match provider.open(path, open_flags) {
@@ -452,13 +537,14 @@ unsafe extern "C" fn open_fn(
             (*out_info).mtime = systemtime_to_timestamp(info.mtime);
             (*out_info).atime = systemtime_to_timestamp(info.atime);
             (*out_info).ctime = systemtime_to_timestamp(info.ctime);
+            (*out_info).set_etag(&info.etag);
             CResult {
                 code: FS9_OK,
                 error_msg: ptr::null(),
                 error_msg_len: 0,
             }
         }
-        Err(e) => make_cresult_err(fserror_to_code(&e)),
+        Err(e) => fserror_to_cresult(&e),
     }
 }
 
@@ -477,14 +563,14 @@ unsafe extern "C" fn read_fn(
 
     match provider.read(handle, offset, size) {
         Ok(data) => {
-            *out_data = fs9_sdk_ffi::vec_to_cbytes(data.to_vec());
+            *out_data = fs9_sdk_ffi::bytes_to_cbytes(data);
             CResult {
                 code: FS9_OK,
                 error_msg: ptr::null(),
                 error_msg_len: 0,
             }
         }
-        Err(e) => make_cresult_err(fserror_to_code(&e)),
+        Err(e) => fserror_to_cresult(&e),
     }
 }
 
@@ -516,7 +602,7 @@ unsafe extern "C" fn write_fn(
                 error_msg_len: 0,
             }
         }
-        Err(e) => make_cresult_err(fserror_to_code(&e)),
+        Err(e) => fserror_to_cresult(&e),
     }
 }
 
@@ -533,7 +619,7 @@ unsafe extern "C" fn close_fn(provider: *mut c_void, handle: u64, _sync: u8) ->
             error_msg: ptr::null(),
             error_msg_len: 0,
         },
-        Err(e) => make_cresult_err(fserror_to_code(&e)),
+        Err(e) => fserror_to_cresult(&e),
     }
 }
 
@@ -556,7 +642,7 @@ unsafe extern "C" fn readdir_fn(
         Ok(entries) => {
             for entry in entries {
                 let path_bytes = entry.path.as_bytes();
-                let info = CFileInfo {
+                let mut info = CFileInfo {
                     path: path_bytes.as_ptr() as *const c_char,
                     path_len: path_bytes.len(),
                     size: entry.size,
@@ -571,7 +657,9 @@ unsafe extern "C" fn readdir_fn(
                     atime: systemtime_to_timestamp(entry.atime),
                     mtime: systemtime_to_timestamp(entry.mtime),
                     ctime: systemtime_to_timestamp(entry.ctime),
+                    ..CFileInfo::default()
                 };
+                info.set_etag(&entry.etag);
                 if callback(&info, user_data) != 0 {
                     break;
                 }
@@ -582,7 +670,7 @@ unsafe extern "C" fn readdir_fn(
                 error_msg_len: 0,
             }
         }
-        Err(e) => make_cresult_err(fserror_to_code(&e)),
+        Err(e) => fserror_to_cresult(&e),
     }
 }
 
@@ -605,10 +693,124 @@ unsafe extern "C" fn remove_fn(
             error_msg: ptr::null(),
             error_msg_len: 0,
         },
-        Err(e) => make_cresult_err(fserror_to_code(&e)),
+        Err(e) => fserror_to_cresult(&e),
     }
 }
 
+unsafe extern "C" fn fsync_fn(_provider: *mut c_void, _handle: u64, _data_only: u8) -> CResult {
+    // Files written here only ever live in memory.
+    CResult {
+        code: FS9_OK,
+        error_msg: ptr::null(),
+        error_msg_len: 0,
+    }
+}
+
+unsafe extern "C" fn flush_fn(_provider: *mut c_void) -> CResult {
+    CResult {
+        code: FS9_OK,
+        error_msg: ptr::null(),
+        error_msg_len: 0,
+    }
+}
+
+unsafe extern "C" fn health_check_fn(_provider: *mut c_void) -> CResult {
+    CResult::ok()
+}
+
+unsafe extern "C" fn snapshot_fn(
+    _provider: *mut c_void,
+    _path: *const c_char,
+    _path_len: size_t,
+    _out_id: *mut u64,
+) -> CResult {
+    make_cresult_err(fs9_sdk_ffi::FS9_ERR_NOT_IMPLEMENTED)
+}
+
+unsafe extern "C" fn open_snapshot_fn(
+    _provider: *mut c_void,
+    _snapshot_id: u64,
+    _out_view: *mut *mut c_void,
+) -> CResult {
+    make_cresult_err(fs9_sdk_ffi::FS9_ERR_NOT_IMPLEMENTED)
+}
+
+unsafe extern "C" fn getxattr_fn(
+    _provider: *mut c_void,
+    _path: *const c_char,
+    _path_len: size_t,
+    _name: *const c_char,
+    _name_len: size_t,
+    _out_value: *mut CBytes,
+) -> CResult {
+    make_cresult_err(fs9_sdk_ffi::FS9_ERR_NOT_IMPLEMENTED)
+}
+
+unsafe extern "C" fn setxattr_fn(
+    _provider: *mut c_void,
+    _path: *const c_char,
+    _path_len: size_t,
+    _name: *const c_char,
+    _name_len: size_t,
+    _value: *const u8,
+    _value_len: size_t,
+) -> CResult {
+    make_cresult_err(fs9_sdk_ffi::FS9_ERR_NOT_IMPLEMENTED)
+}
+
+unsafe extern "C" fn listxattr_fn(
+    _provider: *mut c_void,
+    _path: *const c_char,
+    _path_len: size_t,
+    _out_names: *mut CBytes,
+) -> CResult {
+    make_cresult_err(fs9_sdk_ffi::FS9_ERR_NOT_IMPLEMENTED)
+}
+
+unsafe extern "C" fn removexattr_fn(
+    _provider: *mut c_void,
+    _path: *const c_char,
+    _path_len: size_t,
+    _name: *const c_char,
+    _name_len: size_t,
+) -> CResult {
+    make_cresult_err(fs9_sdk_ffi::FS9_ERR_NOT_IMPLEMENTED)
+}
+
+unsafe extern "C" fn link_fn(
+    _provider: *mut c_void,
+    _existing_path: *const c_char,
+    _existing_path_len: size_t,
+    _new_path: *const c_char,
+    _new_path_len: size_t,
+) -> CResult {
+    make_cresult_err(fs9_sdk_ffi::FS9_ERR_NOT_IMPLEMENTED)
+}
+
+unsafe extern "C" fn rename_fn(
+    _provider: *mut c_void,
+    _old_path: *const c_char,
+    _old_path_len: size_t,
+    _new_path: *const c_char,
+    _new_path_len: size_t,
+) -> CResult {
+    make_cresult_err(fs9_sdk_ffi::FS9_ERR_NOT_IMPLEMENTED)
+}
+
+unsafe extern "C" fn readdir_at_fn(
+    _provider: *mut c_void,
+    _path: *const c_char,
+    _path_len: size_t,
+    _cursor: *const c_char,
+    _cursor_len: size_t,
+    _max: size_t,
+    _callback: fs9_sdk_ffi::ReaddirCallback,
+    _user_data: *mut c_void,
+    _out_next_cursor: *mut CBytes,
+) -> CResult {
+    make_cresult_err(fs9_sdk_ffi::FS9_ERR_NOT_IMPLEMENTED)
+}
+
 static PLUGIN_NAME: &[u8] = b"hellofs";
 static PLUGIN_VERSION: &[u8] = b"0.1.0";
 
@@ -630,6 +832,18 @@ static VTABLE: PluginVTable = PluginVTable {
     close: close_fn,
     readdir: readdir_fn,
     remove: remove_fn,
+    fsync: fsync_fn,
+    link: link_fn,
+    rename: rename_fn,
+    readdir_at: readdir_at_fn,
+    flush: flush_fn,
+    getxattr: getxattr_fn,
+    setxattr: setxattr_fn,
+    listxattr: listxattr_fn,
+    removexattr: removexattr_fn,
+    health_check: health_check_fn,
+    snapshot: snapshot_fn,
+    open_snapshot: open_snapshot_fn,
 };
 
 #[no_mangle]
@@ -645,6 +859,9 @@ pub extern "C" fn fs9_plugin_vtable() -> *const PluginVTable {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::Arc;
+    use std::time::{Duration, UNIX_EPOCH};
 
     #[test]
     fn version_matches_sdk() {
@@ -674,7 +891,7 @@ mod tests {
         let provider = HelloProvider::new(HelloConfig::default());
 
         let flags = OpenFlags::read();
-        let handle = provider.open("/hello", flags).unwrap();
+        let (handle, _) = provider.open("/hello", flags).unwrap();
         let data = provider.read(handle.id(), 0, 100).unwrap();
         assert_eq!(&data[..], b"Hello, World!\n");
         provider.close(handle.id()).unwrap();
@@ -684,11 +901,12 @@ mod tests {
     fn custom_greeting() {
         let config = HelloConfig {
             greeting: "Hi there!".to_string(),
+            ..Default::default()
         };
         let provider = HelloProvider::new(config);
 
         let flags = OpenFlags::read();
-        let handle = provider.open("/hello", flags).unwrap();
+        let (handle, _) = provider.open("/hello", flags).unwrap();
         let data = provider.read(handle.id(), 0, 100).unwrap();
         assert_eq!(&data[..], b"Hi there!\n");
         provider.close(handle.id()).unwrap();
@@ -699,12 +917,12 @@ mod tests {
         let provider = HelloProvider::new(HelloConfig::default());
 
         let flags = OpenFlags::create_file();
-        let handle = provider.open("/test.txt", flags).unwrap();
+        let (handle, _) = provider.open("/test.txt", flags).unwrap();
         provider.write(handle.id(), 0, b"test data").unwrap();
         provider.close(handle.id()).unwrap();
 
         let flags = OpenFlags::read();
-        let handle = provider.open("/test.txt", flags).unwrap();
+        let (handle, _) = provider.open("/test.txt", flags).unwrap();
         let data = provider.read(handle.id(), 0, 100).unwrap();
         assert_eq!(&data[..], b"test data");
         provider.close(handle.id()).unwrap();
@@ -729,6 +947,8 @@ mod tests {
             truncate: false,
             append: false,
             directory: false,
+            symlink: false,
+            exclusive: false,
         };
         let result = provider.open("/hello", flags);
         assert!(result.is_err());
@@ -740,4 +960,74 @@ mod tests {
         let result = provider.remove("/hello");
         assert!(result.is_err());
     }
+
+    /// A provider on host `box` whose clock reads `now`, in seconds.
+    fn templated(greeting: &str, writable_greeting: bool) -> (HelloProvider, Arc<AtomicU64>) {
+        let now = Arc::new(AtomicU64::new(1_700_000_000));
+        let clock = now.clone();
+        let config = HelloConfig {
+            greeting: greeting.to_string(),
+            writable_greeting,
+        };
+        let provider = HelloProvider::with_host_and_clock(
+            config,
+            "box".to_string(),
+            Box::new(move || UNIX_EPOCH + Duration::from_secs(clock.load(Ordering::SeqCst))),
+        );
+        (provider, now)
+    }
+
+    fn read_all(provider: &HelloProvider, path: &str) -> Bytes {
+        let (handle, info) = provider.open(path, OpenFlags::read()).unwrap();
+        let data = provider.read(handle.id(), 0, 4096).unwrap();
+        provider.close(handle.id()).unwrap();
+        assert_eq!(info.size, data.len() as u64);
+        data
+    }
+
+    #[test]
+    fn template_is_rendered_on_every_read() {
+        let (provider, now) = templated("Hello from {{hostname}} at {{time}}", false);
+
+        assert_eq!(
+            &read_all(&provider, "/hello")[..],
+            b"Hello from box at 2023-11-14T22:13:20Z\n"
+        );
+        now.fetch_add(60, Ordering::SeqCst);
+        assert_eq!(
+            &read_all(&provider, "/hello")[..],
+            b"Hello from box at 2023-11-14T22:14:20Z\n"
+        );
+    }
+
+    #[test]
+    fn writing_greeting_changes_hello() {
+        let (provider, _) = templated("Hello, World!", true);
+        assert_eq!(&read_all(&provider, "/greeting")[..], b"Hello, World!\n");
+
+        let (handle, _) = provider
+            .open("/greeting", OpenFlags::create_truncate())
+            .unwrap();
+        provider
+            .write(handle.id(), 0, b"Howdy from {{hostname}}\n")
+            .unwrap();
+        provider.close(handle.id()).unwrap();
+
+        assert_eq!(&read_all(&provider, "/hello")[..], b"Howdy from box\n");
+        assert!(provider.open("/hello", OpenFlags::write()).is_err());
+        assert!(provider.remove("/greeting").is_err());
+        let entries = provider.readdir("/").unwrap();
+        assert!(entries.iter().any(|e| e.path == "/greeting"));
+    }
+
+    #[test]
+    fn greeting_file_is_opt_in() {
+        let provider = HelloProvider::new(HelloConfig::default());
+        assert!(matches!(
+            provider.stat("/greeting"),
+            Err(FsError::NotFound(_))
+        ));
+        let entries = provider.readdir("/").unwrap();
+        assert!(!entries.iter().any(|e| e.path == "/greeting"));
+    }
 }
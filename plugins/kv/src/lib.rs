@@ -1,12 +1,15 @@
 #![allow(clippy::missing_safety_doc)]
 
 use std::collections::BTreeMap;
+use std::ops::Bound;
 use std::ptr;
 use std::sync::{Mutex, RwLock};
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use bytes::Bytes;
-use fs9_sdk::{Capabilities, FileInfo, FileType, FsError, FsResult, Handle, OpenFlags};
+use fs9_sdk::{
+    content_etag, Capabilities, DirPage, FileInfo, FileType, FsError, FsResult, Handle, OpenFlags,
+};
 use fs9_sdk_ffi::{
     CBytes, CFileInfo, CFsStats, COpenFlags, CResult, CStatChanges, PluginVTable,
     FILE_TYPE_DIRECTORY, FILE_TYPE_REGULAR, FS9_ERR_ALREADY_EXISTS, FS9_ERR_INVALID_HANDLE,
@@ -15,6 +18,26 @@ use fs9_sdk_ffi::{
 use libc::{c_char, c_void, size_t};
 use serde::Deserialize;
 
+/// Reserved directory of control paths for compare-and-swap. Writing
+/// `{"expected": ..., "new": ...}` to `/.cas/lock` replaces the contents of
+/// `/lock` with `new` only if they currently equal `expected`; a null
+/// `expected` means the file must not exist yet. Both are JSON strings, so
+/// only UTF-8 values can be swapped this way. Each write is one operation,
+/// so the request must arrive in a single write.
+const CAS_DIR: &str = "/.cas";
+
+/// The path a compare-and-swap control path under [`CAS_DIR`] acts on.
+fn cas_target(path: &str) -> Option<&str> {
+    path.strip_prefix(CAS_DIR)
+        .filter(|target| target.len() > 1 && target.starts_with('/'))
+}
+
+#[derive(Debug, Deserialize)]
+struct CasRequest {
+    expected: Option<String>,
+    new: String,
+}
+
 #[derive(Debug, Clone, Deserialize)]
 #[allow(dead_code)]
 struct KvConfig {
@@ -44,6 +67,9 @@ enum KvEntry {
         data: Bytes,
         mode: u32,
         mtime: SystemTime,
+        /// Once this time passes the file is treated as missing and
+        /// removed the next time it is looked up.
+        expires_at: Option<SystemTime>,
     },
 }
 
@@ -64,12 +90,30 @@ impl KvEntry {
         }
     }
 
+    fn is_expired(&self) -> bool {
+        match self {
+            Self::Directory { .. } => false,
+            Self::File { expires_at, .. } => {
+                expires_at.is_some_and(|expires_at| expires_at <= SystemTime::now())
+            }
+        }
+    }
+
     fn size(&self) -> u64 {
         match self {
             Self::Directory { .. } => 0,
             Self::File { data, .. } => data.len() as u64,
         }
     }
+
+    /// A strong etag for files; the values are in memory, so hashing them
+    /// is cheap.
+    fn etag(&self) -> String {
+        match self {
+            Self::Directory { .. } => String::new(),
+            Self::File { data, .. } => content_etag(data),
+        }
+    }
 }
 
 struct KvProvider {
@@ -108,8 +152,25 @@ impl KvProvider {
         }
     }
 
+    /// Remove `path` if it has expired, so the caller sees it as missing.
+    fn evict_if_expired(&self, path: &str) {
+        let expired = self
+            .store
+            .read()
+            .unwrap()
+            .get(path)
+            .is_some_and(KvEntry::is_expired);
+        if expired {
+            let mut store = self.store.write().unwrap();
+            if store.get(path).is_some_and(KvEntry::is_expired) {
+                store.remove(path);
+            }
+        }
+    }
+
     fn stat(&self, path: &str) -> FsResult<FileInfo> {
         let path = self.normalize_path(path);
+        self.evict_if_expired(&path);
         let store = self.store.read().unwrap();
 
         store
@@ -128,7 +189,7 @@ impl KvProvider {
                 atime: entry.mtime(),
                 mtime: entry.mtime(),
                 ctime: entry.mtime(),
-                etag: String::new(),
+                etag: entry.etag(),
                 symlink_target: None,
             })
             .ok_or_else(|| FsError::not_found(&path))
@@ -136,6 +197,15 @@ impl KvProvider {
 
     fn open(&self, path: &str, flags: OpenFlags) -> FsResult<(Handle, FileInfo)> {
         let path = self.normalize_path(path);
+        if path == CAS_DIR {
+            return Err(FsError::permission_denied(format!(
+                "{CAS_DIR} is reserved for compare-and-swap"
+            )));
+        }
+        if cas_target(&path).is_some() {
+            return self.open_cas(path, flags);
+        }
+        self.evict_if_expired(&path);
 
         if flags.create {
             let mut store = self.store.write().unwrap();
@@ -160,6 +230,7 @@ impl KvProvider {
                             data: Bytes::new(),
                             mode: 0o644,
                             mtime: SystemTime::now(),
+                            expires_at: None,
                         },
                     );
                 }
@@ -184,17 +255,93 @@ impl KvProvider {
         Ok((Handle::new(handle_id), info))
     }
 
+    fn open_cas(&self, path: String, flags: OpenFlags) -> FsResult<(Handle, FileInfo)> {
+        if flags.read || !flags.write {
+            return Err(FsError::invalid_argument(
+                "compare-and-swap paths can only be opened write-only",
+            ));
+        }
+
+        let info = FileInfo {
+            path: path.clone(),
+            size: 0,
+            file_type: FileType::Regular,
+            mode: 0o200,
+            uid: 0,
+            gid: 0,
+            atime: SystemTime::now(),
+            mtime: SystemTime::now(),
+            ctime: SystemTime::now(),
+            etag: String::new(),
+            symlink_target: None,
+        };
+
+        let mut next = self.next_handle.lock().unwrap();
+        let handle_id = *next;
+        *next += 1;
+
+        self.handles
+            .lock()
+            .unwrap()
+            .insert(handle_id, (path, flags));
+        Ok((Handle::new(handle_id), info))
+    }
+
+    /// Replace the contents of `path` with `new` if they currently equal
+    /// `expected` (or, when `expected` is `None`, create it if missing).
+    /// The store lock is held across the comparison and the update.
+    fn compare_and_swap(&self, path: &str, expected: Option<&[u8]>, new: &[u8]) -> FsResult<()> {
+        let mut store = self.store.write().unwrap();
+        if store.get(path).is_some_and(KvEntry::is_expired) {
+            store.remove(path);
+        }
+
+        match (store.get_mut(path), expected) {
+            (Some(KvEntry::Directory { .. }), _) => Err(FsError::is_directory(path)),
+            (Some(KvEntry::File { data, mtime, .. }), Some(expected)) if data[..] == *expected => {
+                *data = Bytes::copy_from_slice(new);
+                *mtime = SystemTime::now();
+                Ok(())
+            }
+            (None, None) => {
+                let parent = path.rsplit_once('/').map(|(p, _)| p).unwrap_or("/");
+                let parent_path = if parent.is_empty() { "/" } else { parent };
+                if !store.contains_key(parent_path) {
+                    return Err(FsError::not_found(parent_path));
+                }
+                store.insert(
+                    path.to_string(),
+                    KvEntry::File {
+                        data: Bytes::copy_from_slice(new),
+                        mode: 0o644,
+                        mtime: SystemTime::now(),
+                        expires_at: None,
+                    },
+                );
+                Ok(())
+            }
+            _ => Err(FsError::already_exists(format!(
+                "compare-and-swap failed: {path} does not hold the expected contents"
+            ))),
+        }
+    }
+
     fn read(&self, handle: u64, offset: u64, size: usize) -> FsResult<Bytes> {
-        let handles = self.handles.lock().unwrap();
-        let (path, _) = handles
+        let path = self
+            .handles
+            .lock()
+            .unwrap()
             .get(&handle)
-            .ok_or_else(|| FsError::invalid_handle(handle))?;
+            .ok_or_else(|| FsError::invalid_handle(handle))?
+            .0
+            .clone();
+        self.evict_if_expired(&path);
 
         let store = self.store.read().unwrap();
-        let entry = store.get(path).ok_or_else(|| FsError::not_found(path))?;
+        let entry = store.get(&path).ok_or_else(|| FsError::not_found(&path))?;
 
         match entry {
-            KvEntry::Directory { .. } => Err(FsError::is_directory(path)),
+            KvEntry::Directory { .. } => Err(FsError::is_directory(&path)),
             KvEntry::File { data, .. } => {
                 let start = (offset as usize).min(data.len());
                 let end = (start + size).min(data.len());
@@ -211,6 +358,20 @@ impl KvProvider {
             .clone();
         drop(handles);
 
+        if let Some(target) = cas_target(&path) {
+            let request: CasRequest = serde_json::from_slice(data).map_err(|e| {
+                FsError::invalid_argument(format!("invalid compare-and-swap request: {e}"))
+            })?;
+            self.compare_and_swap(
+                target,
+                request.expected.as_deref().map(str::as_bytes),
+                request.new.as_bytes(),
+            )?;
+            return Ok(data.len());
+        }
+
+        self.evict_if_expired(&path);
+
         let mut store = self.store.write().unwrap();
         let entry = store
             .get_mut(&path)
@@ -252,6 +413,18 @@ impl KvProvider {
     }
 
     fn readdir(&self, path: &str) -> FsResult<Vec<FileInfo>> {
+        Ok(self.readdir_at(path, None, usize::MAX)?.entries)
+    }
+
+    /// List up to `max` children of `path` in key order, resuming after the
+    /// child named by `cursor`. Only the requested page is materialized.
+    fn readdir_at(&self, path: &str, cursor: Option<&str>, max: usize) -> FsResult<DirPage> {
+        if max == 0 {
+            return Err(FsError::invalid_argument(
+                "readdir page size must be positive",
+            ));
+        }
+
         let path = self.normalize_path(path);
         let store = self.store.read().unwrap();
 
@@ -259,28 +432,39 @@ impl KvProvider {
         if !entry.is_directory() {
             return Err(FsError::not_directory(&path));
         }
+        let mut expired = Vec::new();
 
-        let prefix = if path == "/" { "" } else { &path };
-
-        let entries: Vec<FileInfo> = store
-            .range(format!("{}/", prefix)..)
-            .take_while(|(k, _)| {
-                k.starts_with(&format!("{}/", prefix)) || (path == "/" && k.starts_with('/'))
-            })
-            .filter(|(k, _)| {
-                if *k == "/" {
-                    return false;
-                }
-                let relative = if path == "/" {
-                    &k[1..]
-                } else if k.starts_with(&format!("{}/", path)) {
-                    &k[path.len() + 1..]
-                } else {
-                    return false;
-                };
-                !relative.is_empty() && !relative.contains('/')
-            })
-            .map(|(k, v)| FileInfo {
+        let child_prefix = if path == "/" {
+            "/".to_string()
+        } else {
+            format!("{}/", path)
+        };
+        let start = match cursor {
+            Some(name) => Bound::Excluded(format!("{}{}", child_prefix, name)),
+            None => Bound::Included(child_prefix.clone()),
+        };
+
+        let mut entries = Vec::new();
+        let mut next_cursor = None;
+        for (k, v) in store
+            .range::<String, _>((start, Bound::Unbounded))
+            .take_while(|(k, _)| k.starts_with(&child_prefix))
+        {
+            let relative = &k[child_prefix.len()..];
+            if relative.is_empty() || relative.contains('/') {
+                continue;
+            }
+            if v.is_expired() {
+                expired.push(k.clone());
+                continue;
+            }
+            if entries.len() == max {
+                next_cursor = entries
+                    .last()
+                    .map(|last: &FileInfo| last.path[child_prefix.len()..].to_string());
+                break;
+            }
+            entries.push(FileInfo {
                 path: k.clone(),
                 size: v.size(),
                 file_type: if v.is_directory() {
@@ -294,12 +478,20 @@ impl KvProvider {
                 atime: v.mtime(),
                 mtime: v.mtime(),
                 ctime: v.mtime(),
-                etag: String::new(),
+                etag: v.etag(),
                 symlink_target: None,
-            })
-            .collect();
+            });
+        }
+        drop(store);
 
-        Ok(entries)
+        for key in expired {
+            self.evict_if_expired(&key);
+        }
+
+        Ok(DirPage {
+            entries,
+            next_cursor,
+        })
     }
 
     fn remove(&self, path: &str) -> FsResult<()> {
@@ -310,15 +502,21 @@ impl KvProvider {
 
         let mut store = self.store.write().unwrap();
 
-        let has_children = store
+        let children: Vec<_> = store
             .range(format!("{}/", path)..)
             .take_while(|(k, _)| k.starts_with(&format!("{}/", path)))
-            .next()
-            .is_some();
+            .map(|(k, v)| (k.clone(), v.is_expired()))
+            .collect();
 
-        if has_children {
+        if children.iter().any(|(_, expired)| !expired) {
             return Err(FsError::directory_not_empty(&path));
         }
+        for (child, _) in children {
+            store.remove(&child);
+        }
+        if store.get(&path).is_some_and(KvEntry::is_expired) {
+            store.remove(&path);
+        }
 
         store
             .remove(&path)
@@ -326,8 +524,15 @@ impl KvProvider {
             .ok_or_else(|| FsError::not_found(&path))
     }
 
-    fn wstat(&self, path: &str, mode: Option<u32>, size: Option<u64>) -> FsResult<()> {
+    fn wstat(
+        &self,
+        path: &str,
+        mode: Option<u32>,
+        size: Option<u64>,
+        ttl: Option<Duration>,
+    ) -> FsResult<()> {
         let path = self.normalize_path(path);
+        self.evict_if_expired(&path);
         let mut store = self.store.write().unwrap();
 
         let entry = store
@@ -339,6 +544,11 @@ impl KvProvider {
                 mode: entry_mode,
                 mtime,
             } => {
+                if ttl.is_some() {
+                    return Err(FsError::invalid_argument(
+                        "expiry is only supported on files",
+                    ));
+                }
                 if let Some(m) = mode {
                     *entry_mode = m;
                 }
@@ -348,7 +558,18 @@ impl KvProvider {
                 data,
                 mode: entry_mode,
                 mtime,
+                expires_at,
             } => {
+                if let Some(ttl) = ttl {
+                    *expires_at = if ttl.is_zero() {
+                        None
+                    } else {
+                        let expiry = SystemTime::now().checked_add(ttl).ok_or_else(|| {
+                            FsError::invalid_argument("expiry is too far in the future")
+                        })?;
+                        Some(expiry)
+                    };
+                }
                 if let Some(m) = mode {
                     *entry_mode = m;
                 }
@@ -379,6 +600,10 @@ fn make_cresult_err(code: i32) -> CResult {
     }
 }
 
+fn fserror_to_cresult(err: &FsError) -> CResult {
+    CResult::from_error(fserror_to_code(err), err)
+}
+
 fn fserror_to_code(err: &FsError) -> i32 {
     match err {
         FsError::NotFound(_) => FS9_ERR_NOT_FOUND,
@@ -386,6 +611,8 @@ fn fserror_to_code(err: &FsError) -> i32 {
         FsError::IsDirectory(_) => FS9_ERR_IS_DIRECTORY,
         FsError::NotDirectory(_) => FS9_ERR_NOT_DIRECTORY,
         FsError::InvalidHandle(_) => FS9_ERR_INVALID_HANDLE,
+        FsError::InvalidArgument(_) => fs9_sdk_ffi::FS9_ERR_INVALID_ARGUMENT,
+        FsError::NoSpace(_) => fs9_sdk_ffi::FS9_ERR_NO_SPACE,
         _ => fs9_sdk_ffi::FS9_ERR_INTERNAL,
     }
 }
@@ -441,13 +668,14 @@ unsafe extern "C" fn stat_fn(
             (*out_info).mtime = systemtime_to_timestamp(info.mtime);
             (*out_info).atime = systemtime_to_timestamp(info.atime);
             (*out_info).ctime = systemtime_to_timestamp(info.ctime);
+            (*out_info).set_etag(&info.etag);
             CResult {
                 code: FS9_OK,
                 error_msg: ptr::null(),
                 error_msg_len: 0,
             }
         }
-        Err(e) => make_cresult_err(fserror_to_code(&e)),
+        Err(e) => fserror_to_cresult(&e),
     }
 }
 
@@ -476,14 +704,19 @@ unsafe extern "C" fn wstat_fn(
     } else {
         None
     };
+    let ttl = if changes.has_ttl != 0 {
+        Some(Duration::from_secs(changes.ttl_secs))
+    } else {
+        None
+    };
 
-    match provider.wstat(path, mode, size) {
+    match provider.wstat(path, mode, size, ttl) {
         Ok(()) => CResult {
             code: FS9_OK,
             error_msg: ptr::null(),
             error_msg_len: 0,
         },
-        Err(e) => make_cresult_err(fserror_to_code(&e)),
+        Err(e) => fserror_to_cresult(&e),
     }
 }
 
@@ -536,6 +769,8 @@ unsafe extern "C" fn open_fn(
         truncate: flags.truncate != 0,
         append: flags.append != 0,
         directory: flags.directory != 0,
+        symlink: flags.symlink != 0,
+        exclusive: flags.excl != 0,
     };
 
     match provider.open(path, open_flags) {
@@ -553,13 +788,14 @@ unsafe extern "C" fn open_fn(
             (*out_info).mtime = systemtime_to_timestamp(info.mtime);
             (*out_info).atime = systemtime_to_timestamp(info.atime);
             (*out_info).ctime = systemtime_to_timestamp(info.ctime);
+            (*out_info).set_etag(&info.etag);
             CResult {
                 code: FS9_OK,
                 error_msg: ptr::null(),
                 error_msg_len: 0,
             }
         }
-        Err(e) => make_cresult_err(fserror_to_code(&e)),
+        Err(e) => fserror_to_cresult(&e),
     }
 }
 
@@ -578,14 +814,14 @@ unsafe extern "C" fn read_fn(
 
     match provider.read(handle, offset, size) {
         Ok(data) => {
-            *out_data = fs9_sdk_ffi::vec_to_cbytes(data.to_vec());
+            *out_data = fs9_sdk_ffi::bytes_to_cbytes(data);
             CResult {
                 code: FS9_OK,
                 error_msg: ptr::null(),
                 error_msg_len: 0,
             }
         }
-        Err(e) => make_cresult_err(fserror_to_code(&e)),
+        Err(e) => fserror_to_cresult(&e),
     }
 }
 
@@ -617,7 +853,7 @@ unsafe extern "C" fn write_fn(
                 error_msg_len: 0,
             }
         }
-        Err(e) => make_cresult_err(fserror_to_code(&e)),
+        Err(e) => fserror_to_cresult(&e),
     }
 }
 
@@ -634,7 +870,7 @@ unsafe extern "C" fn close_fn(provider: *mut c_void, handle: u64, _sync: u8) ->
             error_msg: ptr::null(),
             error_msg_len: 0,
         },
-        Err(e) => make_cresult_err(fserror_to_code(&e)),
+        Err(e) => fserror_to_cresult(&e),
     }
 }
 
@@ -655,35 +891,90 @@ unsafe extern "C" fn readdir_fn(
 
     match provider.readdir(path) {
         Ok(entries) => {
-            for entry in entries {
-                let path_bytes = entry.path.as_bytes();
-                let info = CFileInfo {
-                    path: path_bytes.as_ptr() as *const c_char,
-                    path_len: path_bytes.len(),
-                    size: entry.size,
-                    file_type: if entry.file_type == FileType::Directory {
-                        FILE_TYPE_DIRECTORY
-                    } else {
-                        FILE_TYPE_REGULAR
-                    },
-                    mode: entry.mode,
-                    uid: 0,
-                    gid: 0,
-                    atime: systemtime_to_timestamp(entry.atime),
-                    mtime: systemtime_to_timestamp(entry.mtime),
-                    ctime: systemtime_to_timestamp(entry.ctime),
-                };
-                if callback(&info, user_data) != 0 {
-                    break;
-                }
+            emit_entries(&entries, callback, user_data);
+            CResult {
+                code: FS9_OK,
+                error_msg: ptr::null(),
+                error_msg_len: 0,
             }
+        }
+        Err(e) => fserror_to_cresult(&e),
+    }
+}
+
+unsafe extern "C" fn readdir_at_fn(
+    provider: *mut c_void,
+    path: *const c_char,
+    path_len: size_t,
+    cursor: *const c_char,
+    cursor_len: size_t,
+    max: size_t,
+    callback: fs9_sdk_ffi::ReaddirCallback,
+    user_data: *mut c_void,
+    out_next_cursor: *mut CBytes,
+) -> CResult {
+    if provider.is_null() || out_next_cursor.is_null() {
+        return make_cresult_err(fs9_sdk_ffi::FS9_ERR_INVALID_ARGUMENT);
+    }
+
+    let provider = &*(provider as *const KvProvider);
+    let path =
+        std::str::from_utf8_unchecked(std::slice::from_raw_parts(path as *const u8, path_len));
+    let cursor = if cursor.is_null() || cursor_len == 0 {
+        None
+    } else {
+        Some(std::str::from_utf8_unchecked(std::slice::from_raw_parts(
+            cursor as *const u8,
+            cursor_len,
+        )))
+    };
+
+    match provider.readdir_at(path, cursor, max) {
+        Ok(page) => {
+            emit_entries(&page.entries, callback, user_data);
+            *out_next_cursor = match page.next_cursor {
+                Some(next) => fs9_sdk_ffi::vec_to_cbytes(next.into_bytes()),
+                None => CBytes::default(),
+            };
             CResult {
                 code: FS9_OK,
                 error_msg: ptr::null(),
                 error_msg_len: 0,
             }
         }
-        Err(e) => make_cresult_err(fserror_to_code(&e)),
+        Err(e) => fserror_to_cresult(&e),
+    }
+}
+
+/// Hand each entry to a `ReaddirCallback`, stopping early if it asks to.
+unsafe fn emit_entries(
+    entries: &[FileInfo],
+    callback: fs9_sdk_ffi::ReaddirCallback,
+    user_data: *mut c_void,
+) {
+    for entry in entries {
+        let path_bytes = entry.path.as_bytes();
+        let mut info = CFileInfo {
+            path: path_bytes.as_ptr() as *const c_char,
+            path_len: path_bytes.len(),
+            size: entry.size,
+            file_type: if entry.file_type == FileType::Directory {
+                FILE_TYPE_DIRECTORY
+            } else {
+                FILE_TYPE_REGULAR
+            },
+            mode: entry.mode,
+            uid: 0,
+            gid: 0,
+            atime: systemtime_to_timestamp(entry.atime),
+            mtime: systemtime_to_timestamp(entry.mtime),
+            ctime: systemtime_to_timestamp(entry.ctime),
+            ..CFileInfo::default()
+        };
+        info.set_etag(&entry.etag);
+        if callback(&info, user_data) != 0 {
+            break;
+        }
     }
 }
 
@@ -706,10 +997,110 @@ unsafe extern "C" fn remove_fn(
             error_msg: ptr::null(),
             error_msg_len: 0,
         },
-        Err(e) => make_cresult_err(fserror_to_code(&e)),
+        Err(e) => fserror_to_cresult(&e),
+    }
+}
+
+unsafe extern "C" fn fsync_fn(_provider: *mut c_void, _handle: u64, _data_only: u8) -> CResult {
+    // The store is an in-memory map with no durable copy to bring up to date.
+    CResult {
+        code: FS9_OK,
+        error_msg: ptr::null(),
+        error_msg_len: 0,
     }
 }
 
+unsafe extern "C" fn flush_fn(_provider: *mut c_void) -> CResult {
+    CResult {
+        code: FS9_OK,
+        error_msg: ptr::null(),
+        error_msg_len: 0,
+    }
+}
+
+unsafe extern "C" fn health_check_fn(_provider: *mut c_void) -> CResult {
+    CResult::ok()
+}
+
+unsafe extern "C" fn snapshot_fn(
+    _provider: *mut c_void,
+    _path: *const c_char,
+    _path_len: size_t,
+    _out_id: *mut u64,
+) -> CResult {
+    make_cresult_err(fs9_sdk_ffi::FS9_ERR_NOT_IMPLEMENTED)
+}
+
+unsafe extern "C" fn open_snapshot_fn(
+    _provider: *mut c_void,
+    _snapshot_id: u64,
+    _out_view: *mut *mut c_void,
+) -> CResult {
+    make_cresult_err(fs9_sdk_ffi::FS9_ERR_NOT_IMPLEMENTED)
+}
+
+unsafe extern "C" fn getxattr_fn(
+    _provider: *mut c_void,
+    _path: *const c_char,
+    _path_len: size_t,
+    _name: *const c_char,
+    _name_len: size_t,
+    _out_value: *mut CBytes,
+) -> CResult {
+    make_cresult_err(fs9_sdk_ffi::FS9_ERR_NOT_IMPLEMENTED)
+}
+
+unsafe extern "C" fn setxattr_fn(
+    _provider: *mut c_void,
+    _path: *const c_char,
+    _path_len: size_t,
+    _name: *const c_char,
+    _name_len: size_t,
+    _value: *const u8,
+    _value_len: size_t,
+) -> CResult {
+    make_cresult_err(fs9_sdk_ffi::FS9_ERR_NOT_IMPLEMENTED)
+}
+
+unsafe extern "C" fn listxattr_fn(
+    _provider: *mut c_void,
+    _path: *const c_char,
+    _path_len: size_t,
+    _out_names: *mut CBytes,
+) -> CResult {
+    make_cresult_err(fs9_sdk_ffi::FS9_ERR_NOT_IMPLEMENTED)
+}
+
+unsafe extern "C" fn removexattr_fn(
+    _provider: *mut c_void,
+    _path: *const c_char,
+    _path_len: size_t,
+    _name: *const c_char,
+    _name_len: size_t,
+) -> CResult {
+    make_cresult_err(fs9_sdk_ffi::FS9_ERR_NOT_IMPLEMENTED)
+}
+
+unsafe extern "C" fn link_fn(
+    _provider: *mut c_void,
+    _existing_path: *const c_char,
+    _existing_path_len: size_t,
+    _new_path: *const c_char,
+    _new_path_len: size_t,
+) -> CResult {
+    make_cresult_err(fs9_sdk_ffi::FS9_ERR_NOT_IMPLEMENTED)
+}
+
+unsafe extern "C" fn rename_fn(
+    _provider: *mut c_void,
+    _old_path: *const c_char,
+    _old_path_len: size_t,
+    _new_path: *const c_char,
+    _new_path_len: size_t,
+) -> CResult {
+    make_cresult_err(fs9_sdk_ffi::FS9_ERR_NOT_IMPLEMENTED)
+}
+
 static PLUGIN_NAME: &[u8] = b"kv";
 static PLUGIN_VERSION: &[u8] = b"0.1.0";
 
@@ -731,6 +1122,18 @@ static VTABLE: PluginVTable = PluginVTable {
     close: close_fn,
     readdir: readdir_fn,
     remove: remove_fn,
+    fsync: fsync_fn,
+    link: link_fn,
+    rename: rename_fn,
+    readdir_at: readdir_at_fn,
+    flush: flush_fn,
+    getxattr: getxattr_fn,
+    setxattr: setxattr_fn,
+    listxattr: listxattr_fn,
+    removexattr: removexattr_fn,
+    health_check: health_check_fn,
+    snapshot: snapshot_fn,
+    open_snapshot: open_snapshot_fn,
 };
 
 #[no_mangle]
@@ -775,7 +1178,7 @@ mod tests {
         let provider = KvProvider::new(KvConfig::default());
 
         let flags = OpenFlags::create_file();
-        let handle = provider.open("/test.txt", flags).unwrap();
+        let handle = provider.open("/test.txt", flags).unwrap().0;
 
         provider.write(handle.id(), 0, b"kv store data").unwrap();
 
@@ -785,6 +1188,26 @@ mod tests {
         provider.close(handle.id()).unwrap();
     }
 
+    #[test]
+    fn etag_follows_file_contents() {
+        let provider = KvProvider::new(KvConfig::default());
+        let write = |path: &str, data: &[u8]| {
+            let handle = provider.open(path, OpenFlags::create_file()).unwrap().0;
+            provider.write(handle.id(), 0, data).unwrap();
+            provider.close(handle.id()).unwrap();
+            provider.stat(path).unwrap().etag
+        };
+
+        let a = write("/a.txt", b"same");
+        assert_eq!(write("/b.txt", b"same"), a);
+        assert_eq!(a, fs9_sdk::content_etag(b"same"));
+        assert_ne!(write("/b.txt", b"diff"), a);
+
+        let entries = provider.readdir("/").unwrap();
+        assert_eq!(entries[0].etag, a);
+        assert_eq!(entries[1].etag, fs9_sdk::content_etag(b"diff"));
+    }
+
     #[test]
     fn btree_ordering() {
         let provider = KvProvider::new(KvConfig::default());
@@ -792,7 +1215,7 @@ mod tests {
         let flags = OpenFlags::create_file();
         for name in ["c.txt", "a.txt", "b.txt"] {
             let path = format!("/{}", name);
-            let handle = provider.open(&path, flags).unwrap();
+            let handle = provider.open(&path, flags).unwrap().0;
             provider.close(handle.id()).unwrap();
         }
 
@@ -814,7 +1237,8 @@ mod tests {
         let file_flags = OpenFlags::create_file();
         let handle = provider
             .open("/level1/level2/file.txt", file_flags)
-            .unwrap();
+            .unwrap()
+            .0;
         provider.close(handle.id()).unwrap();
 
         let entries = provider.readdir("/level1").unwrap();
@@ -834,7 +1258,7 @@ mod tests {
         provider.open("/parent", dir_flags).unwrap();
 
         let file_flags = OpenFlags::create_file();
-        let handle = provider.open("/parent/child.txt", file_flags).unwrap();
+        let handle = provider.open("/parent/child.txt", file_flags).unwrap().0;
         provider.close(handle.id()).unwrap();
 
         let result = provider.remove("/parent");
@@ -849,15 +1273,283 @@ mod tests {
         let provider = KvProvider::new(KvConfig::default());
 
         let flags = OpenFlags::create_file();
-        let handle = provider.open("/truncate.txt", flags).unwrap();
+        let handle = provider.open("/truncate.txt", flags).unwrap().0;
         provider
             .write(handle.id(), 0, b"long content here")
             .unwrap();
         provider.close(handle.id()).unwrap();
 
-        provider.wstat("/truncate.txt", None, Some(5)).unwrap();
+        provider
+            .wstat("/truncate.txt", None, Some(5), None)
+            .unwrap();
 
         let info = provider.stat("/truncate.txt").unwrap();
         assert_eq!(info.size, 5);
     }
+
+    fn expire_now(provider: &KvProvider, path: &str) {
+        let mut store = provider.store.write().unwrap();
+        if let Some(KvEntry::File { expires_at, .. }) = store.get_mut(path) {
+            *expires_at = Some(SystemTime::now() - Duration::from_secs(1));
+        }
+    }
+
+    #[test]
+    fn ttl_expires_entries_lazily() {
+        let provider = KvProvider::new(KvConfig::default());
+        provider.open("/cache", OpenFlags::create_dir()).unwrap();
+        let handle = provider
+            .open("/cache/item", OpenFlags::create_file())
+            .unwrap()
+            .0;
+        provider.write(handle.id(), 0, b"cached").unwrap();
+
+        provider
+            .wstat("/cache/item", None, None, Some(Duration::from_secs(60)))
+            .unwrap();
+        assert_eq!(provider.stat("/cache/item").unwrap().size, 6);
+        assert_eq!(&provider.read(handle.id(), 0, 100).unwrap()[..], b"cached");
+        assert_eq!(provider.readdir("/cache").unwrap().len(), 1);
+
+        expire_now(&provider, "/cache/item");
+
+        assert!(provider.readdir("/cache").unwrap().is_empty());
+        assert!(!provider.store.read().unwrap().contains_key("/cache/item"));
+        assert!(matches!(
+            provider.stat("/cache/item"),
+            Err(FsError::NotFound(_))
+        ));
+        assert!(matches!(
+            provider.read(handle.id(), 0, 100),
+            Err(FsError::NotFound(_))
+        ));
+        provider.close(handle.id()).unwrap();
+
+        provider.remove("/cache").unwrap();
+    }
+
+    #[test]
+    fn expired_entry_is_evicted_on_stat() {
+        let provider = KvProvider::new(KvConfig::default());
+        let handle = provider
+            .open("/tmp.txt", OpenFlags::create_file())
+            .unwrap()
+            .0;
+        provider.close(handle.id()).unwrap();
+        provider
+            .wstat("/tmp.txt", None, None, Some(Duration::from_secs(60)))
+            .unwrap();
+
+        expire_now(&provider, "/tmp.txt");
+        assert!(provider.stat("/tmp.txt").is_err());
+        assert!(!provider.store.read().unwrap().contains_key("/tmp.txt"));
+
+        let handle = provider
+            .open("/tmp.txt", OpenFlags::create_file())
+            .unwrap()
+            .0;
+        provider.close(handle.id()).unwrap();
+        assert_eq!(provider.stat("/tmp.txt").unwrap().size, 0);
+    }
+
+    #[test]
+    fn overflowing_ttl_is_rejected() {
+        let provider = KvProvider::new(KvConfig::default());
+        let handle = provider
+            .open("/forever.txt", OpenFlags::create_file())
+            .unwrap()
+            .0;
+        provider.close(handle.id()).unwrap();
+
+        assert!(matches!(
+            provider.wstat(
+                "/forever.txt",
+                Some(0o600),
+                None,
+                Some(Duration::from_secs(u64::MAX))
+            ),
+            Err(FsError::InvalidArgument(_))
+        ));
+        assert!(matches!(
+            provider.store.read().unwrap().get("/forever.txt"),
+            Some(KvEntry::File {
+                expires_at: None,
+                ..
+            })
+        ));
+        assert_ne!(provider.stat("/forever.txt").unwrap().mode, 0o600);
+    }
+
+    #[test]
+    fn zero_ttl_clears_expiry() {
+        let provider = KvProvider::new(KvConfig::default());
+        let handle = provider
+            .open("/keep.txt", OpenFlags::create_file())
+            .unwrap()
+            .0;
+        provider.close(handle.id()).unwrap();
+
+        provider
+            .wstat("/keep.txt", None, None, Some(Duration::from_secs(60)))
+            .unwrap();
+        provider
+            .wstat("/keep.txt", None, None, Some(Duration::ZERO))
+            .unwrap();
+        assert!(matches!(
+            provider.store.read().unwrap().get("/keep.txt"),
+            Some(KvEntry::File {
+                expires_at: None,
+                ..
+            })
+        ));
+
+        let provider = KvProvider::new(KvConfig::default());
+        provider.open("/dir", OpenFlags::create_dir()).unwrap();
+        assert!(matches!(
+            provider.wstat("/dir", None, None, Some(Duration::from_secs(1))),
+            Err(FsError::InvalidArgument(_))
+        ));
+    }
+
+    fn cas(provider: &KvProvider, path: &str, request: &str) -> FsResult<usize> {
+        let handle = provider.open(path, OpenFlags::write())?.0;
+        let result = provider.write(handle.id(), 0, request.as_bytes());
+        provider.close(handle.id()).unwrap();
+        result
+    }
+
+    fn contents(provider: &KvProvider, path: &str) -> Bytes {
+        let handle = provider.open(path, OpenFlags::read()).unwrap().0;
+        let data = provider.read(handle.id(), 0, 1024).unwrap();
+        provider.close(handle.id()).unwrap();
+        data
+    }
+
+    #[test]
+    fn compare_and_swap_replaces_matching_contents() {
+        let provider = KvProvider::new(KvConfig::default());
+
+        cas(&provider, "/.cas/lock", r#"{"expected":null,"new":"free"}"#).unwrap();
+        assert_eq!(&contents(&provider, "/lock")[..], b"free");
+
+        cas(
+            &provider,
+            "/.cas/lock",
+            r#"{"expected":"free","new":"alice"}"#,
+        )
+        .unwrap();
+        assert_eq!(&contents(&provider, "/lock")[..], b"alice");
+        assert!(provider.stat("/.cas/lock").is_err());
+
+        // Only the reserved directory is special.
+        cas(
+            &provider,
+            "/.cas/lock.cas",
+            r#"{"expected":null,"new":"x"}"#,
+        )
+        .unwrap();
+        let handle = provider
+            .open("/notes.cas", OpenFlags::create_file())
+            .unwrap()
+            .0;
+        provider.write(handle.id(), 0, b"plain").unwrap();
+        provider.close(handle.id()).unwrap();
+        assert_eq!(&contents(&provider, "/notes.cas")[..], b"plain");
+        assert_eq!(&contents(&provider, "/lock.cas")[..], b"x");
+        assert!(matches!(
+            provider.open("/.cas", OpenFlags::create_dir()),
+            Err(FsError::PermissionDenied(_))
+        ));
+    }
+
+    #[test]
+    fn compare_and_swap_mismatch_fails() {
+        let provider = KvProvider::new(KvConfig::default());
+        cas(&provider, "/.cas/lock", r#"{"expected":null,"new":"bob"}"#).unwrap();
+
+        let err = cas(
+            &provider,
+            "/.cas/lock",
+            r#"{"expected":"free","new":"alice"}"#,
+        )
+        .unwrap_err();
+        assert!(matches!(err, FsError::AlreadyExists(_)));
+        assert_eq!(fserror_to_code(&err), FS9_ERR_ALREADY_EXISTS);
+
+        assert!(matches!(
+            cas(
+                &provider,
+                "/.cas/lock",
+                r#"{"expected":null,"new":"alice"}"#
+            ),
+            Err(FsError::AlreadyExists(_))
+        ));
+        assert!(matches!(
+            cas(&provider, "/.cas/lock", "not json"),
+            Err(FsError::InvalidArgument(_))
+        ));
+        assert!(provider.open("/.cas/lock", OpenFlags::read()).is_err());
+        assert_eq!(&contents(&provider, "/lock")[..], b"bob");
+    }
+
+    #[test]
+    fn concurrent_compare_and_swap_has_one_winner() {
+        let provider = KvProvider::new(KvConfig::default());
+        cas(&provider, "/.cas/lock", r#"{"expected":null,"new":"free"}"#).unwrap();
+
+        for _ in 0..50 {
+            let results: Vec<_> = std::thread::scope(|scope| {
+                let workers: Vec<_> = ["alice", "bob"]
+                    .into_iter()
+                    .map(|owner| {
+                        let provider = &provider;
+                        scope.spawn(move || {
+                            let request = format!(r#"{{"expected":"free","new":"{owner}"}}"#);
+                            cas(provider, "/.cas/lock", &request).is_ok()
+                        })
+                    })
+                    .collect();
+                workers.into_iter().map(|w| w.join().unwrap()).collect()
+            });
+
+            assert_eq!(results.iter().filter(|won| **won).count(), 1);
+            let owner = contents(&provider, "/lock");
+            assert!(&owner[..] == b"alice" || &owner[..] == b"bob");
+
+            let release = format!(
+                r#"{{"expected":"{}","new":"free"}}"#,
+                String::from_utf8_lossy(&owner)
+            );
+            cas(&provider, "/.cas/lock", &release).unwrap();
+        }
+    }
+
+    #[test]
+    fn readdir_at_pages_without_gaps() {
+        let provider = KvProvider::new(KvConfig::default());
+        provider.open("/big", OpenFlags::create_dir()).unwrap();
+        for i in 0..1000 {
+            let path = format!("/big/f{i:04}");
+            let handle = provider.open(&path, OpenFlags::create_file()).unwrap().0;
+            provider.close(handle.id()).unwrap();
+        }
+
+        let mut seen = Vec::new();
+        let mut cursor: Option<String> = None;
+        let mut pages = 0;
+        loop {
+            let page = provider.readdir_at("/big", cursor.as_deref(), 100).unwrap();
+            assert!(page.entries.len() <= 100);
+            seen.extend(page.entries.into_iter().map(|e| e.path));
+            pages += 1;
+            match page.next_cursor {
+                Some(next) => cursor = Some(next),
+                None => break,
+            }
+        }
+
+        assert_eq!(pages, 10);
+        let expected: Vec<_> = (0..1000).map(|i| format!("/big/f{i:04}")).collect();
+        assert_eq!(seen, expected);
+    }
 }
@@ -0,0 +1,94 @@
+use std::collections::BTreeMap;
+
+/// Write-back cache of dirty pages keyed by `(inode_id, page_num)`.
+///
+/// Pages are held in their decoded, full-page form; encoding (and
+/// compression) happens when they are flushed to the KV backend.
+#[derive(Default)]
+pub struct DirtyPages {
+    pages: BTreeMap<(u64, u64), Vec<u8>>,
+    bytes: usize,
+}
+
+impl DirtyPages {
+    pub fn get(&self, inode_id: u64, page_num: u64) -> Option<&Vec<u8>> {
+        self.pages.get(&(inode_id, page_num))
+    }
+
+    pub fn insert(&mut self, inode_id: u64, page_num: u64, data: Vec<u8>) {
+        self.bytes += data.len();
+        if let Some(old) = self.pages.insert((inode_id, page_num), data) {
+            self.bytes -= old.len();
+        }
+    }
+
+    pub fn remove(&mut self, inode_id: u64, page_num: u64) {
+        if let Some(old) = self.pages.remove(&(inode_id, page_num)) {
+            self.bytes -= old.len();
+        }
+    }
+
+    /// Total size of all buffered pages.
+    pub const fn bytes(&self) -> usize {
+        self.bytes
+    }
+
+    /// Remove and return the dirty pages of one inode with page number
+    /// `first_page` or later, in page order.
+    pub fn take_inode_from(&mut self, inode_id: u64, first_page: u64) -> Vec<(u64, Vec<u8>)> {
+        let mut taken = self.pages.split_off(&(inode_id, first_page));
+        if let Some(next) = inode_id.checked_add(1) {
+            let mut rest = taken.split_off(&(next, 0));
+            self.pages.append(&mut rest);
+        }
+        taken
+            .into_iter()
+            .map(|((_, page_num), data)| {
+                self.bytes -= data.len();
+                (page_num, data)
+            })
+            .collect()
+    }
+
+    /// Remove and return every dirty page.
+    pub fn take_all(&mut self) -> Vec<((u64, u64), Vec<u8>)> {
+        self.bytes = 0;
+        std::mem::take(&mut self.pages).into_iter().collect()
+    }
+}
+
+/// Clean pages fetched ahead of a sequential reader, keyed like
+/// [`DirtyPages`]. Entries are dropped whenever their page is rewritten or
+/// deleted, and once the reader has moved past them.
+#[derive(Default)]
+pub struct PrefetchedPages {
+    pages: BTreeMap<(u64, u64), Vec<u8>>,
+}
+
+impl PrefetchedPages {
+    pub fn get(&self, inode_id: u64, page_num: u64) -> Option<&Vec<u8>> {
+        self.pages.get(&(inode_id, page_num))
+    }
+
+    pub fn contains(&self, inode_id: u64, page_num: u64) -> bool {
+        self.pages.contains_key(&(inode_id, page_num))
+    }
+
+    pub fn insert(&mut self, inode_id: u64, page_num: u64, data: Vec<u8>) {
+        self.pages.insert((inode_id, page_num), data);
+    }
+
+    pub fn remove(&mut self, inode_id: u64, page_num: u64) {
+        self.pages.remove(&(inode_id, page_num));
+    }
+
+    /// Drop the pages of one inode numbered below `first_page`.
+    pub fn discard_before(&mut self, inode_id: u64, first_page: u64) {
+        self.pages
+            .retain(|&(id, page_num), _| id != inode_id || page_num >= first_page);
+    }
+
+    pub fn remove_inode(&mut self, inode_id: u64) {
+        self.pages.retain(|&(id, _), _| id != inode_id);
+    }
+}
@@ -0,0 +1,98 @@
+use std::io::{Read, Write};
+
+use serde::Deserialize;
+
+/// First byte of every compressed page. Raw pages are always exactly one page
+/// long, so the length check alone separates them from framed ones; the magic
+/// byte guards against reading garbage as a header.
+const PAGE_MAGIC: u8 = 0xC9;
+
+/// magic (1) + codec (1) + original length (4, big-endian)
+const HEADER_LEN: usize = 6;
+
+const ZSTD_LEVEL: i32 = 3;
+
+/// Page compression applied by `write_page` before storing in the KV backend.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Compression {
+    #[default]
+    None,
+    Zlib,
+    Zstd,
+}
+
+impl Compression {
+    const fn tag(self) -> u8 {
+        match self {
+            Self::None => 0,
+            Self::Zlib => 1,
+            Self::Zstd => 2,
+        }
+    }
+
+    const fn from_tag(tag: u8) -> Option<Self> {
+        match tag {
+            0 => Some(Self::None),
+            1 => Some(Self::Zlib),
+            2 => Some(Self::Zstd),
+            _ => None,
+        }
+    }
+
+    fn compress(self, data: &[u8]) -> Option<Vec<u8>> {
+        match self {
+            Self::None => None,
+            Self::Zlib => {
+                let mut encoder =
+                    flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+                encoder.write_all(data).ok()?;
+                encoder.finish().ok()
+            }
+            Self::Zstd => zstd::bulk::compress(data, ZSTD_LEVEL).ok(),
+        }
+    }
+
+    fn decompress(self, data: &[u8], original_len: usize) -> Option<Vec<u8>> {
+        match self {
+            Self::None => Some(data.to_vec()),
+            Self::Zlib => {
+                let mut out = Vec::with_capacity(original_len);
+                flate2::read::ZlibDecoder::new(data)
+                    .read_to_end(&mut out)
+                    .ok()?;
+                Some(out)
+            }
+            Self::Zstd => zstd::bulk::decompress(data, original_len).ok(),
+        }
+    }
+}
+
+/// Encode a full page for storage. Falls back to the raw page when compression
+/// is disabled or doesn't make the page smaller.
+pub fn encode_page(compression: Compression, page: &[u8]) -> Vec<u8> {
+    match compression.compress(page) {
+        Some(payload) if HEADER_LEN + payload.len() < page.len() => {
+            let mut out = Vec::with_capacity(HEADER_LEN + payload.len());
+            out.push(PAGE_MAGIC);
+            out.push(compression.tag());
+            out.extend_from_slice(&(page.len() as u32).to_be_bytes());
+            out.extend_from_slice(&payload);
+            out
+        }
+        _ => page.to_vec(),
+    }
+}
+
+/// Decode a stored page. Pages of exactly `page_size` bytes are raw, whether
+/// written before compression was enabled or stored raw as a fallback.
+pub fn decode_page(stored: Vec<u8>, page_size: usize) -> Option<Vec<u8>> {
+    if stored.len() == page_size || stored.len() < HEADER_LEN || stored[0] != PAGE_MAGIC {
+        return Some(stored);
+    }
+
+    let codec = Compression::from_tag(stored[1])?;
+    let original_len = u32::from_be_bytes(stored[2..HEADER_LEN].try_into().ok()?) as usize;
+    let page = codec.decompress(&stored[HEADER_LEN..], original_len)?;
+    (page.len() == original_len).then_some(page)
+}
@@ -3,7 +3,7 @@ use std::ptr;
 use fs9_sdk::{Capabilities, FileType, OpenFlags, StatChanges};
 use fs9_sdk_ffi::{
     CBytes, CFileInfo, CFsStats, COpenFlags, CResult, CStatChanges, PluginVTable,
-    FILE_TYPE_DIRECTORY, FILE_TYPE_REGULAR, FS9_OK, FS9_SDK_VERSION,
+    FILE_TYPE_DIRECTORY, FILE_TYPE_REGULAR, FILE_TYPE_SYMLINK, FS9_OK, FS9_SDK_VERSION,
 };
 use libc::{c_char, c_void, size_t};
 
@@ -11,7 +11,7 @@ use crate::provider::PageFsProvider;
 #[cfg(feature = "tikv")]
 use crate::TikvKvBackend;
 use crate::{
-    fserror_to_code, make_cresult_err, systemtime_to_timestamp, timestamp_to_system_time,
+    fserror_to_cresult, make_cresult_err, systemtime_to_timestamp, timestamp_to_system_time,
     BackendConfig, InMemoryKv, KvBackend, PageFsConfig,
 };
 
@@ -39,7 +39,13 @@ fn create_provider_inner(config: *const c_char, config_len: size_t) -> *mut c_vo
         PageFsConfig::default()
     } else {
         let config_slice = unsafe { std::slice::from_raw_parts(config as *const u8, config_len) };
-        serde_json::from_slice(config_slice).unwrap_or_default()
+        match serde_json::from_slice(config_slice) {
+            Ok(cfg) => cfg,
+            Err(e) => {
+                eprintln!("pagefs: create_provider failed: invalid config: {e}");
+                return ptr::null_mut();
+            }
+        }
     };
 
     let backend: Box<dyn KvBackend> = match cfg.backend {
@@ -61,9 +67,32 @@ fn create_provider_inner(config: *const c_char, config_len: size_t) -> *mut c_vo
             cert_path,
             key_path,
         )),
+        #[cfg(feature = "rocksdb")]
+        BackendConfig::RocksDb { path } => match crate::RocksKvBackend::new(&path) {
+            Ok(kv) => Box::new(kv),
+            Err(e) => {
+                eprintln!("pagefs: create_provider failed: cannot open RocksDB at {path}: {e}");
+                return ptr::null_mut();
+            }
+        },
     };
 
-    let provider = Box::new(PageFsProvider::with_config(backend, cfg.uid, cfg.gid));
+    let provider = match PageFsProvider::try_with_config(backend, cfg.uid, cfg.gid, cfg.page_size) {
+        Ok(provider) => provider,
+        Err(e) => {
+            eprintln!("pagefs: create_provider failed: {e}");
+            return ptr::null_mut();
+        }
+    };
+    let provider = provider
+        .with_compression(cfg.compression)
+        .with_write_cache(cfg.write_cache_bytes)
+        .with_read_ahead(cfg.read_ahead_pages)
+        .with_creation_modes(cfg.default_file_mode, cfg.default_dir_mode, cfg.umask);
+    let provider = Box::new(match cfg.total_pages {
+        Some(pages) => provider.with_total_pages(pages),
+        None => provider,
+    });
     Box::into_raw(provider) as *mut c_void
 }
 
@@ -78,10 +107,23 @@ unsafe extern "C" fn get_capabilities(_provider: *mut c_void) -> u64 {
         | Capabilities::TRUNCATE
         | Capabilities::RENAME
         | Capabilities::CHMOD
-        | Capabilities::UTIME)
+        | Capabilities::UTIME
+        | Capabilities::SYMLINK
+        | Capabilities::HARDLINK
+        | Capabilities::FSYNC
+        | Capabilities::XATTR
+        | Capabilities::SNAPSHOT)
         .bits()
 }
 
+fn file_type_to_c(file_type: FileType) -> u8 {
+    match file_type {
+        FileType::Regular => FILE_TYPE_REGULAR,
+        FileType::Directory => FILE_TYPE_DIRECTORY,
+        FileType::Symlink => FILE_TYPE_SYMLINK,
+    }
+}
+
 unsafe extern "C" fn stat_fn(
     provider: *mut c_void,
     path: *const c_char,
@@ -99,22 +141,19 @@ unsafe extern "C" fn stat_fn(
     match provider.stat(path) {
         Ok(info) => {
             (*out_info).size = info.size;
-            (*out_info).file_type = if info.file_type == FileType::Directory {
-                FILE_TYPE_DIRECTORY
-            } else {
-                FILE_TYPE_REGULAR
-            };
+            (*out_info).file_type = file_type_to_c(info.file_type);
             (*out_info).mode = info.mode;
             (*out_info).mtime = systemtime_to_timestamp(info.mtime);
             (*out_info).atime = systemtime_to_timestamp(info.atime);
             (*out_info).ctime = systemtime_to_timestamp(info.ctime);
+            (*out_info).set_etag(&info.etag);
             CResult {
                 code: FS9_OK,
                 error_msg: ptr::null(),
                 error_msg_len: 0,
             }
         }
-        Err(e) => make_cresult_err(fserror_to_code(&e)),
+        Err(e) => fserror_to_cresult(&e),
     }
 }
 
@@ -155,7 +194,21 @@ unsafe extern "C" fn wstat_fn(
         } else {
             None
         },
-        symlink_target: None,
+        symlink_target: if c.has_symlink_target != 0 && !c.symlink_target.is_null() {
+            std::str::from_utf8(std::slice::from_raw_parts(
+                c.symlink_target as *const u8,
+                c.symlink_target_len,
+            ))
+            .ok()
+            .map(String::from)
+        } else {
+            None
+        },
+        ttl: if c.has_ttl != 0 {
+            Some(std::time::Duration::from_secs(c.ttl_secs))
+        } else {
+            None
+        },
     };
 
     match provider.wstat(path, &stat_changes) {
@@ -164,7 +217,7 @@ unsafe extern "C" fn wstat_fn(
             error_msg: ptr::null(),
             error_msg_len: 0,
         },
-        Err(e) => make_cresult_err(fserror_to_code(&e)),
+        Err(e) => fserror_to_cresult(&e),
     }
 }
 
@@ -179,14 +232,14 @@ unsafe extern "C" fn statfs_fn(
     }
 
     let provider = &*(provider as *const PageFsProvider);
-    let sb = provider.load_superblock();
+    let stats = provider.statfs();
 
-    (*out_stats).total_bytes = sb.total_pages * sb.page_size as u64;
-    (*out_stats).free_bytes = (sb.total_pages - sb.used_pages) * sb.page_size as u64;
-    (*out_stats).total_inodes = 1_000_000;
-    (*out_stats).free_inodes = 1_000_000 - sb.next_inode;
-    (*out_stats).block_size = sb.page_size as u32;
-    (*out_stats).max_name_len = 255;
+    (*out_stats).total_bytes = stats.total_bytes;
+    (*out_stats).free_bytes = stats.free_bytes;
+    (*out_stats).total_inodes = stats.total_inodes;
+    (*out_stats).free_inodes = stats.free_inodes;
+    (*out_stats).block_size = stats.block_size;
+    (*out_stats).max_name_len = stats.max_name_len;
 
     CResult {
         code: FS9_OK,
@@ -219,30 +272,29 @@ unsafe extern "C" fn open_fn(
         truncate: flags.truncate != 0,
         append: flags.append != 0,
         directory: flags.directory != 0,
+        symlink: flags.symlink != 0,
+        exclusive: flags.excl != 0,
     };
 
     match provider.open(path, open_flags) {
         Ok((handle, info)) => {
             *out_handle = handle.id();
             (*out_info).size = info.size;
-            (*out_info).file_type = if info.file_type == FileType::Directory {
-                FILE_TYPE_DIRECTORY
-            } else {
-                FILE_TYPE_REGULAR
-            };
+            (*out_info).file_type = file_type_to_c(info.file_type);
             (*out_info).mode = info.mode;
             (*out_info).uid = info.uid;
             (*out_info).gid = info.gid;
             (*out_info).mtime = systemtime_to_timestamp(info.mtime);
             (*out_info).atime = systemtime_to_timestamp(info.atime);
             (*out_info).ctime = systemtime_to_timestamp(info.ctime);
+            (*out_info).set_etag(&info.etag);
             CResult {
                 code: FS9_OK,
                 error_msg: ptr::null(),
                 error_msg_len: 0,
             }
         }
-        Err(e) => make_cresult_err(fserror_to_code(&e)),
+        Err(e) => fserror_to_cresult(&e),
     }
 }
 
@@ -261,14 +313,14 @@ unsafe extern "C" fn read_fn(
 
     match provider.read(handle, offset, size) {
         Ok(data) => {
-            *out_data = fs9_sdk_ffi::vec_to_cbytes(data.to_vec());
+            *out_data = fs9_sdk_ffi::bytes_to_cbytes(data);
             CResult {
                 code: FS9_OK,
                 error_msg: ptr::null(),
                 error_msg_len: 0,
             }
         }
-        Err(e) => make_cresult_err(fserror_to_code(&e)),
+        Err(e) => fserror_to_cresult(&e),
     }
 }
 
@@ -300,7 +352,7 @@ unsafe extern "C" fn write_fn(
                 error_msg_len: 0,
             }
         }
-        Err(e) => make_cresult_err(fserror_to_code(&e)),
+        Err(e) => fserror_to_cresult(&e),
     }
 }
 
@@ -317,7 +369,7 @@ unsafe extern "C" fn close_fn(provider: *mut c_void, handle: u64, _sync: u8) ->
             error_msg: ptr::null(),
             error_msg_len: 0,
         },
-        Err(e) => make_cresult_err(fserror_to_code(&e)),
+        Err(e) => fserror_to_cresult(&e),
     }
 }
 
@@ -338,35 +390,86 @@ unsafe extern "C" fn readdir_fn(
 
     match provider.readdir(path) {
         Ok(entries) => {
-            for entry in entries {
-                let path_bytes = entry.path.as_bytes();
-                let info = CFileInfo {
-                    path: path_bytes.as_ptr() as *const c_char,
-                    path_len: path_bytes.len(),
-                    size: entry.size,
-                    file_type: if entry.file_type == FileType::Directory {
-                        FILE_TYPE_DIRECTORY
-                    } else {
-                        FILE_TYPE_REGULAR
-                    },
-                    mode: entry.mode,
-                    uid: 0,
-                    gid: 0,
-                    atime: systemtime_to_timestamp(entry.atime),
-                    mtime: systemtime_to_timestamp(entry.mtime),
-                    ctime: systemtime_to_timestamp(entry.ctime),
-                };
-                if callback(&info, user_data) != 0 {
-                    break;
-                }
+            emit_entries(&entries, callback, user_data);
+            CResult {
+                code: FS9_OK,
+                error_msg: ptr::null(),
+                error_msg_len: 0,
             }
+        }
+        Err(e) => fserror_to_cresult(&e),
+    }
+}
+
+unsafe extern "C" fn readdir_at_fn(
+    provider: *mut c_void,
+    path: *const c_char,
+    path_len: size_t,
+    cursor: *const c_char,
+    cursor_len: size_t,
+    max: size_t,
+    callback: fs9_sdk_ffi::ReaddirCallback,
+    user_data: *mut c_void,
+    out_next_cursor: *mut CBytes,
+) -> CResult {
+    if provider.is_null() || out_next_cursor.is_null() {
+        return make_cresult_err(fs9_sdk_ffi::FS9_ERR_INVALID_ARGUMENT);
+    }
+
+    let provider = &*(provider as *const PageFsProvider);
+    let path =
+        std::str::from_utf8_unchecked(std::slice::from_raw_parts(path as *const u8, path_len));
+    let cursor = if cursor.is_null() || cursor_len == 0 {
+        None
+    } else {
+        Some(std::str::from_utf8_unchecked(std::slice::from_raw_parts(
+            cursor as *const u8,
+            cursor_len,
+        )))
+    };
+
+    match provider.readdir_at(path, cursor, max) {
+        Ok(page) => {
+            emit_entries(&page.entries, callback, user_data);
+            *out_next_cursor = match page.next_cursor {
+                Some(next) => fs9_sdk_ffi::vec_to_cbytes(next.into_bytes()),
+                None => CBytes::default(),
+            };
             CResult {
                 code: FS9_OK,
                 error_msg: ptr::null(),
                 error_msg_len: 0,
             }
         }
-        Err(e) => make_cresult_err(fserror_to_code(&e)),
+        Err(e) => fserror_to_cresult(&e),
+    }
+}
+
+/// Hand each entry to a `ReaddirCallback`, stopping early if it asks to.
+unsafe fn emit_entries(
+    entries: &[fs9_sdk::FileInfo],
+    callback: fs9_sdk_ffi::ReaddirCallback,
+    user_data: *mut c_void,
+) {
+    for entry in entries {
+        let path_bytes = entry.path.as_bytes();
+        let mut info = CFileInfo {
+            path: path_bytes.as_ptr() as *const c_char,
+            path_len: path_bytes.len(),
+            size: entry.size,
+            file_type: file_type_to_c(entry.file_type),
+            mode: entry.mode,
+            uid: 0,
+            gid: 0,
+            atime: systemtime_to_timestamp(entry.atime),
+            mtime: systemtime_to_timestamp(entry.mtime),
+            ctime: systemtime_to_timestamp(entry.ctime),
+            ..CFileInfo::default()
+        };
+        info.set_etag(&entry.etag);
+        if callback(&info, user_data) != 0 {
+            break;
+        }
     }
 }
 
@@ -389,7 +492,293 @@ unsafe extern "C" fn remove_fn(
             error_msg: ptr::null(),
             error_msg_len: 0,
         },
-        Err(e) => make_cresult_err(fserror_to_code(&e)),
+        Err(e) => fserror_to_cresult(&e),
+    }
+}
+
+unsafe extern "C" fn fsync_fn(provider: *mut c_void, handle: u64, _data_only: u8) -> CResult {
+    if provider.is_null() {
+        return make_cresult_err(fs9_sdk_ffi::FS9_ERR_INVALID_ARGUMENT);
+    }
+
+    let provider = &*(provider as *const PageFsProvider);
+
+    // Inode metadata is written through, so only pages can be dirty and
+    // `data_only` makes no difference.
+    match provider.fsync(handle) {
+        Ok(()) => CResult {
+            code: FS9_OK,
+            error_msg: ptr::null(),
+            error_msg_len: 0,
+        },
+        Err(e) => fserror_to_cresult(&e),
+    }
+}
+
+unsafe extern "C" fn flush_fn(provider: *mut c_void) -> CResult {
+    if provider.is_null() {
+        return make_cresult_err(fs9_sdk_ffi::FS9_ERR_INVALID_ARGUMENT);
+    }
+
+    let provider = &*(provider as *const PageFsProvider);
+    provider.flush();
+    CResult {
+        code: FS9_OK,
+        error_msg: ptr::null(),
+        error_msg_len: 0,
+    }
+}
+
+unsafe extern "C" fn health_check_fn(provider: *mut c_void) -> CResult {
+    if provider.is_null() {
+        return make_cresult_err(fs9_sdk_ffi::FS9_ERR_INVALID_ARGUMENT);
+    }
+
+    // Loading the root inode goes through the KV backend.
+    let provider = &*(provider as *const PageFsProvider);
+    match provider.stat("/") {
+        Ok(_) => CResult {
+            code: FS9_OK,
+            error_msg: ptr::null(),
+            error_msg_len: 0,
+        },
+        Err(e) => fserror_to_cresult(&e),
+    }
+}
+
+unsafe extern "C" fn getxattr_fn(
+    provider: *mut c_void,
+    path: *const c_char,
+    path_len: size_t,
+    name: *const c_char,
+    name_len: size_t,
+    out_value: *mut CBytes,
+) -> CResult {
+    if provider.is_null() || path.is_null() || name.is_null() || out_value.is_null() {
+        return make_cresult_err(fs9_sdk_ffi::FS9_ERR_INVALID_ARGUMENT);
+    }
+
+    let provider = &*(provider as *const PageFsProvider);
+    let path =
+        std::str::from_utf8_unchecked(std::slice::from_raw_parts(path as *const u8, path_len));
+    let name =
+        std::str::from_utf8_unchecked(std::slice::from_raw_parts(name as *const u8, name_len));
+
+    match provider.getxattr(path, name) {
+        Ok(value) => {
+            *out_value = fs9_sdk_ffi::vec_to_cbytes(value);
+            CResult {
+                code: FS9_OK,
+                error_msg: ptr::null(),
+                error_msg_len: 0,
+            }
+        }
+        Err(e) => fserror_to_cresult(&e),
+    }
+}
+
+unsafe extern "C" fn setxattr_fn(
+    provider: *mut c_void,
+    path: *const c_char,
+    path_len: size_t,
+    name: *const c_char,
+    name_len: size_t,
+    value: *const u8,
+    value_len: size_t,
+) -> CResult {
+    if provider.is_null() || path.is_null() || name.is_null() {
+        return make_cresult_err(fs9_sdk_ffi::FS9_ERR_INVALID_ARGUMENT);
+    }
+
+    let provider = &*(provider as *const PageFsProvider);
+    let path =
+        std::str::from_utf8_unchecked(std::slice::from_raw_parts(path as *const u8, path_len));
+    let name =
+        std::str::from_utf8_unchecked(std::slice::from_raw_parts(name as *const u8, name_len));
+    let value = if value.is_null() || value_len == 0 {
+        &[]
+    } else {
+        std::slice::from_raw_parts(value, value_len)
+    };
+
+    match provider.setxattr(path, name, value) {
+        Ok(()) => CResult {
+            code: FS9_OK,
+            error_msg: ptr::null(),
+            error_msg_len: 0,
+        },
+        Err(e) => fserror_to_cresult(&e),
+    }
+}
+
+unsafe extern "C" fn listxattr_fn(
+    provider: *mut c_void,
+    path: *const c_char,
+    path_len: size_t,
+    out_names: *mut CBytes,
+) -> CResult {
+    if provider.is_null() || path.is_null() || out_names.is_null() {
+        return make_cresult_err(fs9_sdk_ffi::FS9_ERR_INVALID_ARGUMENT);
+    }
+
+    let provider = &*(provider as *const PageFsProvider);
+    let path =
+        std::str::from_utf8_unchecked(std::slice::from_raw_parts(path as *const u8, path_len));
+
+    match provider.listxattr(path) {
+        Ok(names) => {
+            let mut buf = Vec::new();
+            for name in names {
+                buf.extend_from_slice(name.as_bytes());
+                buf.push(0);
+            }
+            *out_names = fs9_sdk_ffi::vec_to_cbytes(buf);
+            CResult {
+                code: FS9_OK,
+                error_msg: ptr::null(),
+                error_msg_len: 0,
+            }
+        }
+        Err(e) => fserror_to_cresult(&e),
+    }
+}
+
+unsafe extern "C" fn removexattr_fn(
+    provider: *mut c_void,
+    path: *const c_char,
+    path_len: size_t,
+    name: *const c_char,
+    name_len: size_t,
+) -> CResult {
+    if provider.is_null() || path.is_null() || name.is_null() {
+        return make_cresult_err(fs9_sdk_ffi::FS9_ERR_INVALID_ARGUMENT);
+    }
+
+    let provider = &*(provider as *const PageFsProvider);
+    let path =
+        std::str::from_utf8_unchecked(std::slice::from_raw_parts(path as *const u8, path_len));
+    let name =
+        std::str::from_utf8_unchecked(std::slice::from_raw_parts(name as *const u8, name_len));
+
+    match provider.removexattr(path, name) {
+        Ok(()) => CResult {
+            code: FS9_OK,
+            error_msg: ptr::null(),
+            error_msg_len: 0,
+        },
+        Err(e) => fserror_to_cresult(&e),
+    }
+}
+
+unsafe extern "C" fn link_fn(
+    provider: *mut c_void,
+    existing_path: *const c_char,
+    existing_path_len: size_t,
+    new_path: *const c_char,
+    new_path_len: size_t,
+) -> CResult {
+    if provider.is_null() || existing_path.is_null() || new_path.is_null() {
+        return make_cresult_err(fs9_sdk_ffi::FS9_ERR_INVALID_ARGUMENT);
+    }
+
+    let provider = &*(provider as *const PageFsProvider);
+    let existing_path = std::str::from_utf8_unchecked(std::slice::from_raw_parts(
+        existing_path as *const u8,
+        existing_path_len,
+    ));
+    let new_path = std::str::from_utf8_unchecked(std::slice::from_raw_parts(
+        new_path as *const u8,
+        new_path_len,
+    ));
+
+    match provider.link(existing_path, new_path) {
+        Ok(()) => CResult {
+            code: FS9_OK,
+            error_msg: ptr::null(),
+            error_msg_len: 0,
+        },
+        Err(e) => fserror_to_cresult(&e),
+    }
+}
+
+unsafe extern "C" fn rename_fn(
+    provider: *mut c_void,
+    old_path: *const c_char,
+    old_path_len: size_t,
+    new_path: *const c_char,
+    new_path_len: size_t,
+) -> CResult {
+    if provider.is_null() || old_path.is_null() || new_path.is_null() {
+        return make_cresult_err(fs9_sdk_ffi::FS9_ERR_INVALID_ARGUMENT);
+    }
+
+    let provider = &*(provider as *const PageFsProvider);
+    let old_path = std::str::from_utf8_unchecked(std::slice::from_raw_parts(
+        old_path as *const u8,
+        old_path_len,
+    ));
+    let new_path = std::str::from_utf8_unchecked(std::slice::from_raw_parts(
+        new_path as *const u8,
+        new_path_len,
+    ));
+
+    match provider.rename(old_path, new_path) {
+        Ok(()) => CResult {
+            code: FS9_OK,
+            error_msg: ptr::null(),
+            error_msg_len: 0,
+        },
+        Err(e) => fserror_to_cresult(&e),
+    }
+}
+
+unsafe extern "C" fn snapshot_fn(
+    provider: *mut c_void,
+    path: *const c_char,
+    path_len: size_t,
+    out_id: *mut u64,
+) -> CResult {
+    if provider.is_null() || path.is_null() || out_id.is_null() {
+        return make_cresult_err(fs9_sdk_ffi::FS9_ERR_INVALID_ARGUMENT);
+    }
+
+    let provider = &*(provider as *const PageFsProvider);
+    let path =
+        std::str::from_utf8_unchecked(std::slice::from_raw_parts(path as *const u8, path_len));
+
+    match provider.snapshot(path) {
+        Ok(snapshot_id) => {
+            *out_id = snapshot_id;
+            CResult {
+                code: FS9_OK,
+                error_msg: ptr::null(),
+                error_msg_len: 0,
+            }
+        }
+        Err(e) => fserror_to_cresult(&e),
+    }
+}
+
+unsafe extern "C" fn open_snapshot_fn(
+    provider: *mut c_void,
+    snapshot_id: u64,
+    out_view: *mut *mut c_void,
+) -> CResult {
+    if provider.is_null() || out_view.is_null() {
+        return make_cresult_err(fs9_sdk_ffi::FS9_ERR_INVALID_ARGUMENT);
+    }
+
+    let provider = &*(provider as *const PageFsProvider);
+    match provider.snapshot_view(snapshot_id) {
+        Ok(view) => {
+            *out_view = Box::into_raw(Box::new(view)) as *mut c_void;
+            CResult {
+                code: FS9_OK,
+                error_msg: ptr::null(),
+                error_msg_len: 0,
+            }
+        }
+        Err(e) => fserror_to_cresult(&e),
     }
 }
 
@@ -414,6 +803,18 @@ static VTABLE: PluginVTable = PluginVTable {
     close: close_fn,
     readdir: readdir_fn,
     remove: remove_fn,
+    fsync: fsync_fn,
+    link: link_fn,
+    rename: rename_fn,
+    readdir_at: readdir_at_fn,
+    flush: flush_fn,
+    getxattr: getxattr_fn,
+    setxattr: setxattr_fn,
+    listxattr: listxattr_fn,
+    removexattr: removexattr_fn,
+    health_check: health_check_fn,
+    snapshot: snapshot_fn,
+    open_snapshot: open_snapshot_fn,
 };
 
 #[no_mangle]
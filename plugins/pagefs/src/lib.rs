@@ -1,25 +1,52 @@
 #![allow(clippy::missing_safety_doc)]
 
 use std::collections::BTreeMap;
+use std::ops::Bound;
 use std::ptr;
 use std::sync::RwLock;
 use std::time::{SystemTime, UNIX_EPOCH};
 
-use fs9_sdk::FsError;
+use fs9_sdk::{FileType, FsError};
 use fs9_sdk_ffi::{
-    CResult, FS9_ERR_ALREADY_EXISTS, FS9_ERR_INVALID_HANDLE, FS9_ERR_IS_DIRECTORY,
-    FS9_ERR_NOT_DIRECTORY, FS9_ERR_NOT_FOUND,
+    CResult, FS9_ERR_ALREADY_EXISTS, FS9_ERR_INVALID_ARGUMENT, FS9_ERR_INVALID_HANDLE,
+    FS9_ERR_IS_DIRECTORY, FS9_ERR_NOT_DIRECTORY, FS9_ERR_NOT_FOUND, FS9_ERR_PERMISSION_DENIED,
 };
 use serde::{Deserialize, Serialize};
 
+mod cache;
+mod compression;
 pub mod ffi;
 pub mod provider;
+mod snapshot;
+
+pub use compression::Compression;
 
 #[cfg(test)]
 mod tests;
 
+/// Default page size, used unless `page_size` is configured.
 pub const PAGE_SIZE: usize = 16 * 1024;
+pub const MIN_PAGE_SIZE: usize = 4 * 1024;
+pub const MAX_PAGE_SIZE: usize = 16 * 1024 * 1024;
+pub const DEFAULT_WRITE_CACHE_BYTES: usize = 4 * 1024 * 1024;
+/// Mode of new files unless `default_file_mode` is configured.
+pub const DEFAULT_FILE_MODE: u32 = 0o644;
+/// Mode of new directories unless `default_dir_mode` is configured.
+pub const DEFAULT_DIR_MODE: u32 = 0o755;
 pub(crate) const ROOT_INODE: u64 = 1;
+/// Symlinks followed while resolving a single path before giving up.
+pub(crate) const MAX_SYMLINK_DEPTH: usize = 40;
+/// Entries fetched per backend scan when `readdir` walks a whole directory.
+pub(crate) const READDIR_BATCH: usize = 1024;
+/// Longest extended attribute name, as on Linux.
+pub const MAX_XATTR_NAME_LEN: usize = 255;
+/// Largest extended attribute value, as on Linux.
+pub const MAX_XATTR_VALUE_SIZE: usize = 64 * 1024;
+/// Longest symlink target, Linux's `PATH_MAX`.
+pub const MAX_SYMLINK_TARGET_LEN: usize = 4096;
+/// Batched inode loads use one range scan while the requested ids cover at
+/// least 1 in this many of the ids in their range.
+pub(crate) const INODE_SCAN_SPREAD: usize = 4;
 
 /// Convert a signed Unix timestamp (seconds since epoch) to SystemTime.
 /// Handles negative timestamps (pre-1970) correctly.
@@ -36,6 +63,29 @@ pub trait KvBackend: Send + Sync {
     fn set(&self, key: &[u8], value: &[u8]);
     fn scan(&self, prefix: &[u8]) -> Vec<(Vec<u8>, Vec<u8>)>;
     fn delete(&self, key: &[u8]);
+
+    /// The values of `keys`, in the same order. Backends where each `get` is
+    /// a round trip should override this to fetch them together; the
+    /// default gets them one at a time.
+    fn get_many(&self, keys: &[Vec<u8>]) -> Vec<Option<Vec<u8>>> {
+        keys.iter().map(|key| self.get(key)).collect()
+    }
+
+    /// Up to `limit` entries under `prefix` whose keys sort strictly after
+    /// `start_after`, in key order. Backends that can seek should override
+    /// this; the default filters a full prefix scan.
+    fn scan_after(
+        &self,
+        prefix: &[u8],
+        start_after: &[u8],
+        limit: usize,
+    ) -> Vec<(Vec<u8>, Vec<u8>)> {
+        let mut entries = self.scan(prefix);
+        entries.retain(|(k, _)| k.as_slice() > start_after);
+        entries.sort_by(|a, b| a.0.cmp(&b.0));
+        entries.truncate(limit);
+        entries
+    }
 }
 
 pub struct InMemoryKv {
@@ -61,6 +111,11 @@ impl KvBackend for InMemoryKv {
         self.data.read().unwrap().get(key).cloned()
     }
 
+    fn get_many(&self, keys: &[Vec<u8>]) -> Vec<Option<Vec<u8>>> {
+        let data = self.data.read().unwrap();
+        keys.iter().map(|key| data.get(key).cloned()).collect()
+    }
+
     fn set(&self, key: &[u8], value: &[u8]) {
         self.data
             .write()
@@ -79,6 +134,25 @@ impl KvBackend for InMemoryKv {
     fn delete(&self, key: &[u8]) {
         self.data.write().unwrap().remove(key);
     }
+
+    fn scan_after(
+        &self,
+        prefix: &[u8],
+        start_after: &[u8],
+        limit: usize,
+    ) -> Vec<(Vec<u8>, Vec<u8>)> {
+        let lower = if start_after < prefix {
+            Bound::Included(prefix)
+        } else {
+            Bound::Excluded(start_after)
+        };
+        let data = self.data.read().unwrap();
+        data.range::<[u8], _>((lower, Bound::Unbounded))
+            .take_while(|(k, _)| k.starts_with(prefix))
+            .take(limit)
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect()
+    }
 }
 
 #[cfg(feature = "tikv")]
@@ -256,14 +330,7 @@ impl KvBackend for TikvKvBackend {
     }
 
     fn scan(&self, prefix: &[u8]) -> Vec<(Vec<u8>, Vec<u8>)> {
-        let mut end = prefix.to_vec();
-        if let Some(last) = end.last_mut() {
-            if *last < 0xFF {
-                *last += 1;
-            } else {
-                end.push(0x00);
-            }
-        }
+        let end = tikv_prefix_end(prefix);
 
         const BATCH: u32 = 10240;
         let mut result = Vec::new();
@@ -303,6 +370,50 @@ impl KvBackend for TikvKvBackend {
             eprintln!("[pagefs-tikv] delete FAILED: {e}");
         }
     }
+
+    /// Seeks straight past `start_after` instead of scanning the prefix.
+    fn scan_after(
+        &self,
+        prefix: &[u8],
+        start_after: &[u8],
+        limit: usize,
+    ) -> Vec<(Vec<u8>, Vec<u8>)> {
+        let lower = if start_after < prefix {
+            Bound::Included(prefix.to_vec())
+        } else {
+            Bound::Excluded(start_after.to_vec())
+        };
+        let range = (lower, Bound::Excluded(tikv_prefix_end(prefix)));
+        let limit = u32::try_from(limit).unwrap_or(u32::MAX);
+
+        self.runtime.block_on(async {
+            let mut snapshot = self.client.snapshot(
+                self.client.current_timestamp().await.unwrap(),
+                tikv_client::TransactionOptions::default(),
+            );
+            match snapshot.scan(range, limit).await {
+                Ok(pairs) => pairs.map(|kv| (kv.0.into(), kv.1)).collect(),
+                Err(e) => {
+                    eprintln!("[pagefs-tikv] scan FAILED: {e}");
+                    Vec::new()
+                }
+            }
+        })
+    }
+}
+
+/// The first key after every key that starts with `prefix`.
+#[cfg(feature = "tikv")]
+fn tikv_prefix_end(prefix: &[u8]) -> Vec<u8> {
+    let mut end = prefix.to_vec();
+    if let Some(last) = end.last_mut() {
+        if *last < 0xFF {
+            *last += 1;
+        } else {
+            end.push(0x00);
+        }
+    }
+    end
 }
 
 #[cfg(feature = "s3")]
@@ -351,27 +462,44 @@ impl S3KvBackend {
     }
 }
 
+#[cfg(feature = "s3")]
+async fn s3_get(client: &aws_sdk_s3::Client, bucket: &str, s3_key: &str) -> Option<Vec<u8>> {
+    match client.get_object().bucket(bucket).key(s3_key).send().await {
+        Ok(output) => output
+            .body
+            .collect()
+            .await
+            .ok()
+            .map(|data| data.into_bytes().to_vec()),
+        Err(_) => None,
+    }
+}
+
 #[cfg(feature = "s3")]
 impl KvBackend for S3KvBackend {
     fn get(&self, key: &[u8]) -> Option<Vec<u8>> {
         let s3_key = self.make_key(key);
+        self.runtime
+            .block_on(s3_get(&self.client, &self.bucket, &s3_key))
+    }
+
+    /// Issues every GetObject at once rather than one round trip per key.
+    fn get_many(&self, keys: &[Vec<u8>]) -> Vec<Option<Vec<u8>>> {
         self.runtime.block_on(async {
-            match self
-                .client
-                .get_object()
-                .bucket(&self.bucket)
-                .key(&s3_key)
-                .send()
-                .await
-            {
-                Ok(output) => output
-                    .body
-                    .collect()
-                    .await
-                    .ok()
-                    .map(|data| data.into_bytes().to_vec()),
-                Err(_) => None,
+            let fetches: Vec<_> = keys
+                .iter()
+                .map(|key| {
+                    let client = self.client.clone();
+                    let bucket = self.bucket.clone();
+                    let s3_key = self.make_key(key);
+                    tokio::spawn(async move { s3_get(&client, &bucket, &s3_key).await })
+                })
+                .collect();
+            let mut values = Vec::with_capacity(fetches.len());
+            for fetch in fetches {
+                values.push(fetch.await.ok().flatten());
             }
+            values
         })
     }
 
@@ -445,6 +573,151 @@ impl KvBackend for S3KvBackend {
                 .await;
         });
     }
+
+    /// Lists from `start_after` on with ListObjectsV2's `start-after`, which
+    /// keeps byte order because every key byte is two hex digits.
+    fn scan_after(
+        &self,
+        prefix: &[u8],
+        start_after: &[u8],
+        limit: usize,
+    ) -> Vec<(Vec<u8>, Vec<u8>)> {
+        let s3_prefix = self.make_key(prefix);
+        let s3_start_after = self.make_key(start_after);
+        let keys = self.runtime.block_on(async {
+            let mut keys = Vec::new();
+            let mut continuation_token: Option<String> = None;
+
+            while keys.len() < limit {
+                let mut req = self
+                    .client
+                    .list_objects_v2()
+                    .bucket(&self.bucket)
+                    .prefix(&s3_prefix)
+                    .start_after(&s3_start_after)
+                    .max_keys(i32::try_from(limit - keys.len()).unwrap_or(i32::MAX));
+                if let Some(token) = continuation_token.take() {
+                    req = req.continuation_token(token);
+                }
+
+                match req.send().await {
+                    Ok(output) => {
+                        keys.extend(
+                            output
+                                .contents
+                                .unwrap_or_default()
+                                .into_iter()
+                                .filter_map(|obj| self.parse_key(obj.key.as_deref()?)),
+                        );
+                        if output.is_truncated.unwrap_or(false) {
+                            continuation_token = output.next_continuation_token;
+                        } else {
+                            break;
+                        }
+                    }
+                    Err(_) => break,
+                }
+            }
+            keys.truncate(limit);
+            keys
+        });
+
+        let values = self.get_many(&keys);
+        keys.into_iter()
+            .zip(values)
+            .filter_map(|(key, value)| Some((key, value?)))
+            .collect()
+    }
+}
+
+#[cfg(feature = "rocksdb")]
+pub struct RocksKvBackend {
+    db: rocksdb::DB,
+}
+
+#[cfg(feature = "rocksdb")]
+impl RocksKvBackend {
+    /// Open, or create, the database at `path`.
+    ///
+    /// # Errors
+    ///
+    /// Returns the RocksDB error when the database can't be opened, for
+    /// example because another process holds its lock.
+    pub fn new(path: impl AsRef<std::path::Path>) -> Result<Self, rocksdb::Error> {
+        let mut opts = rocksdb::Options::default();
+        opts.create_if_missing(true);
+        let db = rocksdb::DB::open(&opts, path)?;
+        Ok(Self { db })
+    }
+}
+
+#[cfg(feature = "rocksdb")]
+impl KvBackend for RocksKvBackend {
+    fn get(&self, key: &[u8]) -> Option<Vec<u8>> {
+        match self.db.get(key) {
+            Ok(val) => val,
+            Err(e) => {
+                eprintln!("[pagefs-rocksdb] get FAILED: {e}");
+                None
+            }
+        }
+    }
+
+    fn set(&self, key: &[u8], value: &[u8]) {
+        if let Err(e) = self.db.put(key, value) {
+            eprintln!("[pagefs-rocksdb] put FAILED: {e}");
+        }
+    }
+
+    fn scan(&self, prefix: &[u8]) -> Vec<(Vec<u8>, Vec<u8>)> {
+        // Keys are byte-ordered, so the matching keys are one contiguous run
+        // starting at `prefix`; stop at the first key past it.
+        self.db
+            .prefix_iterator(prefix)
+            .map_while(|item| match item {
+                Ok(kv) => Some(kv),
+                Err(e) => {
+                    eprintln!("[pagefs-rocksdb] scan FAILED: {e}");
+                    None
+                }
+            })
+            .take_while(|(key, _)| key.starts_with(prefix))
+            .map(|(key, value)| (key.to_vec(), value.to_vec()))
+            .collect()
+    }
+
+    fn delete(&self, key: &[u8]) {
+        if let Err(e) = self.db.delete(key) {
+            eprintln!("[pagefs-rocksdb] delete FAILED: {e}");
+        }
+    }
+
+    /// Seeks straight to `start_after` instead of scanning the prefix.
+    fn scan_after(
+        &self,
+        prefix: &[u8],
+        start_after: &[u8],
+        limit: usize,
+    ) -> Vec<(Vec<u8>, Vec<u8>)> {
+        let from = start_after.max(prefix);
+        self.db
+            .iterator(rocksdb::IteratorMode::From(
+                from,
+                rocksdb::Direction::Forward,
+            ))
+            .map_while(|item| match item {
+                Ok(kv) => Some(kv),
+                Err(e) => {
+                    eprintln!("[pagefs-rocksdb] scan FAILED: {e}");
+                    None
+                }
+            })
+            .skip_while(|(key, _)| &**key == start_after)
+            .take_while(|(key, _)| key.starts_with(prefix))
+            .take(limit)
+            .map(|(key, value)| (key.to_vec(), value.to_vec()))
+            .collect()
+    }
 }
 
 pub(crate) mod keys {
@@ -481,12 +754,45 @@ pub(crate) mod keys {
         key
     }
 
+    pub fn xattr(inode_id: u64, name: &str) -> Vec<u8> {
+        let mut key = xattr_prefix(inode_id);
+        key.extend_from_slice(name.as_bytes());
+        key
+    }
+
+    pub fn xattr_prefix(inode_id: u64) -> Vec<u8> {
+        let mut key = vec![b'X'];
+        key.extend_from_slice(&inode_id.to_be_bytes());
+        key.push(b':');
+        key
+    }
+
     pub fn page_prefix(inode_id: u64) -> Vec<u8> {
         let mut key = vec![b'P'];
         key.extend_from_slice(&inode_id.to_be_bytes());
         key.push(b':');
         key
     }
+
+    /// Page data shared by snapshots and clones.
+    pub fn block(block_id: u64) -> Vec<u8> {
+        let mut key = vec![b'B'];
+        key.extend_from_slice(&block_id.to_be_bytes());
+        key
+    }
+
+    /// Number of page keys and snapshots referring to a shared block.
+    pub fn block_refs(block_id: u64) -> Vec<u8> {
+        let mut key = vec![b'R'];
+        key.extend_from_slice(&block_id.to_be_bytes());
+        key
+    }
+
+    pub fn snapshot(snapshot_id: u64) -> Vec<u8> {
+        let mut key = vec![b'N'];
+        key.extend_from_slice(&snapshot_id.to_be_bytes());
+        key
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -495,6 +801,18 @@ pub(crate) struct Superblock {
     pub(crate) page_size: usize,
     pub(crate) total_pages: u64,
     pub(crate) used_pages: u64,
+    #[serde(default)]
+    pub(crate) next_block: u64,
+    #[serde(default)]
+    pub(crate) next_snapshot: u64,
+    /// Shared blocks currently stored, each counted once however many files
+    /// and snapshots refer to it.
+    #[serde(default)]
+    pub(crate) shared_pages: u64,
+    /// False in stores formatted before `used_pages` was maintained; their
+    /// usage is recounted from the inodes when they are next opened.
+    #[serde(default)]
+    pub(crate) pages_counted: bool,
 }
 
 impl Default for Superblock {
@@ -504,6 +822,10 @@ impl Default for Superblock {
             page_size: PAGE_SIZE,
             total_pages: 1_000_000,
             used_pages: 0,
+            next_block: 0,
+            next_snapshot: 0,
+            shared_pages: 0,
+            pages_counted: true,
         }
     }
 }
@@ -512,6 +834,7 @@ impl Default for Superblock {
 pub(crate) enum InodeType {
     File,
     Directory,
+    Symlink,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -525,6 +848,17 @@ pub(crate) struct Inode {
     pub(crate) mtime: i64,
     pub(crate) ctime: i64,
     pub(crate) nlink: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) symlink_target: Option<String>,
+    /// Entries in a directory, kept current by every link and unlink. `None`
+    /// for other inodes and for directories stored before the count existed,
+    /// which are counted with a scan on first use.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) child_count: Option<u64>,
+    /// Set once any page of the file may refer to a shared block, so writes
+    /// know to check for one before overwriting a page in place.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub(crate) shared: bool,
 }
 
 impl Inode {
@@ -543,6 +877,9 @@ impl Inode {
             mtime: now,
             ctime: now,
             nlink: 1,
+            symlink_target: None,
+            child_count: None,
+            shared: false,
         }
     }
 
@@ -561,6 +898,30 @@ impl Inode {
             mtime: now,
             ctime: now,
             nlink: 2,
+            symlink_target: None,
+            child_count: Some(0),
+            shared: false,
+        }
+    }
+
+    pub(crate) fn new_symlink(id: u64, target: String) -> Self {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs() as i64;
+        Self {
+            id,
+            inode_type: InodeType::Symlink,
+            mode: 0o777,
+            size: target.len() as u64,
+            page_count: 0,
+            atime: now,
+            mtime: now,
+            ctime: now,
+            nlink: 1,
+            symlink_target: Some(target),
+            child_count: None,
+            shared: false,
         }
     }
 
@@ -568,6 +929,18 @@ impl Inode {
         self.inode_type == InodeType::Directory
     }
 
+    pub(crate) fn is_symlink(&self) -> bool {
+        self.inode_type == InodeType::Symlink
+    }
+
+    pub(crate) fn file_type(&self) -> FileType {
+        match self.inode_type {
+            InodeType::File => FileType::Regular,
+            InodeType::Directory => FileType::Directory,
+            InodeType::Symlink => FileType::Symlink,
+        }
+    }
+
     pub(crate) fn touch_mtime(&mut self) {
         let now = SystemTime::now()
             .duration_since(UNIX_EPOCH)
@@ -577,6 +950,13 @@ impl Inode {
         self.ctime = now;
     }
 
+    pub(crate) fn touch_ctime(&mut self) {
+        self.ctime = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs() as i64;
+    }
+
     pub(crate) fn touch_atime(&mut self) {
         self.atime = SystemTime::now()
             .duration_since(UNIX_EPOCH)
@@ -585,7 +965,7 @@ impl Inode {
     }
 }
 
-#[derive(Debug, Clone, Deserialize, Default)]
+#[derive(Debug, Clone, Deserialize)]
 pub(crate) struct PageFsConfig {
     #[serde(default)]
     pub(crate) uid: u32,
@@ -594,10 +974,53 @@ pub(crate) struct PageFsConfig {
     #[serde(default)]
     pub(crate) backend: BackendConfig,
     #[serde(default)]
+    pub(crate) compression: Compression,
+    /// Must match the page size the store was created with.
+    #[serde(default = "default_page_size")]
+    pub(crate) page_size: usize,
+    /// Byte budget of the write-back page cache; 0 disables it.
+    #[serde(default = "default_write_cache_bytes")]
+    pub(crate) write_cache_bytes: usize,
+    /// Pages fetched ahead of sequential readers; 0 disables read-ahead.
+    #[serde(default)]
+    pub(crate) read_ahead_pages: usize,
+    /// Capacity in pages; unset keeps the store's current capacity.
+    #[serde(default)]
+    pub(crate) total_pages: Option<u64>,
+    /// Mode of new files before `umask` is applied. Modes are numbers, or
+    /// octal strings such as `"0o640"`.
+    #[serde(default = "default_file_mode", deserialize_with = "mode_bits")]
+    pub(crate) default_file_mode: u32,
+    /// Mode of new directories before `umask` is applied.
+    #[serde(default = "default_dir_mode", deserialize_with = "mode_bits")]
+    pub(crate) default_dir_mode: u32,
+    /// Permission bits cleared from the mode of every new file and directory.
+    #[serde(default, deserialize_with = "mode_bits")]
+    pub(crate) umask: u32,
+    #[serde(default)]
     #[allow(dead_code)]
     pub(crate) ns: Option<String>,
 }
 
+impl Default for PageFsConfig {
+    fn default() -> Self {
+        Self {
+            uid: 0,
+            gid: 0,
+            backend: BackendConfig::default(),
+            compression: Compression::default(),
+            page_size: PAGE_SIZE,
+            write_cache_bytes: DEFAULT_WRITE_CACHE_BYTES,
+            read_ahead_pages: 0,
+            total_pages: None,
+            default_file_mode: DEFAULT_FILE_MODE,
+            default_dir_mode: DEFAULT_DIR_MODE,
+            umask: 0,
+            ns: None,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Deserialize, Default)]
 #[serde(tag = "type", rename_all = "lowercase")]
 pub(crate) enum BackendConfig {
@@ -623,6 +1046,50 @@ pub(crate) enum BackendConfig {
         #[serde(default)]
         keyspace: Option<String>,
     },
+    #[cfg(feature = "rocksdb")]
+    RocksDb { path: String },
+}
+
+const fn default_page_size() -> usize {
+    PAGE_SIZE
+}
+
+const fn default_write_cache_bytes() -> usize {
+    DEFAULT_WRITE_CACHE_BYTES
+}
+
+const fn default_file_mode() -> u32 {
+    DEFAULT_FILE_MODE
+}
+
+const fn default_dir_mode() -> u32 {
+    DEFAULT_DIR_MODE
+}
+
+/// Permission bits given as a number or as an octal string (`"0o640"`,
+/// `"0640"` or `"640"`), since JSON has no octal literals.
+fn mode_bits<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<u32, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Mode {
+        Number(u32),
+        Octal(String),
+    }
+
+    let mode = match Mode::deserialize(deserializer)? {
+        Mode::Number(mode) => mode,
+        Mode::Octal(s) => {
+            let digits = s.strip_prefix("0o").unwrap_or(&s);
+            u32::from_str_radix(digits, 8)
+                .map_err(|_| serde::de::Error::custom(format!("invalid octal mode '{s}'")))?
+        }
+    };
+    if mode > 0o7777 {
+        return Err(serde::de::Error::custom(format!(
+            "mode {mode:#o} has bits outside 0o7777"
+        )));
+    }
+    Ok(mode)
 }
 
 #[cfg(feature = "tikv")]
@@ -644,6 +1111,10 @@ pub(crate) fn make_cresult_err(code: i32) -> CResult {
     }
 }
 
+pub(crate) fn fserror_to_cresult(err: &FsError) -> CResult {
+    CResult::from_error(fserror_to_code(err), err)
+}
+
 pub(crate) fn fserror_to_code(err: &FsError) -> i32 {
     match err {
         FsError::NotFound(_) => FS9_ERR_NOT_FOUND,
@@ -651,6 +1122,9 @@ pub(crate) fn fserror_to_code(err: &FsError) -> i32 {
         FsError::IsDirectory(_) => FS9_ERR_IS_DIRECTORY,
         FsError::NotDirectory(_) => FS9_ERR_NOT_DIRECTORY,
         FsError::InvalidHandle(_) => FS9_ERR_INVALID_HANDLE,
+        FsError::InvalidArgument(_) => FS9_ERR_INVALID_ARGUMENT,
+        FsError::PermissionDenied(_) => FS9_ERR_PERMISSION_DENIED,
+        FsError::NoSpace(_) => fs9_sdk_ffi::FS9_ERR_NO_SPACE,
         _ => fs9_sdk_ffi::FS9_ERR_INTERNAL,
     }
 }
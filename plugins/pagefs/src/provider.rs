@@ -1,52 +1,212 @@
 use bytes::Bytes;
-use fs9_sdk::{FileInfo, FileType, FsError, FsResult, Handle, OpenFlags, StatChanges};
+use fs9_sdk::{
+    metadata_etag, DirPage, FileInfo, FileType, FsError, FsResult, FsStats, Handle, OpenFlags,
+    StatChanges,
+};
 
+use crate::cache::{DirtyPages, PrefetchedPages};
+use crate::compression::{decode_page, encode_page};
+use crate::snapshot::{
+    decode_page_ref, encode_page_ref, load_page, load_pages, SnapshotKv, SnapshotNode,
+};
 use crate::{
-    keys, systemtime_to_timestamp, timestamp_to_system_time, Inode, KvBackend, Superblock,
-    PAGE_SIZE, ROOT_INODE,
+    keys, systemtime_to_timestamp, timestamp_to_system_time, Compression, Inode, InodeType,
+    KvBackend, Superblock, DEFAULT_DIR_MODE, DEFAULT_FILE_MODE, DEFAULT_WRITE_CACHE_BYTES,
+    INODE_SCAN_SPREAD, MAX_PAGE_SIZE, MAX_SYMLINK_DEPTH, MAX_SYMLINK_TARGET_LEN,
+    MAX_XATTR_NAME_LEN, MAX_XATTR_VALUE_SIZE, MIN_PAGE_SIZE, PAGE_SIZE, READDIR_BATCH, ROOT_INODE,
 };
-use std::collections::BTreeMap;
-use std::sync::Mutex;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::ops::Range;
+use std::sync::{Arc, Mutex};
+
+/// (inode, path, flags, end offset of the last read)
+type OpenHandle = (u64, String, OpenFlags, Option<u64>);
+
+/// What [`PageFsProvider::collect_garbage`] found and fixed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct GcReport {
+    /// Inodes reachable from the root.
+    pub live_inodes: u64,
+    /// Inodes no directory entry led to, deleted.
+    pub unreachable_inodes: u64,
+    /// Entries naming a missing inode or inside an unreachable directory,
+    /// deleted.
+    pub dangling_entries: u64,
+    /// Link counts and directory entry counts that were recounted.
+    pub repaired_counts: u64,
+    /// Inode ids that had pages but no inode.
+    pub orphaned_inodes: u64,
+    /// Page keys deleted.
+    pub orphaned_pages: u64,
+    /// `used_pages` in the superblock before and after it was recounted.
+    pub used_pages_before: u64,
+    pub used_pages_after: u64,
+}
 
+/// # Crash consistency
+///
+/// Every metadata change is a sequence of single-key writes, ordered so
+/// that a crash between any two of them leaves garbage, never a reference
+/// to something that is not there:
+///
+/// - an inode id is reserved in the superblock before it is used, so a
+///   crash can leak an id but never hand it out twice;
+/// - an inode (and a new file's first page) is stored before the directory
+///   entry naming it, and a link count is raised before the entry it counts;
+/// - an entry is deleted before the inode it names, and a rename adds the
+///   new name before dropping the old one.
+///
+/// What a crash can leave behind is an unreachable inode, an inode whose
+/// link count or entry count is off, a file under both names of a rename,
+/// and pages or `used_pages` charged for them. [`Self::collect_garbage`]
+/// repairs all of these.
 pub struct PageFsProvider {
-    pub(crate) kv: Box<dyn KvBackend>,
-    handles: Mutex<BTreeMap<u64, (u64, String, OpenFlags)>>,
+    pub(crate) kv: Arc<dyn KvBackend>,
+    handles: Mutex<BTreeMap<u64, OpenHandle>>,
     next_handle: Mutex<u64>,
+    /// Held from the existence check to the new directory entry in `open`
+    /// with `create`, so exclusive creates of one path can't both succeed.
+    create_lock: Mutex<()>,
+    /// Held across every load-change-save of the superblock, so concurrent
+    /// updates can't save over each other.
+    superblock_lock: Mutex<()>,
     pub(crate) uid: u32,
     pub(crate) gid: u32,
+    page_size: usize,
+    compression: Compression,
+    dirty: Mutex<DirtyPages>,
+    write_cache_bytes: usize,
+    prefetched: Mutex<PrefetchedPages>,
+    read_ahead_pages: usize,
+    file_mode: u32,
+    dir_mode: u32,
+    umask: u32,
 }
 
 impl PageFsProvider {
-    pub fn new(kv: Box<dyn KvBackend>) -> Self {
+    pub fn new(kv: Box<dyn KvBackend>) -> FsResult<Self> {
         Self::with_config(kv, 0, 0)
     }
 
-    pub fn with_config(kv: Box<dyn KvBackend>, uid: u32, gid: u32) -> Self {
+    /// Open (or format) the filesystem in `kv` with the default page size.
+    /// Fails if the backend holds a filesystem with a different page size.
+    pub fn with_config(kv: Box<dyn KvBackend>, uid: u32, gid: u32) -> FsResult<Self> {
+        Self::try_with_config(kv, uid, gid, PAGE_SIZE)
+    }
+
+    /// Open (or format) the filesystem in `kv` with the given page size.
+    /// Fails if `page_size` is out of range or the backend already holds a
+    /// filesystem created with a different page size.
+    pub fn try_with_config(
+        kv: Box<dyn KvBackend>,
+        uid: u32,
+        gid: u32,
+        page_size: usize,
+    ) -> FsResult<Self> {
+        if !page_size.is_power_of_two() || !(MIN_PAGE_SIZE..=MAX_PAGE_SIZE).contains(&page_size) {
+            return Err(FsError::invalid_argument(format!(
+                "page size must be a power of two between {MIN_PAGE_SIZE} and {MAX_PAGE_SIZE}, got {page_size}"
+            )));
+        }
+
         let provider = Self {
-            kv,
+            kv: Arc::from(kv),
             handles: Mutex::new(BTreeMap::new()),
             next_handle: Mutex::new(1),
+            create_lock: Mutex::new(()),
+            superblock_lock: Mutex::new(()),
             uid,
             gid,
+            page_size,
+            compression: Compression::None,
+            dirty: Mutex::new(DirtyPages::default()),
+            write_cache_bytes: DEFAULT_WRITE_CACHE_BYTES,
+            prefetched: Mutex::new(PrefetchedPages::default()),
+            read_ahead_pages: 0,
+            file_mode: DEFAULT_FILE_MODE,
+            dir_mode: DEFAULT_DIR_MODE,
+            umask: 0,
         };
-        provider.init_filesystem();
-        provider
+        provider.init_filesystem()?;
+        Ok(provider)
+    }
+
+    /// Compress pages written from now on. Existing pages stay readable
+    /// whatever codec (if any) they were stored with.
+    #[must_use]
+    pub fn with_compression(mut self, compression: Compression) -> Self {
+        self.compression = compression;
+        self
     }
 
-    pub fn with_memory_backend() -> Self {
+    /// Buffer up to `bytes` of dirty pages before writing them back. Dirty
+    /// pages of a file are also written back when a handle to it is closed or
+    /// fsynced. Zero writes every page straight through to the backend.
+    #[must_use]
+    pub fn with_write_cache(mut self, bytes: usize) -> Self {
+        self.write_cache_bytes = bytes;
+        self
+    }
+
+    /// Once a handle reads sequentially, fetch up to `pages` pages ahead of
+    /// it concurrently. Zero disables read-ahead.
+    #[must_use]
+    pub fn with_read_ahead(mut self, pages: usize) -> Self {
+        self.read_ahead_pages = pages;
+        self
+    }
+
+    /// Create files with `file_mode` and directories with `dir_mode`, less
+    /// the bits set in `umask`. Only affects what is created from now on;
+    /// the root directory keeps the mode it was formatted with.
+    #[must_use]
+    pub const fn with_creation_modes(mut self, file_mode: u32, dir_mode: u32, umask: u32) -> Self {
+        self.file_mode = file_mode;
+        self.dir_mode = dir_mode;
+        self.umask = umask;
+        self
+    }
+
+    /// Cap the filesystem at `pages` file pages. Creating or growing a
+    /// file past the cap fails with `FsError::NoSpace`. The cap is stored
+    /// in the superblock, so it also applies to later opens of the store.
+    #[must_use]
+    pub fn with_total_pages(self, pages: u64) -> Self {
+        let _ = self.update_superblock(|sb| {
+            sb.total_pages = pages;
+            Ok(())
+        });
+        self
+    }
+
+    pub fn with_memory_backend() -> FsResult<Self> {
         Self::new(Box::new(crate::InMemoryKv::new()))
     }
 
-    fn init_filesystem(&self) {
+    fn init_filesystem(&self) -> FsResult<()> {
         if self.kv.get(&keys::superblock()).is_none() {
             eprintln!("[pagefs] No superblock found, creating fresh filesystem");
-            let sb = Superblock::default();
+            let sb = Superblock {
+                page_size: self.page_size,
+                ..Superblock::default()
+            };
             self.save_superblock(&sb);
 
             let root = Inode::new_directory(ROOT_INODE, 0o755);
             self.save_inode(&root);
             eprintln!("[pagefs] Created superblock and root inode");
-        } else if self.load_inode(ROOT_INODE).is_none() {
+            return Ok(());
+        }
+
+        let mut sb = self.load_superblock();
+        if sb.page_size != self.page_size {
+            return Err(FsError::invalid_argument(format!(
+                "filesystem was created with page size {}, configured page size is {}",
+                sb.page_size, self.page_size
+            )));
+        }
+
+        if self.load_inode(ROOT_INODE).is_none() {
             // Superblock exists but root inode is missing (e.g. stale data from
             // a previous session where writes failed silently). Recreate it.
             eprintln!("[pagefs] WARNING: Superblock exists but root inode missing — recreating");
@@ -55,6 +215,193 @@ impl PageFsProvider {
         } else {
             eprintln!("[pagefs] Filesystem already initialized, superblock and root inode OK");
         }
+
+        if !sb.pages_counted {
+            sb.used_pages = self.count_file_pages();
+            sb.pages_counted = true;
+            self.save_superblock(&sb);
+        }
+        Ok(())
+    }
+
+    /// Pages held by every file in the store, from a full scan of its inodes.
+    fn count_file_pages(&self) -> u64 {
+        self.kv
+            .scan(b"I")
+            .into_iter()
+            .filter_map(|(_, data)| serde_json::from_slice::<Inode>(&data).ok())
+            .filter(|inode| inode.inode_type == InodeType::File)
+            .map(|inode| inode.page_count)
+            .sum()
+    }
+
+    /// Check the store and repair what a crash can leave behind (see
+    /// [crash consistency](Self#crash-consistency)): delete inodes no entry
+    /// leads to from the root, entries naming missing inodes, and pages
+    /// left behind by inodes that no longer exist; recount link and entry
+    /// counts; and recount `used_pages` from the remaining inodes.
+    ///
+    /// Scans every inode, entry and page key, so it is meant to be run
+    /// offline or while the provider is idle: a file created while it runs
+    /// may be taken for unreachable.
+    pub fn collect_garbage(&self) -> GcReport {
+        let mut inodes: HashMap<u64, Inode> = self
+            .kv
+            .scan(b"I")
+            .into_iter()
+            .filter_map(|(key, data)| {
+                let id = u64::from_be_bytes(key.get(1..9)?.try_into().ok()?);
+                Some((id, serde_json::from_slice(&data).ok()?))
+            })
+            .collect();
+
+        // Walk the tree from the root, dropping entries that lead nowhere.
+        let mut names: HashMap<u64, u32> = HashMap::new();
+        let mut entry_counts: HashMap<u64, u64> = HashMap::new();
+        let mut reachable = HashSet::from([ROOT_INODE]);
+        let mut pending = vec![ROOT_INODE];
+        let mut dangling_entries = 0;
+        while let Some(dir) = pending.pop() {
+            let mut count = 0;
+            for (name, child) in self.list_dir(dir) {
+                let Some(inode) = inodes.get(&child) else {
+                    self.kv.delete(&keys::dir_entry(dir, &name));
+                    dangling_entries += 1;
+                    continue;
+                };
+                count += 1;
+                *names.entry(child).or_default() += 1;
+                if reachable.insert(child) && inode.is_directory() {
+                    pending.push(child);
+                }
+            }
+            entry_counts.insert(dir, count);
+        }
+
+        // Entries of directories the walk never reached.
+        for (key, _) in self.kv.scan(b"D") {
+            let parent = key
+                .get(1..9)
+                .and_then(|id| id.try_into().ok())
+                .map(u64::from_be_bytes);
+            if parent.is_some_and(|parent| !reachable.contains(&parent)) {
+                self.kv.delete(&key);
+                dangling_entries += 1;
+            }
+        }
+
+        let unreachable: Vec<u64> = inodes
+            .keys()
+            .copied()
+            .filter(|id| !reachable.contains(id))
+            .collect();
+        for &inode_id in &unreachable {
+            self.delete_inode(inode_id);
+            inodes.remove(&inode_id);
+        }
+
+        let repaired_counts = self.repair_counts(&mut inodes, &names, &entry_counts);
+
+        let live: HashSet<u64> = inodes.keys().copied().collect();
+
+        let mut orphans = BTreeSet::new();
+        let mut orphaned_pages = 0;
+        for (key, _) in self.kv.scan(b"P") {
+            let Some(inode_id) = key
+                .get(1..9)
+                .and_then(|id| id.try_into().ok())
+                .map(u64::from_be_bytes)
+            else {
+                continue;
+            };
+            if !live.contains(&inode_id) {
+                orphans.insert(inode_id);
+                orphaned_pages += 1;
+            }
+        }
+        for &inode_id in &orphans {
+            self.delete_pages(inode_id);
+        }
+
+        let (used_pages_before, used_pages_after) = self
+            .update_superblock(|sb| {
+                let before = sb.used_pages;
+                sb.used_pages = self.count_file_pages();
+                if let Some(&highest) = live.iter().max() {
+                    sb.next_inode = sb.next_inode.max(highest + 1);
+                }
+                Ok((before, sb.used_pages))
+            })
+            .unwrap_or_default();
+
+        let report = GcReport {
+            live_inodes: live.len() as u64,
+            unreachable_inodes: unreachable.len() as u64,
+            dangling_entries,
+            repaired_counts,
+            orphaned_inodes: orphans.len() as u64,
+            orphaned_pages,
+            used_pages_before,
+            used_pages_after,
+        };
+        let repaired = unreachable.len() as u64 + dangling_entries + repaired_counts;
+        if repaired > 0 || orphaned_pages > 0 || used_pages_before != used_pages_after {
+            eprintln!("[pagefs] garbage collection: {report:?}");
+        }
+        report
+    }
+
+    /// Set the link count of each non-directory to its number of `names`
+    /// and the cached entry count of each directory to `entry_counts`,
+    /// saving the inodes that change. Returns how many did.
+    fn repair_counts(
+        &self,
+        inodes: &mut HashMap<u64, Inode>,
+        names: &HashMap<u64, u32>,
+        entry_counts: &HashMap<u64, u64>,
+    ) -> u64 {
+        let mut repaired = 0;
+        for (inode_id, inode) in inodes {
+            let changed = if inode.is_directory() {
+                let count = entry_counts.get(inode_id).copied().unwrap_or(0);
+                let stale = inode.child_count.is_some_and(|cached| cached != count);
+                if stale {
+                    inode.child_count = Some(count);
+                }
+                stale
+            } else {
+                let count = names.get(inode_id).copied().unwrap_or(0);
+                let stale = inode.nlink != count;
+                inode.nlink = count;
+                stale
+            };
+            if changed {
+                self.save_inode(inode);
+                repaired += 1;
+            }
+        }
+        repaired
+    }
+
+    /// Capacity and free space, counting every file page against
+    /// `total_pages`. Pages a file was grown over but never written count
+    /// too: growing a file reserves its space the way `fallocate` does,
+    /// even though nothing is stored for them until they are written.
+    pub fn statfs(&self) -> FsStats {
+        let sb = self.load_superblock();
+        let page_size = sb.page_size as u64;
+        FsStats {
+            total_bytes: sb.total_pages * page_size,
+            free_bytes: sb.total_pages.saturating_sub(sb.used_pages) * page_size,
+            total_inodes: 1_000_000,
+            free_inodes: 1_000_000u64.saturating_sub(sb.next_inode),
+            block_size: sb.page_size as u32,
+            max_name_len: 255,
+        }
+    }
+
+    pub const fn page_size(&self) -> usize {
+        self.page_size
     }
 
     pub(crate) fn load_superblock(&self) -> Superblock {
@@ -69,12 +416,51 @@ impl PageFsProvider {
         self.kv.set(&keys::superblock(), &data);
     }
 
-    fn alloc_inode(&self) -> u64 {
+    /// Load the superblock, apply `change` and save the result, all under
+    /// `superblock_lock`. Nothing is saved if `change` fails.
+    fn update_superblock<T>(
+        &self,
+        change: impl FnOnce(&mut Superblock) -> FsResult<T>,
+    ) -> FsResult<T> {
+        let _guard = self.superblock_lock.lock().unwrap();
         let mut sb = self.load_superblock();
-        let id = sb.next_inode;
-        sb.next_inode += 1;
+        let result = change(&mut sb)?;
         self.save_superblock(&sb);
-        id
+        Ok(result)
+    }
+
+    /// Account for `delta` more (or, when negative, fewer) file pages,
+    /// refusing growth past `total_pages`.
+    fn charge_pages(&self, delta: i64) -> FsResult<()> {
+        if delta == 0 {
+            return Ok(());
+        }
+        self.update_superblock(|sb| {
+            if delta > 0 {
+                let needed = sb.used_pages + delta.unsigned_abs();
+                if needed > sb.total_pages {
+                    return Err(FsError::no_space(format!(
+                        "{needed} pages needed, {} available",
+                        sb.total_pages
+                    )));
+                }
+                sb.used_pages = needed;
+            } else {
+                sb.used_pages = sb.used_pages.saturating_sub(delta.unsigned_abs());
+            }
+            Ok(())
+        })
+    }
+
+    /// Reserve a fresh inode id. The superblock is saved before the id is
+    /// used anywhere, so a crash right after only leaks it.
+    fn alloc_inode(&self) -> u64 {
+        self.update_superblock(|sb| {
+            let id = sb.next_inode;
+            sb.next_inode += 1;
+            Ok(id)
+        })
+        .unwrap()
     }
 
     pub(crate) fn load_inode(&self, inode_id: u64) -> Option<Inode> {
@@ -83,12 +469,51 @@ impl PageFsProvider {
             .and_then(|data| serde_json::from_slice(&data).ok())
     }
 
+    /// Load several inodes at once. Ids allocated close together, like the
+    /// children of a directory filled in one go, come back from a single
+    /// range scan over their `I` keys; sparse ids fall back to one `get`
+    /// each. Missing inodes are left out of the map.
+    fn load_inodes(&self, ids: &[u64]) -> HashMap<u64, Inode> {
+        let (Some(&min), Some(&max)) = (ids.iter().min(), ids.iter().max()) else {
+            return HashMap::new();
+        };
+        let span = usize::try_from(max - min + 1).unwrap_or(usize::MAX);
+        if ids.len() < 2 || span > ids.len().saturating_mul(INODE_SCAN_SPREAD) {
+            return ids
+                .iter()
+                .filter_map(|&id| Some((id, self.load_inode(id)?)))
+                .collect();
+        }
+
+        let wanted: std::collections::HashSet<u64> = ids.iter().copied().collect();
+        let start_after = if min == 0 {
+            b"I".to_vec()
+        } else {
+            keys::inode(min - 1)
+        };
+        self.kv
+            .scan_after(b"I", &start_after, span)
+            .into_iter()
+            .filter_map(|(key, value)| {
+                let id = u64::from_be_bytes(key.get(1..)?.try_into().ok()?);
+                if !wanted.contains(&id) {
+                    return None;
+                }
+                Some((id, serde_json::from_slice(&value).ok()?))
+            })
+            .collect()
+    }
+
     fn save_inode(&self, inode: &Inode) {
         let data = serde_json::to_vec(inode).unwrap();
         self.kv.set(&keys::inode(inode.id), &data);
     }
 
+    /// Delete an inode together with its extended attributes.
     fn delete_inode(&self, inode_id: u64) {
+        for (key, _) in self.kv.scan(&keys::xattr_prefix(inode_id)) {
+            self.kv.delete(&key);
+        }
         self.kv.delete(&keys::inode(inode_id));
     }
 
@@ -104,15 +529,48 @@ impl PageFsProvider {
             })
     }
 
-    fn link(&self, parent_inode: u64, name: &str, child_inode: u64) {
+    /// Add a new entry; replacing an existing name must go through
+    /// `remove_entry` first so the parent's count stays right.
+    fn add_entry(&self, parent_inode: u64, name: &str, child_inode: u64) {
         self.kv.set(
             &keys::dir_entry(parent_inode, name),
             &child_inode.to_be_bytes(),
         );
+        self.adjust_child_count(parent_inode, 1);
     }
 
-    fn unlink(&self, parent_inode: u64, name: &str) {
+    /// Store a newly allocated `inode` and then name it `name` in
+    /// `parent_inode`. The entry goes last: until it is written the inode
+    /// is unreachable, and `collect_garbage` deletes it after a crash.
+    fn link_new_inode(&self, parent_inode: u64, name: &str, inode: &Inode) {
+        self.save_inode(inode);
+        self.add_entry(parent_inode, name, inode.id);
+    }
+
+    fn remove_entry(&self, parent_inode: u64, name: &str) {
         self.kv.delete(&keys::dir_entry(parent_inode, name));
+        self.adjust_child_count(parent_inode, -1);
+    }
+
+    /// Apply `delta` to the cached entry count of directory `dir_inode`,
+    /// counting from scratch if the directory has no count yet.
+    fn adjust_child_count(&self, dir_inode: u64, delta: i64) {
+        let Some(mut dir) = self.load_inode(dir_inode) else {
+            return;
+        };
+        dir.child_count = Some(match dir.child_count {
+            Some(count) => count.saturating_add_signed(delta),
+            None => self.list_dir(dir_inode).len() as u64,
+        });
+        self.save_inode(&dir);
+    }
+
+    /// Number of entries in the directory `inode`, from its cached count
+    /// when it has one.
+    fn child_count(&self, inode_id: u64, inode: &Inode) -> u64 {
+        inode
+            .child_count
+            .unwrap_or_else(|| self.list_dir(inode_id).len() as u64)
     }
 
     fn list_dir(&self, parent_inode: u64) -> Vec<(String, u64)> {
@@ -130,56 +588,459 @@ impl PageFsProvider {
     }
 
     pub(crate) fn read_page(&self, inode_id: u64, page_num: u64) -> Option<Vec<u8>> {
-        self.kv.get(&keys::page(inode_id, page_num))
+        if let Some(page) = self.dirty.lock().unwrap().get(inode_id, page_num) {
+            return Some(page.clone());
+        }
+        if let Some(page) = self.prefetched.lock().unwrap().get(inode_id, page_num) {
+            return Some(page.clone());
+        }
+        let stored = load_page(&*self.kv, inode_id, page_num)?;
+        self.decode_stored_page(inode_id, page_num, stored)
+    }
+
+    /// Like [`read_page`](Self::read_page) for every page in `pages`, with
+    /// the ones not already in memory fetched in one batch.
+    fn read_pages(&self, inode_id: u64, pages: Range<u64>) -> Vec<Option<Vec<u8>>> {
+        let first = pages.start;
+        let mut result: Vec<Option<Vec<u8>>> = {
+            let dirty = self.dirty.lock().unwrap();
+            pages
+                .clone()
+                .map(|page_num| dirty.get(inode_id, page_num).cloned())
+                .collect()
+        };
+        {
+            let prefetched = self.prefetched.lock().unwrap();
+            for (page_num, page) in pages.zip(result.iter_mut()) {
+                if page.is_none() {
+                    *page = prefetched.get(inode_id, page_num).cloned();
+                }
+            }
+        }
+
+        let missing: Vec<u64> = (first..)
+            .zip(&result)
+            .filter(|(_, page)| page.is_none())
+            .map(|(page_num, _)| page_num)
+            .collect();
+        if missing.is_empty() {
+            return result;
+        }
+        let fetched = load_pages(&*self.kv, inode_id, &missing);
+        for (page_num, stored) in missing.into_iter().zip(fetched) {
+            result[(page_num - first) as usize] =
+                stored.and_then(|stored| self.decode_stored_page(inode_id, page_num, stored));
+        }
+        result
+    }
+
+    fn decode_stored_page(&self, inode_id: u64, page_num: u64, stored: Vec<u8>) -> Option<Vec<u8>> {
+        let page = decode_page(stored, self.page_size);
+        if page.is_none() {
+            eprintln!("[pagefs] WARNING: failed to decode page {page_num} of inode {inode_id}");
+        }
+        page
     }
 
+    /// Write a page straight through to the backend, superseding any dirty
+    /// copy of it.
     fn write_page(&self, inode_id: u64, page_num: u64, data: &[u8]) {
+        self.dirty.lock().unwrap().remove(inode_id, page_num);
+        self.store_page(inode_id, page_num, data);
+    }
+
+    /// Buffer a page in the write-back cache, flushing everything once the
+    /// cache grows past its budget.
+    fn buffer_page(&self, inode_id: u64, page_num: u64, data: Vec<u8>) {
+        if self.write_cache_bytes == 0 {
+            return self.write_page(inode_id, page_num, &data);
+        }
+
+        let mut dirty = self.dirty.lock().unwrap();
+        dirty.insert(inode_id, page_num, data);
+        if dirty.bytes() > self.write_cache_bytes {
+            for ((inode_id, page_num), data) in dirty.take_all() {
+                self.store_page(inode_id, page_num, &data);
+            }
+        }
+    }
+
+    fn store_page(&self, inode_id: u64, page_num: u64, data: &[u8]) {
         let mut page_data = data.to_vec();
-        if page_data.len() < PAGE_SIZE {
-            page_data.resize(PAGE_SIZE, 0);
+        if page_data.len() < self.page_size {
+            page_data.resize(self.page_size, 0);
+        }
+        let stored = encode_page(self.compression, &page_data);
+        self.kv.set(&keys::page(inode_id, page_num), &stored);
+        self.prefetched.lock().unwrap().remove(inode_id, page_num);
+    }
+
+    fn flush_inode(&self, inode_id: u64) {
+        // Hold the lock while storing so readers never miss a page that has
+        // left the cache but not yet reached the backend.
+        let mut dirty = self.dirty.lock().unwrap();
+        for (page_num, data) in dirty.take_inode_from(inode_id, 0) {
+            self.store_page(inode_id, page_num, &data);
         }
-        self.kv.set(&keys::page(inode_id, page_num), &page_data);
+    }
+
+    /// Write every dirty page back to the KV backend.
+    pub fn flush(&self) {
+        let mut dirty = self.dirty.lock().unwrap();
+        for ((inode_id, page_num), data) in dirty.take_all() {
+            self.store_page(inode_id, page_num, &data);
+        }
+    }
+
+    /// Write the dirty pages of the file behind `handle` back to the backend.
+    pub fn fsync(&self, handle: u64) -> FsResult<()> {
+        let inode_id = self
+            .handles
+            .lock()
+            .unwrap()
+            .get(&handle)
+            .map(|(inode_id, _, _, _)| *inode_id)
+            .ok_or_else(|| FsError::invalid_handle(handle))?;
+        self.flush_inode(inode_id);
+        Ok(())
     }
 
     fn delete_pages(&self, inode_id: u64) {
+        self.delete_pages_from(inode_id, 0);
+    }
+
+    /// Delete the pages of `inode_id` numbered `first_page` and up. Only
+    /// pages actually stored are visited, so cutting a sparse file is cheap.
+    fn delete_pages_from(&self, inode_id: u64, first_page: u64) {
+        self.dirty
+            .lock()
+            .unwrap()
+            .take_inode_from(inode_id, first_page);
         let prefix = keys::page_prefix(inode_id);
-        let pages: Vec<_> = self.kv.scan(&prefix).into_iter().map(|(k, _)| k).collect();
-        for key in pages {
+        for (key, stored) in self.kv.scan(&prefix) {
+            let page_num = key
+                .get(prefix.len()..)
+                .and_then(|num| num.try_into().ok())
+                .map(u64::from_be_bytes);
+            if page_num.is_some_and(|page_num| page_num < first_page) {
+                continue;
+            }
+            if let Some(block_id) = decode_page_ref(&stored) {
+                self.release_block(block_id);
+            }
             self.kv.delete(&key);
         }
+        self.prefetched.lock().unwrap().remove_inode(inode_id);
+    }
+
+    /// The shared block behind page `page_num` of `inode`, if the page is
+    /// still a reference to one.
+    fn shared_block(&self, inode: &Inode, page_num: u64) -> Option<u64> {
+        if !inode.shared {
+            return None;
+        }
+        let stored = self.kv.get(&keys::page(inode.id, page_num))?;
+        decode_page_ref(&stored)
+    }
+
+    fn block_refs(&self, block_id: u64) -> u64 {
+        self.kv
+            .get(&keys::block_refs(block_id))
+            .and_then(|data| Some(u64::from_be_bytes(data.try_into().ok()?)))
+            .unwrap_or(0)
     }
 
-    pub(crate) fn resolve_path(&self, path: &str) -> FsResult<(u64, Inode)> {
+    fn retain_block(&self, block_id: u64) {
+        let refs = self.block_refs(block_id) + 1;
+        self.kv
+            .set(&keys::block_refs(block_id), &refs.to_be_bytes());
+    }
+
+    /// Drop one reference to a shared block, deleting it with the last one.
+    fn release_block(&self, block_id: u64) {
+        let refs = self.block_refs(block_id).saturating_sub(1);
+        if refs > 0 {
+            self.kv
+                .set(&keys::block_refs(block_id), &refs.to_be_bytes());
+            return;
+        }
+
+        self.kv.delete(&keys::block(block_id));
+        self.kv.delete(&keys::block_refs(block_id));
+        let _ = self.update_superblock(|sb| {
+            sb.shared_pages = sb.shared_pages.saturating_sub(1);
+            Ok(())
+        });
+    }
+
+    /// Record the tree at `path` as a snapshot and return its id. Every page
+    /// below `path` moves into a shared block, so the snapshot costs one
+    /// reference per page rather than a copy; later writes to either side
+    /// copy just the pages they touch.
+    pub fn snapshot(&self, path: &str) -> FsResult<u64> {
         let path = self.normalize_path(path);
-        if path == "/" {
-            let inode = self
-                .load_inode(ROOT_INODE)
-                .ok_or_else(|| FsError::internal("root inode missing"))?;
-            return Ok((ROOT_INODE, inode));
+        let (inode_id, inode) = self.resolve_path(&path, true)?;
+
+        let (root, snapshot_id) = self.update_superblock(|sb| {
+            let root = self.snapshot_node(inode_id, inode, sb);
+            let snapshot_id = sb.next_snapshot;
+            sb.next_snapshot += 1;
+            Ok((root, snapshot_id))
+        })?;
+
+        let data = serde_json::to_vec(&root).unwrap();
+        self.kv.set(&keys::snapshot(snapshot_id), &data);
+        Ok(snapshot_id)
+    }
+
+    fn snapshot_node(&self, inode_id: u64, mut inode: Inode, sb: &mut Superblock) -> SnapshotNode {
+        let mut pages = Vec::new();
+        let mut children = BTreeMap::new();
+
+        match inode.inode_type {
+            InodeType::File => {
+                self.flush_inode(inode_id);
+                let prefix = keys::page_prefix(inode_id);
+                for (key, stored) in self.kv.scan(&prefix) {
+                    let Ok(page_num) = key[prefix.len()..].try_into().map(u64::from_be_bytes)
+                    else {
+                        continue;
+                    };
+                    let block_id = match decode_page_ref(&stored) {
+                        Some(block_id) => block_id,
+                        None => {
+                            let block_id = sb.next_block;
+                            sb.next_block += 1;
+                            sb.shared_pages += 1;
+                            self.kv.set(&keys::block(block_id), &stored);
+                            self.retain_block(block_id);
+                            self.kv.set(&key, &encode_page_ref(block_id));
+                            block_id
+                        }
+                    };
+                    self.retain_block(block_id);
+                    pages.push((page_num, block_id));
+                }
+                if !inode.shared {
+                    inode.shared = true;
+                    self.save_inode(&inode);
+                }
+            }
+            InodeType::Directory => {
+                let entries = self.list_dir(inode_id);
+                let ids: Vec<u64> = entries.iter().map(|(_, id)| *id).collect();
+                let mut inodes = self.load_inodes(&ids);
+                for (name, child_id) in entries {
+                    if let Some(child) = inodes.remove(&child_id) {
+                        children.insert(name, self.snapshot_node(child_id, child, sb));
+                    }
+                }
+            }
+            InodeType::Symlink => {}
+        }
+
+        let prefix = keys::xattr_prefix(inode_id);
+        let xattrs = self
+            .kv
+            .scan(&prefix)
+            .into_iter()
+            .filter_map(|(key, value)| {
+                Some((String::from_utf8(key[prefix.len()..].to_vec()).ok()?, value))
+            })
+            .collect();
+
+        SnapshotNode {
+            inode,
+            pages,
+            xattrs,
+            children,
+        }
+    }
+
+    /// Recreate snapshot `snapshot_id` at `dest_path`, which must not exist.
+    /// The new files share the snapshot's pages until they are written.
+    /// Files hard linked within the snapshot come back as separate files.
+    pub fn clone_snapshot(&self, snapshot_id: u64, dest_path: &str) -> FsResult<()> {
+        let dest_path = self.normalize_path(dest_path);
+        let root = self.load_snapshot(snapshot_id)?;
+        let (parent_inode, name) = self.resolve_parent(&dest_path)?;
+        if self.lookup(parent_inode, &name).is_some() {
+            return Err(FsError::already_exists(&dest_path));
+        }
+
+        self.charge_pages(root.file_pages() as i64)?;
+        self.clone_node(&root, parent_inode, &name);
+        Ok(())
+    }
+
+    fn clone_node(&self, node: &SnapshotNode, parent_inode: u64, name: &str) {
+        let new_id = self.alloc_inode();
+        let mut inode = node.inode.clone();
+        inode.id = new_id;
+        inode.shared = !node.pages.is_empty();
+        if inode.is_directory() {
+            inode.nlink = 2;
+            inode.child_count = Some(0);
+        } else {
+            inode.nlink = 1;
+        }
+        self.save_inode(&inode);
+
+        for &(page_num, block_id) in &node.pages {
+            self.retain_block(block_id);
+            self.kv
+                .set(&keys::page(new_id, page_num), &encode_page_ref(block_id));
+        }
+        for (attr, value) in &node.xattrs {
+            self.kv.set(&keys::xattr(new_id, attr), value);
+        }
+        // Named last, once its pages and attributes are in place.
+        self.add_entry(parent_inode, name, new_id);
+
+        for (child_name, child) in &node.children {
+            self.clone_node(child, new_id, child_name);
+        }
+    }
+
+    /// Delete snapshot `snapshot_id`, freeing pages no file still shares.
+    pub fn delete_snapshot(&self, snapshot_id: u64) -> FsResult<()> {
+        let root = self.load_snapshot(snapshot_id)?;
+        for block_id in root.blocks() {
+            self.release_block(block_id);
+        }
+        self.kv.delete(&keys::snapshot(snapshot_id));
+        Ok(())
+    }
+
+    /// A provider serving snapshot `snapshot_id` as a filesystem rooted at
+    /// the snapshotted directory. Its files share the snapshot's pages, so
+    /// they keep their contents whatever happens to the live files, for as
+    /// long as the snapshot is not deleted. Changes made through the view
+    /// are kept in memory and never reach this provider's store.
+    pub fn snapshot_view(&self, snapshot_id: u64) -> FsResult<Self> {
+        let root = self.load_snapshot(snapshot_id)?;
+        if !root.inode.is_directory() {
+            return Err(FsError::not_directory(format!("snapshot {snapshot_id}")));
+        }
+        let kv = SnapshotKv::new(&root, &self.load_superblock(), self.kv.clone());
+        Ok(
+            Self::try_with_config(Box::new(kv), self.uid, self.gid, self.page_size)?
+                .with_read_ahead(self.read_ahead_pages),
+        )
+    }
+
+    fn load_snapshot(&self, snapshot_id: u64) -> FsResult<SnapshotNode> {
+        self.kv
+            .get(&keys::snapshot(snapshot_id))
+            .and_then(|data| serde_json::from_slice(&data).ok())
+            .ok_or_else(|| FsError::not_found(format!("snapshot {snapshot_id}")))
+    }
+
+    /// Fetch the `read_ahead_pages` pages starting at the one holding
+    /// `next_offset` into the prefetch cache, skipping any already there.
+    fn read_ahead(&self, inode_id: u64, next_offset: u64, file_size: u64) {
+        let first_page = next_offset / self.page_size as u64;
+        let end_page =
+            (first_page + self.read_ahead_pages as u64).min(self.pages_needed(file_size));
+
+        // Hold the lock across the fetch: writers drop a page from the cache
+        // only after storing its new contents, so a fetch can't race a write
+        // and leave a stale copy behind.
+        let mut prefetched = self.prefetched.lock().unwrap();
+        prefetched.discard_before(inode_id, first_page);
+        let wanted: Vec<u64> = (first_page..end_page)
+            .filter(|&page_num| !prefetched.contains(inode_id, page_num))
+            .collect();
+        if wanted.is_empty() {
+            return;
+        }
+
+        // One batched fetch, so a wide read-ahead costs a single round trip
+        // on backends that batch rather than a thread per page.
+        let fetched = load_pages(&*self.kv, inode_id, &wanted);
+        for (page_num, stored) in wanted.into_iter().zip(fetched) {
+            if let Some(page) = stored.and_then(|stored| decode_page(stored, self.page_size)) {
+                prefetched.insert(inode_id, page_num, page);
+            }
         }
+    }
+
+    /// Walk `path` from the root. Symlinks in intermediate components are
+    /// always followed; the final component is followed only when
+    /// `follow_final` is set. Absolute symlink targets are resolved from the
+    /// root of this filesystem.
+    pub(crate) fn resolve_path(&self, path: &str, follow_final: bool) -> FsResult<(u64, Inode)> {
+        let path = self.normalize_path(path);
 
-        let parts: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
-        let mut current_inode = ROOT_INODE;
+        // Components still to walk, in reverse so the next one is on top.
+        let mut pending: Vec<String> = path
+            .split('/')
+            .filter(|s| !s.is_empty())
+            .rev()
+            .map(String::from)
+            .collect();
+        // Inodes from the root down to the current directory.
+        let mut ancestors = vec![ROOT_INODE];
+        let mut links_followed = 0;
+
+        while let Some(part) = pending.pop() {
+            match part.as_str() {
+                "." => continue,
+                ".." => {
+                    if ancestors.len() > 1 {
+                        ancestors.pop();
+                    }
+                    continue;
+                }
+                _ => {}
+            }
 
-        for (i, part) in parts.iter().enumerate() {
+            let parent = *ancestors.last().unwrap();
             let child_inode = self
-                .lookup(current_inode, part)
+                .lookup(parent, &part)
+                .ok_or_else(|| FsError::not_found(&path))?;
+            let inode = self
+                .load_inode(child_inode)
                 .ok_or_else(|| FsError::not_found(&path))?;
 
-            if i < parts.len() - 1 {
-                let inode = self
-                    .load_inode(child_inode)
-                    .ok_or_else(|| FsError::not_found(&path))?;
-                if !inode.is_directory() {
-                    return Err(FsError::not_directory(*part));
+            if inode.is_symlink() && (follow_final || !pending.is_empty()) {
+                links_followed += 1;
+                if links_followed > MAX_SYMLINK_DEPTH {
+                    return Err(FsError::invalid_argument(format!(
+                        "too many levels of symbolic links: {path}"
+                    )));
                 }
+                let target = inode.symlink_target.unwrap_or_default();
+                if target.is_empty() {
+                    return Err(FsError::not_found(&path));
+                }
+                if target.starts_with('/') {
+                    ancestors.truncate(1);
+                }
+                pending.extend(
+                    target
+                        .split('/')
+                        .filter(|s| !s.is_empty())
+                        .rev()
+                        .map(String::from),
+                );
+                continue;
+            }
+
+            if !pending.is_empty() && !inode.is_directory() {
+                return Err(FsError::not_directory(part));
             }
-            current_inode = child_inode;
+            ancestors.push(child_inode);
         }
 
-        let inode = self
-            .load_inode(current_inode)
-            .ok_or_else(|| FsError::not_found(&path))?;
+        let current_inode = *ancestors.last().unwrap();
+        let inode = self.load_inode(current_inode).ok_or_else(|| {
+            if current_inode == ROOT_INODE {
+                FsError::internal("root inode missing")
+            } else {
+                FsError::not_found(&path)
+            }
+        })?;
         Ok((current_inode, inode))
     }
 
@@ -192,7 +1053,7 @@ impl PageFsProvider {
         let (parent, name) = path.rsplit_once('/').unwrap_or(("", &path));
         let parent_path = if parent.is_empty() { "/" } else { parent };
 
-        let (parent_inode, parent_node) = self.resolve_path(parent_path)?;
+        let (parent_inode, parent_node) = self.resolve_path(parent_path, true)?;
         if !parent_node.is_directory() {
             return Err(FsError::not_directory(parent_path));
         }
@@ -209,82 +1070,136 @@ impl PageFsProvider {
         }
     }
 
-    fn pages_needed(size: u64) -> u64 {
+    fn pages_needed(&self, size: u64) -> u64 {
         if size == 0 {
             0
         } else {
-            (size + PAGE_SIZE as u64 - 1) / PAGE_SIZE as u64
+            (size + self.page_size as u64 - 1) / self.page_size as u64
         }
     }
 
-    pub fn stat(&self, path: &str) -> FsResult<FileInfo> {
-        let path = self.normalize_path(path);
-        let (_, inode) = self.resolve_path(&path)?;
-
-        Ok(FileInfo {
-            path: path.clone(),
+    /// Files get a weak etag from their size and mtime: hashing the pages
+    /// on every stat would cost a full read.
+    fn file_info(&self, path: String, inode: &Inode) -> FileInfo {
+        let file_type = inode.file_type();
+        let mtime = timestamp_to_system_time(inode.mtime);
+        FileInfo {
+            path,
             size: inode.size,
-            file_type: if inode.is_directory() {
-                FileType::Directory
-            } else {
-                FileType::Regular
-            },
+            file_type,
             mode: inode.mode,
             uid: self.uid,
             gid: self.gid,
             atime: timestamp_to_system_time(inode.atime),
-            mtime: timestamp_to_system_time(inode.mtime),
+            mtime,
             ctime: timestamp_to_system_time(inode.ctime),
-            etag: String::new(),
-            symlink_target: None,
-        })
+            etag: if file_type == FileType::Regular {
+                metadata_etag(inode.size, mtime)
+            } else {
+                String::new()
+            },
+            symlink_target: inode.symlink_target.clone(),
+        }
+    }
+
+    /// Stat `path` without following a symlink in its final component.
+    pub fn stat(&self, path: &str) -> FsResult<FileInfo> {
+        let path = self.normalize_path(path);
+        let (_, inode) = self.resolve_path(&path, false)?;
+        Ok(self.file_info(path, &inode))
+    }
+
+    /// Create a symlink at `path` pointing at `target`.
+    pub fn symlink(&self, path: &str, target: &str) -> FsResult<()> {
+        let path = self.normalize_path(path);
+        if target.len() > MAX_SYMLINK_TARGET_LEN {
+            return Err(FsError::invalid_argument(format!(
+                "symlink target longer than {MAX_SYMLINK_TARGET_LEN} bytes: {path}"
+            )));
+        }
+        let (parent_inode, name) = self.resolve_parent(&path)?;
+        if self.lookup(parent_inode, &name).is_some() {
+            return Err(FsError::already_exists(&path));
+        }
+
+        let new_id = self.alloc_inode();
+        self.link_new_inode(
+            parent_inode,
+            &name,
+            &Inode::new_symlink(new_id, target.to_string()),
+        );
+        Ok(())
     }
 
     pub fn open(&self, path: &str, flags: OpenFlags) -> FsResult<(Handle, FileInfo)> {
         let path = self.normalize_path(path);
+        let follow = !flags.symlink;
 
         let inode_id = if flags.create {
-            match self.resolve_path(&path) {
+            let _creating = self.create_lock.lock().unwrap();
+            match self.resolve_path(&path, follow) {
+                Ok(_) if flags.exclusive => return Err(FsError::already_exists(&path)),
                 Ok((id, _)) => id,
                 Err(FsError::NotFound(_)) => {
                     let (parent_inode, name) = self.resolve_parent(&path)?;
+                    if self.lookup(parent_inode, &name).is_some() {
+                        // A dangling symlink: don't replace the link itself.
+                        return Err(FsError::already_exists(&path));
+                    }
+                    let is_file = !flags.directory && !flags.symlink;
+                    if is_file {
+                        self.charge_pages(1)?;
+                    }
                     let new_id = self.alloc_inode();
 
                     let inode = if flags.directory {
-                        Inode::new_directory(new_id, 0o755)
+                        Inode::new_directory(new_id, self.dir_mode & !self.umask)
+                    } else if flags.symlink {
+                        Inode::new_symlink(new_id, String::new())
                     } else {
-                        let mut f = Inode::new_file(new_id, 0o644);
+                        let mut f = Inode::new_file(new_id, self.file_mode & !self.umask);
                         f.page_count = 1;
-                        self.write_page(new_id, 0, &vec![0u8; PAGE_SIZE]);
+                        self.write_page(new_id, 0, &vec![0u8; self.page_size]);
                         f
                     };
 
-                    self.save_inode(&inode);
-                    self.link(parent_inode, &name, new_id);
+                    self.link_new_inode(parent_inode, &name, &inode);
 
                     new_id
                 }
                 Err(e) => return Err(e),
             }
         } else {
-            let (id, _) = self.resolve_path(&path)?;
+            let (id, _) = self.resolve_path(&path, follow)?;
             id
         };
 
+        let mut inode = self
+            .load_inode(inode_id)
+            .ok_or_else(|| FsError::not_found(&path))?;
+
         if flags.truncate {
-            if let Some(mut inode) = self.load_inode(inode_id) {
-                if !inode.is_directory() {
+            match inode.inode_type {
+                InodeType::File => {
+                    self.charge_pages(1 - inode.page_count as i64)?;
                     self.delete_pages(inode_id);
                     inode.size = 0;
                     inode.page_count = 1;
-                    self.write_page(inode_id, 0, &vec![0u8; PAGE_SIZE]);
+                    self.write_page(inode_id, 0, &vec![0u8; self.page_size]);
+                    inode.touch_mtime();
+                    self.save_inode(&inode);
+                }
+                InodeType::Symlink => {
+                    inode.symlink_target = Some(String::new());
+                    inode.size = 0;
                     inode.touch_mtime();
                     self.save_inode(&inode);
                 }
+                InodeType::Directory => {}
             }
         }
 
-        let info = self.stat(&path)?;
+        let info = self.file_info(path.clone(), &inode);
 
         let mut next = self.next_handle.lock().unwrap();
         let handle_id = *next;
@@ -293,14 +1208,14 @@ impl PageFsProvider {
         self.handles
             .lock()
             .unwrap()
-            .insert(handle_id, (inode_id, path, flags));
+            .insert(handle_id, (inode_id, path, flags, None));
 
         Ok((Handle::new(handle_id), info))
     }
 
     pub fn read(&self, handle: u64, offset: u64, size: usize) -> FsResult<Bytes> {
         let handles = self.handles.lock().unwrap();
-        let (inode_id, path, _) = handles
+        let (inode_id, path, _, last_read_end) = handles
             .get(&handle)
             .ok_or_else(|| FsError::invalid_handle(handle))?
             .clone();
@@ -314,6 +1229,13 @@ impl PageFsProvider {
             return Err(FsError::is_directory(&path));
         }
 
+        if let Some(target) = &inode.symlink_target {
+            let target = target.as_bytes();
+            let start = (offset as usize).min(target.len());
+            let end = start.saturating_add(size).min(target.len());
+            return Ok(Bytes::copy_from_slice(&target[start..end]));
+        }
+
         let file_size = inode.size;
         if offset >= file_size {
             return Ok(Bytes::new());
@@ -327,12 +1249,17 @@ impl PageFsProvider {
         let mut bytes_read = 0usize;
         let mut current_offset = offset as usize;
 
+        // Fetch every page the read touches up front, so that a backend
+        // with per-request latency pays it once rather than once per page.
+        let first_page = (read_start / self.page_size) as u64;
+        let end_page = read_end.div_ceil(self.page_size) as u64;
+        let mut pages = self.read_pages(inode_id, first_page..end_page).into_iter();
+
         while bytes_read < total_to_read {
-            let page_num = (current_offset / PAGE_SIZE) as u64;
-            let page_offset = current_offset % PAGE_SIZE;
-            let bytes_in_page = (PAGE_SIZE - page_offset).min(total_to_read - bytes_read);
+            let page_offset = current_offset % self.page_size;
+            let bytes_in_page = (self.page_size - page_offset).min(total_to_read - bytes_read);
 
-            if let Some(page_data) = self.read_page(inode_id, page_num) {
+            if let Some(page_data) = pages.next().flatten() {
                 let available = page_data.len().saturating_sub(page_offset);
                 let to_copy = bytes_in_page.min(available);
                 if to_copy > 0 {
@@ -345,6 +1272,15 @@ impl PageFsProvider {
             current_offset += bytes_in_page;
         }
 
+        // A read starting where the previous one ended is sequential; any
+        // other offset, including a seek backwards, resets the detector.
+        if let Some(entry) = self.handles.lock().unwrap().get_mut(&handle) {
+            entry.3 = Some(read_end as u64);
+        }
+        if self.read_ahead_pages > 0 && last_read_end == Some(offset) {
+            self.read_ahead(inode_id, read_end as u64, file_size);
+        }
+
         inode.touch_atime();
         self.save_inode(&inode);
 
@@ -353,7 +1289,7 @@ impl PageFsProvider {
 
     pub fn write(&self, handle: u64, offset: u64, data: &[u8]) -> FsResult<usize> {
         let handles = self.handles.lock().unwrap();
-        let (inode_id, path, flags) = handles
+        let (inode_id, path, flags, _) = handles
             .get(&handle)
             .ok_or_else(|| FsError::invalid_handle(handle))?
             .clone();
@@ -367,41 +1303,60 @@ impl PageFsProvider {
             return Err(FsError::is_directory(&path));
         }
 
+        if inode.is_symlink() {
+            return self.write_symlink_target(inode, &path, offset, flags, data);
+        }
+
         let write_offset = if flags.append {
             inode.size as usize
         } else {
             offset as usize
         };
 
+        let new_size = (write_offset + data.len()) as u64;
+        let new_page_count = if new_size > inode.size {
+            self.pages_needed(new_size).max(1)
+        } else {
+            inode.page_count
+        };
+        self.charge_pages(new_page_count as i64 - inode.page_count as i64)?;
+
         let mut bytes_written = 0usize;
         let mut current_offset = write_offset;
 
         while bytes_written < data.len() {
-            let page_num = (current_offset / PAGE_SIZE) as u64;
-            let page_offset = current_offset % PAGE_SIZE;
-            let bytes_to_write = (PAGE_SIZE - page_offset).min(data.len() - bytes_written);
+            let page_num = (current_offset / self.page_size) as u64;
+            let page_offset = current_offset % self.page_size;
+            let bytes_to_write = (self.page_size - page_offset).min(data.len() - bytes_written);
+            let shared_block = self.shared_block(&inode, page_num);
 
             let mut page_data = self
                 .read_page(inode_id, page_num)
-                .unwrap_or_else(|| vec![0u8; PAGE_SIZE]);
+                .unwrap_or_else(|| vec![0u8; self.page_size]);
 
-            if page_data.len() < PAGE_SIZE {
-                page_data.resize(PAGE_SIZE, 0);
+            if page_data.len() < self.page_size {
+                page_data.resize(self.page_size, 0);
             }
 
             page_data[page_offset..page_offset + bytes_to_write]
                 .copy_from_slice(&data[bytes_written..bytes_written + bytes_to_write]);
 
-            self.write_page(inode_id, page_num, &page_data);
+            if let Some(block_id) = shared_block {
+                // Copy on write: the file gets its own copy of the page and
+                // stops referring to the shared one.
+                self.write_page(inode_id, page_num, &page_data);
+                self.release_block(block_id);
+            } else {
+                self.buffer_page(inode_id, page_num, page_data);
+            }
 
             bytes_written += bytes_to_write;
             current_offset += bytes_to_write;
         }
 
-        let new_size = (write_offset + data.len()) as u64;
         if new_size > inode.size {
             inode.size = new_size;
-            inode.page_count = Self::pages_needed(new_size).max(1);
+            inode.page_count = new_page_count;
         }
         inode.touch_mtime();
         self.save_inode(&inode);
@@ -409,56 +1364,123 @@ impl PageFsProvider {
         Ok(data.len())
     }
 
+    /// Writes through a symlink handle edit the link target in place.
+    fn write_symlink_target(
+        &self,
+        mut inode: Inode,
+        path: &str,
+        offset: u64,
+        flags: OpenFlags,
+        data: &[u8],
+    ) -> FsResult<usize> {
+        let mut target = inode.symlink_target.take().unwrap_or_default().into_bytes();
+        let start = if flags.append {
+            Some(target.len())
+        } else {
+            usize::try_from(offset).ok()
+        };
+        let end = start
+            .and_then(|start| start.checked_add(data.len()))
+            .filter(|&end| end <= MAX_SYMLINK_TARGET_LEN)
+            .ok_or_else(|| {
+                FsError::invalid_argument(format!(
+                    "symlink target longer than {MAX_SYMLINK_TARGET_LEN} bytes: {path}"
+                ))
+            })?;
+        let start = end - data.len();
+        if target.len() < end {
+            target.resize(end, 0);
+        }
+        target[start..end].copy_from_slice(data);
+
+        let target = String::from_utf8(target).map_err(|_| {
+            FsError::invalid_argument(format!("symlink target is not UTF-8: {path}"))
+        })?;
+        inode.size = target.len() as u64;
+        inode.symlink_target = Some(target);
+        inode.touch_mtime();
+        self.save_inode(&inode);
+
+        Ok(data.len())
+    }
+
     pub fn close(&self, handle: u64) -> FsResult<()> {
-        self.handles
+        let (inode_id, _, _, _) = self
+            .handles
             .lock()
             .unwrap()
             .remove(&handle)
-            .map(|_| ())
-            .ok_or_else(|| FsError::invalid_handle(handle))
+            .ok_or_else(|| FsError::invalid_handle(handle))?;
+        self.flush_inode(inode_id);
+        self.prefetched.lock().unwrap().remove_inode(inode_id);
+        Ok(())
     }
 
     pub fn readdir(&self, path: &str) -> FsResult<Vec<FileInfo>> {
+        let mut result = Vec::new();
+        let mut cursor = None;
+        loop {
+            let page = self.readdir_at(path, cursor.as_deref(), READDIR_BATCH)?;
+            result.extend(page.entries);
+            match page.next_cursor {
+                Some(next) => cursor = Some(next),
+                None => return Ok(result),
+            }
+        }
+    }
+
+    /// List up to `max` entries of `path` in name order, resuming after the
+    /// entry named by `cursor`. Each page is a single bounded backend scan.
+    pub fn readdir_at(&self, path: &str, cursor: Option<&str>, max: usize) -> FsResult<DirPage> {
+        if max == 0 {
+            return Err(FsError::invalid_argument(
+                "readdir page size must be positive",
+            ));
+        }
+
         let path = self.normalize_path(path);
-        let (inode_id, inode) = self.resolve_path(&path)?;
+        let (inode_id, inode) = self.resolve_path(&path, true)?;
 
         if !inode.is_directory() {
             return Err(FsError::not_directory(&path));
         }
 
-        let entries = self.list_dir(inode_id);
-        let mut result = Vec::with_capacity(entries.len());
+        let prefix = keys::dir_prefix(inode_id);
+        let start_after = match cursor {
+            Some(name) => keys::dir_entry(inode_id, name),
+            None => prefix.clone(),
+        };
+        let batch = self.kv.scan_after(&prefix, &start_after, max);
+        let exhausted = batch.len() < max;
 
-        for (name, child_inode_id) in entries {
-            if let Some(child_inode) = self.load_inode(child_inode_id) {
+        let children: Vec<(String, u64)> = batch
+            .into_iter()
+            .filter_map(|(key, value)| {
+                let name = String::from_utf8(key[prefix.len()..].to_vec()).ok()?;
+                Some((name, u64::from_be_bytes(value.try_into().ok()?)))
+            })
+            .collect();
+        let ids: Vec<u64> = children.iter().map(|(_, id)| *id).collect();
+        let inodes = self.load_inodes(&ids);
+
+        let mut entries = Vec::with_capacity(children.len());
+        let mut last_name = None;
+        for (name, child_inode_id) in children {
+            if let Some(child_inode) = inodes.get(&child_inode_id) {
                 let child_path = if path == "/" {
                     format!("/{}", name)
                 } else {
                     format!("{}/{}", path, name)
                 };
-
-                result.push(FileInfo {
-                    path: child_path,
-                    size: child_inode.size,
-                    file_type: if child_inode.is_directory() {
-                        FileType::Directory
-                    } else {
-                        FileType::Regular
-                    },
-                    mode: child_inode.mode,
-                    uid: self.uid,
-                    gid: self.gid,
-                    atime: timestamp_to_system_time(child_inode.atime),
-                    mtime: timestamp_to_system_time(child_inode.mtime),
-                    ctime: timestamp_to_system_time(child_inode.ctime),
-                    etag: String::new(),
-                    symlink_target: None,
-                });
+                entries.push(self.file_info(child_path, child_inode));
             }
+            last_name = Some(name);
         }
 
-        result.sort_by(|a, b| a.path.cmp(&b.path));
-        Ok(result)
+        Ok(DirPage {
+            entries,
+            next_cursor: if exhausted { None } else { last_name },
+        })
     }
 
     pub fn remove(&self, path: &str) -> FsResult<()> {
@@ -467,32 +1489,89 @@ impl PageFsProvider {
             return Err(FsError::permission_denied("cannot remove root"));
         }
 
-        let (inode_id, inode) = self.resolve_path(&path)?;
+        let (inode_id, inode) = self.resolve_path(&path, false)?;
 
+        if inode.is_directory() && self.child_count(inode_id, &inode) > 0 {
+            return Err(FsError::directory_not_empty(&path));
+        }
+
+        let (parent_inode, name) = self.resolve_parent(&path)?;
+        self.remove_entry(parent_inode, &name);
         if inode.is_directory() {
-            let children = self.list_dir(inode_id);
-            if !children.is_empty() {
-                return Err(FsError::directory_not_empty(&path));
-            }
+            self.delete_inode(inode_id);
         } else {
-            self.delete_pages(inode_id);
+            self.drop_link(inode);
         }
 
-        let (parent_inode, name) = self.resolve_parent(&path)?;
-        self.unlink(parent_inode, &name);
-        self.delete_inode(inode_id);
+        Ok(())
+    }
+
+    /// Add `new_path` as another directory entry for the file at
+    /// `existing_path`. Directories cannot be hard linked.
+    pub fn link(&self, existing_path: &str, new_path: &str) -> FsResult<()> {
+        let existing_path = self.normalize_path(existing_path);
+        let new_path = self.normalize_path(new_path);
+
+        let (inode_id, mut inode) = self.resolve_path(&existing_path, false)?;
+        if inode.is_directory() {
+            return Err(FsError::permission_denied(format!(
+                "cannot hard link directory: {existing_path}"
+            )));
+        }
+
+        let (parent_inode, name) = self.resolve_parent(&new_path)?;
+        if self.lookup(parent_inode, &name).is_some() {
+            return Err(FsError::already_exists(&new_path));
+        }
+
+        inode.nlink += 1;
+        inode.touch_ctime();
+        self.save_inode(&inode);
+        self.add_entry(parent_inode, &name, inode_id);
 
         Ok(())
     }
 
+    /// Drop one name of a non-directory inode, deleting the inode and its
+    /// pages once no names are left.
+    fn drop_link(&self, mut inode: Inode) {
+        inode.nlink = inode.nlink.saturating_sub(1);
+        if inode.nlink > 0 {
+            inode.touch_ctime();
+            self.save_inode(&inode);
+            return;
+        }
+
+        if inode.inode_type == InodeType::File {
+            self.delete_pages(inode.id);
+            // Refunds never fail.
+            let _ = self.charge_pages(-(inode.page_count as i64));
+        }
+        self.delete_inode(inode.id);
+    }
+
     pub fn wstat(&self, path: &str, changes: &StatChanges) -> FsResult<()> {
         let path = self.normalize_path(path);
 
+        if let Some(target) = &changes.symlink_target {
+            return self.symlink(&path, target);
+        }
+
+        // Renaming through wstat predates the rename operation and is kept
+        // for callers that have not moved over yet.
         if let Some(new_name) = &changes.name {
-            return self.rename(&path, new_name);
+            let new_path = if new_name.starts_with('/') {
+                new_name.clone()
+            } else {
+                match self.parent_path(&path).as_deref() {
+                    None | Some("/") => format!("/{new_name}"),
+                    Some(parent) => format!("{parent}/{new_name}"),
+                }
+            };
+            return self.rename(&path, &new_path);
         }
 
-        let (inode_id, mut inode) = self.resolve_path(&path)?;
+        let (inode_id, mut inode) = self.resolve_path(&path, true)?;
 
         if let Some(m) = changes.mode {
             inode.mode = m;
@@ -504,27 +1583,31 @@ impl PageFsProvider {
             }
 
             let old_page_count = inode.page_count;
-            let new_page_count = Self::pages_needed(new_size).max(1);
-
-            if new_page_count < old_page_count {
-                for page_num in new_page_count..old_page_count {
-                    self.kv.delete(&keys::page(inode_id, page_num));
-                }
-            } else if new_page_count > old_page_count {
-                for page_num in old_page_count..new_page_count {
-                    self.write_page(inode_id, page_num, &vec![0u8; PAGE_SIZE]);
-                }
+            let kept_pages = self.pages_needed(new_size);
+            let new_page_count = kept_pages.max(1);
+            self.charge_pages(new_page_count as i64 - old_page_count as i64)?;
+
+            // Growing only moves the end of the file: the pages past the old
+            // end are not stored and read back as zeros until written. An
+            // empty file still counts one page but keeps none stored, so
+            // truncating to 0 drops page 0 as well.
+            if kept_pages < old_page_count {
+                self.delete_pages_from(inode_id, kept_pages);
             }
 
             if new_size < inode.size {
                 let last_page = new_page_count - 1;
-                let page_offset = (new_size % PAGE_SIZE as u64) as usize;
+                let page_offset = (new_size % self.page_size as u64) as usize;
                 if page_offset > 0 {
                     if let Some(mut page_data) = self.read_page(inode_id, last_page) {
-                        for i in page_offset..PAGE_SIZE {
+                        let shared_block = self.shared_block(&inode, last_page);
+                        for i in page_offset..self.page_size {
                             page_data[i] = 0;
                         }
                         self.write_page(inode_id, last_page, &page_data);
+                        if let Some(block_id) = shared_block {
+                            self.release_block(block_id);
+                        }
                     }
                 }
             }
@@ -548,50 +1631,116 @@ impl PageFsProvider {
         Ok(())
     }
 
-    fn rename(&self, old_path: &str, new_name: &str) -> FsResult<()> {
-        let new_path = if new_name.starts_with('/') {
-            self.normalize_path(new_name)
-        } else {
-            let parent = self
-                .parent_path(old_path)
-                .unwrap_or_else(|| "/".to_string());
-            if parent == "/" {
-                self.normalize_path(&format!("/{new_name}"))
-            } else {
-                self.normalize_path(&format!("{parent}/{new_name}"))
-            }
-        };
+    /// Move `old_path` to `new_path`, replacing an existing file or empty
+    /// directory there.
+    pub fn rename(&self, old_path: &str, new_path: &str) -> FsResult<()> {
+        let old_path = self.normalize_path(old_path);
+        let new_path = self.normalize_path(new_path);
 
         if old_path == new_path {
             return Ok(());
         }
+        if old_path == "/" {
+            return Err(FsError::invalid_argument(
+                "cannot rename the root directory",
+            ));
+        }
+        if new_path.starts_with(&format!("{old_path}/")) {
+            return Err(FsError::invalid_argument(format!(
+                "cannot move {old_path} into its own subdirectory {new_path}"
+            )));
+        }
 
-        let (src_inode_id, src_inode) = self.resolve_path(old_path)?;
+        let (src_inode_id, src_inode) = self.resolve_path(&old_path, false)?;
+        let (old_parent_id, old_name) = self.resolve_parent(&old_path)?;
+        let (new_parent_id, new_entry_name) = self.resolve_parent(&new_path)?;
 
-        if let Ok((dst_inode_id, dst_inode)) = self.resolve_path(&new_path) {
+        if let Ok((dst_inode_id, dst_inode)) = self.resolve_path(&new_path, false) {
+            if dst_inode_id == src_inode_id {
+                // Both names already refer to the same inode.
+                return Ok(());
+            }
             if dst_inode.is_directory() {
                 if !src_inode.is_directory() {
                     return Err(FsError::is_directory(&new_path));
                 }
-                let entries = self.list_dir(dst_inode_id);
-                if !entries.is_empty() {
+                if self.child_count(dst_inode_id, &dst_inode) > 0 {
                     return Err(FsError::directory_not_empty(&new_path));
                 }
             } else if src_inode.is_directory() {
                 return Err(FsError::not_directory(&new_path));
             }
-            if !dst_inode.is_directory() {
-                self.delete_pages(dst_inode_id);
+            self.remove_entry(new_parent_id, &new_entry_name);
+            if dst_inode.is_directory() {
+                self.delete_inode(dst_inode_id);
+            } else {
+                self.drop_link(dst_inode);
             }
-            self.delete_inode(dst_inode_id);
         }
 
-        let (old_parent_id, old_name) = self.resolve_parent(old_path)?;
-        self.unlink(old_parent_id, &old_name);
+        self.add_entry(new_parent_id, &new_entry_name, src_inode_id);
+        self.remove_entry(old_parent_id, &old_name);
 
-        let (new_parent_id, new_entry_name) = self.resolve_parent(&new_path)?;
-        self.link(new_parent_id, &new_entry_name, src_inode_id);
+        Ok(())
+    }
 
+    /// Value of the extended attribute `name` on `path`.
+    pub fn getxattr(&self, path: &str, name: &str) -> FsResult<Vec<u8>> {
+        let path = self.normalize_path(path);
+        let (inode_id, _) = self.resolve_path(&path, true)?;
+        self.kv
+            .get(&keys::xattr(inode_id, name))
+            .ok_or_else(|| FsError::not_found(format!("{path}: no attribute {name}")))
+    }
+
+    /// Create or replace the extended attribute `name` on `path`.
+    pub fn setxattr(&self, path: &str, name: &str, value: &[u8]) -> FsResult<()> {
+        if name.is_empty() || name.len() > MAX_XATTR_NAME_LEN || name.contains('\0') {
+            return Err(FsError::invalid_argument(format!(
+                "invalid attribute name: {name:?}"
+            )));
+        }
+        if value.len() > MAX_XATTR_VALUE_SIZE {
+            return Err(FsError::invalid_argument(format!(
+                "attribute value of {} bytes exceeds the {MAX_XATTR_VALUE_SIZE}-byte limit",
+                value.len()
+            )));
+        }
+
+        let path = self.normalize_path(path);
+        let (inode_id, mut inode) = self.resolve_path(&path, true)?;
+        self.kv.set(&keys::xattr(inode_id, name), value);
+        inode.touch_ctime();
+        self.save_inode(&inode);
+        Ok(())
+    }
+
+    /// Names of the extended attributes on `path`, in name order.
+    pub fn listxattr(&self, path: &str) -> FsResult<Vec<String>> {
+        let path = self.normalize_path(path);
+        let (inode_id, _) = self.resolve_path(&path, true)?;
+        let prefix = keys::xattr_prefix(inode_id);
+        let mut names: Vec<String> = self
+            .kv
+            .scan(&prefix)
+            .into_iter()
+            .filter_map(|(key, _)| String::from_utf8(key[prefix.len()..].to_vec()).ok())
+            .collect();
+        names.sort();
+        Ok(names)
+    }
+
+    /// Remove the extended attribute `name` from `path`.
+    pub fn removexattr(&self, path: &str, name: &str) -> FsResult<()> {
+        let path = self.normalize_path(path);
+        let (inode_id, mut inode) = self.resolve_path(&path, true)?;
+        let key = keys::xattr(inode_id, name);
+        if self.kv.get(&key).is_none() {
+            return Err(FsError::not_found(format!("{path}: no attribute {name}")));
+        }
+        self.kv.delete(&key);
+        inode.touch_ctime();
+        self.save_inode(&inode);
         Ok(())
     }
 
@@ -607,3 +1756,9 @@ impl PageFsProvider {
         }
     }
 }
+
+impl Drop for PageFsProvider {
+    fn drop(&mut self) {
+        self.flush();
+    }
+}
@@ -0,0 +1,193 @@
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{keys, InMemoryKv, Inode, InodeType, KvBackend, Superblock, ROOT_INODE};
+
+/// First byte of a page value that refers to a shared block instead of
+/// holding page data. Raw pages are a full page long and compressed ones
+/// start with their own magic byte, so neither can be mistaken for a
+/// reference.
+const PAGE_REF_MAGIC: u8 = 0xCB;
+
+/// magic (1) + block id (8, big-endian)
+const PAGE_REF_LEN: usize = 9;
+
+/// The page value pointing a file's page at shared block `block_id`.
+pub fn encode_page_ref(block_id: u64) -> Vec<u8> {
+    let mut value = Vec::with_capacity(PAGE_REF_LEN);
+    value.push(PAGE_REF_MAGIC);
+    value.extend_from_slice(&block_id.to_be_bytes());
+    value
+}
+
+/// The shared block a stored page value refers to, if it is a reference.
+pub fn decode_page_ref(value: &[u8]) -> Option<u64> {
+    if value.len() != PAGE_REF_LEN || value[0] != PAGE_REF_MAGIC {
+        return None;
+    }
+    Some(u64::from_be_bytes(value[1..].try_into().ok()?))
+}
+
+/// Fetch the stored (still encoded) contents of a page, following a
+/// reference to a shared block.
+pub fn load_page(kv: &dyn KvBackend, inode_id: u64, page_num: u64) -> Option<Vec<u8>> {
+    let stored = kv.get(&keys::page(inode_id, page_num))?;
+    match decode_page_ref(&stored) {
+        Some(block_id) => kv.get(&keys::block(block_id)),
+        None => Some(stored),
+    }
+}
+
+/// Like [`load_page`] for several pages of one file, fetching the page
+/// values in one batch and then the shared blocks they refer to in another.
+pub fn load_pages(kv: &dyn KvBackend, inode_id: u64, page_nums: &[u64]) -> Vec<Option<Vec<u8>>> {
+    let page_keys: Vec<Vec<u8>> = page_nums
+        .iter()
+        .map(|&page_num| keys::page(inode_id, page_num))
+        .collect();
+    let mut stored = kv.get_many(&page_keys);
+
+    let refs: Vec<(usize, u64)> = stored
+        .iter()
+        .enumerate()
+        .filter_map(|(i, value)| Some((i, decode_page_ref(value.as_deref()?)?)))
+        .collect();
+    if !refs.is_empty() {
+        let block_keys: Vec<Vec<u8>> = refs.iter().map(|&(_, block)| keys::block(block)).collect();
+        for ((i, _), block) in refs.into_iter().zip(kv.get_many(&block_keys)) {
+            stored[i] = block;
+        }
+    }
+    stored
+}
+
+/// One file, directory or symlink of a snapshotted tree. Files list the
+/// shared block behind each of their pages; the snapshot holds a reference
+/// to every one of them until it is deleted.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SnapshotNode {
+    pub inode: Inode,
+    /// `(page number, block id)` pairs, in page order.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub pages: Vec<(u64, u64)>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub xattrs: BTreeMap<String, Vec<u8>>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub children: BTreeMap<String, SnapshotNode>,
+}
+
+impl SnapshotNode {
+    /// Every block reference held by this node and the nodes below it.
+    pub fn blocks(&self) -> Vec<u64> {
+        let mut blocks: Vec<u64> = self.pages.iter().map(|(_, block)| *block).collect();
+        for child in self.children.values() {
+            blocks.extend(child.blocks());
+        }
+        blocks
+    }
+
+    /// Pages of every file in this node's subtree, as charged against the
+    /// filesystem's capacity once the tree is cloned.
+    pub fn file_pages(&self) -> u64 {
+        let own = match self.inode.inode_type {
+            InodeType::File => self.inode.page_count,
+            _ => 0,
+        };
+        own + self.children.values().map(Self::file_pages).sum::<u64>()
+    }
+}
+
+/// The store behind a snapshot view: the snapshot's tree laid out as a
+/// filesystem of its own, rooted at the snapshotted directory, whose pages
+/// refer to the snapshot's shared blocks in the live store.
+///
+/// Only blocks are read from the live store. Anything written goes to the
+/// view's own copy of the metadata and never reaches the live store.
+pub struct SnapshotKv {
+    meta: InMemoryKv,
+    live: Arc<dyn KvBackend>,
+}
+
+impl SnapshotKv {
+    pub fn new(root: &SnapshotNode, superblock: &Superblock, live: Arc<dyn KvBackend>) -> Self {
+        let meta = InMemoryKv::new();
+        meta.set(
+            &keys::superblock(),
+            &serde_json::to_vec(superblock).unwrap(),
+        );
+        lay_out(&meta, root, ROOT_INODE);
+        Self { meta, live }
+    }
+}
+
+/// Store `node` as inode `inode_id`, and everything below it under the ids
+/// it had when the snapshot was taken.
+fn lay_out(meta: &InMemoryKv, node: &SnapshotNode, inode_id: u64) {
+    let inode = Inode {
+        id: inode_id,
+        ..node.inode.clone()
+    };
+    meta.set(&keys::inode(inode_id), &serde_json::to_vec(&inode).unwrap());
+    for &(page_num, block_id) in &node.pages {
+        meta.set(&keys::page(inode_id, page_num), &encode_page_ref(block_id));
+    }
+    for (name, value) in &node.xattrs {
+        meta.set(&keys::xattr(inode_id, name), value);
+    }
+    for (name, child) in &node.children {
+        meta.set(
+            &keys::dir_entry(inode_id, name),
+            &child.inode.id.to_be_bytes(),
+        );
+        lay_out(meta, child, child.inode.id);
+    }
+}
+
+impl KvBackend for SnapshotKv {
+    fn get(&self, key: &[u8]) -> Option<Vec<u8>> {
+        self.meta.get(key).or_else(|| {
+            if key.starts_with(b"B") {
+                self.live.get(key)
+            } else {
+                None
+            }
+        })
+    }
+
+    fn get_many(&self, keys: &[Vec<u8>]) -> Vec<Option<Vec<u8>>> {
+        let mut values = self.meta.get_many(keys);
+        let misses: Vec<usize> = (0..keys.len())
+            .filter(|&i| values[i].is_none() && keys[i].starts_with(b"B"))
+            .collect();
+        if !misses.is_empty() {
+            let live_keys: Vec<Vec<u8>> = misses.iter().map(|&i| keys[i].clone()).collect();
+            for (i, value) in misses.into_iter().zip(self.live.get_many(&live_keys)) {
+                values[i] = value;
+            }
+        }
+        values
+    }
+
+    fn set(&self, key: &[u8], value: &[u8]) {
+        self.meta.set(key, value);
+    }
+
+    fn scan(&self, prefix: &[u8]) -> Vec<(Vec<u8>, Vec<u8>)> {
+        self.meta.scan(prefix)
+    }
+
+    fn delete(&self, key: &[u8]) {
+        self.meta.delete(key);
+    }
+
+    fn scan_after(
+        &self,
+        prefix: &[u8],
+        start_after: &[u8],
+        limit: usize,
+    ) -> Vec<(Vec<u8>, Vec<u8>)> {
+        self.meta.scan_after(prefix, start_after, limit)
+    }
+}
@@ -15,7 +15,7 @@ trait PipeExt: Sized {
 impl<T> PipeExt for T {}
 
 fn create_provider() -> PageFsProvider {
-    PageFsProvider::with_memory_backend()
+    PageFsProvider::with_memory_backend().unwrap()
 }
 
 #[test]
@@ -45,7 +45,8 @@ fn create_file_allocates_one_page() {
 
     let handle = provider
         .open("/test.txt", OpenFlags::create_file())
-        .unwrap();
+        .unwrap()
+        .0;
     provider.close(handle.id()).unwrap();
 
     let inode = provider.load_inode(2).unwrap();
@@ -61,11 +62,12 @@ fn create_and_read_file() {
 
     let handle = provider
         .open("/test.txt", OpenFlags::create_file())
-        .unwrap();
+        .unwrap()
+        .0;
     provider.write(handle.id(), 0, b"hello pagefs").unwrap();
     provider.close(handle.id()).unwrap();
 
-    let handle = provider.open("/test.txt", OpenFlags::read()).unwrap();
+    let handle = provider.open("/test.txt", OpenFlags::read()).unwrap().0;
     let data = provider.read(handle.id(), 0, 100).unwrap();
     assert_eq!(&data[..], b"hello pagefs");
     provider.close(handle.id()).unwrap();
@@ -77,16 +79,17 @@ fn write_across_page_boundary() {
 
     let handle = provider
         .open("/cross.txt", OpenFlags::create_file())
-        .unwrap();
+        .unwrap()
+        .0;
 
     let data: Vec<u8> = (0..PAGE_SIZE + 1000).map(|i| (i % 256) as u8).collect();
     provider.write(handle.id(), 0, &data).unwrap();
     provider.close(handle.id()).unwrap();
 
-    let inode = provider.resolve_path("/cross.txt").unwrap().1;
+    let inode = provider.resolve_path("/cross.txt", true).unwrap().1;
     assert_eq!(inode.page_count, 2);
 
-    let handle = provider.open("/cross.txt", OpenFlags::read()).unwrap();
+    let handle = provider.open("/cross.txt", OpenFlags::read()).unwrap().0;
     let read_data = provider.read(handle.id(), 0, data.len()).unwrap();
     assert_eq!(&read_data[..], &data[..]);
     provider.close(handle.id()).unwrap();
@@ -98,12 +101,13 @@ fn read_partial_page() {
 
     let handle = provider
         .open("/partial.txt", OpenFlags::create_file())
-        .unwrap();
+        .unwrap()
+        .0;
     let data = b"0123456789ABCDEF0123456789";
     provider.write(handle.id(), 0, data).unwrap();
     provider.close(handle.id()).unwrap();
 
-    let handle = provider.open("/partial.txt", OpenFlags::read()).unwrap();
+    let handle = provider.open("/partial.txt", OpenFlags::read()).unwrap().0;
     let result = provider.read(handle.id(), 10, 10).unwrap();
     assert_eq!(&result[..], b"ABCDEF0123");
     provider.close(handle.id()).unwrap();
@@ -113,7 +117,7 @@ fn read_partial_page() {
 fn create_directory() {
     let provider = create_provider();
 
-    let handle = provider.open("/mydir", OpenFlags::create_dir()).unwrap();
+    let handle = provider.open("/mydir", OpenFlags::create_dir()).unwrap().0;
     provider.close(handle.id()).unwrap();
 
     let info = provider.stat("/mydir").unwrap();
@@ -127,37 +131,66 @@ fn nested_directories() {
     provider
         .open("/a", OpenFlags::create_dir())
         .unwrap()
+        .0
         .id()
         .pipe(|h| provider.close(h).unwrap());
     provider
         .open("/a/b", OpenFlags::create_dir())
         .unwrap()
+        .0
         .id()
         .pipe(|h| provider.close(h).unwrap());
     provider
         .open("/a/b/c", OpenFlags::create_dir())
         .unwrap()
+        .0
         .id()
         .pipe(|h| provider.close(h).unwrap());
 
     let handle = provider
         .open("/a/b/c/file.txt", OpenFlags::create_file())
-        .unwrap();
+        .unwrap()
+        .0;
     provider.write(handle.id(), 0, b"deep file").unwrap();
     provider.close(handle.id()).unwrap();
 
-    let handle = provider.open("/a/b/c/file.txt", OpenFlags::read()).unwrap();
+    let handle = provider
+        .open("/a/b/c/file.txt", OpenFlags::read())
+        .unwrap()
+        .0;
     let data = provider.read(handle.id(), 0, 100).unwrap();
     assert_eq!(&data[..], b"deep file");
 }
 
+#[test]
+fn files_have_a_weak_etag_that_follows_edits() {
+    let provider = create_provider();
+    let write = |data: &[u8]| {
+        let handle = provider
+            .open("/f.txt", OpenFlags::create_truncate())
+            .unwrap()
+            .0;
+        provider.write(handle.id(), 0, data).unwrap();
+        provider.close(handle.id()).unwrap();
+        provider.stat("/f.txt").unwrap().etag
+    };
+
+    let first = write(b"hello");
+    assert!(fs9_sdk::is_weak_etag(&first));
+    assert_ne!(write(b"hello pagefs"), first);
+
+    let listed = provider.readdir("/").unwrap();
+    assert_eq!(listed[0].etag, provider.stat("/f.txt").unwrap().etag);
+    assert!(provider.stat("/").unwrap().etag.is_empty());
+}
+
 #[test]
 fn readdir_lists_children() {
     let provider = create_provider();
 
     for name in ["c.txt", "a.txt", "b.txt"] {
         let path = format!("/{}", name);
-        let handle = provider.open(&path, OpenFlags::create_file()).unwrap();
+        let handle = provider.open(&path, OpenFlags::create_file()).unwrap().0;
         provider.close(handle.id()).unwrap();
     }
 
@@ -168,17 +201,111 @@ fn readdir_lists_children() {
     assert_eq!(entries[2].path, "/c.txt");
 }
 
+#[test]
+fn readdir_of_huge_directory_batches_inode_loads() {
+    let inode_gets = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+    let kv = CountingKv {
+        inner: InMemoryKv::new(),
+        page_sets: std::sync::Arc::default(),
+        page_gets: std::sync::Arc::default(),
+        page_batches: std::sync::Arc::default(),
+        inode_gets: inode_gets.clone(),
+    };
+    let provider = PageFsProvider::new(Box::new(kv)).unwrap();
+
+    let handle = provider.open("/big", OpenFlags::create_dir()).unwrap().0;
+    provider.close(handle.id()).unwrap();
+    for i in 0..5000 {
+        let path = format!("/big/f{i:05}");
+        let handle = provider.open(&path, OpenFlags::create_file()).unwrap().0;
+        provider.close(handle.id()).unwrap();
+    }
+
+    let before = inode_gets.load(std::sync::atomic::Ordering::SeqCst);
+    let entries = provider.readdir("/big").unwrap();
+    let gets = inode_gets.load(std::sync::atomic::Ordering::SeqCst) - before;
+
+    assert_eq!(entries.len(), 5000);
+    assert_eq!(entries[0].path, "/big/f00000");
+    assert_eq!(entries[4999].path, "/big/f04999");
+    // Only path resolution, once per page, still loads inodes one by one.
+    assert!(gets < 50, "{gets} inode gets for 5000 entries");
+}
+
+#[test]
+fn child_count_follows_link_and_unlink() {
+    let provider = create_provider();
+    let count = |path: &str| provider.resolve_path(path, true).unwrap().1.child_count;
+
+    assert_eq!(count("/"), Some(0));
+    let handle = provider.open("/d", OpenFlags::create_dir()).unwrap().0;
+    provider.close(handle.id()).unwrap();
+    for name in ["a", "b", "c"] {
+        let handle = provider
+            .open(&format!("/d/{name}"), OpenFlags::create_file())
+            .unwrap()
+            .0;
+        provider.close(handle.id()).unwrap();
+    }
+    assert_eq!(count("/"), Some(1));
+    assert_eq!(count("/d"), Some(3));
+
+    provider.link("/d/a", "/d/a2").unwrap();
+    provider.symlink("/d/s", "/d/a").unwrap();
+    assert_eq!(count("/d"), Some(5));
+
+    provider.remove("/d/a2").unwrap();
+    provider.rename("/d/b", "/b").unwrap();
+    provider.rename("/d/c", "/d/s").unwrap();
+    assert_eq!(count("/d"), Some(2));
+    assert_eq!(count("/"), Some(2));
+
+    provider.remove("/d/a").unwrap();
+    provider.remove("/d/s").unwrap();
+    assert_eq!(count("/d"), Some(0));
+    provider.remove("/d").unwrap();
+    assert_eq!(count("/"), Some(1));
+}
+
+#[test]
+fn directories_without_a_count_are_counted_on_first_use() {
+    let provider = create_provider();
+    let handle = provider.open("/old", OpenFlags::create_dir()).unwrap().0;
+    provider.close(handle.id()).unwrap();
+    let handle = provider.open("/old/x", OpenFlags::create_file()).unwrap().0;
+    provider.close(handle.id()).unwrap();
+
+    // Simulate a directory written before counts were stored.
+    let (dir_id, mut dir) = provider.resolve_path("/old", true).unwrap();
+    dir.child_count = None;
+    provider
+        .kv
+        .set(&keys::inode(dir_id), &serde_json::to_vec(&dir).unwrap());
+
+    assert!(matches!(
+        provider.remove("/old"),
+        Err(FsError::DirectoryNotEmpty(_))
+    ));
+    let handle = provider.open("/old/y", OpenFlags::create_file()).unwrap().0;
+    provider.close(handle.id()).unwrap();
+    assert_eq!(
+        provider.resolve_path("/old", true).unwrap().1.child_count,
+        Some(2)
+    );
+}
+
 #[test]
 fn remove_file_deletes_pages() {
     let provider = create_provider();
 
     let handle = provider
         .open("/todelete.txt", OpenFlags::create_file())
-        .unwrap();
+        .unwrap()
+        .0;
     provider.write(handle.id(), 0, b"will be deleted").unwrap();
     provider.close(handle.id()).unwrap();
 
-    let inode_id = provider.resolve_path("/todelete.txt").unwrap().0;
+    let inode_id = provider.resolve_path("/todelete.txt", true).unwrap().0;
     assert!(provider.read_page(inode_id, 0).is_some());
 
     provider.remove("/todelete.txt").unwrap();
@@ -197,12 +324,14 @@ fn cannot_remove_non_empty_dir() {
     provider
         .open("/parent", OpenFlags::create_dir())
         .unwrap()
+        .0
         .id()
         .pipe(|h| provider.close(h).unwrap());
 
     let handle = provider
         .open("/parent/child.txt", OpenFlags::create_file())
-        .unwrap();
+        .unwrap()
+        .0;
     provider.close(handle.id()).unwrap();
 
     assert!(matches!(
@@ -220,7 +349,8 @@ fn truncate_file() {
 
     let handle = provider
         .open("/trunc.txt", OpenFlags::create_file())
-        .unwrap();
+        .unwrap()
+        .0;
     provider
         .write(handle.id(), 0, b"long content here that will be truncated")
         .unwrap();
@@ -233,7 +363,7 @@ fn truncate_file() {
     let info = provider.stat("/trunc.txt").unwrap();
     assert_eq!(info.size, 10);
 
-    let handle = provider.open("/trunc.txt", OpenFlags::read()).unwrap();
+    let handle = provider.open("/trunc.txt", OpenFlags::read()).unwrap().0;
     let data = provider.read(handle.id(), 0, 100).unwrap();
     assert_eq!(&data[..], b"long conte");
 }
@@ -244,7 +374,8 @@ fn extend_file_via_wstat() {
 
     let handle = provider
         .open("/extend.txt", OpenFlags::create_file())
-        .unwrap();
+        .unwrap()
+        .0;
     provider.write(handle.id(), 0, b"short").unwrap();
     provider.close(handle.id()).unwrap();
 
@@ -256,13 +387,137 @@ fn extend_file_via_wstat() {
     assert_eq!(info.size, 20);
 }
 
+#[test]
+fn truncate_down_then_up_zero_fills() {
+    let provider = create_provider();
+
+    let handle = provider
+        .open("/regrow.txt", OpenFlags::create_file())
+        .unwrap()
+        .0;
+    provider.write(handle.id(), 0, b"hello world").unwrap();
+    provider.close(handle.id()).unwrap();
+
+    provider
+        .wstat("/regrow.txt", &StatChanges::truncate(5))
+        .unwrap();
+    assert_eq!(provider.stat("/regrow.txt").unwrap().size, 5);
+    provider
+        .wstat("/regrow.txt", &StatChanges::truncate(8))
+        .unwrap();
+    assert_eq!(provider.stat("/regrow.txt").unwrap().size, 8);
+
+    // The bytes cut off first must not come back when the file grows.
+    let handle = provider.open("/regrow.txt", OpenFlags::read()).unwrap().0;
+    let data = provider.read(handle.id(), 0, 100).unwrap();
+    assert_eq!(&data[..], b"hello\0\0\0");
+}
+
+#[test]
+fn truncate_to_zero_then_up_zero_fills() {
+    let provider = create_provider();
+    write_file(&provider, "/empty.txt", b"hello world");
+
+    provider
+        .wstat("/empty.txt", &StatChanges::truncate(0))
+        .unwrap();
+    provider
+        .wstat("/empty.txt", &StatChanges::truncate(11))
+        .unwrap();
+    let handle = provider.open("/empty.txt", OpenFlags::read()).unwrap().0;
+    let data = provider.read(handle.id(), 0, 100).unwrap();
+    assert_eq!(&data[..], &[0u8; 11]);
+    provider.close(handle.id()).unwrap();
+
+    provider
+        .wstat("/empty.txt", &StatChanges::truncate(0))
+        .unwrap();
+    let handle = provider.open("/empty.txt", OpenFlags::write()).unwrap().0;
+    provider.write(handle.id(), 8, b"X").unwrap();
+    provider.close(handle.id()).unwrap();
+    let handle = provider.open("/empty.txt", OpenFlags::read()).unwrap().0;
+    let data = provider.read(handle.id(), 0, 100).unwrap();
+    assert_eq!(&data[..], b"\0\0\0\0\0\0\0\0X");
+}
+
+#[test]
+fn growing_by_wstat_writes_no_pages() {
+    let provider = create_provider();
+    write_file(&provider, "/prealloc.bin", b"head");
+    let inode_id = provider.resolve_path("/prealloc.bin", false).unwrap().0;
+
+    let size = (PAGE_SIZE * 1000 + 7) as u64;
+    provider
+        .wstat("/prealloc.bin", &StatChanges::truncate(size))
+        .unwrap();
+    provider.flush();
+
+    let inode = provider.load_inode(inode_id).unwrap();
+    assert_eq!(inode.size, size);
+    assert_eq!(inode.page_count, 1001);
+    assert_eq!(stored_page_count(&provider, inode_id), 1);
+    // The grown pages are reserved even though none is stored.
+    assert_eq!(provider.load_superblock().used_pages, 1001);
+
+    let (handle, _) = provider.open("/prealloc.bin", OpenFlags::read()).unwrap();
+    let data = provider.read(handle.id(), 0, 8).unwrap();
+    assert_eq!(&data[..], b"head\0\0\0\0");
+    let hole = provider
+        .read(handle.id(), (PAGE_SIZE * 500) as u64, PAGE_SIZE * 2)
+        .unwrap();
+    assert_eq!(hole.len(), PAGE_SIZE * 2);
+    assert!(hole.iter().all(|&b| b == 0));
+    let tail = provider.read(handle.id(), size - 10, 100).unwrap();
+    assert_eq!(&tail[..], &[0u8; 10]);
+    provider.close(handle.id()).unwrap();
+
+    // Shrinking back frees the reservation and keeps the written page.
+    provider
+        .wstat("/prealloc.bin", &StatChanges::truncate(4))
+        .unwrap();
+    assert_eq!(provider.load_superblock().used_pages, 1);
+    assert_eq!(stored_page_count(&provider, inode_id), 1);
+}
+
+#[test]
+fn writes_into_a_grown_file_fill_its_holes() {
+    let provider = create_provider();
+    write_file(&provider, "/grown.bin", b"");
+    provider
+        .wstat("/grown.bin", &StatChanges::truncate((PAGE_SIZE * 4) as u64))
+        .unwrap();
+
+    let (handle, _) = provider.open("/grown.bin", OpenFlags::write()).unwrap();
+    provider
+        .write(handle.id(), (PAGE_SIZE * 2 + 1) as u64, b"middle")
+        .unwrap();
+    provider.close(handle.id()).unwrap();
+    provider.flush();
+
+    // Only the first page, stored when the file was created, and the one
+    // written to.
+    let inode_id = provider.resolve_path("/grown.bin", false).unwrap().0;
+    assert_eq!(stored_page_count(&provider, inode_id), 2);
+    assert_eq!(
+        provider.stat("/grown.bin").unwrap().size,
+        (PAGE_SIZE * 4) as u64
+    );
+
+    let (handle, _) = provider.open("/grown.bin", OpenFlags::read()).unwrap();
+    let data = provider.read(handle.id(), 0, PAGE_SIZE * 4).unwrap();
+    assert_eq!(&data[PAGE_SIZE * 2 + 1..PAGE_SIZE * 2 + 7], b"middle");
+    assert_eq!(data.iter().filter(|&&b| b != 0).count(), 6);
+    provider.close(handle.id()).unwrap();
+}
+
 #[test]
 fn append_mode() {
     let provider = create_provider();
 
     let handle = provider
         .open("/append.txt", OpenFlags::create_file())
-        .unwrap();
+        .unwrap()
+        .0;
     provider.write(handle.id(), 0, b"first").unwrap();
     provider.close(handle.id()).unwrap();
 
@@ -271,11 +526,11 @@ fn append_mode() {
         append: true,
         ..Default::default()
     };
-    let handle = provider.open("/append.txt", flags).unwrap();
+    let handle = provider.open("/append.txt", flags).unwrap().0;
     provider.write(handle.id(), 0, b"second").unwrap();
     provider.close(handle.id()).unwrap();
 
-    let handle = provider.open("/append.txt", OpenFlags::read()).unwrap();
+    let handle = provider.open("/append.txt", OpenFlags::read()).unwrap().0;
     let data = provider.read(handle.id(), 0, 100).unwrap();
     assert_eq!(&data[..], b"firstsecond");
 }
@@ -286,7 +541,8 @@ fn large_file_spanning_many_pages() {
 
     let handle = provider
         .open("/large.bin", OpenFlags::create_file())
-        .unwrap();
+        .unwrap()
+        .0;
 
     let data: Vec<u8> = (0..(PAGE_SIZE * 3 + 5000))
         .map(|i| (i % 256) as u8)
@@ -297,10 +553,10 @@ fn large_file_spanning_many_pages() {
     let info = provider.stat("/large.bin").unwrap();
     assert_eq!(info.size, data.len() as u64);
 
-    let inode = provider.resolve_path("/large.bin").unwrap().1;
+    let inode = provider.resolve_path("/large.bin", true).unwrap().1;
     assert_eq!(inode.page_count, 4);
 
-    let handle = provider.open("/large.bin", OpenFlags::read()).unwrap();
+    let handle = provider.open("/large.bin", OpenFlags::read()).unwrap().0;
     let read_data = provider.read(handle.id(), 0, data.len()).unwrap();
     assert_eq!(read_data.len(), data.len());
     assert_eq!(&read_data[..], &data[..]);
@@ -312,7 +568,8 @@ fn sparse_write() {
 
     let handle = provider
         .open("/sparse.txt", OpenFlags::create_file())
-        .unwrap();
+        .unwrap()
+        .0;
     provider
         .write(handle.id(), PAGE_SIZE as u64, b"sparse data")
         .unwrap();
@@ -321,10 +578,10 @@ fn sparse_write() {
     let info = provider.stat("/sparse.txt").unwrap();
     assert_eq!(info.size, PAGE_SIZE as u64 + 11);
 
-    let inode = provider.resolve_path("/sparse.txt").unwrap().1;
+    let inode = provider.resolve_path("/sparse.txt", true).unwrap().1;
     assert_eq!(inode.page_count, 2);
 
-    let handle = provider.open("/sparse.txt", OpenFlags::read()).unwrap();
+    let handle = provider.open("/sparse.txt", OpenFlags::read()).unwrap().0;
     let first_page = provider.read(handle.id(), 0, PAGE_SIZE).unwrap();
     assert!(first_page.iter().all(|&b| b == 0));
 
@@ -371,7 +628,7 @@ fn negative_timestamps_handled_correctly() {
 
 #[test]
 fn configurable_uid_gid() {
-    let provider = PageFsProvider::with_config(Box::new(InMemoryKv::new()), 1000, 1001);
+    let provider = PageFsProvider::with_config(Box::new(InMemoryKv::new()), 1000, 1001).unwrap();
 
     let info = provider.stat("/").unwrap();
     assert_eq!(info.uid, 1000);
@@ -379,7 +636,8 @@ fn configurable_uid_gid() {
 
     let handle = provider
         .open("/file.txt", OpenFlags::create_file())
-        .unwrap();
+        .unwrap()
+        .0;
     provider.close(handle.id()).unwrap();
 
     let entries = provider.readdir("/").unwrap();
@@ -388,11 +646,72 @@ fn configurable_uid_gid() {
     assert_eq!(entries[0].gid, 1001);
 }
 
+#[test]
+fn default_modes_are_unchanged() {
+    let provider = create_provider();
+    provider.open("/dir", OpenFlags::create_dir()).unwrap();
+    let (handle, _) = provider.open("/file", OpenFlags::create_file()).unwrap();
+    provider.close(handle.id()).unwrap();
+
+    assert_eq!(provider.stat("/dir").unwrap().mode, 0o755);
+    assert_eq!(provider.stat("/file").unwrap().mode, 0o644);
+}
+
+#[test]
+fn configured_modes_are_masked_by_umask() {
+    let provider = create_provider().with_creation_modes(0o666, 0o777, 0o027);
+    provider.open("/dir", OpenFlags::create_dir()).unwrap();
+    let (handle, info) = provider
+        .open("/dir/file", OpenFlags::create_file())
+        .unwrap();
+    provider.close(handle.id()).unwrap();
+
+    assert_eq!(info.mode, 0o640);
+    assert_eq!(provider.stat("/dir/file").unwrap().mode, 0o640);
+    assert_eq!(provider.stat("/dir").unwrap().mode, 0o750);
+    assert_eq!(
+        provider.stat("/").unwrap().mode,
+        0o755,
+        "root keeps its mode"
+    );
+
+    // chmod sets the mode as given; the umask only applies at creation.
+    provider
+        .wstat("/dir/file", &StatChanges::chmod(0o666))
+        .unwrap();
+    assert_eq!(provider.stat("/dir/file").unwrap().mode, 0o666);
+}
+
+#[test]
+fn mode_config_parsing() {
+    let cfg: PageFsConfig = serde_json::from_str("{}").unwrap();
+    assert_eq!(
+        (cfg.default_file_mode, cfg.default_dir_mode, cfg.umask),
+        (0o644, 0o755, 0)
+    );
+
+    let cfg: PageFsConfig = serde_json::from_str(
+        r#"{"default_file_mode": "0o600", "default_dir_mode": "0700", "umask": 18}"#,
+    )
+    .unwrap();
+    assert_eq!(
+        (cfg.default_file_mode, cfg.default_dir_mode, cfg.umask),
+        (0o600, 0o700, 0o022)
+    );
+
+    for bad in [r#"{"umask": "0o9"}"#, r#"{"default_file_mode": 4096}"#] {
+        assert!(serde_json::from_str::<PageFsConfig>(bad).is_err(), "{bad}");
+    }
+}
+
 #[test]
 fn rename_file_same_dir() {
-    let provider = PageFsProvider::with_memory_backend();
+    let provider = PageFsProvider::with_memory_backend().unwrap();
 
-    let handle = provider.open("/old.txt", OpenFlags::create_file()).unwrap();
+    let handle = provider
+        .open("/old.txt", OpenFlags::create_file())
+        .unwrap()
+        .0;
     provider.write(handle.id(), 0, b"content").unwrap();
     provider.close(handle.id()).unwrap();
 
@@ -407,12 +726,13 @@ fn rename_file_same_dir() {
 
 #[test]
 fn rename_file_cross_dir() {
-    let provider = PageFsProvider::with_memory_backend();
+    let provider = PageFsProvider::with_memory_backend().unwrap();
 
-    provider.open("/subdir", OpenFlags::create_dir()).unwrap();
+    provider.open("/subdir", OpenFlags::create_dir()).unwrap().0;
     let handle = provider
         .open("/file.txt", OpenFlags::create_file())
-        .unwrap();
+        .unwrap()
+        .0;
     provider.write(handle.id(), 0, b"data").unwrap();
     provider.close(handle.id()).unwrap();
 
@@ -427,13 +747,19 @@ fn rename_file_cross_dir() {
 
 #[test]
 fn rename_replaces_existing_file() {
-    let provider = PageFsProvider::with_memory_backend();
+    let provider = PageFsProvider::with_memory_backend().unwrap();
 
-    let h1 = provider.open("/src.txt", OpenFlags::create_file()).unwrap();
+    let h1 = provider
+        .open("/src.txt", OpenFlags::create_file())
+        .unwrap()
+        .0;
     provider.write(h1.id(), 0, b"source").unwrap();
     provider.close(h1.id()).unwrap();
 
-    let h2 = provider.open("/dst.txt", OpenFlags::create_file()).unwrap();
+    let h2 = provider
+        .open("/dst.txt", OpenFlags::create_file())
+        .unwrap()
+        .0;
     provider.write(h2.id(), 0, b"old content").unwrap();
     provider.close(h2.id()).unwrap();
 
@@ -445,21 +771,22 @@ fn rename_replaces_existing_file() {
     let info = provider.stat("/dst.txt").unwrap();
     assert_eq!(info.size, 6);
 
-    let handle = provider.open("/dst.txt", OpenFlags::read()).unwrap();
+    let handle = provider.open("/dst.txt", OpenFlags::read()).unwrap().0;
     let data = provider.read(handle.id(), 0, 100).unwrap();
     assert_eq!(&data[..], b"source");
 }
 
 #[test]
 fn rename_file_to_dir_fails() {
-    let provider = PageFsProvider::with_memory_backend();
+    let provider = PageFsProvider::with_memory_backend().unwrap();
 
     let handle = provider
         .open("/file.txt", OpenFlags::create_file())
-        .unwrap();
+        .unwrap()
+        .0;
     provider.close(handle.id()).unwrap();
 
-    provider.open("/dir", OpenFlags::create_dir()).unwrap();
+    provider.open("/dir", OpenFlags::create_dir()).unwrap().0;
 
     let result = provider.wstat("/file.txt", &StatChanges::rename("dir"));
     assert!(matches!(result, Err(FsError::IsDirectory(_))));
@@ -467,11 +794,11 @@ fn rename_file_to_dir_fails() {
 
 #[test]
 fn rename_dir_to_file_fails() {
-    let provider = PageFsProvider::with_memory_backend();
+    let provider = PageFsProvider::with_memory_backend().unwrap();
 
-    provider.open("/dir", OpenFlags::create_dir()).unwrap();
+    provider.open("/dir", OpenFlags::create_dir()).unwrap().0;
 
-    let handle = provider.open("/file", OpenFlags::create_file()).unwrap();
+    let handle = provider.open("/file", OpenFlags::create_file()).unwrap().0;
     provider.close(handle.id()).unwrap();
 
     let result = provider.wstat("/dir", &StatChanges::rename("file"));
@@ -480,198 +807,2054 @@ fn rename_dir_to_file_fails() {
 
 #[test]
 fn rename_dir_to_nonempty_dir_fails() {
-    let provider = PageFsProvider::with_memory_backend();
+    let provider = PageFsProvider::with_memory_backend().unwrap();
 
-    provider.open("/src", OpenFlags::create_dir()).unwrap();
-    provider.open("/dst", OpenFlags::create_dir()).unwrap();
+    provider.open("/src", OpenFlags::create_dir()).unwrap().0;
+    provider.open("/dst", OpenFlags::create_dir()).unwrap().0;
 
     let handle = provider
         .open("/dst/child.txt", OpenFlags::create_file())
-        .unwrap();
+        .unwrap()
+        .0;
     provider.close(handle.id()).unwrap();
 
     let result = provider.wstat("/src", &StatChanges::rename("dst"));
     assert!(matches!(result, Err(FsError::DirectoryNotEmpty(_))));
 }
 
-#[cfg(feature = "s3")]
-mod s3_tests {
-    use super::*;
+#[test]
+fn ffi_create_rejects_unparsable_config() {
+    unsafe {
+        let vtable = &*ffi::fs9_plugin_vtable();
+        for config in [&b"{not json"[..], br#"{"backend": {"type": "floppy"}}"#] {
+            let raw = (vtable.create)(config.as_ptr().cast(), config.len());
+            assert!(raw.is_null(), "{}", String::from_utf8_lossy(config));
+        }
 
-    #[test]
-    fn s3_make_key_without_prefix() {
-        let backend = S3KvBackend {
-            client: create_mock_client(),
-            bucket: "test-bucket".to_string(),
-            prefix: String::new(),
-            runtime: tokio::runtime::Runtime::new().unwrap(),
-        };
+        let config = br#"{"backend": {"type": "memory"}, "ns": "t1"}"#;
+        let raw = (vtable.create)(config.as_ptr().cast(), config.len());
+        assert!(!raw.is_null());
+        (vtable.destroy)(raw);
+    }
+}
+
+/// Rename through the vtable slot, the way core's `PluginProvider` does.
+unsafe fn ffi_rename(provider: *mut std::ffi::c_void, old_path: &str, new_path: &str) -> i32 {
+    let vtable = &*ffi::fs9_plugin_vtable();
+    (vtable.rename)(
+        provider,
+        old_path.as_ptr().cast(),
+        old_path.len(),
+        new_path.as_ptr().cast(),
+        new_path.len(),
+    )
+    .code
+}
+
+#[test]
+fn ffi_rename_across_directories() {
+    unsafe {
+        let vtable = &*ffi::fs9_plugin_vtable();
+        let raw = (vtable.create)(std::ptr::null(), 0);
+        assert!(!raw.is_null());
+        let provider = &*(raw as *const PageFsProvider);
+
+        provider.open("/a", OpenFlags::create_dir()).unwrap();
+        provider.open("/b", OpenFlags::create_dir()).unwrap();
+        let handle = provider
+            .open("/a/file.txt", OpenFlags::create_file())
+            .unwrap()
+            .0;
+        provider.write(handle.id(), 0, b"payload").unwrap();
+        provider.close(handle.id()).unwrap();
 
-        assert_eq!(backend.make_key(b"S"), "53");
         assert_eq!(
-            backend.make_key(b"I\x00\x00\x00\x00\x00\x00\x00\x01"),
-            "490000000000000001"
+            ffi_rename(raw, "/a/file.txt", "/b/moved.txt"),
+            fs9_sdk_ffi::FS9_OK
         );
-        assert_eq!(backend.make_key(b"hello"), "68656c6c6f");
-    }
+        assert!(provider.stat("/a/file.txt").is_err());
+        let handle = provider.open("/b/moved.txt", OpenFlags::read()).unwrap().0;
+        assert_eq!(provider.read(handle.id(), 0, 16).unwrap(), b"payload"[..]);
+        provider.close(handle.id()).unwrap();
 
-    #[test]
-    fn s3_make_key_with_prefix() {
-        let backend = S3KvBackend {
-            client: create_mock_client(),
-            bucket: "test-bucket".to_string(),
-            prefix: "pagefs".to_string(),
-            runtime: tokio::runtime::Runtime::new().unwrap(),
-        };
+        assert_eq!(ffi_rename(raw, "/a", "/b/a"), fs9_sdk_ffi::FS9_OK);
+        assert_eq!(
+            provider.stat("/b/a").unwrap().file_type,
+            FileType::Directory
+        );
+        assert!(provider
+            .readdir("/")
+            .unwrap()
+            .iter()
+            .all(|e| e.path != "/a"));
 
-        assert_eq!(backend.make_key(b"S"), "pagefs/53");
-        assert_eq!(backend.make_key(b"hello"), "pagefs/68656c6c6f");
+        (vtable.destroy)(raw);
     }
+}
 
-    #[test]
-    fn s3_parse_key_without_prefix() {
-        let backend = S3KvBackend {
-            client: create_mock_client(),
-            bucket: "test-bucket".to_string(),
-            prefix: String::new(),
-            runtime: tokio::runtime::Runtime::new().unwrap(),
-        };
-
-        assert_eq!(backend.parse_key("53"), Some(vec![0x53]));
-        assert_eq!(backend.parse_key("68656c6c6f"), Some(b"hello".to_vec()));
-    }
+#[test]
+fn ffi_rename_errors_map_to_codes() {
+    unsafe {
+        let vtable = &*ffi::fs9_plugin_vtable();
+        let raw = (vtable.create)(std::ptr::null(), 0);
+        let provider = &*(raw as *const PageFsProvider);
 
-    #[test]
-    fn s3_parse_key_with_prefix() {
-        let backend = S3KvBackend {
-            client: create_mock_client(),
-            bucket: "test-bucket".to_string(),
-            prefix: "pagefs".to_string(),
-            runtime: tokio::runtime::Runtime::new().unwrap(),
-        };
+        provider.open("/dir", OpenFlags::create_dir()).unwrap();
 
-        assert_eq!(backend.parse_key("pagefs/53"), Some(vec![0x53]));
         assert_eq!(
-            backend.parse_key("pagefs/68656c6c6f"),
-            Some(b"hello".to_vec())
+            ffi_rename(raw, "/missing", "/dir/x"),
+            fs9_sdk_ffi::FS9_ERR_NOT_FOUND
         );
-        assert_eq!(backend.parse_key("other/53"), None);
+        assert_eq!(
+            ffi_rename(raw, "/dir", "/dir/inner"),
+            fs9_sdk_ffi::FS9_ERR_INVALID_ARGUMENT
+        );
+        assert_eq!(
+            ffi_rename(std::ptr::null_mut(), "/dir", "/other"),
+            fs9_sdk_ffi::FS9_ERR_INVALID_ARGUMENT
+        );
+
+        (vtable.destroy)(raw);
     }
+}
 
-    #[test]
-    fn s3_key_roundtrip() {
-        let backend = S3KvBackend {
-            client: create_mock_client(),
-            bucket: "test-bucket".to_string(),
-            prefix: "test".to_string(),
-            runtime: tokio::runtime::Runtime::new().unwrap(),
-        };
+#[test]
+fn ffi_errors_carry_their_message() {
+    unsafe {
+        let vtable = &*ffi::fs9_plugin_vtable();
+        let raw = (vtable.create)(std::ptr::null(), 0);
+        let path = "/some/path";
+        let mut info = fs9_sdk_ffi::CFileInfo::default();
+
+        let result = (vtable.stat)(raw, path.as_ptr().cast(), path.len(), &mut info);
+        assert_eq!(result.code, fs9_sdk_ffi::FS9_ERR_NOT_FOUND);
+        let msg = std::slice::from_raw_parts(result.error_msg.cast::<u8>(), result.error_msg_len);
+        assert!(
+            String::from_utf8_lossy(msg).contains("/some"),
+            "{:?}",
+            String::from_utf8_lossy(msg)
+        );
 
-        let test_keys: Vec<&[u8]> = vec![
-            b"S",
-            b"I\x00\x00\x00\x00\x00\x00\x00\x01",
-            b"D\x00\x00\x00\x00\x00\x00\x00\x01:file.txt",
-            b"P\x00\x00\x00\x00\x00\x00\x00\x02:\x00\x00\x00\x00\x00\x00\x00\x00",
-        ];
+        (vtable.destroy)(raw);
+    }
+}
 
-        for key in test_keys {
-            let encoded = backend.make_key(key);
-            let decoded = backend.parse_key(&encoded);
-            assert_eq!(
-                decoded,
-                Some(key.to_vec()),
-                "Roundtrip failed for {:?}",
-                key
+#[test]
+fn exclusive_create_fails_if_path_exists() {
+    let provider = create_provider();
+    let flags = OpenFlags {
+        exclusive: true,
+        ..OpenFlags::create_file()
+    };
+
+    let (handle, info) = provider.open("/lock", flags).unwrap();
+    assert_eq!(info.file_type, FileType::Regular);
+    provider.close(handle.id()).unwrap();
+
+    assert!(matches!(
+        provider.open("/lock", flags),
+        Err(FsError::AlreadyExists(_))
+    ));
+    // Without `exclusive`, create still opens the existing file.
+    let (handle, _) = provider.open("/lock", OpenFlags::create_file()).unwrap();
+    provider.close(handle.id()).unwrap();
+}
+
+#[test]
+fn ffi_open_honors_excl() {
+    unsafe {
+        let vtable = &*ffi::fs9_plugin_vtable();
+        let raw = (vtable.create)(std::ptr::null(), 0);
+        let flags = fs9_sdk_ffi::COpenFlags {
+            read: 1,
+            write: 1,
+            create: 1,
+            truncate: 0,
+            append: 0,
+            directory: 0,
+            symlink: 0,
+            excl: 1,
+        };
+        let open = || {
+            let mut handle = 0;
+            let mut info = fs9_sdk_ffi::CFileInfo::default();
+            let path = "/lock";
+            (vtable.open)(
+                raw,
+                path.as_ptr().cast(),
+                path.len(),
+                &flags,
+                &mut handle,
+                &mut info,
+            )
+            .code
+        };
+
+        assert_eq!(open(), fs9_sdk_ffi::FS9_OK);
+        assert_eq!(open(), fs9_sdk_ffi::FS9_ERR_ALREADY_EXISTS);
+
+        (vtable.destroy)(raw);
+    }
+}
+
+/// Collects entry paths from a `ReaddirCallback` into a `Vec<String>`.
+unsafe extern "C" fn collect_path(
+    info: *const fs9_sdk_ffi::CFileInfo,
+    user_data: *mut std::ffi::c_void,
+) -> i32 {
+    let info = &*info;
+    let path = std::slice::from_raw_parts(info.path.cast::<u8>(), info.path_len);
+    (*user_data.cast::<Vec<String>>()).push(String::from_utf8_lossy(path).into_owned());
+    0
+}
+
+#[test]
+fn ffi_readdir_at_pages_without_gaps() {
+    unsafe {
+        let vtable = &*ffi::fs9_plugin_vtable();
+        let raw = (vtable.create)(std::ptr::null(), 0);
+        let provider = &*(raw as *const PageFsProvider);
+
+        provider.open("/big", OpenFlags::create_dir()).unwrap();
+        for i in 0..1000 {
+            let handle = provider
+                .open(&format!("/big/f{i:04}"), OpenFlags::create_file())
+                .unwrap()
+                .0;
+            provider.close(handle.id()).unwrap();
+        }
+
+        let path = "/big";
+        let mut seen: Vec<String> = Vec::new();
+        let mut cursor: Vec<u8> = Vec::new();
+        loop {
+            let mut page: Vec<String> = Vec::new();
+            let mut next = fs9_sdk_ffi::CBytes::default();
+            let result = (vtable.readdir_at)(
+                raw,
+                path.as_ptr().cast(),
+                path.len(),
+                cursor.as_ptr().cast(),
+                cursor.len(),
+                100,
+                collect_path,
+                (&mut page as *mut Vec<String>).cast(),
+                &mut next,
             );
+            assert_eq!(result.code, fs9_sdk_ffi::FS9_OK);
+            assert!(page.len() <= 100);
+            seen.extend(page);
+
+            if next.data.is_null() {
+                break;
+            }
+            cursor = std::slice::from_raw_parts(next.data, next.len).to_vec();
+            fs9_sdk_ffi::fs9_bytes_free(&mut next);
         }
+
+        let expected: Vec<_> = (0..1000).map(|i| format!("/big/f{i:04}")).collect();
+        assert_eq!(seen, expected);
+
+        (vtable.destroy)(raw);
     }
+}
 
-    fn create_mock_client() -> aws_sdk_s3::Client {
-        let config = aws_sdk_s3::Config::builder()
-            .behavior_version(aws_sdk_s3::config::BehaviorVersion::latest())
-            .region(aws_sdk_s3::config::Region::new("us-east-1"))
-            .build();
-        aws_sdk_s3::Client::from_conf(config)
+#[test]
+fn readdir_spans_multiple_scan_batches() {
+    let provider = create_provider();
+    provider.open("/many", OpenFlags::create_dir()).unwrap();
+    for i in 0..(READDIR_BATCH + 5) {
+        let handle = provider
+            .open(&format!("/many/{i:05}"), OpenFlags::create_file())
+            .unwrap()
+            .0;
+        provider.close(handle.id()).unwrap();
     }
 
-    #[test]
-    #[ignore]
-    fn s3_integration_basic_operations() {
-        let bucket = std::env::var("FS9_TEST_S3_BUCKET")
-            .expect("FS9_TEST_S3_BUCKET env var required for S3 integration tests");
-        let prefix = std::env::var("FS9_TEST_S3_PREFIX")
-            .unwrap_or_else(|_| format!("pagefs-test-{}", std::process::id()));
+    let entries = provider.readdir("/many").unwrap();
+    assert_eq!(entries.len(), READDIR_BATCH + 5);
+    assert!(entries.windows(2).all(|w| w[0].path < w[1].path));
+}
 
-        let backend = S3KvBackend::new(bucket, prefix);
+#[test]
+fn wstat_rename_still_supported() {
+    let provider = PageFsProvider::with_memory_backend().unwrap();
 
-        let test_key = b"test-key";
-        let test_value = b"test-value-12345";
+    provider.open("/dir", OpenFlags::create_dir()).unwrap();
+    let handle = provider
+        .open("/dir/old.txt", OpenFlags::create_file())
+        .unwrap()
+        .0;
+    provider.close(handle.id()).unwrap();
 
-        backend.set(test_key, test_value);
+    provider
+        .wstat("/dir/old.txt", &StatChanges::rename("new.txt"))
+        .unwrap();
+    assert!(provider.stat("/dir/new.txt").is_ok());
+    assert!(provider.stat("/dir/old.txt").is_err());
+}
 
-        let retrieved = backend.get(test_key);
-        assert_eq!(retrieved, Some(test_value.to_vec()));
+/// Deterministic xorshift bytes; incompressible for any real codec.
+fn noise(len: usize) -> Vec<u8> {
+    let mut state = 0x9E37_79B9_7F4A_7C15u64;
+    (0..len)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state as u8
+        })
+        .collect()
+}
 
-        backend.delete(test_key);
+fn stored_page_len(provider: &PageFsProvider, path: &str, page_num: u64) -> usize {
+    let inode_id = provider.resolve_path(path, true).unwrap().0;
+    provider
+        .kv
+        .get(&keys::page(inode_id, page_num))
+        .unwrap()
+        .len()
+}
 
-        let after_delete = backend.get(test_key);
-        assert_eq!(after_delete, None);
+#[test]
+fn compressed_zeros_roundtrip() {
+    for compression in [Compression::Zlib, Compression::Zstd] {
+        let provider = PageFsProvider::with_memory_backend()
+            .unwrap()
+            .with_compression(compression);
+
+        let handle = provider
+            .open("/zeros.bin", OpenFlags::create_file())
+            .unwrap()
+            .0;
+        let data = vec![0u8; PAGE_SIZE * 2 + 100];
+        provider.write(handle.id(), 0, &data).unwrap();
+        provider.write(handle.id(), 10, b"marker").unwrap();
+        provider.close(handle.id()).unwrap();
+
+        for page_num in 0..3 {
+            assert!(stored_page_len(&provider, "/zeros.bin", page_num) < 512);
+        }
+
+        let handle = provider.open("/zeros.bin", OpenFlags::read()).unwrap().0;
+        let read_data = provider.read(handle.id(), 0, data.len()).unwrap();
+        assert_eq!(read_data.len(), data.len());
+        assert_eq!(&read_data[10..16], b"marker");
+        assert!(read_data[16..].iter().all(|&b| b == 0));
     }
+}
 
-    #[test]
-    #[ignore]
-    fn s3_integration_scan() {
-        let bucket = std::env::var("FS9_TEST_S3_BUCKET")
-            .expect("FS9_TEST_S3_BUCKET env var required for S3 integration tests");
-        let prefix = std::env::var("FS9_TEST_S3_PREFIX")
-            .unwrap_or_else(|_| format!("pagefs-test-{}", std::process::id()));
+#[test]
+fn incompressible_page_stored_raw() {
+    for compression in [Compression::Zlib, Compression::Zstd] {
+        let provider = PageFsProvider::with_memory_backend()
+            .unwrap()
+            .with_compression(compression);
 
-        let backend = S3KvBackend::new(bucket, prefix);
+        let handle = provider
+            .open("/random.bin", OpenFlags::create_file())
+            .unwrap()
+            .0;
+        let data = noise(PAGE_SIZE);
+        provider.write(handle.id(), 0, &data).unwrap();
+        provider.close(handle.id()).unwrap();
 
-        backend.set(b"prefix:a", b"value-a");
-        backend.set(b"prefix:b", b"value-b");
-        backend.set(b"prefix:c", b"value-c");
-        backend.set(b"other:x", b"value-x");
+        assert_eq!(stored_page_len(&provider, "/random.bin", 0), PAGE_SIZE);
 
-        let results = backend.scan(b"prefix:");
-        assert_eq!(results.len(), 3);
+        let handle = provider.open("/random.bin", OpenFlags::read()).unwrap().0;
+        let read_data = provider.read(handle.id(), 0, PAGE_SIZE).unwrap();
+        assert_eq!(&read_data[..], &data[..]);
+    }
+}
 
-        for (k, _) in &results {
-            assert!(k.starts_with(b"prefix:"));
+#[test]
+fn uncompressed_pages_readable_after_enabling_compression() {
+    let provider = create_provider();
+
+    let handle = provider
+        .open("/legacy.txt", OpenFlags::create_file())
+        .unwrap()
+        .0;
+    provider.write(handle.id(), 0, b"written raw").unwrap();
+    provider.close(handle.id()).unwrap();
+    assert_eq!(stored_page_len(&provider, "/legacy.txt", 0), PAGE_SIZE);
+
+    let provider = provider.with_compression(Compression::Zstd);
+    let handle = provider
+        .open("/legacy.txt", OpenFlags::read_write())
+        .unwrap()
+        .0;
+    assert_eq!(
+        &provider.read(handle.id(), 0, 100).unwrap()[..],
+        b"written raw"
+    );
+
+    provider.write(handle.id(), 11, b" then zstd").unwrap();
+    provider.close(handle.id()).unwrap();
+    assert!(stored_page_len(&provider, "/legacy.txt", 0) < PAGE_SIZE);
+
+    let handle = provider.open("/legacy.txt", OpenFlags::read()).unwrap().0;
+    assert_eq!(
+        &provider.read(handle.id(), 0, 100).unwrap()[..],
+        b"written raw then zstd"
+    );
+}
+
+#[test]
+fn compression_config_parsing() {
+    let cfg: PageFsConfig = serde_json::from_str(r#"{"compression": "zstd"}"#).unwrap();
+    assert_eq!(cfg.compression, Compression::Zstd);
+
+    let cfg: PageFsConfig = serde_json::from_str("{}").unwrap();
+    assert_eq!(cfg.compression, Compression::None);
+}
+
+/// Wraps `InMemoryKv` and counts `set` calls on page keys, page values
+/// fetched, the `get` and `get_many` calls that fetched them, and `get`
+/// calls on inode keys.
+struct CountingKv {
+    inner: InMemoryKv,
+    page_sets: std::sync::Arc<std::sync::atomic::AtomicUsize>,
+    page_gets: std::sync::Arc<std::sync::atomic::AtomicUsize>,
+    page_batches: std::sync::Arc<std::sync::atomic::AtomicUsize>,
+    inode_gets: std::sync::Arc<std::sync::atomic::AtomicUsize>,
+}
+
+impl KvBackend for CountingKv {
+    fn get(&self, key: &[u8]) -> Option<Vec<u8>> {
+        match key.first() {
+            Some(b'P') => {
+                self.page_batches
+                    .fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                self.page_gets
+                    .fetch_add(1, std::sync::atomic::Ordering::SeqCst)
+            }
+            Some(b'I') => self
+                .inode_gets
+                .fetch_add(1, std::sync::atomic::Ordering::SeqCst),
+            _ => 0,
+        };
+        self.inner.get(key)
+    }
+
+    fn get_many(&self, keys: &[Vec<u8>]) -> Vec<Option<Vec<u8>>> {
+        let pages = keys.iter().filter(|key| key.first() == Some(&b'P')).count();
+        if pages > 0 {
+            self.page_batches
+                .fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            self.page_gets
+                .fetch_add(pages, std::sync::atomic::Ordering::SeqCst);
         }
+        self.inner.get_many(keys)
+    }
 
-        backend.delete(b"prefix:a");
-        backend.delete(b"prefix:b");
-        backend.delete(b"prefix:c");
-        backend.delete(b"other:x");
+    fn set(&self, key: &[u8], value: &[u8]) {
+        if key.first() == Some(&b'P') {
+            self.page_sets
+                .fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        }
+        self.inner.set(key, value);
     }
 
-    #[test]
-    #[ignore]
-    fn s3_integration_full_pagefs() {
-        let bucket = std::env::var("FS9_TEST_S3_BUCKET")
-            .expect("FS9_TEST_S3_BUCKET env var required for S3 integration tests");
-        let prefix = std::env::var("FS9_TEST_S3_PREFIX")
-            .unwrap_or_else(|_| format!("pagefs-test-{}", std::process::id()));
+    fn scan(&self, prefix: &[u8]) -> Vec<(Vec<u8>, Vec<u8>)> {
+        self.inner.scan(prefix)
+    }
 
-        let backend = Box::new(S3KvBackend::new(bucket, prefix));
-        let provider = PageFsProvider::new(backend);
+    fn delete(&self, key: &[u8]) {
+        self.inner.delete(key);
+    }
 
-        let info = provider.stat("/").unwrap();
-        assert_eq!(info.file_type, FileType::Directory);
+    fn scan_after(
+        &self,
+        prefix: &[u8],
+        start_after: &[u8],
+        limit: usize,
+    ) -> Vec<(Vec<u8>, Vec<u8>)> {
+        self.inner.scan_after(prefix, start_after, limit)
+    }
+}
 
-        let handle = provider
-            .open("/test.txt", OpenFlags::create_file())
-            .unwrap();
-        provider.write(handle.id(), 0, b"Hello S3 PageFS!").unwrap();
-        provider.close(handle.id()).unwrap();
+fn counting_provider() -> (
+    PageFsProvider,
+    std::sync::Arc<std::sync::atomic::AtomicUsize>,
+) {
+    let page_sets = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+    let kv = CountingKv {
+        inner: InMemoryKv::new(),
+        page_sets: page_sets.clone(),
+        page_gets: std::sync::Arc::default(),
+        page_batches: std::sync::Arc::default(),
+        inode_gets: std::sync::Arc::default(),
+    };
+    (PageFsProvider::new(Box::new(kv)).unwrap(), page_sets)
+}
 
-        let handle = provider.open("/test.txt", OpenFlags::read()).unwrap();
-        let data = provider.read(handle.id(), 0, 100).unwrap();
-        assert_eq!(&data[..], b"Hello S3 PageFS!");
-        provider.close(handle.id()).unwrap();
+fn page_sets_since(counter: &std::sync::atomic::AtomicUsize, start: usize) -> usize {
+    counter.load(std::sync::atomic::Ordering::SeqCst) - start
+}
 
-        provider.remove("/test.txt").unwrap();
-        assert!(provider.stat("/test.txt").is_err());
+#[test]
+fn small_writes_flush_once_per_page_on_close() {
+    let (provider, page_sets) = counting_provider();
+
+    let handle = provider
+        .open("/seq.bin", OpenFlags::create_file())
+        .unwrap()
+        .0;
+    let start = page_sets.load(std::sync::atomic::Ordering::SeqCst);
+
+    let chunk = [7u8; 512];
+    let total = PAGE_SIZE * 3;
+    for offset in (0..total).step_by(chunk.len()) {
+        provider.write(handle.id(), offset as u64, &chunk).unwrap();
+    }
+    assert_eq!(page_sets_since(&page_sets, start), 0);
+
+    provider.close(handle.id()).unwrap();
+    assert_eq!(page_sets_since(&page_sets, start), 3);
+
+    let handle = provider.open("/seq.bin", OpenFlags::read()).unwrap().0;
+    let data = provider.read(handle.id(), 0, total).unwrap();
+    assert_eq!(data.len(), total);
+    assert!(data.iter().all(|&b| b == 7));
+}
+
+#[test]
+fn reads_see_dirty_pages_before_flush() {
+    let (provider, page_sets) = counting_provider();
+
+    let writer = provider
+        .open("/dirty.txt", OpenFlags::create_file())
+        .unwrap()
+        .0;
+    let start = page_sets.load(std::sync::atomic::Ordering::SeqCst);
+    provider.write(writer.id(), 0, b"not yet flushed").unwrap();
+
+    let reader = provider.open("/dirty.txt", OpenFlags::read()).unwrap().0;
+    let data = provider.read(reader.id(), 0, 100).unwrap();
+    assert_eq!(&data[..], b"not yet flushed");
+    assert_eq!(page_sets_since(&page_sets, start), 0);
+}
+
+#[test]
+fn write_cache_flushes_when_over_budget() {
+    let (provider, page_sets) = counting_provider();
+    let provider = provider.with_write_cache(PAGE_SIZE * 2);
+
+    let handle = provider
+        .open("/big.bin", OpenFlags::create_file())
+        .unwrap()
+        .0;
+    let start = page_sets.load(std::sync::atomic::Ordering::SeqCst);
+
+    provider
+        .write(handle.id(), 0, &vec![1u8; PAGE_SIZE * 2])
+        .unwrap();
+    assert_eq!(page_sets_since(&page_sets, start), 0);
+
+    provider
+        .write(handle.id(), (PAGE_SIZE * 2) as u64, b"third page")
+        .unwrap();
+    assert_eq!(page_sets_since(&page_sets, start), 3);
+
+    provider.close(handle.id()).unwrap();
+    assert_eq!(page_sets_since(&page_sets, start), 3);
+}
+
+#[test]
+fn fsync_flushes_dirty_pages() {
+    let (provider, page_sets) = counting_provider();
+
+    let handle = provider
+        .open("/sync.txt", OpenFlags::create_file())
+        .unwrap()
+        .0;
+    let start = page_sets.load(std::sync::atomic::Ordering::SeqCst);
+    provider.write(handle.id(), 0, b"durable").unwrap();
+
+    provider.fsync(handle.id()).unwrap();
+    assert_eq!(page_sets_since(&page_sets, start), 1);
+
+    let inode_id = provider.resolve_path("/sync.txt", true).unwrap().0;
+    let stored = provider.kv.get(&keys::page(inode_id, 0)).unwrap();
+    assert_eq!(&stored[..7], b"durable");
+
+    provider.close(handle.id()).unwrap();
+    assert_eq!(page_sets_since(&page_sets, start), 1);
+
+    assert!(matches!(
+        provider.fsync(handle.id()),
+        Err(FsError::InvalidHandle(_))
+    ));
+}
+
+#[test]
+fn ffi_fsync_flushes_dirty_pages() {
+    let (provider, page_sets) = counting_provider();
+    let handle = provider
+        .open("/sync.txt", OpenFlags::create_file())
+        .unwrap()
+        .0;
+    let start = page_sets.load(std::sync::atomic::Ordering::SeqCst);
+    provider.write(handle.id(), 0, b"durable").unwrap();
+    assert_eq!(page_sets_since(&page_sets, start), 0);
+
+    unsafe {
+        let vtable = &*ffi::fs9_plugin_vtable();
+        let caps = fs9_sdk::Capabilities::from_bits_truncate((vtable.get_capabilities)(
+            std::ptr::null_mut(),
+        ));
+        assert!(caps.supports_fsync());
+
+        let raw = Box::into_raw(Box::new(provider)).cast::<std::ffi::c_void>();
+        let result = (vtable.fsync)(raw, handle.id(), 1);
+        assert_eq!(result.code, fs9_sdk_ffi::FS9_OK);
+        assert_eq!(page_sets_since(&page_sets, start), 1);
+        (vtable.destroy)(raw);
+    }
+}
+
+#[test]
+fn remove_discards_dirty_pages() {
+    let provider = create_provider();
+
+    let handle = provider
+        .open("/gone.txt", OpenFlags::create_file())
+        .unwrap()
+        .0;
+    provider.write(handle.id(), 0, b"never flushed").unwrap();
+    let inode_id = provider.resolve_path("/gone.txt", true).unwrap().0;
+
+    provider.remove("/gone.txt").unwrap();
+    provider.flush();
+
+    assert!(provider.read_page(inode_id, 0).is_none());
+    assert!(provider.kv.scan(&keys::page_prefix(inode_id)).is_empty());
+}
+
+#[test]
+fn write_cache_disabled_writes_through() {
+    let (provider, page_sets) = counting_provider();
+    let provider = provider.with_write_cache(0);
+
+    let handle = provider
+        .open("/through.txt", OpenFlags::create_file())
+        .unwrap()
+        .0;
+    let start = page_sets.load(std::sync::atomic::Ordering::SeqCst);
+    provider.write(handle.id(), 0, b"a").unwrap();
+    provider.write(handle.id(), 1, b"b").unwrap();
+    assert_eq!(page_sets_since(&page_sets, start), 2);
+}
+
+#[cfg(feature = "s3")]
+mod s3_tests {
+    use super::*;
+
+    #[test]
+    fn s3_make_key_without_prefix() {
+        let backend = S3KvBackend {
+            client: create_mock_client(),
+            bucket: "test-bucket".to_string(),
+            prefix: String::new(),
+            runtime: tokio::runtime::Runtime::new().unwrap(),
+        };
+
+        assert_eq!(backend.make_key(b"S"), "53");
+        assert_eq!(
+            backend.make_key(b"I\x00\x00\x00\x00\x00\x00\x00\x01"),
+            "490000000000000001"
+        );
+        assert_eq!(backend.make_key(b"hello"), "68656c6c6f");
+    }
+
+    #[test]
+    fn s3_make_key_with_prefix() {
+        let backend = S3KvBackend {
+            client: create_mock_client(),
+            bucket: "test-bucket".to_string(),
+            prefix: "pagefs".to_string(),
+            runtime: tokio::runtime::Runtime::new().unwrap(),
+        };
+
+        assert_eq!(backend.make_key(b"S"), "pagefs/53");
+        assert_eq!(backend.make_key(b"hello"), "pagefs/68656c6c6f");
     }
+
+    #[test]
+    fn s3_parse_key_without_prefix() {
+        let backend = S3KvBackend {
+            client: create_mock_client(),
+            bucket: "test-bucket".to_string(),
+            prefix: String::new(),
+            runtime: tokio::runtime::Runtime::new().unwrap(),
+        };
+
+        assert_eq!(backend.parse_key("53"), Some(vec![0x53]));
+        assert_eq!(backend.parse_key("68656c6c6f"), Some(b"hello".to_vec()));
+    }
+
+    #[test]
+    fn s3_parse_key_with_prefix() {
+        let backend = S3KvBackend {
+            client: create_mock_client(),
+            bucket: "test-bucket".to_string(),
+            prefix: "pagefs".to_string(),
+            runtime: tokio::runtime::Runtime::new().unwrap(),
+        };
+
+        assert_eq!(backend.parse_key("pagefs/53"), Some(vec![0x53]));
+        assert_eq!(
+            backend.parse_key("pagefs/68656c6c6f"),
+            Some(b"hello".to_vec())
+        );
+        assert_eq!(backend.parse_key("other/53"), None);
+    }
+
+    #[test]
+    fn s3_key_roundtrip() {
+        let backend = S3KvBackend {
+            client: create_mock_client(),
+            bucket: "test-bucket".to_string(),
+            prefix: "test".to_string(),
+            runtime: tokio::runtime::Runtime::new().unwrap(),
+        };
+
+        let test_keys: Vec<&[u8]> = vec![
+            b"S",
+            b"I\x00\x00\x00\x00\x00\x00\x00\x01",
+            b"D\x00\x00\x00\x00\x00\x00\x00\x01:file.txt",
+            b"P\x00\x00\x00\x00\x00\x00\x00\x02:\x00\x00\x00\x00\x00\x00\x00\x00",
+        ];
+
+        for key in test_keys {
+            let encoded = backend.make_key(key);
+            let decoded = backend.parse_key(&encoded);
+            assert_eq!(
+                decoded,
+                Some(key.to_vec()),
+                "Roundtrip failed for {:?}",
+                key
+            );
+        }
+    }
+
+    fn create_mock_client() -> aws_sdk_s3::Client {
+        let config = aws_sdk_s3::Config::builder()
+            .behavior_version(aws_sdk_s3::config::BehaviorVersion::latest())
+            .region(aws_sdk_s3::config::Region::new("us-east-1"))
+            .build();
+        aws_sdk_s3::Client::from_conf(config)
+    }
+
+    #[test]
+    #[ignore]
+    fn s3_integration_basic_operations() {
+        let bucket = std::env::var("FS9_TEST_S3_BUCKET")
+            .expect("FS9_TEST_S3_BUCKET env var required for S3 integration tests");
+        let prefix = std::env::var("FS9_TEST_S3_PREFIX")
+            .unwrap_or_else(|_| format!("pagefs-test-{}", std::process::id()));
+
+        let backend = S3KvBackend::new(bucket, prefix);
+
+        let test_key = b"test-key";
+        let test_value = b"test-value-12345";
+
+        backend.set(test_key, test_value);
+
+        let retrieved = backend.get(test_key);
+        assert_eq!(retrieved, Some(test_value.to_vec()));
+
+        backend.delete(test_key);
+
+        let after_delete = backend.get(test_key);
+        assert_eq!(after_delete, None);
+    }
+
+    #[test]
+    #[ignore]
+    fn s3_integration_scan() {
+        let bucket = std::env::var("FS9_TEST_S3_BUCKET")
+            .expect("FS9_TEST_S3_BUCKET env var required for S3 integration tests");
+        let prefix = std::env::var("FS9_TEST_S3_PREFIX")
+            .unwrap_or_else(|_| format!("pagefs-test-{}", std::process::id()));
+
+        let backend = S3KvBackend::new(bucket, prefix);
+
+        backend.set(b"prefix:a", b"value-a");
+        backend.set(b"prefix:b", b"value-b");
+        backend.set(b"prefix:c", b"value-c");
+        backend.set(b"other:x", b"value-x");
+
+        let results = backend.scan(b"prefix:");
+        assert_eq!(results.len(), 3);
+
+        for (k, _) in &results {
+            assert!(k.starts_with(b"prefix:"));
+        }
+
+        backend.delete(b"prefix:a");
+        backend.delete(b"prefix:b");
+        backend.delete(b"prefix:c");
+        backend.delete(b"other:x");
+    }
+
+    #[test]
+    #[ignore]
+    fn s3_integration_full_pagefs() {
+        let bucket = std::env::var("FS9_TEST_S3_BUCKET")
+            .expect("FS9_TEST_S3_BUCKET env var required for S3 integration tests");
+        let prefix = std::env::var("FS9_TEST_S3_PREFIX")
+            .unwrap_or_else(|_| format!("pagefs-test-{}", std::process::id()));
+
+        let backend = Box::new(S3KvBackend::new(bucket, prefix));
+        let provider = PageFsProvider::new(backend).unwrap();
+
+        let info = provider.stat("/").unwrap();
+        assert_eq!(info.file_type, FileType::Directory);
+
+        let handle = provider
+            .open("/test.txt", OpenFlags::create_file())
+            .unwrap()
+            .0;
+        provider.write(handle.id(), 0, b"Hello S3 PageFS!").unwrap();
+        provider.close(handle.id()).unwrap();
+
+        let handle = provider.open("/test.txt", OpenFlags::read()).unwrap().0;
+        let data = provider.read(handle.id(), 0, 100).unwrap();
+        assert_eq!(&data[..], b"Hello S3 PageFS!");
+        provider.close(handle.id()).unwrap();
+
+        provider.remove("/test.txt").unwrap();
+        assert!(provider.stat("/test.txt").is_err());
+    }
+}
+
+fn write_file(provider: &PageFsProvider, path: &str, data: &[u8]) {
+    let (handle, _) = provider.open(path, OpenFlags::create_file()).unwrap();
+    provider.write(handle.id(), 0, data).unwrap();
+    provider.close(handle.id()).unwrap();
+}
+
+fn make_symlink(provider: &PageFsProvider, path: &str, target: &str) {
+    let (handle, info) = provider.open(path, OpenFlags::create_symlink()).unwrap();
+    assert_eq!(info.file_type, FileType::Symlink);
+    provider.write(handle.id(), 0, target.as_bytes()).unwrap();
+    provider.close(handle.id()).unwrap();
+}
+
+#[test]
+fn create_and_stat_symlink() {
+    let provider = create_provider();
+    write_file(&provider, "/target.txt", b"hello");
+    make_symlink(&provider, "/link", "/target.txt");
+
+    let info = provider.stat("/link").unwrap();
+    assert_eq!(info.file_type, FileType::Symlink);
+    assert_eq!(info.symlink_target.as_deref(), Some("/target.txt"));
+    assert_eq!(info.size, "/target.txt".len() as u64);
+
+    let entries = provider.readdir("/").unwrap();
+    let link = entries.iter().find(|e| e.path == "/link").unwrap();
+    assert_eq!(link.file_type, FileType::Symlink);
+    assert_eq!(link.symlink_target.as_deref(), Some("/target.txt"));
+}
+
+#[test]
+fn open_follows_symlink() {
+    let provider = create_provider();
+    provider.open("/dir", OpenFlags::create_dir()).unwrap();
+    write_file(&provider, "/dir/target.txt", b"through the link");
+    make_symlink(&provider, "/dir/rel", "target.txt");
+    make_symlink(&provider, "/abs", "/dir/rel");
+    make_symlink(&provider, "/dirlink", "dir");
+
+    for path in ["/dir/rel", "/abs", "/dirlink/target.txt"] {
+        let (handle, info) = provider.open(path, OpenFlags::read()).unwrap();
+        assert_eq!(info.file_type, FileType::Regular);
+        let data = provider.read(handle.id(), 0, 100).unwrap();
+        assert_eq!(&data[..], b"through the link", "reading {path}");
+        provider.close(handle.id()).unwrap();
+    }
+
+    let entries = provider.readdir("/dirlink").unwrap();
+    assert_eq!(entries.len(), 2);
+}
+
+#[test]
+fn read_symlink_returns_target() {
+    let provider = create_provider();
+    make_symlink(&provider, "/link", "/some/where");
+
+    let flags = OpenFlags {
+        read: true,
+        symlink: true,
+        ..Default::default()
+    };
+    let (handle, _) = provider.open("/link", flags).unwrap();
+    let data = provider.read(handle.id(), 0, 100).unwrap();
+    assert_eq!(&data[..], b"/some/where");
+    provider.close(handle.id()).unwrap();
+
+    // The target doesn't exist, so following the link fails.
+    assert!(matches!(
+        provider.open("/link", OpenFlags::read()),
+        Err(FsError::NotFound(_))
+    ));
+}
+
+#[test]
+fn wstat_creates_symlink() {
+    let provider = create_provider();
+    write_file(&provider, "/target.txt", b"data");
+
+    provider
+        .wstat("/link", &StatChanges::symlink("target.txt"))
+        .unwrap();
+    let info = provider.stat("/link").unwrap();
+    assert_eq!(info.symlink_target.as_deref(), Some("target.txt"));
+
+    assert!(matches!(
+        provider.wstat("/link", &StatChanges::symlink("other")),
+        Err(FsError::AlreadyExists(_))
+    ));
+}
+
+#[test]
+fn symlink_targets_are_capped_at_path_max() {
+    let provider = create_provider();
+    let (handle, _) = provider.open("/link", OpenFlags::create_symlink()).unwrap();
+    for offset in [MAX_SYMLINK_TARGET_LEN as u64, u64::MAX] {
+        assert!(matches!(
+            provider.write(handle.id(), offset, b"x"),
+            Err(FsError::InvalidArgument(_))
+        ));
+    }
+    let full = "a".repeat(MAX_SYMLINK_TARGET_LEN);
+    provider.write(handle.id(), 0, full.as_bytes()).unwrap();
+    provider.close(handle.id()).unwrap();
+    assert_eq!(provider.stat("/link").unwrap().size, full.len() as u64);
+
+    let too_long = "a".repeat(MAX_SYMLINK_TARGET_LEN + 1);
+    assert!(matches!(
+        provider.wstat("/other", &StatChanges::symlink(too_long)),
+        Err(FsError::InvalidArgument(_))
+    ));
+}
+
+#[test]
+fn remove_symlink_keeps_target() {
+    let provider = create_provider();
+    write_file(&provider, "/target.txt", b"data");
+    make_symlink(&provider, "/link", "/target.txt");
+
+    provider.remove("/link").unwrap();
+    assert!(provider.stat("/link").is_err());
+    assert_eq!(provider.stat("/target.txt").unwrap().size, 4);
+}
+
+#[test]
+fn symlink_loop_is_detected() {
+    let provider = create_provider();
+    make_symlink(&provider, "/a", "/b");
+    make_symlink(&provider, "/b", "/a");
+    make_symlink(&provider, "/self", "self");
+
+    for path in ["/a", "/self", "/a/child"] {
+        assert!(
+            matches!(
+                provider.open(path, OpenFlags::read()),
+                Err(FsError::InvalidArgument(_))
+            ),
+            "opening {path}"
+        );
+    }
+
+    // The links themselves are still visible without following them.
+    assert_eq!(provider.stat("/a").unwrap().file_type, FileType::Symlink);
+}
+
+fn stored_page_count(provider: &PageFsProvider, inode_id: u64) -> usize {
+    provider.kv.scan(&keys::page_prefix(inode_id)).len()
+}
+
+#[test]
+fn hard_link_shares_contents() {
+    let provider = create_provider();
+    write_file(&provider, "/original.txt", b"first");
+    provider.open("/dir", OpenFlags::create_dir()).unwrap();
+    provider.link("/original.txt", "/dir/alias.txt").unwrap();
+
+    let inode = provider.resolve_path("/original.txt", false).unwrap();
+    let alias = provider.resolve_path("/dir/alias.txt", false).unwrap();
+    assert_eq!(inode.0, alias.0);
+    assert_eq!(alias.1.nlink, 2);
+
+    let (handle, _) = provider.open("/dir/alias.txt", OpenFlags::write()).unwrap();
+    provider.write(handle.id(), 0, b"FIRST, edited").unwrap();
+    provider.close(handle.id()).unwrap();
+
+    let (handle, info) = provider.open("/original.txt", OpenFlags::read()).unwrap();
+    assert_eq!(info.size, 13);
+    let data = provider.read(handle.id(), 0, 100).unwrap();
+    assert_eq!(&data[..], b"FIRST, edited");
+    provider.close(handle.id()).unwrap();
+}
+
+#[test]
+fn pages_deleted_only_on_last_unlink() {
+    let provider = create_provider();
+    write_file(&provider, "/a.txt", &vec![7u8; PAGE_SIZE + 1]);
+    provider.link("/a.txt", "/b.txt").unwrap();
+    let inode_id = provider.resolve_path("/a.txt", false).unwrap().0;
+    assert_eq!(stored_page_count(&provider, inode_id), 2);
+
+    provider.remove("/a.txt").unwrap();
+    assert!(provider.stat("/a.txt").is_err());
+    assert_eq!(stored_page_count(&provider, inode_id), 2);
+    let inode = provider.load_inode(inode_id).unwrap();
+    assert_eq!(inode.nlink, 1);
+    assert_eq!(provider.stat("/b.txt").unwrap().size, PAGE_SIZE as u64 + 1);
+
+    provider.remove("/b.txt").unwrap();
+    assert_eq!(stored_page_count(&provider, inode_id), 0);
+    assert!(provider.load_inode(inode_id).is_none());
+}
+
+#[test]
+fn rename_over_hard_link_keeps_other_name() {
+    let provider = create_provider();
+    write_file(&provider, "/a.txt", b"shared");
+    provider.link("/a.txt", "/b.txt").unwrap();
+    write_file(&provider, "/c.txt", b"other");
+
+    provider
+        .wstat("/c.txt", &StatChanges::rename("/b.txt"))
+        .unwrap();
+
+    let inode_id = provider.resolve_path("/a.txt", false).unwrap().0;
+    assert_eq!(provider.load_inode(inode_id).unwrap().nlink, 1);
+    assert_eq!(stored_page_count(&provider, inode_id), 1);
+    assert_eq!(provider.stat("/b.txt").unwrap().size, 5);
+}
+
+#[test]
+fn link_rejects_directories_and_existing_names() {
+    let provider = create_provider();
+    provider.open("/dir", OpenFlags::create_dir()).unwrap();
+    write_file(&provider, "/a.txt", b"a");
+    write_file(&provider, "/b.txt", b"b");
+
+    assert!(matches!(
+        provider.link("/dir", "/dir2"),
+        Err(FsError::PermissionDenied(_))
+    ));
+    assert!(matches!(
+        provider.link("/a.txt", "/b.txt"),
+        Err(FsError::AlreadyExists(_))
+    ));
+    assert!(matches!(
+        provider.link("/missing.txt", "/c.txt"),
+        Err(FsError::NotFound(_))
+    ));
+}
+
+const LARGE_PAGE: usize = 64 * 1024;
+
+fn large_page_provider() -> PageFsProvider {
+    PageFsProvider::try_with_config(Box::new(InMemoryKv::new()), 0, 0, LARGE_PAGE).unwrap()
+}
+
+#[test]
+fn large_page_write_across_page_boundary() {
+    let provider = large_page_provider();
+    assert_eq!(provider.page_size(), LARGE_PAGE);
+
+    let (handle, _) = provider
+        .open("/cross.txt", OpenFlags::create_file())
+        .unwrap();
+    // Fits in one 64KB page, though it would have spanned two 16KB pages.
+    let small: Vec<u8> = (0..PAGE_SIZE + 1000).map(|i| (i % 256) as u8).collect();
+    provider.write(handle.id(), 0, &small).unwrap();
+    provider.close(handle.id()).unwrap();
+    let (inode_id, inode) = provider.resolve_path("/cross.txt", true).unwrap();
+    assert_eq!(inode.page_count, 1);
+
+    let (handle, _) = provider.open("/cross.txt", OpenFlags::write()).unwrap();
+    let data: Vec<u8> = (0..LARGE_PAGE + 1000).map(|i| (i % 251) as u8).collect();
+    provider.write(handle.id(), 0, &data).unwrap();
+    provider.close(handle.id()).unwrap();
+
+    let inode = provider.load_inode(inode_id).unwrap();
+    assert_eq!(inode.page_count, 2);
+    assert_eq!(provider.read_page(inode_id, 0).unwrap().len(), LARGE_PAGE);
+    assert_eq!(
+        provider.read_page(inode_id, 1).unwrap()[..1000],
+        data[LARGE_PAGE..]
+    );
+
+    let (handle, _) = provider.open("/cross.txt", OpenFlags::read()).unwrap();
+    let read = provider
+        .read(handle.id(), LARGE_PAGE as u64 - 10, 20)
+        .unwrap();
+    assert_eq!(&read[..], &data[LARGE_PAGE - 10..LARGE_PAGE + 10]);
+    provider.close(handle.id()).unwrap();
+}
+
+#[test]
+fn large_page_truncate() {
+    let provider = large_page_provider();
+    let (handle, _) = provider.open("/t.bin", OpenFlags::create_file()).unwrap();
+    provider
+        .write(handle.id(), 0, &vec![9u8; LARGE_PAGE * 2])
+        .unwrap();
+    provider.close(handle.id()).unwrap();
+
+    provider
+        .wstat("/t.bin", &StatChanges::truncate(LARGE_PAGE as u64 + 5))
+        .unwrap();
+    let (inode_id, inode) = provider.resolve_path("/t.bin", true).unwrap();
+    assert_eq!(inode.page_count, 2);
+    let tail = provider.read_page(inode_id, 1).unwrap();
+    assert_eq!(&tail[..5], &[9u8; 5]);
+    assert!(tail[5..].iter().all(|&b| b == 0));
+}
+
+/// Lets several providers in a row open the same in-memory store.
+struct SharedKv(std::sync::Arc<InMemoryKv>);
+
+impl KvBackend for SharedKv {
+    fn get(&self, key: &[u8]) -> Option<Vec<u8>> {
+        self.0.get(key)
+    }
+
+    fn set(&self, key: &[u8], value: &[u8]) {
+        self.0.set(key, value);
+    }
+
+    fn scan(&self, prefix: &[u8]) -> Vec<(Vec<u8>, Vec<u8>)> {
+        self.0.scan(prefix)
+    }
+
+    fn delete(&self, key: &[u8]) {
+        self.0.delete(key);
+    }
+}
+
+#[test]
+fn page_size_mismatch_is_rejected() {
+    let kv = std::sync::Arc::new(InMemoryKv::new());
+    drop(
+        PageFsProvider::try_with_config(Box::new(SharedKv(kv.clone())), 0, 0, LARGE_PAGE).unwrap(),
+    );
+
+    assert!(matches!(
+        PageFsProvider::try_with_config(Box::new(SharedKv(kv.clone())), 0, 0, PAGE_SIZE),
+        Err(FsError::InvalidArgument(_))
+    ));
+    assert!(PageFsProvider::try_with_config(Box::new(SharedKv(kv)), 0, 0, LARGE_PAGE).is_ok());
+}
+
+#[test]
+fn invalid_page_size_is_rejected() {
+    for page_size in [0, 1000, 1024, 48 * 1024] {
+        assert!(
+            PageFsProvider::try_with_config(Box::new(InMemoryKv::new()), 0, 0, page_size).is_err(),
+            "page size {page_size}"
+        );
+    }
+}
+
+#[test]
+fn page_size_config_parsing() {
+    let cfg: PageFsConfig = serde_json::from_str("{}").unwrap();
+    assert_eq!(cfg.page_size, PAGE_SIZE);
+    let cfg: PageFsConfig = serde_json::from_str(r#"{"page_size": 65536}"#).unwrap();
+    assert_eq!(cfg.page_size, LARGE_PAGE);
+}
+
+/// A provider holding an `pages`-page file at /seq.bin, plus counters of
+/// page reads and batched page fetches from the backend.
+fn read_ahead_provider(
+    read_ahead: usize,
+    pages: usize,
+) -> (
+    PageFsProvider,
+    std::sync::Arc<std::sync::atomic::AtomicUsize>,
+    std::sync::Arc<std::sync::atomic::AtomicUsize>,
+) {
+    let page_gets = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+    let page_batches = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+    let kv = CountingKv {
+        inner: InMemoryKv::new(),
+        page_sets: std::sync::Arc::default(),
+        page_gets: page_gets.clone(),
+        page_batches: page_batches.clone(),
+        inode_gets: std::sync::Arc::default(),
+    };
+    let provider = PageFsProvider::new(Box::new(kv))
+        .unwrap()
+        .with_read_ahead(read_ahead);
+    write_file(&provider, "/seq.bin", &noise(PAGE_SIZE * pages));
+    page_gets.store(0, std::sync::atomic::Ordering::SeqCst);
+    page_batches.store(0, std::sync::atomic::Ordering::SeqCst);
+    (provider, page_gets, page_batches)
+}
+
+fn read_pages(provider: &PageFsProvider, handle: u64, pages: &[usize]) -> Vec<u8> {
+    let mut out = Vec::new();
+    for &page in pages {
+        let data = provider
+            .read(handle, (page * PAGE_SIZE) as u64, PAGE_SIZE)
+            .unwrap();
+        out.extend_from_slice(&data);
+    }
+    out
+}
+
+#[test]
+fn multi_page_read_fetches_pages_in_one_batch() {
+    let page_gets = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+    let page_batches = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+    let kv = CountingKv {
+        inner: InMemoryKv::new(),
+        page_sets: std::sync::Arc::default(),
+        page_gets: page_gets.clone(),
+        page_batches: page_batches.clone(),
+        inode_gets: std::sync::Arc::default(),
+    };
+    let provider = PageFsProvider::new(Box::new(kv)).unwrap();
+    // Ten whole pages and a partial last one.
+    let data = noise(PAGE_SIZE * 10 + 100);
+    write_file(&provider, "/big.bin", &data);
+    let (handle, _) = provider.open("/big.bin", OpenFlags::read()).unwrap();
+    page_gets.store(0, std::sync::atomic::Ordering::SeqCst);
+    page_batches.store(0, std::sync::atomic::Ordering::SeqCst);
+
+    let read = provider.read(handle.id(), 0, data.len() + 10).unwrap();
+    assert_eq!(read, data);
+    assert_eq!(page_gets.load(std::sync::atomic::Ordering::SeqCst), 11);
+    assert_eq!(page_batches.load(std::sync::atomic::Ordering::SeqCst), 1);
+
+    // A ranged read starting and ending part way through pages.
+    let start = PAGE_SIZE * 3 + 17;
+    let end = PAGE_SIZE * 7 + 5;
+    let read = provider
+        .read(handle.id(), start as u64, end - start)
+        .unwrap();
+    assert_eq!(read, data[start..end]);
+    assert_eq!(page_gets.load(std::sync::atomic::Ordering::SeqCst), 16);
+    assert_eq!(page_batches.load(std::sync::atomic::Ordering::SeqCst), 2);
+
+    // Sparse pages that were never written read back as zeros.
+    let (sparse, _) = provider
+        .open("/sparse.bin", OpenFlags::create_file())
+        .unwrap();
+    provider
+        .write(sparse.id(), (PAGE_SIZE * 3) as u64, b"end")
+        .unwrap();
+    provider.close(sparse.id()).unwrap();
+    let mut expected = vec![0u8; PAGE_SIZE * 3];
+    expected.extend_from_slice(b"end");
+    assert_eq!(read_file(&provider, "/sparse.bin"), expected);
+    provider.close(handle.id()).unwrap();
+}
+
+#[test]
+fn read_ahead_prefetches_on_sequential_reads() {
+    let (provider, page_gets, page_batches) = read_ahead_provider(4, 8);
+    let (handle, _) = provider.open("/seq.bin", OpenFlags::read()).unwrap();
+
+    // The first read has nothing to be sequential with.
+    read_pages(&provider, handle.id(), &[0]);
+    assert_eq!(page_gets.load(std::sync::atomic::Ordering::SeqCst), 1);
+
+    // The second continues the first: pages 2..=5 are fetched ahead, all
+    // in one batch.
+    let batches = page_batches.load(std::sync::atomic::Ordering::SeqCst);
+    read_pages(&provider, handle.id(), &[1]);
+    assert_eq!(page_gets.load(std::sync::atomic::Ordering::SeqCst), 6);
+    assert_eq!(
+        page_batches.load(std::sync::atomic::Ordering::SeqCst),
+        batches + 2
+    );
+
+    // The rest of the file is then read exactly once, never past its end.
+    let rest = read_pages(&provider, handle.id(), &[2, 3, 4, 5, 6, 7]);
+    assert_eq!(page_gets.load(std::sync::atomic::Ordering::SeqCst), 8);
+    assert_eq!(rest, noise(PAGE_SIZE * 8)[PAGE_SIZE * 2..]);
+    provider.close(handle.id()).unwrap();
+}
+
+#[test]
+fn read_ahead_skips_random_and_backward_reads() {
+    let (provider, page_gets, _) = read_ahead_provider(4, 8);
+    let (handle, _) = provider.open("/seq.bin", OpenFlags::read()).unwrap();
+
+    read_pages(&provider, handle.id(), &[5, 2, 6, 0]);
+    assert_eq!(page_gets.load(std::sync::atomic::Ordering::SeqCst), 4);
+
+    // Jumping forward and then back is not sequential either.
+    read_pages(&provider, handle.id(), &[3, 1]);
+    assert_eq!(page_gets.load(std::sync::atomic::Ordering::SeqCst), 6);
+
+    // Continuing from page 1 is, and prefetches pages 3..=6.
+    read_pages(&provider, handle.id(), &[2]);
+    assert_eq!(page_gets.load(std::sync::atomic::Ordering::SeqCst), 11);
+    provider.close(handle.id()).unwrap();
+}
+
+#[test]
+fn read_ahead_disabled_by_default() {
+    let (provider, page_gets, _) = read_ahead_provider(0, 8);
+    let (handle, _) = provider.open("/seq.bin", OpenFlags::read()).unwrap();
+    read_pages(&provider, handle.id(), &[0, 1, 2]);
+    assert_eq!(page_gets.load(std::sync::atomic::Ordering::SeqCst), 3);
+    provider.close(handle.id()).unwrap();
+
+    let cfg: PageFsConfig = serde_json::from_str("{}").unwrap();
+    assert_eq!(cfg.read_ahead_pages, 0);
+}
+
+#[test]
+fn prefetched_pages_invalidated_by_writes() {
+    let (provider, _, _) = read_ahead_provider(4, 4);
+    let (reader, _) = provider.open("/seq.bin", OpenFlags::read()).unwrap();
+    read_pages(&provider, reader.id(), &[0, 1]);
+
+    let (writer, _) = provider.open("/seq.bin", OpenFlags::write()).unwrap();
+    provider
+        .write(writer.id(), (PAGE_SIZE * 2) as u64, b"fresh")
+        .unwrap();
+    provider.close(writer.id()).unwrap();
+
+    let data = provider
+        .read(reader.id(), (PAGE_SIZE * 2) as u64, 5)
+        .unwrap();
+    assert_eq!(&data[..], b"fresh");
+    provider.close(reader.id()).unwrap();
+}
+
+fn create_file(provider: &PageFsProvider, path: &str) {
+    let handle = provider.open(path, OpenFlags::create_file()).unwrap().0;
+    provider.close(handle.id()).unwrap();
+}
+
+#[test]
+fn xattrs_round_trip() {
+    let provider = create_provider();
+    create_file(&provider, "/tagged.txt");
+
+    provider
+        .setxattr("/tagged.txt", "user.mime", b"text/plain")
+        .unwrap();
+    provider
+        .setxattr("/tagged.txt", "user.checksum", &[0, 1, 2, 255])
+        .unwrap();
+    provider.setxattr("/tagged.txt", "user.empty", b"").unwrap();
+
+    assert_eq!(
+        provider.listxattr("/tagged.txt").unwrap(),
+        vec!["user.checksum", "user.empty", "user.mime"]
+    );
+    assert_eq!(
+        provider.getxattr("/tagged.txt", "user.mime").unwrap(),
+        b"text/plain"
+    );
+    assert_eq!(
+        provider.getxattr("/tagged.txt", "user.checksum").unwrap(),
+        [0, 1, 2, 255]
+    );
+    assert!(provider
+        .getxattr("/tagged.txt", "user.empty")
+        .unwrap()
+        .is_empty());
+
+    provider
+        .setxattr("/tagged.txt", "user.mime", b"text/markdown")
+        .unwrap();
+    assert_eq!(
+        provider.getxattr("/tagged.txt", "user.mime").unwrap(),
+        b"text/markdown"
+    );
+
+    provider.removexattr("/tagged.txt", "user.mime").unwrap();
+    assert!(matches!(
+        provider.getxattr("/tagged.txt", "user.mime"),
+        Err(FsError::NotFound(_))
+    ));
+    assert!(matches!(
+        provider.removexattr("/tagged.txt", "user.mime"),
+        Err(FsError::NotFound(_))
+    ));
+    assert_eq!(
+        provider.listxattr("/tagged.txt").unwrap(),
+        vec!["user.checksum", "user.empty"]
+    );
+}
+
+#[test]
+fn xattrs_are_per_file() {
+    let provider = create_provider();
+    create_file(&provider, "/a.txt");
+    create_file(&provider, "/b.txt");
+
+    provider.setxattr("/a.txt", "user.owner", b"a").unwrap();
+    assert!(provider.listxattr("/b.txt").unwrap().is_empty());
+    assert!(matches!(
+        provider.getxattr("/b.txt", "user.owner"),
+        Err(FsError::NotFound(_))
+    ));
+    assert!(matches!(
+        provider.setxattr("/missing.txt", "user.owner", b"x"),
+        Err(FsError::NotFound(_))
+    ));
+}
+
+#[test]
+fn xattr_limits_enforced() {
+    let provider = create_provider();
+    create_file(&provider, "/limits.txt");
+
+    let max = vec![7u8; MAX_XATTR_VALUE_SIZE];
+    provider.setxattr("/limits.txt", "user.big", &max).unwrap();
+    assert_eq!(provider.getxattr("/limits.txt", "user.big").unwrap(), max);
+
+    let too_big = vec![7u8; MAX_XATTR_VALUE_SIZE + 1];
+    assert!(matches!(
+        provider.setxattr("/limits.txt", "user.big", &too_big),
+        Err(FsError::InvalidArgument(_))
+    ));
+
+    let long_name = "u".repeat(MAX_XATTR_NAME_LEN + 1);
+    for name in ["", "user.\0nul", long_name.as_str()] {
+        assert!(matches!(
+            provider.setxattr("/limits.txt", name, b"v"),
+            Err(FsError::InvalidArgument(_))
+        ));
+    }
+}
+
+#[test]
+fn remove_deletes_xattrs() {
+    let provider = create_provider();
+    create_file(&provider, "/doomed.txt");
+    provider.setxattr("/doomed.txt", "user.a", b"1").unwrap();
+    provider.setxattr("/doomed.txt", "user.b", b"2").unwrap();
+    let inode_id = provider.resolve_path("/doomed.txt", true).unwrap().0;
+    assert_eq!(provider.kv.scan(&keys::xattr_prefix(inode_id)).len(), 2);
+
+    provider.remove("/doomed.txt").unwrap();
+    assert!(provider.kv.scan(&keys::xattr_prefix(inode_id)).is_empty());
+
+    // A new file under the same name starts with no attributes.
+    create_file(&provider, "/doomed.txt");
+    assert!(provider.listxattr("/doomed.txt").unwrap().is_empty());
+}
+
+#[test]
+fn xattrs_through_ffi() {
+    let provider = create_provider();
+    create_file(&provider, "/ffi.txt");
+    let path = "/ffi.txt";
+    let name = "user.tag";
+
+    unsafe {
+        let vtable = &*ffi::fs9_plugin_vtable();
+        let caps = fs9_sdk::Capabilities::from_bits_truncate((vtable.get_capabilities)(
+            std::ptr::null_mut(),
+        ));
+        assert!(caps.supports_xattr());
+
+        let raw = Box::into_raw(Box::new(provider)).cast::<std::ffi::c_void>();
+        let result = (vtable.setxattr)(
+            raw,
+            path.as_ptr().cast(),
+            path.len(),
+            name.as_ptr().cast(),
+            name.len(),
+            b"blue".as_ptr(),
+            4,
+        );
+        assert_eq!(result.code, fs9_sdk_ffi::FS9_OK);
+
+        let mut value = fs9_sdk_ffi::CBytes::default();
+        let result = (vtable.getxattr)(
+            raw,
+            path.as_ptr().cast(),
+            path.len(),
+            name.as_ptr().cast(),
+            name.len(),
+            &mut value,
+        );
+        assert_eq!(result.code, fs9_sdk_ffi::FS9_OK);
+        assert_eq!(std::slice::from_raw_parts(value.data, value.len), b"blue");
+        fs9_sdk_ffi::fs9_bytes_free(&mut value);
+
+        let mut names = fs9_sdk_ffi::CBytes::default();
+        let result = (vtable.listxattr)(raw, path.as_ptr().cast(), path.len(), &mut names);
+        assert_eq!(result.code, fs9_sdk_ffi::FS9_OK);
+        assert_eq!(
+            std::slice::from_raw_parts(names.data, names.len),
+            b"user.tag\0"
+        );
+        fs9_sdk_ffi::fs9_bytes_free(&mut names);
+
+        (vtable.destroy)(raw);
+    }
+}
+
+fn read_file(provider: &PageFsProvider, path: &str) -> Vec<u8> {
+    let (handle, info) = provider.open(path, OpenFlags::read()).unwrap();
+    let data = provider.read(handle.id(), 0, info.size as usize).unwrap();
+    provider.close(handle.id()).unwrap();
+    data.to_vec()
+}
+
+fn shared_blocks(provider: &PageFsProvider) -> usize {
+    provider.kv.scan(b"B").len()
+}
+
+fn is_shared_page(provider: &PageFsProvider, path: &str, page_num: u64) -> bool {
+    let inode_id = provider.resolve_path(path, true).unwrap().0;
+    let stored = provider.kv.get(&keys::page(inode_id, page_num)).unwrap();
+    snapshot::decode_page_ref(&stored).is_some()
+}
+
+#[test]
+fn clone_shares_pages_until_written() {
+    let provider = create_provider();
+    let original: Vec<u8> = (0..PAGE_SIZE * 3).map(|i| (i % 251) as u8).collect();
+    write_file(&provider, "/orig.bin", &original);
+
+    let id = provider.snapshot("/orig.bin").unwrap();
+    provider.clone_snapshot(id, "/copy.bin").unwrap();
+    assert_eq!(shared_blocks(&provider), 3);
+    assert_eq!(read_file(&provider, "/copy.bin"), original);
+
+    let (handle, _) = provider.open("/copy.bin", OpenFlags::write()).unwrap();
+    provider
+        .write(handle.id(), PAGE_SIZE as u64 + 10, b"changed")
+        .unwrap();
+    provider.close(handle.id()).unwrap();
+
+    // Only the written page was copied; the others are still shared.
+    assert!(is_shared_page(&provider, "/copy.bin", 0));
+    assert!(!is_shared_page(&provider, "/copy.bin", 1));
+    assert!(is_shared_page(&provider, "/copy.bin", 2));
+    assert_eq!(shared_blocks(&provider), 3);
+
+    let mut expected = original.clone();
+    expected[PAGE_SIZE + 10..PAGE_SIZE + 17].copy_from_slice(b"changed");
+    assert_eq!(read_file(&provider, "/copy.bin"), expected);
+    assert_eq!(read_file(&provider, "/orig.bin"), original);
+}
+
+#[test]
+fn writes_to_original_leave_snapshot_intact() {
+    let provider = create_provider();
+    write_file(&provider, "/doc.txt", b"version one");
+
+    let id = provider.snapshot("/doc.txt").unwrap();
+    write_file(&provider, "/doc.txt", b"VERSION");
+    assert_eq!(read_file(&provider, "/doc.txt"), b"VERSION one");
+
+    provider.clone_snapshot(id, "/restored.txt").unwrap();
+    assert_eq!(read_file(&provider, "/restored.txt"), b"version one");
+}
+
+#[test]
+fn snapshot_view_serves_old_contents() {
+    let provider = create_provider();
+    provider.open("/dir", OpenFlags::create_dir()).unwrap();
+    write_file(&provider, "/dir/doc.txt", b"version one");
+
+    let id = provider.snapshot("/dir").unwrap();
+    write_file(&provider, "/dir/doc.txt", b"VERSION");
+    write_file(&provider, "/dir/new.txt", b"after");
+
+    let view = provider.snapshot_view(id).unwrap();
+    assert_eq!(read_file(&view, "/doc.txt"), b"version one");
+    assert!(matches!(view.stat("/new.txt"), Err(FsError::NotFound(_))));
+    assert_eq!(read_file(&provider, "/dir/doc.txt"), b"VERSION one");
+
+    // Writes through the view stay in the view.
+    write_file(&view, "/doc.txt", b"scratch");
+    assert_eq!(read_file(&provider, "/dir/doc.txt"), b"VERSION one");
+    assert_eq!(
+        read_file(&provider.snapshot_view(id).unwrap(), "/doc.txt"),
+        b"version one"
+    );
+
+    write_file(&provider, "/file.txt", b"x");
+    let file_id = provider.snapshot("/file.txt").unwrap();
+    assert!(matches!(
+        provider.snapshot_view(file_id),
+        Err(FsError::NotDirectory(_))
+    ));
+    assert!(matches!(
+        provider.snapshot_view(999),
+        Err(FsError::NotFound(_))
+    ));
+}
+
+#[test]
+fn clone_directory_tree() {
+    let provider = create_provider();
+    provider.open("/src", OpenFlags::create_dir()).unwrap();
+    provider.open("/src/sub", OpenFlags::create_dir()).unwrap();
+    write_file(&provider, "/src/a.txt", b"alpha");
+    write_file(&provider, "/src/sub/b.txt", b"beta");
+    provider.symlink("/src/link", "a.txt").unwrap();
+    provider.setxattr("/src/a.txt", "user.tag", b"x").unwrap();
+
+    let id = provider.snapshot("/src").unwrap();
+    provider.clone_snapshot(id, "/dst").unwrap();
+
+    assert_eq!(read_file(&provider, "/dst/a.txt"), b"alpha");
+    assert_eq!(read_file(&provider, "/dst/sub/b.txt"), b"beta");
+    assert_eq!(
+        provider
+            .stat("/dst/link")
+            .unwrap()
+            .symlink_target
+            .as_deref(),
+        Some("a.txt")
+    );
+    assert_eq!(provider.getxattr("/dst/a.txt", "user.tag").unwrap(), b"x");
+    assert_eq!(provider.readdir("/dst").unwrap().len(), 3);
+    assert_ne!(
+        provider.resolve_path("/dst/a.txt", true).unwrap().0,
+        provider.resolve_path("/src/a.txt", true).unwrap().0
+    );
+
+    // The clone is an independent tree.
+    provider.remove("/dst/sub/b.txt").unwrap();
+    provider.remove("/dst/sub").unwrap();
+    assert_eq!(read_file(&provider, "/src/sub/b.txt"), b"beta");
+    assert!(matches!(
+        provider.clone_snapshot(id, "/dst"),
+        Err(FsError::AlreadyExists(_))
+    ));
+}
+
+#[test]
+fn shared_blocks_freed_with_last_reference() {
+    let provider = create_provider();
+    write_file(&provider, "/f.txt", b"shared");
+    let id = provider.snapshot("/f.txt").unwrap();
+    provider.clone_snapshot(id, "/g.txt").unwrap();
+    assert_eq!(shared_blocks(&provider), 1);
+    assert_eq!(provider.load_superblock().shared_pages, 1);
+
+    provider.remove("/f.txt").unwrap();
+    provider.delete_snapshot(id).unwrap();
+    assert_eq!(shared_blocks(&provider), 1);
+    assert_eq!(read_file(&provider, "/g.txt"), b"shared");
+
+    provider.remove("/g.txt").unwrap();
+    assert_eq!(shared_blocks(&provider), 0);
+    assert!(provider.kv.scan(b"R").is_empty());
+    assert_eq!(provider.load_superblock().shared_pages, 0);
+    assert!(matches!(
+        provider.clone_snapshot(id, "/h.txt"),
+        Err(FsError::NotFound(_))
+    ));
+}
+
+#[test]
+fn truncating_clone_releases_shared_pages() {
+    let provider = create_provider();
+    write_file(&provider, "/big.bin", &vec![7u8; PAGE_SIZE * 2]);
+    let id = provider.snapshot("/big.bin").unwrap();
+    provider.clone_snapshot(id, "/small.bin").unwrap();
+
+    provider
+        .wstat("/small.bin", &StatChanges::truncate(10))
+        .unwrap();
+    assert_eq!(read_file(&provider, "/small.bin"), vec![7u8; 10]);
+    assert_eq!(read_file(&provider, "/big.bin"), vec![7u8; PAGE_SIZE * 2]);
+
+    provider.remove("/big.bin").unwrap();
+    provider.delete_snapshot(id).unwrap();
+    assert_eq!(shared_blocks(&provider), 0);
+}
+
+#[test]
+fn writes_past_capacity_fail_with_no_space() {
+    let provider = create_provider().with_total_pages(4);
+    let handle = provider.open("/a.bin", OpenFlags::create_file()).unwrap().0;
+    provider
+        .write(handle.id(), 0, &vec![1u8; PAGE_SIZE * 3])
+        .unwrap();
+    assert_eq!(provider.load_superblock().used_pages, 3);
+
+    let err = provider
+        .write(handle.id(), 0, &vec![1u8; PAGE_SIZE * 5])
+        .unwrap_err();
+    assert!(matches!(err, FsError::NoSpace(_)), "{err:?}");
+    assert_eq!(
+        provider.stat("/a.bin").unwrap().size,
+        (PAGE_SIZE * 3) as u64
+    );
+    provider.close(handle.id()).unwrap();
+
+    // The last free page goes to a new file; nothing is left after it.
+    create_file(&provider, "/b.bin");
+    assert!(matches!(
+        provider.open("/c.bin", OpenFlags::create_file()),
+        Err(FsError::NoSpace(_))
+    ));
+    assert!(matches!(
+        provider.wstat("/b.bin", &StatChanges::truncate(PAGE_SIZE as u64 * 2)),
+        Err(FsError::NoSpace(_))
+    ));
+    let id = provider.snapshot("/a.bin").unwrap();
+    assert!(matches!(
+        provider.clone_snapshot(id, "/copy.bin"),
+        Err(FsError::NoSpace(_))
+    ));
+
+    // Removing and truncating files gives their pages back.
+    provider.remove("/b.bin").unwrap();
+    provider.wstat("/a.bin", &StatChanges::truncate(1)).unwrap();
+    assert_eq!(provider.load_superblock().used_pages, 1);
+    write_file(&provider, "/c.bin", &vec![2u8; PAGE_SIZE * 3]);
+    assert_eq!(provider.load_superblock().used_pages, 4);
+}
+
+#[test]
+fn no_space_propagates_through_ffi() {
+    let config = br#"{"total_pages": 2}"#;
+    unsafe {
+        let vtable = &*ffi::fs9_plugin_vtable();
+        let raw = (vtable.create)(config.as_ptr().cast(), config.len());
+        assert!(!raw.is_null());
+        let provider = &*(raw as *const PageFsProvider);
+
+        let handle = provider
+            .open("/full.bin", OpenFlags::create_file())
+            .unwrap()
+            .0;
+        let data = vec![9u8; PAGE_SIZE * 3];
+        let mut written = 0;
+        let result = (vtable.write)(raw, handle.id(), 0, data.as_ptr(), data.len(), &mut written);
+        assert_eq!(result.code, fs9_sdk_ffi::FS9_ERR_NO_SPACE);
+        assert_eq!(written, 0);
+
+        let result = (vtable.write)(
+            raw,
+            handle.id(),
+            0,
+            data.as_ptr(),
+            PAGE_SIZE * 2,
+            &mut written,
+        );
+        assert_eq!(result.code, fs9_sdk_ffi::FS9_OK);
+        assert_eq!(written, PAGE_SIZE * 2);
+
+        let mut stats = std::mem::zeroed::<fs9_sdk_ffi::CFsStats>();
+        let result = (vtable.statfs)(raw, "/".as_ptr().cast(), 1, &mut stats);
+        assert_eq!(result.code, fs9_sdk_ffi::FS9_OK);
+        assert_eq!(stats.total_bytes, (PAGE_SIZE * 2) as u64);
+        assert_eq!(stats.free_bytes, 0);
+
+        provider.close(handle.id()).unwrap();
+        (vtable.destroy)(raw);
+    }
+}
+
+#[test]
+fn statfs_free_space_tracks_usage() {
+    let provider = create_provider().with_total_pages(8);
+    let free_pages = |provider: &PageFsProvider| provider.statfs().free_bytes / PAGE_SIZE as u64;
+    assert_eq!(provider.statfs().total_bytes, 8 * PAGE_SIZE as u64);
+    assert_eq!(free_pages(&provider), 8);
+
+    write_file(&provider, "/a.bin", &vec![1u8; PAGE_SIZE * 3]);
+    assert_eq!(free_pages(&provider), 5);
+    write_file(&provider, "/b.bin", &vec![2u8; PAGE_SIZE * 5]);
+    assert_eq!(free_pages(&provider), 0);
+
+    let (handle, _) = provider.open("/b.bin", OpenFlags::write()).unwrap();
+    assert!(matches!(
+        provider.write(handle.id(), (PAGE_SIZE * 5) as u64, b"more"),
+        Err(FsError::NoSpace(_))
+    ));
+
+    provider.remove("/a.bin").unwrap();
+    assert_eq!(free_pages(&provider), 3);
+    provider
+        .write(handle.id(), (PAGE_SIZE * 5) as u64, b"more")
+        .unwrap();
+    provider.close(handle.id()).unwrap();
+    assert_eq!(free_pages(&provider), 2);
+}
+
+#[test]
+fn rename_over_file_frees_its_pages() {
+    let provider = create_provider();
+    write_file(&provider, "/small.bin", &vec![1u8; PAGE_SIZE * 2]);
+    write_file(&provider, "/large.bin", &vec![2u8; PAGE_SIZE * 5]);
+    assert_eq!(provider.load_superblock().used_pages, 7);
+
+    provider.rename("/small.bin", "/large.bin").unwrap();
+    assert_eq!(provider.load_superblock().used_pages, 2);
+
+    // A file with another name left keeps its pages.
+    provider.link("/large.bin", "/alias.bin").unwrap();
+    provider.remove("/large.bin").unwrap();
+    assert_eq!(provider.load_superblock().used_pages, 2);
+    provider.remove("/alias.bin").unwrap();
+    assert_eq!(provider.load_superblock().used_pages, 0);
+}
+
+#[test]
+fn concurrent_creates_and_writes_keep_the_superblock_consistent() {
+    let provider = create_provider();
+    std::thread::scope(|scope| {
+        for worker in 0..8 {
+            let provider = &provider;
+            scope.spawn(move || {
+                for i in 0..20 {
+                    let path = format!("/w{worker}-{i}.bin");
+                    write_file(provider, &path, &vec![1u8; PAGE_SIZE * 2]);
+                    provider.symlink(&format!("/l{worker}-{i}"), &path).unwrap();
+                }
+            });
+        }
+    });
+
+    // Every file got its own inode and every page was charged once.
+    let mut ids = std::collections::HashSet::new();
+    for worker in 0..8 {
+        for i in 0..20 {
+            let path = format!("/w{worker}-{i}.bin");
+            assert!(ids.insert(provider.resolve_path(&path, false).unwrap().0));
+            assert!(ids.insert(
+                provider
+                    .resolve_path(&format!("/l{worker}-{i}"), false)
+                    .unwrap()
+                    .0
+            ));
+            assert_eq!(read_file(&provider, &path), vec![1u8; PAGE_SIZE * 2]);
+        }
+    }
+    assert_eq!(provider.load_superblock().used_pages, 8 * 20 * 2);
+    assert!(provider.load_superblock().next_inode > *ids.iter().max().unwrap());
+}
+
+#[test]
+fn garbage_collection_removes_orphaned_pages() {
+    let provider = create_provider();
+    write_file(&provider, "/live.bin", &vec![1u8; PAGE_SIZE * 2]);
+    write_file(&provider, "/doomed.bin", &vec![2u8; PAGE_SIZE * 3]);
+    let (doomed_id, _) = provider.resolve_path("/doomed.bin", true).unwrap();
+
+    // Crash between unlinking the file and deleting its pages.
+    provider
+        .kv
+        .delete(&keys::dir_entry(ROOT_INODE, "doomed.bin"));
+    provider.kv.delete(&keys::inode(doomed_id));
+    // An orphan from an inode that never made it to the store.
+    provider.kv.set(&keys::page(9_999, 0), &[3u8; PAGE_SIZE]);
+    assert_eq!(provider.load_superblock().used_pages, 5);
+
+    let report = provider.collect_garbage();
+    assert_eq!(report.orphaned_inodes, 2);
+    assert_eq!(report.orphaned_pages, 4);
+    assert_eq!(report.used_pages_before, 5);
+    assert_eq!(report.used_pages_after, 2);
+    assert!(provider.kv.scan(&keys::page_prefix(doomed_id)).is_empty());
+    assert!(provider.kv.get(&keys::page(9_999, 0)).is_none());
+    assert_eq!(provider.load_superblock().used_pages, 2);
+    assert_eq!(read_file(&provider, "/live.bin"), vec![1u8; PAGE_SIZE * 2]);
+
+    let report = provider.collect_garbage();
+    assert_eq!(report.orphaned_pages, 0);
+    assert_eq!(report.used_pages_before, report.used_pages_after);
+}
+
+/// A store that loses every write after the first `budget`, as if the
+/// process died there.
+struct CrashingKv {
+    inner: std::sync::Arc<InMemoryKv>,
+    budget: std::sync::Arc<std::sync::atomic::AtomicUsize>,
+}
+
+impl CrashingKv {
+    fn write_allowed(&self) -> bool {
+        self.budget
+            .fetch_update(
+                std::sync::atomic::Ordering::SeqCst,
+                std::sync::atomic::Ordering::SeqCst,
+                |left| left.checked_sub(1),
+            )
+            .is_ok()
+    }
+}
+
+impl KvBackend for CrashingKv {
+    fn get(&self, key: &[u8]) -> Option<Vec<u8>> {
+        self.inner.get(key)
+    }
+
+    fn set(&self, key: &[u8], value: &[u8]) {
+        if self.write_allowed() {
+            self.inner.set(key, value);
+        }
+    }
+
+    fn scan(&self, prefix: &[u8]) -> Vec<(Vec<u8>, Vec<u8>)> {
+        self.inner.scan(prefix)
+    }
+
+    fn delete(&self, key: &[u8]) {
+        if self.write_allowed() {
+            self.inner.delete(key);
+        }
+    }
+}
+
+/// Run `op` on a store holding `/keep.bin` and `/dir/old.bin`, crashing
+/// after each of its writes in turn; after every crash, garbage collection
+/// must leave a store that a second pass finds nothing to fix in, with
+/// `/keep.bin` intact. `check` sees each recovered store.
+fn crash_after_each_write(op: impl Fn(&PageFsProvider), check: impl Fn(&PageFsProvider)) {
+    let mut writes = 0;
+    loop {
+        let kv = std::sync::Arc::new(InMemoryKv::new());
+        let budget = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(usize::MAX));
+        let provider = PageFsProvider::new(Box::new(CrashingKv {
+            inner: kv.clone(),
+            budget: budget.clone(),
+        }))
+        .unwrap()
+        .with_write_cache(0);
+        write_file(&provider, "/keep.bin", &vec![1u8; PAGE_SIZE * 2]);
+        provider.open("/dir", OpenFlags::create_dir()).unwrap();
+        write_file(&provider, "/dir/old.bin", b"old");
+
+        budget.store(writes, std::sync::atomic::Ordering::SeqCst);
+        op(&provider);
+        drop(provider);
+        let completed = budget.load(std::sync::atomic::Ordering::SeqCst) > 0;
+
+        let provider = PageFsProvider::new(Box::new(SharedKv(kv))).unwrap();
+        provider.collect_garbage();
+        let again = provider.collect_garbage();
+        assert_eq!(
+            (again.unreachable_inodes, again.dangling_entries),
+            (0, 0),
+            "crash after {writes} writes"
+        );
+        assert_eq!(again.repaired_counts, 0, "crash after {writes} writes");
+        assert_eq!(again.orphaned_pages, 0, "crash after {writes} writes");
+        assert_eq!(again.used_pages_before, again.used_pages_after);
+        assert_eq!(read_file(&provider, "/keep.bin"), vec![1u8; PAGE_SIZE * 2]);
+        check(&provider);
+
+        // Ids handed out after recovery don't collide with surviving ones.
+        write_file(&provider, "/after.bin", b"after");
+        assert_eq!(read_file(&provider, "/keep.bin"), vec![1u8; PAGE_SIZE * 2]);
+        assert_eq!(read_file(&provider, "/after.bin"), b"after");
+
+        if completed {
+            break;
+        }
+        writes += 1;
+    }
+    assert!(writes > 1, "the operation should take several writes");
+}
+
+#[test]
+fn crash_during_create_leaves_a_recoverable_store() {
+    crash_after_each_write(
+        |provider| {
+            let _ = provider.open("/dir/new.bin", OpenFlags::create_file());
+        },
+        |provider| {
+            let entries = provider.readdir("/dir").unwrap();
+            let (_, dir) = provider.resolve_path("/dir", true).unwrap();
+            assert_eq!(dir.child_count, Some(entries.len() as u64));
+            if let Ok(info) = provider.stat("/dir/new.bin") {
+                assert_eq!(info.file_type, FileType::Regular);
+                assert_eq!(read_file(provider, "/dir/new.bin"), b"");
+            }
+        },
+    );
+}
+
+#[test]
+fn crash_during_link_leaves_a_recoverable_store() {
+    crash_after_each_write(
+        |provider| {
+            let _ = provider.link("/dir/old.bin", "/alias.bin");
+        },
+        |provider| {
+            // However many names survived, dropping them all frees the file.
+            let _ = provider.remove("/alias.bin");
+            assert_eq!(read_file(provider, "/dir/old.bin"), b"old");
+            provider.remove("/dir/old.bin").unwrap();
+            assert_eq!(provider.collect_garbage().orphaned_pages, 0);
+        },
+    );
+}
+
+#[test]
+fn crash_during_rename_keeps_the_file() {
+    crash_after_each_write(
+        |provider| {
+            let _ = provider.rename("/dir/old.bin", "/moved.bin");
+        },
+        |provider| {
+            let old = provider.stat("/dir/old.bin").is_ok();
+            let moved = provider.stat("/moved.bin").is_ok();
+            assert!(old || moved, "the file was lost");
+            for path in ["/dir/old.bin", "/moved.bin"] {
+                if provider.stat(path).is_ok() {
+                    assert_eq!(read_file(provider, path), b"old");
+                }
+            }
+        },
+    );
+}
+
+#[test]
+fn crash_during_remove_leaves_a_recoverable_store() {
+    crash_after_each_write(
+        |provider| {
+            let _ = provider.remove("/dir/old.bin");
+        },
+        |provider| {
+            if provider.stat("/dir/old.bin").is_ok() {
+                assert_eq!(read_file(provider, "/dir/old.bin"), b"old");
+            }
+            assert_eq!(provider.load_superblock().used_pages, {
+                let files = provider.readdir("/dir").unwrap().len() as u64;
+                2 + files
+            });
+        },
+    );
+}
+
+#[test]
+fn uncounted_store_is_recounted_on_open() {
+    let kv = std::sync::Arc::new(InMemoryKv::new());
+    let provider = PageFsProvider::new(Box::new(SharedKv(kv.clone()))).unwrap();
+    write_file(&provider, "/a.bin", &vec![1u8; PAGE_SIZE * 3]);
+    provider.open("/dir", OpenFlags::create_dir()).unwrap();
+    write_file(&provider, "/dir/b.bin", b"tiny");
+    drop(provider);
+
+    // Simulate a store formatted before page accounting.
+    let mut sb: Superblock = serde_json::from_slice(&kv.get(&keys::superblock()).unwrap()).unwrap();
+    sb.used_pages = 0;
+    sb.pages_counted = false;
+    kv.set(&keys::superblock(), &serde_json::to_vec(&sb).unwrap());
+
+    let provider = PageFsProvider::new(Box::new(SharedKv(kv))).unwrap();
+    let sb = provider.load_superblock();
+    assert!(sb.pages_counted);
+    assert_eq!(sb.used_pages, 4);
 }
@@ -0,0 +1,74 @@
+#![cfg(feature = "rocksdb")]
+
+use std::path::PathBuf;
+
+use fs9_plugin_pagefs::provider::PageFsProvider;
+use fs9_plugin_pagefs::{KvBackend, RocksKvBackend, PAGE_SIZE};
+use fs9_sdk::OpenFlags;
+
+struct TempDir(PathBuf);
+
+impl TempDir {
+    fn new(test_name: &str) -> Self {
+        let path = std::env::temp_dir().join(format!(
+            "fs9_pagefs_rocksdb_{test_name}_{}",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&path);
+        Self(path)
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+fn open_provider(dir: &TempDir) -> PageFsProvider {
+    PageFsProvider::new(Box::new(RocksKvBackend::new(&dir.0).unwrap())).unwrap()
+}
+
+#[test]
+fn rocksdb_data_survives_reopen() {
+    let dir = TempDir::new("reopen");
+    let data: Vec<u8> = (0..PAGE_SIZE * 2 + 100).map(|i| (i % 251) as u8).collect();
+
+    {
+        let p = open_provider(&dir);
+        p.open("/docs", OpenFlags::create_dir()).unwrap();
+        let (handle, _) = p.open("/docs/file.bin", OpenFlags::create_file()).unwrap();
+        p.write(handle.id(), 0, &data).unwrap();
+        p.close(handle.id()).unwrap();
+    }
+
+    let p = open_provider(&dir);
+    let info = p.stat("/docs/file.bin").unwrap();
+    assert_eq!(info.size, data.len() as u64);
+
+    let (handle, _) = p.open("/docs/file.bin", OpenFlags::read()).unwrap();
+    let read = p.read(handle.id(), 0, data.len()).unwrap();
+    assert_eq!(&read[..], &data[..]);
+    p.close(handle.id()).unwrap();
+
+    let entries = p.readdir("/").unwrap();
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].path, "/docs");
+}
+
+#[test]
+fn rocksdb_scan_returns_only_matching_prefix() {
+    let dir = TempDir::new("scan");
+    let kv = RocksKvBackend::new(&dir.0).unwrap();
+    kv.set(b"Pa:1", b"1");
+    kv.set(b"Pa:2", b"2");
+    kv.set(b"Pb:1", b"3");
+    kv.set(b"Q", b"4");
+
+    let keys: Vec<_> = kv.scan(b"Pa:").into_iter().map(|(k, _)| k).collect();
+    assert_eq!(keys, vec![b"Pa:1".to_vec(), b"Pa:2".to_vec()]);
+
+    kv.delete(b"Pa:1");
+    assert!(kv.get(b"Pa:1").is_none());
+    assert_eq!(kv.scan(b"P").len(), 2);
+}
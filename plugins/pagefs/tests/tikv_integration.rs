@@ -8,14 +8,14 @@ fn fresh_tikv_provider(test_name: &str) -> PageFsProvider {
     let ns = format!("fs9_test_{test_name}");
     let backend = TikvKvBackend::new(vec!["127.0.0.1:2379".to_string()], Some(ns));
     wipe_all_keys(&backend);
-    PageFsProvider::new(Box::new(backend))
+    PageFsProvider::new(Box::new(backend)).unwrap()
 }
 
 fn fresh_tikv_provider_with_uid(test_name: &str, uid: u32, gid: u32) -> PageFsProvider {
     let ns = format!("fs9_test_{test_name}");
     let backend = TikvKvBackend::new(vec!["127.0.0.1:2379".to_string()], Some(ns));
     wipe_all_keys(&backend);
-    PageFsProvider::with_config(Box::new(backend), uid, gid)
+    PageFsProvider::with_config(Box::new(backend), uid, gid).unwrap()
 }
 
 fn wipe_all_keys(backend: &TikvKvBackend) {
@@ -757,7 +757,7 @@ fn tikv_data_persists_across_reconnect() {
     {
         let backend = TikvKvBackend::new(vec!["127.0.0.1:2379".to_string()], Some(ns.to_string()));
         wipe_all_keys(&backend);
-        let p = PageFsProvider::new(Box::new(backend));
+        let p = PageFsProvider::new(Box::new(backend)).unwrap();
 
         let (handle, _) = p.open("/persist.txt", OpenFlags::create_file()).unwrap();
         p.write(handle.id(), 0, b"persistent data").unwrap();
@@ -767,7 +767,7 @@ fn tikv_data_persists_across_reconnect() {
     // Second connection: read data back (no wipe!)
     {
         let backend = TikvKvBackend::new(vec!["127.0.0.1:2379".to_string()], Some(ns.to_string()));
-        let p = PageFsProvider::new(Box::new(backend));
+        let p = PageFsProvider::new(Box::new(backend)).unwrap();
 
         let info = p.stat("/persist.txt").unwrap();
         assert_eq!(info.size, 15);
@@ -800,8 +800,8 @@ fn tikv_keyspace_isolation() {
     let backend_b = TikvKvBackend::new(vec!["127.0.0.1:2379".to_string()], Some(ns_b.to_string()));
     wipe_all_keys(&backend_b);
 
-    let pa = PageFsProvider::new(Box::new(backend_a));
-    let pb = PageFsProvider::new(Box::new(backend_b));
+    let pa = PageFsProvider::new(Box::new(backend_a)).unwrap();
+    let pb = PageFsProvider::new(Box::new(backend_b)).unwrap();
 
     // Write in ns_a
     let (h, _) = pa.open("/secret.txt", OpenFlags::create_file()).unwrap();
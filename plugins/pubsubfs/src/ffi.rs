@@ -32,6 +32,10 @@ fn make_cresult_err(code: i32) -> CResult {
     }
 }
 
+fn fserror_to_cresult(err: &FsError) -> CResult {
+    CResult::from_error(fserror_to_code(err), err)
+}
+
 fn fserror_to_code(err: &FsError) -> i32 {
     match err {
         FsError::NotFound(_) => FS9_ERR_NOT_FOUND,
@@ -41,6 +45,7 @@ fn fserror_to_code(err: &FsError) -> i32 {
         FsError::InvalidHandle(_) => FS9_ERR_INVALID_HANDLE,
         FsError::PermissionDenied(_) => fs9_sdk_ffi::FS9_ERR_PERMISSION_DENIED,
         FsError::InvalidArgument(_) => FS9_ERR_INVALID_ARGUMENT,
+        FsError::NoSpace(_) => fs9_sdk_ffi::FS9_ERR_NO_SPACE,
         _ => fs9_sdk_ffi::FS9_ERR_INTERNAL,
     }
 }
@@ -68,7 +73,8 @@ unsafe extern "C" fn get_capabilities(_provider: *mut c_void) -> u64 {
         | Capabilities::WRITE
         | Capabilities::CREATE
         | Capabilities::DELETE
-        | Capabilities::DIRECTORY)
+        | Capabilities::DIRECTORY
+        | Capabilities::STATEFUL_READ)
         .bits()
 }
 
@@ -100,9 +106,10 @@ unsafe extern "C" fn stat_fn(
             (*out_info).atime = systemtime_to_timestamp(info.atime);
             (*out_info).mtime = systemtime_to_timestamp(info.mtime);
             (*out_info).ctime = systemtime_to_timestamp(info.ctime);
+            (*out_info).set_etag(&info.etag);
             make_cresult_ok()
         }
-        Err(e) => make_cresult_err(fserror_to_code(&e)),
+        Err(e) => fserror_to_cresult(&e),
     }
 }
 
@@ -159,6 +166,8 @@ unsafe extern "C" fn open_fn(
         truncate: flags.truncate != 0,
         append: flags.append != 0,
         directory: flags.directory != 0,
+        symlink: flags.symlink != 0,
+        exclusive: flags.excl != 0,
     };
 
     match provider.open(path, open_flags) {
@@ -176,9 +185,10 @@ unsafe extern "C" fn open_fn(
             (*out_info).mtime = systemtime_to_timestamp(info.mtime);
             (*out_info).atime = systemtime_to_timestamp(info.atime);
             (*out_info).ctime = systemtime_to_timestamp(info.ctime);
+            (*out_info).set_etag(&info.etag);
             make_cresult_ok()
         }
-        Err(e) => make_cresult_err(fserror_to_code(&e)),
+        Err(e) => fserror_to_cresult(&e),
     }
 }
 
@@ -197,10 +207,10 @@ unsafe extern "C" fn read_fn(
 
     match provider.read(handle, offset, size) {
         Ok(data) => {
-            *out_data = fs9_sdk_ffi::vec_to_cbytes(data.to_vec());
+            *out_data = fs9_sdk_ffi::bytes_to_cbytes(data);
             make_cresult_ok()
         }
-        Err(e) => make_cresult_err(fserror_to_code(&e)),
+        Err(e) => fserror_to_cresult(&e),
     }
 }
 
@@ -228,7 +238,7 @@ unsafe extern "C" fn write_fn(
             *out_written = written;
             make_cresult_ok()
         }
-        Err(e) => make_cresult_err(fserror_to_code(&e)),
+        Err(e) => fserror_to_cresult(&e),
     }
 }
 
@@ -241,7 +251,7 @@ unsafe extern "C" fn close_fn(provider: *mut c_void, handle: u64, _sync: u8) ->
 
     match provider.close(handle) {
         Ok(()) => make_cresult_ok(),
-        Err(e) => make_cresult_err(fserror_to_code(&e)),
+        Err(e) => fserror_to_cresult(&e),
     }
 }
 
@@ -264,7 +274,7 @@ unsafe extern "C" fn readdir_fn(
         Ok(entries) => {
             for entry in entries {
                 let path_bytes = entry.path.as_bytes();
-                let info = CFileInfo {
+                let mut info = CFileInfo {
                     path: path_bytes.as_ptr() as *const c_char,
                     path_len: path_bytes.len(),
                     size: entry.size,
@@ -279,14 +289,16 @@ unsafe extern "C" fn readdir_fn(
                     atime: systemtime_to_timestamp(entry.atime),
                     mtime: systemtime_to_timestamp(entry.mtime),
                     ctime: systemtime_to_timestamp(entry.ctime),
+                    ..CFileInfo::default()
                 };
+                info.set_etag(&entry.etag);
                 if callback(&info, user_data) != 0 {
                     break;
                 }
             }
             make_cresult_ok()
         }
-        Err(e) => make_cresult_err(fserror_to_code(&e)),
+        Err(e) => fserror_to_cresult(&e),
     }
 }
 
@@ -305,10 +317,137 @@ unsafe extern "C" fn remove_fn(
 
     match provider.remove(path) {
         Ok(()) => make_cresult_ok(),
-        Err(e) => make_cresult_err(fserror_to_code(&e)),
+        Err(e) => fserror_to_cresult(&e),
+    }
+}
+
+unsafe extern "C" fn fsync_fn(provider: *mut c_void, handle: u64, _data_only: u8) -> CResult {
+    if provider.is_null() {
+        return make_cresult_err(FS9_ERR_INVALID_ARGUMENT);
+    }
+
+    let provider = &*(provider as *const PubSubFsProvider);
+    match provider.fsync(handle) {
+        Ok(()) => make_cresult_ok(),
+        Err(e) => fserror_to_cresult(&e),
     }
 }
 
+unsafe extern "C" fn flush_fn(provider: *mut c_void) -> CResult {
+    if provider.is_null() {
+        return make_cresult_err(FS9_ERR_INVALID_ARGUMENT);
+    }
+
+    let provider = &*(provider as *const PubSubFsProvider);
+    provider.snapshot();
+    make_cresult_ok()
+}
+
+unsafe extern "C" fn health_check_fn(provider: *mut c_void) -> CResult {
+    if provider.is_null() {
+        return make_cresult_err(FS9_ERR_INVALID_ARGUMENT);
+    }
+
+    let provider = &*(provider as *const PubSubFsProvider);
+    match provider.health_check() {
+        Ok(()) => make_cresult_ok(),
+        Err(e) => fserror_to_cresult(&e),
+    }
+}
+
+unsafe extern "C" fn snapshot_fn(
+    _provider: *mut c_void,
+    _path: *const c_char,
+    _path_len: size_t,
+    _out_id: *mut u64,
+) -> CResult {
+    make_cresult_err(fs9_sdk_ffi::FS9_ERR_NOT_IMPLEMENTED)
+}
+
+unsafe extern "C" fn open_snapshot_fn(
+    _provider: *mut c_void,
+    _snapshot_id: u64,
+    _out_view: *mut *mut c_void,
+) -> CResult {
+    make_cresult_err(fs9_sdk_ffi::FS9_ERR_NOT_IMPLEMENTED)
+}
+
+unsafe extern "C" fn getxattr_fn(
+    _provider: *mut c_void,
+    _path: *const c_char,
+    _path_len: size_t,
+    _name: *const c_char,
+    _name_len: size_t,
+    _out_value: *mut CBytes,
+) -> CResult {
+    make_cresult_err(fs9_sdk_ffi::FS9_ERR_NOT_IMPLEMENTED)
+}
+
+unsafe extern "C" fn setxattr_fn(
+    _provider: *mut c_void,
+    _path: *const c_char,
+    _path_len: size_t,
+    _name: *const c_char,
+    _name_len: size_t,
+    _value: *const u8,
+    _value_len: size_t,
+) -> CResult {
+    make_cresult_err(fs9_sdk_ffi::FS9_ERR_NOT_IMPLEMENTED)
+}
+
+unsafe extern "C" fn listxattr_fn(
+    _provider: *mut c_void,
+    _path: *const c_char,
+    _path_len: size_t,
+    _out_names: *mut CBytes,
+) -> CResult {
+    make_cresult_err(fs9_sdk_ffi::FS9_ERR_NOT_IMPLEMENTED)
+}
+
+unsafe extern "C" fn removexattr_fn(
+    _provider: *mut c_void,
+    _path: *const c_char,
+    _path_len: size_t,
+    _name: *const c_char,
+    _name_len: size_t,
+) -> CResult {
+    make_cresult_err(fs9_sdk_ffi::FS9_ERR_NOT_IMPLEMENTED)
+}
+
+unsafe extern "C" fn link_fn(
+    _provider: *mut c_void,
+    _existing_path: *const c_char,
+    _existing_path_len: size_t,
+    _new_path: *const c_char,
+    _new_path_len: size_t,
+) -> CResult {
+    make_cresult_err(fs9_sdk_ffi::FS9_ERR_NOT_IMPLEMENTED)
+}
+
+unsafe extern "C" fn rename_fn(
+    _provider: *mut c_void,
+    _old_path: *const c_char,
+    _old_path_len: size_t,
+    _new_path: *const c_char,
+    _new_path_len: size_t,
+) -> CResult {
+    make_cresult_err(fs9_sdk_ffi::FS9_ERR_NOT_IMPLEMENTED)
+}
+
+unsafe extern "C" fn readdir_at_fn(
+    _provider: *mut c_void,
+    _path: *const c_char,
+    _path_len: size_t,
+    _cursor: *const c_char,
+    _cursor_len: size_t,
+    _max: size_t,
+    _callback: fs9_sdk_ffi::ReaddirCallback,
+    _user_data: *mut c_void,
+    _out_next_cursor: *mut CBytes,
+) -> CResult {
+    make_cresult_err(fs9_sdk_ffi::FS9_ERR_NOT_IMPLEMENTED)
+}
+
 static PLUGIN_NAME: &[u8] = b"pubsubfs";
 static PLUGIN_VERSION: &[u8] = b"0.1.0";
 
@@ -330,6 +469,18 @@ static VTABLE: PluginVTable = PluginVTable {
     close: close_fn,
     readdir: readdir_fn,
     remove: remove_fn,
+    fsync: fsync_fn,
+    link: link_fn,
+    rename: rename_fn,
+    readdir_at: readdir_at_fn,
+    flush: flush_fn,
+    getxattr: getxattr_fn,
+    setxattr: setxattr_fn,
+    listxattr: listxattr_fn,
+    removexattr: removexattr_fn,
+    health_check: health_check_fn,
+    snapshot: snapshot_fn,
+    open_snapshot: open_snapshot_fn,
 };
 
 #[cfg(test)]
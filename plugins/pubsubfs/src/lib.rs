@@ -1,23 +1,29 @@
 #![allow(clippy::missing_safety_doc)]
 
 use std::collections::{HashMap, VecDeque};
+use std::path::PathBuf;
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::{Arc, Mutex, RwLock};
 use std::time::SystemTime;
 
 use bytes::Bytes;
 use fs9_sdk::{FileInfo, FileType, FsError, FsResult, Handle, OpenFlags};
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use tokio::sync::broadcast;
 
 pub mod ffi;
+pub mod store;
+
+pub use store::{DirKvBackend, InMemoryKv, KvBackend};
 
 #[cfg(test)]
 mod tests;
 
 const DEFAULT_RING_SIZE: usize = 100;
 const DEFAULT_CHANNEL_SIZE: usize = 100;
+const DEFAULT_SNAPSHOT_INTERVAL: u64 = 16;
 const MAX_MESSAGE_SIZE: usize = 1024 * 1024;
+const TOPIC_KEY_PREFIX: &[u8] = b"topic:";
 
 const README_CONTENT: &str = r#"PubSubFS - Publish/Subscribe File System Plugin
 
@@ -48,6 +54,18 @@ USAGE:
   cat /pubsub/chat
   tail -f /pubsub/chat           # Recommended: last N + follow
 
+  # Prefix each message with its RFC3339 publish time, or emit JSON lines
+  cat '/pubsub/chat?ts=1'
+  cat '/pubsub/chat?framing=json'  # {"ts":"...","data":"..."}
+
+  # Publish a retained message: late subscribers get it first, before the
+  # ring history. An empty retained message clears it.
+  echo "online" > '/pubsub/status?retain=1'
+  echo "" > '/pubsub/status?retain=1'
+
+  # Fan in every existing topic matching a pattern
+  cat '/pubsub/logs.*'
+
   # View topic info
   cat /pubsub/chat.info
 
@@ -61,12 +79,19 @@ FEATURES:
   - Simple pipe-like interface: read=subscribe, write=publish
   - Multiple publishers and subscribers per topic
   - Ring buffer for late joiners (configurable size)
+  - Subscribers that fall behind see "[lagged N messages]" in their
+    stream, and the topic's .info counts the dropped messages
+  - A retained "last value" per topic, delivered to every new subscriber
   - Real-time message broadcast
   - Topic statistics via .info files
   - Auto-create topics on first write
 
 NOTES:
-  - Messages are in-memory only (not persistent)
+  - Messages are in-memory only unless persistence is enabled; with
+    "persist_dir" set, topics and their ring buffers are snapshotted there
+    (every snapshot_interval messages, on close and fsync of a publisher,
+    and when the server flushes) and restored when the plugin is loaded
+    again
   - Each message is broadcast to all active subscribers
   - Ring buffer stores recent messages for new subscribers
   - Path is short and intuitive: /pubsub/chat vs /pubsub/topics/chat/pub
@@ -78,6 +103,27 @@ pub(crate) struct PubSubFsConfig {
     pub(crate) default_ring_size: usize,
     #[serde(default = "default_channel_size")]
     pub(crate) default_channel_size: usize,
+    /// Directory to snapshot topics into. Persistence is off when unset.
+    #[serde(default)]
+    pub(crate) persist_dir: Option<PathBuf>,
+    /// Number of messages published to a topic between snapshots.
+    #[serde(default = "default_snapshot_interval")]
+    pub(crate) snapshot_interval: u64,
+    /// Default framing for subscribers that don't pick one in the path.
+    #[serde(default)]
+    pub(crate) framing: Framing,
+    /// Maximum number of topics; creating more is refused. Unlimited when
+    /// unset.
+    #[serde(default)]
+    pub(crate) max_topics: Option<usize>,
+    /// Channel sizes for individual topics, by name, overriding
+    /// `default_channel_size` for topics that see bursts.
+    #[serde(default)]
+    pub(crate) channel_sizes: HashMap<String, usize>,
+    /// Tell a subscriber that fell behind the channel how many messages it
+    /// missed with a `[lagged N messages]` line in its stream.
+    #[serde(default = "default_lag_markers")]
+    pub(crate) lag_markers: bool,
 }
 
 fn default_ring_size() -> usize {
@@ -88,21 +134,150 @@ fn default_channel_size() -> usize {
     DEFAULT_CHANNEL_SIZE
 }
 
+fn default_snapshot_interval() -> u64 {
+    DEFAULT_SNAPSHOT_INTERVAL
+}
+
+const fn default_lag_markers() -> bool {
+    true
+}
+
 impl Default for PubSubFsConfig {
     fn default() -> Self {
         Self {
             default_ring_size: DEFAULT_RING_SIZE,
             default_channel_size: DEFAULT_CHANNEL_SIZE,
+            persist_dir: None,
+            snapshot_interval: DEFAULT_SNAPSHOT_INTERVAL,
+            framing: Framing::Raw,
+            max_topics: None,
+            channel_sizes: HashMap::new(),
+            lag_markers: true,
         }
     }
 }
 
+/// How messages are rendered to subscribers, one message per line.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Framing {
+    /// The message bytes as published.
+    #[default]
+    Raw,
+    /// The message prefixed with its RFC3339 publish time and a space.
+    Timestamped,
+    /// A JSON object `{"ts":"...","data":"..."}`.
+    Json,
+}
+
+impl Framing {
+    fn parse(value: &str) -> FsResult<Self> {
+        match value {
+            "raw" => Ok(Self::Raw),
+            "timestamped" => Ok(Self::Timestamped),
+            "json" => Ok(Self::Json),
+            _ => Err(FsError::invalid_argument(format!(
+                "unknown framing: {value} (expected raw, timestamped or json)"
+            ))),
+        }
+    }
+}
+
+/// Options taken from the `?key=value` suffix of a topic path.
+#[derive(Debug, Default)]
+struct TopicQuery {
+    /// `framing=` or `ts=`, for subscribers.
+    framing: Option<Framing>,
+    /// `retain=1`, for publishers.
+    retain: bool,
+}
+
+fn parse_bool(key: &str, value: &str) -> FsResult<bool> {
+    match value {
+        "1" | "true" => Ok(true),
+        "0" | "false" => Ok(false),
+        _ => Err(FsError::invalid_argument(format!(
+            "invalid {key} value: {value}"
+        ))),
+    }
+}
+
+#[derive(Serialize)]
+struct JsonFrame<'a> {
+    ts: String,
+    data: std::borrow::Cow<'a, str>,
+}
+
 #[derive(Debug, Clone)]
 struct Message {
     timestamp: SystemTime,
     data: Bytes,
 }
 
+/// Serialized form of a topic, stored under `topic:<name>`.
+#[derive(Serialize, Deserialize)]
+struct TopicSnapshot {
+    name: String,
+    created_at: SystemTime,
+    mtime: SystemTime,
+    total_messages: u64,
+    messages: Vec<MessageSnapshot>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    retained: Option<MessageSnapshot>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct MessageSnapshot {
+    timestamp: SystemTime,
+    #[serde(with = "base64_data")]
+    data: Vec<u8>,
+}
+
+/// Message payloads in snapshots, as base64 strings rather than the JSON
+/// number arrays serde writes for bytes by default. Snapshots written in
+/// that older form still load.
+mod base64_data {
+    use base64::engine::general_purpose::STANDARD;
+    use base64::Engine;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Encoded {
+        Base64(String),
+        Numbers(Vec<u8>),
+    }
+
+    pub fn serialize<S: Serializer>(data: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&STANDARD.encode(data))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        match Encoded::deserialize(deserializer)? {
+            Encoded::Base64(text) => STANDARD.decode(text).map_err(serde::de::Error::custom),
+            Encoded::Numbers(data) => Ok(data),
+        }
+    }
+}
+
+impl From<MessageSnapshot> for Message {
+    fn from(snapshot: MessageSnapshot) -> Self {
+        Self {
+            timestamp: snapshot.timestamp,
+            data: Bytes::from(snapshot.data),
+        }
+    }
+}
+
+impl From<&Message> for MessageSnapshot {
+    fn from(message: &Message) -> Self {
+        Self {
+            timestamp: message.timestamp,
+            data: message.data.to_vec(),
+        }
+    }
+}
+
 impl Message {
     fn new(mut data: Bytes) -> Self {
         if data.ends_with(b"\n") {
@@ -115,9 +290,39 @@ impl Message {
         }
     }
 
-    fn format(&self) -> Bytes {
+    /// The line a subscriber sees in place of `dropped` messages it lagged
+    /// behind on.
+    fn lag_marker(dropped: u64) -> Self {
+        Self::new(Bytes::from(format!("[lagged {dropped} messages]")))
+    }
+
+    fn same_as(&self, other: &Self) -> bool {
+        self.timestamp == other.timestamp && self.data == other.data
+    }
+
+    fn rfc3339_timestamp(&self) -> String {
+        chrono::DateTime::<chrono::Utc>::from(self.timestamp)
+            .to_rfc3339_opts(chrono::SecondsFormat::Micros, true)
+    }
+
+    fn format(&self, framing: Framing) -> Bytes {
         let mut result = Vec::with_capacity(self.data.len() + 1);
-        result.extend_from_slice(&self.data);
+        match framing {
+            Framing::Raw => result.extend_from_slice(&self.data),
+            Framing::Timestamped => {
+                result.extend_from_slice(self.rfc3339_timestamp().as_bytes());
+                result.push(b' ');
+                result.extend_from_slice(&self.data);
+            }
+            Framing::Json => {
+                let frame = JsonFrame {
+                    ts: self.rfc3339_timestamp(),
+                    data: String::from_utf8_lossy(&self.data),
+                };
+                // Serializing a struct of two strings cannot fail.
+                serde_json::to_writer(&mut result, &frame).unwrap();
+            }
+        }
         result.push(b'\n');
         Bytes::from(result)
     }
@@ -129,7 +334,15 @@ struct Topic {
     mtime: RwLock<SystemTime>,
     ring_buffer: RwLock<VecDeque<Message>>,
     ring_size: usize,
+    /// The last message published with `retain`, kept outside the ring so
+    /// that it outlives the history.
+    retained: RwLock<Option<Message>>,
+    persistent: bool,
     total_messages: AtomicU64,
+    /// Messages subscribers lost by falling more than `channel_size`
+    /// behind, summed over subscribers.
+    dropped_messages: AtomicU64,
+    channel_size: usize,
     sender: broadcast::Sender<Message>,
     subscribers: RwLock<HashMap<u64, SubscriberInfo>>,
     next_subscriber_id: AtomicU64,
@@ -141,7 +354,7 @@ struct SubscriberInfo {
 }
 
 impl Topic {
-    fn new(name: String, ring_size: usize, channel_size: usize) -> Self {
+    fn new(name: String, ring_size: usize, channel_size: usize, persistent: bool) -> Self {
         let (sender, _) = broadcast::channel(channel_size);
         Self {
             name,
@@ -149,14 +362,61 @@ impl Topic {
             mtime: RwLock::new(SystemTime::now()),
             ring_buffer: RwLock::new(VecDeque::with_capacity(ring_size)),
             ring_size,
+            retained: RwLock::new(None),
+            persistent,
             total_messages: AtomicU64::new(0),
+            dropped_messages: AtomicU64::new(0),
+            channel_size,
             sender,
             subscribers: RwLock::new(HashMap::new()),
             next_subscriber_id: AtomicU64::new(1),
         }
     }
 
-    fn publish(&self, data: Bytes) -> FsResult<usize> {
+    /// Rebuild a persistent topic from a snapshot. If the ring size shrank
+    /// since the snapshot was taken only the newest messages are kept.
+    fn from_snapshot(snapshot: TopicSnapshot, ring_size: usize, channel_size: usize) -> Self {
+        let mut topic = Self::new(snapshot.name, ring_size, channel_size, true);
+        topic.created_at = snapshot.created_at;
+        *topic.mtime.get_mut().unwrap() = snapshot.mtime;
+        *topic.total_messages.get_mut() = snapshot.total_messages;
+        *topic.retained.get_mut().unwrap() = snapshot.retained.map(Message::from);
+
+        let skip = snapshot.messages.len().saturating_sub(ring_size);
+        topic
+            .ring_buffer
+            .get_mut()
+            .unwrap()
+            .extend(snapshot.messages.into_iter().skip(skip).map(Message::from));
+        topic
+    }
+
+    fn snapshot(&self) -> TopicSnapshot {
+        TopicSnapshot {
+            name: self.name.clone(),
+            created_at: self.created_at,
+            mtime: *self.mtime.read().unwrap(),
+            total_messages: self.total_messages.load(Ordering::SeqCst),
+            messages: self
+                .ring_buffer
+                .read()
+                .unwrap()
+                .iter()
+                .map(MessageSnapshot::from)
+                .collect(),
+            retained: self
+                .retained
+                .read()
+                .unwrap()
+                .as_ref()
+                .map(MessageSnapshot::from),
+        }
+    }
+
+    /// Publish `data` to the ring and to current subscribers. With `retain`
+    /// it also becomes the topic's retained message, or, when empty, clears
+    /// the retained message without being published.
+    fn publish(&self, data: Bytes, retain: bool) -> FsResult<usize> {
         if data.len() > MAX_MESSAGE_SIZE {
             return Err(FsError::invalid_argument(format!(
                 "message too large: {} > {}",
@@ -168,6 +428,17 @@ impl Topic {
         let msg = Message::new(data);
         let len = msg.data.len();
 
+        if retain {
+            let mut retained = self.retained.write().unwrap();
+            if msg.data.is_empty() {
+                *retained = None;
+                drop(retained);
+                *self.mtime.write().unwrap() = SystemTime::now();
+                return Ok(0);
+            }
+            *retained = Some(msg.clone());
+        }
+
         {
             let mut ring = self.ring_buffer.write().unwrap();
             if ring.len() >= self.ring_size {
@@ -184,7 +455,16 @@ impl Topic {
         Ok(len)
     }
 
-    fn subscribe(&self) -> (u64, broadcast::Receiver<Message>, Vec<Message>) {
+    /// Register a subscriber. Besides its receiver it gets the retained
+    /// message, if any, and the ring history without that same message.
+    fn subscribe(
+        &self,
+    ) -> (
+        u64,
+        broadcast::Receiver<Message>,
+        Option<Message>,
+        Vec<Message>,
+    ) {
         let id = self.next_subscriber_id.fetch_add(1, Ordering::SeqCst);
         let receiver = self.sender.subscribe();
 
@@ -195,9 +475,16 @@ impl Topic {
 
         self.subscribers.write().unwrap().insert(id, info);
 
-        let historical = self.ring_buffer.read().unwrap().iter().cloned().collect();
-
-        (id, receiver, historical)
+        let retained = self.retained.read().unwrap().clone();
+        let history = self
+            .ring_buffer
+            .read()
+            .unwrap()
+            .iter()
+            .filter(|msg| !retained.as_ref().is_some_and(|r| r.same_as(msg)))
+            .cloned()
+            .collect();
+        (id, receiver, retained, history)
     }
 
     fn unsubscribe(&self, subscriber_id: u64) {
@@ -221,11 +508,15 @@ impl Topic {
             .as_secs();
 
         format!(
-            "name: {}\nsubscribers: {}\nmessages: {}\nring_size: {}\ncreated: {}\nmodified: {}\n",
+            "name: {}\nsubscribers: {}\nmessages: {}\ndropped_messages: {}\nring_size: {}\nchannel_size: {}\nretained: {}\npersistent: {}\ncreated: {}\nmodified: {}\n",
             self.name,
             subscriber_count,
             message_count,
+            self.dropped_messages.load(Ordering::SeqCst),
             self.ring_size,
+            self.channel_size,
+            self.retained.read().unwrap().is_some(),
+            self.persistent,
             chrono::DateTime::<chrono::Utc>::from(self.created_at).format("%Y-%m-%d %H:%M:%S"),
             chrono::DateTime::<chrono::Utc>::from(*self.mtime.read().unwrap())
                 .format("%Y-%m-%d %H:%M:%S"),
@@ -236,7 +527,10 @@ impl Topic {
 enum HandleType {
     ReadmeFile(Vec<u8>),
     TopicInfo(Arc<Topic>),
-    TopicPublish(Arc<Topic>),
+    TopicPublish {
+        topic: Arc<Topic>,
+        retain: bool,
+    },
     TopicSubscribe {
         topic: Arc<Topic>,
         subscriber_id: u64,
@@ -246,7 +540,93 @@ enum HandleType {
         historical_sent: bool,
         historical: Vec<Message>,
         historical_index: usize,
+        framing: Framing,
     },
+    /// A subscription to every topic matching a `*` pattern, taken when the
+    /// handle was opened. Sources are polled round-robin.
+    TopicMultiSubscribe {
+        sources: Vec<Subscription>,
+        buffer: Vec<u8>,
+        buffer_offset: u64,
+        historical: Vec<Message>,
+        next_source: usize,
+        framing: Framing,
+    },
+}
+
+struct Subscription {
+    topic: Arc<Topic>,
+    subscriber_id: u64,
+    receiver: broadcast::Receiver<Message>,
+}
+
+/// Match `name` against `pattern`, where `*` matches any run of characters.
+fn glob_match(pattern: &str, name: &str) -> bool {
+    let (pattern, name) = (pattern.as_bytes(), name.as_bytes());
+    let (mut p, mut n) = (0, 0);
+    let mut backtrack = None;
+
+    while n < name.len() {
+        if p < pattern.len() && pattern[p] == b'*' {
+            backtrack = Some((p, n));
+            p += 1;
+        } else if p < pattern.len() && pattern[p] == name[n] {
+            p += 1;
+            n += 1;
+        } else if let Some((star, matched)) = backtrack {
+            p = star + 1;
+            n = matched + 1;
+            backtrack = Some((star, n));
+        } else {
+            return false;
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == b'*')
+}
+
+/// Move the next message queued on `receiver` into `buffer`. If the
+/// subscriber lagged behind instead, count the messages it lost against
+/// `topic` and, with `lag_markers`, put a marker line in their place.
+/// Returns whether there was anything to take.
+fn recv_into(
+    receiver: &mut broadcast::Receiver<Message>,
+    topic: &Topic,
+    buffer: &mut Vec<u8>,
+    framing: Framing,
+    lag_markers: bool,
+) -> bool {
+    match receiver.try_recv() {
+        Ok(msg) => buffer.extend_from_slice(&msg.format(framing)),
+        Err(broadcast::error::TryRecvError::Lagged(dropped)) => {
+            topic.dropped_messages.fetch_add(dropped, Ordering::SeqCst);
+            if lag_markers {
+                buffer.extend_from_slice(&Message::lag_marker(dropped).format(framing));
+            }
+        }
+        Err(_) => return false,
+    }
+    true
+}
+
+/// Serve a read at absolute stream `offset` from a subscriber buffer, trimming
+/// data the reader has long moved past.
+fn read_buffered(buffer: &mut Vec<u8>, buffer_offset: &mut u64, offset: u64, size: usize) -> Bytes {
+    let rel_offset = offset.saturating_sub(*buffer_offset) as usize;
+    if rel_offset >= buffer.len() {
+        return Bytes::new();
+    }
+
+    let end = (rel_offset + size).min(buffer.len());
+    let data = Bytes::copy_from_slice(&buffer[rel_offset..end]);
+
+    if buffer.len() > 1024 * 1024 && rel_offset > 64 * 1024 {
+        let trim = rel_offset - 64 * 1024;
+        buffer.drain(..trim);
+        *buffer_offset += trim as u64;
+    }
+
+    data
 }
 
 struct PubSubHandle {
@@ -259,18 +639,136 @@ pub(crate) struct PubSubFsProvider {
     pub(crate) topics: RwLock<HashMap<String, Arc<Topic>>>,
     default_ring_size: usize,
     default_channel_size: usize,
+    store: Option<Arc<dyn KvBackend>>,
+    persist_dir: Option<PathBuf>,
+    snapshot_interval: u64,
+    framing: Framing,
+    max_topics: Option<usize>,
+    channel_sizes: HashMap<String, usize>,
+    lag_markers: bool,
     handles: Mutex<HashMap<u64, PubSubHandle>>,
     next_handle_id: AtomicU64,
 }
 
 impl PubSubFsProvider {
     pub(crate) fn new(config: PubSubFsConfig) -> Self {
-        Self {
+        let store = config
+            .persist_dir
+            .as_ref()
+            .and_then(|dir| match DirKvBackend::new(dir) {
+                Ok(backend) => Some(Arc::new(backend) as Arc<dyn KvBackend>),
+                Err(e) => {
+                    eprintln!(
+                        "[pubsubfs] cannot use {} for persistence, topics stay in memory: {e}",
+                        dir.display()
+                    );
+                    None
+                }
+            });
+        Self::with_store(config, store)
+    }
+
+    /// Create a provider that snapshots topics to `store` (when given) and
+    /// restores every topic found there.
+    pub(crate) fn with_store(config: PubSubFsConfig, store: Option<Arc<dyn KvBackend>>) -> Self {
+        let provider = Self {
             topics: RwLock::new(HashMap::new()),
             default_ring_size: config.default_ring_size,
             default_channel_size: config.default_channel_size,
+            store,
+            persist_dir: config.persist_dir,
+            snapshot_interval: config.snapshot_interval.max(1),
+            framing: config.framing,
+            max_topics: config.max_topics,
+            channel_sizes: config.channel_sizes,
+            lag_markers: config.lag_markers,
             handles: Mutex::new(HashMap::new()),
             next_handle_id: AtomicU64::new(1),
+        };
+
+        if let Some(store) = &provider.store {
+            for (key, value) in store.scan(TOPIC_KEY_PREFIX) {
+                match serde_json::from_slice::<TopicSnapshot>(&value) {
+                    Ok(snapshot) => {
+                        let channel_size = provider.channel_size(&snapshot.name);
+                        let topic = Topic::from_snapshot(
+                            snapshot,
+                            provider.default_ring_size,
+                            channel_size,
+                        );
+                        provider
+                            .topics
+                            .write()
+                            .unwrap()
+                            .insert(topic.name.clone(), Arc::new(topic));
+                    }
+                    Err(e) => eprintln!(
+                        "[pubsubfs] skipping corrupt snapshot {}: {e}",
+                        String::from_utf8_lossy(&key)
+                    ),
+                }
+            }
+        }
+        provider
+    }
+
+    /// Channel size for the topic `name`: its entry in `channel_sizes`, or
+    /// the default.
+    fn channel_size(&self, name: &str) -> usize {
+        self.channel_sizes
+            .get(name)
+            .copied()
+            .unwrap_or(self.default_channel_size)
+    }
+
+    fn topic_key(name: &str) -> Vec<u8> {
+        [TOPIC_KEY_PREFIX, name.as_bytes()].concat()
+    }
+
+    /// Write a snapshot of `topic` to the store, unless it has been removed
+    /// (or removed and recreated) since the caller got hold of it. The topic
+    /// table stays read-locked while writing, so a concurrent `remove` either
+    /// runs first and the snapshot is skipped, or runs after and deletes it.
+    fn snapshot_topic(&self, topic: &Arc<Topic>) {
+        let Some(store) = &self.store else {
+            return;
+        };
+        let topics = self.topics.read().unwrap();
+        if !topics
+            .get(&topic.name)
+            .is_some_and(|current| Arc::ptr_eq(current, topic))
+        {
+            return;
+        }
+        match serde_json::to_vec(&topic.snapshot()) {
+            Ok(value) => store.set(&Self::topic_key(&topic.name), &value),
+            Err(e) => eprintln!("[pubsubfs] failed to snapshot {}: {e}", topic.name),
+        }
+    }
+
+    /// Write a snapshot of every topic to the store. A no-op when
+    /// persistence is disabled.
+    pub(crate) fn snapshot(&self) {
+        if self.store.is_none() {
+            return;
+        }
+        let topics: Vec<_> = self.topics.read().unwrap().values().cloned().collect();
+        for topic in topics {
+            self.snapshot_topic(&topic);
+        }
+    }
+
+    /// Fail when `persist_dir` is set but could not be opened or has since
+    /// gone away, so that topics would not survive a restart.
+    pub(crate) fn health_check(&self) -> FsResult<()> {
+        match &self.persist_dir {
+            Some(dir) if self.store.is_none() || !dir.is_dir() => {
+                Err(FsError::backend_unavailable(format!(
+                    "persist_dir {} is not usable",
+                    dir.display()
+                )))
+            }
+            _ => Ok(()),
         }
     }
 
@@ -288,19 +786,62 @@ impl PubSubFsProvider {
         }
     }
 
-    fn create_topic_if_needed(&self, name: &str) -> Arc<Topic> {
-        let mut topics = self.topics.write().unwrap();
-        if let Some(topic) = topics.get(name) {
-            topic.clone()
-        } else {
+    /// Split a `?key=value&...` suffix off a topic path and return the
+    /// options it selects.
+    fn parse_query(path: &str) -> FsResult<(&str, TopicQuery)> {
+        let mut options = TopicQuery::default();
+        let Some((path, query)) = path.split_once('?') else {
+            return Ok((path, options));
+        };
+
+        for param in query.split('&').filter(|p| !p.is_empty()) {
+            let (key, value) = param.split_once('=').unwrap_or((param, ""));
+            match key {
+                "ts" => {
+                    options.framing = Some(if parse_bool(key, value)? {
+                        Framing::Timestamped
+                    } else {
+                        Framing::Raw
+                    });
+                }
+                "framing" => options.framing = Some(Framing::parse(value)?),
+                "retain" => options.retain = parse_bool(key, value)?,
+                _ => {
+                    return Err(FsError::invalid_argument(format!(
+                        "unknown query parameter: {key}"
+                    )))
+                }
+            }
+        }
+        Ok((path, options))
+    }
+
+    fn create_topic_if_needed(&self, name: &str) -> FsResult<Arc<Topic>> {
+        let topic = {
+            let mut topics = self.topics.write().unwrap();
+            if let Some(topic) = topics.get(name) {
+                return Ok(topic.clone());
+            }
+            if let Some(max) = self.max_topics {
+                if topics.len() >= max {
+                    return Err(FsError::permission_denied(format!(
+                        "topic limit reached ({max}), cannot create {name}"
+                    )));
+                }
+            }
             let topic = Arc::new(Topic::new(
                 name.to_string(),
                 self.default_ring_size,
-                self.default_channel_size,
+                self.channel_size(name),
+                self.store.is_some(),
             ));
             topics.insert(name.to_string(), topic.clone());
             topic
-        }
+        };
+        // Stored once the table is write-unlocked, so store IO doesn't hold
+        // up creating other topics.
+        self.snapshot_topic(&topic);
+        Ok(topic)
     }
 
     pub(crate) fn stat(&self, path: &str) -> FsResult<FileInfo> {
@@ -388,7 +929,14 @@ impl PubSubFsProvider {
     }
 
     pub(crate) fn open(&self, path: &str, flags: OpenFlags) -> FsResult<(Handle, FileInfo)> {
+        let (path, query) = Self::parse_query(path)?;
         let path = Self::normalize_path(path);
+        let framing = query.framing;
+        if query.retain && !flags.write {
+            return Err(FsError::invalid_argument(
+                "retain only applies when publishing",
+            ));
+        }
         let handle_id = self.next_handle_id.fetch_add(1, Ordering::SeqCst);
 
         let handle_type = if path == "/README" {
@@ -418,9 +966,22 @@ impl PubSubFsProvider {
                     "cannot open a topic for both read (subscribe) and write (publish)",
                 ));
             }
-            if flags.write {
-                let topic = self.create_topic_if_needed(topic_name);
-                HandleType::TopicPublish(topic)
+            if topic_name.contains('*') {
+                if flags.write {
+                    return Err(FsError::invalid_argument(
+                        "cannot publish to a topic pattern",
+                    ));
+                }
+                if !flags.read {
+                    return Err(FsError::invalid_argument("must specify read or write mode"));
+                }
+                self.subscribe_pattern(topic_name, framing.unwrap_or(self.framing))?
+            } else if flags.write {
+                let topic = self.create_topic_if_needed(topic_name)?;
+                HandleType::TopicPublish {
+                    topic,
+                    retain: query.retain,
+                }
             } else if flags.read {
                 let topics = self.topics.read().unwrap();
                 let topic = topics
@@ -428,7 +989,8 @@ impl PubSubFsProvider {
                     .ok_or_else(|| FsError::not_found(topic_name))?
                     .clone();
 
-                let (subscriber_id, receiver, historical) = topic.subscribe();
+                let (subscriber_id, receiver, retained, history) = topic.subscribe();
+                let historical = retained.into_iter().chain(history).collect();
                 HandleType::TopicSubscribe {
                     topic,
                     subscriber_id,
@@ -438,6 +1000,7 @@ impl PubSubFsProvider {
                     historical_sent: false,
                     historical,
                     historical_index: 0,
+                    framing: framing.unwrap_or(self.framing),
                 }
             } else {
                 return Err(FsError::invalid_argument("must specify read or write mode"));
@@ -446,7 +1009,11 @@ impl PubSubFsProvider {
             return Err(FsError::not_found(&path));
         };
 
-        let info = self.stat(&path)?;
+        let info = if matches!(handle_type, HandleType::TopicMultiSubscribe { .. }) {
+            Self::pattern_info(&path)
+        } else {
+            self.stat(&path)?
+        };
 
         let handle = PubSubHandle {
             id: handle_id,
@@ -458,7 +1025,72 @@ impl PubSubFsProvider {
         Ok((Handle::new(handle_id), info))
     }
 
+    /// Subscribe to every existing topic whose name matches `pattern`.
+    /// Topics created afterwards are not picked up.
+    fn subscribe_pattern(&self, pattern: &str, framing: Framing) -> FsResult<HandleType> {
+        let mut matching: Vec<_> = self
+            .topics
+            .read()
+            .unwrap()
+            .values()
+            .filter(|topic| glob_match(pattern, &topic.name))
+            .cloned()
+            .collect();
+        if matching.is_empty() {
+            return Err(FsError::not_found(format!("/{pattern}")));
+        }
+        matching.sort_by(|a, b| a.name.cmp(&b.name));
+
+        let mut retained = Vec::new();
+        let mut historical = Vec::new();
+        let sources = matching
+            .into_iter()
+            .map(|topic| {
+                let (subscriber_id, receiver, last_value, history) = topic.subscribe();
+                retained.extend(last_value);
+                historical.extend(history);
+                Subscription {
+                    topic,
+                    subscriber_id,
+                    receiver,
+                }
+            })
+            .collect();
+        historical.sort_by_key(|msg| msg.timestamp);
+        // Retained messages go first, in topic order, as they do for a
+        // single topic.
+        retained.append(&mut historical);
+        let historical = retained;
+
+        Ok(HandleType::TopicMultiSubscribe {
+            sources,
+            buffer: Vec::new(),
+            buffer_offset: 0,
+            historical,
+            next_source: 0,
+            framing,
+        })
+    }
+
+    fn pattern_info(path: &str) -> FileInfo {
+        let now = SystemTime::now();
+        FileInfo {
+            path: path.to_string(),
+            size: 0,
+            file_type: FileType::Regular,
+            mode: 0o400,
+            uid: 0,
+            gid: 0,
+            atime: now,
+            mtime: now,
+            ctime: now,
+            etag: format!("pattern-{}", path.trim_start_matches('/')),
+            symlink_target: None,
+        }
+    }
+
     pub(crate) fn read(&self, handle: u64, offset: u64, size: usize) -> FsResult<Bytes> {
+        let lag_markers = self.lag_markers;
         let mut handles = self.handles.lock().unwrap();
         let h = handles
             .get_mut(&handle)
@@ -483,80 +1115,120 @@ impl PubSubFsProvider {
                 Ok(Bytes::copy_from_slice(&info.as_bytes()[start..end]))
             }
             HandleType::TopicSubscribe {
+                topic,
                 receiver,
                 buffer,
                 buffer_offset,
                 historical_sent,
                 historical,
                 historical_index,
+                framing,
                 ..
             } => {
                 if !*historical_sent {
                     for msg in &historical[*historical_index..] {
-                        let formatted = msg.format();
+                        let formatted = msg.format(*framing);
                         buffer.extend_from_slice(&formatted);
                     }
                     *historical_sent = true;
                 }
 
-                loop {
-                    match receiver.try_recv() {
-                        Ok(msg) => {
-                            let formatted = msg.format();
-                            buffer.extend_from_slice(&formatted);
-                        }
-                        Err(broadcast::error::TryRecvError::Empty) => break,
-                        Err(broadcast::error::TryRecvError::Lagged(_)) => {
-                            break;
-                        }
-                        Err(broadcast::error::TryRecvError::Closed) => break,
-                    }
-                }
+                while recv_into(receiver, topic, buffer, *framing, lag_markers) {}
 
-                let rel_offset = offset.saturating_sub(*buffer_offset) as usize;
-                if rel_offset < buffer.len() {
-                    let end = (rel_offset + size).min(buffer.len());
-                    let data = Bytes::copy_from_slice(&buffer[rel_offset..end]);
+                Ok(read_buffered(buffer, buffer_offset, offset, size))
+            }
+            HandleType::TopicMultiSubscribe {
+                sources,
+                buffer,
+                buffer_offset,
+                historical,
+                next_source,
+                framing,
+            } => {
+                for msg in historical.drain(..) {
+                    buffer.extend_from_slice(&msg.format(*framing));
+                }
 
-                    if buffer.len() > 1024 * 1024 && rel_offset > 64 * 1024 {
-                        let trim = rel_offset - 64 * 1024;
-                        buffer.drain(..trim);
-                        *buffer_offset += trim as u64;
+                // Take one message from each source in turn so a busy topic
+                // cannot starve the others.
+                let mut idle = 0;
+                while idle < sources.len() {
+                    let current = *next_source;
+                    *next_source = (current + 1) % sources.len();
+                    let source = &mut sources[current];
+                    if recv_into(
+                        &mut source.receiver,
+                        &source.topic,
+                        buffer,
+                        *framing,
+                        lag_markers,
+                    ) {
+                        idle = 0;
+                    } else {
+                        idle += 1;
                     }
-
-                    return Ok(data);
                 }
 
-                Ok(Bytes::new())
+                Ok(read_buffered(buffer, buffer_offset, offset, size))
             }
             _ => Err(FsError::permission_denied("cannot read from this handle")),
         }
     }
 
-    pub(crate) fn write(&self, handle: u64, data: &[u8]) -> FsResult<usize> {
+    /// The topic a publish handle writes to and whether it retains, or
+    /// `None` for other handles.
+    fn publisher(&self, handle: u64) -> FsResult<Option<(Arc<Topic>, bool)>> {
         let handles = self.handles.lock().unwrap();
         let h = handles
             .get(&handle)
             .ok_or_else(|| FsError::invalid_handle(handle))?;
+        Ok(match &h.handle_type {
+            HandleType::TopicPublish { topic, retain } => Some((topic.clone(), *retain)),
+            _ => None,
+        })
+    }
+
+    pub(crate) fn write(&self, handle: u64, data: &[u8]) -> FsResult<usize> {
+        // Publishing and snapshotting happen without the handle table locked,
+        // so a slow store doesn't hold up every other handle.
+        let (topic, retain) = self
+            .publisher(handle)?
+            .ok_or_else(|| FsError::permission_denied("cannot write to this handle"))?;
+        let written = topic.publish(Bytes::copy_from_slice(data), retain)?;
+        if topic.total_messages.load(Ordering::SeqCst) % self.snapshot_interval == 0 {
+            self.snapshot_topic(&topic);
+        }
+        Ok(written)
+    }
 
-        match &h.handle_type {
-            HandleType::TopicPublish(topic) => topic.publish(Bytes::copy_from_slice(data)),
-            _ => Err(FsError::permission_denied("cannot write to this handle")),
+    /// Snapshot the topic behind a publish handle, so that what was
+    /// published through it survives a restart. Other handles have nothing
+    /// to sync.
+    pub(crate) fn fsync(&self, handle: u64) -> FsResult<()> {
+        if let Some((topic, _)) = self.publisher(handle)? {
+            self.snapshot_topic(&topic);
         }
+        Ok(())
     }
 
     pub(crate) fn close(&self, handle: u64) -> FsResult<()> {
-        let mut handles = self.handles.lock().unwrap();
+        let Some(h) = self.handles.lock().unwrap().remove(&handle) else {
+            return Ok(());
+        };
 
-        if let Some(h) = handles.remove(&handle) {
-            if let HandleType::TopicSubscribe {
+        match h.handle_type {
+            HandleType::TopicSubscribe {
                 topic,
                 subscriber_id,
                 ..
-            } = h.handle_type
-            {
-                topic.unsubscribe(subscriber_id);
+            } => topic.unsubscribe(subscriber_id),
+            HandleType::TopicMultiSubscribe { sources, .. } => {
+                for source in sources {
+                    source.topic.unsubscribe(source.subscriber_id);
+                }
             }
+            HandleType::TopicPublish { topic, .. } => self.snapshot_topic(&topic),
+            _ => {}
         }
 
         Ok(())
@@ -636,6 +1308,9 @@ impl PubSubFsProvider {
             if !topic_name.is_empty() && !topic_name.contains('/') {
                 let mut topics = self.topics.write().unwrap();
                 if topics.remove(topic_name).is_some() {
+                    if let Some(store) = &self.store {
+                        store.delete(&Self::topic_key(topic_name));
+                    }
                     return Ok(());
                 } else {
                     return Err(FsError::not_found(&path));
@@ -646,3 +1321,9 @@ impl PubSubFsProvider {
         Err(FsError::not_found(&path))
     }
 }
+
+impl Drop for PubSubFsProvider {
+    fn drop(&mut self) {
+        self.snapshot();
+    }
+}
@@ -0,0 +1,126 @@
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::RwLock;
+
+/// Minimal key-value store used to persist topic snapshots.
+///
+/// Mirrors the `KvBackend` trait of `PageFS` so the same kinds of backends
+/// can be plugged in.
+pub trait KvBackend: Send + Sync {
+    fn get(&self, key: &[u8]) -> Option<Vec<u8>>;
+    fn set(&self, key: &[u8], value: &[u8]);
+    fn scan(&self, prefix: &[u8]) -> Vec<(Vec<u8>, Vec<u8>)>;
+    fn delete(&self, key: &[u8]);
+}
+
+#[derive(Default)]
+pub struct InMemoryKv {
+    data: RwLock<BTreeMap<Vec<u8>, Vec<u8>>>,
+}
+
+impl InMemoryKv {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl KvBackend for InMemoryKv {
+    fn get(&self, key: &[u8]) -> Option<Vec<u8>> {
+        self.data.read().unwrap().get(key).cloned()
+    }
+
+    fn set(&self, key: &[u8], value: &[u8]) {
+        self.data
+            .write()
+            .unwrap()
+            .insert(key.to_vec(), value.to_vec());
+    }
+
+    fn scan(&self, prefix: &[u8]) -> Vec<(Vec<u8>, Vec<u8>)> {
+        let data = self.data.read().unwrap();
+        data.range(prefix.to_vec()..)
+            .take_while(|(k, _)| k.starts_with(prefix))
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect()
+    }
+
+    fn delete(&self, key: &[u8]) {
+        self.data.write().unwrap().remove(key);
+    }
+}
+
+/// Stores each key as one file in a directory.
+///
+/// File names are the hex-encoded key, and values are written to a temporary file and renamed
+/// into place so a crash never leaves a half-written snapshot behind.
+pub struct DirKvBackend {
+    dir: PathBuf,
+}
+
+impl DirKvBackend {
+    pub fn new(dir: impl Into<PathBuf>) -> std::io::Result<Self> {
+        let dir = dir.into();
+        std::fs::create_dir_all(&dir)?;
+        Ok(Self { dir })
+    }
+
+    fn path_for(&self, key: &[u8]) -> PathBuf {
+        self.dir.join(hex_encode(key))
+    }
+}
+
+impl KvBackend for DirKvBackend {
+    fn get(&self, key: &[u8]) -> Option<Vec<u8>> {
+        std::fs::read(self.path_for(key)).ok()
+    }
+
+    fn set(&self, key: &[u8], value: &[u8]) {
+        let path = self.path_for(key);
+        let tmp = path.with_extension("tmp");
+        let result = std::fs::write(&tmp, value).and_then(|()| std::fs::rename(&tmp, &path));
+        if let Err(e) = result {
+            eprintln!("[pubsubfs] failed to write {}: {e}", path.display());
+        }
+    }
+
+    fn scan(&self, prefix: &[u8]) -> Vec<(Vec<u8>, Vec<u8>)> {
+        let Ok(entries) = std::fs::read_dir(&self.dir) else {
+            return Vec::new();
+        };
+        let mut result: Vec<_> = entries
+            .filter_map(Result::ok)
+            .filter_map(|entry| {
+                let key = hex_decode(entry.file_name().to_str()?)?;
+                if !key.starts_with(prefix) {
+                    return None;
+                }
+                let value = std::fs::read(entry.path()).ok()?;
+                Some((key, value))
+            })
+            .collect();
+        result.sort_by(|a, b| a.0.cmp(&b.0));
+        result
+    }
+
+    fn delete(&self, key: &[u8]) {
+        let _ = std::fs::remove_file(self.path_for(key));
+    }
+}
+
+fn hex_encode(bytes: &[u8]) -> String {
+    use std::fmt::Write;
+    bytes.iter().fold(String::new(), |mut s, b| {
+        let _ = write!(s, "{b:02x}");
+        s
+    })
+}
+
+fn hex_decode(s: &str) -> Option<Vec<u8>> {
+    if s.len() % 2 != 0 {
+        return None;
+    }
+    (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(s.get(i..i + 2)?, 16).ok())
+        .collect()
+}
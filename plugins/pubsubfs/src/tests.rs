@@ -38,7 +38,8 @@ fn create_topic_auto() {
                 ..Default::default()
             },
         )
-        .unwrap();
+        .unwrap()
+        .0;
 
     let topics = provider.topics.read().unwrap();
     assert!(topics.contains_key("test_topic"));
@@ -58,7 +59,8 @@ fn delete_topic() {
                 ..Default::default()
             },
         )
-        .unwrap();
+        .unwrap()
+        .0;
     provider.close(h.id()).unwrap();
 
     provider.remove("/test").unwrap();
@@ -79,7 +81,8 @@ fn publish_and_subscribe() {
                 ..Default::default()
             },
         )
-        .unwrap();
+        .unwrap()
+        .0;
 
     let sub_h = provider
         .open(
@@ -89,7 +92,8 @@ fn publish_and_subscribe() {
                 ..Default::default()
             },
         )
-        .unwrap();
+        .unwrap()
+        .0;
 
     provider.write(pub_h.id(), b"hello world").unwrap();
 
@@ -112,7 +116,8 @@ fn multiple_subscribers() {
                 ..Default::default()
             },
         )
-        .unwrap();
+        .unwrap()
+        .0;
 
     let sub1 = provider
         .open(
@@ -122,7 +127,8 @@ fn multiple_subscribers() {
                 ..Default::default()
             },
         )
-        .unwrap();
+        .unwrap()
+        .0;
 
     let sub2 = provider
         .open(
@@ -132,7 +138,8 @@ fn multiple_subscribers() {
                 ..Default::default()
             },
         )
-        .unwrap();
+        .unwrap()
+        .0;
 
     provider.write(pub_h.id(), b"broadcast message").unwrap();
 
@@ -159,7 +166,8 @@ fn topic_info() {
                 ..Default::default()
             },
         )
-        .unwrap();
+        .unwrap()
+        .0;
     provider.close(h.id()).unwrap();
 
     let info_h = provider
@@ -170,7 +178,8 @@ fn topic_info() {
                 ..Default::default()
             },
         )
-        .unwrap();
+        .unwrap()
+        .0;
 
     let data = provider.read(info_h.id(), 0, 4096).unwrap();
     let info = String::from_utf8_lossy(&data);
@@ -194,7 +203,8 @@ fn list_topics() {
                 ..Default::default()
             },
         )
-        .unwrap();
+        .unwrap()
+        .0;
     let h2 = provider
         .open(
             "/topic2",
@@ -203,7 +213,8 @@ fn list_topics() {
                 ..Default::default()
             },
         )
-        .unwrap();
+        .unwrap()
+        .0;
     provider.close(h1.id()).unwrap();
     provider.close(h2.id()).unwrap();
 
@@ -227,7 +238,8 @@ fn readdir_root() {
                 ..Default::default()
             },
         )
-        .unwrap();
+        .unwrap()
+        .0;
     let h2 = provider
         .open(
             "/logs",
@@ -236,7 +248,8 @@ fn readdir_root() {
                 ..Default::default()
             },
         )
-        .unwrap();
+        .unwrap()
+        .0;
     provider.close(h1.id()).unwrap();
     provider.close(h2.id()).unwrap();
 
@@ -254,6 +267,7 @@ fn ring_buffer_historical_messages() {
     let config = PubSubFsConfig {
         default_ring_size: 3,
         default_channel_size: 10,
+        ..Default::default()
     };
     let provider = PubSubFsProvider::new(config);
 
@@ -265,7 +279,8 @@ fn ring_buffer_historical_messages() {
                 ..Default::default()
             },
         )
-        .unwrap();
+        .unwrap()
+        .0;
 
     provider.write(pub_h.id(), b"msg1").unwrap();
     provider.write(pub_h.id(), b"msg2").unwrap();
@@ -280,7 +295,8 @@ fn ring_buffer_historical_messages() {
                 ..Default::default()
             },
         )
-        .unwrap();
+        .unwrap()
+        .0;
 
     let data = provider.read(sub_h.id(), 0, 4096).unwrap();
     let content = String::from_utf8_lossy(&data);
@@ -309,3 +325,642 @@ fn cannot_open_read_write_simultaneously() {
 
     assert!(result.is_err());
 }
+
+fn publish_all(provider: &PubSubFsProvider, path: &str, messages: &[&str]) {
+    let h = provider
+        .open(
+            path,
+            OpenFlags {
+                write: true,
+                ..Default::default()
+            },
+        )
+        .unwrap()
+        .0;
+    for msg in messages {
+        provider.write(h.id(), msg.as_bytes()).unwrap();
+    }
+    provider.close(h.id()).unwrap();
+}
+
+fn read_all(provider: &PubSubFsProvider, path: &str) -> String {
+    let h = provider
+        .open(
+            path,
+            OpenFlags {
+                read: true,
+                ..Default::default()
+            },
+        )
+        .unwrap()
+        .0;
+    let data = provider.read(h.id(), 0, 4096).unwrap();
+    provider.close(h.id()).unwrap();
+    String::from_utf8_lossy(&data).into_owned()
+}
+
+#[test]
+fn persistent_topics_survive_restart() {
+    let store: Arc<dyn KvBackend> = Arc::new(InMemoryKv::new());
+    let config = PubSubFsConfig {
+        default_ring_size: 3,
+        snapshot_interval: 100,
+        ..Default::default()
+    };
+
+    let provider = PubSubFsProvider::with_store(config.clone(), Some(store.clone()));
+    let h = provider
+        .open(
+            "/audit",
+            OpenFlags {
+                write: true,
+                ..Default::default()
+            },
+        )
+        .unwrap()
+        .0;
+    for msg in ["one", "two", "three", "four"] {
+        provider.write(h.id(), msg.as_bytes()).unwrap();
+    }
+    provider.snapshot();
+
+    let restored = PubSubFsProvider::with_store(config, Some(store));
+    assert_eq!(read_all(&restored, "/audit"), "two\nthree\nfour\n");
+
+    let info = read_all(&restored, "/audit.info");
+    assert!(info.contains("messages: 4"));
+    assert!(info.contains("persistent: true"));
+
+    provider.close(h.id()).unwrap();
+}
+
+#[test]
+fn snapshots_follow_interval_and_close() {
+    let store: Arc<dyn KvBackend> = Arc::new(InMemoryKv::new());
+    let config = PubSubFsConfig {
+        snapshot_interval: 2,
+        ..Default::default()
+    };
+    let provider = PubSubFsProvider::with_store(config.clone(), Some(store.clone()));
+
+    let h = provider
+        .open(
+            "/events",
+            OpenFlags {
+                write: true,
+                ..Default::default()
+            },
+        )
+        .unwrap()
+        .0;
+    provider.write(h.id(), b"a").unwrap();
+    provider.write(h.id(), b"b").unwrap();
+    provider.write(h.id(), b"c").unwrap();
+
+    let restored = PubSubFsProvider::with_store(config.clone(), Some(store.clone()));
+    assert_eq!(read_all(&restored, "/events"), "a\nb\n");
+
+    provider.close(h.id()).unwrap();
+    let restored = PubSubFsProvider::with_store(config, Some(store));
+    assert_eq!(read_all(&restored, "/events"), "a\nb\nc\n");
+}
+
+#[test]
+fn fsync_snapshots_the_published_topic() {
+    let store: Arc<dyn KvBackend> = Arc::new(InMemoryKv::new());
+    let config = PubSubFsConfig {
+        snapshot_interval: 100,
+        ..Default::default()
+    };
+    let provider = PubSubFsProvider::with_store(config, Some(store.clone()));
+    let (publisher, _) = provider
+        .open(
+            "/events",
+            OpenFlags {
+                write: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+    let (info, _) = provider.open("/events.info", OpenFlags::read()).unwrap();
+    provider.write(publisher.id(), b"a").unwrap();
+    let persisted = || {
+        store
+            .get(&PubSubFsProvider::topic_key("events"))
+            .map_or(0, |value| {
+                serde_json::from_slice::<TopicSnapshot>(&value)
+                    .unwrap()
+                    .messages
+                    .len()
+            })
+    };
+
+    provider.fsync(info.id()).unwrap();
+    assert_eq!(persisted(), 0);
+    provider.fsync(publisher.id()).unwrap();
+    assert_eq!(persisted(), 1);
+
+    assert!(provider.fsync(999).is_err());
+    provider.close(publisher.id()).unwrap();
+    provider.close(info.id()).unwrap();
+}
+
+#[test]
+fn removed_topic_is_not_restored() {
+    let store: Arc<dyn KvBackend> = Arc::new(InMemoryKv::new());
+    let provider = PubSubFsProvider::with_store(PubSubFsConfig::default(), Some(store.clone()));
+    publish_all(&provider, "/gone", &["x"]);
+    publish_all(&provider, "/kept", &["y"]);
+    provider.remove("/gone").unwrap();
+    drop(provider);
+
+    let restored = PubSubFsProvider::with_store(PubSubFsConfig::default(), Some(store));
+    let topics = restored.topics.read().unwrap();
+    assert!(!topics.contains_key("gone"));
+    assert!(topics.contains_key("kept"));
+}
+
+#[test]
+fn publisher_open_across_remove_does_not_restore_topic() {
+    let store: Arc<dyn KvBackend> = Arc::new(InMemoryKv::new());
+    let config = PubSubFsConfig {
+        snapshot_interval: 1,
+        ..Default::default()
+    };
+    let provider = PubSubFsProvider::with_store(config.clone(), Some(store.clone()));
+    let (publisher, _) = provider
+        .open(
+            "/gone",
+            OpenFlags {
+                write: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+    provider.write(publisher.id(), b"x").unwrap();
+    provider.remove("/gone").unwrap();
+
+    provider.write(publisher.id(), b"y").unwrap();
+    provider.fsync(publisher.id()).unwrap();
+    provider.close(publisher.id()).unwrap();
+    assert!(store.get(&PubSubFsProvider::topic_key("gone")).is_none());
+
+    let restored = PubSubFsProvider::with_store(config, Some(store));
+    assert!(!restored.topics.read().unwrap().contains_key("gone"));
+}
+
+#[test]
+fn ephemeral_by_default() {
+    let provider = PubSubFsProvider::new(PubSubFsConfig::default());
+    publish_all(&provider, "/chat", &["hi"]);
+    assert!(read_all(&provider, "/chat.info").contains("persistent: false"));
+}
+
+#[test]
+fn persist_dir_restores_topics() {
+    let dir = std::env::temp_dir().join(format!("fs9_pubsubfs_persist_{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let config = PubSubFsConfig {
+        persist_dir: Some(dir.clone()),
+        ..Default::default()
+    };
+
+    {
+        let provider = PubSubFsProvider::new(config.clone());
+        publish_all(&provider, "/logs", &["first", "second"]);
+    }
+
+    let provider = PubSubFsProvider::new(config);
+    assert_eq!(read_all(&provider, "/logs"), "first\nsecond\n");
+    provider.health_check().unwrap();
+
+    let _ = std::fs::remove_dir_all(&dir);
+    assert!(matches!(
+        provider.health_check(),
+        Err(FsError::BackendUnavailable(_))
+    ));
+}
+
+#[test]
+fn raw_framing_is_unchanged() {
+    let provider = PubSubFsProvider::new(PubSubFsConfig::default());
+    publish_all(&provider, "/chat", &["hello", "world\n"]);
+
+    assert_eq!(read_all(&provider, "/chat"), "hello\nworld\n");
+    assert_eq!(read_all(&provider, "/chat?ts=0"), "hello\nworld\n");
+}
+
+#[test]
+fn timestamped_framing_prefixes_rfc3339() {
+    let provider = PubSubFsProvider::new(PubSubFsConfig::default());
+    publish_all(&provider, "/chat", &["hello world", "bye"]);
+
+    let content = read_all(&provider, "/chat?ts=1");
+    let lines: Vec<_> = content.lines().collect();
+    assert_eq!(lines.len(), 2);
+
+    let (ts, data) = lines[0].split_once(' ').unwrap();
+    chrono::DateTime::parse_from_rfc3339(ts).unwrap();
+    assert_eq!(data, "hello world");
+    assert!(lines[1].ends_with(" bye"));
+}
+
+#[test]
+fn json_framing_from_config() {
+    let config: PubSubFsConfig = serde_json::from_str(r#"{"framing":"json"}"#).unwrap();
+    let provider = PubSubFsProvider::new(config);
+    publish_all(&provider, "/logs", &["boot \"ok\""]);
+
+    let content = read_all(&provider, "/logs");
+    assert!(content.starts_with("{\"ts\":"));
+    let frame: serde_json::Value = serde_json::from_str(content.trim_end()).unwrap();
+    assert_eq!(frame["data"], "boot \"ok\"");
+    chrono::DateTime::parse_from_rfc3339(frame["ts"].as_str().unwrap()).unwrap();
+
+    assert_eq!(read_all(&provider, "/logs?framing=raw"), "boot \"ok\"\n");
+}
+
+#[test]
+fn invalid_framing_query_is_rejected() {
+    let provider = PubSubFsProvider::new(PubSubFsConfig::default());
+    publish_all(&provider, "/chat", &["x"]);
+
+    let flags = OpenFlags {
+        read: true,
+        ..Default::default()
+    };
+    assert!(matches!(
+        provider.open("/chat?framing=xml", flags),
+        Err(FsError::InvalidArgument(_))
+    ));
+    assert!(matches!(
+        provider.open("/chat?colour=1", flags),
+        Err(FsError::InvalidArgument(_))
+    ));
+}
+
+#[test]
+fn wildcard_subscription_fans_in_matching_topics() {
+    let provider = PubSubFsProvider::new(PubSubFsConfig::default());
+    publish_all(&provider, "/logs.app", &["app-old"]);
+    publish_all(&provider, "/logs.db", &[]);
+    publish_all(&provider, "/logs.cache", &[]);
+    publish_all(&provider, "/metrics", &["metrics-old"]);
+
+    let sub = provider
+        .open(
+            "/logs.*",
+            OpenFlags {
+                read: true,
+                ..Default::default()
+            },
+        )
+        .unwrap()
+        .0;
+
+    publish_all(&provider, "/logs.app", &["app-1", "app-2"]);
+    publish_all(&provider, "/logs.db", &["db-1"]);
+    publish_all(&provider, "/logs.cache", &["cache-1"]);
+    publish_all(&provider, "/metrics", &["metrics-1"]);
+
+    let data = provider.read(sub.id(), 0, 4096).unwrap();
+    let content = String::from_utf8_lossy(&data);
+    let mut lines: Vec<_> = content.lines().collect();
+    assert_eq!(lines[0], "app-old");
+    lines.sort_unstable();
+    assert_eq!(lines, ["app-1", "app-2", "app-old", "cache-1", "db-1"]);
+
+    let app = provider.topics.read().unwrap()["logs.app"].clone();
+    assert_eq!(app.subscribers.read().unwrap().len(), 1);
+    provider.close(sub.id()).unwrap();
+    assert!(app.subscribers.read().unwrap().is_empty());
+}
+
+#[test]
+fn wildcard_subscription_polls_round_robin() {
+    let provider = PubSubFsProvider::new(PubSubFsConfig::default());
+    publish_all(&provider, "/a.1", &[]);
+    publish_all(&provider, "/a.2", &[]);
+
+    let sub = provider
+        .open(
+            "/a.*",
+            OpenFlags {
+                read: true,
+                ..Default::default()
+            },
+        )
+        .unwrap()
+        .0;
+    publish_all(&provider, "/a.1", &["x1", "x2", "x3"]);
+    publish_all(&provider, "/a.2", &["y1", "y2"]);
+
+    let data = provider.read(sub.id(), 0, 4096).unwrap();
+    assert_eq!(&data[..], b"x1\ny1\nx2\ny2\nx3\n");
+    provider.close(sub.id()).unwrap();
+}
+
+#[test]
+fn wildcard_without_matches_or_for_write_fails() {
+    let provider = PubSubFsProvider::new(PubSubFsConfig::default());
+    publish_all(&provider, "/chat", &["x"]);
+
+    let read = OpenFlags {
+        read: true,
+        ..Default::default()
+    };
+    assert!(matches!(
+        provider.open("/logs.*", read),
+        Err(FsError::NotFound(_))
+    ));
+    assert!(matches!(
+        provider.open(
+            "/c*",
+            OpenFlags {
+                write: true,
+                ..Default::default()
+            }
+        ),
+        Err(FsError::InvalidArgument(_))
+    ));
+    assert!(!provider.topics.read().unwrap().contains_key("c*"));
+}
+
+#[test]
+fn glob_matching() {
+    assert!(glob_match("logs.*", "logs.app"));
+    assert!(glob_match("logs.*", "logs."));
+    assert!(!glob_match("logs.*", "logs"));
+    assert!(glob_match("*.err", "app.db.err"));
+    assert!(glob_match("a*b*c", "aXbYbZc"));
+    assert!(!glob_match("a*b*c", "aXbY"));
+    assert!(glob_match("*", "anything"));
+}
+
+#[test]
+fn max_topics_limits_creation() {
+    let config = PubSubFsConfig {
+        max_topics: Some(2),
+        ..Default::default()
+    };
+    let provider = PubSubFsProvider::new(config);
+    let write = OpenFlags {
+        write: true,
+        ..Default::default()
+    };
+
+    publish_all(&provider, "/one", &["a"]);
+    publish_all(&provider, "/two", &["b"]);
+
+    assert!(matches!(
+        provider.open("/three", write),
+        Err(FsError::PermissionDenied(_))
+    ));
+    assert!(!provider.topics.read().unwrap().contains_key("three"));
+
+    publish_all(&provider, "/one", &["c"]);
+    publish_all(&provider, "/two", &["d"]);
+    assert_eq!(read_all(&provider, "/one"), "a\nc\n");
+    assert_eq!(read_all(&provider, "/two"), "b\nd\n");
+
+    provider.remove("/two").unwrap();
+    publish_all(&provider, "/three", &["e"]);
+}
+
+#[test]
+fn max_topics_error_maps_to_permission_denied_code() {
+    let config = br#"{"max_topics":0}"#;
+    let path = b"/topic";
+    let flags = fs9_sdk_ffi::COpenFlags {
+        write: 1,
+        ..Default::default()
+    };
+    unsafe {
+        let vtable = &*ffi::fs9_plugin_vtable();
+        let provider = ffi::create_provider_for_test(config.as_ptr().cast(), config.len());
+        let mut handle = 0;
+        let mut info = fs9_sdk_ffi::CFileInfo::default();
+        let result = (vtable.open)(
+            provider,
+            path.as_ptr().cast(),
+            path.len(),
+            &flags,
+            &mut handle,
+            &mut info,
+        );
+        assert_eq!(result.code, fs9_sdk_ffi::FS9_ERR_PERMISSION_DENIED);
+        ffi::destroy_provider_for_test(provider);
+    }
+}
+
+#[test]
+fn retained_message_reaches_late_subscribers_first() {
+    let config = PubSubFsConfig {
+        default_ring_size: 2,
+        ..Default::default()
+    };
+    let provider = PubSubFsProvider::new(config);
+    publish_all(&provider, "/status?retain=1", &["online"]);
+    publish_all(&provider, "/status", &["a", "b"]);
+    assert!(read_all(&provider, "/status.info").contains("retained: true"));
+
+    // The retained message has left the ring but is still delivered, ahead
+    // of the history and of what is published after subscribing.
+    let flags = OpenFlags {
+        read: true,
+        ..Default::default()
+    };
+    let sub = provider.open("/status", flags).unwrap().0;
+    publish_all(&provider, "/status", &["live"]);
+    let data = provider.read(sub.id(), 0, 4096).unwrap();
+    assert_eq!(&data[..], b"online\na\nb\nlive\n");
+    provider.close(sub.id()).unwrap();
+
+    // A retained message still in the ring is not delivered twice, and a
+    // newer one replaces it.
+    publish_all(&provider, "/status?retain=true", &["busy"]);
+    assert_eq!(read_all(&provider, "/status"), "busy\nlive\n");
+}
+
+#[test]
+fn empty_retained_message_clears_retention() {
+    let provider = PubSubFsProvider::new(PubSubFsConfig::default());
+    publish_all(&provider, "/status?retain=1", &["online\n"]);
+    publish_all(&provider, "/status", &["x"]);
+    publish_all(&provider, "/status?retain=1", &["\n"]);
+
+    assert!(read_all(&provider, "/status.info").contains("retained: false"));
+    assert_eq!(read_all(&provider, "/status"), "online\nx\n");
+
+    let flags = OpenFlags {
+        read: true,
+        ..Default::default()
+    };
+    assert!(matches!(
+        provider.open("/status?retain=1", flags),
+        Err(FsError::InvalidArgument(_))
+    ));
+}
+
+#[test]
+fn retained_messages_lead_wildcard_subscriptions_and_persist() {
+    let store: Arc<dyn KvBackend> = Arc::new(InMemoryKv::new());
+    let config = PubSubFsConfig {
+        default_ring_size: 1,
+        ..Default::default()
+    };
+    let provider = PubSubFsProvider::with_store(config.clone(), Some(store.clone()));
+    publish_all(&provider, "/sensor.b?retain=1", &["b=1"]);
+    publish_all(&provider, "/sensor.a?retain=1", &["a=1"]);
+    publish_all(&provider, "/sensor.a", &["a=2"]);
+    assert_eq!(read_all(&provider, "/sensor.*"), "a=1\nb=1\na=2\n");
+    drop(provider);
+
+    let restored = PubSubFsProvider::with_store(config, Some(store));
+    assert_eq!(read_all(&restored, "/sensor.a"), "a=1\na=2\n");
+}
+
+fn subscribe(provider: &PubSubFsProvider, path: &str) -> u64 {
+    provider
+        .open(
+            path,
+            OpenFlags {
+                read: true,
+                ..Default::default()
+            },
+        )
+        .unwrap()
+        .0
+        .id()
+}
+
+fn info(provider: &PubSubFsProvider, topic: &str) -> String {
+    read_all(provider, &format!("/{topic}.info"))
+}
+
+#[test]
+fn lagging_subscriber_sees_marker_and_drop_count() {
+    let config = PubSubFsConfig {
+        default_ring_size: 0,
+        default_channel_size: 4,
+        ..Default::default()
+    };
+    let provider = PubSubFsProvider::new(config);
+    publish_all(&provider, "/burst", &[]);
+
+    let slow = subscribe(&provider, "/burst");
+    let fast = subscribe(&provider, "/burst");
+    let messages: Vec<String> = (1..=10).map(|i| format!("m{i}")).collect();
+    let mut fast_seen = String::new();
+    for (i, msg) in messages.iter().enumerate() {
+        publish_all(&provider, "/burst", &[msg]);
+        let data = provider.read(fast, fast_seen.len() as u64, 4096).unwrap();
+        fast_seen.push_str(std::str::from_utf8(&data).unwrap());
+        assert!(fast_seen.ends_with(&format!("m{}\n", i + 1)));
+    }
+
+    // Only the last four fit in the slow subscriber's channel.
+    let data = provider.read(slow, 0, 4096).unwrap();
+    assert_eq!(
+        String::from_utf8_lossy(&data),
+        "[lagged 6 messages]\nm7\nm8\nm9\nm10\n"
+    );
+    assert!(info(&provider, "burst").contains("dropped_messages: 6\n"));
+
+    // The subscriber that kept up got everything and lost nothing.
+    assert_eq!(fast_seen, messages.join("\n") + "\n");
+    assert!(!fast_seen.contains("lagged"));
+
+    provider.close(slow).unwrap();
+    provider.close(fast).unwrap();
+}
+
+#[test]
+fn lag_markers_can_be_turned_off() {
+    let config = PubSubFsConfig {
+        default_ring_size: 0,
+        default_channel_size: 2,
+        lag_markers: false,
+        ..Default::default()
+    };
+    let provider = PubSubFsProvider::new(config);
+    publish_all(&provider, "/burst", &[]);
+
+    let slow = subscribe(&provider, "/burst");
+    publish_all(&provider, "/burst", &["a", "b", "c", "d", "e"]);
+
+    let data = provider.read(slow, 0, 4096).unwrap();
+    assert_eq!(String::from_utf8_lossy(&data), "d\ne\n");
+    assert!(info(&provider, "burst").contains("dropped_messages: 3\n"));
+    provider.close(slow).unwrap();
+}
+
+#[test]
+fn per_topic_channel_size_absorbs_bursts() {
+    let config = PubSubFsConfig {
+        default_ring_size: 0,
+        default_channel_size: 2,
+        channel_sizes: HashMap::from([("bursty".to_string(), 16)]),
+        ..Default::default()
+    };
+    let provider = PubSubFsProvider::new(config);
+    publish_all(&provider, "/bursty", &[]);
+    publish_all(&provider, "/quiet", &[]);
+
+    let bursty = subscribe(&provider, "/bursty");
+    let quiet = subscribe(&provider, "/quiet");
+    let burst = ["1", "2", "3", "4", "5", "6", "7", "8"];
+    publish_all(&provider, "/bursty", &burst);
+    publish_all(&provider, "/quiet", &burst);
+
+    let data = provider.read(bursty, 0, 4096).unwrap();
+    assert_eq!(String::from_utf8_lossy(&data), burst.join("\n") + "\n");
+    assert!(info(&provider, "bursty").contains("channel_size: 16\n"));
+    assert!(info(&provider, "bursty").contains("dropped_messages: 0\n"));
+
+    let data = provider.read(quiet, 0, 4096).unwrap();
+    assert!(String::from_utf8_lossy(&data).starts_with("[lagged 6 messages]\n"));
+    assert!(info(&provider, "quiet").contains("channel_size: 2\n"));
+
+    provider.close(bursty).unwrap();
+    provider.close(quiet).unwrap();
+}
+
+#[test]
+fn wildcard_subscribers_count_lag_per_topic() {
+    let config = PubSubFsConfig {
+        default_ring_size: 0,
+        default_channel_size: 2,
+        ..Default::default()
+    };
+    let provider = PubSubFsProvider::new(config);
+    publish_all(&provider, "/logs.a", &[]);
+    publish_all(&provider, "/logs.b", &[]);
+
+    let sub = subscribe(&provider, "/logs.*");
+    publish_all(&provider, "/logs.a", &["a1", "a2", "a3"]);
+    publish_all(&provider, "/logs.b", &["b1"]);
+
+    let data = provider.read(sub, 0, 4096).unwrap();
+    let text = String::from_utf8_lossy(&data);
+    assert!(text.contains("[lagged 1 messages]\n"), "{text}");
+    assert!(text.contains("b1\n") && text.contains("a3\n"), "{text}");
+    assert!(info(&provider, "logs.a").contains("dropped_messages: 1\n"));
+    assert!(info(&provider, "logs.b").contains("dropped_messages: 0\n"));
+    provider.close(sub).unwrap();
+}
+
+#[test]
+fn snapshot_payloads_are_base64() {
+    let message = MessageSnapshot {
+        timestamp: SystemTime::UNIX_EPOCH,
+        data: vec![0, 159, 255],
+    };
+    let mut value = serde_json::to_value(&message).unwrap();
+    assert_eq!(value["data"], "AJ//");
+
+    // Snapshots from before the payloads were encoded.
+    value["data"] = serde_json::json!([0, 159, 255]);
+    let legacy: MessageSnapshot = serde_json::from_value(value).unwrap();
+    assert_eq!(legacy.data, [0, 159, 255]);
+}
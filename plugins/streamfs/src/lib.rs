@@ -1,6 +1,7 @@
 #![allow(clippy::missing_safety_doc)]
 
 use std::collections::HashMap;
+use std::io::{Read, Write};
 use std::ptr;
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::{Arc, Mutex, RwLock};
@@ -19,6 +20,8 @@ use tokio::sync::broadcast;
 
 const DEFAULT_RING_SIZE: usize = 100;
 const DEFAULT_CHANNEL_SIZE: usize = 100;
+/// Largest ring size a stream may ask for with `?ring=N`.
+const MAX_RING_SIZE: usize = 100_000;
 
 const README_CONTENT: &str = r#"StreamFS - Streaming File System Plugin
 
@@ -34,10 +37,20 @@ FEATURES:
 USAGE:
   Write:  echo "data" > /streamfs/mystream
   Read:   cat /streamfs/mystream
+  Deeper history for one stream, chosen when it is created:
+          echo "data" > '/streamfs/telemetry?ring=10000'
 
 NOTES:
   - Streams are append-only (offset is ignored on write)
+  - Once a stream is removed, each reader gets a single end-of-stream error
+    (FS9_ERR_END_OF_STREAM) after draining it; later reads return no
+    data
+  - The ring buffer retains the last ring_size chunks written to a stream
+    (100 by default). Readers may seek backwards to any offset inside that
+    window; reading from an offset older than it fails with EINVAL
   - Data is in-memory only (not persistent across restarts)
+  - With "compress": true in the mount config, chunks are kept gzipped in
+    the ring and unpacked for readers; stream sizes stay uncompressed
 "#;
 
 #[derive(Debug, Clone, Deserialize)]
@@ -46,6 +59,10 @@ struct StreamFsConfig {
     ring_size: usize,
     #[serde(default = "default_channel_size")]
     channel_size: usize,
+    /// Keep ring chunks gzip-compressed, trading CPU on every write and
+    /// read for memory on highly compressible streams.
+    #[serde(default)]
+    compress: bool,
 }
 
 fn default_ring_size() -> usize {
@@ -61,10 +78,59 @@ impl Default for StreamFsConfig {
         Self {
             ring_size: DEFAULT_RING_SIZE,
             channel_size: DEFAULT_CHANNEL_SIZE,
+            compress: false,
         }
     }
 }
 
+/// A chunk as held in the ring and sent to live readers.
+#[derive(Debug, Clone, Default)]
+struct StoredChunk {
+    bytes: Bytes,
+    gzipped: bool,
+}
+
+impl StoredChunk {
+    /// Hold `data` gzipped when `compress` is set and that makes it
+    /// smaller, and as is otherwise.
+    fn new(data: Bytes, compress: bool) -> Self {
+        let gzipped = compress.then(|| {
+            let mut encoder = flate2::write::GzEncoder::new(
+                Vec::with_capacity(data.len() / 2),
+                flate2::Compression::fast(),
+            );
+            encoder.write_all(&data).ok()?;
+            encoder.finish().ok()
+        });
+        match gzipped.flatten() {
+            Some(packed) if packed.len() < data.len() => Self {
+                bytes: Bytes::from(packed),
+                gzipped: true,
+            },
+            _ => Self {
+                bytes: data,
+                gzipped: false,
+            },
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    /// The chunk as it was written.
+    fn data(&self) -> FsResult<Bytes> {
+        if !self.gzipped {
+            return Ok(self.bytes.clone());
+        }
+        let mut data = Vec::new();
+        flate2::read::GzDecoder::new(&self.bytes[..])
+            .read_to_end(&mut data)
+            .map_err(|e| FsError::internal(format!("corrupt compressed chunk: {e}")))?;
+        Ok(Bytes::from(data))
+    }
+}
+
 struct ReaderState {
     #[allow(dead_code)]
     id: u64,
@@ -77,25 +143,28 @@ struct StreamFile {
     total_written: AtomicU64,
     closed: RwLock<bool>,
     mtime: RwLock<SystemTime>,
-    ring_buffer: RwLock<Vec<Bytes>>,
+    /// Recent chunks with the stream offset each one starts at.
+    ring_buffer: RwLock<Vec<(u64, StoredChunk)>>,
     ring_size: usize,
+    compress: bool,
     write_index: AtomicU64,
     total_chunks: AtomicU64,
-    sender: broadcast::Sender<Bytes>,
+    sender: broadcast::Sender<(u64, StoredChunk)>,
     readers: RwLock<HashMap<u64, Arc<ReaderState>>>,
     next_reader_id: AtomicU64,
 }
 
 impl StreamFile {
-    fn new(name: String, ring_size: usize, channel_size: usize) -> Self {
+    fn new(name: String, ring_size: usize, channel_size: usize, compress: bool) -> Self {
         let (sender, _) = broadcast::channel(channel_size);
         Self {
             name,
             total_written: AtomicU64::new(0),
             closed: RwLock::new(false),
             mtime: RwLock::new(SystemTime::now()),
-            ring_buffer: RwLock::new(vec![Bytes::new(); ring_size]),
+            ring_buffer: RwLock::new(vec![(0, StoredChunk::default()); ring_size]),
             ring_size,
+            compress,
             write_index: AtomicU64::new(0),
             total_chunks: AtomicU64::new(0),
             sender,
@@ -134,23 +203,25 @@ impl StreamFile {
             return Ok(0);
         }
 
+        let chunk = StoredChunk::new(data, self.compress);
         {
+            // Holding the ring lock keeps chunk offsets and broadcast order
+            // consistent between concurrent writers.
             let mut ring = self.ring_buffer.write().unwrap();
+            let start = self.total_written.fetch_add(len as u64, Ordering::SeqCst);
             let idx = (self.write_index.load(Ordering::SeqCst) as usize) % self.ring_size;
-            ring[idx] = data.clone();
+            ring[idx] = (start, chunk.clone());
+            self.write_index.fetch_add(1, Ordering::SeqCst);
+            self.total_chunks.fetch_add(1, Ordering::SeqCst);
+            let _ = self.sender.send((start, chunk));
         }
 
-        self.write_index.fetch_add(1, Ordering::SeqCst);
-        self.total_chunks.fetch_add(1, Ordering::SeqCst);
-        self.total_written.fetch_add(len as u64, Ordering::SeqCst);
         *self.mtime.write().unwrap() = SystemTime::now();
 
-        let _ = self.sender.send(data);
-
         Ok(len)
     }
 
-    fn register_reader(&self) -> (u64, broadcast::Receiver<Bytes>) {
+    fn register_reader(&self) -> (u64, broadcast::Receiver<(u64, StoredChunk)>) {
         let id = self.next_reader_id.fetch_add(1, Ordering::SeqCst);
         let receiver = self.sender.subscribe();
 
@@ -167,7 +238,9 @@ impl StreamFile {
         self.readers.write().unwrap().remove(&reader_id);
     }
 
-    fn get_historical_chunks(&self, from_index: u64) -> Vec<Bytes> {
+    /// Chunks still held in the ring from chunk number `from_index` on, each
+    /// paired with the stream offset it starts at.
+    fn get_historical_chunks(&self, from_index: u64) -> FsResult<Vec<(u64, Bytes)>> {
         let ring = self.ring_buffer.read().unwrap();
         let total = self.total_chunks.load(Ordering::SeqCst);
         let oldest = total.saturating_sub(self.ring_size as u64);
@@ -176,13 +249,31 @@ impl StreamFile {
         let mut chunks = Vec::new();
 
         for i in start..total {
-            let idx = (i as usize) % self.ring_size;
-            if !ring[idx].is_empty() {
-                chunks.push(ring[idx].clone());
+            let (offset, chunk) = &ring[(i as usize) % self.ring_size];
+            if !chunk.is_empty() {
+                chunks.push((*offset, chunk.data()?));
             }
         }
 
-        chunks
+        Ok(chunks)
+    }
+
+    /// Stream offset of the oldest chunk the ring still holds, if any.
+    fn oldest_retained_offset(&self) -> Option<u64> {
+        let ring = self.ring_buffer.read().unwrap();
+        let total = self.total_chunks.load(Ordering::SeqCst);
+        let oldest = total.saturating_sub(self.ring_size as u64);
+        (oldest..total)
+            .map(|i| &ring[(i as usize) % self.ring_size])
+            .find(|(_, chunk)| !chunk.is_empty())
+            .map(|(offset, _)| *offset)
+    }
+
+    /// Bytes the ring currently holds, compressed or not.
+    #[cfg(test)]
+    fn stored_bytes(&self) -> usize {
+        let ring = self.ring_buffer.read().unwrap();
+        ring.iter().map(|(_, chunk)| chunk.bytes.len()).sum()
     }
 
     fn close(&self) {
@@ -198,17 +289,78 @@ struct StreamHandle {
     flags: OpenFlags,
     stream: Option<Arc<StreamFile>>,
     reader_id: Option<u64>,
-    receiver: Option<broadcast::Receiver<Bytes>>,
+    receiver: Option<broadcast::Receiver<(u64, StoredChunk)>>,
     read_buffer: Vec<u8>,
     read_base: u64,
+    /// Stream offset that read offset 0 of this handle corresponds to.
+    stream_base: u64,
     historical_sent: bool,
     historical_index: u64,
+    /// Set once this reader has been told the stream ended.
+    eof_reported: bool,
+}
+
+impl StreamHandle {
+    /// Append a chunk that starts at stream offset `offset`, skipping bytes
+    /// already buffered. If chunks were lost in between (the reader lagged
+    /// behind the broadcast channel), the buffer restarts at this chunk.
+    fn append_chunk(&mut self, offset: u64, data: &[u8]) {
+        let end = self.stream_base + self.read_base + self.read_buffer.len() as u64;
+        let chunk_end = offset + data.len() as u64;
+        if chunk_end <= end {
+            return;
+        }
+
+        if offset > end {
+            self.read_buffer.clear();
+            self.read_base = offset - self.stream_base;
+            self.read_buffer.extend_from_slice(data);
+        } else {
+            self.read_buffer
+                .extend_from_slice(&data[(end - offset) as usize..]);
+        }
+    }
+
+    /// Move the start of the read buffer back to `offset`, rebuilding the
+    /// missing bytes from the chunks the ring still retains.
+    fn rewind_to(&mut self, stream: &StreamFile, offset: u64) -> FsResult<()> {
+        let target = self.stream_base + offset;
+        let buffer_start = self.stream_base + self.read_base;
+        let chunks = stream.get_historical_chunks(0)?;
+
+        let retained_from = chunks.first().map_or(buffer_start, |(start, _)| *start);
+        if target < retained_from {
+            return Err(FsError::invalid_argument(format!(
+                "offset {offset} is no longer retained by the ring buffer; \
+                 the oldest readable offset is {}",
+                retained_from.saturating_sub(self.stream_base)
+            )));
+        }
+
+        let mut rebuilt =
+            Vec::with_capacity((buffer_start - target) as usize + self.read_buffer.len());
+        for (start, data) in &chunks {
+            let chunk_end = start + data.len() as u64;
+            if chunk_end <= target || *start >= buffer_start {
+                continue;
+            }
+            let from = target.saturating_sub(*start) as usize;
+            let to = (chunk_end.min(buffer_start) - start) as usize;
+            rebuilt.extend_from_slice(&data[from..to]);
+        }
+        rebuilt.extend_from_slice(&self.read_buffer);
+
+        self.read_buffer = rebuilt;
+        self.read_base = offset;
+        Ok(())
+    }
 }
 
 struct StreamFsProvider {
     streams: RwLock<HashMap<String, Arc<StreamFile>>>,
     ring_size: usize,
     channel_size: usize,
+    compress: bool,
     handles: Mutex<HashMap<u64, StreamHandle>>,
     next_handle_id: AtomicU64,
 }
@@ -219,6 +371,7 @@ impl StreamFsProvider {
             streams: RwLock::new(HashMap::new()),
             ring_size: config.ring_size,
             channel_size: config.channel_size,
+            compress: config.compress,
             handles: Mutex::new(HashMap::new()),
             next_handle_id: AtomicU64::new(1),
         }
@@ -285,7 +438,36 @@ impl StreamFsProvider {
         Ok(stream.get_info())
     }
 
+    /// Split a `?ring=N` suffix off an open path.
+    fn parse_query(path: &str) -> FsResult<(&str, Option<usize>)> {
+        let Some((path, query)) = path.split_once('?') else {
+            return Ok((path, None));
+        };
+
+        let mut ring_size = None;
+        for param in query.split('&').filter(|p| !p.is_empty()) {
+            let (key, value) = param.split_once('=').unwrap_or((param, ""));
+            if key != "ring" {
+                return Err(FsError::invalid_argument(format!(
+                    "unknown query parameter: {key}"
+                )));
+            }
+            let size = value
+                .parse::<usize>()
+                .ok()
+                .filter(|size| (1..=MAX_RING_SIZE).contains(size))
+                .ok_or_else(|| {
+                    FsError::invalid_argument(format!(
+                        "ring size must be between 1 and {MAX_RING_SIZE}, got {value}"
+                    ))
+                })?;
+            ring_size = Some(size);
+        }
+        Ok((path, ring_size))
+    }
+
     fn open(&self, path: &str, flags: OpenFlags) -> FsResult<(Handle, FileInfo)> {
+        let (path, ring_size) = Self::parse_query(path)?;
         let path = Self::normalize_path(path);
 
         if path == "/README" {
@@ -300,8 +482,10 @@ impl StreamFsProvider {
                 receiver: None,
                 read_buffer: README_CONTENT.as_bytes().to_vec(),
                 read_base: 0,
+                stream_base: 0,
                 historical_sent: true,
                 historical_index: 0,
+                eof_reported: false,
             };
             self.handles.lock().unwrap().insert(handle_id, handle);
             return Ok((Handle::new(handle_id), info));
@@ -315,10 +499,14 @@ impl StreamFsProvider {
                 if !flags.create && !flags.write {
                     return Err(FsError::not_found(&path));
                 }
+                // The ring size is fixed when the stream is created; a
+                // `?ring=` on later opens of an existing stream is ignored.
+                let ring_size = ring_size.filter(|_| flags.create).unwrap_or(self.ring_size);
                 let s = Arc::new(StreamFile::new(
                     path.clone(),
-                    self.ring_size,
+                    ring_size,
                     self.channel_size,
+                    self.compress,
                 ));
                 streams.insert(path.clone(), s.clone());
                 s
@@ -336,6 +524,9 @@ impl StreamFsProvider {
 
         let total_chunks = stream.total_chunks.load(Ordering::SeqCst);
         let oldest = total_chunks.saturating_sub(stream.ring_size as u64);
+        let stream_base = stream
+            .oldest_retained_offset()
+            .unwrap_or_else(|| stream.total_written.load(Ordering::SeqCst));
 
         let handle = StreamHandle {
             id: handle_id,
@@ -346,8 +537,10 @@ impl StreamFsProvider {
             receiver,
             read_buffer: Vec::new(),
             read_base: 0,
+            stream_base,
             historical_sent: false,
             historical_index: oldest,
+            eof_reported: false,
         };
 
         self.handles.lock().unwrap().insert(handle_id, handle);
@@ -373,29 +566,38 @@ impl StreamFsProvider {
 
         let stream = h
             .stream
-            .as_ref()
+            .clone()
             .ok_or_else(|| FsError::internal("no stream"))?;
+        // Checked before draining so every chunk written before the close
+        // is delivered ahead of the end-of-stream signal.
+        let closed = stream.is_closed();
 
         if !h.historical_sent {
-            let historical = stream.get_historical_chunks(h.historical_index);
-            for chunk in historical {
-                h.read_buffer.extend_from_slice(&chunk);
+            let historical = stream.get_historical_chunks(h.historical_index)?;
+            for (start, chunk) in historical {
+                h.append_chunk(start, &chunk);
             }
             h.historical_sent = true;
         }
 
+        let mut received = Vec::new();
         if let Some(ref mut receiver) = h.receiver {
             loop {
                 match receiver.try_recv() {
-                    Ok(chunk) => {
-                        h.read_buffer.extend_from_slice(&chunk);
-                    }
+                    Ok(chunk) => received.push(chunk),
                     Err(broadcast::error::TryRecvError::Empty) => break,
                     Err(broadcast::error::TryRecvError::Lagged(_)) => break,
                     Err(broadcast::error::TryRecvError::Closed) => break,
                 }
             }
         }
+        for (start, chunk) in received {
+            h.append_chunk(start, &chunk.data()?);
+        }
+
+        if offset < h.read_base {
+            h.rewind_to(&stream, offset)?;
+        }
 
         let rel_offset = offset.saturating_sub(h.read_base) as usize;
         if rel_offset < h.read_buffer.len() {
@@ -411,6 +613,11 @@ impl StreamFsProvider {
             return Ok(data);
         }
 
+        if closed && !h.eof_reported {
+            h.eof_reported = true;
+            return Err(FsError::end_of_stream(&h.path));
+        }
+
         Ok(Bytes::new())
     }
 
@@ -492,12 +699,20 @@ fn make_cresult_err(code: i32) -> CResult {
     }
 }
 
+fn fserror_to_cresult(err: &FsError) -> CResult {
+    CResult::from_error(fserror_to_code(err), err)
+}
+
 fn fserror_to_code(err: &FsError) -> i32 {
     match err {
         FsError::NotFound(_) => FS9_ERR_NOT_FOUND,
         FsError::IsDirectory(_) => FS9_ERR_IS_DIRECTORY,
         FsError::InvalidHandle(_) => FS9_ERR_INVALID_HANDLE,
         FsError::PermissionDenied(_) => fs9_sdk_ffi::FS9_ERR_PERMISSION_DENIED,
+        FsError::InvalidArgument(_) => fs9_sdk_ffi::FS9_ERR_INVALID_ARGUMENT,
+        FsError::BackendUnavailable(_) => fs9_sdk_ffi::FS9_ERR_BACKEND_UNAVAILABLE,
+        FsError::NoSpace(_) => fs9_sdk_ffi::FS9_ERR_NO_SPACE,
+        FsError::EndOfStream(_) => fs9_sdk_ffi::FS9_ERR_END_OF_STREAM,
         _ => fs9_sdk_ffi::FS9_ERR_INTERNAL,
     }
 }
@@ -525,7 +740,8 @@ unsafe extern "C" fn get_capabilities(_provider: *mut c_void) -> u64 {
         | Capabilities::WRITE
         | Capabilities::CREATE
         | Capabilities::DELETE
-        | Capabilities::DIRECTORY)
+        | Capabilities::DIRECTORY
+        | Capabilities::STREAMING)
         .bits()
 }
 
@@ -555,13 +771,14 @@ unsafe extern "C" fn stat_fn(
             (*out_info).mtime = systemtime_to_timestamp(info.mtime);
             (*out_info).atime = systemtime_to_timestamp(info.atime);
             (*out_info).ctime = systemtime_to_timestamp(info.ctime);
+            (*out_info).set_etag(&info.etag);
             CResult {
                 code: FS9_OK,
                 error_msg: ptr::null(),
                 error_msg_len: 0,
             }
         }
-        Err(e) => make_cresult_err(fserror_to_code(&e)),
+        Err(e) => fserror_to_cresult(&e),
     }
 }
 
@@ -622,6 +839,8 @@ unsafe extern "C" fn open_fn(
         truncate: flags.truncate != 0,
         append: flags.append != 0,
         directory: flags.directory != 0,
+        symlink: flags.symlink != 0,
+        exclusive: flags.excl != 0,
     };
 
     match provider.open(path, open_flags) {
@@ -639,13 +858,14 @@ unsafe extern "C" fn open_fn(
             (*out_info).mtime = systemtime_to_timestamp(info.mtime);
             (*out_info).atime = systemtime_to_timestamp(info.atime);
             (*out_info).ctime = systemtime_to_timestamp(info.ctime);
+            (*out_info).set_etag(&info.etag);
             CResult {
                 code: FS9_OK,
                 error_msg: ptr::null(),
                 error_msg_len: 0,
             }
         }
-        Err(e) => make_cresult_err(fserror_to_code(&e)),
+        Err(e) => fserror_to_cresult(&e),
     }
 }
 
@@ -664,14 +884,14 @@ unsafe extern "C" fn read_fn(
 
     match provider.read(handle, offset, size) {
         Ok(data) => {
-            *out_data = fs9_sdk_ffi::vec_to_cbytes(data.to_vec());
+            *out_data = fs9_sdk_ffi::bytes_to_cbytes(data);
             CResult {
                 code: FS9_OK,
                 error_msg: ptr::null(),
                 error_msg_len: 0,
             }
         }
-        Err(e) => make_cresult_err(fserror_to_code(&e)),
+        Err(e) => fserror_to_cresult(&e),
     }
 }
 
@@ -703,7 +923,7 @@ unsafe extern "C" fn write_fn(
                 error_msg_len: 0,
             }
         }
-        Err(e) => make_cresult_err(fserror_to_code(&e)),
+        Err(e) => fserror_to_cresult(&e),
     }
 }
 
@@ -720,7 +940,7 @@ unsafe extern "C" fn close_fn(provider: *mut c_void, handle: u64, _sync: u8) ->
             error_msg: ptr::null(),
             error_msg_len: 0,
         },
-        Err(e) => make_cresult_err(fserror_to_code(&e)),
+        Err(e) => fserror_to_cresult(&e),
     }
 }
 
@@ -743,7 +963,7 @@ unsafe extern "C" fn readdir_fn(
         Ok(entries) => {
             for entry in entries {
                 let path_bytes = entry.path.as_bytes();
-                let info = CFileInfo {
+                let mut info = CFileInfo {
                     path: path_bytes.as_ptr() as *const c_char,
                     path_len: path_bytes.len(),
                     size: entry.size,
@@ -758,7 +978,9 @@ unsafe extern "C" fn readdir_fn(
                     atime: systemtime_to_timestamp(entry.atime),
                     mtime: systemtime_to_timestamp(entry.mtime),
                     ctime: systemtime_to_timestamp(entry.ctime),
+                    ..CFileInfo::default()
                 };
+                info.set_etag(&entry.etag);
                 if callback(&info, user_data) != 0 {
                     break;
                 }
@@ -769,7 +991,7 @@ unsafe extern "C" fn readdir_fn(
                 error_msg_len: 0,
             }
         }
-        Err(e) => make_cresult_err(fserror_to_code(&e)),
+        Err(e) => fserror_to_cresult(&e),
     }
 }
 
@@ -792,10 +1014,124 @@ unsafe extern "C" fn remove_fn(
             error_msg: ptr::null(),
             error_msg_len: 0,
         },
-        Err(e) => make_cresult_err(fserror_to_code(&e)),
+        Err(e) => fserror_to_cresult(&e),
+    }
+}
+
+unsafe extern "C" fn fsync_fn(_provider: *mut c_void, _handle: u64, _data_only: u8) -> CResult {
+    // Streams are never persisted, so a sync has nothing to write.
+    CResult {
+        code: FS9_OK,
+        error_msg: ptr::null(),
+        error_msg_len: 0,
     }
 }
 
+unsafe extern "C" fn flush_fn(_provider: *mut c_void) -> CResult {
+    CResult {
+        code: FS9_OK,
+        error_msg: ptr::null(),
+        error_msg_len: 0,
+    }
+}
+
+unsafe extern "C" fn health_check_fn(_provider: *mut c_void) -> CResult {
+    CResult::ok()
+}
+
+unsafe extern "C" fn snapshot_fn(
+    _provider: *mut c_void,
+    _path: *const c_char,
+    _path_len: size_t,
+    _out_id: *mut u64,
+) -> CResult {
+    make_cresult_err(fs9_sdk_ffi::FS9_ERR_NOT_IMPLEMENTED)
+}
+
+unsafe extern "C" fn open_snapshot_fn(
+    _provider: *mut c_void,
+    _snapshot_id: u64,
+    _out_view: *mut *mut c_void,
+) -> CResult {
+    make_cresult_err(fs9_sdk_ffi::FS9_ERR_NOT_IMPLEMENTED)
+}
+
+unsafe extern "C" fn getxattr_fn(
+    _provider: *mut c_void,
+    _path: *const c_char,
+    _path_len: size_t,
+    _name: *const c_char,
+    _name_len: size_t,
+    _out_value: *mut CBytes,
+) -> CResult {
+    make_cresult_err(fs9_sdk_ffi::FS9_ERR_NOT_IMPLEMENTED)
+}
+
+unsafe extern "C" fn setxattr_fn(
+    _provider: *mut c_void,
+    _path: *const c_char,
+    _path_len: size_t,
+    _name: *const c_char,
+    _name_len: size_t,
+    _value: *const u8,
+    _value_len: size_t,
+) -> CResult {
+    make_cresult_err(fs9_sdk_ffi::FS9_ERR_NOT_IMPLEMENTED)
+}
+
+unsafe extern "C" fn listxattr_fn(
+    _provider: *mut c_void,
+    _path: *const c_char,
+    _path_len: size_t,
+    _out_names: *mut CBytes,
+) -> CResult {
+    make_cresult_err(fs9_sdk_ffi::FS9_ERR_NOT_IMPLEMENTED)
+}
+
+unsafe extern "C" fn removexattr_fn(
+    _provider: *mut c_void,
+    _path: *const c_char,
+    _path_len: size_t,
+    _name: *const c_char,
+    _name_len: size_t,
+) -> CResult {
+    make_cresult_err(fs9_sdk_ffi::FS9_ERR_NOT_IMPLEMENTED)
+}
+
+unsafe extern "C" fn link_fn(
+    _provider: *mut c_void,
+    _existing_path: *const c_char,
+    _existing_path_len: size_t,
+    _new_path: *const c_char,
+    _new_path_len: size_t,
+) -> CResult {
+    make_cresult_err(fs9_sdk_ffi::FS9_ERR_NOT_IMPLEMENTED)
+}
+
+unsafe extern "C" fn rename_fn(
+    _provider: *mut c_void,
+    _old_path: *const c_char,
+    _old_path_len: size_t,
+    _new_path: *const c_char,
+    _new_path_len: size_t,
+) -> CResult {
+    make_cresult_err(fs9_sdk_ffi::FS9_ERR_NOT_IMPLEMENTED)
+}
+
+unsafe extern "C" fn readdir_at_fn(
+    _provider: *mut c_void,
+    _path: *const c_char,
+    _path_len: size_t,
+    _cursor: *const c_char,
+    _cursor_len: size_t,
+    _max: size_t,
+    _callback: fs9_sdk_ffi::ReaddirCallback,
+    _user_data: *mut c_void,
+    _out_next_cursor: *mut CBytes,
+) -> CResult {
+    make_cresult_err(fs9_sdk_ffi::FS9_ERR_NOT_IMPLEMENTED)
+}
+
 static PLUGIN_NAME: &[u8] = b"streamfs";
 static PLUGIN_VERSION: &[u8] = b"0.1.0";
 
@@ -817,6 +1153,18 @@ static VTABLE: PluginVTable = PluginVTable {
     close: close_fn,
     readdir: readdir_fn,
     remove: remove_fn,
+    fsync: fsync_fn,
+    link: link_fn,
+    rename: rename_fn,
+    readdir_at: readdir_at_fn,
+    flush: flush_fn,
+    getxattr: getxattr_fn,
+    setxattr: setxattr_fn,
+    listxattr: listxattr_fn,
+    removexattr: removexattr_fn,
+    health_check: health_check_fn,
+    snapshot: snapshot_fn,
+    open_snapshot: open_snapshot_fn,
 };
 
 #[no_mangle]
@@ -870,7 +1218,8 @@ mod tests {
                     ..Default::default()
                 },
             )
-            .unwrap();
+            .unwrap()
+            .0;
 
         let written = provider.write(handle.id(), b"hello").unwrap();
         assert_eq!(written, 5);
@@ -891,7 +1240,8 @@ mod tests {
                     ..Default::default()
                 },
             )
-            .unwrap();
+            .unwrap()
+            .0;
 
         provider.write(wh.id(), b"hello").unwrap();
         provider.write(wh.id(), b"world").unwrap();
@@ -904,7 +1254,8 @@ mod tests {
                     ..Default::default()
                 },
             )
-            .unwrap();
+            .unwrap()
+            .0;
 
         let data = provider.read(rh.id(), 0, 1024).unwrap();
         assert_eq!(&data[..], b"helloworld");
@@ -926,7 +1277,8 @@ mod tests {
                     ..Default::default()
                 },
             )
-            .unwrap();
+            .unwrap()
+            .0;
 
         let h2 = provider
             .open(
@@ -937,7 +1289,8 @@ mod tests {
                     ..Default::default()
                 },
             )
-            .unwrap();
+            .unwrap()
+            .0;
 
         let entries = provider.readdir("/").unwrap();
         assert_eq!(entries.len(), 3);
@@ -959,7 +1312,8 @@ mod tests {
                     ..Default::default()
                 },
             )
-            .unwrap();
+            .unwrap()
+            .0;
         provider.close(h.id()).unwrap();
 
         provider.remove("/test").unwrap();
@@ -967,4 +1321,197 @@ mod tests {
         let result = provider.stat("/test");
         assert!(result.is_err());
     }
+
+    fn open_stream(provider: &StreamFsProvider, path: &str, flags: OpenFlags) -> u64 {
+        provider.open(path, flags).unwrap().0.id()
+    }
+
+    fn chunk(n: usize, len: usize) -> Vec<u8> {
+        (0..len).map(|i| ((n * 31 + i) % 251) as u8).collect()
+    }
+
+    #[test]
+    fn read_after_seeking_back_within_ring() {
+        const CHUNK: usize = 64 * 1024;
+        let provider = StreamFsProvider::new(StreamFsConfig::default());
+        let rh = open_stream(&provider, "/replay", OpenFlags::create_file());
+        let wh = open_stream(&provider, "/replay", OpenFlags::write());
+
+        let expected: Vec<u8> = (0..20).flat_map(|n| chunk(n, CHUNK)).collect();
+        for n in 0..20 {
+            provider.write(wh, &chunk(n, CHUNK)).unwrap();
+        }
+
+        let mut offset = 0;
+        while offset < expected.len() {
+            let data = provider.read(rh, offset as u64, CHUNK).unwrap();
+            assert_eq!(&data[..], &expected[offset..offset + data.len()]);
+            offset += data.len();
+        }
+        assert!(provider.handles.lock().unwrap()[&rh].read_base > 0);
+
+        let data = provider.read(rh, 0, CHUNK * 2).unwrap();
+        assert_eq!(&data[..], &expected[..CHUNK * 2]);
+
+        let data = provider.read(rh, 100_000, 1000).unwrap();
+        assert_eq!(&data[..], &expected[100_000..101_000]);
+    }
+
+    #[test]
+    fn seeking_before_ring_window_fails() {
+        const CHUNK: usize = 256 * 1024;
+        let config = StreamFsConfig {
+            ring_size: 4,
+            ..Default::default()
+        };
+        let provider = StreamFsProvider::new(config);
+        let rh = open_stream(&provider, "/replay", OpenFlags::create_file());
+        let wh = open_stream(&provider, "/replay", OpenFlags::write());
+
+        let expected: Vec<u8> = (0..8).flat_map(|n| chunk(n, CHUNK)).collect();
+        for n in 0..8 {
+            provider.write(wh, &chunk(n, CHUNK)).unwrap();
+        }
+
+        let tail = expected.len() - 1000;
+        let data = provider.read(rh, tail as u64, 1000).unwrap();
+        assert_eq!(&data[..], &expected[tail..]);
+
+        assert!(matches!(
+            provider.read(rh, 0, 1000),
+            Err(FsError::InvalidArgument(_))
+        ));
+        assert!(matches!(
+            provider.read(rh, (CHUNK * 4 - 1) as u64, 1),
+            Err(FsError::InvalidArgument(_))
+        ));
+
+        let oldest = CHUNK * 4;
+        let data = provider.read(rh, oldest as u64, 1000).unwrap();
+        assert_eq!(&data[..], &expected[oldest..oldest + 1000]);
+    }
+
+    #[test]
+    fn chunks_written_before_first_read_are_not_duplicated() {
+        let provider = StreamFsProvider::new(StreamFsConfig::default());
+        let rh = open_stream(&provider, "/s", OpenFlags::create_file());
+        let wh = open_stream(&provider, "/s", OpenFlags::write());
+
+        provider.write(wh, b"hello").unwrap();
+        provider.write(wh, b"world").unwrap();
+
+        let data = provider.read(rh, 0, 1024).unwrap();
+        assert_eq!(&data[..], b"helloworld");
+    }
+
+    #[test]
+    fn reader_sees_end_of_stream_once_after_close() {
+        let provider = StreamFsProvider::new(StreamFsConfig::default());
+        let rh = open_stream(&provider, "/s", OpenFlags::create_file());
+        let wh = open_stream(&provider, "/s", OpenFlags::write());
+
+        provider.write(wh, b"last words").unwrap();
+        assert!(provider.read(rh, 10, 1024).unwrap().is_empty());
+
+        provider.remove("/s").unwrap();
+        assert!(provider.write(wh, b"more").is_err());
+
+        let data = provider.read(rh, 0, 1024).unwrap();
+        assert_eq!(&data[..], b"last words");
+
+        let err = provider.read(rh, 10, 1024).unwrap_err();
+        assert!(err.is_end_of_stream());
+        assert_eq!(fserror_to_code(&err), fs9_sdk_ffi::FS9_ERR_END_OF_STREAM);
+
+        assert!(provider.read(rh, 10, 1024).unwrap().is_empty());
+        assert!(provider.read(rh, 10, 1024).unwrap().is_empty());
+    }
+
+    #[test]
+    fn ring_size_can_be_chosen_per_stream() {
+        let provider = StreamFsProvider::new(StreamFsConfig {
+            ring_size: 5,
+            ..Default::default()
+        });
+        let create = OpenFlags {
+            write: true,
+            create: true,
+            ..Default::default()
+        };
+        let deep = open_stream(&provider, "/telemetry?ring=50", create);
+        let shallow = open_stream(&provider, "/control", create);
+
+        for n in 0..30u8 {
+            provider.write(deep, &[n]).unwrap();
+            provider.write(shallow, &[n]).unwrap();
+        }
+
+        let deep_reader = open_stream(&provider, "/telemetry", OpenFlags::read());
+        let shallow_reader = open_stream(&provider, "/control", OpenFlags::read());
+        assert_eq!(provider.read(deep_reader, 0, 1024).unwrap().len(), 30);
+        assert_eq!(
+            &provider.read(shallow_reader, 0, 1024).unwrap()[..],
+            &[25, 26, 27, 28, 29]
+        );
+
+        let again = open_stream(&provider, "/control?ring=50", create);
+        provider.close(again).unwrap();
+        assert_eq!(provider.streams.read().unwrap()["/control"].ring_size, 5);
+    }
+
+    #[test]
+    fn invalid_ring_size_is_rejected() {
+        let provider = StreamFsProvider::new(StreamFsConfig::default());
+        let create = OpenFlags::create_file();
+
+        for path in ["/s?ring=0", "/s?ring=abc", "/s?ring=100001", "/s?depth=3"] {
+            assert!(
+                matches!(
+                    provider.open(path, create),
+                    Err(FsError::InvalidArgument(_))
+                ),
+                "{path}"
+            );
+        }
+        assert!(provider.streams.read().unwrap().is_empty());
+    }
+
+    #[test]
+    fn compressed_ring_serves_identical_bytes_in_less_memory() {
+        let log_line = |n: usize| format!("{n:06} level=info msg=\"request served\" status=200\n");
+        let run = |compress: bool| {
+            let provider = StreamFsProvider::new(StreamFsConfig {
+                compress,
+                ..Default::default()
+            });
+            let writer = open_stream(&provider, "/logs", OpenFlags::create_file());
+            let live = open_stream(&provider, "/logs", OpenFlags::read());
+            for batch in 0..20 {
+                let chunk: String = (batch * 50..(batch + 1) * 50).map(log_line).collect();
+                provider.write(writer, chunk.as_bytes()).unwrap();
+            }
+            // A tiny chunk does not shrink under gzip and is kept as is.
+            provider.write(writer, b"!").unwrap();
+
+            let late = open_stream(&provider, "/logs", OpenFlags::read());
+            let history = provider.read(late, 0, usize::MAX).unwrap();
+            let streamed = provider.read(live, 0, usize::MAX).unwrap();
+            assert_eq!(history, streamed);
+
+            let stream = provider.streams.read().unwrap()["/logs"].clone();
+            assert_eq!(stream.get_info().size, history.len() as u64);
+            (history, stream.stored_bytes())
+        };
+
+        let (plain, plain_bytes) = run(false);
+        let (unpacked, packed_bytes) = run(true);
+        let expected: String = (0..1000).map(log_line).collect::<String>() + "!";
+        assert_eq!(plain, expected.as_bytes());
+        assert_eq!(unpacked, plain);
+        assert_eq!(plain_bytes, expected.len());
+        assert!(
+            packed_bytes * 4 < plain_bytes,
+            "{packed_bytes} bytes stored compressed vs {plain_bytes} plain"
+        );
+    }
 }
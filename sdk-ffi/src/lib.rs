@@ -1,13 +1,52 @@
 #![allow(missing_docs)]
 #![allow(clippy::missing_safety_doc)]
 
+use bytes::Bytes;
 use libc::{c_char, c_void, size_t};
+use std::cell::RefCell;
 use std::ffi::CStr;
 use std::ptr;
 use std::slice;
 
-pub const FS9_SDK_VERSION: u32 = 2;
-
+pub const FS9_SDK_VERSION: u32 = 16;
+
+/// The oldest SDK version whose plugins can still be loaded. Vtable slots
+/// added since then are only read from plugins new enough to have them.
+pub const FS9_MIN_SDK_VERSION: u32 = 2;
+
+/// SDK versions that added each optional vtable slot. Slots are only ever
+/// appended, so a plugin's vtable holds every slot up to the first one
+/// newer than its `sdk_version`.
+pub const FSYNC_SINCE: u32 = 3;
+pub const LINK_SINCE: u32 = 5;
+pub const RENAME_SINCE: u32 = 7;
+pub const READDIR_AT_SINCE: u32 = 9;
+pub const FLUSH_SINCE: u32 = 10;
+pub const XATTR_SINCE: u32 = 11;
+pub const HEALTH_CHECK_SINCE: u32 = 14;
+pub const SNAPSHOT_SINCE: u32 = 15;
+
+/// SDK version that added `COpenFlags::excl`. Older plugins never see the
+/// byte, so the host checks for an existing path before opening on their
+/// behalf.
+pub const OPEN_EXCL_SINCE: u32 = 12;
+
+/// SDK version that added `data_only` to the `fsync` slot. Older plugins
+/// export a [`LegacyFsyncFn`] there, which the host calls without it.
+pub const FSYNC_DATA_ONLY_SINCE: u32 = 8;
+
+/// SDK version that added `CBytes::release` and `CBytes::owner`. Older
+/// plugins write only the leading fields, so the host zeroes the rest
+/// before every call and the buffer is freed as a `Vec`.
+pub const BYTES_RELEASE_SINCE: u32 = 13;
+
+/// SDK version that added `CFileInfo::etag`. The host zeroes the
+/// `CFileInfo` it passes out, and ignores the etag of readdir entries built
+/// by older plugins, whose `CFileInfo` ends before it.
+pub const ETAG_SINCE: u32 = 16;
+
+/// Longest etag `CFileInfo` can carry.
+pub const CFILEINFO_ETAG_MAX: usize = 64;
 pub const FS9_OK: i32 = 0;
 pub const FS9_ERR_NOT_FOUND: i32 = -1;
 pub const FS9_ERR_PERMISSION_DENIED: i32 = -2;
@@ -20,6 +59,8 @@ pub const FS9_ERR_INVALID_HANDLE: i32 = -8;
 pub const FS9_ERR_INTERNAL: i32 = -9;
 pub const FS9_ERR_NOT_IMPLEMENTED: i32 = -10;
 pub const FS9_ERR_BACKEND_UNAVAILABLE: i32 = -11;
+pub const FS9_ERR_NO_SPACE: i32 = -12;
+pub const FS9_ERR_END_OF_STREAM: i32 = -13;
 
 #[repr(C)]
 #[derive(Debug, Clone, Copy)]
@@ -34,6 +75,29 @@ pub struct CFileInfo {
     pub atime: i64,
     pub mtime: i64,
     pub ctime: i64,
+    /// The first `etag_len` bytes hold the file's etag (see `ETAG_SINCE`).
+    pub etag: [u8; CFILEINFO_ETAG_MAX],
+    pub etag_len: u8,
+}
+
+impl CFileInfo {
+    /// Store `etag`, or leave the etag empty if it is too long to fit.
+    pub fn set_etag(&mut self, etag: &str) {
+        let etag = etag.as_bytes();
+        match u8::try_from(etag.len()) {
+            Ok(len) if etag.len() <= CFILEINFO_ETAG_MAX => {
+                self.etag[..etag.len()].copy_from_slice(etag);
+                self.etag_len = len;
+            }
+            _ => self.etag_len = 0,
+        }
+    }
+
+    #[must_use]
+    pub fn etag(&self) -> String {
+        let len = usize::from(self.etag_len).min(CFILEINFO_ETAG_MAX);
+        String::from_utf8_lossy(&self.etag[..len]).into_owned()
+    }
 }
 
 impl Default for CFileInfo {
@@ -49,6 +113,8 @@ impl Default for CFileInfo {
             atime: 0,
             mtime: 0,
             ctime: 0,
+            etag: [0; CFILEINFO_ETAG_MAX],
+            etag_len: 0,
         }
     }
 }
@@ -57,12 +123,19 @@ pub const FILE_TYPE_REGULAR: u8 = 0;
 pub const FILE_TYPE_DIRECTORY: u8 = 1;
 pub const FILE_TYPE_SYMLINK: u8 = 2;
 
+/// A buffer handed from a plugin to the host.
+///
+/// Released with [`fs9_bytes_free`]. Either a leaked `Vec` (`cap` set, see
+/// [`vec_to_cbytes`]) or memory kept alive by `owner` until `release` is
+/// called on it (see [`bytes_to_cbytes`]).
 #[repr(C)]
 #[derive(Debug, Clone, Copy)]
 pub struct CBytes {
     pub data: *const u8,
     pub len: size_t,
     pub cap: size_t,
+    pub release: Option<unsafe extern "C" fn(owner: *mut c_void)>,
+    pub owner: *mut c_void,
 }
 
 impl Default for CBytes {
@@ -71,6 +144,8 @@ impl Default for CBytes {
             data: ptr::null(),
             len: 0,
             cap: 0,
+            release: None,
+            owner: ptr::null_mut(),
         }
     }
 }
@@ -99,6 +174,55 @@ impl CResult {
             error_msg_len: len,
         }
     }
+
+    /// A failure with `code` that carries `msg` to the host.
+    ///
+    /// The message is copied into a buffer of the plugin's, one per thread,
+    /// which stays valid until the next failure returned on the same
+    /// thread. The host copies it out before it makes another call.
+    #[must_use]
+    pub fn with_message(code: i32, msg: &str) -> Self {
+        ERROR_MESSAGE.with(|buf| {
+            let mut buf = buf.borrow_mut();
+            buf.clear();
+            buf.extend_from_slice(msg.as_bytes());
+            Self::err(code, buf.as_ptr().cast(), buf.len())
+        })
+    }
+
+    /// `err` as a failure with `code`, carrying [`error_detail`] of it so
+    /// the host can rebuild the same error.
+    #[must_use]
+    pub fn from_error(code: i32, err: &fs9_sdk::FsError) -> Self {
+        Self::with_message(code, &error_detail(err))
+    }
+}
+
+thread_local! {
+    static ERROR_MESSAGE: RefCell<Vec<u8>> = const { RefCell::new(Vec::new()) };
+}
+
+/// What an error is about without the kind its `Display` starts with, such
+/// as the path for `NotFound`; the host adds the kind back from the code.
+#[must_use]
+pub fn error_detail(err: &fs9_sdk::FsError) -> String {
+    use fs9_sdk::FsError;
+    match err {
+        FsError::NotFound(detail)
+        | FsError::PermissionDenied(detail)
+        | FsError::AlreadyExists(detail)
+        | FsError::InvalidArgument(detail)
+        | FsError::NotDirectory(detail)
+        | FsError::IsDirectory(detail)
+        | FsError::DirectoryNotEmpty(detail)
+        | FsError::NoSpace(detail)
+        | FsError::Internal(detail)
+        | FsError::NotImplemented(detail)
+        | FsError::BackendUnavailable(detail)
+        | FsError::EndOfStream(detail) => detail.clone(),
+        FsError::InvalidHandle(id) => id.to_string(),
+        other => other.to_string(),
+    }
 }
 
 #[repr(C)]
@@ -110,6 +234,8 @@ pub struct COpenFlags {
     pub truncate: u8,
     pub append: u8,
     pub directory: u8,
+    pub symlink: u8,
+    pub excl: u8,
 }
 
 #[repr(C)]
@@ -133,6 +259,9 @@ pub struct CStatChanges {
     pub has_symlink_target: u8,
     pub symlink_target: *const c_char,
     pub symlink_target_len: size_t,
+    /// Seconds from now until the file expires; 0 clears the expiry.
+    pub has_ttl: u8,
+    pub ttl_secs: u64,
 }
 
 impl Default for CStatChanges {
@@ -157,6 +286,8 @@ impl Default for CStatChanges {
             has_symlink_target: 0,
             symlink_target: ptr::null(),
             symlink_target_len: 0,
+            has_ttl: 0,
+            ttl_secs: 0,
         }
     }
 }
@@ -238,9 +369,123 @@ pub type ReaddirFn = unsafe extern "C" fn(
 pub type ReaddirCallback =
     unsafe extern "C" fn(info: *const CFileInfo, user_data: *mut c_void) -> i32;
 
+/// Paged variant of `ReaddirFn`.
+///
+/// Streams at most `max` entries of `path`, resuming after `cursor` (empty
+/// for the first page). The cursor for the next page is written to
+/// `out_next_cursor` with `vec_to_cbytes` and released by the caller with
+/// `fs9_bytes_free`; it is left empty once the listing is exhausted.
+pub type ReaddirAtFn = unsafe extern "C" fn(
+    provider: *mut c_void,
+    path: *const c_char,
+    path_len: size_t,
+    cursor: *const c_char,
+    cursor_len: size_t,
+    max: size_t,
+    callback: ReaddirCallback,
+    user_data: *mut c_void,
+    out_next_cursor: *mut CBytes,
+) -> CResult;
+
 pub type RemoveFn =
     unsafe extern "C" fn(provider: *mut c_void, path: *const c_char, path_len: size_t) -> CResult;
 
+/// Flush any data buffered for `handle` to durable storage. A non-zero
+/// `data_only` allows skipping metadata, like `fdatasync(2)`.
+pub type FsyncFn =
+    unsafe extern "C" fn(provider: *mut c_void, handle: u64, data_only: u8) -> CResult;
+
+/// `fsync` as plugins built before [`FSYNC_DATA_ONLY_SINCE`] export it,
+/// always flushing data and metadata.
+pub type LegacyFsyncFn = unsafe extern "C" fn(provider: *mut c_void, handle: u64) -> CResult;
+
+/// Flush everything the provider buffers, for all handles, to durable
+/// storage. Called by the host before it shuts down.
+pub type FlushFn = unsafe extern "C" fn(provider: *mut c_void) -> CResult;
+
+/// Check that the provider's backend is reachable and configured well
+/// enough to serve requests. Called by the host when it mounts the provider.
+/// Providers that keep everything in the plugin's own process have no
+/// backend to reach and return [`CResult::ok`].
+pub type HealthCheckFn = unsafe extern "C" fn(provider: *mut c_void) -> CResult;
+
+/// Record the tree at `path` as a snapshot and store its id in `out_id`.
+pub type SnapshotFn = unsafe extern "C" fn(
+    provider: *mut c_void,
+    path: *const c_char,
+    path_len: size_t,
+    out_id: *mut u64,
+) -> CResult;
+
+/// Create a provider serving snapshot `snapshot_id` and store it in `out_view`.
+///
+/// The view is driven through the same vtable, released with `destroy`, and
+/// must stay usable after the provider it came from is destroyed.
+pub type OpenSnapshotFn = unsafe extern "C" fn(
+    provider: *mut c_void,
+    snapshot_id: u64,
+    out_view: *mut *mut c_void,
+) -> CResult;
+
+/// Read the extended attribute `name` of `path` into `out_value`, allocated
+/// with `vec_to_cbytes` and released by the caller with `fs9_bytes_free`.
+/// A missing attribute is `FS9_ERR_NOT_FOUND`.
+pub type GetxattrFn = unsafe extern "C" fn(
+    provider: *mut c_void,
+    path: *const c_char,
+    path_len: size_t,
+    name: *const c_char,
+    name_len: size_t,
+    out_value: *mut CBytes,
+) -> CResult;
+
+/// Create or replace the extended attribute `name` of `path`.
+pub type SetxattrFn = unsafe extern "C" fn(
+    provider: *mut c_void,
+    path: *const c_char,
+    path_len: size_t,
+    name: *const c_char,
+    name_len: size_t,
+    value: *const u8,
+    value_len: size_t,
+) -> CResult;
+
+/// Write the attribute names of `path` to `out_names`, each followed by a
+/// NUL byte as with `listxattr(2)`. Ownership is as for `GetxattrFn`.
+pub type ListxattrFn = unsafe extern "C" fn(
+    provider: *mut c_void,
+    path: *const c_char,
+    path_len: size_t,
+    out_names: *mut CBytes,
+) -> CResult;
+
+/// Remove the extended attribute `name` of `path`.
+pub type RemovexattrFn = unsafe extern "C" fn(
+    provider: *mut c_void,
+    path: *const c_char,
+    path_len: size_t,
+    name: *const c_char,
+    name_len: size_t,
+) -> CResult;
+
+/// Create `new_path` as a hard link to `existing_path`.
+pub type LinkFn = unsafe extern "C" fn(
+    provider: *mut c_void,
+    existing_path: *const c_char,
+    existing_path_len: size_t,
+    new_path: *const c_char,
+    new_path_len: size_t,
+) -> CResult;
+
+/// Move `old_path` to `new_path` within the provider.
+pub type RenameFn = unsafe extern "C" fn(
+    provider: *mut c_void,
+    old_path: *const c_char,
+    old_path_len: size_t,
+    new_path: *const c_char,
+    new_path_len: size_t,
+) -> CResult;
+
 #[derive(Clone, Copy)]
 #[repr(C)]
 pub struct PluginVTable {
@@ -261,11 +506,53 @@ pub struct PluginVTable {
     pub close: CloseFn,
     pub readdir: ReaddirFn,
     pub remove: RemoveFn,
+    pub fsync: FsyncFn,
+    pub link: LinkFn,
+    pub rename: RenameFn,
+    pub readdir_at: ReaddirAtFn,
+    pub flush: FlushFn,
+    pub getxattr: GetxattrFn,
+    pub setxattr: SetxattrFn,
+    pub listxattr: ListxattrFn,
+    pub removexattr: RemovexattrFn,
+    pub health_check: HealthCheckFn,
+    pub snapshot: SnapshotFn,
+    pub open_snapshot: OpenSnapshotFn,
 }
 
 unsafe impl Sync for PluginVTable {}
 unsafe impl Send for PluginVTable {}
 
+/// Size in bytes of the leading part of `PluginVTable` that a plugin built
+/// against `sdk_version` provides.
+#[must_use]
+pub fn vtable_len(sdk_version: u32) -> usize {
+    let vtable = std::mem::MaybeUninit::<PluginVTable>::uninit();
+    let base = vtable.as_ptr();
+    // SAFETY: only field addresses are computed; nothing is read.
+    let slots = unsafe {
+        [
+            (FSYNC_SINCE, ptr::addr_of!((*base).fsync) as usize),
+            (LINK_SINCE, ptr::addr_of!((*base).link) as usize),
+            (RENAME_SINCE, ptr::addr_of!((*base).rename) as usize),
+            (READDIR_AT_SINCE, ptr::addr_of!((*base).readdir_at) as usize),
+            (FLUSH_SINCE, ptr::addr_of!((*base).flush) as usize),
+            (XATTR_SINCE, ptr::addr_of!((*base).getxattr) as usize),
+            (
+                HEALTH_CHECK_SINCE,
+                ptr::addr_of!((*base).health_check) as usize,
+            ),
+            (SNAPSHOT_SINCE, ptr::addr_of!((*base).snapshot) as usize),
+        ]
+    };
+    slots
+        .iter()
+        .find(|(since, _)| sdk_version < *since)
+        .map_or(std::mem::size_of::<PluginVTable>(), |(_, addr)| {
+            addr - base as usize
+        })
+}
+
 pub unsafe fn str_from_c(ptr: *const c_char, len: size_t) -> Option<&'static str> {
     if ptr.is_null() {
         return None;
@@ -292,12 +579,12 @@ pub unsafe extern "C" fn fs9_bytes_free(bytes: *mut CBytes) {
         return;
     }
     let bytes = &mut *bytes;
-    if !bytes.data.is_null() && bytes.cap > 0 {
+    if let Some(release) = bytes.release {
+        release(bytes.owner);
+    } else if !bytes.data.is_null() && bytes.cap > 0 {
         let _ = Vec::from_raw_parts(bytes.data as *mut u8, bytes.len, bytes.cap);
     }
-    bytes.data = ptr::null();
-    bytes.len = 0;
-    bytes.cap = 0;
+    *bytes = CBytes::default();
 }
 
 pub fn vec_to_cbytes(v: Vec<u8>) -> CBytes {
@@ -308,6 +595,26 @@ pub fn vec_to_cbytes(v: Vec<u8>) -> CBytes {
         data: ptr,
         len,
         cap,
+        ..CBytes::default()
+    }
+}
+
+/// Hand `b` over without copying it.
+///
+/// The buffer points into `b`, which is kept alive until [`fs9_bytes_free`].
+/// The release function is compiled into the plugin, so the host never frees
+/// plugin memory itself.
+pub fn bytes_to_cbytes(b: Bytes) -> CBytes {
+    unsafe extern "C" fn release(owner: *mut c_void) {
+        drop(Box::from_raw(owner.cast::<Bytes>()));
+    }
+
+    CBytes {
+        data: b.as_ptr(),
+        len: b.len(),
+        cap: 0,
+        release: Some(release),
+        owner: Box::into_raw(Box::new(b)).cast(),
     }
 }
 
@@ -324,6 +631,8 @@ pub fn fs_error_to_code(err: &fs9_sdk::FsError) -> i32 {
         FsError::InvalidHandle(_) => FS9_ERR_INVALID_HANDLE,
         FsError::NotImplemented(_) => FS9_ERR_NOT_IMPLEMENTED,
         FsError::BackendUnavailable(_) => FS9_ERR_BACKEND_UNAVAILABLE,
+        FsError::NoSpace(_) => FS9_ERR_NO_SPACE,
+        FsError::EndOfStream(_) => FS9_ERR_END_OF_STREAM,
         _ => FS9_ERR_INTERNAL,
     }
 }
@@ -334,7 +643,17 @@ mod tests {
 
     #[test]
     fn version_constant() {
-        assert_eq!(fs9_sdk_version(), 1);
+        assert_eq!(fs9_sdk_version(), 16);
+    }
+
+    #[test]
+    fn cfileinfo_etag_round_trips() {
+        let mut info = CFileInfo::default();
+        assert_eq!(info.etag(), "");
+        info.set_etag("W/10-1234");
+        assert_eq!(info.etag(), "W/10-1234");
+        info.set_etag(&"x".repeat(CFILEINFO_ETAG_MAX + 1));
+        assert_eq!(info.etag(), "");
     }
 
     #[test]
@@ -358,6 +677,39 @@ mod tests {
         }
     }
 
+    #[test]
+    fn bytes_to_cbytes_shares_the_buffer() {
+        let original = Bytes::from(vec![7u8; 1 << 20]);
+        let kept = original.clone();
+        let mut cb = bytes_to_cbytes(original);
+        assert_eq!(cb.data, kept.as_ptr());
+        assert_eq!(cb.len, 1 << 20);
+
+        unsafe {
+            assert_eq!(slice::from_raw_parts(cb.data, cb.len), &kept[..]);
+            fs9_bytes_free(&mut cb);
+            assert!(cb.data.is_null());
+            assert!(cb.release.is_none());
+            // Freeing twice is a no-op.
+            fs9_bytes_free(&mut cb);
+        }
+        // Only the handed-over reference was dropped.
+        assert!(kept.iter().all(|&b| b == 7));
+    }
+
+    #[test]
+    fn bytes_to_cbytes_frees_the_last_reference() {
+        let data = Bytes::from_static(b"static").slice(1..4);
+        let mut cb = bytes_to_cbytes(data);
+        unsafe {
+            assert_eq!(slice::from_raw_parts(cb.data, cb.len), b"tat");
+            fs9_bytes_free(&mut cb);
+        }
+
+        let mut cb = bytes_to_cbytes(Bytes::from(b"owned".to_vec()));
+        unsafe { fs9_bytes_free(&mut cb) };
+    }
+
     #[test]
     fn error_code_mapping() {
         use fs9_sdk::FsError;
@@ -374,5 +726,45 @@ mod tests {
             fs_error_to_code(&FsError::already_exists("test")),
             FS9_ERR_ALREADY_EXISTS
         );
+        assert_eq!(
+            fs_error_to_code(&FsError::end_of_stream("test")),
+            FS9_ERR_END_OF_STREAM
+        );
+    }
+
+    #[test]
+    fn error_message_crosses_the_boundary() {
+        use fs9_sdk::FsError;
+
+        let err = FsError::not_found("/some/path");
+        let result = CResult::from_error(fs_error_to_code(&err), &err);
+        assert_eq!(result.code, FS9_ERR_NOT_FOUND);
+        let msg =
+            unsafe { slice::from_raw_parts(result.error_msg.cast::<u8>(), result.error_msg_len) };
+        assert_eq!(msg, b"/some/path");
+
+        assert_eq!(error_detail(&FsError::invalid_handle(7)), "7");
+        assert_eq!(
+            error_detail(&FsError::Transient("flaky".into())),
+            "transient error (retryable): flaky"
+        );
+    }
+
+    #[test]
+    fn vtable_len_grows_with_sdk_version() {
+        let word = std::mem::size_of::<usize>();
+        // `sdk_version` padded to a word, the name and version strings, and
+        // twelve function pointers from `create` to `remove`.
+        let remove_end = 17 * word;
+        assert_eq!(vtable_len(FS9_MIN_SDK_VERSION), remove_end);
+        assert_eq!(vtable_len(FSYNC_SINCE), remove_end + word);
+        assert_eq!(vtable_len(LINK_SINCE - 1), vtable_len(FSYNC_SINCE));
+        assert!(vtable_len(FLUSH_SINCE) < vtable_len(XATTR_SINCE));
+        assert!(vtable_len(XATTR_SINCE) < vtable_len(HEALTH_CHECK_SINCE));
+        assert!(vtable_len(HEALTH_CHECK_SINCE) < vtable_len(SNAPSHOT_SINCE));
+        assert_eq!(
+            vtable_len(FS9_SDK_VERSION),
+            std::mem::size_of::<PluginVTable>()
+        );
     }
 }
@@ -1,5 +1,7 @@
 use bitflags::bitflags;
 
+use crate::FsError;
+
 bitflags! {
     #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
     pub struct Capabilities: u64 {
@@ -22,12 +24,14 @@ bitflags! {
         const RANDOM_WRITE  = 1 << 32;
         const STREAMING     = 1 << 33;
         const BLOCKING_READ = 1 << 34;
+        const FSYNC         = 1 << 35;
 
         const VERSIONING    = 1 << 40;
         const ETAG          = 1 << 41;
         const ATOMIC_RENAME = 1 << 42;
         const DIRECTORY     = 1 << 43;
         const XATTR         = 1 << 44;
+        const SNAPSHOT      = 1 << 45;
 
         const SYNTHETIC     = 1 << 50;
         const STATEFUL_READ = 1 << 51;
@@ -51,7 +55,6 @@ impl Capabilities {
         .union(Self::CHOWN)
         .union(Self::UTIME)
         .union(Self::SYMLINK)
-        .union(Self::XATTR)
         .union(Self::SYNC)
         .union(Self::RANDOM_WRITE);
 
@@ -65,6 +68,33 @@ impl Capabilities {
         .union(Self::STATEFUL_WRITE)
         .union(Self::BLOCKING_READ);
 
+    /// Parse flag names as written in config files, case-insensitively:
+    /// `["rename", "TRUNCATE"]` is `RENAME | TRUNCATE`.
+    ///
+    /// # Errors
+    ///
+    /// `InvalidArgument` naming the first name that is not a flag.
+    pub fn from_names<I, S>(names: I) -> Result<Self, FsError>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        names.into_iter().try_fold(Self::empty(), |caps, name| {
+            let name = name.as_ref();
+            Self::from_name(&name.to_ascii_uppercase())
+                .map(|flag| caps | flag)
+                .ok_or_else(|| FsError::invalid_argument(format!("unknown capability: {name}")))
+        })
+    }
+
+    /// The flag names set in `self`, lowercased, for messages.
+    #[must_use]
+    pub fn names(&self) -> Vec<String> {
+        self.iter_names()
+            .map(|(name, _)| name.to_ascii_lowercase())
+            .collect()
+    }
+
     #[must_use]
     pub fn supports_read(&self) -> bool {
         self.contains(Self::READ)
@@ -115,6 +145,21 @@ impl Capabilities {
         self.contains(Self::DIRECTORY)
     }
 
+    #[must_use]
+    pub fn supports_fsync(&self) -> bool {
+        self.contains(Self::FSYNC)
+    }
+
+    #[must_use]
+    pub fn supports_xattr(&self) -> bool {
+        self.contains(Self::XATTR)
+    }
+
+    #[must_use]
+    pub fn supports_snapshot(&self) -> bool {
+        self.contains(Self::SNAPSHOT)
+    }
+
     #[must_use]
     pub fn supports_random_write(&self) -> bool {
         self.contains(Self::RANDOM_WRITE)
@@ -172,7 +217,8 @@ mod tests {
         assert!(caps.supports_symlink());
         assert!(caps.supports_random_write());
         assert!(caps.contains(Capabilities::SYNC));
-        assert!(caps.contains(Capabilities::XATTR));
+        // XATTR promises working xattr methods, so providers opt in to it.
+        assert!(!caps.supports_xattr());
     }
 
     #[test]
@@ -198,6 +244,21 @@ mod tests {
         assert!(!caps.supports_directories());
     }
 
+    #[test]
+    fn names_round_trip() {
+        let caps = Capabilities::from_names(["rename", "TRUNCATE"]).unwrap();
+        assert_eq!(caps, Capabilities::RENAME | Capabilities::TRUNCATE);
+        assert_eq!(caps.names(), ["rename", "truncate"]);
+        assert_eq!(
+            Capabilities::from_names(Vec::<String>::new()).unwrap(),
+            Capabilities::empty()
+        );
+        assert!(matches!(
+            Capabilities::from_names(["rename", "teleport"]),
+            Err(FsError::InvalidArgument(msg)) if msg.contains("teleport")
+        ));
+    }
+
     #[test]
     fn capability_combination() {
         let base = Capabilities::BASIC_RW;
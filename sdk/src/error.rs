@@ -24,6 +24,9 @@ pub enum FsError {
     #[error("directory not empty: {0}")]
     DirectoryNotEmpty(String),
 
+    #[error("no space left: {0}")]
+    NoSpace(String),
+
     #[error("invalid handle: {0}")]
     InvalidHandle(u64),
 
@@ -36,6 +39,11 @@ pub enum FsError {
     #[error("storage backend unavailable: {0}")]
     BackendUnavailable(String),
 
+    /// A stream reader has consumed everything its closed stream will ever
+    /// hold. Not a failure of the backend, so never retried.
+    #[error("end of stream: {0}")]
+    EndOfStream(String),
+
     #[error("transient error (retryable): {0}")]
     Transient(String),
 
@@ -45,8 +53,11 @@ pub enum FsError {
     #[error("timeout after {duration:?}")]
     Timeout { duration: Duration },
 
-    #[error("circuit breaker open for {service}")]
-    CircuitBreakerOpen { service: String },
+    #[error("circuit breaker open for {service}, retry in {retry_after:?}")]
+    CircuitBreakerOpen {
+        service: String,
+        retry_after: Duration,
+    },
 
     #[error("too many proxy hops: {depth} (max: {max})")]
     TooManyHops { depth: usize, max: usize },
@@ -67,6 +78,16 @@ impl FsError {
         )
     }
 
+    /// How long the caller should wait before trying again, when the error
+    /// says so.
+    #[must_use]
+    pub fn retry_after(&self) -> Option<Duration> {
+        match self {
+            Self::CircuitBreakerOpen { retry_after, .. } => Some(*retry_after),
+            _ => None,
+        }
+    }
+
     #[must_use]
     pub fn is_not_found(&self) -> bool {
         matches!(self, Self::NotFound(_))
@@ -82,6 +103,11 @@ impl FsError {
         matches!(self, Self::Conflict { .. } | Self::VersionConflict { .. })
     }
 
+    #[must_use]
+    pub fn is_end_of_stream(&self) -> bool {
+        matches!(self, Self::EndOfStream(_))
+    }
+
     #[must_use]
     pub fn http_status(&self) -> u16 {
         match self {
@@ -91,6 +117,8 @@ impl FsError {
             Self::InvalidArgument(_) | Self::InvalidHandle(_) => 400,
             Self::NotDirectory(_) | Self::IsDirectory(_) | Self::DirectoryNotEmpty(_) => 400,
             Self::NotImplemented(_) => 501,
+            Self::NoSpace(_) => 507,
+            Self::EndOfStream(_) => 410,
             Self::Transient(_) | Self::BackendUnavailable(_) | Self::CircuitBreakerOpen { .. } => {
                 503
             }
@@ -100,6 +128,34 @@ impl FsError {
         }
     }
 
+    /// A stable `snake_case` name for the variant, without the message. Meant
+    /// for metric labels and logs where the full text would be too varied.
+    #[must_use]
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::NotFound(_) => "not_found",
+            Self::PermissionDenied(_) => "permission_denied",
+            Self::AlreadyExists(_) => "already_exists",
+            Self::InvalidArgument(_) => "invalid_argument",
+            Self::NotDirectory(_) => "not_directory",
+            Self::IsDirectory(_) => "is_directory",
+            Self::DirectoryNotEmpty(_) => "directory_not_empty",
+            Self::NoSpace(_) => "no_space",
+            Self::InvalidHandle(_) => "invalid_handle",
+            Self::Internal(_) => "internal",
+            Self::NotImplemented(_) => "not_implemented",
+            Self::BackendUnavailable(_) => "backend_unavailable",
+            Self::EndOfStream(_) => "end_of_stream",
+            Self::Transient(_) => "transient",
+            Self::Remote { .. } => "remote",
+            Self::Timeout { .. } => "timeout",
+            Self::CircuitBreakerOpen { .. } => "circuit_breaker_open",
+            Self::TooManyHops { .. } => "too_many_hops",
+            Self::Conflict { .. } => "conflict",
+            Self::VersionConflict { .. } => "version_conflict",
+        }
+    }
+
     #[must_use]
     pub fn not_found(path: impl Into<String>) -> Self {
         Self::NotFound(path.into())
@@ -135,6 +191,11 @@ impl FsError {
         Self::DirectoryNotEmpty(path.into())
     }
 
+    #[must_use]
+    pub fn no_space(reason: impl Into<String>) -> Self {
+        Self::NoSpace(reason.into())
+    }
+
     #[must_use]
     pub fn invalid_handle(id: u64) -> Self {
         Self::InvalidHandle(id)
@@ -155,6 +216,11 @@ impl FsError {
         Self::BackendUnavailable(reason.into())
     }
 
+    #[must_use]
+    pub fn end_of_stream(path: impl Into<String>) -> Self {
+        Self::EndOfStream(path.into())
+    }
+
     #[must_use]
     pub fn transient(reason: impl Into<String>) -> Self {
         Self::Transient(reason.into())
@@ -164,6 +230,14 @@ impl FsError {
     pub fn timeout(duration: Duration) -> Self {
         Self::Timeout { duration }
     }
+
+    #[must_use]
+    pub fn circuit_breaker_open(service: impl Into<String>, retry_after: Duration) -> Self {
+        Self::CircuitBreakerOpen {
+            service: service.into(),
+            retry_after,
+        }
+    }
 }
 
 pub type FsResult<T> = Result<T, FsError>;
@@ -181,6 +255,7 @@ mod tests {
         assert!(!FsError::not_found("/path").is_retryable());
         assert!(!FsError::permission_denied("access").is_retryable());
         assert!(!FsError::invalid_argument("bad").is_retryable());
+        assert!(!FsError::end_of_stream("/s").is_retryable());
     }
 
     #[test]
@@ -190,8 +265,14 @@ mod tests {
         assert_eq!(FsError::already_exists("/path").http_status(), 409);
         assert_eq!(FsError::invalid_argument("bad").http_status(), 400);
         assert_eq!(FsError::not_implemented("feature").http_status(), 501);
+        assert_eq!(FsError::no_space("full").http_status(), 507);
+        assert_eq!(FsError::end_of_stream("/s").http_status(), 410);
         assert_eq!(FsError::transient("error").http_status(), 503);
         assert_eq!(FsError::timeout(Duration::from_secs(30)).http_status(), 504);
+        assert_eq!(
+            FsError::circuit_breaker_open("/data", Duration::from_secs(5)).http_status(),
+            503
+        );
         assert_eq!(
             FsError::TooManyHops { depth: 10, max: 8 }.http_status(),
             508
@@ -199,6 +280,17 @@ mod tests {
         assert_eq!(FsError::internal("error").http_status(), 500);
     }
 
+    #[test]
+    fn error_kinds() {
+        assert_eq!(FsError::not_found("/path").kind(), "not_found");
+        assert_eq!(
+            FsError::directory_not_empty("/d").kind(),
+            "directory_not_empty"
+        );
+        assert_eq!(FsError::timeout(Duration::from_secs(1)).kind(), "timeout");
+        assert_eq!(FsError::no_space("full").kind(), "no_space");
+    }
+
     #[test]
     fn error_predicates() {
         assert!(FsError::not_found("/path").is_not_found());
@@ -218,6 +310,9 @@ mod tests {
         }
         .is_conflict());
         assert!(!FsError::not_found("/path").is_conflict());
+
+        assert!(FsError::end_of_stream("/s").is_end_of_stream());
+        assert!(!FsError::backend_unavailable("s3").is_end_of_stream());
     }
 
     #[test]
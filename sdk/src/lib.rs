@@ -8,4 +8,7 @@ mod types;
 pub use capabilities::Capabilities;
 pub use error::{FsError, FsResult};
 pub use provider::FsProvider;
-pub use types::{FileInfo, FileType, FsStats, Handle, OpenFlags, StatChanges};
+pub use types::{
+    content_etag, is_weak_etag, metadata_etag, normalize_path, rename_target, DirPage, FileInfo,
+    FileType, FsStats, Handle, OpenFlags, StatChanges,
+};
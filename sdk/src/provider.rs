@@ -1,10 +1,18 @@
+use std::sync::Arc;
+
 use async_trait::async_trait;
 use bytes::Bytes;
 
 use crate::capabilities::Capabilities;
-use crate::error::FsResult;
-use crate::types::{FileInfo, FsStats, Handle, OpenFlags, StatChanges};
+use crate::error::{FsError, FsResult};
+use crate::types::{DirPage, FileInfo, FsStats, Handle, OpenFlags, StatChanges};
 
+/// A storage backend mounted into the VFS.
+///
+/// In-process providers such as `MemoryFs` implement this directly and are
+/// awaited on the caller's runtime. Dynamically loaded plugins expose the
+/// synchronous C vtable instead; core's `PluginProvider` adapts them to this
+/// trait by running each call on the blocking pool.
 #[async_trait]
 pub trait FsProvider: Send + Sync {
     async fn stat(&self, path: &str) -> FsResult<FileInfo>;
@@ -25,6 +33,105 @@ pub trait FsProvider: Send + Sync {
 
     async fn remove(&self, path: &str) -> FsResult<()>;
 
+    /// List at most `max` entries of `path`, resuming after `cursor` (a
+    /// `DirPage::next_cursor` from an earlier call, `None` for the first page).
+    /// Providers backed by ordered stores should override the default, which
+    /// lists the whole directory and pages through it by offset.
+    async fn readdir_at(&self, path: &str, cursor: Option<&str>, max: usize) -> FsResult<DirPage> {
+        let entries = self.readdir(path).await?;
+        DirPage::from_listing(entries, cursor, max)
+    }
+
+    /// Create `new_path` as another name for the file at `existing_path`.
+    /// Providers advertising `Capabilities::HARDLINK` must override this.
+    async fn link(&self, existing_path: &str, new_path: &str) -> FsResult<()> {
+        let _ = (existing_path, new_path);
+        Err(FsError::not_implemented("link"))
+    }
+
+    /// Flush data buffered for `handle` to durable storage. With `data_only`
+    /// set, metadata that is not needed to read the data back may be skipped.
+    /// Providers advertising `Capabilities::FSYNC` must override this.
+    async fn fsync(&self, _handle: &Handle, _data_only: bool) -> FsResult<()> {
+        Err(FsError::not_implemented("fsync"))
+    }
+
+    /// Flush everything the provider buffers, across all handles, to durable
+    /// storage. Called on every mounted provider during server shutdown. The
+    /// default does nothing, which suits providers that write through.
+    async fn flush(&self) -> FsResult<()> {
+        Ok(())
+    }
+
+    /// Check that the provider's backend can serve requests. Called once
+    /// when the server sets up a mount, so a misconfigured or unreachable
+    /// backend is reported then rather than on the first request. The
+    /// default stats the root and reads its `statfs`, which may be
+    /// unimplemented.
+    async fn health_check(&self) -> FsResult<()> {
+        self.stat("/").await?;
+        match self.statfs("/").await {
+            Ok(_) | Err(FsError::NotImplemented(_)) => Ok(()),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Value of the extended attribute `name` on `path`; `NotFound` if the
+    /// file has no such attribute. Providers advertising
+    /// `Capabilities::XATTR` must override all four xattr methods.
+    async fn getxattr(&self, path: &str, name: &str) -> FsResult<Bytes> {
+        let _ = (path, name);
+        Err(FsError::not_implemented("getxattr"))
+    }
+
+    /// Create or replace the extended attribute `name` on `path`.
+    async fn setxattr(&self, path: &str, name: &str, value: Bytes) -> FsResult<()> {
+        let _ = (path, name, value);
+        Err(FsError::not_implemented("setxattr"))
+    }
+
+    /// Names of the extended attributes set on `path`, in no set order.
+    async fn listxattr(&self, path: &str) -> FsResult<Vec<String>> {
+        let _ = path;
+        Err(FsError::not_implemented("listxattr"))
+    }
+
+    /// Remove the extended attribute `name` from `path`; `NotFound` if the
+    /// file has no such attribute.
+    async fn removexattr(&self, path: &str, name: &str) -> FsResult<()> {
+        let _ = (path, name);
+        Err(FsError::not_implemented("removexattr"))
+    }
+
+    /// Record the tree at `path` as it is now and return the snapshot's id.
+    /// Providers advertising `Capabilities::SNAPSHOT` must override this
+    /// and `snapshot_view`.
+    async fn snapshot(&self, _path: &str) -> FsResult<u64> {
+        Err(FsError::not_implemented("snapshot"))
+    }
+
+    /// A provider serving snapshot `snapshot_id` as it was taken, rooted at
+    /// the snapshotted path. Later writes through `self` don't show in it;
+    /// it is meant to be mounted read-only.
+    async fn snapshot_view(&self, _snapshot_id: u64) -> FsResult<Arc<dyn FsProvider>> {
+        Err(FsError::not_implemented("snapshot"))
+    }
+
+    /// Move `old_path` to `new_path`. Providers advertising
+    /// `Capabilities::RENAME` should override this; the default falls back
+    /// to the older `wstat` rename through `StatChanges::name`.
+    async fn rename(&self, old_path: &str, new_path: &str) -> FsResult<()> {
+        self.wstat(old_path, StatChanges::rename(new_path)).await
+    }
+
+    /// Cut the file at `path` down, or extend it with zeros, to `size`
+    /// bytes without opening a handle. Providers advertising
+    /// `Capabilities::TRUNCATE` may override this; the default is a `wstat`
+    /// that changes only the size.
+    async fn truncate(&self, path: &str, size: u64) -> FsResult<()> {
+        self.wstat(path, StatChanges::truncate(size)).await
+    }
+
     fn capabilities(&self) -> Capabilities;
 }
 
@@ -66,13 +173,65 @@ impl<P: FsProvider + ?Sized> FsProvider for Box<P> {
         (**self).remove(path).await
     }
 
+    async fn readdir_at(&self, path: &str, cursor: Option<&str>, max: usize) -> FsResult<DirPage> {
+        (**self).readdir_at(path, cursor, max).await
+    }
+
+    async fn link(&self, existing_path: &str, new_path: &str) -> FsResult<()> {
+        (**self).link(existing_path, new_path).await
+    }
+
+    async fn rename(&self, old_path: &str, new_path: &str) -> FsResult<()> {
+        (**self).rename(old_path, new_path).await
+    }
+
+    async fn truncate(&self, path: &str, size: u64) -> FsResult<()> {
+        (**self).truncate(path, size).await
+    }
+
+    async fn fsync(&self, handle: &Handle, data_only: bool) -> FsResult<()> {
+        (**self).fsync(handle, data_only).await
+    }
+
+    async fn flush(&self) -> FsResult<()> {
+        (**self).flush().await
+    }
+
+    async fn health_check(&self) -> FsResult<()> {
+        (**self).health_check().await
+    }
+
+    async fn snapshot(&self, path: &str) -> FsResult<u64> {
+        (**self).snapshot(path).await
+    }
+
+    async fn snapshot_view(&self, snapshot_id: u64) -> FsResult<Arc<dyn FsProvider>> {
+        (**self).snapshot_view(snapshot_id).await
+    }
+
+    async fn getxattr(&self, path: &str, name: &str) -> FsResult<Bytes> {
+        (**self).getxattr(path, name).await
+    }
+
+    async fn setxattr(&self, path: &str, name: &str, value: Bytes) -> FsResult<()> {
+        (**self).setxattr(path, name, value).await
+    }
+
+    async fn listxattr(&self, path: &str) -> FsResult<Vec<String>> {
+        (**self).listxattr(path).await
+    }
+
+    async fn removexattr(&self, path: &str, name: &str) -> FsResult<()> {
+        (**self).removexattr(path, name).await
+    }
+
     fn capabilities(&self) -> Capabilities {
         (**self).capabilities()
     }
 }
 
 #[async_trait]
-impl<P: FsProvider + ?Sized> FsProvider for std::sync::Arc<P> {
+impl<P: FsProvider + ?Sized> FsProvider for Arc<P> {
     async fn stat(&self, path: &str) -> FsResult<FileInfo> {
         (**self).stat(path).await
     }
@@ -109,6 +268,58 @@ impl<P: FsProvider + ?Sized> FsProvider for std::sync::Arc<P> {
         (**self).remove(path).await
     }
 
+    async fn readdir_at(&self, path: &str, cursor: Option<&str>, max: usize) -> FsResult<DirPage> {
+        (**self).readdir_at(path, cursor, max).await
+    }
+
+    async fn link(&self, existing_path: &str, new_path: &str) -> FsResult<()> {
+        (**self).link(existing_path, new_path).await
+    }
+
+    async fn rename(&self, old_path: &str, new_path: &str) -> FsResult<()> {
+        (**self).rename(old_path, new_path).await
+    }
+
+    async fn truncate(&self, path: &str, size: u64) -> FsResult<()> {
+        (**self).truncate(path, size).await
+    }
+
+    async fn fsync(&self, handle: &Handle, data_only: bool) -> FsResult<()> {
+        (**self).fsync(handle, data_only).await
+    }
+
+    async fn flush(&self) -> FsResult<()> {
+        (**self).flush().await
+    }
+
+    async fn health_check(&self) -> FsResult<()> {
+        (**self).health_check().await
+    }
+
+    async fn snapshot(&self, path: &str) -> FsResult<u64> {
+        (**self).snapshot(path).await
+    }
+
+    async fn snapshot_view(&self, snapshot_id: u64) -> FsResult<Arc<dyn FsProvider>> {
+        (**self).snapshot_view(snapshot_id).await
+    }
+
+    async fn getxattr(&self, path: &str, name: &str) -> FsResult<Bytes> {
+        (**self).getxattr(path, name).await
+    }
+
+    async fn setxattr(&self, path: &str, name: &str, value: Bytes) -> FsResult<()> {
+        (**self).setxattr(path, name, value).await
+    }
+
+    async fn listxattr(&self, path: &str) -> FsResult<Vec<String>> {
+        (**self).listxattr(path).await
+    }
+
+    async fn removexattr(&self, path: &str, name: &str) -> FsResult<()> {
+        (**self).removexattr(path, name).await
+    }
+
     fn capabilities(&self) -> Capabilities {
         (**self).capabilities()
     }
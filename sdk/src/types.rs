@@ -1,4 +1,6 @@
-use std::time::SystemTime;
+use std::time::{Duration, SystemTime};
+
+use crate::error::{FsError, FsResult};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
@@ -20,6 +22,15 @@ pub struct FileInfo {
     pub atime: SystemTime,
     pub mtime: SystemTime,
     pub ctime: SystemTime,
+    /// Changes whenever a regular file's contents do; empty when the
+    /// provider tracks none.
+    ///
+    /// A strong etag, from [`content_etag`], is a hash of the contents, so
+    /// two files with the same contents share it and it is safe to use for
+    /// `If-Match`. Providers for which hashing on every `stat` costs too
+    /// much report a weak etag from [`metadata_etag`] instead, which only
+    /// changes along with the size or mtime and starts with `W/`, as weak
+    /// HTTP entity tags do.
     pub etag: String,
     pub symlink_target: Option<String>,
 }
@@ -41,6 +52,76 @@ impl FileInfo {
     }
 }
 
+/// The strong etag of a file holding `data`: its length and 64-bit FNV-1a
+/// hash, in hex.
+#[must_use]
+pub fn content_etag(data: &[u8]) -> String {
+    const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+    const FNV_PRIME: u64 = 0x0100_0000_01b3;
+
+    let hash = data.iter().fold(FNV_OFFSET, |hash, &byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(FNV_PRIME)
+    });
+    format!("{:x}-{hash:016x}", data.len())
+}
+
+/// The weak etag of a file of `size` bytes last modified at `mtime`.
+#[must_use]
+pub fn metadata_etag(size: u64, mtime: SystemTime) -> String {
+    let nanos = mtime
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map_or(0, |d| d.as_nanos());
+    format!("W/{size:x}-{nanos:x}")
+}
+
+/// Whether `etag` is weak, i.e. from [`metadata_etag`].
+#[must_use]
+pub fn is_weak_etag(etag: &str) -> bool {
+    etag.starts_with("W/")
+}
+
+/// The canonical form of `path`: absolute, with `.` and empty components
+/// dropped and each `..` removing the component before it. The root is `/`
+/// and no other result ends in a slash.
+///
+/// This is purely lexical, so `/a/../b` is `/b` whether or not `/a` exists.
+///
+/// # Errors
+///
+/// `InvalidArgument` if a `..` would climb above the root.
+pub fn normalize_path(path: &str) -> FsResult<String> {
+    let mut parts: Vec<&str> = Vec::new();
+    for part in path.split('/') {
+        match part {
+            "" | "." => {}
+            ".." => {
+                if parts.pop().is_none() {
+                    return Err(FsError::invalid_argument(format!(
+                        "path escapes the root: {path}"
+                    )));
+                }
+            }
+            name => parts.push(name),
+        }
+    }
+    Ok(format!("/{}", parts.join("/")))
+}
+
+/// The path a `wstat` rename of `path` to `name` moves it to, normalized:
+/// `name` is either absolute or relative to the directory holding `path`.
+///
+/// # Errors
+///
+/// `InvalidArgument` if either path climbs above the root.
+pub fn rename_target(path: &str, name: &str) -> FsResult<String> {
+    if name.starts_with('/') {
+        return normalize_path(name);
+    }
+    let path = normalize_path(path)?;
+    let parent = path.rsplit_once('/').map_or("", |(dir, _)| dir);
+    normalize_path(&format!("{parent}/{name}"))
+}
+
 #[derive(Debug, Clone, Default)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct StatChanges {
@@ -52,6 +133,9 @@ pub struct StatChanges {
     pub mtime: Option<SystemTime>,
     pub name: Option<String>,
     pub symlink_target: Option<String>,
+    /// Expire the file this long from now; a zero duration clears any
+    /// expiry. Providers without expiry support ignore it.
+    pub ttl: Option<Duration>,
 }
 
 impl StatChanges {
@@ -105,6 +189,14 @@ impl StatChanges {
         }
     }
 
+    #[must_use]
+    pub fn expire_after(ttl: Duration) -> Self {
+        Self {
+            ttl: Some(ttl),
+            ..Default::default()
+        }
+    }
+
     #[must_use]
     pub fn is_empty(&self) -> bool {
         self.mode.is_none()
@@ -115,6 +207,7 @@ impl StatChanges {
             && self.mtime.is_none()
             && self.name.is_none()
             && self.symlink_target.is_none()
+            && self.ttl.is_none()
     }
 }
 
@@ -149,6 +242,51 @@ impl FsStats {
     }
 }
 
+/// One page of a directory listing returned by `FsProvider::readdir_at`.
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DirPage {
+    pub entries: Vec<FileInfo>,
+    /// Opaque cursor for the next page; `None` once the listing is exhausted.
+    pub next_cursor: Option<String>,
+}
+
+impl DirPage {
+    /// Page through a complete listing by offset into its path order, for
+    /// providers that cannot resume a listing natively.
+    ///
+    /// # Errors
+    ///
+    /// `InvalidArgument` if `max` is 0 or `cursor` is not one this function
+    /// returned.
+    pub fn from_listing(
+        mut entries: Vec<FileInfo>,
+        cursor: Option<&str>,
+        max: usize,
+    ) -> FsResult<Self> {
+        if max == 0 {
+            return Err(FsError::invalid_argument(
+                "readdir page size must be positive",
+            ));
+        }
+        let start = match cursor {
+            Some(cursor) => cursor.parse::<usize>().map_err(|_| {
+                FsError::invalid_argument(format!("invalid readdir cursor: {cursor}"))
+            })?,
+            None => 0,
+        };
+
+        entries.sort_by(|a, b| a.path.cmp(&b.path));
+        let end = start.saturating_add(max).min(entries.len());
+        let next_cursor = (end < entries.len()).then(|| end.to_string());
+        let entries = entries.into_iter().skip(start).take(max).collect();
+        Ok(Self {
+            entries,
+            next_cursor,
+        })
+    }
+}
+
 #[derive(Debug, Clone, Copy, Default)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct OpenFlags {
@@ -158,6 +296,12 @@ pub struct OpenFlags {
     pub truncate: bool,
     pub append: bool,
     pub directory: bool,
+    /// Open a symlink itself instead of following it; with `create`, make a
+    /// new symlink whose target is the data written through the handle.
+    pub symlink: bool,
+    /// With `create`, fail with `AlreadyExists` instead of opening the
+    /// path if it exists (`O_EXCL`).
+    pub exclusive: bool,
 }
 
 impl OpenFlags {
@@ -205,6 +349,17 @@ impl OpenFlags {
         }
     }
 
+    #[must_use]
+    pub fn create_symlink() -> Self {
+        Self {
+            read: true,
+            write: true,
+            create: true,
+            symlink: true,
+            ..Default::default()
+        }
+    }
+
     #[must_use]
     pub fn append() -> Self {
         Self {
@@ -294,6 +449,63 @@ mod tests {
         assert!(!file.is_dir());
     }
 
+    #[test]
+    fn content_etag_follows_the_contents() {
+        assert_eq!(content_etag(b"hello"), content_etag(b"hello"));
+        assert_ne!(content_etag(b"hello"), content_etag(b"hellp"));
+        assert_ne!(content_etag(b""), content_etag(b"\0"));
+        assert_eq!(content_etag(b""), "0-cbf29ce484222325");
+        assert!(!is_weak_etag(&content_etag(b"hello")));
+    }
+
+    #[test]
+    fn normalize_path_resolves_dot_components() {
+        assert_eq!(normalize_path("/a/./b").unwrap(), "/a/b");
+        assert_eq!(normalize_path("/a/../b").unwrap(), "/b");
+        assert_eq!(normalize_path("/a/b/c/../../d").unwrap(), "/a/d");
+        assert_eq!(normalize_path("/a/..").unwrap(), "/");
+        assert_eq!(normalize_path("/.").unwrap(), "/");
+        assert_eq!(normalize_path("/a/.hidden/..b").unwrap(), "/a/.hidden/..b");
+    }
+
+    #[test]
+    fn normalize_path_collapses_slashes() {
+        assert_eq!(normalize_path("").unwrap(), "/");
+        assert_eq!(normalize_path("/").unwrap(), "/");
+        assert_eq!(normalize_path("//").unwrap(), "/");
+        assert_eq!(normalize_path("/a/").unwrap(), "/a");
+        assert_eq!(normalize_path("//a///b//").unwrap(), "/a/b");
+        assert_eq!(normalize_path("a/b").unwrap(), "/a/b");
+    }
+
+    #[test]
+    fn normalize_path_rejects_escaping_the_root() {
+        for path in ["/..", "..", "/a/../..", "/a/../../b", "/./../x"] {
+            let err = normalize_path(path).unwrap_err();
+            assert!(matches!(err, FsError::InvalidArgument(_)), "{path}: {err}");
+        }
+    }
+
+    #[test]
+    fn rename_target_is_relative_to_the_parent() {
+        assert_eq!(rename_target("/uploads/a", "b").unwrap(), "/uploads/b");
+        assert_eq!(rename_target("/uploads/a", "/b").unwrap(), "/b");
+        assert_eq!(rename_target("/uploads/a", "../b").unwrap(), "/b");
+        assert_eq!(rename_target("/a", "b").unwrap(), "/b");
+        assert_eq!(rename_target("/uploads/a/", "./b/").unwrap(), "/uploads/b");
+        assert!(rename_target("/a", "../../b").is_err());
+    }
+
+    #[test]
+    fn metadata_etag_is_weak() {
+        let mtime = SystemTime::UNIX_EPOCH + Duration::from_nanos(0x1234);
+        let etag = metadata_etag(16, mtime);
+        assert_eq!(etag, "W/10-1234");
+        assert!(is_weak_etag(&etag));
+        assert_ne!(etag, metadata_etag(17, mtime));
+        assert_ne!(etag, metadata_etag(16, mtime + Duration::from_nanos(1)));
+    }
+
     #[test]
     fn stat_changes_constructors() {
         let chmod = StatChanges::chmod(0o644);
@@ -367,6 +579,11 @@ mod tests {
         assert!(create_dir.create);
         assert!(create_dir.directory);
 
+        let create_symlink = OpenFlags::create_symlink();
+        assert!(create_symlink.create);
+        assert!(create_symlink.symlink);
+        assert!(!create_symlink.directory);
+
         let append = OpenFlags::append();
         assert!(append.write);
         assert!(append.append);
@@ -387,4 +604,34 @@ mod tests {
         let to_u64: u64 = handle.into();
         assert_eq!(to_u64, 42);
     }
+
+    #[test]
+    fn dir_page_from_listing() {
+        let entries: Vec<FileInfo> = (0..5)
+            .map(|i| FileInfo {
+                path: format!("/f{i}"),
+                size: 0,
+                file_type: FileType::Regular,
+                mode: 0o644,
+                uid: 0,
+                gid: 0,
+                atime: SystemTime::UNIX_EPOCH,
+                mtime: SystemTime::UNIX_EPOCH,
+                ctime: SystemTime::UNIX_EPOCH,
+                etag: String::new(),
+                symlink_target: None,
+            })
+            .collect();
+
+        let first = DirPage::from_listing(entries.clone(), None, 2).unwrap();
+        assert_eq!(first.entries.len(), 2);
+        assert_eq!(first.next_cursor.as_deref(), Some("2"));
+
+        let last = DirPage::from_listing(entries.clone(), Some("4"), 2).unwrap();
+        assert_eq!(last.entries[0].path, "/f4");
+        assert!(last.next_cursor.is_none());
+
+        assert!(DirPage::from_listing(entries.clone(), Some("bogus"), 2).is_err());
+        assert!(DirPage::from_listing(entries, None, 0).is_err());
+    }
 }
@@ -0,0 +1,263 @@
+//! Structured per-request access log.
+//!
+//! Every request gets an id, taken from the client's `x-request-id` header
+//! when it sends a usable one and generated otherwise. The id is echoed back
+//! on the response, recorded on the request's tracing span (so it reaches
+//! the OpenTelemetry exporter when `otel` is enabled) and written with the
+//! rest of the request summary as one JSON line under the `fs9::access`
+//! target.
+
+use axum::{
+    body::{Body, HttpBody},
+    extract::{Query, Request},
+    http::{header, HeaderMap, HeaderValue},
+    middleware::Next,
+    response::Response,
+};
+use std::collections::HashMap;
+use std::time::Instant;
+use tracing::Instrument;
+
+use crate::auth::RequestContext;
+
+/// Header carrying the request id in both directions.
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// Tracing target of the access log lines, for filtering them apart from
+/// the rest of the server's logs (e.g. `RUST_LOG=fs9::access=info`).
+pub const ACCESS_LOG_TARGET: &str = "fs9::access";
+
+/// Longest client-supplied id that is honored; longer ones are replaced.
+const MAX_REQUEST_ID_LEN: usize = 128;
+
+/// The id of the request being served, available to handlers as a request
+/// extension.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RequestId(pub String);
+
+/// Who made the request, as resolved by auth. Copied onto the response by
+/// [`record_principal`] so the outer access log layer can see it.
+#[derive(Clone, Debug)]
+struct Principal {
+    ns: String,
+    user_id: String,
+}
+
+/// Outer middleware: assigns the request id, runs the request inside a span
+/// carrying it and logs the outcome. Install it outside auth so rejected
+/// requests are logged too.
+pub async fn access_log_middleware(mut request: Request<Body>, next: Next) -> Response {
+    let start = Instant::now();
+    let request_id = request
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .filter(|id| is_usable_id(id))
+        .map_or_else(|| uuid::Uuid::new_v4().to_string(), str::to_owned);
+
+    let method = request.method().to_string();
+    let route = request.uri().path().to_owned();
+    let fs_path = Query::<HashMap<String, String>>::try_from_uri(request.uri())
+        .ok()
+        .and_then(|Query(mut params)| params.remove("path"));
+    let bytes_in = content_length(request.headers()).or_else(|| request.body().size_hint().exact());
+
+    request
+        .extensions_mut()
+        .insert(RequestId(request_id.clone()));
+
+    let span = tracing::info_span!(
+        "request",
+        request_id = %request_id,
+        method = %method,
+        route = %route,
+    );
+    let mut response = next.run(request).instrument(span).await;
+
+    let principal = response.extensions_mut().remove::<Principal>();
+    let bytes_out =
+        content_length(response.headers()).or_else(|| response.body().size_hint().exact());
+    let line = serde_json::json!({
+        "request_id": request_id,
+        "namespace": principal.as_ref().map(|p| p.ns.as_str()),
+        "principal": principal.as_ref().map(|p| p.user_id.as_str()),
+        "operation": format!("{method} {}", operation(&route)),
+        "path": fs_path,
+        "status": response.status().as_u16(),
+        "bytes_in": bytes_in,
+        "bytes_out": bytes_out,
+        "latency_ms": start.elapsed().as_secs_f64() * 1000.0,
+    });
+    tracing::info!(target: ACCESS_LOG_TARGET, "{line}");
+
+    if let Ok(value) = HeaderValue::from_str(&request_id) {
+        response.headers_mut().insert(REQUEST_ID_HEADER, value);
+    }
+    response
+}
+
+/// Inner middleware: copies the namespace and user resolved by auth onto
+/// the response for [`access_log_middleware`]. Install it inside auth.
+pub async fn record_principal(request: Request<Body>, next: Next) -> Response {
+    let principal = request
+        .extensions()
+        .get::<RequestContext>()
+        .map(|ctx| Principal {
+            ns: ctx.ns.clone(),
+            user_id: ctx.user_id.clone(),
+        });
+    let mut response = next.run(request).await;
+    if let Some(principal) = principal {
+        response.extensions_mut().insert(principal);
+    }
+    response
+}
+
+/// Client ids are echoed into headers and logs, so only short printable
+/// ASCII ones are kept.
+fn is_usable_id(id: &str) -> bool {
+    !id.is_empty() && id.len() <= MAX_REQUEST_ID_LEN && id.bytes().all(|b| b.is_ascii_graphic())
+}
+
+fn content_length(headers: &HeaderMap) -> Option<u64> {
+    headers
+        .get(header::CONTENT_LENGTH)?
+        .to_str()
+        .ok()?
+        .parse()
+        .ok()
+}
+
+/// The API operation a route names: `stat` for `/api/v1/stat` (with or
+/// without a tenant prefix), `dav` under `/dav`, the route itself
+/// otherwise.
+pub(crate) fn operation(route: &str) -> &str {
+    if route == crate::webdav::PREFIX || route.starts_with("/dav/") {
+        return "dav";
+    }
+    route
+        .split_once("api/v1/")
+        .map_or(route, |(_, operation)| operation)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{middleware, routing::get, Router};
+    use std::io::Write;
+    use std::sync::{Arc, Mutex};
+    use tower::Service;
+
+    #[derive(Clone, Default)]
+    struct Captured(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Captured {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn app() -> Router {
+        async fn stat() -> &'static str {
+            "hello"
+        }
+
+        async fn authenticate(mut request: Request<Body>, next: Next) -> Response {
+            request.extensions_mut().insert(RequestContext {
+                ns: "acme".to_string(),
+                user_id: "alice".to_string(),
+                roles: Vec::new(),
+                scopes: Vec::new(),
+            });
+            next.run(request).await
+        }
+
+        Router::new()
+            .route("/api/v1/stat", get(stat))
+            .layer(middleware::from_fn(record_principal))
+            .layer(middleware::from_fn(authenticate))
+            .layer(middleware::from_fn(access_log_middleware))
+    }
+
+    async fn send(request: Request<Body>) -> (Response, Vec<serde_json::Value>) {
+        let captured = Captured::default();
+        let writer = captured.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .with_target(false)
+            .without_time()
+            .with_level(false)
+            .with_ansi(false)
+            .with_writer(move || writer.clone())
+            .finish();
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        let response = app().call(request).await.unwrap();
+        let output = String::from_utf8(captured.0.lock().unwrap().clone()).unwrap();
+        let lines = output
+            .lines()
+            .filter_map(|line| serde_json::from_str(line.get(line.find('{')?..)?).ok())
+            .collect();
+        (response, lines)
+    }
+
+    #[tokio::test]
+    async fn access_log_records_request_fields() {
+        let request = Request::get("/api/v1/stat?path=%2Fdocs%2Fa.txt")
+            .body(Body::empty())
+            .unwrap();
+        let (response, lines) = send(request).await;
+
+        let request_id = response.headers()[REQUEST_ID_HEADER].to_str().unwrap();
+        assert!(uuid::Uuid::parse_str(request_id).is_ok());
+
+        assert_eq!(lines.len(), 1);
+        let line = &lines[0];
+        assert_eq!(line["request_id"], request_id);
+        assert_eq!(line["namespace"], "acme");
+        assert_eq!(line["principal"], "alice");
+        assert_eq!(line["operation"], "GET stat");
+        assert_eq!(line["path"], "/docs/a.txt");
+        assert_eq!(line["status"], 200);
+        assert_eq!(line["bytes_in"], 0);
+        assert_eq!(line["bytes_out"], 5);
+        assert!(line["latency_ms"].as_f64().unwrap() >= 0.0);
+    }
+
+    #[tokio::test]
+    async fn client_request_id_is_honored() {
+        let request = Request::get("/api/v1/stat")
+            .header(REQUEST_ID_HEADER, "trace-42")
+            .body(Body::empty())
+            .unwrap();
+        let (response, lines) = send(request).await;
+
+        assert_eq!(response.headers()[REQUEST_ID_HEADER], "trace-42");
+        assert_eq!(lines[0]["request_id"], "trace-42");
+        assert!(lines[0]["path"].is_null());
+    }
+
+    #[tokio::test]
+    async fn unusable_client_request_id_is_replaced() {
+        let request = Request::get("/api/v1/stat")
+            .header(REQUEST_ID_HEADER, "x".repeat(MAX_REQUEST_ID_LEN + 1))
+            .body(Body::empty())
+            .unwrap();
+        let (response, _) = send(request).await;
+
+        let request_id = response.headers()[REQUEST_ID_HEADER].to_str().unwrap();
+        assert!(uuid::Uuid::parse_str(request_id).is_ok());
+    }
+
+    #[test]
+    fn operation_names_the_api_call() {
+        assert_eq!(operation("/api/v1/download"), "download");
+        assert_eq!(operation("/tenant-1/api/v1/upload"), "upload");
+        assert_eq!(operation("/dav/docs/a.txt"), "dav");
+        assert_eq!(operation("/health"), "/health");
+    }
+}
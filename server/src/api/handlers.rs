@@ -1,21 +1,25 @@
 use axum::{
     body::{Body, Bytes},
     extract::{Extension, Query, State},
-    http::{header, HeaderMap, StatusCode},
+    http::{header, response, HeaderMap, HeaderValue, StatusCode},
     response::{IntoResponse, Response},
     Json,
 };
-use fs9_sdk::{FsError, FsProvider, Handle, OpenFlags};
+use fs9_core::{PluginError, ProviderConfig};
+use fs9_sdk::{rename_target, FileInfo, FsError, FsProvider, Handle, OpenFlags, StatChanges};
 use futures::stream;
 use futures::StreamExt;
 use std::sync::Arc;
 
 use crate::api::models::*;
-use crate::auth::RequestContext;
+use crate::auth::{Access, RequestContext};
 use crate::meta_client::MetaClient;
 use crate::namespace::Namespace;
 use crate::state::AppState;
 use fs9_server::audit::EventType;
+use fs9_server::body_limit::BodyTooLarge;
+use fs9_server::circuit_breaker::retry_after_secs;
+use fs9_server::conditional::{entity_tag, if_match_fails, none_match_hits, strong_entity_tag};
 
 pub type AppResult<T> = Result<T, AppError>;
 
@@ -26,6 +30,8 @@ pub enum AppError {
     BadRequest(String),
     Conflict(String),
     NotFound(String),
+    PreconditionFailed(String),
+    PayloadTooLarge(String),
 }
 
 impl From<FsError> for AppError {
@@ -38,6 +44,15 @@ impl AppError {
     pub fn forbidden(msg: impl Into<String>) -> Self {
         Self::Forbidden(msg.into())
     }
+
+    /// An error reading a streamed request body, which is a 413 when the
+    /// body went over its route's limit.
+    fn body(err: &axum::Error) -> Self {
+        BodyTooLarge::from_body_error(err).map_or_else(
+            || FsError::internal(err.to_string()).into(),
+            |too_large| Self::PayloadTooLarge(too_large.to_string()),
+        )
+    }
 }
 
 impl IntoResponse for AppError {
@@ -50,7 +65,12 @@ impl IntoResponse for AppError {
                     error: e.to_string(),
                     code: e.http_status(),
                 });
-                (status, body).into_response()
+                match retry_after_secs(&e) {
+                    Some(secs) => {
+                        (status, [(header::RETRY_AFTER, secs.to_string())], body).into_response()
+                    }
+                    None => (status, body).into_response(),
+                }
             }
             Self::Unauthorized(msg) => {
                 let body = Json(ErrorResponse {
@@ -87,6 +107,20 @@ impl IntoResponse for AppError {
                 });
                 (StatusCode::NOT_FOUND, body).into_response()
             }
+            Self::PreconditionFailed(msg) => {
+                let body = Json(ErrorResponse {
+                    error: msg,
+                    code: 412,
+                });
+                (StatusCode::PRECONDITION_FAILED, body).into_response()
+            }
+            Self::PayloadTooLarge(msg) => {
+                let body = Json(ErrorResponse {
+                    error: msg,
+                    code: 413,
+                });
+                (StatusCode::PAYLOAD_TOO_LARGE, body).into_response()
+            }
         }
     }
 }
@@ -109,11 +143,16 @@ async fn resolve_ns(state: &AppState, ctx: &RequestContext) -> Result<Arc<Namesp
         .ok_or_else(|| AppError::forbidden("Namespace not found or access denied"))?;
 
     // Try to get namespace from meta
+    let mut quota_bytes = None;
     let needs_provision = match meta_client.get_namespace(&ctx.ns).await {
         Ok(ns_info) => {
             if ns_info.status != "active" {
                 return Err(AppError::forbidden("Namespace is not active"));
             }
+            quota_bytes = ns_info.quota_bytes;
+            if let Some(limits) = ns_info.rate_limit {
+                state.rate_limit.set_namespace_limits(&ctx.ns, limits);
+            }
             false
         }
         Err(_) if state.db9_client.is_some() && state.default_pagefs.is_some() => {
@@ -132,6 +171,7 @@ async fn resolve_ns(state: &AppState, ctx: &RequestContext) -> Result<Arc<Namesp
 
     // Create namespace locally
     let ns = state.namespace_manager.get_or_create(&ctx.ns).await;
+    ns.quota.set_limit(quota_bytes);
 
     // Apply pagefs mount directly from default_pagefs config.
     // fs9-meta does not expose a mounts API; the pagefs backend is always
@@ -184,14 +224,21 @@ async fn apply_pagefs_mount(
         },
         "ns": ns_name,
     });
-    let config_json = serde_json::to_string(&mount_config).unwrap_or_default();
-
-    match state.plugin_manager.create_provider("pagefs", &config_json) {
-        Ok(p) => {
-            let provider: Arc<dyn fs9_sdk::FsProvider> = Arc::new(p);
+    let created = state
+        .provider_pool
+        .get_or_create("pagefs", &mount_config, || {
+            state
+                .plugin_manager
+                .create_provider("pagefs", &mount_config.to_string())
+                .map(|p| Arc::new(p) as Arc<dyn FsProvider>)
+        });
+
+    match created {
+        Ok(provider) => {
             if let Err(e) = ns.mount_table.mount("/", "pagefs", provider).await {
                 tracing::error!(ns = %ns_name, error = %e, "Failed to mount pagefs");
             } else {
+                ns.charge_mount("/").await;
                 tracing::info!(ns = %ns_name, keyspace = %keyspace, "Mounted pagefs from default config");
             }
         }
@@ -215,14 +262,17 @@ async fn load_mounts_from_meta(
                 if let Some(obj) = config.as_object_mut() {
                     obj.insert("ns".to_string(), serde_json::json!(ns_name));
                 }
-                let config_json = serde_json::to_string(&config).unwrap_or_default();
-
-                match state
-                    .plugin_manager
-                    .create_provider(&mount.provider, &config_json)
-                {
-                    Ok(p) => {
-                        let provider: Arc<dyn fs9_sdk::FsProvider> = Arc::new(p);
+                let created = state
+                    .provider_pool
+                    .get_or_create(&mount.provider, &config, || {
+                        state
+                            .plugin_manager
+                            .create_provider(&mount.provider, &config.to_string())
+                            .map(|p| Arc::new(p) as Arc<dyn FsProvider>)
+                    });
+
+                match created {
+                    Ok(provider) => {
                         if let Err(e) = ns
                             .mount_table
                             .mount(&mount.path, &mount.provider, provider)
@@ -233,6 +283,7 @@ async fn load_mounts_from_meta(
                                 error = %e, "Failed to mount from meta config"
                             );
                         } else {
+                            ns.charge_mount(&mount.path).await;
                             tracing::info!(
                                 ns = %ns_name, path = %mount.path,
                                 provider = %mount.provider,
@@ -255,6 +306,27 @@ async fn load_mounts_from_meta(
     }
 }
 
+/// Check the caller's path grants before touching the VFS.
+fn authorize(ctx: &RequestContext, path: &str, access: Access) -> Result<(), AppError> {
+    if ctx.can_access_path(path, access) {
+        Ok(())
+    } else {
+        Err(AppError::forbidden(format!("Access denied: {path}")))
+    }
+}
+
+/// Symlink targets are followed inside the provider, out of reach of path
+/// grants, so principals limited by grants may not create or edit links.
+fn authorize_symlink(ctx: &RequestContext, path: &str) -> Result<(), AppError> {
+    if ctx.scopes.is_empty() {
+        Ok(())
+    } else {
+        Err(AppError::forbidden(format!(
+            "Access denied: path-scoped credentials cannot write symlinks: {path}"
+        )))
+    }
+}
+
 #[derive(Debug, serde::Deserialize)]
 pub struct PathQuery {
     pub path: String,
@@ -265,6 +337,7 @@ pub async fn stat(
     Extension(ctx): Extension<RequestContext>,
     Query(query): Query<PathQuery>,
 ) -> AppResult<Json<FileInfoResponse>> {
+    authorize(&ctx, &query.path, Access::Read)?;
     let ns = resolve_ns(&state, &ctx).await?;
     let info = ns.vfs.stat(&query.path).await?;
     Ok(Json(info.into()))
@@ -275,26 +348,53 @@ pub async fn wstat(
     Extension(ctx): Extension<RequestContext>,
     Json(req): Json<WstatRequest>,
 ) -> AppResult<StatusCode> {
+    authorize(&ctx, &req.path, Access::ReadWrite)?;
+    if req.changes.symlink_target.is_some() {
+        authorize_symlink(&ctx, &req.path)?;
+    }
+    // Resolve a relative name here, so the grant check and the VFS see the
+    // same target.
+    let mut changes = StatChanges::from(req.changes);
+    if let Some(name) = &changes.name {
+        let target = rename_target(&req.path, name)?;
+        authorize(&ctx, &target, Access::ReadWrite)?;
+        changes.name = Some(target);
+    }
     let ns = resolve_ns(&state, &ctx).await?;
-    let event_type = if req.changes.name.is_some() {
+    let event_type = if changes.name.is_some() {
         EventType::Rename
-    } else if req.changes.size.is_some() {
+    } else if changes.size.is_some() {
         EventType::Truncate
-    } else if req.changes.mode.is_some() {
+    } else if changes.mode.is_some() {
         EventType::Chmod
     } else {
         EventType::Chmod
     };
-    ns.vfs.wstat(&req.path, req.changes.into()).await?;
+    ns.wstat(&req.path, changes).await?;
     ns.audit_log.record(event_type, &req.path, &ctx.user_id);
     Ok(StatusCode::NO_CONTENT)
 }
 
+/// POST /api/v1/truncate — set a file's size without opening a handle.
+pub async fn truncate(
+    State(state): State<Arc<AppState>>,
+    Extension(ctx): Extension<RequestContext>,
+    Json(req): Json<TruncateRequest>,
+) -> AppResult<StatusCode> {
+    authorize(&ctx, &req.path, Access::ReadWrite)?;
+    let ns = resolve_ns(&state, &ctx).await?;
+    ns.truncate(&req.path, req.size).await?;
+    ns.audit_log
+        .record(EventType::Truncate, &req.path, &ctx.user_id);
+    Ok(StatusCode::NO_CONTENT)
+}
+
 pub async fn statfs(
     State(state): State<Arc<AppState>>,
     Extension(ctx): Extension<RequestContext>,
     Query(query): Query<PathQuery>,
 ) -> AppResult<Json<FsStatsResponse>> {
+    authorize(&ctx, &query.path, Access::Read)?;
     let ns = resolve_ns(&state, &ctx).await?;
     let stats = ns.vfs.statfs(&query.path).await?;
     Ok(Json(stats.into()))
@@ -305,11 +405,23 @@ pub async fn open(
     Extension(ctx): Extension<RequestContext>,
     Json(req): Json<OpenRequest>,
 ) -> AppResult<Json<OpenResponse>> {
-    let ns = resolve_ns(&state, &ctx).await?;
     let flags: OpenFlags = req.flags.into();
+    // Handles inherit the check: read/write by handle id only works on
+    // handles opened here with matching flags.
+    let access = if flags.write || flags.create || flags.truncate || flags.append {
+        Access::ReadWrite
+    } else {
+        Access::Read
+    };
+    authorize(&ctx, &req.path, access)?;
+    // Data written through a symlink handle becomes the link target.
+    if flags.symlink && access == Access::ReadWrite {
+        authorize_symlink(&ctx, &req.path)?;
+    }
+    let ns = resolve_ns(&state, &ctx).await?;
     let is_create = flags.create;
     let is_directory = flags.directory;
-    let (handle, metadata) = ns.vfs.open(&req.path, flags).await?;
+    let (handle, metadata) = ns.open(&req.path, flags).await?;
 
     let handle_id = handle.id();
     ns.handle_map.write().await.insert(handle_id);
@@ -411,11 +523,11 @@ pub async fn write(
     let mut stream = body.into_data_stream();
 
     while let Some(chunk) = stream.next().await {
-        let chunk = chunk.map_err(|e| FsError::internal(e.to_string()))?;
+        let chunk = chunk.map_err(|e| AppError::body(&e))?;
         if chunk.is_empty() {
             continue;
         }
-        let written = ns.vfs.write(&handle, offset, chunk).await?;
+        let written = ns.write(&handle, offset, chunk).await?;
         offset += written as u64;
         total_written += written;
     }
@@ -444,92 +556,246 @@ pub async fn close(
         .remove(&req.handle_id)
         .ok_or_else(|| FsError::invalid_argument("invalid handle_id"))?;
 
-    ns.vfs.close(Handle::new(handle_id), req.sync).await?;
+    let handle = Handle::new(handle_id);
+    if req.sync {
+        // Providers without an explicit flush persist on write or close.
+        match ns.vfs.fsync(&handle, false).await {
+            Ok(()) | Err(FsError::NotImplemented(_)) => {}
+            Err(e) => {
+                let _ = ns.vfs.close(handle, false).await;
+                return Err(e.into());
+            }
+        }
+    }
+
+    ns.vfs.close(handle, req.sync).await?;
     Ok(StatusCode::NO_CONTENT)
 }
 
+pub async fn fsync(
+    State(state): State<Arc<AppState>>,
+    Extension(ctx): Extension<RequestContext>,
+    Json(req): Json<FsyncRequest>,
+) -> AppResult<StatusCode> {
+    let ns = resolve_ns(&state, &ctx).await?;
+    let handle_id = ns
+        .handle_map
+        .read()
+        .await
+        .get_id(&req.handle_id)
+        .ok_or_else(|| FsError::invalid_argument("invalid handle_id"))?;
+
+    ns.vfs.fsync(&Handle::new(handle_id), req.data_only).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct XattrQuery {
+    pub path: String,
+    pub name: String,
+}
+
+/// GET /api/v1/xattr?path=/foo&name=user.mime — the raw attribute value.
+pub async fn getxattr(
+    State(state): State<Arc<AppState>>,
+    Extension(ctx): Extension<RequestContext>,
+    Query(query): Query<XattrQuery>,
+) -> AppResult<Response> {
+    authorize(&ctx, &query.path, Access::Read)?;
+    let ns = resolve_ns(&state, &ctx).await?;
+    let value = ns.vfs.getxattr(&query.path, &query.name).await?;
+    Ok(([(header::CONTENT_TYPE, "application/octet-stream")], value).into_response())
+}
+
+/// PUT /api/v1/xattr?path=/foo&name=user.mime — set the attribute to the body.
+pub async fn setxattr(
+    State(state): State<Arc<AppState>>,
+    Extension(ctx): Extension<RequestContext>,
+    Query(query): Query<XattrQuery>,
+    value: Bytes,
+) -> AppResult<StatusCode> {
+    authorize(&ctx, &query.path, Access::ReadWrite)?;
+    let ns = resolve_ns(&state, &ctx).await?;
+    ns.vfs.setxattr(&query.path, &query.name, value).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// DELETE /api/v1/xattr?path=/foo&name=user.mime
+pub async fn removexattr(
+    State(state): State<Arc<AppState>>,
+    Extension(ctx): Extension<RequestContext>,
+    Query(query): Query<XattrQuery>,
+) -> AppResult<StatusCode> {
+    authorize(&ctx, &query.path, Access::ReadWrite)?;
+    let ns = resolve_ns(&state, &ctx).await?;
+    ns.vfs.removexattr(&query.path, &query.name).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// GET /api/v1/xattrs?path=/foo — names of the attributes set on a file.
+pub async fn listxattr(
+    State(state): State<Arc<AppState>>,
+    Extension(ctx): Extension<RequestContext>,
+    Query(query): Query<PathQuery>,
+) -> AppResult<Json<Vec<String>>> {
+    authorize(&ctx, &query.path, Access::Read)?;
+    let ns = resolve_ns(&state, &ctx).await?;
+    Ok(Json(ns.vfs.listxattr(&query.path).await?))
+}
+
 // =============================================================================
 // Stateless streaming endpoints: download (GET) and upload (PUT)
 // =============================================================================
 
+/// What a `Range` header asks of a file of known size.
+#[derive(Debug, PartialEq, Eq)]
+enum ByteRange {
+    /// No usable range: serve the whole file. Malformed headers land here
+    /// too, since RFC 9110 says to ignore them.
+    Whole,
+    /// Inclusive `start..=end`, already clamped to the file.
+    Partial(u64, u64),
+    /// A well-formed range that lies entirely past EOF.
+    Unsatisfiable,
+}
+
 /// Parse an HTTP Range header value.
-/// Supports: `bytes=start-end`, `bytes=start-`, `bytes=-suffix`.
-fn parse_range_header(range: &str, file_size: u64) -> Option<(u64, u64)> {
-    let range = range.strip_prefix("bytes=")?;
-    if let Some(suffix) = range.strip_prefix('-') {
+/// Supports: `bytes=start-end`, `bytes=start-`, `bytes=-suffix`. Of a
+/// multi-range request only the first range is served.
+fn parse_range_header(range: &str, file_size: u64) -> ByteRange {
+    let Some(ranges) = range.strip_prefix("bytes=") else {
+        return ByteRange::Whole;
+    };
+    let first = ranges.split(',').next().unwrap_or_default().trim();
+    let Some((start_s, end_s)) = first.split_once('-') else {
+        return ByteRange::Whole;
+    };
+
+    if start_s.is_empty() {
         // bytes=-500  →  last 500 bytes
-        let suffix_len: u64 = suffix.parse().ok()?;
-        if suffix_len == 0 || suffix_len > file_size {
-            return None;
-        }
-        Some((file_size - suffix_len, file_size - 1))
-    } else if let Some((start_s, end_s)) = range.split_once('-') {
-        let start: u64 = start_s.parse().ok()?;
-        if start >= file_size {
-            return None;
-        }
-        if end_s.is_empty() {
-            // bytes=100-  →  from 100 to end
-            Some((start, file_size - 1))
-        } else {
-            let end: u64 = end_s.parse().ok()?;
-            if end < start || end >= file_size {
-                return None;
+        return match end_s.parse::<u64>() {
+            Ok(0) => ByteRange::Unsatisfiable,
+            Ok(_) if file_size == 0 => ByteRange::Unsatisfiable,
+            Ok(suffix_len) => {
+                ByteRange::Partial(file_size.saturating_sub(suffix_len), file_size - 1)
             }
-            Some((start, end))
-        }
+            Err(_) => ByteRange::Whole,
+        };
+    }
+
+    let Ok(start) = start_s.parse::<u64>() else {
+        return ByteRange::Whole;
+    };
+    let end = if end_s.is_empty() {
+        // bytes=100-  →  from 100 to end
+        u64::MAX
     } else {
-        None
+        match end_s.parse::<u64>() {
+            Ok(end) if end >= start => end,
+            _ => return ByteRange::Whole,
+        }
+    };
+    if start >= file_size {
+        return ByteRange::Unsatisfiable;
     }
+    ByteRange::Partial(start, end.min(file_size - 1))
+}
+
+/// Extended attribute whose value, when set, is served as a download's
+/// `Content-Type`.
+const CONTENT_TYPE_XATTR: &str = "user.mime";
+
+/// The `Content-Type` stored on `path`, if it has one.
+async fn stored_content_type(ns: &Namespace, path: &str) -> Option<HeaderValue> {
+    let value = ns.vfs.getxattr(path, CONTENT_TYPE_XATTR).await.ok()?;
+    HeaderValue::from_bytes(&value).ok()
+}
+
+/// Headers describing the file itself, sent alike by GET and HEAD.
+fn file_headers(
+    mut builder: response::Builder,
+    info: &FileInfo,
+    etag: String,
+    content_type: Option<HeaderValue>,
+) -> response::Builder {
+    builder = builder
+        .header(header::ACCEPT_RANGES, "bytes")
+        .header(header::ETAG, etag)
+        .header(header::LAST_MODIFIED, httpdate::fmt_http_date(info.mtime));
+    if let Some(content_type) = content_type {
+        builder = builder.header(header::CONTENT_TYPE, content_type);
+    }
+    builder
+}
+
+/// The 304 answer to a download whose `If-None-Match` names `etag`.
+fn not_modified(etag: String) -> Response {
+    Response::builder()
+        .status(StatusCode::NOT_MODIFIED)
+        .header(header::ETAG, etag)
+        .body(Body::empty())
+        .unwrap()
+}
+
+/// The 416 answer to a download whose `Range` lies past the end of a file
+/// of `file_size` bytes.
+fn range_not_satisfiable(file_size: u64) -> Response {
+    Response::builder()
+        .status(StatusCode::RANGE_NOT_SATISFIABLE)
+        .header(header::CONTENT_RANGE, format!("bytes */{file_size}"))
+        .header(header::ACCEPT_RANGES, "bytes")
+        .body(Body::empty())
+        .unwrap()
 }
 
 /// GET /api/v1/download?path=/foo — stateless file download with Range support.
 ///
 /// Opens the file, streams it in chunks, closes the handle when done.
-/// Supports `Range: bytes=start-end` for partial content (206).
+/// Supports `Range: bytes=start-end` for partial content (206), and answers
+/// 304 when `If-None-Match` names the file's current `ETag`, which is the
+/// provider's own, weak or strong.
 pub async fn download(
     State(state): State<Arc<AppState>>,
     Extension(ctx): Extension<RequestContext>,
     Query(query): Query<PathQuery>,
     headers: HeaderMap,
 ) -> AppResult<Response> {
+    authorize(&ctx, &query.path, Access::Read)?;
     let ns = resolve_ns(&state, &ctx).await?;
 
     // Stat to get file size
     let info = ns.vfs.stat(&query.path).await?;
     let file_size = info.size;
+    let etag = entity_tag(&info);
+    if none_match_hits(&headers, &etag) {
+        return Ok(not_modified(etag));
+    }
+    let content_type = stored_content_type(&ns, &query.path).await;
 
-    // Open for reading
-    let (handle, _metadata) = ns
-        .vfs
-        .open(
-            &query.path,
-            OpenFlags {
-                read: true,
-                ..Default::default()
-            },
-        )
-        .await?;
-    let handle_id = handle.id();
-    ns.handle_map.write().await.insert(handle_id);
-
-    // Parse Range header
     let range = headers
         .get(header::RANGE)
         .and_then(|v| v.to_str().ok())
-        .and_then(|v| parse_range_header(v, file_size));
+        .map_or(ByteRange::Whole, |v| parse_range_header(v, file_size));
+    if range == ByteRange::Unsatisfiable {
+        return Ok(range_not_satisfiable(file_size));
+    }
+
+    // Open for reading
+    let (handle, _metadata) = ns.vfs.open(&query.path, OpenFlags::read()).await?;
+    let handle_id = handle.id();
+    ns.handle_map.write().await.insert(handle_id);
 
     let (start, end, status) = match range {
-        Some((s, e)) => (s, e, StatusCode::PARTIAL_CONTENT),
-        None => {
+        ByteRange::Partial(s, e) => (s, e, StatusCode::PARTIAL_CONTENT),
+        ByteRange::Whole | ByteRange::Unsatisfiable => {
             if file_size == 0 {
                 // Empty file — close handle and return empty body
                 ns.handle_map.write().await.remove(&handle_id.to_string());
                 let _ = ns.vfs.close(Handle::new(handle_id), false).await;
-                return Ok(Response::builder()
+                let builder = Response::builder()
                     .status(StatusCode::OK)
-                    .header(header::CONTENT_LENGTH, "0")
-                    .header(header::ACCEPT_RANGES, "bytes")
+                    .header(header::CONTENT_LENGTH, "0");
+                return Ok(file_headers(builder, &info, etag, content_type)
                     .body(Body::empty())
                     .unwrap());
             }
@@ -590,10 +856,14 @@ pub async fn download(
         }),
     );
 
-    let mut builder = Response::builder()
-        .status(status)
-        .header(header::CONTENT_LENGTH, content_length.to_string())
-        .header(header::ACCEPT_RANGES, "bytes");
+    let mut builder = file_headers(
+        Response::builder()
+            .status(status)
+            .header(header::CONTENT_LENGTH, content_length.to_string()),
+        &info,
+        etag,
+        content_type,
+    );
 
     if status == StatusCode::PARTIAL_CONTENT {
         builder = builder.header(
@@ -605,20 +875,73 @@ pub async fn download(
     Ok(builder.body(Body::from_stream(body_stream)).unwrap())
 }
 
+/// HEAD /api/v1/download?path=/foo — the headers GET would send for the
+/// whole file, from a `stat` alone: the file is neither opened nor read, so
+/// the `ETag` is the provider's own, weak or strong, and never a content
+/// hash. Answers 304 to a matching `If-None-Match` like GET; `Range` is
+/// ignored.
+pub async fn download_head(
+    State(state): State<Arc<AppState>>,
+    Extension(ctx): Extension<RequestContext>,
+    Query(query): Query<PathQuery>,
+    headers: HeaderMap,
+) -> AppResult<Response> {
+    authorize(&ctx, &query.path, Access::Read)?;
+    let ns = resolve_ns(&state, &ctx).await?;
+
+    let info = ns.vfs.stat(&query.path).await?;
+    let etag = entity_tag(&info);
+    if none_match_hits(&headers, &etag) {
+        return Ok(not_modified(etag));
+    }
+    let content_type = stored_content_type(&ns, &query.path).await;
+
+    let builder = Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_LENGTH, info.size.to_string());
+    Ok(file_headers(builder, &info, etag, content_type)
+        .body(Body::empty())
+        .unwrap())
+}
+
 /// PUT /api/v1/upload?path=/foo — stateless streaming file upload.
 ///
 /// Creates/truncates the file, streams the request body in chunks, closes when done.
+/// With `If-Match`, the upload is refused with 412 unless the file's current
+/// strong `ETag` is listed; for a file whose provider only has a weak one,
+/// that is a hash of its content. The check runs before the upload starts, so it does not
+/// guard against a writer that slips in while the body is streaming.
 pub async fn upload(
     State(state): State<Arc<AppState>>,
     Extension(ctx): Extension<RequestContext>,
     Query(query): Query<PathQuery>,
+    headers: HeaderMap,
     body: Body,
 ) -> AppResult<Json<UploadResponse>> {
+    authorize(&ctx, &query.path, Access::ReadWrite)?;
     let ns = resolve_ns(&state, &ctx).await?;
 
+    if headers.contains_key(header::IF_MATCH) {
+        let (exists, current) = match ns.vfs.stat(&query.path).await {
+            Ok(info) => {
+                let (provider, _) = ns.mount_table.resolve(&query.path).await?;
+                let tag = strong_entity_tag(&*ns.vfs, &query.path, &info, provider.capabilities())
+                    .await?;
+                (true, tag)
+            }
+            Err(FsError::NotFound(_)) => (false, None),
+            Err(e) => return Err(e.into()),
+        };
+        if if_match_fails(&headers, exists, current.as_deref()) {
+            return Err(AppError::PreconditionFailed(format!(
+                "{} does not match If-Match",
+                query.path
+            )));
+        }
+    }
+
     // Open for create+truncate+write
     let (handle, _metadata) = ns
-        .vfs
         .open(
             &query.path,
             OpenFlags {
@@ -638,23 +961,34 @@ pub async fn upload(
     let mut offset: u64 = 0;
     let mut total_written: usize = 0;
     let mut stream = body.into_data_stream();
+    let mut streamed = Ok(());
 
     while let Some(chunk) = stream.next().await {
-        let chunk = chunk.map_err(|e| {
-            // On error, try to close the handle
-            FsError::internal(e.to_string())
-        })?;
-        if chunk.is_empty() {
-            continue;
+        let chunk = match chunk {
+            Ok(chunk) if chunk.is_empty() => continue,
+            Ok(chunk) => chunk,
+            Err(e) => {
+                streamed = Err(AppError::body(&e));
+                break;
+            }
+        };
+        match ns.write(&fh, offset, chunk).await {
+            Ok(written) => {
+                offset += written as u64;
+                total_written += written;
+            }
+            Err(e) => {
+                streamed = Err(e.into());
+                break;
+            }
         }
-        let written = ns.vfs.write(&fh, offset, chunk).await?;
-        offset += written as u64;
-        total_written += written;
     }
 
-    // Close handle
+    // Close the handle even when the body failed part way
     ns.handle_map.write().await.remove(&handle_id.to_string());
-    ns.vfs.close(Handle::new(handle_id), true).await?;
+    let closed = ns.vfs.close(Handle::new(handle_id), true).await;
+    streamed?;
+    closed?;
 
     ns.audit_log
         .record(EventType::Upload, &query.path, &ctx.user_id);
@@ -670,6 +1004,7 @@ pub async fn readdir(
     Extension(ctx): Extension<RequestContext>,
     Query(query): Query<PathQuery>,
 ) -> AppResult<Json<Vec<FileInfoResponse>>> {
+    authorize(&ctx, &query.path, Access::Read)?;
     let ns = resolve_ns(&state, &ctx).await?;
     let entries = ns.vfs.readdir(&query.path).await?;
     Ok(Json(entries.into_iter().map(Into::into).collect()))
@@ -680,8 +1015,9 @@ pub async fn remove(
     Extension(ctx): Extension<RequestContext>,
     Query(query): Query<PathQuery>,
 ) -> AppResult<StatusCode> {
+    authorize(&ctx, &query.path, Access::ReadWrite)?;
     let ns = resolve_ns(&state, &ctx).await?;
-    ns.vfs.remove(&query.path).await?;
+    ns.remove(&query.path).await?;
     ns.audit_log
         .record(EventType::Delete, &query.path, &ctx.user_id);
     Ok(StatusCode::NO_CONTENT)
@@ -725,6 +1061,12 @@ pub async fn capabilities(
             if caps.supports_symlink() {
                 cap_list.push("symlink".to_string());
             }
+            if caps.supports_fsync() {
+                cap_list.push("fsync".to_string());
+            }
+            if caps.supports_xattr() {
+                cap_list.push("xattr".to_string());
+            }
             if caps.supports_directories() {
                 cap_list.push("directory".to_string());
             }
@@ -750,17 +1092,179 @@ pub async fn list_mounts(
             .map(|m| MountResponse {
                 path: m.path,
                 provider_name: m.provider_name,
+                read_only: m.read_only,
             })
             .collect(),
     ))
 }
 
+/// Build a provider for a mount in `ns_name`: built-in providers first,
+/// then loaded plugins, which also get the namespace in their config. A
+/// live instance with the same config in the namespace is reused.
+fn create_provider(
+    state: &AppState,
+    ns_name: &str,
+    provider: &str,
+    config: &serde_json::Value,
+) -> AppResult<Arc<dyn FsProvider>> {
+    let options = match config {
+        serde_json::Value::Object(obj) => obj.clone(),
+        serde_json::Value::Null => serde_json::Map::new(),
+        _ => {
+            return Err(AppError::BadRequest(
+                "Mount config must be a JSON object".to_string(),
+            ))
+        }
+    };
+
+    let mut plugin_config = options.clone();
+    plugin_config.insert("ns".to_string(), serde_json::json!(ns_name));
+    let plugin_config = serde_json::Value::Object(plugin_config);
+
+    if state.provider_registry.has(provider) {
+        return Ok(state
+            .provider_pool
+            .get_or_create(provider, &plugin_config, || {
+                let mut provider_config = ProviderConfig::new();
+                provider_config.options.extend(options);
+                state.provider_registry.create(provider, provider_config)
+            })?);
+    }
+
+    let created = state
+        .provider_pool
+        .get_or_create(provider, &plugin_config, || {
+            state
+                .plugin_manager
+                .create_provider(provider, &plugin_config.to_string())
+                .map(|p| Arc::new(p) as Arc<dyn FsProvider>)
+        });
+    match created {
+        Ok(p) => Ok(p),
+        Err(PluginError::NotFound(_)) => Err(AppError::NotFound(format!(
+            "Provider '{provider}' not found"
+        ))),
+        Err(e) => Err(AppError::BadRequest(format!(
+            "Failed to create provider '{provider}': {e}"
+        ))),
+    }
+}
+
+/// Refuse to touch a mount while handles are open through it.
+async fn ensure_mount_idle(ns: &Namespace, path: &str) -> AppResult<()> {
+    match ns.mount_handles(path).await? {
+        0 => Ok(()),
+        open => Err(AppError::Conflict(format!(
+            "{open} handle(s) still open under mount {path}"
+        ))),
+    }
+}
+
+/// POST /api/v1/mount — mount a provider (admin only).
+pub async fn mount(
+    State(state): State<Arc<AppState>>,
+    Extension(ctx): Extension<RequestContext>,
+    Json(req): Json<MountRequest>,
+) -> AppResult<(StatusCode, Json<MountResponse>)> {
+    require_role(&ctx, &["admin"])?;
+    let ns = resolve_ns(&state, &ctx).await?;
+
+    let provider = create_provider(&state, &ctx.ns, &req.provider, &req.config)?;
+    ns.mount_table
+        .mount(&req.path, &req.provider, provider)
+        .await?;
+    ns.charge_mount(&req.path).await;
+
+    tracing::info!(ns = %ctx.ns, path = %req.path, provider = %req.provider, "Mounted");
+    Ok((
+        StatusCode::CREATED,
+        Json(mount_response(&ns, &req.path).await?),
+    ))
+}
+
+/// PUT /api/v1/mount — re-create a mount's provider from new config
+/// (admin only). Fails while handles are open through the mount.
+pub async fn update_mount(
+    State(state): State<Arc<AppState>>,
+    Extension(ctx): Extension<RequestContext>,
+    Json(req): Json<UpdateMountRequest>,
+) -> AppResult<Json<MountResponse>> {
+    require_role(&ctx, &["admin"])?;
+    let ns = resolve_ns(&state, &ctx).await?;
+
+    let current = ns
+        .mount_table
+        .get_mount_info(&req.path)
+        .await
+        .ok_or_else(|| AppError::NotFound(format!("No mount at {}", req.path)))?
+        .0;
+    let provider_name = req.provider.unwrap_or(current.provider_name);
+    ensure_mount_idle(&ns, &req.path).await?;
+
+    let provider = create_provider(&state, &ctx.ns, &provider_name, &req.config)?;
+    let old_usage = ns.mount_usage(&req.path).await;
+    let old = ns
+        .mount_table
+        .replace(&req.path, &provider_name, provider)
+        .await?;
+    if let Err(e) = old.flush().await {
+        tracing::warn!(ns = %ctx.ns, path = %req.path, error = %e, "Flush of replaced provider failed");
+    }
+    ns.quota.release(old_usage);
+    ns.charge_mount(&req.path).await;
+
+    tracing::info!(ns = %ctx.ns, path = %req.path, provider = %provider_name, "Mount updated");
+    Ok(Json(mount_response(&ns, &req.path).await?))
+}
+
+/// DELETE /api/v1/mount?path=/data — unmount (admin only). The provider is
+/// flushed and destroyed; fails while handles are open through it.
+pub async fn unmount(
+    State(state): State<Arc<AppState>>,
+    Extension(ctx): Extension<RequestContext>,
+    Query(query): Query<PathQuery>,
+) -> AppResult<StatusCode> {
+    require_role(&ctx, &["admin"])?;
+    let ns = resolve_ns(&state, &ctx).await?;
+
+    ensure_mount_idle(&ns, &query.path).await?;
+    let usage = ns.mount_usage(&query.path).await;
+    let provider = ns.mount_table.unmount(&query.path).await?;
+    if let Err(e) = provider.flush().await {
+        tracing::warn!(ns = %ctx.ns, path = %query.path, error = %e, "Flush of unmounted provider failed");
+    }
+    ns.quota.release(usage);
+
+    tracing::info!(ns = %ctx.ns, path = %query.path, "Unmounted");
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn mount_response(ns: &Namespace, path: &str) -> AppResult<MountResponse> {
+    let (mount, _) = ns
+        .mount_table
+        .get_mount_info(path)
+        .await
+        .ok_or_else(|| AppError::NotFound(format!("No mount at {path}")))?;
+    Ok(MountResponse {
+        path: mount.path,
+        provider_name: mount.provider_name,
+        read_only: mount.read_only,
+    })
+}
+
+/// The namespace's audit log, or with `Accept: text/event-stream` a live
+/// stream of its changes from now on.
 pub async fn events(
     State(state): State<Arc<AppState>>,
     Extension(ctx): Extension<RequestContext>,
     Query(query): Query<EventsQuery>,
-) -> AppResult<Json<Vec<AuditEventResponse>>> {
+    headers: HeaderMap,
+) -> AppResult<Response> {
     let ns = resolve_ns(&state, &ctx).await?;
+    if wants_event_stream(&headers) {
+        return Ok(change_stream(&ns, ctx, query.path));
+    }
+
     let type_filter = query
         .event_type
         .as_deref()
@@ -768,11 +1272,85 @@ pub async fn events(
         .transpose()
         .map_err(|e| AppError::BadRequest(e))?;
 
-    let events = ns
-        .audit_log
-        .query(query.limit, query.offset, query.path.as_deref(), type_filter);
+    let events = ns.audit_log.query(
+        query.limit,
+        query.offset,
+        query.path.as_deref(),
+        type_filter,
+    );
+
+    // Events are metadata about paths, so they follow the read grants.
+    Ok(Json(
+        events
+            .into_iter()
+            .filter(|event| ctx.can_access_path(&event.path, Access::Read))
+            .map(AuditEventResponse::from)
+            .collect::<Vec<_>>(),
+    )
+    .into_response())
+}
+
+fn wants_event_stream(headers: &HeaderMap) -> bool {
+    headers
+        .get(header::ACCEPT)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|accept| accept.contains("text/event-stream"))
+}
+
+/// Server-sent events for the changes made in `ns`, one per change under
+/// `prefix` whose paths the caller may read. The event name is the change
+/// op and the data its JSON. A client too slow to keep up misses changes
+/// rather than holding up writers; the gap is logged.
+fn change_stream(ns: &Namespace, ctx: RequestContext, prefix: Option<String>) -> Response {
+    use axum::response::sse::{Event, KeepAlive, Sse};
+    use tokio::sync::broadcast::error::RecvError;
+
+    let receiver = ns.vfs.subscribe_changes();
+    let events = stream::unfold(
+        (receiver, ctx, prefix),
+        |(mut receiver, ctx, prefix)| async move {
+            loop {
+                let change = match receiver.recv().await {
+                    Ok(change) => change,
+                    Err(RecvError::Lagged(skipped)) => {
+                        tracing::warn!(ns = %ctx.ns, skipped, "Event stream fell behind, changes dropped");
+                        continue;
+                    }
+                    Err(RecvError::Closed) => return None,
+                };
+                let visible = prefix.as_deref().map_or(true, |p| change.is_under(p))
+                    && std::iter::once(&change.path)
+                        .chain(&change.new_path)
+                        .all(|path| ctx.can_access_path(path, Access::Read));
+                if !visible {
+                    continue;
+                }
+                let Ok(event) = Event::default()
+                    .event(change.op.as_str())
+                    .json_data(&change)
+                else {
+                    continue;
+                };
+                return Some((
+                    Ok::<_, std::convert::Infallible>(event),
+                    (receiver, ctx, prefix),
+                ));
+            }
+        },
+    );
+    Sse::new(events)
+        .keep_alive(KeepAlive::default())
+        .into_response()
+}
 
-    Ok(Json(events.into_iter().map(Into::into).collect()))
+/// Any method under `/dav` — WebDAV access to the caller's namespace.
+pub async fn webdav(
+    State(state): State<Arc<AppState>>,
+    Extension(ctx): Extension<RequestContext>,
+    request: axum::extract::Request,
+) -> AppResult<Response> {
+    let ns = resolve_ns(&state, &ctx).await?;
+    Ok(fs9_server::webdav::serve(&ns, &ctx, request).await)
 }
 
 pub async fn health() -> Json<HealthResponse> {
@@ -794,7 +1372,7 @@ pub async fn refresh_token(
     State(state): State<Arc<AppState>>,
     headers: axum::http::HeaderMap,
 ) -> AppResult<Json<RefreshTokenResponse>> {
-    use crate::auth::{Claims, JwtConfig};
+    use crate::auth::Claims;
 
     let ttl_secs: u64 = 86400; // 24 hours default
     let token = headers
@@ -830,14 +1408,13 @@ pub async fn refresh_token(
     }
 
     // Fallback: local JWT refresh
-    let jwt_secret = state.jwt_secret.read().await;
-    if jwt_secret.is_empty() {
+    let config = state.jwt_config.read().await.clone();
+    if config.secret.is_empty() {
         return Err(AppError::BadRequest(
             "Token refresh not configured".to_string(),
         ));
     }
 
-    let config = JwtConfig::new(jwt_secret.clone());
     let old_claims = config
         .decode_allow_expired(token)
         .map_err(|e| AppError::Unauthorized(format!("Invalid token: {e}")))?;
@@ -845,8 +1422,14 @@ pub async fn refresh_token(
         .ns
         .clone()
         .ok_or_else(|| AppError::Unauthorized("Token missing required 'ns' claim".to_string()))?;
-    let new_claims =
-        Claims::with_namespace(&old_claims.sub, &ns, old_claims.roles.clone(), ttl_secs);
+    // Everything but the lifetime and token id carries over; dropping the
+    // grants would widen a path-scoped token to the whole namespace.
+    let new_claims = Claims {
+        permissions: old_claims.permissions,
+        mounts: old_claims.mounts,
+        scopes: old_claims.scopes,
+        ..Claims::with_namespace(&old_claims.sub, &ns, old_claims.roles, ttl_secs)
+    };
     let new_token = config
         .encode(&new_claims)
         .map_err(|e| AppError::BadRequest(format!("Failed to generate token: {}", e)))?;
@@ -865,16 +1448,44 @@ pub async fn revoke_token(
 ) -> AppResult<StatusCode> {
     require_role(&ctx, &["admin"])?;
 
-    state.revocation_set.revoke(&req.token).await;
-    state.token_cache.remove(&req.token).await;
+    match (req.token, req.jti) {
+        (Some(token), None) => {
+            state.revocation_set.revoke(&token).await;
+            state.token_cache.remove(&token).await;
+        }
+        (None, Some(jti)) => state.revocation_set.revoke_id(&jti, req.expires_at),
+        _ => {
+            return Err(AppError::BadRequest(
+                "Specify exactly one of 'token' or 'jti'".to_string(),
+            ))
+        }
+    }
 
     tracing::info!(user = %ctx.user_id, "Token revoked");
     Ok(StatusCode::NO_CONTENT)
 }
 
-// NOTE: plugin/load, plugin/unload, plugin/list, and mount endpoints
-// have been removed for security reasons. The handler code has been deleted
-// and the routes removed from api_v1_routes().
+/// GET /api/v1/auth/revoked — token ids that are still revoked (admin only).
+pub async fn list_revoked_tokens(
+    State(state): State<Arc<AppState>>,
+    Extension(ctx): Extension<RequestContext>,
+) -> AppResult<Json<Vec<RevokedTokenResponse>>> {
+    require_role(&ctx, &["admin"])?;
+
+    let revoked = state
+        .revocation_set
+        .revoked_ids()
+        .into_iter()
+        .map(|id| RevokedTokenResponse {
+            jti: id.jti,
+            expires_at: id.expires_at,
+        })
+        .collect();
+    Ok(Json(revoked))
+}
+
+// NOTE: plugin/load, plugin/unload and plugin/list have been removed for
+// security reasons. Mount management is admin-only.
 
 // ============================================================================
 // Namespace management API
@@ -962,67 +1573,902 @@ pub async fn get_namespace(
     }
 }
 
+/// PUT /api/v1/namespaces/:ns/quota — set or lift a namespace's byte budget (admin only).
+pub async fn set_namespace_quota(
+    State(state): State<Arc<AppState>>,
+    Extension(ctx): Extension<RequestContext>,
+    axum::extract::Path(ns_name): axum::extract::Path<String>,
+    Json(req): Json<SetQuotaRequest>,
+) -> AppResult<Json<NamespaceUsageResponse>> {
+    require_role(&ctx, &["admin"])?;
+
+    let ns = state
+        .namespace_manager
+        .get(&ns_name)
+        .await
+        .ok_or_else(|| AppError::NotFound(format!("Namespace '{}' not found", ns_name)))?;
+    ns.quota.set_limit(req.quota_bytes);
+    tracing::info!(namespace = %ns_name, quota_bytes = ?req.quota_bytes, "Set namespace quota");
+    Ok(Json(usage_response(&ns)))
+}
+
+/// GET /api/v1/namespaces/:ns/usage — bytes used against the quota (admin or operator).
+///
+/// Usage only counts growth through this server since it loaded the
+/// namespace; it is not persisted and starts at zero after a restart.
+pub async fn namespace_usage(
+    State(state): State<Arc<AppState>>,
+    Extension(ctx): Extension<RequestContext>,
+    axum::extract::Path(ns_name): axum::extract::Path<String>,
+) -> AppResult<Json<NamespaceUsageResponse>> {
+    require_role(&ctx, &["admin", "operator"])?;
+
+    let ns = state
+        .namespace_manager
+        .get(&ns_name)
+        .await
+        .ok_or_else(|| AppError::NotFound(format!("Namespace '{}' not found", ns_name)))?;
+    Ok(Json(usage_response(&ns)))
+}
+
+fn usage_response(ns: &Namespace) -> NamespaceUsageResponse {
+    NamespaceUsageResponse {
+        name: ns.name.clone(),
+        used_bytes: ns.quota.used(),
+        quota_bytes: ns.quota.limit(),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::auth::PathGrant;
+    use axum::http::HeaderValue;
 
     #[test]
     fn parse_range_full() {
-        assert_eq!(parse_range_header("bytes=0-499", 1000), Some((0, 499)));
+        assert_eq!(
+            parse_range_header("bytes=0-499", 1000),
+            ByteRange::Partial(0, 499)
+        );
     }
 
     #[test]
     fn parse_range_open_end() {
-        assert_eq!(parse_range_header("bytes=500-", 1000), Some((500, 999)));
+        assert_eq!(
+            parse_range_header("bytes=500-", 1000),
+            ByteRange::Partial(500, 999)
+        );
     }
 
     #[test]
     fn parse_range_suffix() {
-        assert_eq!(parse_range_header("bytes=-200", 1000), Some((800, 999)));
+        assert_eq!(
+            parse_range_header("bytes=-200", 1000),
+            ByteRange::Partial(800, 999)
+        );
     }
 
     #[test]
     fn parse_range_entire_file() {
-        assert_eq!(parse_range_header("bytes=0-999", 1000), Some((0, 999)));
+        assert_eq!(
+            parse_range_header("bytes=0-999", 1000),
+            ByteRange::Partial(0, 999)
+        );
     }
 
     #[test]
     fn parse_range_single_byte() {
-        assert_eq!(parse_range_header("bytes=0-0", 1000), Some((0, 0)));
+        assert_eq!(
+            parse_range_header("bytes=0-0", 1000),
+            ByteRange::Partial(0, 0)
+        );
     }
 
     #[test]
-    fn parse_range_invalid_start_past_end() {
-        assert_eq!(parse_range_header("bytes=1000-", 1000), None);
+    fn parse_range_start_past_end_is_unsatisfiable() {
+        assert_eq!(
+            parse_range_header("bytes=1000-", 1000),
+            ByteRange::Unsatisfiable
+        );
+        assert_eq!(
+            parse_range_header("bytes=5000-6000", 1000),
+            ByteRange::Unsatisfiable
+        );
     }
 
     #[test]
-    fn parse_range_invalid_end_past_file() {
-        assert_eq!(parse_range_header("bytes=0-1000", 1000), None);
+    fn parse_range_end_past_file_is_clamped() {
+        assert_eq!(
+            parse_range_header("bytes=0-1000", 1000),
+            ByteRange::Partial(0, 999)
+        );
     }
 
     #[test]
     fn parse_range_invalid_reversed() {
-        assert_eq!(parse_range_header("bytes=500-100", 1000), None);
+        assert_eq!(parse_range_header("bytes=500-100", 1000), ByteRange::Whole);
     }
 
     #[test]
     fn parse_range_invalid_format() {
-        assert_eq!(parse_range_header("chars=0-100", 1000), None);
+        assert_eq!(parse_range_header("chars=0-100", 1000), ByteRange::Whole);
+        assert_eq!(parse_range_header("bytes=abc", 1000), ByteRange::Whole);
     }
 
     #[test]
     fn parse_range_suffix_zero() {
-        assert_eq!(parse_range_header("bytes=-0", 1000), None);
+        assert_eq!(
+            parse_range_header("bytes=-0", 1000),
+            ByteRange::Unsatisfiable
+        );
     }
 
     #[test]
-    fn parse_range_suffix_too_large() {
-        assert_eq!(parse_range_header("bytes=-2000", 1000), None);
+    fn parse_range_suffix_too_large_is_whole_file() {
+        assert_eq!(
+            parse_range_header("bytes=-2000", 1000),
+            ByteRange::Partial(0, 999)
+        );
     }
 
     #[test]
     fn parse_range_empty_file() {
-        assert_eq!(parse_range_header("bytes=0-", 0), None);
+        assert_eq!(parse_range_header("bytes=0-", 0), ByteRange::Unsatisfiable);
+    }
+
+    #[test]
+    fn parse_range_multi_range_serves_first() {
+        assert_eq!(
+            parse_range_header("bytes=10-19, 50-59", 1000),
+            ByteRange::Partial(10, 19)
+        );
+    }
+
+    async fn download_with_range(range: Option<&str>) -> Response {
+        let state = Arc::new(AppState::new());
+        let ns = state.namespace_manager.get_or_create("default").await;
+        ns.mount_table
+            .mount("/", "memfs", Arc::new(fs9_core::MemoryFs::new()))
+            .await
+            .unwrap();
+        let (handle, _) = ns
+            .vfs
+            .open("/video.bin", OpenFlags::create_file())
+            .await
+            .unwrap();
+        ns.vfs
+            .write(&handle, 0, Bytes::from_static(b"0123456789abcdef"))
+            .await
+            .unwrap();
+        ns.vfs.close(handle, true).await.unwrap();
+
+        let mut headers = HeaderMap::new();
+        if let Some(range) = range {
+            headers.insert(header::RANGE, range.parse().unwrap());
+        }
+        let ctx = RequestContext {
+            ns: "default".to_string(),
+            user_id: "tester".to_string(),
+            roles: vec!["admin".to_string()],
+            scopes: Vec::new(),
+        };
+        let query = PathQuery {
+            path: "/video.bin".to_string(),
+        };
+        match download(State(state), Extension(ctx), Query(query), headers).await {
+            Ok(response) => response,
+            Err(e) => e.into_response(),
+        }
+    }
+
+    #[tokio::test]
+    async fn download_serves_middle_range_as_partial_content() {
+        let response = download_with_range(Some("bytes=4-9")).await;
+        assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(response.headers()[header::CONTENT_RANGE], "bytes 4-9/16");
+        assert_eq!(response.headers()[header::CONTENT_LENGTH], "6");
+        assert_eq!(response.headers()[header::ACCEPT_RANGES], "bytes");
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(&body[..], b"456789");
+    }
+
+    #[tokio::test]
+    async fn download_rejects_range_past_eof() {
+        let response = download_with_range(Some("bytes=16-")).await;
+        assert_eq!(response.status(), StatusCode::RANGE_NOT_SATISFIABLE);
+        assert_eq!(response.headers()[header::CONTENT_RANGE], "bytes */16");
+    }
+
+    #[tokio::test]
+    async fn download_without_range_serves_whole_file() {
+        let response = download_with_range(None).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[header::ACCEPT_RANGES], "bytes");
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(&body[..], b"0123456789abcdef");
+    }
+
+    #[tokio::test]
+    async fn head_sends_the_get_headers_without_a_body() {
+        let state = Arc::new(AppState::new());
+        let ns = state.namespace_manager.get_or_create("default").await;
+        ns.mount_table
+            .mount("/", "memfs", Arc::new(fs9_core::MemoryFs::new()))
+            .await
+            .unwrap();
+        expect_ok(api_write(&state, "/report.csv", b"a,b\n1,2\n").await);
+        let query = |path: &str| {
+            Query(PathQuery {
+                path: path.to_string(),
+            })
+        };
+
+        let get = expect_ok(
+            download(
+                State(state.clone()),
+                Extension(admin_ctx()),
+                query("/report.csv"),
+                HeaderMap::new(),
+            )
+            .await,
+        );
+        let head = expect_ok(
+            download_head(
+                State(state.clone()),
+                Extension(admin_ctx()),
+                query("/report.csv"),
+                HeaderMap::new(),
+            )
+            .await,
+        );
+        assert_eq!(head.status(), StatusCode::OK);
+        assert_eq!(head.headers(), get.headers());
+        assert_eq!(head.headers()[header::CONTENT_LENGTH], "8");
+        assert!(head.headers().contains_key(header::ETAG));
+        assert!(head.headers().contains_key(header::LAST_MODIFIED));
+        let body = axum::body::to_bytes(head.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert!(body.is_empty());
+
+        let Err(err) = download_head(
+            State(state),
+            Extension(admin_ctx()),
+            query("/missing.csv"),
+            HeaderMap::new(),
+        )
+        .await
+        else {
+            panic!("HEAD on a missing file succeeded");
+        };
+        assert_eq!(err.into_response().status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn download_head_answers_from_stat_alone() {
+        use fs9_core::testing::{HookedFs, Op};
+
+        let state = Arc::new(AppState::new());
+        let ns = state.namespace_manager.get_or_create("default").await;
+        let fs = Arc::new(HookedFs::new());
+        ns.mount_table
+            .mount("/", "hooked", fs.clone())
+            .await
+            .unwrap();
+        expect_ok(api_write(&state, "/big.bin", &[7u8; 4096]).await);
+        let (opens, reads) = (fs.calls(Op::Open), fs.calls(Op::Read));
+
+        let head = expect_ok(
+            download_head(
+                State(state.clone()),
+                Extension(admin_ctx()),
+                Query(PathQuery {
+                    path: "/big.bin".to_string(),
+                }),
+                HeaderMap::new(),
+            )
+            .await,
+        );
+        assert_eq!(head.status(), StatusCode::OK);
+        assert_eq!(head.headers()[header::CONTENT_LENGTH], "4096");
+        assert!(head.headers().contains_key(header::ETAG));
+        assert_eq!(fs.calls(Op::Open), opens);
+        assert_eq!(fs.calls(Op::Read), reads);
+    }
+
+    #[tokio::test]
+    async fn etag_drives_conditional_get_and_upload() {
+        let state = Arc::new(AppState::new());
+        let ns = state.namespace_manager.get_or_create("default").await;
+        ns.mount_table
+            .mount("/", "memfs", Arc::new(fs9_core::MemoryFs::new()))
+            .await
+            .unwrap();
+        expect_ok(api_write(&state, "/doc.txt", b"v1").await);
+        let query = || {
+            Query(PathQuery {
+                path: "/doc.txt".to_string(),
+            })
+        };
+        let conditional = |name, etag: &HeaderValue| {
+            let mut headers = HeaderMap::new();
+            headers.insert(name, etag.clone());
+            headers
+        };
+        let get = |headers| {
+            download(
+                State(state.clone()),
+                Extension(admin_ctx()),
+                query(),
+                headers,
+            )
+        };
+        let put = |headers, body: &'static [u8]| {
+            upload(
+                State(state.clone()),
+                Extension(admin_ctx()),
+                query(),
+                headers,
+                Body::from(body),
+            )
+        };
+
+        let response = expect_ok(get(HeaderMap::new()).await);
+        assert_eq!(response.status(), StatusCode::OK);
+        let v1 = response.headers()[header::ETAG].clone();
+
+        let response = expect_ok(get(conditional(header::IF_NONE_MATCH, &v1)).await);
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(response.headers()[header::ETAG], v1);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert!(body.is_empty());
+
+        let _ = expect_ok(put(conditional(header::IF_MATCH, &v1), b"v2").await);
+        let Err(err) = put(conditional(header::IF_MATCH, &v1), b"v3").await else {
+            panic!("upload with a stale If-Match succeeded");
+        };
+        assert_eq!(
+            err.into_response().status(),
+            StatusCode::PRECONDITION_FAILED
+        );
+
+        let response = expect_ok(get(conditional(header::IF_NONE_MATCH, &v1)).await);
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_ne!(response.headers()[header::ETAG], v1);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(&body[..], b"v2");
+    }
+
+    /// A principal granted `rw` on `/uploads` and read-only access elsewhere.
+    fn uploader_ctx() -> RequestContext {
+        RequestContext {
+            ns: "default".to_string(),
+            user_id: "uploader".to_string(),
+            roles: vec!["read".to_string(), "write".to_string()],
+            scopes: vec![
+                PathGrant {
+                    prefix: "/uploads".to_string(),
+                    access: Access::ReadWrite,
+                },
+                PathGrant {
+                    prefix: "/".to_string(),
+                    access: Access::Read,
+                },
+            ],
+        }
+    }
+
+    /// A namespace on memfs holding the directories `/uploads` and `/other`
+    /// and the empty file `/uploads/a`.
+    async fn scoped_state() -> Arc<AppState> {
+        let state = Arc::new(AppState::new());
+        let ns = state.namespace_manager.get_or_create("default").await;
+        ns.mount_table
+            .mount("/", "memfs", Arc::new(fs9_core::MemoryFs::new()))
+            .await
+            .unwrap();
+        for dir in ["/uploads", "/other"] {
+            let (handle, _) = ns.vfs.open(dir, OpenFlags::create_dir()).await.unwrap();
+            ns.vfs.close(handle, false).await.unwrap();
+        }
+        let (handle, _) = ns
+            .vfs
+            .open("/uploads/a", OpenFlags::create_file())
+            .await
+            .unwrap();
+        ns.vfs.close(handle, false).await.unwrap();
+        state
+    }
+
+    /// Writes through `open` as [`uploader_ctx`].
+    async fn scoped_create(path: &str) -> AppResult<Json<OpenResponse>> {
+        let req = OpenRequest {
+            path: path.to_string(),
+            flags: OpenFlagsRequest {
+                write: true,
+                create: true,
+                ..Default::default()
+            },
+        };
+        open(
+            State(scoped_state().await),
+            Extension(uploader_ctx()),
+            Json(req),
+        )
+        .await
+    }
+
+    async fn scoped_wstat(state: &Arc<AppState>, changes: StatChangesRequest) -> StatusCode {
+        let req = WstatRequest {
+            path: "/uploads/a".to_string(),
+            changes,
+        };
+        match wstat(State(state.clone()), Extension(uploader_ctx()), Json(req)).await {
+            Ok(status) => status,
+            Err(e) => e.into_response().status(),
+        }
+    }
+
+    #[tokio::test]
+    async fn scoped_rename_resolves_a_relative_name_before_the_grant_check() {
+        let state = scoped_state().await;
+        for name in ["../b", "../other/b", "/b"] {
+            let changes = StatChangesRequest {
+                name: Some(name.to_string()),
+                ..Default::default()
+            };
+            assert_eq!(
+                scoped_wstat(&state, changes).await,
+                StatusCode::FORBIDDEN,
+                "{name}"
+            );
+        }
+
+        let changes = StatChangesRequest {
+            name: Some("b".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(scoped_wstat(&state, changes).await, StatusCode::NO_CONTENT);
+        let ns = state.namespace_manager.get("default").await.unwrap();
+        assert!(ns.vfs.stat("/uploads/b").await.is_ok());
+        assert!(ns.vfs.stat("/b").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn scoped_principals_cannot_write_symlinks() {
+        let state = scoped_state().await;
+        let changes = StatChangesRequest {
+            symlink_target: Some("/other".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(scoped_wstat(&state, changes).await, StatusCode::FORBIDDEN);
+
+        let req = OpenRequest {
+            path: "/uploads/ln".to_string(),
+            flags: OpenFlagsRequest {
+                write: true,
+                create: true,
+                symlink: true,
+                ..Default::default()
+            },
+        };
+        let response = open(State(state), Extension(uploader_ctx()), Json(req))
+            .await
+            .unwrap_err()
+            .into_response();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    }
+
+    #[tokio::test]
+    async fn refreshed_scoped_token_keeps_its_grants() {
+        use crate::auth::{Claims, JwtConfig};
+
+        let state = Arc::new(AppState::new());
+        let config = JwtConfig::new("refresh-test-secret");
+        state.set_jwt_config(config.clone()).await;
+        let claims = Claims {
+            scopes: uploader_ctx().scopes,
+            ..Claims::with_namespace("uploader", "default", vec!["write".to_string()], 60)
+        };
+        let mut headers = HeaderMap::new();
+        let bearer = format!("Bearer {}", config.encode(&claims).unwrap());
+        headers.insert(header::AUTHORIZATION, bearer.parse().unwrap());
+
+        let refreshed = expect_ok(refresh_token(State(state), headers).await);
+        let claims = config.decode(&refreshed.0.token).unwrap();
+        let ctx = RequestContext {
+            ns: claims.ns.unwrap(),
+            user_id: claims.sub,
+            roles: claims.roles,
+            scopes: claims.scopes,
+        };
+        assert!(ctx.can_access_path("/uploads/a", Access::ReadWrite));
+        assert!(!ctx.can_access_path("/other/a", Access::ReadWrite));
+    }
+
+    #[tokio::test]
+    async fn scoped_write_under_granted_prefix_succeeds() {
+        assert!(scoped_create("/uploads/a.txt").await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn scoped_write_outside_granted_prefix_is_denied() {
+        let response = scoped_create("/other/b.txt")
+            .await
+            .unwrap_err()
+            .into_response();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    }
+
+    /// `AppError` has no `Debug`, so report the status it would respond with.
+    fn expect_ok<T>(result: AppResult<T>) -> T {
+        match result {
+            Ok(value) => value,
+            Err(e) => panic!("request failed: {}", e.into_response().status()),
+        }
+    }
+
+    fn admin_ctx() -> RequestContext {
+        RequestContext {
+            ns: "default".to_string(),
+            user_id: "admin".to_string(),
+            roles: vec!["admin".to_string()],
+            scopes: Vec::new(),
+        }
+    }
+
+    /// Create `path` and write `data` to it through the REST handlers.
+    async fn api_write(state: &Arc<AppState>, path: &str, data: &'static [u8]) -> AppResult<()> {
+        let req = OpenRequest {
+            path: path.to_string(),
+            flags: OpenFlagsRequest {
+                write: true,
+                create: true,
+                ..Default::default()
+            },
+        };
+        let opened = open(State(state.clone()), Extension(admin_ctx()), Json(req)).await?;
+        let handle_id = opened.0.handle_id;
+        let query = WriteQuery {
+            handle_id: handle_id.clone(),
+            offset: 0,
+        };
+        let written = write(
+            State(state.clone()),
+            Extension(admin_ctx()),
+            Query(query),
+            Body::from(data),
+        )
+        .await;
+        let req = CloseRequest {
+            handle_id,
+            sync: false,
+        };
+        close(State(state.clone()), Extension(admin_ctx()), Json(req)).await?;
+        written.map(|_| ())
+    }
+
+    #[tokio::test]
+    async fn identical_mounts_share_one_provider() {
+        let state = Arc::new(AppState::new());
+        state.namespace_manager.get_or_create("default").await;
+        let root = std::env::temp_dir().to_string_lossy().into_owned();
+        let mount_at = |path: &str, provider: &str| MountRequest {
+            path: path.to_string(),
+            provider: provider.to_string(),
+            config: serde_json::json!({ "root": root }),
+        };
+        for (path, provider) in [("/x", "localfs"), ("/y", "localfs"), ("/m", "memfs")] {
+            let req = mount_at(path, provider);
+            let (status, _) =
+                expect_ok(mount(State(state.clone()), Extension(admin_ctx()), Json(req)).await);
+            assert_eq!(status, StatusCode::CREATED);
+        }
+        assert_eq!(state.provider_pool.live_instances(), 1);
+
+        let unmount_at = |path: &str| PathQuery {
+            path: path.to_string(),
+        };
+        expect_ok(
+            unmount(
+                State(state.clone()),
+                Extension(admin_ctx()),
+                Query(unmount_at("/x")),
+            )
+            .await,
+        );
+        assert_eq!(
+            state.provider_pool.live_instances(),
+            1,
+            "still mounted at /y"
+        );
+        expect_ok(
+            unmount(
+                State(state.clone()),
+                Extension(admin_ctx()),
+                Query(unmount_at("/y")),
+            )
+            .await,
+        );
+        assert_eq!(state.provider_pool.live_instances(), 0);
+    }
+
+    #[tokio::test]
+    async fn namespace_quota_spans_mounts() {
+        let state = Arc::new(AppState::new());
+        let ns = state.namespace_manager.get_or_create("default").await;
+        for mount in ["/a", "/b"] {
+            ns.mount_table
+                .mount(mount, "memfs", Arc::new(fs9_core::MemoryFs::new()))
+                .await
+                .unwrap();
+        }
+        let quota = SetQuotaRequest {
+            quota_bytes: Some(10),
+        };
+        let path = || axum::extract::Path("default".to_string());
+        let applied = expect_ok(
+            set_namespace_quota(
+                State(state.clone()),
+                Extension(admin_ctx()),
+                path(),
+                Json(quota),
+            )
+            .await,
+        );
+        assert_eq!(applied.0.quota_bytes, Some(10));
+
+        expect_ok(api_write(&state, "/a/one", b"123456").await);
+        expect_ok(api_write(&state, "/b/two", b"7890").await);
+        let usage =
+            expect_ok(namespace_usage(State(state.clone()), Extension(admin_ctx()), path()).await);
+        assert_eq!(usage.0.used_bytes, 10);
+        assert_eq!(usage.0.quota_bytes, Some(10));
+
+        let Err(err) = api_write(&state, "/b/three", b"x").await else {
+            panic!("write past the quota succeeded");
+        };
+        assert_eq!(
+            err.into_response().status(),
+            StatusCode::INSUFFICIENT_STORAGE
+        );
+        assert_eq!(ns.vfs.stat("/b/three").await.unwrap().size, 0);
+
+        // Removing a file makes room again.
+        let query = PathQuery {
+            path: "/a/one".to_string(),
+        };
+        expect_ok(remove(State(state.clone()), Extension(admin_ctx()), Query(query)).await);
+        assert_eq!(ns.quota.used(), 4);
+        expect_ok(api_write(&state, "/b/three", b"x").await);
+        assert_eq!(ns.quota.used(), 5);
+    }
+
+    #[tokio::test]
+    async fn truncate_resizes_without_a_handle() {
+        let state = Arc::new(AppState::new());
+        let ns = state.namespace_manager.get_or_create("default").await;
+        ns.mount_table
+            .mount("/", "memfs", Arc::new(fs9_core::MemoryFs::new()))
+            .await
+            .unwrap();
+        expect_ok(api_write(&state, "/f.txt", b"hello world").await);
+        ns.quota.set_limit(Some(10));
+        let req = |size| TruncateRequest {
+            path: "/f.txt".to_string(),
+            size,
+        };
+
+        expect_ok(truncate(State(state.clone()), Extension(admin_ctx()), Json(req(5))).await);
+        assert_eq!(ns.vfs.stat("/f.txt").await.unwrap().size, 5);
+        assert_eq!(ns.quota.used(), 5);
+        expect_ok(truncate(State(state.clone()), Extension(admin_ctx()), Json(req(8))).await);
+        assert_eq!(ns.quota.used(), 8);
+
+        let (handle, _) = ns.vfs.open("/f.txt", OpenFlags::read()).await.unwrap();
+        let data = ns.vfs.read(&handle, 0, 64).await.unwrap();
+        ns.vfs.close(handle, false).await.unwrap();
+        assert_eq!(&data[..], b"hello\0\0\0");
+
+        let Err(err) = truncate(State(state.clone()), Extension(admin_ctx()), Json(req(11))).await
+        else {
+            panic!("truncate past the quota succeeded");
+        };
+        assert_eq!(
+            err.into_response().status(),
+            StatusCode::INSUFFICIENT_STORAGE
+        );
+        assert_eq!(ns.vfs.stat("/f.txt").await.unwrap().size, 8);
+    }
+
+    #[tokio::test]
+    async fn revoke_by_id_is_listed() {
+        let state = Arc::new(AppState::new());
+        let request = |token: Option<&str>, jti: Option<&str>| RevokeTokenRequest {
+            token: token.map(str::to_string),
+            jti: jti.map(str::to_string),
+            expires_at: None,
+        };
+
+        for ambiguous in [request(None, None), request(Some("a.b.c"), Some("id"))] {
+            let result = revoke_token(
+                State(state.clone()),
+                Extension(admin_ctx()),
+                Json(ambiguous),
+            )
+            .await;
+            assert!(matches!(result, Err(AppError::BadRequest(_))));
+        }
+
+        let status = expect_ok(
+            revoke_token(
+                State(state.clone()),
+                Extension(admin_ctx()),
+                Json(request(None, Some("token-1"))),
+            )
+            .await,
+        );
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        let listed =
+            expect_ok(list_revoked_tokens(State(state.clone()), Extension(admin_ctx())).await);
+        assert_eq!(listed.0.len(), 1);
+        assert_eq!(listed.0[0].jti, "token-1");
+    }
+
+    #[tokio::test]
+    async fn failing_mount_trips_its_breaker_and_recovers() {
+        use fs9_core::testing::{HookedFs, Op};
+        use std::sync::atomic::Ordering;
+        use std::time::Duration;
+
+        let cooldown = Duration::from_millis(200);
+        let state = Arc::new(
+            AppState::new().with_mount_circuit_breaker(
+                fs9_core::CircuitBreakerConfig::new(Duration::from_secs(60), cooldown)
+                    .with_min_calls(3),
+            ),
+        );
+        let ns = state.namespace_manager.get_or_create("default").await;
+        // Once `down` is set every call but `close` fails, as with an
+        // unreachable remote store.
+        let down = Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let mut flaky = HookedFs::new();
+        for op in [
+            Op::Stat,
+            Op::Wstat,
+            Op::Statfs,
+            Op::Open,
+            Op::Read,
+            Op::Write,
+            Op::Readdir,
+            Op::Remove,
+        ] {
+            let down = down.clone();
+            flaky = flaky.before(op, move |_| {
+                if down.load(Ordering::SeqCst) {
+                    return Err(FsError::backend_unavailable("connection refused"));
+                }
+                Ok(())
+            });
+        }
+        let flaky = Arc::new(flaky);
+        ns.mount_table
+            .mount("/", "memfs", Arc::new(fs9_core::MemoryFs::new()))
+            .await
+            .unwrap();
+        ns.mount_table
+            .mount("/flaky", "flaky", flaky.clone())
+            .await
+            .unwrap();
+        let stat_of = |path: &str| {
+            let query = PathQuery {
+                path: path.to_string(),
+            };
+            stat(State(state.clone()), Extension(admin_ctx()), Query(query))
+        };
+        let status = |result: AppResult<Json<FileInfoResponse>>| match result {
+            Ok(_) => StatusCode::OK,
+            Err(e) => e.into_response().status(),
+        };
+
+        down.store(true, Ordering::SeqCst);
+        for _ in 0..3 {
+            assert_eq!(
+                status(stat_of("/flaky").await),
+                StatusCode::SERVICE_UNAVAILABLE
+            );
+        }
+        assert_eq!(flaky.calls(Op::Stat), 3);
+
+        // Open: rejected without reaching the backend, with a hint of when
+        // to come back.
+        let Err(err) = stat_of("/flaky").await else {
+            panic!("stat through an open breaker succeeded");
+        };
+        let response = err.into_response();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(response.headers()[header::RETRY_AFTER], "1");
+        assert_eq!(flaky.calls(Op::Stat), 3);
+        // Other mounts are unaffected.
+        assert_eq!(status(stat_of("/").await), StatusCode::OK);
+
+        // After the cool-down one probe goes through; once the backend is
+        // back it closes the breaker.
+        down.store(false, Ordering::SeqCst);
+        tokio::time::sleep(cooldown).await;
+        assert_eq!(status(stat_of("/flaky").await), StatusCode::OK);
+        assert_eq!(status(stat_of("/flaky").await), StatusCode::OK);
+        assert_eq!(flaky.calls(Op::Stat), 5);
+        assert_eq!(
+            ns.vfs.circuit_state("/flaky"),
+            fs9_core::CircuitState::Closed
+        );
+    }
+
+    #[tokio::test]
+    async fn event_stream_delivers_changes_under_the_prefix() {
+        let state = Arc::new(AppState::new());
+        let ns = state.namespace_manager.get_or_create("default").await;
+        ns.mount_table
+            .mount("/", "memfs", Arc::new(fs9_core::MemoryFs::new()))
+            .await
+            .unwrap();
+        let (handle, _) = ns.vfs.open("/docs", OpenFlags::create_dir()).await.unwrap();
+        ns.vfs.close(handle, false).await.unwrap();
+
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::ACCEPT,
+            HeaderValue::from_static("text/event-stream"),
+        );
+        let query = EventsQuery {
+            limit: 100,
+            offset: 0,
+            path: Some("/docs".to_string()),
+            event_type: None,
+        };
+        let response = expect_ok(
+            events(
+                State(state.clone()),
+                Extension(admin_ctx()),
+                Query(query),
+                headers,
+            )
+            .await,
+        );
+        assert_eq!(
+            response.headers()[header::CONTENT_TYPE],
+            "text/event-stream"
+        );
+        let mut body = response.into_body().into_data_stream();
+
+        expect_ok(api_write(&state, "/elsewhere.txt", b"skip").await);
+        expect_ok(api_write(&state, "/docs/a.txt", b"hello").await);
+
+        let mut frames = Vec::new();
+        while frames.len() < 2 {
+            let frame = tokio::time::timeout(std::time::Duration::from_secs(5), body.next())
+                .await
+                .expect("no event before the timeout")
+                .unwrap()
+                .unwrap();
+            frames.push(String::from_utf8(frame.to_vec()).unwrap());
+        }
+        assert_eq!(
+            frames,
+            vec![
+                "event: create\ndata: {\"op\":\"create\",\"path\":\"/docs/a.txt\"}\n\n",
+                "event: write\ndata: {\"op\":\"write\",\"path\":\"/docs/a.txt\"}\n\n",
+            ]
+        );
     }
 }
@@ -3,53 +3,102 @@ pub mod models;
 
 use axum::{
     extract::DefaultBodyLimit,
-    routing::{delete, get, post, put},
+    middleware,
+    routing::{any, delete, get, post, put, MethodRouter},
     Router,
 };
+use fs9_server::{body_limit, webdav};
 use std::sync::Arc;
 
 use crate::state::AppState;
 
-fn api_v1_routes(write_body_limit: usize) -> Router<Arc<AppState>> {
+/// Body size limits for the routes that need a different one from the
+/// server-wide `DefaultBodyLimit`.
+#[derive(Debug, Clone, Copy)]
+pub struct BodyLimits {
+    /// Data endpoints, which stream the body into the filesystem.
+    pub write: usize,
+    /// Auth, namespace and mount administration.
+    pub admin: usize,
+}
+
+/// Bound the body `route` streams to `limit` bytes. Routes that buffer the
+/// body through an extractor only need `DefaultBodyLimit`.
+fn limited(route: MethodRouter<Arc<AppState>>, limit: usize) -> MethodRouter<Arc<AppState>> {
+    route.layer(middleware::from_fn_with_state(
+        limit,
+        body_limit::limit_body,
+    ))
+}
+
+fn admin_routes(limit: usize) -> Router<Arc<AppState>> {
     Router::new()
         .route("/auth/refresh", post(handlers::refresh_token))
         .route("/auth/revoke", post(handlers::revoke_token))
+        .route("/auth/revoked", get(handlers::list_revoked_tokens))
         .route(
             "/namespaces",
             post(handlers::create_namespace).get(handlers::list_namespaces),
         )
         .route("/namespaces/{ns}", get(handlers::get_namespace))
+        .route("/namespaces/{ns}/quota", put(handlers::set_namespace_quota))
+        .route("/namespaces/{ns}/usage", get(handlers::namespace_usage))
+        .route(
+            "/mount",
+            post(handlers::mount)
+                .put(handlers::update_mount)
+                .delete(handlers::unmount),
+        )
+        .route("/mounts", get(handlers::list_mounts))
+        .route_layer(DefaultBodyLimit::max(limit))
+}
+
+fn api_v1_routes(limits: BodyLimits) -> Router<Arc<AppState>> {
+    admin_routes(limits.admin)
         .route("/stat", get(handlers::stat))
         .route("/wstat", post(handlers::wstat))
+        .route("/truncate", post(handlers::truncate))
         .route("/statfs", get(handlers::statfs))
         .route("/open", post(handlers::open))
         .route("/read", post(handlers::read))
+        .route("/write", limited(post(handlers::write), limits.write))
         .route(
-            "/write",
-            post(handlers::write).layer(DefaultBodyLimit::max(write_body_limit)),
+            "/download",
+            get(handlers::download).head(handlers::download_head),
         )
-        .route("/download", get(handlers::download))
+        .route("/upload", limited(put(handlers::upload), limits.write))
+        .route("/close", post(handlers::close))
+        .route("/fsync", post(handlers::fsync))
         .route(
-            "/upload",
-            put(handlers::upload).layer(DefaultBodyLimit::max(write_body_limit)),
+            "/xattr",
+            get(handlers::getxattr)
+                .put(handlers::setxattr)
+                .delete(handlers::removexattr),
         )
-        .route("/close", post(handlers::close))
+        .route("/xattrs", get(handlers::listxattr))
         .route("/readdir", get(handlers::readdir))
         .route("/remove", delete(handlers::remove))
         .route("/capabilities", get(handlers::capabilities))
-        .route("/mounts", get(handlers::list_mounts))
         .route("/events", get(handlers::events))
+        .layer(middleware::from_fn(body_limit::json_payload_too_large))
 }
 
 pub fn create_router(
     state: Arc<AppState>,
-    write_body_limit: usize,
+    body_limits: BodyLimits,
     prometheus_handle: Option<metrics_exporter_prometheus::PrometheusHandle>,
 ) -> Router {
-    let v1 = api_v1_routes(write_body_limit);
+    let v1 = api_v1_routes(body_limits);
+    let namespaces = state.namespace_manager.clone();
 
     let mut router = Router::new()
         .route("/health", get(handlers::health))
+        .route(webdav::PREFIX, any(handlers::webdav))
+        .route("/dav/", any(handlers::webdav))
+        .route(
+            "/dav/{*path}",
+            limited(any(handlers::webdav), body_limits.write),
+        )
         .nest("/api/v1", v1.clone())
         .nest("/{tenant_id}/api/v1", v1)
         .with_state(state);
@@ -57,7 +106,8 @@ pub fn create_router(
     if let Some(handle) = prometheus_handle {
         router = router.route(
             "/metrics",
-            get(fs9_server::metrics::metrics_handler).with_state(handle),
+            get(fs9_server::metrics::metrics_handler)
+                .with_state(fs9_server::metrics::MetricsState { handle, namespaces }),
         );
     }
 
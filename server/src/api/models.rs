@@ -64,6 +64,12 @@ pub struct WstatRequest {
     pub changes: StatChangesRequest,
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TruncateRequest {
+    pub path: String,
+    pub size: u64,
+}
+
 #[derive(Debug, Serialize, Deserialize, Default)]
 pub struct StatChangesRequest {
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -82,6 +88,9 @@ pub struct StatChangesRequest {
     pub name: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub symlink_target: Option<String>,
+    /// Seconds until the file expires; 0 clears an existing expiry.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ttl: Option<u64>,
 }
 
 impl From<StatChangesRequest> for fs9_sdk::StatChanges {
@@ -99,6 +108,7 @@ impl From<StatChangesRequest> for fs9_sdk::StatChanges {
                 .map(|t| SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(t)),
             name: req.name,
             symlink_target: req.symlink_target,
+            ttl: req.ttl.map(std::time::Duration::from_secs),
         }
     }
 }
@@ -151,6 +161,10 @@ pub struct OpenFlagsRequest {
     pub append: bool,
     #[serde(default)]
     pub directory: bool,
+    #[serde(default)]
+    pub symlink: bool,
+    #[serde(default)]
+    pub exclusive: bool,
 }
 
 impl From<OpenFlagsRequest> for OpenFlags {
@@ -162,6 +176,8 @@ impl From<OpenFlagsRequest> for OpenFlags {
             truncate: req.truncate,
             append: req.append,
             directory: req.directory,
+            symlink: req.symlink,
+            exclusive: req.exclusive,
         }
     }
 }
@@ -197,6 +213,13 @@ pub struct CloseRequest {
     pub sync: bool,
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FsyncRequest {
+    pub handle_id: String,
+    #[serde(default)]
+    pub data_only: bool,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ReaddirRequest {
     pub path: String,
@@ -221,8 +244,9 @@ pub struct CapabilitiesResponse {
 #[derive(Debug, Serialize, Deserialize)]
 pub struct MountRequest {
     pub path: String,
-    #[serde(rename = "type")]
-    pub provider_type: String,
+    #[serde(alias = "type")]
+    pub provider: String,
+    #[serde(default)]
     pub config: serde_json::Value,
 }
 
@@ -230,6 +254,19 @@ pub struct MountRequest {
 pub struct MountResponse {
     pub path: String,
     pub provider_name: String,
+    #[serde(default)]
+    pub read_only: bool,
+}
+
+/// New configuration for an existing mount; the provider is kept unless
+/// another one is named.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UpdateMountRequest {
+    pub path: String,
+    #[serde(default)]
+    pub provider: Option<String>,
+    #[serde(default)]
+    pub config: serde_json::Value,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -259,6 +296,21 @@ pub struct NamespaceInfoResponse {
     pub status: String,
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SetQuotaRequest {
+    /// `None` removes the limit.
+    pub quota_bytes: Option<u64>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct NamespaceUsageResponse {
+    pub name: String,
+    /// Growth since this server loaded the namespace, not the size of what
+    /// is stored.
+    pub used_bytes: u64,
+    pub quota_bytes: Option<u64>,
+}
+
 // ============================================================================
 // Auth models
 // ============================================================================
@@ -269,9 +321,22 @@ pub struct RefreshTokenResponse {
     pub expires_in: u64,
 }
 
+/// Either a whole token or a bare token id (`jti`). A bare id without
+/// `expires_at` stays revoked for the server's revocation retention.
 #[derive(Debug, Serialize, Deserialize)]
 pub struct RevokeTokenRequest {
-    pub token: String,
+    #[serde(default)]
+    pub token: Option<String>,
+    #[serde(default)]
+    pub jti: Option<String>,
+    #[serde(default)]
+    pub expires_at: Option<u64>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RevokedTokenResponse {
+    pub jti: String,
+    pub expires_at: u64,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
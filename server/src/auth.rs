@@ -6,6 +6,7 @@ use axum::{
     response::{IntoResponse, Response},
     Json,
 };
+use jsonwebtoken::errors::ErrorKind;
 use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, Validation};
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
@@ -35,6 +36,41 @@ pub struct Claims {
     pub permissions: Vec<String>,
     #[serde(default)]
     pub mounts: Vec<String>,
+    /// Path-prefix grants issued by fs9-meta; empty means unrestricted.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub scopes: Vec<PathGrant>,
+    /// Token id, the handle used to revoke this token before it expires.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub jti: Option<String>,
+}
+
+/// Access level of a [`PathGrant`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Access {
+    #[serde(rename = "r")]
+    Read,
+    #[serde(rename = "rw")]
+    ReadWrite,
+}
+
+/// Access to everything under a path prefix, e.g.
+/// `{"prefix":"/uploads","access":"rw"}`. Mirrors fs9-meta's grant model.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PathGrant {
+    pub prefix: String,
+    pub access: Access,
+}
+
+impl PathGrant {
+    /// Whether the normalized `path` is the prefix itself or lies below it.
+    fn covers(&self, path: &str) -> bool {
+        let prefix = self.prefix.trim_end_matches('/');
+        prefix.is_empty()
+            || path == prefix
+            || path
+                .strip_prefix(prefix)
+                .is_some_and(|rest| rest.starts_with('/'))
+    }
 }
 
 /// Context extracted from JWT and carried through the entire request.
@@ -43,6 +79,27 @@ pub struct RequestContext {
     pub ns: String,
     pub user_id: String,
     pub roles: Vec<String>,
+    /// Path-prefix grants; empty means the roles alone decide.
+    pub scopes: Vec<PathGrant>,
+}
+
+impl RequestContext {
+    /// Whether the caller may access `path` at the given level. Without
+    /// grants everything is allowed; with grants, only paths covered by a
+    /// grant of sufficient access are (deny by default). The path is
+    /// normalized first, so grants cannot be escaped with paths like
+    /// `/uploads/../secrets`; paths climbing above the root are denied.
+    pub fn can_access_path(&self, path: &str, access: Access) -> bool {
+        if self.scopes.is_empty() {
+            return true;
+        }
+        let Ok(path) = fs9_sdk::normalize_path(path) else {
+            return false;
+        };
+        self.scopes.iter().any(|grant| {
+            grant.covers(&path) && (access == Access::Read || grant.access == Access::ReadWrite)
+        })
+    }
 }
 
 impl Claims {
@@ -65,6 +122,8 @@ impl Claims {
             roles: Vec::new(),
             permissions,
             mounts,
+            scopes: Vec::new(),
+            jti: Some(uuid::Uuid::new_v4().to_string()),
         }
     }
 
@@ -82,6 +141,8 @@ impl Claims {
             roles,
             permissions: Vec::new(),
             mounts: Vec::new(),
+            scopes: Vec::new(),
+            jti: Some(uuid::Uuid::new_v4().to_string()),
         }
     }
 
@@ -113,6 +174,10 @@ impl Claims {
 #[derive(Clone)]
 pub struct JwtConfig {
     pub secret: String,
+    /// Secrets in use before `secret` was rotated in. New tokens are only
+    /// signed with `secret`, but tokens signed with one of these keep
+    /// verifying until they expire.
+    pub previous_secrets: Vec<String>,
     pub issuer: Option<String>,
     pub audience: Option<String>,
 }
@@ -121,11 +186,27 @@ impl JwtConfig {
     pub fn new(secret: impl Into<String>) -> Self {
         Self {
             secret: secret.into(),
+            previous_secrets: Vec::new(),
             issuer: None,
             audience: None,
         }
     }
 
+    /// Also verify tokens signed with `secrets`; empty ones are ignored.
+    #[must_use]
+    pub fn with_previous_secrets<I, S>(mut self, secrets: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.previous_secrets = secrets
+            .into_iter()
+            .map(Into::into)
+            .filter(|secret| !secret.is_empty())
+            .collect();
+        self
+    }
+
     pub fn with_issuer(mut self, issuer: impl Into<String>) -> Self {
         self.issuer = Some(issuer.into());
         self
@@ -144,8 +225,9 @@ impl JwtConfig {
         )
     }
 
-    pub fn decode(&self, token: &str) -> Result<Claims, jsonwebtoken::errors::Error> {
+    fn validation(&self, validate_exp: bool) -> Validation {
         let mut validation = Validation::default();
+        validation.validate_exp = validate_exp;
 
         if let Some(issuer) = &self.issuer {
             validation.set_issuer(&[issuer]);
@@ -155,69 +237,62 @@ impl JwtConfig {
             validation.set_audience(&[audience]);
         }
 
-        let token_data = decode::<Claims>(
+        validation
+    }
+
+    /// Verify `token` against the current secret, then against each
+    /// previous one. Only a signature mismatch moves on to the next key:
+    /// any other failure means the key matched and the token itself is bad.
+    fn verify(
+        &self,
+        token: &str,
+        validation: &Validation,
+    ) -> Result<Claims, jsonwebtoken::errors::Error> {
+        let mut result = decode::<Claims>(
             token,
             &DecodingKey::from_secret(self.secret.as_bytes()),
-            &validation,
-        )?;
+            validation,
+        );
+        for secret in &self.previous_secrets {
+            match &result {
+                Err(e) if *e.kind() == ErrorKind::InvalidSignature => {}
+                _ => break,
+            }
+            result = decode::<Claims>(
+                token,
+                &DecodingKey::from_secret(secret.as_bytes()),
+                validation,
+            );
+        }
+        Ok(result?.claims)
+    }
 
-        Ok(token_data.claims)
+    pub fn decode(&self, token: &str) -> Result<Claims, jsonwebtoken::errors::Error> {
+        self.verify(token, &self.validation(true))
     }
 
     /// Decode a token while ignoring `exp` validation (signature still verified).
     pub fn decode_ignore_exp(&self, token: &str) -> Result<Claims, jsonwebtoken::errors::Error> {
-        let mut validation = Validation::default();
-        validation.validate_exp = false;
-
-        if let Some(issuer) = &self.issuer {
-            validation.set_issuer(&[issuer]);
-        }
-
-        if let Some(audience) = &self.audience {
-            validation.set_audience(&[audience]);
-        }
-
-        let token_data = decode::<Claims>(
-            token,
-            &DecodingKey::from_secret(self.secret.as_bytes()),
-            &validation,
-        )?;
-
-        Ok(token_data.claims)
+        self.verify(token, &self.validation(false))
     }
 
     /// Decode a token, allowing expired tokens (for refresh endpoint).
     /// Still validates the signature, just ignores expiration.
     pub fn decode_allow_expired(&self, token: &str) -> Result<Claims, jsonwebtoken::errors::Error> {
-        let mut validation = Validation::default();
-        validation.validate_exp = false; // Allow expired tokens
-
-        if let Some(issuer) = &self.issuer {
-            validation.set_issuer(&[issuer]);
-        }
-
-        if let Some(audience) = &self.audience {
-            validation.set_audience(&[audience]);
-        }
-
-        let token_data = decode::<Claims>(
-            token,
-            &DecodingKey::from_secret(self.secret.as_bytes()),
-            &validation,
-        )?;
+        let claims = self.verify(token, &self.validation(false))?;
 
         let now = SystemTime::now()
             .duration_since(UNIX_EPOCH)
             .unwrap()
             .as_secs();
         let grace_period = 4 * 60 * 60; // 4 hours
-        if token_data.claims.exp + grace_period < now {
+        if claims.exp + grace_period < now {
             return Err(jsonwebtoken::errors::Error::from(
-                jsonwebtoken::errors::ErrorKind::ExpiredSignature,
+                ErrorKind::ExpiredSignature,
             ));
         }
 
-        Ok(token_data.claims)
+        Ok(claims)
     }
 }
 
@@ -238,14 +313,62 @@ impl AuthState {
 pub struct AuthMiddlewareState {
     pub auth: AuthState,
     pub app_state: Arc<AppState>,
+    /// Static bearer token that alone grants access to `/metrics`.
+    pub metrics_key: Option<String>,
 }
 
 impl AuthMiddlewareState {
     pub fn new(auth: AuthState, app_state: Arc<AppState>) -> Self {
-        Self { auth, app_state }
+        Self {
+            auth,
+            app_state,
+            metrics_key: None,
+        }
+    }
+
+    pub fn with_metrics_key(mut self, key: Option<String>) -> Self {
+        self.metrics_key = key;
+        self
     }
 }
 
+/// The password of an `Authorization: Basic` header. WebDAV clients such as
+/// Finder cannot send bearer tokens, so under `/dav` the token is accepted as
+/// the password and the user name is ignored.
+fn basic_auth_password(value: &str) -> Option<String> {
+    use base64::Engine;
+
+    let encoded = value.strip_prefix("Basic ")?;
+    let decoded = base64::engine::general_purpose::STANDARD
+        .decode(encoded.trim())
+        .ok()?;
+    let credentials = String::from_utf8(decoded).ok()?;
+    let (_, password) = credentials.split_once(':')?;
+    Some(password.to_string())
+}
+
+/// Whether the request carries `Authorization: Bearer <key>`.
+fn has_bearer_key(request: &Request<Body>, key: &str) -> bool {
+    request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .is_some_and(|token| token == key)
+}
+
+/// Prefix of API keys issued by fs9-meta; bearer tokens with it are resolved
+/// as API keys rather than decoded as JWTs.
+pub const API_KEY_PREFIX: &str = "fs9_";
+
+/// Header carrying an API key as an alternative to `Authorization: Bearer`.
+pub const API_KEY_HEADER: &str = "x-fs9-api-key";
+
+/// How long a validated API key is trusted before fs9-meta is asked again.
+/// This bounds how long a key revoked in fs9-meta keeps working here;
+/// revoking it through `/api/v1/auth/revoke` takes effect immediately.
+const API_KEY_CACHE_SECS: u64 = 60;
+
 /// Extract tenant_id from URL paths like `/{tenant_id}/api/v1/...`.
 fn extract_tenant_id(path: &str) -> Option<&str> {
     let path = path.strip_prefix('/')?;
@@ -270,16 +393,28 @@ pub async fn auth_middleware(
             ns: crate::namespace::DEFAULT_NAMESPACE.to_string(),
             user_id: "anonymous".to_string(),
             roles: Vec::new(),
+            scopes: Vec::new(),
         });
         return next.run(request).await;
     }
 
+    // Prometheus scrapers authenticate with a static key instead of a JWT.
+    if path == "/metrics" {
+        if let Some(key) = &state.metrics_key {
+            if !has_bearer_key(&request, key) {
+                return unauthorized("Invalid or missing metrics key");
+            }
+            return next.run(request).await;
+        }
+    }
+
     // Auth is disabled: allow all requests and run as anonymous in the default namespace.
     if !state.auth.enabled {
         request.extensions_mut().insert(RequestContext {
             ns: crate::namespace::DEFAULT_NAMESPACE.to_string(),
             user_id: "anonymous".to_string(),
             roles: vec!["admin".to_string()],
+            scopes: Vec::new(),
         });
         return next.run(request).await;
     }
@@ -290,8 +425,18 @@ pub async fn auth_middleware(
         return next.run(request).await;
     }
 
+    let api_key = request
+        .headers()
+        .get(API_KEY_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_owned);
+    if let Some(key) = api_key {
+        return authenticate_api_key(&state, &key, request, next).await;
+    }
+
     let auth_header = request.headers().get(header::AUTHORIZATION);
 
+    let is_dav = path == crate::webdav::PREFIX || path.starts_with("/dav/");
     let token = match auth_header {
         Some(value) => {
             let value = match value.to_str() {
@@ -299,14 +444,23 @@ pub async fn auth_middleware(
                 Err(_) => return unauthorized("Invalid Authorization header"),
             };
 
-            if !value.starts_with("Bearer ") {
+            if let Some(token) = value.strip_prefix("Bearer ") {
+                token.to_string()
+            } else if let Some(token) = basic_auth_password(value).filter(|_| is_dav) {
+                token
+            } else {
                 return unauthorized("Authorization header must use Bearer scheme");
             }
-
-            &value[7..]
         }
+        // WebDAV clients only send credentials after a Basic challenge.
+        None if is_dav => return basic_challenge(),
         None => return unauthorized("Missing Authorization header"),
     };
+    let token = token.as_str();
+
+    if token.starts_with(API_KEY_PREFIX) {
+        return authenticate_api_key(&state, token, request, next).await;
+    }
 
     // Try db9 token auth if tenant_id is in the URL and db9_client is configured
     let tenant_id = extract_tenant_id(&path);
@@ -317,6 +471,7 @@ pub async fn auth_middleware(
                     ns: tenant_id.to_string(),
                     user_id: customer_id,
                     roles: vec!["admin".to_string()],
+                    scopes: Vec::new(),
                 };
                 request.extensions_mut().insert(ctx);
                 return next.run(request).await;
@@ -351,6 +506,7 @@ pub async fn auth_middleware(
             ns: cached.namespace.clone(),
             user_id: cached.user_id.clone(),
             roles: cached.roles.clone(),
+            scopes: cached.scopes.clone(),
         };
         request.extensions_mut().insert(ctx);
         // Insert a Claims-like view without extending the token lifetime.
@@ -366,6 +522,8 @@ pub async fn auth_middleware(
             roles: cached.roles.clone(),
             permissions: Vec::new(),
             mounts: Vec::new(),
+            scopes: cached.scopes.clone(),
+            jti: None,
         };
         request.extensions_mut().insert(claims);
         return next.run(request).await;
@@ -387,6 +545,7 @@ pub async fn auth_middleware(
                     .clone()
                     .unwrap_or_else(|| "unknown".to_string());
                 let roles = resp.roles.clone();
+                let scopes = resp.scopes.clone();
 
                 let now = SystemTime::now()
                     .duration_since(UNIX_EPOCH)
@@ -407,6 +566,7 @@ pub async fn auth_middleware(
                             user_id.clone(),
                             namespace.clone(),
                             roles.clone(),
+                            scopes.clone(),
                             claims.exp,
                         )
                         .await;
@@ -416,6 +576,7 @@ pub async fn auth_middleware(
                     ns: namespace.clone(),
                     user_id: user_id.clone(),
                     roles: roles.clone(),
+                    scopes: scopes.clone(),
                 };
                 request.extensions_mut().insert(ctx);
                 // Insert a Claims-like view without extending the token lifetime.
@@ -428,6 +589,8 @@ pub async fn auth_middleware(
                     roles,
                     permissions: Vec::new(),
                     mounts: Vec::new(),
+                    scopes,
+                    jti: token_claims.and_then(|c| c.jti),
                 };
                 request.extensions_mut().insert(claims);
                 return next.run(request).await;
@@ -469,6 +632,7 @@ pub async fn auth_middleware(
                     claims.sub.clone(),
                     ns.clone(),
                     claims.roles.clone(),
+                    claims.scopes.clone(),
                     claims.exp,
                 )
                 .await;
@@ -477,6 +641,7 @@ pub async fn auth_middleware(
                 ns,
                 user_id: claims.sub.clone(),
                 roles: claims.roles.clone(),
+                scopes: claims.scopes.clone(),
             };
             request.extensions_mut().insert(ctx);
             request.extensions_mut().insert(claims);
@@ -486,6 +651,93 @@ pub async fn auth_middleware(
     }
 }
 
+/// Authenticate with a long-lived API key, resolved through fs9-meta into a
+/// user, namespace and role set and cached in the token cache.
+async fn authenticate_api_key(
+    state: &AuthMiddlewareState,
+    key: &str,
+    mut request: Request<Body>,
+    next: Next,
+) -> Response {
+    let app_state = &state.app_state;
+    if app_state.revocation_set.is_revoked(key).await {
+        return unauthorized("API key has been revoked");
+    }
+
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    let (user_id, namespace, roles, scopes, expires_at) =
+        if let Some(cached) = app_state.token_cache.get(key).await {
+            (
+                cached.user_id,
+                cached.namespace,
+                cached.roles,
+                cached.scopes,
+                cached.expires_at,
+            )
+        } else {
+            let Some(meta_client) = &app_state.meta_client else {
+                return unauthorized("API keys require the meta service");
+            };
+            match meta_client
+                .validate_api_key_with_circuit_breaker(key, &app_state.circuit_breaker, 3, 100)
+                .await
+            {
+                Ok(resp) if resp.valid => {
+                    let namespace = resp
+                        .namespace
+                        .unwrap_or_else(|| crate::namespace::DEFAULT_NAMESPACE.to_string());
+                    let user_id = resp.user_id.unwrap_or_else(|| "unknown".to_string());
+                    let expires_at = now + API_KEY_CACHE_SECS;
+                    app_state
+                        .token_cache
+                        .set(
+                            key,
+                            user_id.clone(),
+                            namespace.clone(),
+                            resp.roles.clone(),
+                            resp.scopes.clone(),
+                            expires_at,
+                        )
+                        .await;
+                    (user_id, namespace, resp.roles, resp.scopes, expires_at)
+                }
+                Ok(resp) => {
+                    return unauthorized(&format!(
+                        "Invalid API key: {}",
+                        resp.error
+                            .unwrap_or_else(|| "validation failed".to_string())
+                    ));
+                }
+                Err(e) => {
+                    tracing::warn!(error = %e, "Meta service unavailable, cannot validate API key");
+                    return service_unavailable("API key validation unavailable");
+                }
+            }
+        };
+
+    request.extensions_mut().insert(RequestContext {
+        ns: namespace.clone(),
+        user_id: user_id.clone(),
+        roles: roles.clone(),
+        scopes: scopes.clone(),
+    });
+    request.extensions_mut().insert(Claims {
+        sub: user_id,
+        exp: expires_at,
+        iat: now,
+        ns: Some(namespace),
+        roles,
+        permissions: Vec::new(),
+        mounts: Vec::new(),
+        scopes,
+        jti: None,
+    });
+    next.run(request).await
+}
+
 fn unauthorized(message: &str) -> Response {
     (
         StatusCode::UNAUTHORIZED,
@@ -497,6 +749,15 @@ fn unauthorized(message: &str) -> Response {
         .into_response()
 }
 
+fn basic_challenge() -> Response {
+    let mut response = unauthorized("Missing Authorization header");
+    response.headers_mut().insert(
+        header::WWW_AUTHENTICATE,
+        header::HeaderValue::from_static("Basic realm=\"fs9\""),
+    );
+    response
+}
+
 fn forbidden(message: &str) -> Response {
     (
         StatusCode::FORBIDDEN,
@@ -519,10 +780,56 @@ fn too_many_requests(message: &str) -> Response {
         .into_response()
 }
 
+fn service_unavailable(message: &str) -> Response {
+    (
+        StatusCode::SERVICE_UNAVAILABLE,
+        Json(ErrorResponse {
+            error: message.to_string(),
+            code: 503,
+        }),
+    )
+        .into_response()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn metrics_key_must_match_bearer_token() {
+        let request = |auth: Option<&str>| {
+            let mut builder = Request::builder().uri("/metrics");
+            if let Some(auth) = auth {
+                builder = builder.header(header::AUTHORIZATION, auth);
+            }
+            builder.body(Body::empty()).unwrap()
+        };
+
+        assert!(has_bearer_key(
+            &request(Some("Bearer scrape-key")),
+            "scrape-key"
+        ));
+        assert!(!has_bearer_key(
+            &request(Some("Bearer other")),
+            "scrape-key"
+        ));
+        assert!(!has_bearer_key(&request(Some("scrape-key")), "scrape-key"));
+        assert!(!has_bearer_key(&request(None), "scrape-key"));
+    }
+
+    #[test]
+    fn basic_auth_password_is_the_token() {
+        use base64::Engine;
+
+        let encoded = base64::engine::general_purpose::STANDARD.encode("alice:jwt.token.here");
+        assert_eq!(
+            basic_auth_password(&format!("Basic {encoded}")).as_deref(),
+            Some("jwt.token.here")
+        );
+        assert_eq!(basic_auth_password("Bearer abc"), None);
+        assert_eq!(basic_auth_password("Basic not-base64!"), None);
+    }
+
     #[test]
     fn create_and_verify_token() {
         let config = JwtConfig::new("test-secret-key-12345");
@@ -585,6 +892,89 @@ mod tests {
         assert!(claims.can_access_mount("/anything"));
     }
 
+    fn scoped_ctx() -> RequestContext {
+        RequestContext {
+            ns: "default".to_string(),
+            user_id: "u".to_string(),
+            roles: vec!["read".to_string()],
+            scopes: vec![
+                PathGrant {
+                    prefix: "/uploads".to_string(),
+                    access: Access::ReadWrite,
+                },
+                PathGrant {
+                    prefix: "/docs/".to_string(),
+                    access: Access::Read,
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn path_grants_deny_by_default() {
+        let ctx = scoped_ctx();
+        assert!(ctx.can_access_path("/uploads", Access::ReadWrite));
+        assert!(ctx.can_access_path("/uploads/a/b.txt", Access::ReadWrite));
+        assert!(ctx.can_access_path("/docs/readme", Access::Read));
+        assert!(!ctx.can_access_path("/docs/readme", Access::ReadWrite));
+        assert!(!ctx.can_access_path("/uploadsX/a", Access::Read));
+        assert!(!ctx.can_access_path("/other", Access::Read));
+    }
+
+    #[test]
+    fn path_grants_cannot_be_escaped() {
+        let ctx = scoped_ctx();
+        assert!(!ctx.can_access_path("/uploads/../secret", Access::Read));
+        assert!(!ctx.can_access_path("/../uploads/x", Access::Read));
+        assert!(ctx.can_access_path("//uploads/./x", Access::ReadWrite));
+    }
+
+    #[test]
+    fn no_grants_allows_everything() {
+        let ctx = RequestContext {
+            scopes: Vec::new(),
+            ..scoped_ctx()
+        };
+        assert!(ctx.can_access_path("/anything", Access::ReadWrite));
+    }
+
+    #[test]
+    fn rotated_secret_still_verifies_old_tokens() {
+        let claims = Claims::new("user", vec!["read".into()], vec![], 3600);
+        let old_token = JwtConfig::new("old-secret").encode(&claims).unwrap();
+
+        let rotated = JwtConfig::new("new-secret").with_previous_secrets(["old-secret"]);
+        assert_eq!(rotated.decode(&old_token).unwrap().sub, "user");
+        assert_eq!(
+            rotated.decode_allow_expired(&old_token).unwrap().sub,
+            "user"
+        );
+
+        let new_token = rotated.encode(&claims).unwrap();
+        assert!(JwtConfig::new("new-secret").decode(&new_token).is_ok());
+        assert!(JwtConfig::new("old-secret").decode(&new_token).is_err());
+
+        assert!(JwtConfig::new("new-secret").decode(&old_token).is_err());
+        let other = JwtConfig::new("other").with_previous_secrets(["another"]);
+        assert_eq!(
+            *other.decode(&old_token).unwrap_err().kind(),
+            ErrorKind::InvalidSignature
+        );
+    }
+
+    #[test]
+    fn expired_token_signed_with_previous_secret_is_rejected() {
+        let mut claims = Claims::new("user", vec![], vec![], 0);
+        claims.exp = claims.iat - 100;
+        let token = JwtConfig::new("old-secret").encode(&claims).unwrap();
+
+        let rotated = JwtConfig::new("new-secret").with_previous_secrets(["old-secret"]);
+        assert_eq!(
+            *rotated.decode(&token).unwrap_err().kind(),
+            ErrorKind::ExpiredSignature
+        );
+    }
+
     #[test]
     fn expired_token_rejected() {
         let config = JwtConfig::new("test-secret");
@@ -0,0 +1,202 @@
+//! Per-route request body limits.
+//!
+//! `DefaultBodyLimit` only bounds extractors that buffer the body, such as
+//! `Json`. The data routes stream the raw body into the filesystem, so
+//! [`limit_body`] enforces their limit itself: a declared `Content-Length`
+//! over the limit is refused before anything is read, and a chunked body is
+//! counted as it streams and fails with [`BodyTooLarge`] once it goes over.
+//! Either way the client gets a `413 Payload Too Large` carrying the usual
+//! JSON error body.
+
+use axum::{
+    body::Body,
+    extract::{Request, State},
+    http::{header, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use futures::StreamExt;
+
+/// JSON error body, matching the one the API handlers return.
+#[derive(Debug, serde::Serialize)]
+struct ErrorResponse {
+    error: String,
+    code: u16,
+}
+
+/// The error a limited body yields once more than `limit` bytes have
+/// arrived.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+#[error("request body is larger than {limit} bytes")]
+pub struct BodyTooLarge {
+    pub limit: usize,
+}
+
+impl BodyTooLarge {
+    /// The limit `err` reports, if it came from a body over its limit.
+    #[must_use]
+    pub fn from_body_error(err: &axum::Error) -> Option<Self> {
+        let mut source = std::error::Error::source(err);
+        while let Some(err) = source {
+            if let Some(too_large) = err.downcast_ref::<Self>() {
+                return Some(*too_large);
+            }
+            source = err.source();
+        }
+        None
+    }
+}
+
+impl IntoResponse for BodyTooLarge {
+    fn into_response(self) -> Response {
+        payload_too_large(self.to_string())
+    }
+}
+
+fn payload_too_large(error: String) -> Response {
+    (
+        StatusCode::PAYLOAD_TOO_LARGE,
+        Json(ErrorResponse { error, code: 413 }),
+    )
+        .into_response()
+}
+
+/// Middleware refusing request bodies over `limit` bytes. Use it with
+/// `middleware::from_fn_with_state(limit, limit_body)`.
+pub async fn limit_body(State(limit): State<usize>, request: Request, next: Next) -> Response {
+    let declared = request
+        .headers()
+        .get(header::CONTENT_LENGTH)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<u64>().ok());
+    if declared.is_some_and(|len| len > limit as u64) {
+        return BodyTooLarge { limit }.into_response();
+    }
+
+    let (parts, body) = request.into_parts();
+    let mut seen = 0usize;
+    let body = body.into_data_stream().map(move |chunk| {
+        let chunk = chunk?;
+        seen = seen.saturating_add(chunk.len());
+        if seen > limit {
+            return Err(axum::Error::new(BodyTooLarge { limit }));
+        }
+        Ok(chunk)
+    });
+    next.run(Request::from_parts(parts, Body::from_stream(body)))
+        .await
+}
+
+/// Middleware giving `413` responses that are not already JSON, such as
+/// the plain-text rejection of a `Json` extractor over its
+/// `DefaultBodyLimit`, the JSON error body.
+pub async fn json_payload_too_large(request: Request, next: Next) -> Response {
+    let response = next.run(request).await;
+    if response.status() != StatusCode::PAYLOAD_TOO_LARGE {
+        return response;
+    }
+    let is_json = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .is_some_and(|value| value.as_bytes().starts_with(b"application/json"));
+    if is_json {
+        return response;
+    }
+    payload_too_large("request body is too large".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{middleware, routing::post, Router};
+    use bytes::Bytes;
+    use tower::Service;
+
+    fn app(limit: usize) -> Router {
+        async fn count(body: Body) -> Response {
+            let mut total = 0;
+            let mut stream = body.into_data_stream();
+            while let Some(chunk) = stream.next().await {
+                match chunk {
+                    Ok(chunk) => total += chunk.len(),
+                    Err(e) => {
+                        return BodyTooLarge::from_body_error(&e).map_or_else(
+                            || StatusCode::BAD_REQUEST.into_response(),
+                            IntoResponse::into_response,
+                        )
+                    }
+                }
+            }
+            total.to_string().into_response()
+        }
+
+        async fn json(Json(value): Json<serde_json::Value>) -> Json<serde_json::Value> {
+            Json(value)
+        }
+
+        Router::new()
+            .route("/stream", post(count))
+            .route("/json", post(json))
+            .route_layer(middleware::from_fn_with_state(limit, limit_body))
+            .layer(middleware::from_fn(json_payload_too_large))
+    }
+
+    fn chunked(chunks: &[&'static [u8]]) -> Body {
+        let chunks: Vec<Result<Bytes, std::io::Error>> =
+            chunks.iter().map(|c| Ok(Bytes::from_static(c))).collect();
+        Body::from_stream(futures::stream::iter(chunks))
+    }
+
+    async fn post_to(app: &mut Router, path: &str, body: Body) -> (StatusCode, serde_json::Value) {
+        let request = Request::post(path)
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(body)
+            .unwrap();
+        let response = app.call(request).await.unwrap();
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (
+            status,
+            serde_json::from_slice(&body).unwrap_or(serde_json::Value::Null),
+        )
+    }
+
+    #[tokio::test]
+    async fn declared_length_over_the_limit_is_refused_up_front() {
+        let mut app = app(8);
+        let request = Request::post("/stream")
+            .header(header::CONTENT_LENGTH, "9")
+            .body(Body::from("123456789"))
+            .unwrap();
+        let response = app.call(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+
+        let (status, body) = post_to(&mut app, "/stream", Body::from("12345678")).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, 8);
+    }
+
+    #[tokio::test]
+    async fn chunked_body_fails_once_it_goes_over() {
+        let mut app = app(8);
+        let (status, body) = post_to(&mut app, "/stream", chunked(&[b"1234", b"5678"])).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, 8);
+
+        let (status, body) = post_to(&mut app, "/stream", chunked(&[b"1234", b"5678", b"9"])).await;
+        assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+        assert_eq!(body["code"], 413);
+        assert_eq!(body["error"], "request body is larger than 8 bytes");
+    }
+
+    #[tokio::test]
+    async fn json_extractor_rejection_gets_a_json_body() {
+        let mut app = app(1024).layer(axum::extract::DefaultBodyLimit::max(8));
+        let (status, body) = post_to(&mut app, "/json", chunked(&[b"[1, 2, 3, ", b"4]"])).await;
+        assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+        assert_eq!(body["code"], 413);
+    }
+}
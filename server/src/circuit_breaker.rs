@@ -1,3 +1,4 @@
+use fs9_sdk::FsError;
 use std::sync::atomic::{AtomicU32, Ordering};
 use std::time::{Duration, Instant};
 use tokio::sync::RwLock;
@@ -70,10 +71,27 @@ impl CircuitBreaker {
     }
 }
 
+/// Whole seconds for a `Retry-After` header when `err` comes from an open
+/// mount breaker, rounded up so clients never retry early.
+#[must_use]
+pub fn retry_after_secs(err: &FsError) -> Option<u64> {
+    let wait = err.retry_after()?;
+    Some((wait.as_secs() + u64::from(wait.subsec_nanos() > 0)).max(1))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn retry_after_rounds_up() {
+        let open = |ms| FsError::circuit_breaker_open("/data", Duration::from_millis(ms));
+        assert_eq!(retry_after_secs(&open(1500)), Some(2));
+        assert_eq!(retry_after_secs(&open(2000)), Some(2));
+        assert_eq!(retry_after_secs(&open(0)), Some(1));
+        assert_eq!(retry_after_secs(&FsError::transient("busy")), None);
+    }
+
     #[tokio::test]
     async fn starts_closed() {
         let cb = CircuitBreaker::new(3, Duration::from_secs(30));
@@ -0,0 +1,295 @@
+//! HTTP conditional requests: entity tags for files, and the
+//! `If-None-Match` and `If-Match` checks made against them.
+
+use axum::http::{header, HeaderMap};
+use fs9_sdk::{
+    is_weak_etag, metadata_etag, Capabilities, FileInfo, FsProvider, FsResult, OpenFlags,
+};
+use sha2::{Digest, Sha256};
+
+const HASH_CHUNK_SIZE: usize = 256 * 1024;
+
+/// The quoted entity tag for an `ETag` header, from `stat` alone: the
+/// provider's etag, weak or strong, or a weak one made from the file's size
+/// and modification time if the provider tracks none.
+#[must_use]
+pub fn entity_tag(info: &FileInfo) -> String {
+    let etag = if info.etag.is_empty() {
+        metadata_etag(info.size, info.mtime)
+    } else {
+        info.etag.clone()
+    };
+    match etag.strip_prefix("W/") {
+        Some(weak) => format!("W/\"{weak}\""),
+        None => format!("\"{etag}\""),
+    }
+}
+
+/// The quoted strong entity tag of the file at `path`, for comparing with
+/// `If-Match`, or `None` if it has none.
+///
+/// Files whose provider tracks no etag, or only a weak one, are tagged with
+/// a hash of their first `info.size` bytes, which costs reading them.
+/// Streams and other files whose reads consume data have no strong tag;
+/// `capabilities` are those of the provider serving `path`.
+pub async fn strong_entity_tag(
+    vfs: &dyn FsProvider,
+    path: &str,
+    info: &FileInfo,
+    capabilities: Capabilities,
+) -> FsResult<Option<String>> {
+    if !info.etag.is_empty() && !is_weak_etag(&info.etag) {
+        return Ok(Some(format!("\"{}\"", info.etag)));
+    }
+    if capabilities.intersects(Capabilities::STREAMING | Capabilities::STATEFUL_READ) {
+        return Ok(None);
+    }
+
+    let (handle, _) = vfs.open(path, OpenFlags::read()).await?;
+    let mut hasher = Sha256::new();
+    let mut offset = 0u64;
+    let read_all = loop {
+        if offset >= info.size {
+            break Ok(());
+        }
+        let want = usize::try_from(info.size - offset)
+            .map_or(HASH_CHUNK_SIZE, |left| left.min(HASH_CHUNK_SIZE));
+        match vfs.read(&handle, offset, want).await {
+            Ok(chunk) if chunk.is_empty() => break Ok(()),
+            Ok(chunk) => {
+                let take = chunk.len().min(want);
+                hasher.update(&chunk[..take]);
+                offset += take as u64;
+            }
+            Err(e) => break Err(e),
+        }
+    };
+    let _ = vfs.close(handle, false).await;
+    read_all?;
+
+    let digest = hasher.finalize();
+    let hex: String = digest[..16].iter().map(|b| format!("{b:02x}")).collect();
+    Ok(Some(format!("\"sha256-{hex}\"")))
+}
+
+/// The entity tags listed in `name`, or `None` without the header. `*` is
+/// returned as is.
+fn listed_tags<'a>(headers: &'a HeaderMap, name: &header::HeaderName) -> Option<Vec<&'a str>> {
+    let mut values = headers.get_all(name).iter().peekable();
+    values.peek()?;
+    Some(
+        values
+            .filter_map(|v| v.to_str().ok())
+            .flat_map(|v| v.split(','))
+            .map(str::trim)
+            .filter(|tag| !tag.is_empty())
+            .collect(),
+    )
+}
+
+/// Whether `If-None-Match` names `etag`, so that a GET can be answered
+/// with 304. Weak and strong tags compare equal here.
+#[must_use]
+pub fn none_match_hits(headers: &HeaderMap, etag: &str) -> bool {
+    let weak = |tag: &str| tag.strip_prefix("W/").unwrap_or(tag).to_string();
+    listed_tags(headers, &header::IF_NONE_MATCH).is_some_and(|tags| {
+        tags.iter()
+            .any(|&tag| tag == "*" || weak(tag) == weak(etag))
+    })
+}
+
+/// Whether `If-Match` rules out a write to a file whose current strong tag
+/// is `current`; `exists` is false if there is no file. Only strong tags
+/// can match, and `*` matches any existing file, with a strong tag or not.
+#[must_use]
+pub fn if_match_fails(headers: &HeaderMap, exists: bool, current: Option<&str>) -> bool {
+    let Some(tags) = listed_tags(headers, &header::IF_MATCH) else {
+        return false;
+    };
+    if !exists {
+        return true;
+    }
+    !tags.iter().any(|&tag| {
+        tag == "*" || (!tag.starts_with("W/") && current.is_some_and(|current| tag == current))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+    use bytes::Bytes;
+    use fs9_core::MemoryFs;
+    use fs9_sdk::{Capabilities, FsStats, Handle, StatChanges};
+
+    /// A `MemoryFs` that reports no etags, or weak ones if `weak` is set.
+    #[derive(Default)]
+    struct UntaggedFs(MemoryFs, bool);
+
+    #[async_trait::async_trait]
+    impl FsProvider for UntaggedFs {
+        async fn stat(&self, path: &str) -> FsResult<FileInfo> {
+            let mut info = self.0.stat(path).await?;
+            info.etag = if self.1 {
+                fs9_sdk::metadata_etag(info.size, info.mtime)
+            } else {
+                String::new()
+            };
+            Ok(info)
+        }
+
+        async fn wstat(&self, path: &str, changes: StatChanges) -> FsResult<()> {
+            self.0.wstat(path, changes).await
+        }
+
+        async fn statfs(&self, path: &str) -> FsResult<FsStats> {
+            self.0.statfs(path).await
+        }
+
+        async fn open(&self, path: &str, flags: OpenFlags) -> FsResult<(Handle, FileInfo)> {
+            self.0.open(path, flags).await
+        }
+
+        async fn read(&self, handle: &Handle, offset: u64, size: usize) -> FsResult<Bytes> {
+            self.0.read(handle, offset, size).await
+        }
+
+        async fn write(&self, handle: &Handle, offset: u64, data: Bytes) -> FsResult<usize> {
+            self.0.write(handle, offset, data).await
+        }
+
+        async fn close(&self, handle: Handle, sync: bool) -> FsResult<()> {
+            self.0.close(handle, sync).await
+        }
+
+        async fn readdir(&self, path: &str) -> FsResult<Vec<FileInfo>> {
+            self.0.readdir(path).await
+        }
+
+        async fn remove(&self, path: &str) -> FsResult<()> {
+            self.0.remove(path).await
+        }
+
+        fn capabilities(&self) -> Capabilities {
+            self.0.capabilities()
+        }
+    }
+
+    async fn tag_of(fs: &UntaggedFs, data: &'static [u8]) -> String {
+        let (handle, _) = fs.open("/f", OpenFlags::create_truncate()).await.unwrap();
+        fs.write(&handle, 0, Bytes::from_static(data))
+            .await
+            .unwrap();
+        fs.close(handle, false).await.unwrap();
+        let info = fs.stat("/f").await.unwrap();
+        strong_entity_tag(fs, "/f", &info, fs.capabilities())
+            .await
+            .unwrap()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn untagged_files_are_tagged_by_content() {
+        let fs = UntaggedFs::default();
+        let first = tag_of(&fs, b"hello").await;
+        assert!(first.starts_with("\"sha256-"));
+        assert_eq!(tag_of(&fs, b"hello").await, first);
+        assert_ne!(tag_of(&fs, b"hellO").await, first);
+    }
+
+    #[tokio::test]
+    async fn weakly_tagged_files_are_tagged_by_content() {
+        let fs = UntaggedFs(MemoryFs::default(), true);
+        let first = tag_of(&fs, b"hello").await;
+        assert_eq!(first, tag_of(&UntaggedFs::default(), b"hello").await);
+        assert_ne!(tag_of(&fs, b"hellO").await, first);
+    }
+
+    #[tokio::test]
+    async fn hashing_stops_at_the_stat_size() {
+        let fs = fs9_core::NullFs::new();
+        let info = fs.stat("/zero").await.unwrap();
+        let tag = tokio::time::timeout(
+            std::time::Duration::from_secs(5),
+            strong_entity_tag(&fs, "/zero", &info, fs.capabilities()),
+        )
+        .await
+        .expect("hashing /zero never finished")
+        .unwrap();
+        assert!(tag.unwrap().starts_with("\"sha256-"));
+    }
+
+    #[tokio::test]
+    async fn streams_have_no_strong_tag() {
+        let fs = UntaggedFs::default();
+        tag_of(&fs, b"hello").await;
+        let info = fs.stat("/f").await.unwrap();
+        let tag = strong_entity_tag(&fs, "/f", &info, Capabilities::STREAMING)
+            .await
+            .unwrap();
+        assert_eq!(tag, None);
+    }
+
+    #[tokio::test]
+    async fn header_tags_come_from_stat_alone() {
+        let fs = UntaggedFs(MemoryFs::default(), true);
+        tag_of(&fs, b"hello").await;
+        let info = fs.stat("/f").await.unwrap();
+        let tag = entity_tag(&info);
+        assert_eq!(tag, format!("W/\"{}\"", &info.etag[2..]));
+
+        let info = UntaggedFs::default().0.stat("/").await.unwrap();
+        assert!(entity_tag(&info).starts_with('"'));
+    }
+
+    fn headers(name: header::HeaderName, value: &'static str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(name, HeaderValue::from_static(value));
+        headers
+    }
+
+    #[test]
+    fn if_none_match_uses_weak_comparison() {
+        let etag = "\"abc\"";
+        assert!(!none_match_hits(&HeaderMap::new(), etag));
+        assert!(none_match_hits(
+            &headers(header::IF_NONE_MATCH, "\"x\", W/\"abc\""),
+            etag
+        ));
+        assert!(none_match_hits(&headers(header::IF_NONE_MATCH, "*"), etag));
+        assert!(!none_match_hits(
+            &headers(header::IF_NONE_MATCH, "\"abd\""),
+            etag
+        ));
+    }
+
+    #[test]
+    fn if_match_uses_strong_comparison() {
+        let current = Some("\"abc\"");
+        assert!(!if_match_fails(&HeaderMap::new(), true, current));
+        assert!(!if_match_fails(&HeaderMap::new(), false, None));
+        assert!(!if_match_fails(
+            &headers(header::IF_MATCH, "\"abc\""),
+            true,
+            current
+        ));
+        assert!(if_match_fails(
+            &headers(header::IF_MATCH, "W/\"abc\""),
+            true,
+            current
+        ));
+        assert!(!if_match_fails(
+            &headers(header::IF_MATCH, "*"),
+            true,
+            current
+        ));
+        assert!(if_match_fails(&headers(header::IF_MATCH, "*"), false, None));
+        // Files without a strong tag only match `*`.
+        assert!(if_match_fails(
+            &headers(header::IF_MATCH, "\"abc\""),
+            true,
+            None
+        ));
+        assert!(!if_match_fails(&headers(header::IF_MATCH, "*"), true, None));
+    }
+}
@@ -1,14 +1,19 @@
 #![allow(missing_docs)]
 
+pub mod access_log;
 pub mod audit;
 pub mod auth;
+pub mod body_limit;
 pub mod circuit_breaker;
+pub mod conditional;
 pub mod db9_client;
 pub mod meta_client;
 pub mod metrics;
 pub mod namespace;
 pub mod rate_limit;
+pub mod shutdown;
 pub mod state;
 pub mod token_cache;
 pub mod token_revocation;
 pub mod tracing_otel;
+pub mod webdav;
@@ -2,13 +2,15 @@
 
 mod api;
 
+use fs9_server::access_log;
 use fs9_server::auth;
 use fs9_server::db9_client::Db9Client;
 use fs9_server::meta_client;
 use fs9_server::meta_client::MetaClient;
 use fs9_server::metrics as fs9_metrics;
 use fs9_server::namespace;
-use fs9_server::rate_limit::{self, RateLimitState};
+use fs9_server::rate_limit;
+use fs9_server::shutdown;
 use fs9_server::state;
 #[cfg(feature = "otel")]
 use fs9_server::tracing_otel;
@@ -17,14 +19,18 @@ use axum::extract::DefaultBodyLimit;
 use axum::middleware;
 use clap::Parser;
 use fs9_config::Fs9Config;
-use fs9_core::{default_registry, ProviderConfig};
+use fs9_core::{
+    default_registry, CircuitBreakerConfig, MetadataCacheConfig, ProviderConfig, RetryPolicy,
+};
 use std::path::Path;
 use std::sync::Arc;
 use std::time::Duration;
 use tower::limit::ConcurrencyLimitLayer;
 use tower_http::timeout::TimeoutLayer;
 use tower_http::trace::TraceLayer;
-use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
+use tracing_subscriber::{
+    layer::SubscriberExt, reload, util::SubscriberInitExt, EnvFilter, Registry,
+};
 
 use auth::{AuthMiddlewareState, AuthState, JwtConfig};
 use namespace::DEFAULT_NAMESPACE;
@@ -43,21 +49,23 @@ struct Args {
 async fn main() {
     let args = Args::parse();
 
-    let config = match &args.config {
-        Some(path) => fs9_config::load_from_file(path).unwrap_or_else(|e| {
-            eprintln!("Error: Failed to load config from {path}: {e}");
-            std::process::exit(1);
-        }),
-        None => fs9_config::load().unwrap_or_else(|e| {
-            eprintln!("Warning: Failed to load config: {e}, using defaults");
-            Fs9Config::default()
-        }),
+    let loader = match &args.config {
+        Some(path) => fs9_config::ConfigLoader::new().with_file(path),
+        None => fs9_config::ConfigLoader::new(),
     };
+    let watcher = loader.watch(CONFIG_POLL_INTERVAL).unwrap_or_else(|e| {
+        match &args.config {
+            Some(path) => eprintln!("Error: Failed to load config from {path}: {e}"),
+            None => eprintln!("Error: Failed to load config: {e}"),
+        }
+        std::process::exit(1);
+    });
+    let config = watcher.current();
 
     #[cfg(feature = "otel")]
-    let otel_provider = init_logging_with_otel(&config);
+    let (otel_provider, log_filter) = init_logging_with_otel(&config);
     #[cfg(not(feature = "otel"))]
-    init_logging(&config);
+    let log_filter = init_logging(&config);
 
     // meta_url is required unless FS9_SKIP_META_CHECK is set (for testing)
     let skip_meta_check = std::env::var("FS9_SKIP_META_CHECK").is_ok();
@@ -87,15 +95,24 @@ async fn main() {
         tracing::info!("Default pagefs config loaded for auto-provisioning");
     }
 
-    let state = Arc::new(state::AppState::with_meta(
-        meta_client,
-        db9_client,
-        default_pagefs,
-    ));
+    let app_state = state::AppState::with_meta(meta_client, db9_client, default_pagefs);
+    let app_state = configure_vfs(app_state, &config);
+    let state = Arc::new(app_state);
     let registry = default_registry();
 
     load_plugins(&state, &config);
-    setup_mounts(&state, &registry, &config).await;
+    if let Err(e) = setup_mounts(&state, &registry, &config).await {
+        eprintln!("Error: {e}");
+        std::process::exit(1);
+    }
+
+    spawn_config_reloads(
+        watcher,
+        Arc::clone(&state),
+        registry,
+        log_filter,
+        config.clone(),
+    );
 
     let jwt_secret = if config.server.auth.jwt_secret.is_empty() {
         let generated = format!("{}{}", uuid::Uuid::new_v4(), uuid::Uuid::new_v4());
@@ -105,28 +122,28 @@ async fn main() {
         config.server.auth.jwt_secret.clone()
     };
 
-    // Store jwt_secret in app state for refresh endpoint
-    state.set_jwt_secret(jwt_secret.clone()).await;
+    let jwt_config = JwtConfig::new(jwt_secret)
+        .with_previous_secrets(config.server.auth.previous_secrets.iter().cloned());
+    // Store the keys in app state for refresh endpoint
+    state.set_jwt_config(jwt_config.clone()).await;
 
     let auth_enabled = config.server.auth.enabled || has_meta;
-    let auth_state = AuthState::new(auth_enabled, JwtConfig::new(jwt_secret));
-    let auth_middleware_state = AuthMiddlewareState::new(auth_state, Arc::clone(&state));
+    let auth_state = AuthState::new(auth_enabled, jwt_config);
+    let mut auth_middleware_state = AuthMiddlewareState::new(auth_state, Arc::clone(&state));
+    if config.server.metrics.enabled {
+        auth_middleware_state =
+            auth_middleware_state.with_metrics_key(config.server.metrics.admin_key.clone());
+    }
 
     let request_timeout = Duration::from_secs(config.server.request_timeout_secs.unwrap_or(30));
     let max_concurrent = config.server.max_concurrent_requests.unwrap_or(1000);
     let default_body_limit = config.server.max_body_size_bytes.unwrap_or(2 * 1024 * 1024);
-    let write_body_limit = config
-        .server
-        .max_write_size_bytes
-        .unwrap_or(256 * 1024 * 1024);
-
-    let rate_limit_state = if config.server.rate_limit.enabled {
-        RateLimitState::new(
-            config.server.rate_limit.namespace_qps,
-            config.server.rate_limit.user_qps,
-        )
-    } else {
-        RateLimitState::disabled()
+    let body_limits = api::BodyLimits {
+        write: config
+            .server
+            .max_write_size_bytes
+            .unwrap_or(256 * 1024 * 1024),
+        admin: config.server.max_admin_body_size_bytes.unwrap_or(64 * 1024),
     };
 
     let prometheus_handle = if config.server.metrics.enabled {
@@ -135,7 +152,7 @@ async fn main() {
         None
     };
 
-    let mut app = api::create_router(state.clone(), write_body_limit, prometheus_handle.clone());
+    let mut app = api::create_router(state.clone(), body_limits, prometheus_handle.clone());
 
     if config.server.metrics.enabled {
         app = app.layer(middleware::from_fn(fs9_metrics::metrics_middleware));
@@ -143,9 +160,10 @@ async fn main() {
 
     let app = app
         .layer(middleware::from_fn_with_state(
-            rate_limit_state,
+            state.rate_limit.clone(),
             rate_limit::rate_limit_middleware,
         ))
+        .layer(middleware::from_fn(access_log::record_principal))
         .layer(middleware::from_fn_with_state(
             auth_middleware_state,
             auth::auth_middleware,
@@ -153,15 +171,18 @@ async fn main() {
         .layer(DefaultBodyLimit::max(default_body_limit))
         .layer(TimeoutLayer::new(request_timeout))
         .layer(ConcurrencyLimitLayer::new(max_concurrent))
+        .layer(middleware::from_fn(access_log::access_log_middleware))
         .layer(TraceLayer::new_for_http());
 
     let addr = format!("{}:{}", config.server.host, config.server.port);
     let listener = tokio::net::TcpListener::bind(&addr).await.unwrap();
     tracing::info!("FS9 Server listening on http://{}", addr);
 
-    let shutdown_state = state.clone();
-    axum::serve(listener, app)
-        .with_graceful_shutdown(shutdown_signal(shutdown_state))
+    let drain_timeout = config
+        .server
+        .shutdown_timeout_secs
+        .map_or(shutdown::DEFAULT_DRAIN_TIMEOUT, Duration::from_secs);
+    shutdown::serve(listener, app, state, shutdown::signal(), drain_timeout)
         .await
         .unwrap();
 
@@ -171,55 +192,37 @@ async fn main() {
     }
 }
 
-async fn shutdown_signal(state: Arc<state::AppState>) {
-    let ctrl_c = async {
-        tokio::signal::ctrl_c()
-            .await
-            .expect("install Ctrl+C handler");
-    };
-
-    #[cfg(unix)]
-    let terminate = async {
-        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
-            .expect("install SIGTERM handler")
-            .recv()
-            .await;
-    };
+/// How often the config files are checked for changes.
+const CONFIG_POLL_INTERVAL: Duration = Duration::from_secs(5);
 
-    #[cfg(not(unix))]
-    let terminate = std::future::pending::<()>();
+/// Swaps the log filter of the running subscriber on config reload.
+type LogFilter = reload::Handle<EnvFilter, Registry>;
 
-    tokio::select! {
-        () = ctrl_c => tracing::info!("Received Ctrl+C, shutting down"),
-        () = terminate => tracing::info!("Received SIGTERM, shutting down"),
+fn log_filter_for(config: &Fs9Config) -> EnvFilter {
+    if config.logging.filter.is_empty() {
+        EnvFilter::new(config.logging.level.as_str())
+    } else {
+        EnvFilter::new(&config.logging.filter)
     }
-
-    tracing::info!("Draining open handles...");
-    state.namespace_manager.drain_all().await;
-    tracing::info!("Shutdown complete");
 }
 
 #[cfg(not(feature = "otel"))]
-fn init_logging(config: &Fs9Config) {
-    let filter = if config.logging.filter.is_empty() {
-        config.logging.level.as_str().to_string()
-    } else {
-        config.logging.filter.clone()
-    };
+fn init_logging(config: &Fs9Config) -> LogFilter {
+    let (filter, handle) = reload::Layer::new(log_filter_for(config));
 
     tracing_subscriber::registry()
+        .with(filter)
         .with(tracing_subscriber::fmt::layer())
-        .with(tracing_subscriber::EnvFilter::new(filter))
         .init();
+
+    handle
 }
 
 #[cfg(feature = "otel")]
-fn init_logging_with_otel(config: &Fs9Config) -> Option<opentelemetry_sdk::trace::TracerProvider> {
-    let filter = if config.logging.filter.is_empty() {
-        config.logging.level.as_str().to_string()
-    } else {
-        config.logging.filter.clone()
-    };
+fn init_logging_with_otel(
+    config: &Fs9Config,
+) -> (Option<opentelemetry_sdk::trace::TracerProvider>, LogFilter) {
+    let (filter, handle) = reload::Layer::new(log_filter_for(config));
 
     let otel_enabled = std::env::var("OTEL_EXPORTER_OTLP_ENDPOINT").is_ok();
 
@@ -228,12 +231,12 @@ fn init_logging_with_otel(config: &Fs9Config) -> Option<opentelemetry_sdk::trace
             Ok(provider) => {
                 let tracer = tracing_otel::otel_tracer(&provider);
                 tracing_subscriber::registry()
-                    .with(tracing_subscriber::EnvFilter::new(filter))
+                    .with(filter)
                     .with(tracing_subscriber::fmt::layer())
                     .with(tracing_opentelemetry::layer().with_tracer(tracer))
                     .init();
                 tracing::info!("OpenTelemetry tracing enabled");
-                return Some(provider);
+                return (Some(provider), handle);
             }
             Err(e) => {
                 eprintln!("Warning: Failed to initialize OpenTelemetry: {e}, falling back to stdout tracing");
@@ -242,16 +245,49 @@ fn init_logging_with_otel(config: &Fs9Config) -> Option<opentelemetry_sdk::trace
     }
 
     tracing_subscriber::registry()
+        .with(filter)
         .with(tracing_subscriber::fmt::layer())
-        .with(tracing_subscriber::EnvFilter::new(filter))
         .init();
 
-    None
+    (None, handle)
+}
+
+/// Apply the optional per-namespace VFS features from `server.*`.
+fn configure_vfs(mut app_state: state::AppState, config: &Fs9Config) -> state::AppState {
+    if config.server.metadata_cache.enabled {
+        let cache = &config.server.metadata_cache;
+        app_state = app_state.with_metadata_cache(
+            MetadataCacheConfig::new(Duration::from_millis(cache.ttl_ms))
+                .with_negative_lookups(cache.negative)
+                .with_max_entries(cache.max_entries),
+        );
+    }
+    if config.server.mount_circuit_breaker.enabled {
+        let breaker = &config.server.mount_circuit_breaker;
+        app_state = app_state.with_mount_circuit_breaker(
+            CircuitBreakerConfig::new(
+                Duration::from_secs(breaker.window_secs),
+                Duration::from_secs(breaker.cooldown_secs),
+            )
+            .with_min_calls(breaker.min_calls)
+            .with_failure_rate(breaker.failure_rate),
+        );
+    }
+    app_state.with_rate_limit(&config.server.rate_limit)
 }
 
 fn load_plugins(state: &state::AppState, config: &Fs9Config) {
     let mut total_loaded = 0;
 
+    let allowed = &config.server.plugins.allowed_sha256;
+    if !allowed.is_empty() {
+        state.plugin_manager.set_allowed_sha256(allowed);
+        tracing::info!(
+            count = allowed.len(),
+            "Plugins restricted to sha256 allowlist"
+        );
+    }
+
     for dir in &config.server.plugins.directories {
         let path = Path::new(dir);
         if path.is_dir() {
@@ -284,11 +320,61 @@ fn load_plugins(state: &state::AppState, config: &Fs9Config) {
     }
 }
 
+/// Check a configured mount's `require_capabilities` against its provider
+/// and apply its `mask_capabilities`.
+async fn apply_capabilities(
+    mount_table: &fs9_core::MountTable,
+    mount: &fs9_config::MountConfig,
+) -> fs9_sdk::FsResult<()> {
+    let required = fs9_sdk::Capabilities::from_names(&mount.require_capabilities)?;
+    mount_table
+        .require_capabilities(&mount.path, required)
+        .await?;
+    let masked = fs9_sdk::Capabilities::from_names(&mount.mask_capabilities)?;
+    mount_table.mask_capabilities(&mount.path, masked).await
+}
+
+fn provider_config(mount: &fs9_config::MountConfig) -> ProviderConfig {
+    let mut pc = ProviderConfig::new();
+    if let Some(obj) = mount.config.as_ref().and_then(|json| json.as_object()) {
+        for (k, v) in obj {
+            pc.options.insert(k.clone(), v.clone());
+        }
+    }
+    pc
+}
+
+/// Run the provider's health check before it is mounted, bounded by the
+/// mount's `op_timeout_secs`. A failure is an error for a `fail_fast` mount
+/// and a warning otherwise.
+async fn check_health(
+    mount: &fs9_config::MountConfig,
+    provider: &dyn fs9_sdk::FsProvider,
+) -> Result<(), String> {
+    let checked = match mount.op_timeout_secs.map(Duration::from_secs) {
+        Some(limit) => tokio::time::timeout(limit, provider.health_check())
+            .await
+            .unwrap_or_else(|_| Err(fs9_sdk::FsError::timeout(limit))),
+        None => provider.health_check().await,
+    };
+    match checked {
+        Ok(()) => Ok(()),
+        Err(e) if mount.fail_fast => Err(format!(
+            "mount {}: health check of provider {} failed: {e}",
+            mount.path, mount.provider
+        )),
+        Err(e) => {
+            tracing::warn!(path = %mount.path, provider = %mount.provider, error = %e, "Health check failed, mounting anyway");
+            Ok(())
+        }
+    }
+}
+
 async fn setup_mounts(
     state: &Arc<state::AppState>,
     registry: &fs9_core::ProviderRegistry,
     config: &Fs9Config,
-) {
+) -> Result<(), String> {
     // All config-defined mounts go into the default namespace.
     let default_ns = match state
         .namespace_manager
@@ -304,63 +390,346 @@ async fn setup_mounts(
     };
 
     for mount in &config.mounts {
-        let config_json = {
-            let mut cfg = mount
-                .config
-                .clone()
-                .unwrap_or(serde_json::Value::Object(Default::default()));
-            if let Some(obj) = cfg.as_object_mut() {
-                obj.insert(
-                    "ns".to_string(),
-                    serde_json::Value::String(DEFAULT_NAMESPACE.to_string()),
-                );
-            }
-            serde_json::to_string(&cfg).unwrap_or_default()
-        };
+        setup_mount(state, registry, &default_ns, mount).await?;
+    }
+    Ok(())
+}
 
-        let provider_config = match &mount.config {
-            Some(json) => {
-                let mut pc = ProviderConfig::new();
-                if let Some(obj) = json.as_object() {
-                    for (k, v) in obj {
-                        pc.options.insert(k.clone(), v.clone());
-                    }
-                }
-                pc
-            }
-            None => ProviderConfig::new(),
-        };
+/// Mount one configured mount into `ns`. Returns an error only for problems
+/// that must stop the server; a provider that cannot be created or mounted
+/// is logged and skipped, and `false` is returned.
+async fn setup_mount(
+    state: &Arc<state::AppState>,
+    registry: &fs9_core::ProviderRegistry,
+    ns: &namespace::Namespace,
+    mount: &fs9_config::MountConfig,
+) -> Result<bool, String> {
+    let mut cfg = mount
+        .config
+        .clone()
+        .unwrap_or(serde_json::Value::Object(Default::default()));
+    if let Some(obj) = cfg.as_object_mut() {
+        obj.insert(
+            "ns".to_string(),
+            serde_json::Value::String(DEFAULT_NAMESPACE.to_string()),
+        );
+    }
 
-        let provider: Result<Arc<dyn fs9_sdk::FsProvider>, _> = if registry.has(&mount.provider) {
-            registry.create(&mount.provider, provider_config)
-        } else {
-            match state
+    let pool = &state.provider_pool;
+    let provider: Result<Arc<dyn fs9_sdk::FsProvider>, _> = if registry.has(&mount.provider) {
+        pool.get_or_create(&mount.provider, &cfg, || {
+            registry.create(&mount.provider, provider_config(mount))
+        })
+    } else {
+        let created = pool.get_or_create(&mount.provider, &cfg, || {
+            let config_json = serde_json::to_string(&cfg).unwrap_or_default();
+            state
                 .plugin_manager
                 .create_provider(&mount.provider, &config_json)
-            {
-                Ok(p) => Ok(Arc::new(p) as Arc<dyn fs9_sdk::FsProvider>),
-                Err(e) => {
-                    tracing::error!(path = %mount.path, provider = %mount.provider, error = %e, "Unknown provider or creation failed");
-                    continue;
-                }
+                .map(|p| Arc::new(p) as Arc<dyn fs9_sdk::FsProvider>)
+        });
+        match created {
+            Ok(p) => Ok(p),
+            Err(e) => {
+                tracing::error!(path = %mount.path, provider = %mount.provider, error = %e, "Unknown provider or creation failed");
+                return Ok(false);
             }
-        };
+        }
+    };
 
-        match provider {
-            Ok(p) => {
-                if let Err(e) = default_ns
-                    .mount_table
-                    .mount(&mount.path, &mount.provider, p)
-                    .await
-                {
-                    tracing::error!(path = %mount.path, error = %e, "Failed to mount");
-                } else {
-                    tracing::info!(path = %mount.path, provider = %mount.provider, ns = DEFAULT_NAMESPACE, "Mounted");
+    match provider {
+        Ok(p) => {
+            check_health(mount, p.as_ref()).await?;
+            let mount_table = &ns.mount_table;
+            let mounted = match mount_table.mount(&mount.path, &mount.provider, p).await {
+                Ok(()) if mount.read_only => mount_table.set_read_only(&mount.path, true).await,
+                result => result,
+            };
+            let mounted = match (mounted, mount.op_timeout_secs) {
+                (Ok(()), Some(secs)) => {
+                    mount_table
+                        .set_op_timeout(&mount.path, Some(Duration::from_secs(secs)))
+                        .await
                 }
+                (result, _) => result,
+            };
+            let mounted = match (mounted, &mount.retry) {
+                (Ok(()), Some(retry)) => {
+                    let policy = RetryPolicy::new(retry.max_attempts).with_backoff(
+                        Duration::from_millis(retry.base_backoff_ms),
+                        Duration::from_millis(retry.max_backoff_ms),
+                    );
+                    mount_table
+                        .set_retry_policy(&mount.path, Some(policy))
+                        .await
+                }
+                (result, _) => result,
+            };
+            if let Err(e) = mounted {
+                tracing::error!(path = %mount.path, error = %e, "Failed to mount");
+                Ok(false)
+            } else if let Err(e) = apply_capabilities(mount_table, mount).await {
+                Err(format!("mount {}: {e}", mount.path))
+            } else {
+                ns.charge_mount(&mount.path).await;
+                tracing::info!(path = %mount.path, provider = %mount.provider, read_only = mount.read_only, ns = DEFAULT_NAMESPACE, "Mounted");
+                Ok(true)
             }
-            Err(e) => {
-                tracing::error!(path = %mount.path, provider = %mount.provider, error = %e, "Failed to create provider");
+        }
+        Err(e) => {
+            tracing::error!(path = %mount.path, provider = %mount.provider, error = %e, "Failed to create provider");
+            Ok(false)
+        }
+    }
+}
+
+/// Apply every config the watcher publishes for as long as the server runs.
+fn spawn_config_reloads(
+    watcher: fs9_config::ConfigWatcher,
+    state: Arc<state::AppState>,
+    registry: fs9_core::ProviderRegistry,
+    log_filter: LogFilter,
+    mut running: Fs9Config,
+) {
+    let mut reloads = watcher.subscribe();
+    tokio::spawn(async move {
+        let _watcher = watcher;
+        while reloads.changed().await.is_ok() {
+            let reloaded = reloads.borrow_and_update().config.clone();
+            apply_reload(&state, &registry, &log_filter, &mut running, &reloaded).await;
+        }
+    });
+}
+
+/// Apply the parts of a reloaded config that can change while serving: the
+/// log filter, `server.rate_limit` and mounts added under `mounts`. Mounts
+/// that were changed or removed are reported as needing a restart.
+/// `running` tracks the mounts in effect.
+async fn apply_reload(
+    state: &Arc<state::AppState>,
+    registry: &fs9_core::ProviderRegistry,
+    log_filter: &LogFilter,
+    running: &mut Fs9Config,
+    reloaded: &Fs9Config,
+) {
+    if let Err(e) = log_filter.reload(log_filter_for(reloaded)) {
+        tracing::warn!(error = %e, "Failed to apply reloaded log filter");
+    }
+    state.rate_limit.reconfigure(&reloaded.server.rate_limit);
+
+    let default_ns = state.default_namespace().await;
+    for mount in &reloaded.mounts {
+        match running.mounts.iter().find(|m| m.path == mount.path) {
+            None => match setup_mount(state, registry, &default_ns, mount).await {
+                Ok(true) => running.mounts.push(mount.clone()),
+                Ok(false) => {}
+                Err(e) => tracing::error!(error = %e, "Failed to apply reloaded mount"),
+            },
+            Some(current) if !same_mount(current, mount) => {
+                tracing::warn!(path = %mount.path, "Mount changed; restart to apply");
             }
+            Some(_) => {}
+        }
+    }
+    for mount in &running.mounts {
+        if !reloaded.mounts.iter().any(|m| m.path == mount.path) {
+            tracing::warn!(path = %mount.path, "Mount removed; restart to apply");
         }
     }
 }
+
+fn same_mount(a: &fs9_config::MountConfig, b: &fs9_config::MountConfig) -> bool {
+    serde_json::to_value(a).ok() == serde_json::to_value(b).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A proxy mount whose upstream refuses connections.
+    async fn unreachable_mount(fail_fast: bool) -> Fs9Config {
+        let closed = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let upstream = format!("http://{}", closed.local_addr().unwrap());
+        drop(closed);
+        let mut config = Fs9Config::default();
+        config.mounts.push(fs9_config::MountConfig {
+            path: "/remote".to_string(),
+            provider: "proxyfs".to_string(),
+            config: Some(serde_json::json!({ "upstream": upstream })),
+            read_only: false,
+            op_timeout_secs: Some(10),
+            retry: None,
+            require_capabilities: Vec::new(),
+            mask_capabilities: Vec::new(),
+            fail_fast,
+        });
+        config
+    }
+
+    async fn mounted_paths(state: &state::AppState) -> Vec<String> {
+        let ns = state
+            .namespace_manager
+            .get(DEFAULT_NAMESPACE)
+            .await
+            .unwrap();
+        let mut paths: Vec<_> = ns
+            .mount_table
+            .list_mounts()
+            .await
+            .into_iter()
+            .map(|m| m.path)
+            .collect();
+        paths.sort();
+        paths
+    }
+
+    #[tokio::test]
+    async fn failing_health_check_aborts_a_fail_fast_mount() {
+        let state = Arc::new(state::AppState::new());
+        let config = unreachable_mount(true).await;
+
+        let err = setup_mounts(&state, &default_registry(), &config)
+            .await
+            .unwrap_err();
+        assert!(err.contains("/remote"), "{err}");
+        assert!(err.contains("health check"), "{err}");
+        assert_eq!(mounted_paths(&state).await, ["/"]);
+    }
+
+    #[tokio::test]
+    async fn failing_health_check_only_warns_without_fail_fast() {
+        let state = Arc::new(state::AppState::new());
+        let config = unreachable_mount(false).await;
+
+        setup_mounts(&state, &default_registry(), &config)
+            .await
+            .unwrap();
+        assert_eq!(mounted_paths(&state).await, ["/", "/remote"]);
+    }
+
+    #[tokio::test]
+    async fn reload_applies_log_filter_rate_limits_and_new_mounts() {
+        let state = Arc::new(state::AppState::new());
+        let registry = default_registry();
+        let mut running = Fs9Config::default();
+        setup_mounts(&state, &registry, &running).await.unwrap();
+        let (_filter, handle) = reload::Layer::<_, Registry>::new(log_filter_for(&running));
+
+        let mut reloaded = running.clone();
+        reloaded.logging.filter = "fs9_server=debug".to_string();
+        reloaded.server.rate_limit.enabled = true;
+        reloaded.mounts.push(fs9_config::MountConfig {
+            path: "/scratch".to_string(),
+            provider: "memfs".to_string(),
+            config: None,
+            read_only: true,
+            op_timeout_secs: None,
+            retry: None,
+            require_capabilities: Vec::new(),
+            mask_capabilities: Vec::new(),
+            fail_fast: false,
+        });
+        apply_reload(&state, &registry, &handle, &mut running, &reloaded).await;
+
+        assert_eq!(
+            handle.with_current(ToString::to_string).unwrap(),
+            "fs9_server=debug"
+        );
+        assert!(state.rate_limit.enabled());
+        assert_eq!(mounted_paths(&state).await, ["/", "/scratch"]);
+        assert!(running.mounts.iter().any(|m| m.path == "/scratch"));
+
+        // Dropping the mount again needs a restart; it stays mounted.
+        reloaded.mounts.retain(|m| m.path != "/scratch");
+        apply_reload(&state, &registry, &handle, &mut running, &reloaded).await;
+        assert_eq!(mounted_paths(&state).await, ["/", "/scratch"]);
+    }
+
+    /// The API router with a `limit`-byte write limit and a 16-byte admin
+    /// limit, behind auth with auth disabled.
+    async fn limited_app(limit: usize) -> axum::Router {
+        let state = Arc::new(state::AppState::new());
+        setup_mounts(&state, &default_registry(), &Fs9Config::default())
+            .await
+            .unwrap();
+        let auth = AuthMiddlewareState::new(
+            AuthState::new(false, JwtConfig::new("secret")),
+            Arc::clone(&state),
+        );
+        let limits = api::BodyLimits {
+            write: limit,
+            admin: 16,
+        };
+        api::create_router(state, limits, None)
+            .layer(middleware::from_fn_with_state(auth, auth::auth_middleware))
+    }
+
+    async fn send(
+        app: &mut axum::Router,
+        request: axum::http::Request<axum::body::Body>,
+    ) -> (axum::http::StatusCode, serde_json::Value) {
+        use tower::Service;
+
+        let response = app.call(request).await.unwrap();
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, serde_json::from_slice(&body).unwrap_or_default())
+    }
+
+    fn upload(body: axum::body::Body) -> axum::http::Request<axum::body::Body> {
+        axum::http::Request::put("/api/v1/upload?path=/blob.bin")
+            .body(body)
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn write_limit_allows_bodies_up_to_it() {
+        let mut app = limited_app(1024).await;
+
+        let (status, body) = send(&mut app, upload(vec![7u8; 1024].into())).await;
+        assert_eq!(status, axum::http::StatusCode::OK);
+        assert_eq!(body["bytes_written"], 1024);
+
+        let (status, body) = send(&mut app, upload(vec![7u8; 1025].into())).await;
+        assert_eq!(status, axum::http::StatusCode::PAYLOAD_TOO_LARGE);
+        assert_eq!(body["code"], 413);
+        assert_eq!(body["error"], "request body is larger than 1024 bytes");
+    }
+
+    #[tokio::test]
+    async fn write_limit_applies_to_chunked_uploads() {
+        let mut app = limited_app(1024).await;
+        let chunked = |sizes: &[usize]| {
+            let chunks: Vec<Result<Vec<u8>, std::io::Error>> =
+                sizes.iter().map(|&n| Ok(vec![7u8; n])).collect();
+            axum::body::Body::from_stream(futures::stream::iter(chunks))
+        };
+
+        let (status, _) = send(&mut app, upload(chunked(&[512, 512]))).await;
+        assert_eq!(status, axum::http::StatusCode::OK);
+
+        let (status, body) = send(&mut app, upload(chunked(&[512, 512, 1]))).await;
+        assert_eq!(status, axum::http::StatusCode::PAYLOAD_TOO_LARGE);
+        assert_eq!(body["code"], 413);
+    }
+
+    #[tokio::test]
+    async fn admin_endpoints_have_their_own_limit() {
+        let mut app = limited_app(1024).await;
+        let create = |name: &str| {
+            axum::http::Request::post("/api/v1/namespaces")
+                .header("content-type", "application/json")
+                .body(serde_json::json!({ "name": name }).to_string().into())
+                .unwrap()
+        };
+
+        let (status, body) = send(&mut app, create("a-namespace-name-over-the-limit")).await;
+        assert_eq!(status, axum::http::StatusCode::PAYLOAD_TOO_LARGE);
+        assert_eq!(body["code"], 413);
+
+        let (status, _) = send(&mut app, create("ns")).await;
+        assert_eq!(status, axum::http::StatusCode::CREATED);
+    }
+}
@@ -5,6 +5,7 @@ use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 use std::time::Duration;
 
+use crate::auth::PathGrant;
 use crate::circuit_breaker::CircuitBreaker;
 
 /// Client for fs9-meta service API.
@@ -21,7 +22,13 @@ struct ValidateRequest {
     token: String,
 }
 
-/// Response from token validation.
+/// Request to validate an API key.
+#[derive(Serialize)]
+struct ValidateApiKeyRequest {
+    key: String,
+}
+
+/// Response from token or API key validation.
 #[derive(Debug, Deserialize)]
 pub struct ValidateResponse {
     pub valid: bool,
@@ -29,6 +36,8 @@ pub struct ValidateResponse {
     pub namespace: Option<String>,
     #[serde(default)]
     pub roles: Vec<String>,
+    #[serde(default)]
+    pub scopes: Vec<PathGrant>,
     pub expires_at: Option<String>,
     pub error: Option<String>,
 }
@@ -63,6 +72,12 @@ pub struct NamespaceInfo {
     /// fs9-meta does not return a status field; default to "active"
     #[serde(default = "default_status_active")]
     pub status: String,
+    /// Byte budget across all of the namespace's mounts; `None` is unlimited.
+    #[serde(default)]
+    pub quota_bytes: Option<u64>,
+    /// Read/write request limits replacing the server-wide ones, if any.
+    #[serde(default)]
+    pub rate_limit: Option<fs9_config::NamespaceRateLimit>,
 }
 
 fn default_status_active() -> String {
@@ -124,6 +139,31 @@ impl MetaClient {
         Ok(response.json().await?)
     }
 
+    /// Resolve a long-lived API key into its user, namespace and roles.
+    ///
+    /// Unknown, expired and revoked keys come back with `valid == false`.
+    pub async fn validate_api_key(&self, key: &str) -> Result<ValidateResponse, MetaClientError> {
+        let url = format!("{}/api/v1/apikeys/validate", self.base_url);
+
+        let mut req = self.client.post(&url).json(&ValidateApiKeyRequest {
+            key: key.to_string(),
+        });
+        if let Some(key) = &self.admin_key {
+            req = req.header("x-fs9-meta-key", key);
+        }
+        let response = req.send().await?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response.text().await.unwrap_or_default();
+            return Err(MetaClientError::ServiceError(format!(
+                "HTTP {status}: {body}"
+            )));
+        }
+
+        Ok(response.json().await?)
+    }
+
     pub async fn validate_token_with_circuit_breaker(
         &self,
         token: &str,
@@ -131,35 +171,23 @@ impl MetaClient {
         max_retries: u32,
         base_delay_ms: u64,
     ) -> Result<ValidateResponse, MetaClientError> {
-        if !circuit_breaker.allow_request().await {
-            return Err(MetaClientError::ServiceError(
-                "Circuit breaker is open — meta service unavailable".to_string(),
-            ));
-        }
-
-        let mut last_err = None;
-        for attempt in 0..max_retries.max(1) {
-            if attempt > 0 {
-                let delay = Duration::from_millis(base_delay_ms * 2u64.pow(attempt - 1));
-                tokio::time::sleep(delay).await;
-            }
-
-            match self.validate_token(token).await {
-                Ok(resp) => {
-                    circuit_breaker.record_success().await;
-                    return Ok(resp);
-                }
-                Err(e) => {
-                    tracing::warn!(attempt = attempt + 1, error = %e, "Meta validate_token failed");
-                    last_err = Some(e);
-                }
-            }
-        }
+        with_circuit_breaker(circuit_breaker, max_retries, base_delay_ms, || {
+            self.validate_token(token)
+        })
+        .await
+    }
 
-        circuit_breaker.record_failure().await;
-        Err(last_err.unwrap_or_else(|| {
-            MetaClientError::ServiceError("All retry attempts exhausted".to_string())
-        }))
+    pub async fn validate_api_key_with_circuit_breaker(
+        &self,
+        key: &str,
+        circuit_breaker: &Arc<CircuitBreaker>,
+        max_retries: u32,
+        base_delay_ms: u64,
+    ) -> Result<ValidateResponse, MetaClientError> {
+        with_circuit_breaker(circuit_breaker, max_retries, base_delay_ms, || {
+            self.validate_api_key(key)
+        })
+        .await
     }
 
     /// Fetch a namespace's info from meta service.
@@ -280,6 +308,48 @@ impl MetaClient {
     }
 }
 
+/// Run a meta validation call with retries, guarded by the circuit breaker.
+async fn with_circuit_breaker<F, Fut>(
+    circuit_breaker: &Arc<CircuitBreaker>,
+    max_retries: u32,
+    base_delay_ms: u64,
+    mut call: F,
+) -> Result<ValidateResponse, MetaClientError>
+where
+    F: FnMut() -> Fut,
+    Fut: std::future::Future<Output = Result<ValidateResponse, MetaClientError>>,
+{
+    if !circuit_breaker.allow_request().await {
+        return Err(MetaClientError::ServiceError(
+            "Circuit breaker is open — meta service unavailable".to_string(),
+        ));
+    }
+
+    let mut last_err = None;
+    for attempt in 0..max_retries.max(1) {
+        if attempt > 0 {
+            let delay = Duration::from_millis(base_delay_ms * 2u64.pow(attempt - 1));
+            tokio::time::sleep(delay).await;
+        }
+
+        match call().await {
+            Ok(resp) => {
+                circuit_breaker.record_success().await;
+                return Ok(resp);
+            }
+            Err(e) => {
+                tracing::warn!(attempt = attempt + 1, error = %e, "Meta validation failed");
+                last_err = Some(e);
+            }
+        }
+    }
+
+    circuit_breaker.record_failure().await;
+    Err(last_err.unwrap_or_else(|| {
+        MetaClientError::ServiceError("All retry attempts exhausted".to_string())
+    }))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1,11 +1,36 @@
 use axum::{body::Body, extract::Request, middleware::Next, response::Response};
+use fs9_core::CacheStats;
 use metrics::{counter, histogram};
+use metrics_exporter_prometheus::{Matcher, PrometheusBuilder};
+use std::sync::Arc;
 use std::time::Instant;
 
 use crate::auth::RequestContext;
+use crate::namespace::NamespaceManager;
+
+#[derive(Clone)]
+pub struct MetricsState {
+    pub handle: metrics_exporter_prometheus::PrometheusHandle,
+    pub namespaces: Arc<NamespaceManager>,
+}
+
+/// Bucket bounds for `fs9_vfs_op_duration_seconds`, from fast in-memory
+/// lookups up to slow remote backends.
+const VFS_LATENCY_BUCKETS: [f64; 12] = [
+    0.0001, 0.00025, 0.0005, 0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.5, 1.0,
+];
+
+fn prometheus_builder() -> PrometheusBuilder {
+    PrometheusBuilder::new()
+        .set_buckets_for_metric(
+            Matcher::Full("fs9_vfs_op_duration_seconds".to_string()),
+            &VFS_LATENCY_BUCKETS,
+        )
+        .expect("latency buckets are non-empty")
+}
 
 pub fn init_metrics() -> metrics_exporter_prometheus::PrometheusHandle {
-    metrics_exporter_prometheus::PrometheusBuilder::new()
+    prometheus_builder()
         .install_recorder()
         .expect("failed to install Prometheus recorder")
 }
@@ -44,6 +69,10 @@ pub async fn metrics_middleware(request: Request<Body>, next: Next) -> Response
 }
 
 fn normalize_path(path: &str) -> String {
+    // One label for the whole WebDAV tree rather than one per file.
+    if path == crate::webdav::PREFIX || path.starts_with("/dav/") {
+        return crate::webdav::PREFIX.to_string();
+    }
     let parts: Vec<&str> = path.split('/').collect();
     if parts.len() >= 5 && parts[1] == "api" && parts[2] == "v1" && parts[3] == "namespaces" {
         return format!(
@@ -58,12 +87,21 @@ fn normalize_path(path: &str) -> String {
     path.to_string()
 }
 
+/// Publish a namespace's VFS metadata cache counters. The cache keeps its
+/// own running totals, so these are set absolutely rather than incremented.
+pub fn record_vfs_cache_stats(namespace: &str, stats: CacheStats) {
+    let labels = [("namespace", namespace.to_string())];
+    counter!("fs9_vfs_cache_hits_total", &labels).absolute(stats.hits);
+    counter!("fs9_vfs_cache_misses_total", &labels).absolute(stats.misses);
+}
+
 pub async fn metrics_handler(
-    axum::extract::State(handle): axum::extract::State<
-        metrics_exporter_prometheus::PrometheusHandle,
-    >,
+    axum::extract::State(state): axum::extract::State<MetricsState>,
 ) -> String {
-    handle.render()
+    for (namespace, stats) in state.namespaces.metadata_cache_stats() {
+        record_vfs_cache_stats(&namespace, stats);
+    }
+    state.handle.render()
 }
 
 #[cfg(test)]
@@ -74,6 +112,68 @@ mod tests {
     fn normalize_path_preserves_static() {
         assert_eq!(normalize_path("/api/v1/stat"), "/api/v1/stat");
         assert_eq!(normalize_path("/health"), "/health");
+        assert_eq!(normalize_path("/dav/docs/a.txt"), "/dav");
+    }
+
+    #[test]
+    fn vfs_operations_are_scraped_per_mount() {
+        use bytes::Bytes;
+        use fs9_core::{HandleRegistry, MemoryFs, MountTable, VfsRouter};
+        use fs9_sdk::{FsProvider, OpenFlags};
+        use std::time::Duration;
+
+        let recorder = prometheus_builder().build_recorder();
+        let handle = recorder.handle();
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+
+        metrics::with_local_recorder(&recorder, || {
+            runtime.block_on(async {
+                let vfs = VfsRouter::new(
+                    Arc::new(MountTable::new()),
+                    Arc::new(HandleRegistry::new(Duration::from_secs(60))),
+                );
+                vfs.mount_table()
+                    .mount("/data", "memfs", Arc::new(MemoryFs::new()))
+                    .await
+                    .unwrap();
+
+                let (h, _) = vfs
+                    .open("/data/a.txt", OpenFlags::create_file())
+                    .await
+                    .unwrap();
+                vfs.write(&h, 0, Bytes::from_static(b"hello"))
+                    .await
+                    .unwrap();
+                vfs.close(h, false).await.unwrap();
+                vfs.stat("/data/a.txt").await.unwrap();
+                vfs.readdir("/data").await.unwrap();
+                assert!(vfs.stat("/data/missing").await.is_err());
+                vfs.remove("/data/a.txt").await.unwrap();
+            });
+        });
+
+        let output = handle.render();
+        let labels = |op: &str| format!(r#"op="{op}",mount="/data",provider="memfs""#);
+        for op in ["open", "write", "close", "readdir", "remove"] {
+            let line = format!("fs9_vfs_ops_total{{{}}} 1", labels(op));
+            assert!(output.contains(&line), "missing {line} in:\n{output}");
+        }
+        let stat = format!("fs9_vfs_ops_total{{{}}} 2", labels("stat"));
+        assert!(output.contains(&stat), "missing {stat}");
+
+        let bucket = format!(
+            "fs9_vfs_op_duration_seconds_bucket{{{},le=\"+Inf\"}} 1",
+            labels("write")
+        );
+        assert!(output.contains(&bucket), "missing {bucket}");
+
+        let errors = format!(
+            "fs9_vfs_errors_total{{{},code=\"not_found\"}} 1",
+            labels("stat")
+        );
+        assert!(output.contains(&errors), "missing {errors}");
     }
 
     #[test]
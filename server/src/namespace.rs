@@ -1,9 +1,18 @@
+use bytes::Bytes;
 use dashmap::DashMap;
-use fs9_core::{start_cleanup_task, HandleRegistry, MountTable, VfsRouter};
+use fs9_core::{
+    start_cleanup_task, CacheStats, CircuitBreakerConfig, HandleRegistry, MetadataCacheConfig,
+    MountTable, VfsRouter,
+};
+use fs9_sdk::{
+    normalize_path, rename_target, FileInfo, FileType, FsError, FsProvider, FsResult, Handle,
+    OpenFlags, StatChanges,
+};
 use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
-use tokio::sync::RwLock;
+use tokio::sync::{Mutex, OwnedMutexGuard, RwLock};
 
 use crate::audit::AuditLog;
 use crate::state::HandleMap;
@@ -18,6 +27,10 @@ pub struct Namespace {
     pub handle_registry: Arc<HandleRegistry>,
     pub handle_map: Arc<RwLock<HandleMap>>,
     pub audit_log: Arc<AuditLog>,
+    pub quota: Quota,
+    /// Serializes quota-charging operations on the same path, so each one
+    /// sees the size the previous one left behind.
+    path_locks: PathLocks,
     #[allow(dead_code)]
     cleanup_task: tokio::task::JoinHandle<()>,
 }
@@ -25,9 +38,26 @@ pub struct Namespace {
 impl Namespace {
     #[must_use]
     pub fn new(name: &str, handle_ttl: Duration) -> Self {
+        Self::with_vfs_options(name, handle_ttl, None, None)
+    }
+
+    #[must_use]
+    pub fn with_vfs_options(
+        name: &str,
+        handle_ttl: Duration,
+        metadata_cache: Option<MetadataCacheConfig>,
+        circuit_breaker: Option<CircuitBreakerConfig>,
+    ) -> Self {
         let mount_table = Arc::new(MountTable::new());
         let handle_registry = Arc::new(HandleRegistry::new(handle_ttl));
-        let vfs = Arc::new(VfsRouter::new(mount_table.clone(), handle_registry.clone()));
+        let mut vfs = VfsRouter::new(mount_table.clone(), handle_registry.clone());
+        if let Some(config) = metadata_cache {
+            vfs = vfs.with_metadata_cache(config);
+        }
+        if let Some(config) = circuit_breaker {
+            vfs = vfs.with_circuit_breaker(config);
+        }
+        let vfs = Arc::new(vfs);
         let cleanup_task = start_cleanup_task(handle_registry.clone(), HANDLE_CLEANUP_INTERVAL);
 
         Self {
@@ -37,9 +67,319 @@ impl Namespace {
             handle_registry,
             handle_map: Arc::new(RwLock::new(HandleMap::new())),
             audit_log: Arc::new(AuditLog::default()),
+            quota: Quota::default(),
+            path_locks: PathLocks::default(),
             cleanup_task,
         }
     }
+
+    /// Open through the VFS. Truncating an existing file gives its bytes
+    /// back to the quota.
+    pub async fn open(&self, path: &str, flags: OpenFlags) -> FsResult<(Handle, FileInfo)> {
+        let _locked = self.path_locks.lock(&[path]).await;
+        let freed = if flags.truncate {
+            self.file_bytes(path).await
+        } else {
+            0
+        };
+        let opened = self.vfs.open(path, flags).await?;
+        self.quota.release(freed);
+        Ok(opened)
+    }
+
+    /// Write through the VFS. What the file would grow by is reserved
+    /// against the quota first; once written, the charge is settled on what
+    /// it actually grew by.
+    pub async fn write(&self, handle: &Handle, offset: u64, data: Bytes) -> FsResult<usize> {
+        let open = self
+            .handle_registry
+            .get(handle.id())
+            .await
+            .ok_or_else(|| FsError::invalid_handle(handle.id()))?;
+        let path = open.path().await?;
+        let _locked = self.path_locks.lock(&[&path]).await;
+        let size = self.file_bytes(&path).await;
+        let len = data.len() as u64;
+        let end = if open.flags().await?.append {
+            size + len
+        } else {
+            offset + len
+        };
+
+        let reserved = end.saturating_sub(size);
+        self.quota.reserve(reserved)?;
+        let written = self.vfs.write(handle, offset, data).await;
+        let grown = self.file_bytes(&path).await.saturating_sub(size);
+        self.quota.release(reserved.saturating_sub(grown));
+        self.quota.charge(grown.saturating_sub(reserved));
+        written
+    }
+
+    /// Change attributes through the VFS, charging a file's growth to the
+    /// quota and giving back bytes freed by shrinking it or by renaming
+    /// over another file.
+    pub async fn wstat(&self, path: &str, changes: StatChanges) -> FsResult<()> {
+        let target = match changes
+            .name
+            .as_deref()
+            .map(|name| rename_target(path, name))
+        {
+            Some(Ok(target)) => Some(target),
+            _ => None,
+        };
+        let mut locked_paths = vec![path];
+        locked_paths.extend(target.as_deref());
+        let _locked = self.path_locks.lock(&locked_paths).await;
+        let size = self.file_bytes(path).await;
+        let replaced = match &target {
+            Some(target) => self.file_bytes(target).await,
+            None => 0,
+        };
+        let new_size = changes.size;
+        let growth = new_size.map_or(0, |new| new.saturating_sub(size));
+
+        self.quota.reserve(growth)?;
+        if let Err(e) = self.vfs.wstat(path, changes).await {
+            self.quota.release(growth);
+            return Err(e);
+        }
+        let shrunk = new_size.map_or(0, |new| size.saturating_sub(new));
+        self.quota.release(shrunk + replaced);
+        Ok(())
+    }
+
+    /// Truncate through the VFS, with the same quota accounting as a size
+    /// change made with [`wstat`](Self::wstat).
+    pub async fn truncate(&self, path: &str, size: u64) -> FsResult<()> {
+        let _locked = self.path_locks.lock(&[path]).await;
+        let old_size = self.file_bytes(path).await;
+        let growth = size.saturating_sub(old_size);
+
+        self.quota.reserve(growth)?;
+        if let Err(e) = self.vfs.truncate(path, size).await {
+            self.quota.release(growth);
+            return Err(e);
+        }
+        self.quota.release(old_size.saturating_sub(size));
+        Ok(())
+    }
+
+    /// Remove through the VFS, giving the file's bytes back to the quota.
+    pub async fn remove(&self, path: &str) -> FsResult<()> {
+        let _locked = self.path_locks.lock(&[path]).await;
+        let freed = self.file_bytes(path).await;
+        self.vfs.remove(path).await?;
+        self.quota.release(freed);
+        Ok(())
+    }
+
+    /// Handles still open through the provider mounted at `path`. Such a
+    /// mount can't be removed or replaced without breaking them.
+    pub async fn mount_handles(&self, path: &str) -> FsResult<usize> {
+        if self.mount_table.get_mount_info(path).await.is_none() {
+            return Err(FsError::not_found(path));
+        }
+        let (provider, _) = self.mount_table.resolve(path).await?;
+        Ok(self.handle_registry.count_for_provider(&provider).await)
+    }
+
+    /// Size of the files under the mount at `path`, as they count against
+    /// the quota: zero for bind mounts, which share their source's files,
+    /// and for a provider that another mount also serves, whose files count
+    /// once. Take it once a mount is in place to charge it (see
+    /// [`Self::charge_mount`]), and before unmounting to release it after.
+    pub async fn mount_usage(&self, path: &str) -> u64 {
+        let Some((mount, _)) = self.mount_table.get_mount_info(path).await else {
+            return 0;
+        };
+        if mount.bind_source.is_some() {
+            return 0;
+        }
+        let (provider, root) = match self.mount_table.resolve(&mount.path).await {
+            Ok(resolved) => resolved,
+            Err(e) => {
+                tracing::warn!(namespace = %self.name, mount = %mount.path, error = %e, "Cannot count mount usage");
+                return 0;
+            }
+        };
+        for other in self.mount_table.list_mounts().await {
+            if other.path == mount.path || other.bind_source.is_some() {
+                continue;
+            }
+            if let Ok((shared, _)) = self.mount_table.resolve(&other.path).await {
+                if Arc::ptr_eq(&shared, &provider) {
+                    return 0;
+                }
+            }
+        }
+        tree_bytes(provider.as_ref(), &root).await
+    }
+
+    /// Charge the quota for the files already under the mount just made at
+    /// `path`, so that they count and give their bytes back when removed.
+    /// Usage claimed by calls in flight is kept.
+    pub async fn charge_mount(&self, path: &str) {
+        self.quota.charge(self.mount_usage(path).await);
+    }
+
+    /// Size of the file at `path`; zero for directories and missing files.
+    async fn file_bytes(&self, path: &str) -> u64 {
+        match self.vfs.stat(path).await {
+            Ok(info) if !info.is_dir() => info.size,
+            _ => 0,
+        }
+    }
+}
+
+/// Total size of the regular files at and below `root` in `provider`.
+async fn tree_bytes(provider: &dyn FsProvider, root: &str) -> u64 {
+    let mut total: u64 = 0;
+    let mut pending = vec![root.to_string()];
+    while let Some(dir) = pending.pop() {
+        let Ok(entries) = provider.readdir(&dir).await else {
+            continue;
+        };
+        for entry in entries {
+            match entry.file_type {
+                FileType::Directory => pending.push(entry.path),
+                FileType::Regular => total = total.saturating_add(entry.size),
+                FileType::Symlink => {}
+            }
+        }
+    }
+    total
+}
+
+/// Async locks keyed by path, created on first use and dropped again with
+/// their last guard.
+#[derive(Default)]
+struct PathLocks(DashMap<String, Arc<Mutex<()>>>);
+
+impl PathLocks {
+    /// Lock every path in `paths`, in a fixed order so that two callers
+    /// locking the same pair can't deadlock.
+    async fn lock(&self, paths: &[&str]) -> PathGuard<'_> {
+        let mut keys: Vec<String> = paths
+            .iter()
+            .map(|path| normalize_path(path).unwrap_or_else(|_| (*path).to_string()))
+            .collect();
+        keys.sort();
+        keys.dedup();
+
+        let mut guards = Vec::with_capacity(keys.len());
+        for key in &keys {
+            let lock = self.0.entry(key.clone()).or_default().clone();
+            guards.push(lock.lock_owned().await);
+        }
+        PathGuard {
+            locks: self,
+            keys,
+            guards,
+        }
+    }
+}
+
+struct PathGuard<'a> {
+    locks: &'a PathLocks,
+    keys: Vec<String>,
+    guards: Vec<OwnedMutexGuard<()>>,
+}
+
+impl Drop for PathGuard<'_> {
+    fn drop(&mut self) {
+        self.guards.clear();
+        for key in &self.keys {
+            self.locks
+                .0
+                .remove_if(key, |_, lock| Arc::strong_count(lock) == 1);
+        }
+    }
+}
+
+/// Byte budget shared by every mount of a namespace.
+///
+/// Usage is charged the size of the files already in each mount as it is
+/// made (see [`Namespace::charge_mount`]), then follows what files grow by
+/// through this server, less what truncating and removing files and
+/// unmounting give back.
+#[derive(Debug)]
+pub struct Quota {
+    /// `u64::MAX` when unlimited.
+    limit: AtomicU64,
+    used: AtomicU64,
+}
+
+impl Default for Quota {
+    fn default() -> Self {
+        Self {
+            limit: AtomicU64::new(u64::MAX),
+            used: AtomicU64::new(0),
+        }
+    }
+}
+
+impl Quota {
+    pub fn limit(&self) -> Option<u64> {
+        match self.limit.load(Ordering::Acquire) {
+            u64::MAX => None,
+            limit => Some(limit),
+        }
+    }
+
+    /// Set the budget; `None` lifts it. Lowering it below current usage
+    /// only blocks further growth.
+    pub fn set_limit(&self, limit: Option<u64>) {
+        self.limit
+            .store(limit.unwrap_or(u64::MAX), Ordering::Release);
+    }
+
+    pub fn used(&self) -> u64 {
+        self.used.load(Ordering::Acquire)
+    }
+
+    /// Claim `bytes` more, failing with `NoSpace` instead of going over the
+    /// limit. Concurrent claims never overshoot it together.
+    pub fn reserve(&self, bytes: u64) -> FsResult<()> {
+        if bytes == 0 {
+            return Ok(());
+        }
+        let limit = self.limit.load(Ordering::Acquire);
+        self.used
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |used| {
+                used.checked_add(bytes).filter(|&total| total <= limit)
+            })
+            .map(|_| ())
+            .map_err(|used| {
+                FsError::no_space(format!(
+                    "namespace quota of {limit} bytes exceeded ({used} used, {bytes} more requested)"
+                ))
+            })
+    }
+
+    /// Count `bytes` that are already in use, whatever the limit. For
+    /// growth that has happened and can't be refused any more.
+    pub fn charge(&self, bytes: u64) {
+        if bytes == 0 {
+            return;
+        }
+        let _ = self
+            .used
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |used| {
+                Some(used.saturating_add(bytes))
+            });
+    }
+
+    /// Give back `bytes` claimed earlier.
+    pub fn release(&self, bytes: u64) {
+        if bytes == 0 {
+            return;
+        }
+        let _ = self
+            .used
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |used| {
+                Some(used.saturating_sub(bytes))
+            });
+    }
 }
 
 /// Default namespace name used when auth is disabled or JWT has no `ns` field.
@@ -105,6 +445,8 @@ fn iso8601_now() -> String {
 pub struct NamespaceManager {
     namespaces: DashMap<String, (Arc<Namespace>, NamespaceInfo)>,
     handle_ttl: Duration,
+    metadata_cache: Option<MetadataCacheConfig>,
+    circuit_breaker: Option<CircuitBreakerConfig>,
 }
 
 impl NamespaceManager {
@@ -113,9 +455,60 @@ impl NamespaceManager {
         Self {
             namespaces: DashMap::new(),
             handle_ttl,
+            metadata_cache: None,
+            circuit_breaker: None,
+        }
+    }
+
+    /// Give every namespace created from now on a VFS metadata cache.
+    #[must_use]
+    pub fn with_metadata_cache(mut self, config: MetadataCacheConfig) -> Self {
+        self.metadata_cache = Some(config);
+        self
+    }
+
+    /// Give every namespace created from now on a circuit breaker per mount.
+    #[must_use]
+    pub fn with_circuit_breaker(mut self, config: CircuitBreakerConfig) -> Self {
+        self.circuit_breaker = Some(config);
+        self
+    }
+
+    /// A manager with the same settings but no namespaces.
+    #[must_use]
+    pub fn fresh(&self) -> Self {
+        Self {
+            namespaces: DashMap::new(),
+            handle_ttl: self.handle_ttl,
+            metadata_cache: self.metadata_cache,
+            circuit_breaker: self.circuit_breaker,
         }
     }
 
+    pub fn handle_ttl(&self) -> Duration {
+        self.handle_ttl
+    }
+
+    fn new_namespace(&self, name: &str) -> Arc<Namespace> {
+        Arc::new(Namespace::with_vfs_options(
+            name,
+            self.handle_ttl,
+            self.metadata_cache,
+            self.circuit_breaker,
+        ))
+    }
+
+    /// Metadata cache counters for every namespace that has the cache enabled.
+    pub fn metadata_cache_stats(&self) -> Vec<(String, CacheStats)> {
+        self.namespaces
+            .iter()
+            .filter_map(|r| {
+                let stats = r.value().0.vfs.cache_stats()?;
+                Some((r.key().clone(), stats))
+            })
+            .collect()
+    }
+
     pub async fn create(&self, name: &str, created_by: &str) -> Result<Arc<Namespace>, String> {
         validate_namespace_name(name)?;
 
@@ -123,7 +516,7 @@ impl NamespaceManager {
             return Err(format!("Namespace '{}' already exists", name));
         }
 
-        let ns = Arc::new(Namespace::new(name, self.handle_ttl));
+        let ns = self.new_namespace(name);
         let info = NamespaceInfo {
             name: name.to_string(),
             created_at: iso8601_now(),
@@ -149,7 +542,7 @@ impl NamespaceManager {
             return entry.value().0.clone();
         }
 
-        let ns = Arc::new(Namespace::new(name, self.handle_ttl));
+        let ns = self.new_namespace(name);
         let info = NamespaceInfo {
             name: name.to_string(),
             created_at: iso8601_now(),
@@ -197,6 +590,8 @@ impl NamespaceManager {
         self.namespaces.get(name).map(|r| r.value().1.clone())
     }
 
+    /// Close every open handle, then flush every mounted provider, in each
+    /// namespace. Used on shutdown; failures are logged, not returned.
     pub async fn drain_all(&self) {
         let namespaces: Vec<Arc<Namespace>> = self
             .namespaces
@@ -208,6 +603,150 @@ impl NamespaceManager {
             if count > 0 {
                 tracing::info!(namespace = %ns.name, closed = count, "Drained handles");
             }
+            for (mount, e) in ns.mount_table.flush_all().await {
+                tracing::error!(namespace = %ns.name, mount = %mount, error = %e, "Flush failed");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fs9_core::MemoryFs;
+
+    #[test]
+    fn concurrent_reservations_never_overshoot() {
+        let quota = Arc::new(Quota::default());
+        quota.set_limit(Some(1000));
+        let threads: Vec<_> = (0..8)
+            .map(|_| {
+                let quota = quota.clone();
+                std::thread::spawn(move || (0..100).filter(|_| quota.reserve(3).is_ok()).count())
+            })
+            .collect();
+        let granted: usize = threads.into_iter().map(|t| t.join().unwrap()).sum();
+        assert_eq!(granted, 333);
+        assert_eq!(quota.used(), 999);
+        assert!(matches!(quota.reserve(2), Err(FsError::NoSpace(_))));
+        quota.release(5000);
+        assert_eq!(quota.used(), 0);
+    }
+
+    #[tokio::test]
+    async fn overwrites_and_truncation_are_accounted() {
+        let ns = Namespace::new("quota", Duration::from_secs(60));
+        ns.mount_table
+            .mount("/", "memfs", Arc::new(MemoryFs::new()))
+            .await
+            .unwrap();
+        ns.quota.set_limit(Some(8));
+
+        let (handle, _) = ns.open("/f", OpenFlags::create_file()).await.unwrap();
+        ns.write(&handle, 0, Bytes::from_static(b"12345678"))
+            .await
+            .unwrap();
+        // Rewriting bytes the file already has costs nothing.
+        ns.write(&handle, 2, Bytes::from_static(b"ab"))
+            .await
+            .unwrap();
+        assert!(matches!(
+            ns.write(&handle, 8, Bytes::from_static(b"9")).await,
+            Err(FsError::NoSpace(_))
+        ));
+        ns.vfs.close(handle, false).await.unwrap();
+        assert_eq!(ns.quota.used(), 8);
+
+        ns.wstat("/f", StatChanges::truncate(3)).await.unwrap();
+        assert_eq!(ns.quota.used(), 3);
+        let (handle, _) = ns.open("/f", OpenFlags::create_truncate()).await.unwrap();
+        ns.vfs.close(handle, false).await.unwrap();
+        assert_eq!(ns.quota.used(), 0);
+    }
+
+    #[tokio::test]
+    async fn usage_is_seeded_from_existing_files() {
+        // Files written straight to the provider, before the namespace saw it.
+        let fs = Arc::new(MemoryFs::new());
+        fs.open("/dir", OpenFlags::create_dir()).await.unwrap();
+        for (path, data) in [("/a", &b"12345"[..]), ("/dir/b", b"123")] {
+            let (handle, _) = fs.open(path, OpenFlags::create_file()).await.unwrap();
+            fs.write(&handle, 0, Bytes::copy_from_slice(data))
+                .await
+                .unwrap();
+            fs.close(handle, false).await.unwrap();
+        }
+
+        let ns = Namespace::new("seeded", Duration::from_secs(60));
+        ns.mount_table.mount("/", "memfs", fs).await.unwrap();
+        ns.charge_mount("/").await;
+        ns.mount_table.bind("/", "/alias").await.unwrap();
+        ns.charge_mount("/alias").await;
+        assert_eq!(ns.quota.used(), 8);
+
+        ns.remove("/dir/b").await.unwrap();
+        assert_eq!(ns.quota.used(), 5);
+    }
+
+    #[tokio::test]
+    async fn mounts_are_counted_one_at_a_time() {
+        let shared = Arc::new(MemoryFs::new());
+        let (handle, _) = shared.open("/a", OpenFlags::create_file()).await.unwrap();
+        shared
+            .write(&handle, 0, Bytes::from_static(b"12345"))
+            .await
+            .unwrap();
+        shared.close(handle, false).await.unwrap();
+
+        let ns = Namespace::new("counted", Duration::from_secs(60));
+        ns.quota.reserve(100).unwrap();
+        ns.mount_table
+            .mount("/one", "memfs", shared.clone())
+            .await
+            .unwrap();
+        ns.charge_mount("/one").await;
+        // A pooled provider mounted twice holds its files once.
+        ns.mount_table.mount("/two", "memfs", shared).await.unwrap();
+        ns.charge_mount("/two").await;
+        assert_eq!(ns.quota.used(), 105);
+
+        let usage = ns.mount_usage("/two").await;
+        ns.mount_table.unmount("/two").await.unwrap();
+        ns.quota.release(usage);
+        assert_eq!(ns.quota.used(), 105);
+        let usage = ns.mount_usage("/one").await;
+        ns.mount_table.unmount("/one").await.unwrap();
+        ns.quota.release(usage);
+        // The claim made before any mount is still held.
+        assert_eq!(ns.quota.used(), 100);
+    }
+
+    #[tokio::test]
+    async fn concurrent_writes_to_one_file_are_charged_once() {
+        let ns = Arc::new(Namespace::new("racing", Duration::from_secs(60)));
+        ns.mount_table
+            .mount("/", "memfs", Arc::new(MemoryFs::new()))
+            .await
+            .unwrap();
+        ns.quota.set_limit(Some(100));
+        let (created, _) = ns.open("/f", OpenFlags::create_file()).await.unwrap();
+        ns.vfs.close(created, false).await.unwrap();
+
+        let writers: Vec<_> = (0..8)
+            .map(|_| {
+                let ns = ns.clone();
+                tokio::spawn(async move {
+                    let (handle, _) = ns.open("/f", OpenFlags::write()).await.unwrap();
+                    ns.write(&handle, 0, Bytes::from_static(b"0123456789"))
+                        .await
+                        .unwrap();
+                    ns.vfs.close(handle, false).await.unwrap();
+                })
+            })
+            .collect();
+        for writer in writers {
+            writer.await.unwrap();
         }
+        assert_eq!(ns.quota.used(), 10);
     }
 }
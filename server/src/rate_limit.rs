@@ -1,46 +1,307 @@
+//! Per-tenant request rate limiting.
+//!
+//! Every authenticated request draws from its namespace's bucket and from
+//! its user's bucket. On top of that each namespace has separate read and
+//! write budgets, so a tenant streaming uploads cannot starve its own
+//! readers. Read/write limits come from config and may be overridden per
+//! namespace, either in config or by fs9-meta when the namespace is loaded.
+//! A config reload swaps in the new limits without restarting the server.
+//!
+//! Rejected requests get `429 Too Many Requests` with a `Retry-After`
+//! header and are counted in `fs9_rate_limited_total`.
+
 use axum::{
     body::Body,
     extract::Request,
-    http::StatusCode,
+    http::{header, Method, StatusCode},
     middleware::Next,
     response::{IntoResponse, Response},
 };
+use dashmap::DashMap;
+use fs9_config::{NamespaceRateLimit, RateLimitConfig};
+use governor::clock::{Clock, DefaultClock};
 use governor::{DefaultKeyedRateLimiter, Quota, RateLimiter};
 use std::num::NonZeroU32;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
 
 use crate::auth::RequestContext;
 
+/// The budget a request is charged to besides the namespace and user ones.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum OperationClass {
+    Read,
+    Write,
+}
+
+impl OperationClass {
+    /// Safe methods and `read` calls are reads; everything else may change
+    /// the namespace and counts as a write.
+    #[must_use]
+    pub fn of(method: &Method, route: &str) -> Self {
+        let is_read = matches!(*method, Method::GET | Method::HEAD | Method::OPTIONS)
+            || method.as_str() == "PROPFIND"
+            || crate::access_log::operation(route) == "read";
+        if is_read {
+            Self::Read
+        } else {
+            Self::Write
+        }
+    }
+
+    #[must_use]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Read => "read",
+            Self::Write => "write",
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct RateLimitState {
-    ns_limiter: Arc<DefaultKeyedRateLimiter<String>>,
-    user_limiter: Arc<DefaultKeyedRateLimiter<String>>,
+    limits: Arc<RwLock<Arc<Limits>>>,
+    /// Namespace limits set at runtime by fs9-meta. They survive config
+    /// reloads and win over the config's own overrides.
+    namespace_limits: Arc<DashMap<String, NamespaceRateLimit>>,
+}
+
+/// The limiters built from one version of the config.
+struct Limits {
+    ns_limiter: DefaultKeyedRateLimiter<String>,
+    user_limiter: DefaultKeyedRateLimiter<String>,
+    classes: ClassLimiter,
     enabled: bool,
 }
 
+impl Limits {
+    fn from_config(config: &RateLimitConfig) -> Self {
+        let ns_quota =
+            NonZeroU32::new(config.namespace_qps).unwrap_or(NonZeroU32::new(1000).unwrap());
+        let user_quota = NonZeroU32::new(config.user_qps).unwrap_or(NonZeroU32::new(100).unwrap());
+        let classes = ClassLimiter {
+            read_qps: config.read_qps,
+            write_qps: config.write_qps,
+            overrides: config
+                .namespaces
+                .iter()
+                .map(|(ns, limits)| (ns.clone(), *limits))
+                .collect(),
+            buckets: DashMap::new(),
+            idle: Duration::from_secs(config.idle_secs.max(1)),
+            max_buckets: config.max_buckets.max(1),
+            last_sweep: Mutex::new(Instant::now()),
+        };
+
+        Self {
+            ns_limiter: RateLimiter::dashmap(Quota::per_second(ns_quota)),
+            user_limiter: RateLimiter::dashmap(Quota::per_second(user_quota)),
+            classes,
+            enabled: config.enabled,
+        }
+    }
+
+    /// Charge one request; on rejection returns which limit was hit and
+    /// how long until it would be admitted.
+    fn check(
+        &self,
+        ctx: &RequestContext,
+        class: OperationClass,
+        now: Instant,
+    ) -> Result<(), (&'static str, Duration)> {
+        self.sweep(now);
+
+        // The class bucket goes first: a request it turns away must not use
+        // up the namespace and user budgets, and unlike those it can give
+        // its token back when they turn the request away.
+        self.classes
+            .check(&ctx.ns, class, now)
+            .map_err(|wait| (class.as_str(), wait))?;
+
+        let clock = DefaultClock::default();
+        if let Err(not_until) = self.ns_limiter.check_key(&ctx.ns) {
+            self.classes.refund(&ctx.ns, class);
+            return Err(("namespace", not_until.wait_time_from(clock.now())));
+        }
+
+        let user_key = format!("{}:{}", ctx.ns, ctx.user_id);
+        if let Err(not_until) = self.user_limiter.check_key(&user_key) {
+            self.classes.refund(&ctx.ns, class);
+            return Err(("user", not_until.wait_time_from(clock.now())));
+        }
+        Ok(())
+    }
+
+    /// Drop the state of namespaces and users that have gone idle, at most
+    /// once per idle period.
+    fn sweep(&self, now: Instant) {
+        {
+            let mut last_sweep = self.classes.last_sweep.lock().unwrap();
+            if now.saturating_duration_since(*last_sweep) < self.classes.idle {
+                return;
+            }
+            *last_sweep = now;
+        }
+        self.ns_limiter.retain_recent();
+        self.ns_limiter.shrink_to_fit();
+        self.user_limiter.retain_recent();
+        self.user_limiter.shrink_to_fit();
+        self.classes.evict_idle(now);
+    }
+}
+
 impl RateLimitState {
     pub fn new(ns_qps: u32, user_qps: u32) -> Self {
-        let ns_quota = NonZeroU32::new(ns_qps).unwrap_or(NonZeroU32::new(1000).unwrap());
-        let user_quota = NonZeroU32::new(user_qps).unwrap_or(NonZeroU32::new(100).unwrap());
+        Self::from_config(&RateLimitConfig {
+            enabled: true,
+            namespace_qps: ns_qps,
+            user_qps,
+            ..RateLimitConfig::default()
+        })
+    }
 
+    #[must_use]
+    pub fn from_config(config: &RateLimitConfig) -> Self {
         Self {
-            ns_limiter: Arc::new(RateLimiter::dashmap(Quota::per_second(ns_quota))),
-            user_limiter: Arc::new(RateLimiter::dashmap(Quota::per_second(user_quota))),
-            enabled: true,
+            limits: Arc::new(RwLock::new(Arc::new(Limits::from_config(config)))),
+            namespace_limits: Arc::new(DashMap::new()),
         }
     }
 
     pub fn disabled() -> Self {
+        Self::from_config(&RateLimitConfig::default())
+    }
+
+    /// Switch every clone of this state to the limits in `config`, e.g.
+    /// after a config reload. All buckets start over; limits set through
+    /// [`Self::set_namespace_limits`] are kept.
+    pub fn reconfigure(&self, config: &RateLimitConfig) {
+        let limits = Limits::from_config(config);
+        for entry in self.namespace_limits.iter() {
+            limits.classes.overrides.insert(entry.key().clone(), *entry);
+        }
+        *self.limits.write().unwrap() = Arc::new(limits);
+    }
+
+    #[must_use]
+    pub fn enabled(&self) -> bool {
+        self.current().enabled
+    }
+
+    /// Replace the read/write limits of one namespace, e.g. with the ones
+    /// fs9-meta keeps for it. Its buckets start over at the new limits.
+    pub fn set_namespace_limits(&self, ns: &str, limits: NamespaceRateLimit) {
+        self.namespace_limits.insert(ns.to_string(), limits);
+        let current = self.current();
+        current.classes.overrides.insert(ns.to_string(), limits);
+        current
+            .classes
+            .buckets
+            .retain(|(bucket_ns, _), _| bucket_ns != ns);
+    }
+
+    fn current(&self) -> Arc<Limits> {
+        self.limits.read().unwrap().clone()
+    }
+}
+
+/// Token buckets keyed by (namespace, operation class).
+struct ClassLimiter {
+    read_qps: u32,
+    write_qps: u32,
+    overrides: DashMap<String, NamespaceRateLimit>,
+    buckets: DashMap<(String, OperationClass), Bucket>,
+    idle: Duration,
+    max_buckets: usize,
+    last_sweep: Mutex<Instant>,
+}
+
+impl ClassLimiter {
+    /// Requests per second `ns` may make in `class`; 0 is unlimited.
+    fn qps(&self, ns: &str, class: OperationClass) -> u32 {
+        let limits = self.overrides.get(ns).map(|entry| *entry);
+        match class {
+            OperationClass::Read => limits.and_then(|l| l.read_qps).unwrap_or(self.read_qps),
+            OperationClass::Write => limits.and_then(|l| l.write_qps).unwrap_or(self.write_qps),
+        }
+    }
+
+    fn check(&self, ns: &str, class: OperationClass, now: Instant) -> Result<(), Duration> {
+        let qps = self.qps(ns, class);
+        if qps == 0 {
+            return Ok(());
+        }
+
+        let key = (ns.to_string(), class);
+        if !self.buckets.contains_key(&key) && self.buckets.len() >= self.max_buckets {
+            self.evict_idle(now);
+            if self.buckets.len() >= self.max_buckets {
+                self.evict_least_recent();
+            }
+        }
+        self.buckets
+            .entry(key)
+            .or_insert_with(|| Bucket::full(qps, now))
+            .take(now)
+    }
+
+    /// Give back the token a request took from `ns`'s `class` bucket.
+    fn refund(&self, ns: &str, class: OperationClass) {
+        if let Some(mut bucket) = self.buckets.get_mut(&(ns.to_string(), class)) {
+            bucket.refund();
+        }
+    }
+
+    fn evict_idle(&self, now: Instant) {
+        self.buckets
+            .retain(|_, bucket| now.saturating_duration_since(bucket.last_seen) < self.idle);
+    }
+
+    fn evict_least_recent(&self) {
+        let oldest = self
+            .buckets
+            .iter()
+            .min_by_key(|entry| entry.last_seen)
+            .map(|entry| entry.key().clone());
+        if let Some(key) = oldest {
+            self.buckets.remove(&key);
+        }
+    }
+}
+
+/// A token bucket refilled at `qps` tokens per second, holding at most one
+/// second's worth.
+struct Bucket {
+    qps: u32,
+    tokens: f64,
+    last_seen: Instant,
+}
+
+impl Bucket {
+    fn full(qps: u32, now: Instant) -> Self {
         Self {
-            ns_limiter: Arc::new(RateLimiter::dashmap(Quota::per_second(
-                NonZeroU32::new(1).unwrap(),
-            ))),
-            user_limiter: Arc::new(RateLimiter::dashmap(Quota::per_second(
-                NonZeroU32::new(1).unwrap(),
-            ))),
-            enabled: false,
+            qps,
+            tokens: f64::from(qps),
+            last_seen: now,
         }
     }
+
+    fn take(&mut self, now: Instant) -> Result<(), Duration> {
+        let rate = f64::from(self.qps);
+        let elapsed = now.saturating_duration_since(self.last_seen).as_secs_f64();
+        self.tokens = elapsed.mul_add(rate, self.tokens).min(rate);
+        self.last_seen = now;
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((1.0 - self.tokens) / rate))
+        }
+    }
+
+    fn refund(&mut self) {
+        self.tokens = (self.tokens + 1.0).min(f64::from(self.qps));
+    }
 }
 
 pub async fn rate_limit_middleware(
@@ -48,7 +309,8 @@ pub async fn rate_limit_middleware(
     request: Request<Body>,
     next: Next,
 ) -> Response {
-    if !state.enabled {
+    let limits = state.current();
+    if !limits.enabled {
         return next.run(request).await;
     }
 
@@ -58,18 +320,22 @@ pub async fn rate_limit_middleware(
     }
 
     if let Some(ctx) = request.extensions().get::<RequestContext>() {
-        if state.ns_limiter.check_key(&ctx.ns).is_err() {
+        let class = OperationClass::of(request.method(), path);
+        if let Err((limit, wait)) = limits.check(ctx, class, Instant::now()) {
+            metrics::counter!(
+                "fs9_rate_limited_total",
+                "namespace" => ctx.ns.clone(),
+                "limit" => limit,
+            )
+            .increment(1);
+            let retry_after = wait.as_secs() + u64::from(wait.subsec_nanos() > 0);
             return (
                 StatusCode::TOO_MANY_REQUESTS,
-                "Namespace rate limit exceeded",
+                [(header::RETRY_AFTER, retry_after.max(1).to_string())],
+                format!("Rate limit exceeded ({limit})"),
             )
                 .into_response();
         }
-
-        let user_key = format!("{}:{}", ctx.ns, ctx.user_id);
-        if state.user_limiter.check_key(&user_key).is_err() {
-            return (StatusCode::TOO_MANY_REQUESTS, "User rate limit exceeded").into_response();
-        }
     }
 
     next.run(request).await
@@ -78,16 +344,248 @@ pub async fn rate_limit_middleware(
 #[cfg(test)]
 mod tests {
     use super::*;
+    use axum::{
+        middleware,
+        routing::{get, post},
+        Router,
+    };
+    use tower::Service;
 
     #[test]
     fn rate_limit_state_creation() {
         let state = RateLimitState::new(500, 50);
-        assert!(state.enabled);
+        assert!(state.enabled());
     }
 
     #[test]
     fn rate_limit_state_disabled() {
         let state = RateLimitState::disabled();
-        assert!(!state.enabled);
+        assert!(!state.enabled());
+    }
+
+    #[test]
+    fn operation_class_follows_method_and_route() {
+        assert_eq!(
+            OperationClass::of(&Method::GET, "/api/v1/stat"),
+            OperationClass::Read
+        );
+        assert_eq!(
+            OperationClass::of(&Method::POST, "/tenant/api/v1/read"),
+            OperationClass::Read
+        );
+        assert_eq!(
+            OperationClass::of(&Method::POST, "/api/v1/write"),
+            OperationClass::Write
+        );
+        assert_eq!(
+            OperationClass::of(&Method::DELETE, "/api/v1/remove"),
+            OperationClass::Write
+        );
+    }
+
+    fn app(state: RateLimitState) -> Router {
+        async fn ok() -> &'static str {
+            "ok"
+        }
+
+        async fn authenticate(mut request: Request<Body>, next: Next) -> Response {
+            let ns = request.headers()["x-test-ns"].to_str().unwrap().to_string();
+            request.extensions_mut().insert(RequestContext {
+                ns,
+                user_id: "alice".to_string(),
+                roles: Vec::new(),
+                scopes: Vec::new(),
+            });
+            next.run(request).await
+        }
+
+        Router::new()
+            .route("/api/v1/stat", get(ok))
+            .route("/api/v1/write", post(ok))
+            .layer(middleware::from_fn_with_state(state, rate_limit_middleware))
+            .layer(middleware::from_fn(authenticate))
+    }
+
+    async fn send(app: &mut Router, method: Method, uri: &str, ns: &str) -> Response {
+        let request = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header("x-test-ns", ns)
+            .body(Body::empty())
+            .unwrap();
+        app.call(request).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn write_limit_rejects_with_retry_after_per_namespace() {
+        let mut app = app(RateLimitState::from_config(&RateLimitConfig {
+            enabled: true,
+            write_qps: 2,
+            ..RateLimitConfig::default()
+        }));
+
+        for _ in 0..2 {
+            let response = send(&mut app, Method::POST, "/api/v1/write", "acme").await;
+            assert_eq!(response.status(), StatusCode::OK);
+        }
+        let response = send(&mut app, Method::POST, "/api/v1/write", "acme").await;
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(response.headers()[header::RETRY_AFTER], "1");
+
+        // Reads in the same namespace and writes in another are unaffected.
+        let response = send(&mut app, Method::GET, "/api/v1/stat", "acme").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let response = send(&mut app, Method::POST, "/api/v1/write", "globex").await;
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn each_limit_charges_only_admitted_requests() {
+        let state = RateLimitState::from_config(&RateLimitConfig {
+            enabled: true,
+            namespace_qps: 2,
+            write_qps: 1,
+            ..RateLimitConfig::default()
+        });
+        let mut first = app(state);
+
+        let response = send(&mut first, Method::POST, "/api/v1/write", "acme").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        // Turned away by the write bucket, leaving the namespace budget.
+        let response = send(&mut first, Method::POST, "/api/v1/write", "acme").await;
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        let response = send(&mut first, Method::GET, "/api/v1/stat", "acme").await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        // Turned away by the namespace bucket, the write bucket gets its
+        // token back.
+        let state = RateLimitState::from_config(&RateLimitConfig {
+            enabled: true,
+            namespace_qps: 1,
+            write_qps: 1,
+            ..RateLimitConfig::default()
+        });
+        let mut second = app(state.clone());
+        let response = send(&mut second, Method::GET, "/api/v1/stat", "acme").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let response = send(&mut second, Method::POST, "/api/v1/write", "acme").await;
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert!(state
+            .current()
+            .classes
+            .check("acme", OperationClass::Write, Instant::now())
+            .is_ok());
+    }
+
+    #[tokio::test]
+    async fn namespace_limits_override_the_defaults() {
+        let state = RateLimitState::from_config(&RateLimitConfig {
+            enabled: true,
+            write_qps: 1,
+            namespaces: [(
+                "bulk".to_string(),
+                NamespaceRateLimit {
+                    read_qps: None,
+                    write_qps: Some(3),
+                },
+            )]
+            .into(),
+            ..RateLimitConfig::default()
+        });
+        state.set_namespace_limits(
+            "quiet",
+            NamespaceRateLimit {
+                read_qps: Some(1),
+                write_qps: None,
+            },
+        );
+        let mut app = app(state);
+
+        for _ in 0..3 {
+            let response = send(&mut app, Method::POST, "/api/v1/write", "bulk").await;
+            assert_eq!(response.status(), StatusCode::OK);
+        }
+        let response = send(&mut app, Method::POST, "/api/v1/write", "bulk").await;
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+
+        let response = send(&mut app, Method::GET, "/api/v1/stat", "quiet").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let response = send(&mut app, Method::GET, "/api/v1/stat", "quiet").await;
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    }
+
+    #[tokio::test]
+    async fn reconfigure_applies_to_running_routers() {
+        let state = RateLimitState::disabled();
+        state.set_namespace_limits(
+            "quiet",
+            NamespaceRateLimit {
+                read_qps: Some(1),
+                write_qps: None,
+            },
+        );
+        let mut app = app(state.clone());
+        for _ in 0..3 {
+            let response = send(&mut app, Method::POST, "/api/v1/write", "acme").await;
+            assert_eq!(response.status(), StatusCode::OK);
+        }
+
+        state.reconfigure(&RateLimitConfig {
+            enabled: true,
+            write_qps: 1,
+            ..RateLimitConfig::default()
+        });
+        let response = send(&mut app, Method::POST, "/api/v1/write", "acme").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let response = send(&mut app, Method::POST, "/api/v1/write", "acme").await;
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+
+        // Limits fs9-meta set outlive the reload.
+        let response = send(&mut app, Method::GET, "/api/v1/stat", "quiet").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let response = send(&mut app, Method::GET, "/api/v1/stat", "quiet").await;
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+
+        state.reconfigure(&RateLimitConfig::default());
+        let response = send(&mut app, Method::POST, "/api/v1/write", "acme").await;
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[test]
+    fn buckets_refill_and_are_bounded() {
+        let state = RateLimitState::from_config(&RateLimitConfig {
+            enabled: true,
+            write_qps: 1,
+            idle_secs: 60,
+            max_buckets: 2,
+            ..RateLimitConfig::default()
+        });
+        let limits = state.current();
+        let limiter = &limits.classes;
+        let start = Instant::now();
+
+        assert!(limiter.check("a", OperationClass::Write, start).is_ok());
+        let wait = limiter
+            .check("a", OperationClass::Write, start)
+            .unwrap_err();
+        assert!(wait <= Duration::from_secs(1));
+        assert!(limiter
+            .check("a", OperationClass::Write, start + Duration::from_secs(1))
+            .is_ok());
+
+        // A third namespace pushes out the least recently used one.
+        let later = start + Duration::from_secs(2);
+        assert!(limiter.check("b", OperationClass::Write, later).is_ok());
+        assert!(limiter
+            .check("c", OperationClass::Write, later + Duration::from_secs(1))
+            .is_ok());
+        assert_eq!(limiter.buckets.len(), 2);
+        assert!(!limiter
+            .buckets
+            .contains_key(&("a".to_string(), OperationClass::Write)));
+
+        // Idle namespaces are dropped altogether.
+        limiter.evict_idle(later + Duration::from_secs(120));
+        assert!(limiter.buckets.is_empty());
     }
 }
@@ -0,0 +1,82 @@
+//! Graceful shutdown: stop accepting connections, give in-flight requests a
+//! bounded time to finish, then close open handles and flush every mounted
+//! provider so write-back caches (PageFS) reach storage before exit.
+
+use std::future::{Future, IntoFuture};
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::Router;
+use tokio::net::TcpListener;
+use tokio::sync::Notify;
+
+use crate::state::AppState;
+
+/// How long in-flight requests may run after shutdown starts, unless
+/// `server.shutdown_timeout_secs` says otherwise.
+pub const DEFAULT_DRAIN_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Serve `app` on `listener` until `signal` resolves, then shut down.
+///
+/// New connections are refused as soon as `signal` fires. Requests already
+/// running get `drain_timeout` to complete; whatever is still running after
+/// that is abandoned. Either way, every namespace's handles are closed and
+/// its providers flushed before this returns.
+pub async fn serve(
+    listener: TcpListener,
+    app: Router,
+    state: Arc<AppState>,
+    signal: impl Future<Output = ()> + Send + 'static,
+    drain_timeout: Duration,
+) -> std::io::Result<()> {
+    let started = Arc::new(Notify::new());
+    let trigger = started.clone();
+    let server = axum::serve(listener, app)
+        .with_graceful_shutdown(async move {
+            signal.await;
+            trigger.notify_one();
+        })
+        .into_future();
+    let deadline = async {
+        started.notified().await;
+        tokio::time::sleep(drain_timeout).await;
+    };
+
+    tokio::select! {
+        result = server => result?,
+        () = deadline => tracing::warn!(
+            timeout_secs = drain_timeout.as_secs(),
+            "In-flight requests did not drain in time"
+        ),
+    }
+
+    tracing::info!("Closing handles and flushing providers...");
+    state.namespace_manager.drain_all().await;
+    tracing::info!("Shutdown complete");
+    Ok(())
+}
+
+/// Resolves on Ctrl+C or, on Unix, SIGTERM.
+pub async fn signal() {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c()
+            .await
+            .expect("install Ctrl+C handler");
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("install SIGTERM handler")
+            .recv()
+            .await;
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        () = ctrl_c => tracing::info!("Received Ctrl+C, shutting down"),
+        () = terminate => tracing::info!("Received SIGTERM, shutting down"),
+    }
+}
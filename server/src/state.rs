@@ -1,13 +1,15 @@
-use fs9_core::{PluginManager, ProviderRegistry};
+use fs9_core::{PluginManager, ProviderPool, ProviderRegistry};
 use std::collections::HashSet;
 use std::sync::Arc;
 use std::time::Duration;
 use tokio::sync::RwLock;
 
+use crate::auth::JwtConfig;
 use crate::circuit_breaker::CircuitBreaker;
 use crate::db9_client::Db9Client;
 use crate::meta_client::MetaClient;
 use crate::namespace::{Namespace, NamespaceManager, DEFAULT_NAMESPACE};
+use crate::rate_limit::RateLimitState;
 use crate::token_cache::TokenCache;
 use crate::token_revocation::RevocationSet;
 use fs9_config::DefaultPagefsConfig;
@@ -16,13 +18,17 @@ pub struct AppState {
     pub namespace_manager: Arc<NamespaceManager>,
     pub plugin_manager: Arc<PluginManager>,
     pub provider_registry: Arc<ProviderRegistry>,
-    pub jwt_secret: RwLock<String>,
+    /// Live provider instances, shared by mounts of the same config.
+    pub provider_pool: Arc<ProviderPool>,
+    /// Keys for local token refresh; the secret is empty until set.
+    pub jwt_config: RwLock<JwtConfig>,
     pub meta_client: Option<MetaClient>,
     pub db9_client: Option<Db9Client>,
     pub default_pagefs: Option<DefaultPagefsConfig>,
     pub token_cache: TokenCache,
     pub circuit_breaker: Arc<CircuitBreaker>,
     pub revocation_set: Arc<RevocationSet>,
+    pub rate_limit: RateLimitState,
 }
 
 pub struct HandleMap {
@@ -113,19 +119,47 @@ impl AppState {
             namespace_manager,
             plugin_manager,
             provider_registry,
-            jwt_secret: RwLock::new(String::new()),
+            provider_pool: Arc::new(ProviderPool::new()),
+            jwt_config: RwLock::new(JwtConfig::new(String::new())),
             meta_client,
             db9_client,
             default_pagefs,
             token_cache,
             circuit_breaker,
             revocation_set,
+            rate_limit: RateLimitState::disabled(),
         }
     }
 
-    /// Set the JWT secret for token refresh
-    pub async fn set_jwt_secret(&self, secret: String) {
-        *self.jwt_secret.write().await = secret;
+    /// Enable the VFS metadata cache for all namespaces. Call before any
+    /// namespace is created; existing namespaces are dropped.
+    #[must_use]
+    pub fn with_metadata_cache(mut self, config: fs9_core::MetadataCacheConfig) -> Self {
+        self.namespace_manager =
+            Arc::new(self.namespace_manager.fresh().with_metadata_cache(config));
+        self
+    }
+
+    /// Give each mount in every namespace its own circuit breaker, so that a
+    /// failing backend is answered with 503 instead of being waited on.
+    /// Call before any namespace is created; existing namespaces are dropped.
+    #[must_use]
+    pub fn with_mount_circuit_breaker(mut self, config: fs9_core::CircuitBreakerConfig) -> Self {
+        self.namespace_manager =
+            Arc::new(self.namespace_manager.fresh().with_circuit_breaker(config));
+        self
+    }
+
+    /// Limit request rates as configured; see [`crate::rate_limit`].
+    #[must_use]
+    pub fn with_rate_limit(mut self, config: &fs9_config::RateLimitConfig) -> Self {
+        self.rate_limit = RateLimitState::from_config(config);
+        self
+    }
+
+    /// Set the JWT keys for token refresh
+    pub async fn set_jwt_config(&self, config: JwtConfig) {
+        *self.jwt_config.write().await = config;
     }
 
     /// Get the default namespace, creating it if needed.
@@ -7,6 +7,8 @@
 //! - Background cleanup of expired entries
 
 use moka::future::Cache;
+
+use crate::auth::PathGrant;
 use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 fn now_unix_secs() -> u64 {
@@ -22,6 +24,7 @@ pub struct CachedToken {
     pub user_id: String,
     pub namespace: String,
     pub roles: Vec<String>,
+    pub scopes: Vec<PathGrant>,
     /// JWT expiration (seconds since Unix epoch). Cache entries never outlive this.
     pub expires_at: u64,
     pub cached_at: Instant,
@@ -98,12 +101,14 @@ impl TokenCache {
         user_id: String,
         namespace: String,
         roles: Vec<String>,
+        scopes: Vec<PathGrant>,
         expires_at: u64,
     ) {
         let entry = CachedToken {
             user_id,
             namespace,
             roles,
+            scopes,
             expires_at,
             cached_at: Instant::now(),
         };
@@ -172,6 +177,7 @@ mod tests {
                 "user1".to_string(),
                 "ns1".to_string(),
                 vec!["admin".to_string()],
+                Vec::new(),
                 now_unix() + 60,
             )
             .await;
@@ -200,6 +206,7 @@ mod tests {
                 "user1".to_string(),
                 "ns1".to_string(),
                 vec![],
+                Vec::new(),
                 now_unix() + 60,
             )
             .await;
@@ -222,6 +229,7 @@ mod tests {
                 "user1".to_string(),
                 "ns1".to_string(),
                 vec![],
+                Vec::new(),
                 now_unix() + 60,
             )
             .await;
@@ -241,6 +249,7 @@ mod tests {
                 "u1".to_string(),
                 "ns1".to_string(),
                 vec![],
+                Vec::new(),
                 now_unix() + 60,
             )
             .await;
@@ -253,6 +262,7 @@ mod tests {
                 "u2".to_string(),
                 "ns2".to_string(),
                 vec![],
+                Vec::new(),
                 now_unix() + 60,
             )
             .await;
@@ -273,6 +283,7 @@ mod tests {
                 "user1".to_string(),
                 "ns1".to_string(),
                 vec![],
+                Vec::new(),
                 now_unix(),
             )
             .await;
@@ -291,6 +302,7 @@ mod tests {
                     format!("u{i}"),
                     format!("ns{i}"),
                     vec![],
+                    Vec::new(),
                     now_unix() + 60,
                 )
                 .await;
@@ -1,9 +1,26 @@
+use base64::Engine;
 use moka::future::Cache;
+use serde::Deserialize;
 use sha2::{Digest, Sha256};
-use std::time::Duration;
+use std::collections::HashMap;
+use std::sync::RwLock;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// How long revoked token hashes are remembered, and how long a token id
+/// revoked without a known expiry stays blacklisted.
+pub const RETENTION: Duration = Duration::from_secs(25 * 3600);
+
+/// A blacklisted `jti` and the Unix time its token expires, after which
+/// the entry is dropped.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RevokedId {
+    pub jti: String,
+    pub expires_at: u64,
+}
 
 pub struct RevocationSet {
     revoked: Cache<String, ()>,
+    revoked_ids: RwLock<HashMap<String, u64>>,
 }
 
 impl RevocationSet {
@@ -11,19 +28,70 @@ impl RevocationSet {
         Self {
             revoked: Cache::builder()
                 .max_capacity(max_capacity)
-                .time_to_live(Duration::from_secs(25 * 3600))
+                .time_to_live(RETENTION)
                 .build(),
+            revoked_ids: RwLock::new(HashMap::new()),
         }
     }
 
+    /// Revoke a token. If it is a JWT carrying a `jti`, that id is
+    /// blacklisted too, until the token's own `exp`.
     pub async fn revoke(&self, token: &str) {
         let hash = token_hash(token);
         self.revoked.insert(hash, ()).await;
+        if let Some(id) = token_id(token) {
+            self.revoke_id(&id.jti, id.exp);
+        }
     }
 
     pub async fn is_revoked(&self, token: &str) -> bool {
         let hash = token_hash(token);
-        self.revoked.get(&hash).await.is_some()
+        if self.revoked.get(&hash).await.is_some() {
+            return true;
+        }
+        if self.revoked_ids.read().unwrap().is_empty() {
+            return false;
+        }
+        token_id(token).is_some_and(|id| self.is_id_revoked(&id.jti))
+    }
+
+    /// Blacklist every token carrying `jti` until `expires_at` (Unix
+    /// seconds), or for [`RETENTION`] when the expiry is unknown.
+    pub fn revoke_id(&self, jti: &str, expires_at: Option<u64>) {
+        let now = now_secs();
+        let expires_at = expires_at.unwrap_or(now + RETENTION.as_secs());
+        let mut ids = self.revoked_ids.write().unwrap();
+        ids.retain(|_, exp| *exp > now);
+        if expires_at > now {
+            let exp = ids.entry(jti.to_string()).or_insert(expires_at);
+            *exp = (*exp).max(expires_at);
+        }
+    }
+
+    pub fn is_id_revoked(&self, jti: &str) -> bool {
+        self.revoked_ids
+            .read()
+            .unwrap()
+            .get(jti)
+            .is_some_and(|exp| *exp > now_secs())
+    }
+
+    /// Token ids that are still blacklisted, soonest to expire first.
+    pub fn revoked_ids(&self) -> Vec<RevokedId> {
+        let now = now_secs();
+        let mut ids: Vec<RevokedId> = self
+            .revoked_ids
+            .read()
+            .unwrap()
+            .iter()
+            .filter(|(_, exp)| **exp > now)
+            .map(|(jti, exp)| RevokedId {
+                jti: jti.clone(),
+                expires_at: *exp,
+            })
+            .collect();
+        ids.sort_by(|a, b| (a.expires_at, &a.jti).cmp(&(b.expires_at, &b.jti)));
+        ids
     }
 
     pub async fn count(&self) -> u64 {
@@ -39,6 +107,34 @@ pub fn token_hash(token: &str) -> String {
     hex::encode(&result[..16])
 }
 
+#[derive(Deserialize)]
+struct TokenId {
+    jti: String,
+    exp: Option<u64>,
+}
+
+/// The `jti` and `exp` claims of a JWT, read without checking its
+/// signature. Only used to reject tokens, so a forged id gains nothing.
+fn token_id(token: &str) -> Option<TokenId> {
+    let mut parts = token.split('.');
+    let (Some(_), Some(payload), Some(_), None) =
+        (parts.next(), parts.next(), parts.next(), parts.next())
+    else {
+        return None;
+    };
+    let payload = base64::engine::general_purpose::URL_SAFE_NO_PAD
+        .decode(payload)
+        .ok()?;
+    serde_json::from_slice(&payload).ok()
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
 mod hex {
     pub fn encode(bytes: &[u8]) -> String {
         bytes.iter().map(|b| format!("{b:02x}")).collect()
@@ -71,6 +167,45 @@ mod tests {
         assert_eq!(set.count().await, 3);
     }
 
+    fn jwt_with(claims: &serde_json::Value) -> String {
+        let engine = base64::engine::general_purpose::URL_SAFE_NO_PAD;
+        format!(
+            "{}.{}.sig",
+            engine.encode(r#"{"alg":"HS256"}"#),
+            engine.encode(claims.to_string())
+        )
+    }
+
+    #[tokio::test]
+    async fn revoked_id_rejects_every_token_carrying_it() {
+        let set = RevocationSet::new(1000);
+        let exp = now_secs() + 60;
+        let first = jwt_with(&serde_json::json!({"sub": "a", "jti": "id-1", "exp": exp}));
+        let reissued = jwt_with(&serde_json::json!({"sub": "b", "jti": "id-1", "exp": exp}));
+        let other = jwt_with(&serde_json::json!({"sub": "a", "jti": "id-2", "exp": exp}));
+
+        set.revoke(&first).await;
+        assert!(set.is_revoked(&reissued).await);
+        assert!(!set.is_revoked(&other).await);
+        assert_eq!(
+            set.revoked_ids(),
+            vec![RevokedId {
+                jti: "id-1".to_string(),
+                expires_at: exp
+            }]
+        );
+    }
+
+    #[test]
+    fn expired_ids_are_dropped() {
+        let set = RevocationSet::new(1000);
+        set.revoke_id("stale", Some(now_secs() - 1));
+        set.revoke_id("live", Some(now_secs() + 60));
+        assert!(!set.is_id_revoked("stale"));
+        assert!(set.is_id_revoked("live"));
+        assert_eq!(set.revoked_ids().len(), 1);
+    }
+
     #[test]
     fn token_hash_deterministic() {
         let h1 = token_hash("test-token");
@@ -0,0 +1,652 @@
+//! WebDAV front-end (RFC 4918, class 1) over a namespace's VFS.
+//!
+//! Served under [`PREFIX`] so that Finder, Explorer and davfs2 can mount a
+//! namespace as a network drive. Every method maps onto plain `VfsRouter`
+//! calls: PROPFIND is `stat` plus `readdir`, MOVE is `rename`, and COPY and
+//! recursive DELETE walk the tree. Locking (class 2) is not implemented, so
+//! clients that insist on LOCK, such as Finder, mount the share read-only.
+
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+
+use axum::body::{Body, Bytes};
+use axum::http::{header, HeaderMap, HeaderValue, Request, StatusCode, Uri};
+use axum::response::{IntoResponse, Response};
+use fs9_core::VfsRouter;
+use fs9_sdk::{FileInfo, FsError, FsProvider, Handle, OpenFlags};
+use futures::{stream, StreamExt};
+use percent_encoding::{percent_decode_str, utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+
+use crate::audit::EventType;
+use crate::auth::{Access, RequestContext};
+use crate::body_limit::BodyTooLarge;
+use crate::circuit_breaker::retry_after_secs;
+use crate::namespace::Namespace;
+
+/// URL prefix the WebDAV tree is served under.
+pub const PREFIX: &str = "/dav";
+
+const ALLOW: &str = "OPTIONS, GET, HEAD, PUT, DELETE, MKCOL, PROPFIND, MOVE, COPY";
+const CHUNK_SIZE: usize = 256 * 1024;
+
+/// Characters escaped in a path segment of an `href`: everything except
+/// RFC 3986 unreserved characters.
+const SEGMENT: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_')
+    .remove(b'~');
+
+type DavResult<T> = Result<T, DavError>;
+
+struct DavError {
+    status: StatusCode,
+    message: String,
+    retry_after: Option<u64>,
+}
+
+impl DavError {
+    fn new(status: StatusCode, message: impl Into<String>) -> Self {
+        Self {
+            status,
+            message: message.into(),
+            retry_after: None,
+        }
+    }
+}
+
+impl From<FsError> for DavError {
+    fn from(err: FsError) -> Self {
+        let status =
+            StatusCode::from_u16(err.http_status()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+        Self {
+            retry_after: retry_after_secs(&err),
+            ..Self::new(status, err.to_string())
+        }
+    }
+}
+
+impl IntoResponse for DavError {
+    fn into_response(self) -> Response {
+        match self.retry_after {
+            Some(secs) => (
+                self.status,
+                [(header::RETRY_AFTER, secs.to_string())],
+                self.message,
+            )
+                .into_response(),
+            None => (self.status, self.message).into_response(),
+        }
+    }
+}
+
+/// Handle one WebDAV request against `ns` on behalf of `ctx`, whose user is
+/// recorded in the audit log for mutations and whose path grants are
+/// checked before any VFS call.
+pub async fn serve(ns: &Namespace, ctx: &RequestContext, request: Request<Body>) -> Response {
+    let (parts, body) = request.into_parts();
+    let path = match vfs_path(parts.uri.path()) {
+        Ok(path) => path,
+        Err(e) => return e.into_response(),
+    };
+    if let Err(e) = authorize(ctx, &parts.method, &path, &parts.headers) {
+        return e.into_response();
+    }
+    let dav = Dav {
+        ns,
+        user: &ctx.user_id,
+    };
+
+    let result = match parts.method.as_str() {
+        "OPTIONS" => Ok(options()),
+        "GET" => dav.get(&path, true).await,
+        "HEAD" => dav.get(&path, false).await,
+        "PUT" => dav.put(&path, body).await,
+        "DELETE" => dav.delete(&path).await,
+        "MKCOL" => dav.mkcol(&path, body).await,
+        "PROPFIND" => dav.propfind(&path, &parts.headers).await,
+        "MOVE" => dav.transfer(&path, &parts.headers, true).await,
+        "COPY" => dav.transfer(&path, &parts.headers, false).await,
+        _ => Ok((
+            StatusCode::METHOD_NOT_ALLOWED,
+            [(header::ALLOW, HeaderValue::from_static(ALLOW))],
+        )
+            .into_response()),
+    };
+    result.unwrap_or_else(IntoResponse::into_response)
+}
+
+fn options() -> Response {
+    (
+        StatusCode::OK,
+        [
+            (header::ALLOW, HeaderValue::from_static(ALLOW)),
+            (
+                header::HeaderName::from_static("dav"),
+                HeaderValue::from_static("1"),
+            ),
+            (
+                header::HeaderName::from_static("ms-author-via"),
+                HeaderValue::from_static("DAV"),
+            ),
+        ],
+    )
+        .into_response()
+}
+
+struct Dav<'a> {
+    ns: &'a Namespace,
+    user: &'a str,
+}
+
+impl Dav<'_> {
+    fn vfs(&self) -> &Arc<VfsRouter> {
+        &self.ns.vfs
+    }
+
+    async fn get(&self, path: &str, with_body: bool) -> DavResult<Response> {
+        let info = self.vfs().stat(path).await?;
+        if info.is_dir() {
+            return Err(DavError::new(
+                StatusCode::METHOD_NOT_ALLOWED,
+                "collections have no content; use PROPFIND",
+            ));
+        }
+
+        let mut response = Response::builder()
+            .status(StatusCode::OK)
+            .header(header::CONTENT_LENGTH, info.size)
+            .header(header::CONTENT_TYPE, "application/octet-stream")
+            .header(header::LAST_MODIFIED, httpdate::fmt_http_date(info.mtime));
+        if !info.etag.is_empty() {
+            response = response.header(header::ETAG, format!("\"{}\"", info.etag));
+        }
+        if !with_body || info.size == 0 {
+            return Ok(response.body(Body::empty()).unwrap());
+        }
+
+        let (handle, _) = self.vfs().open(path, OpenFlags::read()).await?;
+        let vfs = self.vfs().clone();
+        let size = info.size;
+        let chunks = stream::unfold(Some(0u64), move |offset| {
+            let vfs = vfs.clone();
+            async move {
+                let offset = offset?;
+                let want = (size - offset).min(CHUNK_SIZE as u64) as usize;
+                let read = vfs.read(&handle, offset, want).await;
+                let next = match &read {
+                    Ok(data) if !data.is_empty() && offset + (data.len() as u64) < size => {
+                        Some(offset + data.len() as u64)
+                    }
+                    _ => None,
+                };
+                if next.is_none() {
+                    let _ = vfs.close(handle, false).await;
+                }
+                let item = read.map_err(|e| std::io::Error::other(e.to_string()));
+                Some((item, next))
+            }
+        });
+        Ok(response.body(Body::from_stream(chunks)).unwrap())
+    }
+
+    async fn put(&self, path: &str, body: Body) -> DavResult<Response> {
+        let existed = match self.vfs().stat(path).await {
+            Ok(info) if info.is_dir() => {
+                return Err(DavError::new(
+                    StatusCode::METHOD_NOT_ALLOWED,
+                    "cannot PUT to a collection",
+                ))
+            }
+            Ok(_) => true,
+            Err(FsError::NotFound(_)) => false,
+            Err(e) => return Err(e.into()),
+        };
+
+        let (handle, _) = self
+            .ns
+            .open(path, OpenFlags::create_truncate())
+            .await
+            .map_err(missing_parent)?;
+        let written = self.write_body(handle, body).await;
+        let closed = self.vfs().close(handle, true).await;
+        written?;
+        closed?;
+
+        self.ns.audit_log.record(EventType::Upload, path, self.user);
+        Ok(created_or_replaced(existed))
+    }
+
+    async fn write_body(&self, handle: Handle, body: Body) -> DavResult<()> {
+        let mut offset = 0u64;
+        let mut chunks = body.into_data_stream();
+        while let Some(chunk) = chunks.next().await {
+            let chunk = chunk.map_err(|e| {
+                BodyTooLarge::from_body_error(&e).map_or_else(
+                    || DavError::new(StatusCode::BAD_REQUEST, e.to_string()),
+                    |too_large| DavError::new(StatusCode::PAYLOAD_TOO_LARGE, too_large.to_string()),
+                )
+            })?;
+            if !chunk.is_empty() {
+                offset += self.ns.write(&handle, offset, chunk).await? as u64;
+            }
+        }
+        Ok(())
+    }
+
+    async fn delete(&self, path: &str) -> DavResult<Response> {
+        if path == "/" {
+            return Err(DavError::new(
+                StatusCode::FORBIDDEN,
+                "cannot delete the root collection",
+            ));
+        }
+        remove_tree(self.ns, path.to_string()).await?;
+        self.ns.audit_log.record(EventType::Delete, path, self.user);
+        Ok(StatusCode::NO_CONTENT.into_response())
+    }
+
+    async fn mkcol(&self, path: &str, body: Body) -> DavResult<Response> {
+        let body = axum::body::to_bytes(body, CHUNK_SIZE)
+            .await
+            .map_err(|e| DavError::new(StatusCode::BAD_REQUEST, e.to_string()))?;
+        if !body.is_empty() {
+            return Err(DavError::new(
+                StatusCode::UNSUPPORTED_MEDIA_TYPE,
+                "MKCOL does not accept a request body",
+            ));
+        }
+        if self.vfs().stat(path).await.is_ok() {
+            return Err(DavError::new(
+                StatusCode::METHOD_NOT_ALLOWED,
+                format!("{path} already exists"),
+            ));
+        }
+
+        make_dir(self.vfs(), path).await.map_err(missing_parent)?;
+        self.ns.audit_log.record(EventType::Mkdir, path, self.user);
+        Ok(StatusCode::CREATED.into_response())
+    }
+
+    async fn propfind(&self, path: &str, headers: &HeaderMap) -> DavResult<Response> {
+        // An absent Depth means infinity, which we refuse like most servers
+        // do: a single request must not walk an entire remote backend.
+        let depth = match headers.get("depth").and_then(|v| v.to_str().ok()) {
+            Some("0") => 0,
+            Some("1") => 1,
+            Some(d) if !d.eq_ignore_ascii_case("infinity") => {
+                return Err(DavError::new(StatusCode::BAD_REQUEST, "invalid Depth"))
+            }
+            _ => {
+                return Ok((
+                    StatusCode::FORBIDDEN,
+                    [(header::CONTENT_TYPE, "application/xml; charset=utf-8")],
+                    "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n\
+                     <D:error xmlns:D=\"DAV:\"><D:propfind-finite-depth/></D:error>\n",
+                )
+                    .into_response())
+            }
+        };
+
+        let info = self.vfs().stat(path).await?;
+        let mut xml = String::from(
+            "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<D:multistatus xmlns:D=\"DAV:\">\n",
+        );
+        write_response(&mut xml, path, &info, &self.ns.name);
+        if depth == 1 && info.is_dir() {
+            for entry in self.vfs().readdir(path).await? {
+                write_response(&mut xml, &entry.path, &entry, &self.ns.name);
+            }
+        }
+        xml.push_str("</D:multistatus>\n");
+
+        Ok((
+            StatusCode::MULTI_STATUS,
+            [(header::CONTENT_TYPE, "application/xml; charset=utf-8")],
+            xml,
+        )
+            .into_response())
+    }
+
+    /// MOVE (`is_move`) or COPY `path` to the request's `Destination`.
+    async fn transfer(
+        &self,
+        path: &str,
+        headers: &HeaderMap,
+        is_move: bool,
+    ) -> DavResult<Response> {
+        let destination = destination(headers)?;
+        let overwrite = match headers.get("overwrite").map(HeaderValue::as_bytes) {
+            None | Some(b"T") => true,
+            Some(b"F") => false,
+            Some(_) => return Err(DavError::new(StatusCode::BAD_REQUEST, "invalid Overwrite")),
+        };
+        let recursive = match headers.get("depth").and_then(|v| v.to_str().ok()) {
+            None => true,
+            Some(d) if d.eq_ignore_ascii_case("infinity") => true,
+            Some("0") if !is_move => false,
+            Some(_) => return Err(DavError::new(StatusCode::BAD_REQUEST, "invalid Depth")),
+        };
+        if destination == path || is_within(&destination, path) {
+            return Err(DavError::new(
+                StatusCode::FORBIDDEN,
+                "destination is the source or lies inside it",
+            ));
+        }
+
+        self.vfs().stat(path).await?;
+        let existed = self.vfs().stat(&destination).await.is_ok();
+        if existed {
+            if !overwrite {
+                return Err(DavError::new(
+                    StatusCode::PRECONDITION_FAILED,
+                    format!("{destination} exists and Overwrite is F"),
+                ));
+            }
+            remove_tree(self.ns, destination.clone()).await?;
+        }
+
+        if is_move && self.same_mount(path, &destination).await? {
+            self.vfs()
+                .rename(path, &destination)
+                .await
+                .map_err(missing_parent)?;
+        } else {
+            copy_tree(self.ns, path.to_string(), destination.clone(), recursive)
+                .await
+                .map_err(missing_parent)?;
+            if is_move {
+                remove_tree(self.ns, path.to_string()).await?;
+            }
+        }
+
+        let event = if is_move {
+            EventType::Rename
+        } else {
+            EventType::Create
+        };
+        self.ns.audit_log.record(event, &destination, self.user);
+        Ok(created_or_replaced(existed))
+    }
+
+    /// Whether a rename between the two paths stays within one provider.
+    /// Moves across mounts fall back to copy and delete.
+    async fn same_mount(&self, a: &str, b: &str) -> DavResult<bool> {
+        let (a, _) = self.ns.mount_table.resolve(a).await?;
+        let (b, _) = self.ns.mount_table.resolve(b).await?;
+        Ok(Arc::ptr_eq(&a, &b))
+    }
+}
+
+fn created_or_replaced(existed: bool) -> Response {
+    if existed {
+        StatusCode::NO_CONTENT.into_response()
+    } else {
+        StatusCode::CREATED.into_response()
+    }
+}
+
+/// RFC 4918 wants 409 when the parent collection of a new resource is missing.
+fn missing_parent(err: FsError) -> DavError {
+    match err {
+        FsError::NotFound(_) => {
+            DavError::new(StatusCode::CONFLICT, "parent collection does not exist")
+        }
+        e => e.into(),
+    }
+}
+
+/// Check `ctx`'s path grants for `method` on `path` and, for MOVE and COPY,
+/// on the destination.
+fn authorize(
+    ctx: &RequestContext,
+    method: &axum::http::Method,
+    path: &str,
+    headers: &HeaderMap,
+) -> DavResult<()> {
+    let (source, target) = match method.as_str() {
+        "OPTIONS" | "GET" | "HEAD" | "PROPFIND" => (Access::Read, false),
+        "MOVE" => (Access::ReadWrite, true),
+        "COPY" => (Access::Read, true),
+        _ => (Access::ReadWrite, false),
+    };
+    let denied =
+        |path: &str| DavError::new(StatusCode::FORBIDDEN, format!("access denied: {path}"));
+    if !ctx.can_access_path(path, source) {
+        return Err(denied(path));
+    }
+    if target {
+        // A missing Destination is reported by the method itself.
+        if let Ok(dest) = destination(headers) {
+            if !ctx.can_access_path(&dest, Access::ReadWrite) {
+                return Err(denied(&dest));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Map a request path under [`PREFIX`] to a normalized VFS path.
+fn vfs_path(uri_path: &str) -> DavResult<String> {
+    let rest = uri_path
+        .strip_prefix(PREFIX)
+        .filter(|rest| rest.is_empty() || rest.starts_with('/'))
+        .ok_or_else(|| {
+            DavError::new(
+                StatusCode::BAD_GATEWAY,
+                format!("{uri_path} is not under {PREFIX}"),
+            )
+        })?;
+    let decoded = percent_decode_str(rest)
+        .decode_utf8()
+        .map_err(|_| DavError::new(StatusCode::BAD_REQUEST, "path is not valid UTF-8"))?;
+
+    let mut path = String::new();
+    for segment in decoded.split('/') {
+        match segment {
+            "" | "." => {}
+            ".." => {
+                return Err(DavError::new(
+                    StatusCode::BAD_REQUEST,
+                    "'..' is not allowed in paths",
+                ))
+            }
+            segment => {
+                path.push('/');
+                path.push_str(segment);
+            }
+        }
+    }
+    if path.is_empty() {
+        path.push('/');
+    }
+    Ok(path)
+}
+
+/// The VFS path named by the `Destination` header, which may be an absolute
+/// URL or an absolute path.
+fn destination(headers: &HeaderMap) -> DavResult<String> {
+    let value = headers
+        .get("destination")
+        .and_then(|v| v.to_str().ok())
+        .ok_or_else(|| DavError::new(StatusCode::BAD_REQUEST, "missing Destination header"))?;
+    let uri: Uri = value
+        .parse()
+        .map_err(|_| DavError::new(StatusCode::BAD_REQUEST, "invalid Destination header"))?;
+    vfs_path(uri.path())
+}
+
+fn is_within(path: &str, dir: &str) -> bool {
+    dir == "/"
+        || path
+            .strip_prefix(dir)
+            .is_some_and(|rest| rest.starts_with('/'))
+}
+
+fn href(path: &str, is_dir: bool) -> String {
+    let mut href = PREFIX.to_string();
+    for segment in path.split('/').filter(|s| !s.is_empty()) {
+        href.push('/');
+        href.extend(utf8_percent_encode(segment, SEGMENT));
+    }
+    if is_dir || href == PREFIX {
+        href.push('/');
+    }
+    href
+}
+
+fn write_response(xml: &mut String, path: &str, info: &FileInfo, ns_name: &str) {
+    use std::fmt::Write;
+
+    let name = path.rsplit('/').find(|s| !s.is_empty()).unwrap_or(ns_name);
+    let _ = write!(
+        xml,
+        "<D:response><D:href>{}</D:href><D:propstat><D:prop>\
+         <D:displayname>{}</D:displayname>",
+        escape(&href(path, info.is_dir())),
+        escape(name),
+    );
+    if info.is_dir() {
+        xml.push_str("<D:resourcetype><D:collection/></D:resourcetype>");
+    } else {
+        let _ = write!(
+            xml,
+            "<D:resourcetype/><D:getcontentlength>{}</D:getcontentlength>",
+            info.size
+        );
+    }
+    let _ = write!(
+        xml,
+        "<D:getlastmodified>{}</D:getlastmodified>",
+        httpdate::fmt_http_date(info.mtime)
+    );
+    if !info.etag.is_empty() {
+        let _ = write!(xml, "<D:getetag>\"{}\"</D:getetag>", escape(&info.etag));
+    }
+    xml.push_str("</D:prop><D:status>HTTP/1.1 200 OK</D:status></D:propstat></D:response>\n");
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+async fn make_dir(vfs: &VfsRouter, path: &str) -> Result<(), FsError> {
+    let (handle, _) = vfs.open(path, OpenFlags::create_dir()).await?;
+    vfs.close(handle, false).await
+}
+
+/// Remove `path`, and everything below it if it is a directory.
+fn remove_tree(ns: &Namespace, path: String) -> BoxFuture<'_, Result<(), FsError>> {
+    Box::pin(async move {
+        if ns.vfs.stat(&path).await?.is_dir() {
+            for entry in ns.vfs.readdir(&path).await? {
+                remove_tree(ns, entry.path).await?;
+            }
+        }
+        ns.remove(&path).await
+    })
+}
+
+/// Copy `from` to `to`; directories are copied with their contents when
+/// `recursive` is set, otherwise as an empty directory.
+fn copy_tree(
+    ns: &Namespace,
+    from: String,
+    to: String,
+    recursive: bool,
+) -> BoxFuture<'_, Result<(), FsError>> {
+    Box::pin(async move {
+        if !ns.vfs.stat(&from).await?.is_dir() {
+            return copy_file(ns, &from, &to).await;
+        }
+        make_dir(&ns.vfs, &to).await?;
+        if recursive {
+            for entry in ns.vfs.readdir(&from).await? {
+                let name = entry.path.rsplit('/').next().unwrap_or_default();
+                let target = format!("{}/{name}", to.trim_end_matches('/'));
+                copy_tree(ns, entry.path.clone(), target, true).await?;
+            }
+        }
+        Ok(())
+    })
+}
+
+async fn copy_file(ns: &Namespace, from: &str, to: &str) -> Result<(), FsError> {
+    let vfs = &ns.vfs;
+    let (source, _) = vfs.open(from, OpenFlags::read()).await?;
+    let target = match ns.open(to, OpenFlags::create_truncate()).await {
+        Ok((target, _)) => target,
+        Err(e) => {
+            let _ = vfs.close(source, false).await;
+            return Err(e);
+        }
+    };
+
+    let mut copied = Ok(());
+    let mut offset = 0u64;
+    loop {
+        let chunk: Bytes = match vfs.read(&source, offset, CHUNK_SIZE).await {
+            Ok(chunk) if chunk.is_empty() => break,
+            Ok(chunk) => chunk,
+            Err(e) => {
+                copied = Err(e);
+                break;
+            }
+        };
+        let len = chunk.len() as u64;
+        if let Err(e) = ns.write(&target, offset, chunk).await {
+            copied = Err(e);
+            break;
+        }
+        offset += len;
+    }
+
+    let _ = vfs.close(source, false).await;
+    let closed = vfs.close(target, true).await;
+    copied.and(closed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn request_paths_map_to_vfs_paths() {
+        assert_eq!(vfs_path("/dav").ok().as_deref(), Some("/"));
+        assert_eq!(vfs_path("/dav/").ok().as_deref(), Some("/"));
+        assert_eq!(
+            vfs_path("/dav/docs/a%20b.txt").ok().as_deref(),
+            Some("/docs/a b.txt")
+        );
+        assert_eq!(vfs_path("/dav//docs/./x/").ok().as_deref(), Some("/docs/x"));
+        assert_eq!(
+            vfs_path("/dav/docs/../etc").err().map(|e| e.status),
+            Some(StatusCode::BAD_REQUEST)
+        );
+        assert_eq!(
+            vfs_path("/davx/file").err().map(|e| e.status),
+            Some(StatusCode::BAD_GATEWAY)
+        );
+    }
+
+    #[test]
+    fn hrefs_are_escaped_and_collections_end_in_slash() {
+        assert_eq!(href("/", true), "/dav/");
+        assert_eq!(href("/docs", true), "/dav/docs/");
+        assert_eq!(href("/docs/a b&c.txt", false), "/dav/docs/a%20b%26c.txt");
+    }
+}
@@ -0,0 +1,181 @@
+//! API key authentication: keys issued by an in-process `fs9-meta` are
+//! resolved by the server's auth middleware.
+
+use axum::routing::get;
+use axum::{middleware, Extension, Json, Router};
+use fs9_server::auth::{
+    auth_middleware, AuthMiddlewareState, AuthState, JwtConfig, RequestContext, API_KEY_HEADER,
+};
+use fs9_server::meta_client::MetaClient;
+use fs9_server::state::AppState;
+use reqwest::{Client, RequestBuilder, StatusCode};
+use serde_json::{json, Value};
+use std::sync::Arc;
+use tokio::net::TcpListener;
+
+async fn whoami(Extension(ctx): Extension<RequestContext>) -> Json<Value> {
+    Json(json!({ "ns": ctx.ns, "user_id": ctx.user_id, "roles": ctx.roles }))
+}
+
+/// Start fs9-meta on an in-memory SQLite store and return its base URL.
+async fn start_meta() -> String {
+    let store = fs9_meta::MetaStore::connect("sqlite::memory:")
+        .await
+        .unwrap();
+    store.migrate().await.unwrap();
+    let state = fs9_meta::AppState::new(store, "meta-secret".to_string(), None);
+
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(fs9_meta::serve(listener, fs9_meta::app(state)));
+    url
+}
+
+/// Start an auth-enabled server that resolves credentials through `meta_url`
+/// and answers `/api/v1/whoami` with the resulting request context.
+async fn start_server(meta_url: &str) -> (String, Arc<AppState>) {
+    let app_state = Arc::new(AppState::with_meta(
+        Some(MetaClient::new(meta_url, None)),
+        None,
+        None,
+    ));
+    let auth = AuthMiddlewareState::new(
+        AuthState::new(true, JwtConfig::new("server-secret")),
+        app_state.clone(),
+    );
+    let app = Router::new()
+        .route("/api/v1/whoami", get(whoami))
+        .layer(middleware::from_fn_with_state(auth, auth_middleware));
+
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    (url, app_state)
+}
+
+/// Create a namespace, a user and an API key for them in it; returns
+/// `(user_id, key_id, raw_key)`.
+async fn create_key(client: &Client, meta_url: &str, namespace: &str) -> (String, String, String) {
+    client
+        .post(format!("{meta_url}/api/v1/namespaces"))
+        .json(&json!({ "name": namespace }))
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+    let user: Value = client
+        .post(format!("{meta_url}/api/v1/users"))
+        .json(&json!({ "username": "ci-bot" }))
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let created: Value = client
+        .post(format!("{meta_url}/api/v1/apikeys"))
+        .json(&json!({
+            "user_id": user["id"],
+            "name": "ci",
+            "namespace": namespace,
+            "roles": ["read-write"],
+        }))
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    (
+        user["id"].as_str().unwrap().to_string(),
+        created["id"].as_str().unwrap().to_string(),
+        created["key"].as_str().unwrap().to_string(),
+    )
+}
+
+async fn send(request: RequestBuilder) -> (StatusCode, Value) {
+    let resp = request.send().await.unwrap();
+    let status = resp.status();
+    (status, resp.json().await.unwrap_or(Value::Null))
+}
+
+#[tokio::test]
+async fn api_key_authenticates_until_revoked() {
+    let client = Client::new();
+    let meta_url = start_meta().await;
+    let (server_url, server_state) = start_server(&meta_url).await;
+    let (user_id, key_id, key) = create_key(&client, &meta_url, "team-a").await;
+    assert!(key.starts_with("fs9_"));
+
+    let whoami = format!("{server_url}/api/v1/whoami");
+    let (status, body) = send(client.get(&whoami).bearer_auth(&key)).await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    assert_eq!(body["ns"], "team-a");
+    assert_eq!(body["user_id"], user_id);
+    assert_eq!(body["roles"], json!(["read-write"]));
+
+    let (status, body) = send(client.get(&whoami).header(API_KEY_HEADER, &key)).await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    assert_eq!(body["ns"], "team-a");
+
+    let (status, _) = send(client.get(&whoami).bearer_auth("fs9_not-a-real-key")).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    client
+        .delete(format!("{meta_url}/api/v1/apikeys/{key_id}"))
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+
+    // A server without a cached lookup asks fs9-meta and is refused.
+    let (fresh_url, _) = start_server(&meta_url).await;
+    let (status, body) = send(
+        client
+            .get(format!("{fresh_url}/api/v1/whoami"))
+            .bearer_auth(&key),
+    )
+    .await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED, "{body}");
+
+    // The server that cached the key stops accepting it once the revocation
+    // reaches it (what `/api/v1/auth/revoke` does).
+    server_state.revocation_set.revoke(&key).await;
+    server_state.token_cache.remove(&key).await;
+    for request in [
+        client.get(&whoami).bearer_auth(&key),
+        client.get(&whoami).header(API_KEY_HEADER, &key),
+    ] {
+        let (status, body) = send(request).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED, "{body}");
+    }
+}
+
+#[tokio::test]
+async fn api_key_requires_meta_service() {
+    let app_state = Arc::new(AppState::new());
+    let auth = AuthMiddlewareState::new(
+        AuthState::new(true, JwtConfig::new("server-secret")),
+        app_state,
+    );
+    let app = Router::new()
+        .route("/api/v1/whoami", get(whoami))
+        .layer(middleware::from_fn_with_state(auth, auth_middleware));
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+    let (status, _) = send(
+        Client::new()
+            .get(format!("{url}/api/v1/whoami"))
+            .header(API_KEY_HEADER, "fs9_0123"),
+    )
+    .await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
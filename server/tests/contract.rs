@@ -671,3 +671,143 @@ async fn pagefs_capabilities() {
     let caps: Caps = resp.json().await.unwrap();
     assert!(caps.flags > 0, "PageFS should have capabilities");
 }
+
+/// PageFS as the target of a rename from a `MemoryFs` mount, which the VFS
+/// router carries out as a copy followed by removing the source.
+#[tokio::test]
+async fn pagefs_receives_cross_mount_rename() {
+    use bytes::Bytes;
+    use fs9_core::{HandleRegistry, MemoryFs, MountTable, VfsRouter};
+    use fs9_sdk::{FsError, FsProvider, OpenFlags, StatChanges};
+    use std::sync::Arc;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    let (_plugins, pagefs) = harness::load_pagefs();
+    let vfs = VfsRouter::new(
+        Arc::new(MountTable::new()),
+        Arc::new(HandleRegistry::new(Duration::from_secs(60))),
+    );
+    vfs.mount_table()
+        .mount("/mem", "memfs", Arc::new(MemoryFs::new()))
+        .await
+        .unwrap();
+    vfs.mount_table()
+        .mount("/page", "pagefs", pagefs)
+        .await
+        .unwrap();
+
+    // Bigger than one PageFS page, so the copy spans several.
+    let content: Vec<u8> = (0..=250u8).cycle().take(64 * 1024).collect();
+    let (handle, _) = vfs
+        .open("/mem/report.bin", OpenFlags::create_file())
+        .await
+        .unwrap();
+    vfs.write(&handle, 0, Bytes::from(content.clone()))
+        .await
+        .unwrap();
+    vfs.close(handle, false).await.unwrap();
+    let mtime = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+    vfs.wstat(
+        "/mem/report.bin",
+        StatChanges {
+            mode: Some(0o640),
+            mtime: Some(mtime),
+            ..Default::default()
+        },
+    )
+    .await
+    .unwrap();
+
+    vfs.rename("/mem/report.bin", "/page/report.bin")
+        .await
+        .unwrap();
+
+    assert!(matches!(
+        vfs.stat("/mem/report.bin").await,
+        Err(FsError::NotFound(_))
+    ));
+    let info = vfs.stat("/page/report.bin").await.unwrap();
+    assert_eq!(info.size, content.len() as u64);
+    assert_eq!(info.mode & 0o777, 0o640);
+    assert_eq!(info.mtime, mtime);
+
+    let (handle, _) = vfs
+        .open("/page/report.bin", OpenFlags::read())
+        .await
+        .unwrap();
+    let read = vfs.read(&handle, 0, content.len()).await.unwrap();
+    vfs.close(handle, false).await.unwrap();
+    assert_eq!(read, content);
+
+    let entries = vfs.readdir("/page").await.unwrap();
+    assert_eq!(entries.len(), 1, "staging copy left behind: {entries:?}");
+}
+
+/// A PageFS snapshot mounted beside the live mount keeps serving the old
+/// contents and refuses writes.
+#[tokio::test]
+async fn pagefs_snapshot_mount_is_point_in_time() {
+    use bytes::Bytes;
+    use fs9_core::{HandleRegistry, MountTable, VfsRouter};
+    use fs9_sdk::{FsError, FsProvider, OpenFlags};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    async fn read_all(vfs: &VfsRouter, path: &str) -> Vec<u8> {
+        let (handle, info) = vfs.open(path, OpenFlags::read()).await.unwrap();
+        let data = vfs.read(&handle, 0, info.size as usize).await.unwrap();
+        vfs.close(handle, false).await.unwrap();
+        data.to_vec()
+    }
+
+    let (_plugins, pagefs) = harness::load_pagefs();
+    let vfs = VfsRouter::new(
+        Arc::new(MountTable::new()),
+        Arc::new(HandleRegistry::new(Duration::from_secs(60))),
+    );
+    vfs.mount_table()
+        .mount("/page", "pagefs", pagefs)
+        .await
+        .unwrap();
+
+    let (handle, _) = vfs
+        .open("/page/notes.txt", OpenFlags::create_file())
+        .await
+        .unwrap();
+    vfs.write(&handle, 0, Bytes::from_static(b"first draft"))
+        .await
+        .unwrap();
+    vfs.close(handle, false).await.unwrap();
+
+    vfs.mount_table()
+        .snapshot("/page", "/page-snap")
+        .await
+        .unwrap();
+
+    let (handle, _) = vfs
+        .open("/page/notes.txt", OpenFlags::write())
+        .await
+        .unwrap();
+    vfs.write(&handle, 0, Bytes::from_static(b"FINAL"))
+        .await
+        .unwrap();
+    vfs.close(handle, false).await.unwrap();
+
+    assert_eq!(read_all(&vfs, "/page/notes.txt").await, b"FINAL draft");
+    assert_eq!(read_all(&vfs, "/page-snap/notes.txt").await, b"first draft");
+
+    assert!(matches!(
+        vfs.open("/page-snap/notes.txt", OpenFlags::write()).await,
+        Err(FsError::PermissionDenied(_))
+    ));
+    assert!(matches!(
+        vfs.open("/page-snap/new.txt", OpenFlags::create_file())
+            .await,
+        Err(FsError::PermissionDenied(_))
+    ));
+    assert!(matches!(
+        vfs.remove("/page-snap/notes.txt").await,
+        Err(FsError::PermissionDenied(_))
+    ));
+    assert_eq!(read_all(&vfs, "/page-snap/notes.txt").await, b"first draft");
+}
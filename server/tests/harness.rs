@@ -12,6 +12,35 @@ use std::time::Duration;
 use tokio::net::TcpListener;
 use tokio::sync::oneshot;
 
+/// Load the pagefs plugin built into `target/debug` and create a provider
+/// backed by its in-memory KV store.
+#[must_use]
+pub fn load_pagefs() -> (Arc<PluginManager>, Arc<dyn FsProvider>) {
+    let manifest_dir = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let workspace_root = manifest_dir.parent().unwrap();
+
+    let plugin_filename = format!("libfs9_plugin_pagefs{}", std::env::consts::DLL_SUFFIX);
+    let plugin_path = workspace_root.join("target/debug").join(plugin_filename);
+    if !plugin_path.exists() {
+        panic!(
+            "PageFS plugin not found at {:?}. Run `cargo build -p fs9-plugin-pagefs` first.",
+            plugin_path
+        );
+    }
+
+    let plugin_manager = Arc::new(PluginManager::new());
+    plugin_manager
+        .load("pagefs", &plugin_path)
+        .expect("Failed to load PageFS plugin");
+
+    let provider = Arc::new(
+        plugin_manager
+            .create_provider("pagefs", r#"{"uid": 1000, "gid": 1000}"#)
+            .expect("Failed to create PageFS provider"),
+    );
+    (plugin_manager, provider)
+}
+
 /// A test server instance running in the background.
 pub struct TestServer {
     pub url: String,
@@ -28,29 +57,7 @@ impl TestServer {
 
     /// Start a test server with PageFS plugin (in-memory KV backend).
     pub async fn start_with_pagefs() -> Self {
-        let manifest_dir = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
-        let workspace_root = manifest_dir.parent().unwrap();
-
-        let plugin_filename = format!("libfs9_plugin_pagefs{}", std::env::consts::DLL_SUFFIX);
-        let plugin_path = workspace_root.join("target/debug").join(plugin_filename);
-        if !plugin_path.exists() {
-            panic!(
-                "PageFS plugin not found at {:?}. Run `cargo build -p fs9-plugin-pagefs` first.",
-                plugin_path
-            );
-        }
-
-        let plugin_manager = Arc::new(PluginManager::new());
-        plugin_manager
-            .load("pagefs", &plugin_path)
-            .expect("Failed to load PageFS plugin");
-
-        let provider = Arc::new(
-            plugin_manager
-                .create_provider("pagefs", r#"{"uid": 1000, "gid": 1000}"#)
-                .expect("Failed to create PageFS provider"),
-        );
-
+        let (plugin_manager, provider) = load_pagefs();
         Self::start_with_provider_and_plugin_manager(provider, Some(plugin_manager)).await
     }
 
@@ -373,6 +380,7 @@ fn build_multitenant_router(state: Arc<MultiTenantAppState>, jwt_secret: &str) -
                         ns: DEFAULT_NAMESPACE.to_string(),
                         user_id: "anonymous".to_string(),
                         roles: Vec::new(),
+                        scopes: Vec::new(),
                     });
                     return next.run(req).await;
                 }
@@ -418,7 +426,12 @@ fn build_multitenant_router(state: Arc<MultiTenantAppState>, jwt_secret: &str) -
                             .map(|arr| arr.iter().filter_map(|v| v.as_str().map(String::from)).collect())
                             .unwrap_or_default();
 
-                        req.extensions_mut().insert(RequestContext { ns, user_id, roles });
+                        req.extensions_mut().insert(RequestContext {
+                            ns,
+                            user_id,
+                            roles,
+                            scopes: Vec::new(),
+                        });
                         next.run(req).await
                     }
                     Err(e) => {
@@ -520,6 +533,8 @@ fn parse_flags(bits: u32) -> OpenFlags {
         truncate,
         append: false,
         directory: false,
+        symlink: false,
+        exclusive: false,
     }
 }
 
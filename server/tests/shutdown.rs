@@ -0,0 +1,192 @@
+//! Graceful shutdown: buffered writes reach storage before the server exits.
+
+use async_trait::async_trait;
+use axum::extract::{Request, State};
+use axum::response::Response;
+use axum::routing::any;
+use axum::Router;
+use bytes::Bytes;
+use fs9_core::MemoryFs;
+use fs9_sdk::{
+    Capabilities, FileInfo, FsProvider, FsResult, FsStats, Handle, OpenFlags, StatChanges,
+};
+use fs9_server::auth::RequestContext;
+use fs9_server::namespace::{Namespace, DEFAULT_NAMESPACE};
+use fs9_server::state::AppState;
+use fs9_server::{shutdown, webdav};
+use reqwest::{Client, StatusCode};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::net::TcpListener;
+use tokio::sync::oneshot;
+use tokio::task::JoinHandle;
+
+/// A write-back cache over a `MemoryFs` "disk": writes are held in memory
+/// and only reach the disk when the provider is flushed.
+struct WriteBackFs {
+    disk: Arc<MemoryFs>,
+    paths: Mutex<HashMap<u64, String>>,
+    pending: Mutex<Vec<(String, u64, Bytes)>>,
+    flushes: AtomicUsize,
+}
+
+impl WriteBackFs {
+    fn new(disk: Arc<MemoryFs>) -> Self {
+        Self {
+            disk,
+            paths: Mutex::new(HashMap::new()),
+            pending: Mutex::new(Vec::new()),
+            flushes: AtomicUsize::new(0),
+        }
+    }
+}
+
+#[async_trait]
+impl FsProvider for WriteBackFs {
+    async fn stat(&self, path: &str) -> FsResult<FileInfo> {
+        self.disk.stat(path).await
+    }
+
+    async fn wstat(&self, path: &str, changes: StatChanges) -> FsResult<()> {
+        self.disk.wstat(path, changes).await
+    }
+
+    async fn statfs(&self, path: &str) -> FsResult<FsStats> {
+        self.disk.statfs(path).await
+    }
+
+    async fn open(&self, path: &str, flags: OpenFlags) -> FsResult<(Handle, FileInfo)> {
+        let (handle, info) = self.disk.open(path, flags).await?;
+        self.paths
+            .lock()
+            .unwrap()
+            .insert(handle.id(), path.to_string());
+        Ok((handle, info))
+    }
+
+    async fn read(&self, handle: &Handle, offset: u64, size: usize) -> FsResult<Bytes> {
+        self.disk.read(handle, offset, size).await
+    }
+
+    async fn write(&self, handle: &Handle, offset: u64, data: Bytes) -> FsResult<usize> {
+        let path = self.paths.lock().unwrap()[&handle.id()].clone();
+        let len = data.len();
+        self.pending.lock().unwrap().push((path, offset, data));
+        Ok(len)
+    }
+
+    async fn close(&self, handle: Handle, sync: bool) -> FsResult<()> {
+        self.paths.lock().unwrap().remove(&handle.id());
+        self.disk.close(handle, sync).await
+    }
+
+    async fn readdir(&self, path: &str) -> FsResult<Vec<FileInfo>> {
+        self.disk.readdir(path).await
+    }
+
+    async fn remove(&self, path: &str) -> FsResult<()> {
+        self.disk.remove(path).await
+    }
+
+    async fn flush(&self) -> FsResult<()> {
+        self.flushes.fetch_add(1, Ordering::SeqCst);
+        let pending = std::mem::take(&mut *self.pending.lock().unwrap());
+        for (path, offset, data) in pending {
+            let (handle, _) = self.disk.open(&path, OpenFlags::write()).await?;
+            self.disk.write(&handle, offset, data).await?;
+            self.disk.close(handle, true).await?;
+        }
+        Ok(())
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        self.disk.capabilities()
+    }
+}
+
+async fn dav(State(ns): State<Arc<Namespace>>, request: Request) -> Response {
+    let ctx = RequestContext {
+        ns: DEFAULT_NAMESPACE.to_string(),
+        user_id: "tester".to_string(),
+        roles: Vec::new(),
+        scopes: Vec::new(),
+    };
+    webdav::serve(&ns, &ctx, request).await
+}
+
+/// Serve `fs` over WebDAV until the returned sender fires.
+async fn start(
+    fs: Arc<WriteBackFs>,
+) -> (String, oneshot::Sender<()>, JoinHandle<std::io::Result<()>>) {
+    let state = Arc::new(AppState::new());
+    let ns = state
+        .namespace_manager
+        .get_or_create(DEFAULT_NAMESPACE)
+        .await;
+    ns.mount_table.mount("/", "writeback", fs).await.unwrap();
+
+    let app = Router::new().route("/dav/{*path}", any(dav)).with_state(ns);
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let (stop, stopped) = oneshot::channel();
+    let server = tokio::spawn(shutdown::serve(
+        listener,
+        app,
+        state,
+        async {
+            let _ = stopped.await;
+        },
+        Duration::from_secs(5),
+    ));
+    (url, stop, server)
+}
+
+#[tokio::test]
+async fn shutdown_flushes_buffered_writes() {
+    let disk = Arc::new(MemoryFs::new());
+    let fs = Arc::new(WriteBackFs::new(disk.clone()));
+    let (url, stop, server) = start(fs.clone()).await;
+    let client = Client::new();
+
+    let resp = client
+        .put(format!("{url}/dav/data.txt"))
+        .body("persist me")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::CREATED);
+    assert_eq!(disk.stat("/data.txt").await.unwrap().size, 0);
+
+    stop.send(()).unwrap();
+    server.await.unwrap().unwrap();
+    assert_eq!(fs.flushes.load(Ordering::SeqCst), 1);
+
+    // Restart on the same disk with an empty cache.
+    let (url, stop, server) = start(Arc::new(WriteBackFs::new(disk))).await;
+    let resp = client
+        .get(format!("{url}/dav/data.txt"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(resp.text().await.unwrap(), "persist me");
+
+    stop.send(()).unwrap();
+    server.await.unwrap().unwrap();
+}
+
+#[tokio::test]
+async fn shutdown_refuses_new_connections() {
+    let (url, stop, server) = start(Arc::new(WriteBackFs::new(Arc::new(MemoryFs::new())))).await;
+
+    stop.send(()).unwrap();
+    server.await.unwrap().unwrap();
+
+    let result = Client::new()
+        .get(format!("{url}/dav/data.txt"))
+        .send()
+        .await;
+    assert!(result.is_err());
+}
@@ -0,0 +1,93 @@
+//! Token revocation by id: once a token's `jti` is revoked the auth
+//! middleware refuses it, even after it has been cached.
+
+use axum::routing::get;
+use axum::{middleware, Extension, Json, Router};
+use fs9_server::auth::{
+    auth_middleware, AuthMiddlewareState, AuthState, Claims, JwtConfig, RequestContext,
+};
+use fs9_server::state::AppState;
+use reqwest::{Client, StatusCode};
+use serde_json::{json, Value};
+use std::sync::Arc;
+use tokio::net::TcpListener;
+
+const SECRET: &str = "server-secret";
+
+async fn whoami(Extension(ctx): Extension<RequestContext>) -> Json<Value> {
+    Json(json!({ "ns": ctx.ns, "user_id": ctx.user_id }))
+}
+
+/// Start an auth-enabled server validating JWTs locally and return its
+/// `/api/v1/whoami` URL.
+async fn start_server() -> (String, Arc<AppState>) {
+    let app_state = Arc::new(AppState::new());
+    let auth = AuthMiddlewareState::new(
+        AuthState::new(true, JwtConfig::new(SECRET)),
+        app_state.clone(),
+    );
+    let app = Router::new()
+        .route("/api/v1/whoami", get(whoami))
+        .layer(middleware::from_fn_with_state(auth, auth_middleware));
+
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}/api/v1/whoami", listener.local_addr().unwrap());
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    (url, app_state)
+}
+
+fn generate(user: &str) -> (String, Claims) {
+    let claims = Claims::with_namespace(user, "team-a", vec!["read-write".to_string()], 3600);
+    let token = JwtConfig::new(SECRET).encode(&claims).unwrap();
+    (token, claims)
+}
+
+async fn status(client: &Client, url: &str, token: &str) -> StatusCode {
+    client
+        .get(url)
+        .bearer_auth(token)
+        .send()
+        .await
+        .unwrap()
+        .status()
+}
+
+#[tokio::test]
+async fn revoked_token_id_is_rejected() {
+    let client = Client::new();
+    let (url, state) = start_server().await;
+    let (token, claims) = generate("alice");
+    let (other, _) = generate("bob");
+    let jti = claims.jti.clone().expect("generated tokens carry a jti");
+
+    // The first request validates and caches the token.
+    assert_eq!(status(&client, &url, &token).await, StatusCode::OK);
+
+    state.revocation_set.revoke_id(&jti, Some(claims.exp));
+    assert_eq!(
+        status(&client, &url, &token).await,
+        StatusCode::UNAUTHORIZED
+    );
+    assert_eq!(status(&client, &url, &other).await, StatusCode::OK);
+
+    let revoked = state.revocation_set.revoked_ids();
+    assert_eq!(revoked.len(), 1);
+    assert_eq!(revoked[0].jti, jti);
+    assert_eq!(revoked[0].expires_at, claims.exp);
+}
+
+#[tokio::test]
+async fn revoking_a_token_blacklists_its_id() {
+    let client = Client::new();
+    let (url, state) = start_server().await;
+    let (token, claims) = generate("alice");
+
+    state.revocation_set.revoke(&token).await;
+    assert_eq!(
+        status(&client, &url, &token).await,
+        StatusCode::UNAUTHORIZED
+    );
+    assert!(state
+        .revocation_set
+        .is_id_revoked(claims.jti.as_deref().unwrap()));
+}
@@ -0,0 +1,298 @@
+//! WebDAV gateway tests: raw DAV requests against an in-process server.
+
+use axum::extract::{Request, State};
+use axum::response::Response;
+use axum::routing::any;
+use axum::Router;
+use fs9_core::MemoryFs;
+use fs9_server::auth::RequestContext;
+use fs9_server::namespace::Namespace;
+use fs9_server::webdav;
+use reqwest::{Client, Method, StatusCode};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::TcpListener;
+
+async fn dav(State(ns): State<Arc<Namespace>>, request: Request) -> Response {
+    let ctx = RequestContext {
+        ns: "default".to_string(),
+        user_id: "tester".to_string(),
+        roles: Vec::new(),
+        scopes: Vec::new(),
+    };
+    webdav::serve(&ns, &ctx, request).await
+}
+
+/// Serve a fresh MemoryFs namespace under `/dav` and return its base URL.
+async fn start() -> String {
+    let ns = Arc::new(Namespace::new("default", Duration::from_secs(60)));
+    ns.mount_table
+        .mount("/", "memfs", Arc::new(MemoryFs::new()))
+        .await
+        .unwrap();
+
+    let app = Router::new()
+        .route(webdav::PREFIX, any(dav))
+        .route("/dav/", any(dav))
+        .route("/dav/{*path}", any(dav))
+        .with_state(ns);
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    url
+}
+
+fn method(name: &str) -> Method {
+    Method::from_bytes(name.as_bytes()).unwrap()
+}
+
+#[tokio::test]
+async fn put_creates_then_replaces_and_get_reads_back() {
+    let url = start().await;
+    let client = Client::new();
+
+    let resp = client
+        .put(format!("{url}/dav/notes.txt"))
+        .body("first")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::CREATED);
+
+    let resp = client
+        .put(format!("{url}/dav/notes.txt"))
+        .body("second")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+
+    let resp = client
+        .get(format!("{url}/dav/notes.txt"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert!(resp.headers().contains_key("last-modified"));
+    assert_eq!(resp.text().await.unwrap(), "second");
+}
+
+#[tokio::test]
+async fn put_into_missing_collection_conflicts() {
+    let url = start().await;
+    let resp = Client::new()
+        .put(format!("{url}/dav/missing/file.txt"))
+        .body("x")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::CONFLICT);
+}
+
+#[tokio::test]
+async fn propfind_lists_collection_as_multistatus() {
+    let url = start().await;
+    let client = Client::new();
+
+    let resp = client
+        .request(method("MKCOL"), format!("{url}/dav/docs"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::CREATED);
+    let resp = client
+        .request(method("MKCOL"), format!("{url}/dav/docs"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::METHOD_NOT_ALLOWED);
+
+    client
+        .put(format!("{url}/dav/docs/a%20b&c.txt"))
+        .body("hello")
+        .send()
+        .await
+        .unwrap();
+
+    let resp = client
+        .request(method("PROPFIND"), format!("{url}/dav/docs"))
+        .header("Depth", "1")
+        .body(r#"<?xml version="1.0"?><D:propfind xmlns:D="DAV:"><D:allprop/></D:propfind>"#)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status().as_u16(), 207);
+    let xml = resp.text().await.unwrap();
+    assert_eq!(xml.matches("<D:response>").count(), 2, "{xml}");
+    assert!(xml.contains("<D:href>/dav/docs/</D:href>"), "{xml}");
+    assert!(xml.contains("<D:resourcetype><D:collection/></D:resourcetype>"));
+    assert!(
+        xml.contains("<D:href>/dav/docs/a%20b%26c.txt</D:href>"),
+        "{xml}"
+    );
+    assert!(xml.contains("<D:displayname>a b&amp;c.txt</D:displayname>"));
+    assert!(xml.contains("<D:getcontentlength>5</D:getcontentlength>"));
+
+    let resp = client
+        .request(method("PROPFIND"), format!("{url}/dav/docs"))
+        .header("Depth", "0")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(
+        resp.text().await.unwrap().matches("<D:response>").count(),
+        1
+    );
+}
+
+#[tokio::test]
+async fn propfind_rejects_infinite_depth_and_missing_paths() {
+    let url = start().await;
+    let client = Client::new();
+
+    let resp = client
+        .request(method("PROPFIND"), format!("{url}/dav/"))
+        .header("Depth", "infinity")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+    assert!(resp
+        .text()
+        .await
+        .unwrap()
+        .contains("<D:propfind-finite-depth/>"));
+
+    let resp = client
+        .request(method("PROPFIND"), format!("{url}/dav/nope"))
+        .header("Depth", "0")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn move_follows_destination_and_overwrite_headers() {
+    let url = start().await;
+    let client = Client::new();
+    for (name, body) in [("a.txt", "aaa"), ("b.txt", "bbb")] {
+        client
+            .put(format!("{url}/dav/{name}"))
+            .body(body)
+            .send()
+            .await
+            .unwrap();
+    }
+
+    let resp = client
+        .request(method("MOVE"), format!("{url}/dav/a.txt"))
+        .header("Destination", format!("{url}/dav/b.txt"))
+        .header("Overwrite", "F")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::PRECONDITION_FAILED);
+
+    let resp = client
+        .request(method("MOVE"), format!("{url}/dav/a.txt"))
+        .header("Destination", "/dav/c.txt")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::CREATED);
+
+    let resp = client
+        .request(method("MOVE"), format!("{url}/dav/c.txt"))
+        .header("Destination", format!("{url}/dav/b.txt"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+
+    let old = client.get(format!("{url}/dav/a.txt")).send().await.unwrap();
+    assert_eq!(old.status(), StatusCode::NOT_FOUND);
+    let moved = client.get(format!("{url}/dav/b.txt")).send().await.unwrap();
+    assert_eq!(moved.text().await.unwrap(), "aaa");
+
+    let resp = client
+        .request(method("MOVE"), format!("{url}/dav/b.txt"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn copy_and_delete_collections_recursively() {
+    let url = start().await;
+    let client = Client::new();
+    client
+        .request(method("MKCOL"), format!("{url}/dav/src"))
+        .send()
+        .await
+        .unwrap();
+    client
+        .request(method("MKCOL"), format!("{url}/dav/src/sub"))
+        .send()
+        .await
+        .unwrap();
+    client
+        .put(format!("{url}/dav/src/sub/file.txt"))
+        .body("nested")
+        .send()
+        .await
+        .unwrap();
+
+    let resp = client
+        .request(method("COPY"), format!("{url}/dav/src"))
+        .header("Destination", format!("{url}/dav/dst"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::CREATED);
+    let copied = client
+        .get(format!("{url}/dav/dst/sub/file.txt"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(copied.text().await.unwrap(), "nested");
+
+    let resp = client
+        .request(method("COPY"), format!("{url}/dav/src"))
+        .header("Destination", format!("{url}/dav/src/sub/inner"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+
+    let resp = client
+        .delete(format!("{url}/dav/src"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+    let resp = client
+        .request(method("PROPFIND"), format!("{url}/dav/src"))
+        .header("Depth", "0")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn options_advertises_dav_class_1() {
+    let url = start().await;
+    let resp = Client::new()
+        .request(Method::OPTIONS, format!("{url}/dav/"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(resp.headers()["dav"], "1");
+    assert!(resp.headers()["allow"]
+        .to_str()
+        .unwrap()
+        .contains("PROPFIND"));
+}
@@ -6,7 +6,9 @@ use rustyline::hint::Hinter;
 use rustyline::validate::Validator;
 use rustyline::{Context, Helper};
 use sh9::eval::namespace::Namespace;
+use sh9::eval::router::NamespaceRouter;
 use sh9::lexer::{lexer, QuoteType, Token};
+use sh9::shell::complete_path;
 use std::borrow::Cow;
 use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
@@ -45,10 +47,11 @@ impl Sh9Helper {
 
 const BUILTINS: &[&str] = &[
     "alias", "basename", "bind", "break", "cat", "cd", "chroot", "continue", "cp", "cut", "date",
-    "dirname", "download", "echo", "env", "exit", "export", "false", "grep", "head", "help",
-    "http", "jobs", "jq", "local", "ls", "mkdir", "mount", "mv", "ns", "plugin", "pwd", "return",
-    "rev", "rm", "set", "sleep", "sort", "source", "stat", "tail", "tee", "test", "touch", "tr",
-    "tree", "true", "truncate", "unalias", "uniq", "unmount", "unset", "upload", "wait", "wc",
+    "df", "dirname", "download", "du", "echo", "env", "exit", "export", "false", "find", "grep",
+    "head", "help", "http", "jobs", "jq", "local", "ls", "mkdir", "mount", "mv", "ns", "plugin",
+    "pwd", "return", "rev", "rm", "set", "sleep", "sort", "source", "stat", "tail", "tee", "test",
+    "touch", "tr", "tree", "true", "truncate", "unalias", "uniq", "unmount", "unset", "upload",
+    "wait", "wc",
 ];
 
 impl Completer for Sh9Helper {
@@ -126,62 +129,17 @@ impl Completer for Sh9Helper {
 
         if word.starts_with('/') || word.starts_with('.') || word.contains('/') || !is_first_word {
             let cwd = self.cwd.read().unwrap().clone();
-
-            let (dir_path, partial_name) = if let Some(last_slash) = word.rfind('/') {
-                let dir = &word[..=last_slash];
-                let name = &word[last_slash + 1..];
-                (resolve_path(&cwd, dir.trim_end_matches('/')), name)
-            } else {
-                (cwd.clone(), word)
-            };
-
-            let partial_owned = partial_name.to_string();
-
-            let local_completions = {
-                let ns = self.namespace.read().unwrap();
-                let resolutions = ns.resolve(&dir_path);
-                if resolutions.is_empty() {
-                    None
-                } else {
-                    let mut names = Vec::new();
-                    let mut seen = std::collections::HashSet::new();
-                    for (source, rel) in &resolutions {
-                        let local_dir = source.join(rel.trim_start_matches('/'));
-                        if let Ok(entries) = std::fs::read_dir(&local_dir) {
-                            for entry in entries.flatten() {
-                                let name = entry.file_name().to_string_lossy().to_string();
-                                if name.starts_with(&*partial_owned) && seen.insert(name.clone()) {
-                                    let is_dir = entry.file_type().is_ok_and(|ft| ft.is_dir());
-                                    names.push(if is_dir { format!("{name}/") } else { name });
-                                }
-                            }
-                        }
-                    }
-                    Some(names)
-                }
-            };
-
-            let path_completions = if let Some(local) = local_completions {
-                local
-            } else if let Some(client) = &self.client {
-                let client = client.clone();
-                let dir_path_owned = dir_path.clone();
-                tokio::task::block_in_place(|| {
-                    self.runtime.block_on(async {
-                        complete_path(&client, &dir_path_owned, &partial_owned).await
-                    })
-                })
-            } else {
-                vec![]
-            };
-
-            for name in path_completions {
-                let replacement = if word.contains('/') {
-                    let last_slash = word.rfind('/').unwrap();
-                    format!("{}{}", &word[..=last_slash], name)
-                } else {
-                    name.clone()
-                };
+            let router = NamespaceRouter::with_namespace(
+                self.namespace.read().unwrap().clone(),
+                self.client.clone(),
+            );
+            let path_completions = tokio::task::block_in_place(|| {
+                self.runtime.block_on(complete_path(&router, &cwd, word))
+            });
+
+            let prefix_len = word.rfind('/').map_or(0, |slash| slash + 1);
+            for replacement in path_completions {
+                let name = replacement[prefix_len..].to_string();
 
                 let display = if name.ends_with('/') {
                     format!("{}  (dir)", name)
@@ -200,23 +158,6 @@ impl Completer for Sh9Helper {
     }
 }
 
-async fn complete_path(client: &Fs9Client, dir: &str, partial: &str) -> Vec<String> {
-    match client.readdir(dir).await {
-        Ok(entries) => entries
-            .into_iter()
-            .filter(|e| e.name().starts_with(partial))
-            .map(|e| {
-                if e.is_dir() {
-                    format!("{}/", e.name())
-                } else {
-                    e.name().to_string()
-                }
-            })
-            .collect(),
-        Err(_) => vec![],
-    }
-}
-
 fn find_word_start(line: &str) -> (usize, &str) {
     let mut start = line.len();
     for (i, c) in line.char_indices().rev() {
@@ -228,27 +169,6 @@ fn find_word_start(line: &str) -> (usize, &str) {
     (start, &line[start..])
 }
 
-fn resolve_path(cwd: &str, path: &str) -> String {
-    if path.starts_with('/') {
-        path.to_string()
-    } else if path == "." {
-        cwd.to_string()
-    } else if path == ".." {
-        let parts: Vec<&str> = cwd.split('/').filter(|s| !s.is_empty()).collect();
-        if parts.is_empty() {
-            "/".to_string()
-        } else {
-            format!("/{}", parts[..parts.len() - 1].join("/"))
-        }
-    } else if path.is_empty() {
-        cwd.to_string()
-    } else if cwd == "/" {
-        format!("/{}", path)
-    } else {
-        format!("{}/{}", cwd, path)
-    }
-}
-
 impl Hinter for Sh9Helper {
     type Hint = String;
 
@@ -1,6 +1,9 @@
 use super::namespace::MountFlags;
 use super::router::NamespaceRouter;
-use super::utils::format_mtime;
+use super::router::RouteFileInfo;
+use super::utils::{
+    format_mode, format_mtime, format_size_human, format_timestamp, match_glob_pattern,
+};
 use super::{ExecContext, Output};
 use crate::error::{Sh9Error, Sh9Result};
 use crate::shell::Shell;
@@ -14,8 +17,8 @@ impl Shell {
     ) -> Option<Sh9Result<i32>> {
         match name {
             "ls" | "mkdir" | "touch" | "truncate" | "rm" | "mv" | "cp" | "stat" | "mount"
-            | "lsfs" | "tree" | "plugin" | "chmod" | "chroot" | "basename" | "dirname" | "pwd"
-            | "cd" | "bind" | "unmount" | "ns" => {
+            | "find" | "lsfs" | "tree" | "plugin" | "chmod" | "chroot" | "basename" | "dirname"
+            | "pwd" | "cd" | "bind" | "unmount" | "ns" | "du" | "df" => {
                 Some(self.dispatch_fs_builtin(name, args, ctx).await)
             }
             _ => None,
@@ -48,6 +51,9 @@ impl Shell {
                 Ok(1)
             }
             "tree" => self.cmd_tree(args, ctx).await,
+            "find" => self.cmd_find(args, ctx).await,
+            "du" => self.cmd_du(args, ctx).await,
+            "df" => self.cmd_df(args, ctx).await,
             "plugin" => {
                 ctx.write_err("plugin: command disabled for security reasons");
                 Ok(1)
@@ -133,20 +139,7 @@ impl Shell {
                 let colorize = matches!(&ctx.stdout, Output::Stdout)
                     && unsafe { libc::isatty(libc::STDOUT_FILENO) } == 1;
                 if long_format {
-                    let type_char = '-';
-                    let mode = info.mode;
-                    let mode_str = format!(
-                        "{}{}{}{}{}{}{}{}{}",
-                        if mode & 0o400 != 0 { 'r' } else { '-' },
-                        if mode & 0o200 != 0 { 'w' } else { '-' },
-                        if mode & 0o100 != 0 { 'x' } else { '-' },
-                        if mode & 0o040 != 0 { 'r' } else { '-' },
-                        if mode & 0o020 != 0 { 'w' } else { '-' },
-                        if mode & 0o010 != 0 { 'x' } else { '-' },
-                        if mode & 0o004 != 0 { 'r' } else { '-' },
-                        if mode & 0o002 != 0 { 'w' } else { '-' },
-                        if mode & 0o001 != 0 { 'x' } else { '-' },
-                    );
+                    let mode_str = format_mode('-', info.mode);
                     let mtime_str = format_mtime(info.mtime);
                     let name = if colorize && info.mode & 0o111 != 0 {
                         format!("\x1b[32m{}\x1b[0m", path)
@@ -154,8 +147,8 @@ impl Shell {
                         path.to_string()
                     };
                     let line = format!(
-                        "{}{} {} {} {:>6} {} {}",
-                        type_char, mode_str, info.uid, info.gid, info.size, mtime_str, name
+                        "{} {} {} {:>6} {} {}",
+                        mode_str, info.uid, info.gid, info.size, mtime_str, name
                     );
                     ctx.stdout.writeln(&line).map_err(Sh9Error::Io)?;
                 } else {
@@ -196,19 +189,7 @@ impl Shell {
                 for entry in &entries {
                     if long_format {
                         let type_char = if entry.is_dir { 'd' } else { '-' };
-                        let mode = entry.mode;
-                        let mode_str = format!(
-                            "{}{}{}{}{}{}{}{}{}",
-                            if mode & 0o400 != 0 { 'r' } else { '-' },
-                            if mode & 0o200 != 0 { 'w' } else { '-' },
-                            if mode & 0o100 != 0 { 'x' } else { '-' },
-                            if mode & 0o040 != 0 { 'r' } else { '-' },
-                            if mode & 0o020 != 0 { 'w' } else { '-' },
-                            if mode & 0o010 != 0 { 'x' } else { '-' },
-                            if mode & 0o004 != 0 { 'r' } else { '-' },
-                            if mode & 0o002 != 0 { 'w' } else { '-' },
-                            if mode & 0o001 != 0 { 'x' } else { '-' },
-                        );
+                        let mode_str = format_mode(type_char, entry.mode);
                         let mtime_str = format_mtime(entry.mtime);
                         let name = if colorize {
                             if entry.is_dir {
@@ -222,8 +203,8 @@ impl Shell {
                             entry.name.clone()
                         };
                         let line = format!(
-                            "{}{} {} {} {:>6} {} {}",
-                            type_char, mode_str, entry.uid, entry.gid, entry.size, mtime_str, name
+                            "{} {} {} {:>6} {} {}",
+                            mode_str, entry.uid, entry.gid, entry.size, mtime_str, name
                         );
                         ctx.stdout.writeln(&line).map_err(Sh9Error::Io)?;
                     } else {
@@ -558,31 +539,57 @@ impl Shell {
     }
 
     async fn cmd_stat(&mut self, args: &[String], ctx: &mut ExecContext) -> Sh9Result<i32> {
+        if args.is_empty() {
+            ctx.write_err("stat: missing operand");
+            return Ok(1);
+        }
+
         let router = self.router();
+        let mut status = 0;
         for path in args {
             let full_path = self.resolve_path(path);
-            match router.stat(&full_path).await {
-                Ok(info) => {
-                    ctx.stdout
-                        .writeln(&format!("file: {}", path))
-                        .map_err(Sh9Error::Io)?;
-                    ctx.stdout
-                        .writeln(&format!("size: {}", info.size))
-                        .map_err(Sh9Error::Io)?;
-                    ctx.stdout
-                        .writeln(&format!(
-                            "type: {}",
-                            if info.is_dir { "directory" } else { "file" }
-                        ))
-                        .map_err(Sh9Error::Io)?;
-                }
+            let info = match router.stat(&full_path).await {
+                Ok(info) => info,
                 Err(e) => {
                     ctx.write_err(&format!("stat: {}: {}", path, e));
-                    return Ok(1);
+                    status = 1;
+                    continue;
                 }
+            };
+
+            let (type_char, type_name) = if info.is_dir {
+                ('d', "directory")
+            } else if info.is_symlink {
+                ('l', "symbolic link")
+            } else {
+                ('-', "regular file")
+            };
+            let mut lines = vec![
+                match &info.symlink_target {
+                    Some(target) => format!("  File: {} -> {}", path, target),
+                    None => format!("  File: {}", path),
+                },
+                format!("  Size: {}", info.size),
+                format!("  Type: {}", type_name),
+                format!(
+                    "  Mode: {:04o} ({})",
+                    info.mode & 0o7777,
+                    format_mode(type_char, info.mode)
+                ),
+                format!("   Uid: {}", info.uid),
+                format!("   Gid: {}", info.gid),
+                format!("Access: {}", format_timestamp(info.atime)),
+                format!("Modify: {}", format_timestamp(info.mtime)),
+                format!("Change: {}", format_timestamp(info.ctime)),
+            ];
+            if !info.etag.is_empty() {
+                lines.push(format!("  ETag: {}", info.etag));
+            }
+            for line in lines {
+                ctx.stdout.writeln(&line).map_err(Sh9Error::Io)?;
             }
         }
-        Ok(0)
+        Ok(status)
     }
 
     // cmd_mount, cmd_lsfs removed — disabled for security reasons
@@ -635,6 +642,208 @@ impl Shell {
         Ok(0)
     }
 
+    async fn cmd_find(&mut self, args: &[String], ctx: &mut ExecContext) -> Sh9Result<i32> {
+        let mut roots: Vec<&str> = Vec::new();
+        let mut name_pattern: Option<&str> = None;
+        let mut want_dir: Option<bool> = None;
+        let mut max_depth: Option<usize> = None;
+
+        let mut i = 0;
+        while i < args.len() && !args[i].starts_with('-') {
+            roots.push(&args[i]);
+            i += 1;
+        }
+        while i < args.len() {
+            let value = args.get(i + 1).map(String::as_str);
+            match (args[i].as_str(), value) {
+                ("-name", Some(pattern)) => name_pattern = Some(pattern),
+                ("-type", Some("f")) => want_dir = Some(false),
+                ("-type", Some("d")) => want_dir = Some(true),
+                ("-type", Some(other)) => {
+                    ctx.write_err(&format!("find: unknown type: {}", other));
+                    return Ok(1);
+                }
+                ("-maxdepth", Some(depth)) => match depth.parse() {
+                    Ok(depth) => max_depth = Some(depth),
+                    Err(_) => {
+                        ctx.write_err(&format!("find: invalid maxdepth: {}", depth));
+                        return Ok(1);
+                    }
+                },
+                ("-name" | "-type" | "-maxdepth", None) => {
+                    ctx.write_err(&format!("find: missing argument to {}", args[i]));
+                    return Ok(1);
+                }
+                (other, _) => {
+                    ctx.write_err(&format!("find: unknown predicate: {}", other));
+                    return Ok(1);
+                }
+            }
+            i += 2;
+        }
+        if roots.is_empty() {
+            roots.push(".");
+        }
+
+        let router = self.router();
+        let mut status = 0;
+        for root in roots {
+            let full_path = self.resolve_path(root);
+            let info = match router.stat(&full_path).await {
+                Ok(info) => info,
+                Err(e) => {
+                    ctx.write_err(&format!("find: {}: {}", root, e));
+                    status = 1;
+                    continue;
+                }
+            };
+
+            // Depth-first with an explicit stack so each match is written as
+            // soon as it is visited; children come back sorted by name.
+            let mut pending = vec![(full_path, root.to_string(), info.is_dir, 0)];
+            while let Some((path, shown, is_dir, depth)) = pending.pop() {
+                let name = shown.rsplit('/').find(|s| !s.is_empty()).unwrap_or(&shown);
+                let type_matches = want_dir.is_none() || want_dir == Some(is_dir);
+                let name_matches = match name_pattern {
+                    Some(pattern) => match_glob_pattern(pattern, name),
+                    None => true,
+                };
+                if type_matches && name_matches {
+                    ctx.stdout.writeln(&shown).map_err(Sh9Error::Io)?;
+                }
+
+                if !is_dir || max_depth.is_some_and(|max| depth >= max) {
+                    continue;
+                }
+                match router.readdir(&path).await {
+                    Ok(entries) => {
+                        for entry in entries.into_iter().rev() {
+                            let child = if path == "/" {
+                                format!("/{}", entry.name)
+                            } else {
+                                format!("{}/{}", path, entry.name)
+                            };
+                            let child_shown =
+                                format!("{}/{}", shown.trim_end_matches('/'), entry.name);
+                            pending.push((child, child_shown, entry.is_dir, depth + 1));
+                        }
+                    }
+                    Err(e) => {
+                        ctx.write_err(&format!("find: {}: {}", shown, e));
+                        status = 1;
+                    }
+                }
+            }
+        }
+        Ok(status)
+    }
+
+    async fn cmd_du(&mut self, args: &[String], ctx: &mut ExecContext) -> Sh9Result<i32> {
+        let mut human = false;
+        let mut summarize = false;
+        let mut roots: Vec<&str> = Vec::new();
+        for arg in args {
+            match arg.as_str() {
+                "-h" => human = true,
+                "-s" => summarize = true,
+                "-sh" | "-hs" => {
+                    human = true;
+                    summarize = true;
+                }
+                other if other.starts_with('-') && other != "-" => {
+                    ctx.write_err(&format!("du: unknown option: {}", other));
+                    return Ok(1);
+                }
+                path => roots.push(path),
+            }
+        }
+        if roots.is_empty() {
+            roots.push(".");
+        }
+
+        let router = self.router();
+        let mut status = 0;
+        for root in roots {
+            let full_path = self.resolve_path(root);
+            match router.stat(&full_path).await {
+                Ok(info) if info.is_dir && !info.is_symlink => {
+                    let report = DuReport { human, summarize };
+                    if du_tree(&router, full_path, root, report, ctx).await? {
+                        status = 1;
+                    }
+                }
+                Ok(info) => {
+                    ctx.stdout
+                        .writeln(&format!("{}\t{}", du_size(info.size, human), root))
+                        .map_err(Sh9Error::Io)?;
+                }
+                Err(e) => {
+                    ctx.write_err(&format!("du: {}: {}", root, e));
+                    status = 1;
+                }
+            }
+        }
+        Ok(status)
+    }
+
+    async fn cmd_df(&mut self, args: &[String], ctx: &mut ExecContext) -> Sh9Result<i32> {
+        let mut human = false;
+        let mut paths: Vec<&str> = Vec::new();
+        for arg in args {
+            match arg.as_str() {
+                "-h" => human = true,
+                other if other.starts_with('-') => {
+                    ctx.write_err(&format!("df: unknown option: {}", other));
+                    return Ok(1);
+                }
+                path => paths.push(path),
+            }
+        }
+        if paths.is_empty() {
+            paths.push(".");
+        }
+
+        let router = self.router();
+        ctx.stdout
+            .writeln(&format!(
+                "{:>12} {:>12} {:>12} {:>5}  Path",
+                "Size", "Used", "Avail", "Use%"
+            ))
+            .map_err(Sh9Error::Io)?;
+        let mut status = 0;
+        for path in paths {
+            let full_path = self.resolve_path(path);
+            let stats = match router.statfs(&full_path).await {
+                Ok(stats) => stats,
+                Err(e) => {
+                    ctx.write_err(&format!("df: {}: {}", path, e));
+                    status = 1;
+                    continue;
+                }
+            };
+            let used = stats.used_bytes();
+            let percent = if stats.total_bytes == 0 {
+                "-".to_string()
+            } else {
+                format!(
+                    "{}%",
+                    (u128::from(used) * 100).div_ceil(u128::from(stats.total_bytes))
+                )
+            };
+            ctx.stdout
+                .writeln(&format!(
+                    "{:>12} {:>12} {:>12} {:>5}  {}",
+                    du_size(stats.total_bytes, human),
+                    du_size(used, human),
+                    du_size(stats.free_bytes, human),
+                    percent,
+                    path
+                ))
+                .map_err(Sh9Error::Io)?;
+        }
+        Ok(status)
+    }
+
     // cmd_plugin removed — disabled for security reasons
 
     async fn cmd_chmod(&mut self, args: &[String], ctx: &mut ExecContext) -> Sh9Result<i32> {
@@ -914,3 +1123,90 @@ fn format_mount_flags(flags: MountFlags) -> String {
     }
     parts.join(",")
 }
+
+#[derive(Clone, Copy)]
+struct DuReport {
+    human: bool,
+    summarize: bool,
+}
+
+/// One directory on the `du` walk: its running total and the entries not
+/// yet visited. Only the listings along the current path are held at once.
+struct DuFrame {
+    path: String,
+    shown: String,
+    total: u64,
+    children: std::vec::IntoIter<RouteFileInfo>,
+}
+
+fn du_size(bytes: u64, human: bool) -> String {
+    if human {
+        format_size_human(bytes)
+    } else {
+        bytes.to_string()
+    }
+}
+
+/// Walks the directory at `path` depth-first, printing each directory's
+/// subtotal once its subtree is done. Unreadable directories are reported
+/// and counted as empty; returns whether any were.
+async fn du_tree(
+    router: &NamespaceRouter,
+    path: String,
+    shown: &str,
+    report: DuReport,
+    ctx: &mut ExecContext,
+) -> Sh9Result<bool> {
+    let mut had_error = false;
+    let mut open_dir = |path: String,
+                        shown: String,
+                        entries: Result<Vec<RouteFileInfo>, String>,
+                        ctx: &mut ExecContext| {
+        let children = entries.unwrap_or_else(|e| {
+            ctx.write_err(&format!("du: {}: {}", shown, e));
+            had_error = true;
+            Vec::new()
+        });
+        DuFrame {
+            path,
+            shown,
+            total: 0,
+            children: children.into_iter(),
+        }
+    };
+
+    let entries = router.readdir(&path).await;
+    let mut stack = vec![open_dir(path, shown.to_string(), entries, ctx)];
+    while let Some(frame) = stack.last_mut() {
+        if let Some(entry) = frame.children.next() {
+            if !entry.is_dir || entry.is_symlink {
+                frame.total += entry.size;
+                continue;
+            }
+            let child = if frame.path == "/" {
+                format!("/{}", entry.name)
+            } else {
+                format!("{}/{}", frame.path, entry.name)
+            };
+            let child_shown = format!("{}/{}", frame.shown.trim_end_matches('/'), entry.name);
+            let entries = router.readdir(&child).await;
+            stack.push(open_dir(child, child_shown, entries, ctx));
+            continue;
+        }
+
+        let done = stack.pop().expect("frame is on the stack");
+        if !report.summarize || stack.is_empty() {
+            ctx.stdout
+                .writeln(&format!(
+                    "{}\t{}",
+                    du_size(done.total, report.human),
+                    done.shown
+                ))
+                .map_err(Sh9Error::Io)?;
+        }
+        if let Some(parent) = stack.last_mut() {
+            parent.total += done.total;
+        }
+    }
+    Ok(had_error)
+}
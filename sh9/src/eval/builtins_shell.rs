@@ -105,6 +105,8 @@ impl Shell {
                 let enabled = arg == "-o";
                 match name.as_str() {
                     "pipefail" => self.options.pipefail = enabled,
+                    "nullglob" => self.options.nullglob = enabled,
+                    "failglob" => self.options.failglob = enabled,
                     _ => {
                         ctx.write_err(&format!("set: unsupported option: {}", name));
                         return Ok(1);
@@ -151,11 +151,8 @@ impl Shell {
         let mut all_items = Vec::new();
         for item in &for_loop.items {
             let expanded = self.expand_word(item, ctx).await?;
-            let glob_expanded = self.expand_glob(&expanded).await;
-            for glob_item in glob_expanded {
-                for part in glob_item.split_whitespace() {
-                    all_items.push(part.to_string());
-                }
+            for part in expanded.split_whitespace() {
+                all_items.extend(self.expand_glob(part).await?);
             }
         }
 
@@ -386,18 +386,36 @@ impl Shell {
         Ok(output)
     }
 
-    pub(crate) async fn expand_glob(&self, pattern: &str) -> Vec<String> {
+    /// Expands `pattern` against the directory it names. A pattern matching
+    /// nothing stays as is, expands to nothing under `nullglob` or fails
+    /// under `failglob`.
+    pub(crate) async fn expand_glob(&self, pattern: &str) -> Sh9Result<Vec<String>> {
         if !contains_glob_chars(pattern) {
-            return vec![pattern.to_string()];
+            return Ok(vec![pattern.to_string()]);
         }
 
+        let matches = self.glob_matches(pattern).await;
+        if !matches.is_empty() {
+            Ok(matches)
+        } else if self.options.failglob {
+            Err(Sh9Error::Runtime(format!("sh9: no match: {}", pattern)))
+        } else if self.options.nullglob {
+            Ok(Vec::new())
+        } else {
+            Ok(vec![pattern.to_string()])
+        }
+    }
+
+    /// Sorted paths matching `pattern`; only its last component may hold
+    /// glob characters.
+    async fn glob_matches(&self, pattern: &str) -> Vec<String> {
         let (dir, file_pattern) = if pattern.contains('/') {
             let last_slash = pattern.rfind('/').unwrap();
             let dir_part = &pattern[..=last_slash];
             let file_part = &pattern[last_slash + 1..];
 
             if contains_glob_chars(dir_part) {
-                return vec![pattern.to_string()];
+                return Vec::new();
             }
 
             (
@@ -412,7 +430,7 @@ impl Shell {
 
         let entries = match router.readdir(&dir).await {
             Ok(e) => e,
-            Err(_) => return vec![pattern.to_string()],
+            Err(_) => return Vec::new(),
         };
 
         let mut matches: Vec<String> = entries
@@ -429,12 +447,7 @@ impl Shell {
             .collect();
 
         matches.sort();
-
-        if matches.is_empty() {
-            vec![pattern.to_string()]
-        } else {
-            matches
-        }
+        matches
     }
 }
 
@@ -624,10 +637,10 @@ mod tests {
             ns.bind(tmp.path(), "/mnt", MountFlags::MREPL);
         }
 
-        let txt = shell.expand_glob("/mnt/*.txt").await;
+        let txt = shell.expand_glob("/mnt/*.txt").await.unwrap();
         assert_eq!(txt, vec!["/mnt/a.txt", "/mnt/b.txt"]);
 
-        let all = shell.expand_glob("/mnt/*").await;
+        let all = shell.expand_glob("/mnt/*").await.unwrap();
         assert_eq!(all, vec!["/mnt/a.txt", "/mnt/b.txt", "/mnt/c.log"]);
     }
 
@@ -647,7 +660,7 @@ mod tests {
             ns.bind(upper.path(), "/union", MountFlags::MBEFORE);
         }
 
-        let matches = shell.expand_glob("/union/*.txt").await;
+        let matches = shell.expand_glob("/union/*.txt").await.unwrap();
         assert_eq!(
             matches,
             vec!["/union/a.txt", "/union/b.txt", "/union/shared.txt"]
@@ -665,10 +678,10 @@ mod tests {
             ns.bind(tmp.path(), "/mnt", MountFlags::MREPL);
         }
 
-        let no_match = shell.expand_glob("/mnt/*.xyz").await;
+        let no_match = shell.expand_glob("/mnt/*.xyz").await.unwrap();
         assert_eq!(no_match, vec!["/mnt/*.xyz"]);
 
-        let unmounted = shell.expand_glob("/remote/*.txt").await;
+        let unmounted = shell.expand_glob("/remote/*.txt").await.unwrap();
         assert_eq!(unmounted, vec!["/remote/*.txt"]);
     }
 }
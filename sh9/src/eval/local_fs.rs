@@ -1,6 +1,8 @@
 use std::path::{Path, PathBuf};
 use std::{ffi::OsString, fs, io::Write};
 
+use fs9_client::FsStats;
+
 use std::os::unix::fs::{MetadataExt, PermissionsExt};
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -9,7 +11,9 @@ pub struct LocalFileInfo {
     pub size: u64,
     pub is_dir: bool,
     pub mode: u32,
+    pub atime: u64,
     pub mtime: u64,
+    pub ctime: u64,
     pub uid: u32,
     pub gid: u32,
 }
@@ -74,12 +78,40 @@ pub fn local_stat(path: &Path) -> Result<LocalFileInfo, String> {
         size: metadata.len(),
         is_dir: metadata.is_dir(),
         mode: metadata.mode(),
+        atime: u64::try_from(metadata.atime()).unwrap_or(0),
         mtime,
+        ctime: u64::try_from(metadata.ctime()).unwrap_or(0),
         uid: metadata.uid(),
         gid: metadata.gid(),
     })
 }
 
+/// Space on the local filesystem holding `path`, as `statvfs` reports it.
+/// Free space is what an unprivileged user may still write.
+// The `statvfs` field widths vary by platform; they are only u64 on some.
+#[allow(clippy::useless_conversion)]
+pub fn local_statfs(path: &Path) -> Result<FsStats, String> {
+    use std::os::unix::ffi::OsStrExt;
+
+    let c_path = std::ffi::CString::new(path.as_os_str().as_bytes()).map_err(|e| e.to_string())?;
+    let mut stats = std::mem::MaybeUninit::<libc::statvfs>::uninit();
+    // SAFETY: `c_path` is NUL-terminated and `stats` is only read after
+    // `statvfs` reports it filled in.
+    if unsafe { libc::statvfs(c_path.as_ptr(), stats.as_mut_ptr()) } != 0 {
+        return Err(std::io::Error::last_os_error().to_string());
+    }
+    let stats = unsafe { stats.assume_init() };
+    let fragment = u64::from(stats.f_frsize);
+    Ok(FsStats {
+        total_bytes: u64::from(stats.f_blocks) * fragment,
+        free_bytes: u64::from(stats.f_bavail) * fragment,
+        total_inodes: u64::from(stats.f_files),
+        free_inodes: u64::from(stats.f_favail),
+        block_size: u32::try_from(stats.f_bsize).unwrap_or(u32::MAX),
+        max_name_len: u32::try_from(stats.f_namemax).unwrap_or(u32::MAX),
+    })
+}
+
 pub fn local_readdir(path: &Path) -> Result<Vec<LocalFileInfo>, String> {
     let mut entries = fs::read_dir(path)
         .map_err(|e| e.to_string())?
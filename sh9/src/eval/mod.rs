@@ -3,8 +3,8 @@
 use crate::ast::*;
 use crate::error::{Sh9Error, Sh9Result};
 use crate::help::{format_help, get_help, wants_help};
-use crate::shell::Shell;
-use fs9_client::Fs9Client;
+use crate::shell::{CapturedOutput, Shell};
+use fs9_client::{Fs9Client, OpenFlags};
 use std::collections::HashMap;
 use std::future::Future;
 use std::io::Write;
@@ -82,23 +82,20 @@ impl Output {
             } => {
                 if !buffer.is_empty() {
                     let to_write = std::mem::take(buffer);
-                    if let Err(e) = match mode {
-                        FileWriteMode::Write => {
-                            let result = client.write_file(path, &to_write).await;
-                            *mode = FileWriteMode::Append;
-                            result
-                        }
-                        FileWriteMode::Append => {
-                            let existing: bytes::Bytes =
-                                client.read_file(path).await.unwrap_or_default();
-                            let mut combined = existing.to_vec();
-                            combined.extend_from_slice(&to_write);
-                            client.write_file(path, &combined).await
-                        }
-                    } {
-                        let msg = format!("Error flushing to {}: {}\n", path, e);
-                        let _ = std::io::stderr().write_all(msg.as_bytes());
-                    }
+                    // `>` truncates on the first flush only; later flushes of
+                    // the same redirection, and `>>`, append. Stream-like
+                    // providers (pubsubfs, streamfs) treat both as a publish.
+                    let flags = match mode {
+                        FileWriteMode::Write => OpenFlags::create_truncate(),
+                        FileWriteMode::Append => OpenFlags {
+                            create: true,
+                            ..OpenFlags::append()
+                        },
+                    };
+                    *mode = FileWriteMode::Append;
+                    write_through(client, path, flags, &to_write)
+                        .await
+                        .map_err(|e| std::io::Error::other(format!("{}: {}", path, e)))?;
                 }
                 Ok(())
             }
@@ -119,8 +116,7 @@ impl Output {
                             .map_err(|e| e.to_string()),
                     };
                     if let Err(e) = result {
-                        let msg = format!("Error flushing to {}: {}\n", path.display(), e);
-                        let _ = std::io::stderr().write_all(msg.as_bytes());
+                        return Err(std::io::Error::other(format!("{}: {}", path.display(), e)));
                     }
                 }
                 Ok(())
@@ -129,6 +125,37 @@ impl Output {
     }
 }
 
+/// Open `path` with `flags`, write `data` and close the handle again, closing
+/// it even when the write fails.
+async fn write_through(
+    client: &Fs9Client,
+    path: &str,
+    flags: OpenFlags,
+    data: &[u8],
+) -> fs9_client::Result<()> {
+    let handle = client.open(path, flags).await?;
+    let mut offset = 0;
+    let mut written = Ok(());
+    while (offset as usize) < data.len() {
+        match client
+            .write(&handle, offset, &data[offset as usize..])
+            .await
+        {
+            Ok(0) => {
+                written = Err(fs9_client::Fs9Error::Server("short write".to_string()));
+                break;
+            }
+            Ok(n) => offset += n as u64,
+            Err(e) => {
+                written = Err(e);
+                break;
+            }
+        }
+    }
+    let closed = client.close(handle).await;
+    written.and(closed)
+}
+
 pub struct ExecContext {
     pub locals: HashMap<String, String>,
     pub positional: Vec<String>,
@@ -413,61 +440,43 @@ impl Shell {
         }
     }
 
+    /// Run `pipeline`, each stage reading the stdout captured from the one
+    /// before it. A stage that fails with an error reports it on stderr and
+    /// exits non-zero, as an external command would, and the stages after
+    /// it still run on whatever it wrote. The pipeline's status is the last
+    /// stage's, or with `pipefail` the rightmost non-zero one.
     async fn execute_pipeline(
         &mut self,
         pipeline: &Pipeline,
         ctx: &mut ExecContext,
     ) -> Sh9Result<i32> {
-        if pipeline.elements.is_empty() {
+        let Some((last, stages)) = pipeline.elements.split_last() else {
             return Ok(0);
+        };
+        if stages.is_empty() {
+            return self.execute_pipeline_element(last, ctx).await;
         }
 
-        if pipeline.elements.len() == 1 {
-            return self
-                .execute_pipeline_element(&pipeline.elements[0], ctx)
-                .await;
-        }
-
-        // Save the original stdout so the last element writes to the correct destination
-        let mut original_stdout = Some(std::mem::replace(
-            &mut ctx.stdout,
-            Output::Buffer(Vec::new()),
-        ));
-        let mut input: Option<Vec<u8>> = ctx.stdin.take();
-
+        let mut input = ctx.stdin.take();
         let mut exit_codes = Vec::with_capacity(pipeline.elements.len());
-
-        for (i, elem) in pipeline.elements.iter().enumerate() {
-            let is_last = i == pipeline.elements.len() - 1;
-
-            ctx.stdin = input.take();
-
-            if is_last {
-                ctx.stdout = original_stdout.take().unwrap_or(Output::Stdout);
-            } else {
-                ctx.stdout = Output::Buffer(Vec::new());
-            }
-
-            if !is_last {
-                ctx.push_errexit_suppression();
-            }
-
-            let result = self.execute_pipeline_element(elem, ctx).await;
-            if !is_last {
-                ctx.pop_errexit_suppression();
+        for elem in stages {
+            let captured = self.capture_pipeline_stage(elem, ctx, input.take()).await?;
+            if !captured.stderr.is_empty() {
+                let _ = ctx.stderr.write(&captured.stderr);
             }
+            exit_codes.push(captured.exit_code);
+            input = Some(captured.stdout);
+        }
 
-            let exit_code = result?;
-            exit_codes.push(exit_code);
-
-            if !is_last {
-                if let Output::Buffer(buf) =
-                    std::mem::replace(&mut ctx.stdout, Output::Buffer(Vec::new()))
-                {
-                    input = Some(buf);
-                }
+        ctx.stdin = input;
+        let exit_code = match self.execute_pipeline_element(last, ctx).await {
+            Err(e) if !matches!(e, Sh9Error::Exit(_)) => {
+                ctx.write_err(&format!("sh9: {e}"));
+                stage_status(&e)
             }
-        }
+            result => result?,
+        };
+        exit_codes.push(exit_code);
 
         let exit_code = if self.options.pipefail {
             exit_codes
@@ -477,12 +486,44 @@ impl Shell {
                 .find(|code| *code != 0)
                 .unwrap_or(0)
         } else {
-            *exit_codes.last().unwrap_or(&0)
+            exit_code
         };
 
         Ok(exit_code)
     }
 
+    /// Run one stage of a pipeline other than the last on `stdin`, capturing
+    /// its stdout for the next stage. Errors other than `exit` become the
+    /// stage's status and a message in its captured stderr.
+    async fn capture_pipeline_stage(
+        &mut self,
+        elem: &PipelineElement,
+        ctx: &mut ExecContext,
+        stdin: Option<Vec<u8>>,
+    ) -> Sh9Result<CapturedOutput> {
+        let outer_stdout = std::mem::replace(&mut ctx.stdout, Output::Buffer(Vec::new()));
+        ctx.stdin = stdin;
+        ctx.push_errexit_suppression();
+        let result = self.execute_pipeline_element(elem, ctx).await;
+        ctx.pop_errexit_suppression();
+        ctx.stdin = None;
+
+        let stdout = match std::mem::replace(&mut ctx.stdout, outer_stdout) {
+            Output::Buffer(buf) => buf,
+            _ => Vec::new(),
+        };
+        let (exit_code, stderr) = match result {
+            Ok(code) => (code, Vec::new()),
+            Err(e @ Sh9Error::Exit(_)) => return Err(e),
+            Err(e) => (stage_status(&e), format!("sh9: {e}\n").into_bytes()),
+        };
+        Ok(CapturedOutput {
+            exit_code,
+            stdout,
+            stderr,
+        })
+    }
+
     async fn execute_pipeline_element(
         &mut self,
         elem: &PipelineElement,
@@ -562,7 +603,7 @@ impl Shell {
             };
 
             for be in brace_expanded {
-                let glob_expanded = self.expand_glob(&be).await;
+                let glob_expanded = self.expand_glob(&be).await?;
                 args.extend(glob_expanded);
             }
         }
@@ -650,16 +691,26 @@ impl Shell {
             None
         };
 
-        let result = self.execute_builtin(&name, &args, ctx).await;
+        let mut result = self.execute_builtin(&name, &args, ctx).await;
 
+        // A redirection target that cannot be written (read-only file,
+        // missing directory, ...) fails the command, like in POSIX shells.
         if let Some(prev) = saved_stderr {
-            ctx.stderr.flush().await.map_err(Sh9Error::Io)?;
+            let flushed = ctx.stderr.flush().await;
             ctx.stderr = prev;
+            if let Err(e) = flushed {
+                ctx.write_err(&format!("sh9: {}", e));
+                result = result.map(|_| 1);
+            }
         }
 
         if let Some(prev) = saved_stdout {
-            ctx.stdout.flush().await.map_err(Sh9Error::Io)?;
+            let flushed = ctx.stdout.flush().await;
             ctx.stdout = prev;
+            if let Err(e) = flushed {
+                ctx.write_err(&format!("sh9: {}", e));
+                result = result.map(|_| 1);
+            }
         }
 
         if let Ok(code) = &result {
@@ -674,8 +725,8 @@ impl Shell {
         args: &[String],
         ctx: &mut ExecContext,
     ) -> Option<Sh9Result<i32>> {
-        if wants_help(args) {
-            if let Some(cmd_help) = get_help(name) {
+        if let Some(cmd_help) = get_help(name) {
+            if wants_help(cmd_help, args) {
                 let _ = ctx.stdout.write(format_help(cmd_help).as_bytes());
                 return Some(Ok(0));
             }
@@ -751,6 +802,16 @@ impl Shell {
     }
 }
 
+/// The status a pipeline stage exits with when it fails with `err`: 127
+/// for an unknown command, as other shells use, and 1 otherwise.
+fn stage_status(err: &Sh9Error) -> i32 {
+    if matches!(err, Sh9Error::CommandNotFound(_)) {
+        127
+    } else {
+        1
+    }
+}
+
 fn mount_matches_path(target: &str, path: &str) -> bool {
     if target == "/" {
         return true;
@@ -930,6 +991,410 @@ mod tests {
         );
     }
 
+    fn find_fixture() -> (TempDirGuard, Shell) {
+        let root = TempDirGuard::new();
+        fs::create_dir_all(root.path().join("logs/deep")).expect("mkdir failed");
+        for file in ["app.log", "notes.txt", "logs/old.log", "logs/deep/x.log"] {
+            fs::write(root.path().join(file), b"x").expect("write failed");
+        }
+        let shell = Shell::new("http://localhost:8080");
+        shell
+            .namespace
+            .write()
+            .unwrap()
+            .bind(root.path(), "/data", MountFlags::MREPL);
+        (root, shell)
+    }
+
+    async fn find_lines(shell: &mut Shell, command: &str) -> Vec<String> {
+        let output = shell.execute_capture(command).await.expect("find failed");
+        assert_eq!(output.exit_code, 0, "{command}");
+        String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .map(str::to_string)
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_find_walks_depth_first_in_name_order() {
+        let (_root, mut shell) = find_fixture();
+        assert_eq!(
+            find_lines(&mut shell, "find /data").await,
+            [
+                "/data",
+                "/data/app.log",
+                "/data/logs",
+                "/data/logs/deep",
+                "/data/logs/deep/x.log",
+                "/data/logs/old.log",
+                "/data/notes.txt",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_find_name_and_type_predicates() {
+        let (_root, mut shell) = find_fixture();
+        assert_eq!(
+            find_lines(&mut shell, "find /data -name '*.log'").await,
+            [
+                "/data/app.log",
+                "/data/logs/deep/x.log",
+                "/data/logs/old.log"
+            ]
+        );
+        assert_eq!(
+            find_lines(&mut shell, "find /data -type d").await,
+            ["/data", "/data/logs", "/data/logs/deep"]
+        );
+        assert_eq!(
+            find_lines(&mut shell, "find /data -type f -name 'n*'").await,
+            ["/data/notes.txt"]
+        );
+        assert_eq!(
+            find_lines(&mut shell, "find /data -name 'logs' -type f").await,
+            Vec::<String>::new()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_find_maxdepth() {
+        let (_root, mut shell) = find_fixture();
+        assert_eq!(
+            find_lines(&mut shell, "find /data -maxdepth 0").await,
+            ["/data"]
+        );
+        assert_eq!(
+            find_lines(&mut shell, "find /data -name '*.log' -type f -maxdepth 2").await,
+            ["/data/app.log", "/data/logs/old.log"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_find_is_relative_to_working_directory() {
+        let (_root, mut shell) = find_fixture();
+        shell.cwd = "/data/logs".to_string();
+        assert_eq!(
+            find_lines(&mut shell, "find -type f").await,
+            ["./deep/x.log", "./old.log"]
+        );
+        assert_eq!(
+            find_lines(&mut shell, "find deep").await,
+            ["deep", "deep/x.log"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_find_reports_bad_arguments() {
+        let (_root, mut shell) = find_fixture();
+        for command in [
+            "find /data -type x",
+            "find /data -bogus",
+            "find /data/missing",
+        ] {
+            let output = shell.execute_capture(command).await.expect("find failed");
+            assert_eq!(output.exit_code, 1, "{command}");
+            assert!(!output.stderr.is_empty(), "{command}");
+        }
+    }
+
+    fn du_fixture() -> (TempDirGuard, Shell) {
+        let root = TempDirGuard::new();
+        fs::create_dir_all(root.path().join("sub/deep")).expect("mkdir failed");
+        fs::create_dir_all(root.path().join("empty")).expect("mkdir failed");
+        for (file, size) in [
+            ("a.txt", 100),
+            ("d.txt", 50),
+            ("sub/b.txt", 200),
+            ("sub/deep/c.txt", 300),
+        ] {
+            fs::write(root.path().join(file), vec![b'x'; size]).expect("write failed");
+        }
+        let shell = Shell::new("http://localhost:8080");
+        shell
+            .namespace
+            .write()
+            .unwrap()
+            .bind(root.path(), "/data", MountFlags::MREPL);
+        (root, shell)
+    }
+
+    #[tokio::test]
+    async fn test_du_prints_directory_subtotals() {
+        let (_root, mut shell) = du_fixture();
+        assert_eq!(
+            find_lines(&mut shell, "du /data").await,
+            [
+                "0\t/data/empty",
+                "300\t/data/sub/deep",
+                "500\t/data/sub",
+                "650\t/data",
+            ]
+        );
+        assert_eq!(find_lines(&mut shell, "du -s /data").await, ["650\t/data"]);
+        assert_eq!(
+            find_lines(&mut shell, "du /data/sub/b.txt").await,
+            ["200\t/data/sub/b.txt"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_du_human_readable_and_relative() {
+        let (root, mut shell) = du_fixture();
+        fs::write(root.path().join("sub/big.bin"), vec![0u8; 3 * 1024 * 1024]).unwrap();
+        shell.cwd = "/data".to_string();
+        assert_eq!(find_lines(&mut shell, "du -sh sub").await, ["3.0M\tsub"]);
+        assert_eq!(find_lines(&mut shell, "du -s").await, ["3146378\t."]);
+    }
+
+    #[tokio::test]
+    async fn test_du_reports_missing_paths_and_keeps_going() {
+        let (_root, mut shell) = du_fixture();
+        let output = shell
+            .execute_capture("du -s /data/missing /data/sub")
+            .await
+            .expect("du failed");
+        assert_eq!(output.exit_code, 1);
+        assert_eq!(String::from_utf8_lossy(&output.stdout), "500\t/data/sub\n");
+        assert!(String::from_utf8_lossy(&output.stderr).contains("/data/missing"));
+
+        let output = shell
+            .execute_capture("du -x /data")
+            .await
+            .expect("du failed");
+        assert_eq!(output.exit_code, 1);
+    }
+
+    #[tokio::test]
+    async fn test_df_on_a_local_bind_reads_the_host_filesystem() {
+        let (_root, mut shell) = du_fixture();
+        let output = shell.execute_capture("df /data").await.expect("df failed");
+        assert_eq!(output.exit_code, 0);
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let row: Vec<&str> = stdout.lines().nth(1).unwrap().split_whitespace().collect();
+        assert_eq!(row.len(), 5);
+        assert_eq!(row[4], "/data");
+        let total: u64 = row[0].parse().unwrap();
+        let used: u64 = row[1].parse().unwrap();
+        let free: u64 = row[2].parse().unwrap();
+        assert!(total > 0 && used + free <= total);
+    }
+
+    fn log_fixture() -> (TempDirGuard, Shell) {
+        let root = TempDirGuard::new();
+        fs::write(
+            root.path().join("app"),
+            "ok 1\nERROR disk full\nok 2\nERROR timeout\n",
+        )
+        .expect("write failed");
+        let shell = Shell::new("http://localhost:8080");
+        shell
+            .namespace
+            .write()
+            .unwrap()
+            .bind(root.path(), "/logs", MountFlags::MREPL);
+        (root, shell)
+    }
+
+    #[tokio::test]
+    async fn test_three_stage_pipeline_threads_output() {
+        let (_root, mut shell) = log_fixture();
+        let output = shell
+            .execute_capture("cat /logs/app | grep ERROR | wc -l")
+            .await
+            .expect("pipeline failed");
+        assert_eq!(output.exit_code, 0);
+        assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), "2");
+        assert!(output.stderr.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_failing_middle_stage_sets_status_with_pipefail() {
+        let (_root, mut shell) = log_fixture();
+        let command = "cat /logs/app | grep PANIC | wc -l";
+        let output = shell
+            .execute_capture(command)
+            .await
+            .expect("pipeline failed");
+        assert_eq!(output.exit_code, 0);
+        assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), "0");
+
+        shell.options.pipefail = true;
+        let output = shell
+            .execute_capture(command)
+            .await
+            .expect("pipeline failed");
+        assert_eq!(output.exit_code, 1);
+        assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), "0");
+    }
+
+    #[tokio::test]
+    async fn test_erroring_stage_does_not_abort_the_pipeline() {
+        let (_root, mut shell) = log_fixture();
+        shell.options.pipefail = true;
+        let output = shell
+            .execute_capture("cat /logs/app | nosuchcmd | wc -l")
+            .await
+            .expect("pipeline failed");
+        assert_eq!(output.exit_code, 127);
+        assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), "0");
+        assert!(String::from_utf8_lossy(&output.stderr).contains("nosuchcmd"));
+    }
+
+    /// Minimal stand-in for the FS9 HTTP API: records every request as
+    /// `(path, body)`, refuses to open anything named README the way
+    /// pubsubfs does, and reports every path as a directory on a 4 GiB
+    /// filesystem with 1 GiB free.
+    async fn mock_fs9_server() -> (String, Arc<std::sync::Mutex<Vec<(String, String)>>>) {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let log = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let requests = log.clone();
+        tokio::spawn(async move {
+            loop {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let mut raw = Vec::new();
+                let mut buf = [0u8; 4096];
+                let header_end = loop {
+                    let n = socket.read(&mut buf).await.unwrap();
+                    raw.extend_from_slice(&buf[..n]);
+                    if let Some(pos) = raw.windows(4).position(|w| w == b"\r\n\r\n") {
+                        break pos + 4;
+                    }
+                };
+                let head = String::from_utf8_lossy(&raw[..header_end]).to_string();
+                let length: usize = head
+                    .lines()
+                    .find_map(|l| {
+                        l.to_ascii_lowercase()
+                            .strip_prefix("content-length:")
+                            .map(|v| v.trim().parse().unwrap())
+                    })
+                    .unwrap_or(0);
+                while raw.len() < header_end + length {
+                    let n = socket.read(&mut buf).await.unwrap();
+                    raw.extend_from_slice(&buf[..n]);
+                }
+                let path = head.split_whitespace().nth(1).unwrap().to_string();
+                let body = String::from_utf8_lossy(&raw[header_end..]).to_string();
+
+                let (status, reply) = if path.starts_with("/api/v1/open") && body.contains("README")
+                {
+                    (
+                        "403 Forbidden",
+                        r#"{"error":"permission denied: README is read-only","code":403}"#
+                            .to_string(),
+                    )
+                } else if path.starts_with("/api/v1/open") {
+                    ("200 OK", r#"{"handle_id":"h1","metadata":{"path":"/ps/chat","size":0,"file_type":"regular","mode":420,"uid":0,"gid":0,"atime":0,"mtime":0,"ctime":0,"etag":"","symlink_target":null}}"#.to_string())
+                } else if path.starts_with("/api/v1/statfs") {
+                    ("200 OK", r#"{"total_bytes":4294967296,"free_bytes":1073741824,"total_inodes":1000,"free_inodes":900,"block_size":4096,"max_name_len":255}"#.to_string())
+                } else if path.starts_with("/api/v1/stat") {
+                    ("200 OK", r#"{"path":"/","size":0,"file_type":"directory","mode":493,"uid":0,"gid":0,"atime":0,"mtime":0,"ctime":0,"etag":"","symlink_target":null}"#.to_string())
+                } else if path.starts_with("/api/v1/write") {
+                    ("200 OK", format!(r#"{{"bytes_written":{}}}"#, body.len()))
+                } else {
+                    ("200 OK", String::new())
+                };
+                requests.lock().unwrap().push((path, body));
+                let response = format!(
+                    "HTTP/1.1 {status}\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{reply}",
+                    reply.len()
+                );
+                socket.write_all(response.as_bytes()).await.unwrap();
+            }
+        });
+        (url, log)
+    }
+
+    #[tokio::test]
+    async fn test_df_reports_provider_statfs() {
+        let (url, log) = mock_fs9_server().await;
+        let mut shell = Shell::new(&url);
+        shell.connect().await.unwrap();
+
+        let output = shell.execute_capture("df /ps").await.expect("df failed");
+        assert_eq!(output.exit_code, 0);
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let row: Vec<&str> = stdout.lines().nth(1).unwrap().split_whitespace().collect();
+        assert_eq!(
+            row,
+            ["4294967296", "3221225472", "1073741824", "75%", "/ps"]
+        );
+        assert!(log
+            .lock()
+            .unwrap()
+            .iter()
+            .any(|(path, _)| path == "/api/v1/statfs?path=%2Fps"));
+
+        let output = shell.execute_capture("df -h /ps").await.expect("df failed");
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let row: Vec<&str> = stdout.lines().nth(1).unwrap().split_whitespace().collect();
+        assert_eq!(row, ["4.0G", "3.0G", "1.0G", "75%", "/ps"]);
+    }
+
+    #[tokio::test]
+    async fn test_redirection_opens_with_truncate_or_append_flags() {
+        let (url, log) = mock_fs9_server().await;
+        let mut shell = Shell::new(&url);
+        shell.client = Some(Arc::new(Fs9Client::new(&url).unwrap()));
+
+        let output = shell
+            .execute_capture("echo hello > /ps/chat; echo world >> /ps/chat")
+            .await
+            .expect("redirection failed");
+        assert_eq!(output.exit_code, 0);
+        assert!(output.stderr.is_empty());
+
+        let requests = log.lock().unwrap().clone();
+        let endpoints: Vec<&str> = requests
+            .iter()
+            .map(|(path, _)| path.split('?').next().unwrap())
+            .collect();
+        assert_eq!(
+            endpoints,
+            [
+                "/api/v1/open",
+                "/api/v1/write",
+                "/api/v1/close",
+                "/api/v1/open",
+                "/api/v1/write",
+                "/api/v1/close",
+            ]
+        );
+
+        let flags = |i: usize| -> serde_json::Value {
+            serde_json::from_str::<serde_json::Value>(&requests[i].1).unwrap()["flags"].clone()
+        };
+        assert_eq!(flags(0)["truncate"], true);
+        assert_eq!(flags(0)["append"], false);
+        assert_eq!(flags(3)["truncate"], false);
+        assert_eq!(flags(3)["append"], true);
+        assert_eq!(flags(3)["create"], true);
+        assert_eq!(requests[1].1, "hello\n");
+        assert_eq!(requests[4].1, "world\n");
+    }
+
+    #[tokio::test]
+    async fn test_redirection_into_read_only_path_fails_command() {
+        let (url, log) = mock_fs9_server().await;
+        let mut shell = Shell::new(&url);
+        shell.client = Some(Arc::new(Fs9Client::new(&url).unwrap()));
+
+        let output = shell
+            .execute_capture("echo nope >> /ps/README")
+            .await
+            .expect("redirection should not abort the script");
+        assert_eq!(output.exit_code, 1);
+        assert_eq!(
+            String::from_utf8_lossy(&output.stderr),
+            "sh9: /ps/README: permission denied: README is read-only\n"
+        );
+        assert_eq!(log.lock().unwrap().len(), 1);
+    }
+
     #[tokio::test]
     async fn test_heredoc_basic() {
         let mut shell = Shell::new("http://localhost:8080");
@@ -986,4 +1451,114 @@ mod tests {
         assert_eq!(output.exit_code, 0);
         assert_eq!(String::from_utf8_lossy(&output.stdout), "hello world\n");
     }
+
+    fn stat_fixture() -> (TempDirGuard, Shell) {
+        use std::os::unix::fs::PermissionsExt;
+
+        let root = TempDirGuard::new();
+        fs::create_dir(root.path().join("dir")).expect("mkdir failed");
+        fs::set_permissions(root.path().join("dir"), fs::Permissions::from_mode(0o755))
+            .expect("chmod failed");
+        fs::write(root.path().join("ro.txt"), b"hello").expect("write failed");
+        fs::set_permissions(
+            root.path().join("ro.txt"),
+            fs::Permissions::from_mode(0o444),
+        )
+        .expect("chmod failed");
+        let shell = Shell::new("http://localhost:8080");
+        shell
+            .namespace
+            .write()
+            .unwrap()
+            .bind(root.path(), "/data", MountFlags::MREPL);
+        (root, shell)
+    }
+
+    #[tokio::test]
+    async fn test_stat_prints_symbolic_mode() {
+        let (_root, mut shell) = stat_fixture();
+
+        let output = shell
+            .execute_capture("stat /data/dir /data/ro.txt")
+            .await
+            .expect("stat failed");
+        assert_eq!(output.exit_code, 0);
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(stdout.contains("  File: /data/dir\n"), "{stdout}");
+        assert!(stdout.contains("  Type: directory\n"), "{stdout}");
+        assert!(stdout.contains("  Mode: 0755 (drwxr-xr-x)\n"), "{stdout}");
+        assert!(stdout.contains("  File: /data/ro.txt\n"), "{stdout}");
+        assert!(stdout.contains("  Size: 5\n"), "{stdout}");
+        assert!(stdout.contains("  Type: regular file\n"), "{stdout}");
+        assert!(stdout.contains("  Mode: 0444 (-r--r--r--)\n"), "{stdout}");
+        assert!(stdout.contains("Modify: "), "{stdout}");
+    }
+
+    #[tokio::test]
+    async fn test_stat_continues_past_missing_paths() {
+        let (_root, mut shell) = stat_fixture();
+
+        let output = shell
+            .execute_capture("stat /data/missing /data/ro.txt")
+            .await
+            .expect("stat failed");
+        assert_eq!(output.exit_code, 1);
+        assert!(String::from_utf8_lossy(&output.stderr).contains("stat: /data/missing"));
+        assert!(String::from_utf8_lossy(&output.stdout).contains("  File: /data/ro.txt\n"));
+    }
+
+    fn glob_fixture() -> (TempDirGuard, Shell) {
+        let root = TempDirGuard::new();
+        for file in ["c.txt", "a.txt", "b 2.txt", "notes.log"] {
+            fs::write(root.path().join(file), b"x").expect("write failed");
+        }
+        let shell = Shell::new("http://localhost:8080");
+        shell
+            .namespace
+            .write()
+            .unwrap()
+            .bind(root.path(), "/logs", MountFlags::MREPL);
+        (root, shell)
+    }
+
+    #[tokio::test]
+    async fn test_for_loop_runs_once_per_glob_match_in_order() {
+        let (_root, mut shell) = glob_fixture();
+
+        let output = shell
+            .execute_capture("for f in /logs/*.txt; do echo \"[$f]\"; done")
+            .await
+            .expect("for failed");
+        assert_eq!(output.exit_code, 0);
+        assert_eq!(
+            String::from_utf8_lossy(&output.stdout),
+            "[/logs/a.txt]\n[/logs/b 2.txt]\n[/logs/c.txt]\n"
+        );
+
+        let output = shell
+            .execute_capture("echo /logs/[ab]*.txt /logs/?.txt")
+            .await
+            .expect("echo failed");
+        assert_eq!(
+            String::from_utf8_lossy(&output.stdout),
+            "/logs/a.txt /logs/b 2.txt /logs/a.txt /logs/c.txt\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_unmatched_glob_follows_glob_options() {
+        let (_root, mut shell) = glob_fixture();
+        let script = "for f in /logs/*.csv; do echo \"[$f]\"; done";
+
+        let output = shell.execute_capture(script).await.expect("for failed");
+        assert_eq!(String::from_utf8_lossy(&output.stdout), "[/logs/*.csv]\n");
+
+        shell.options.nullglob = true;
+        let output = shell.execute_capture(script).await.expect("for failed");
+        assert!(output.stdout.is_empty());
+
+        shell.options.failglob = true;
+        let err = shell.execute_capture(script).await.unwrap_err();
+        assert!(err.to_string().contains("no match: /logs/*.csv"), "{err}");
+    }
 }
@@ -2,11 +2,11 @@ use std::collections::HashSet;
 use std::path::PathBuf;
 use std::sync::Arc;
 
-use fs9_client::{FileInfo, Fs9Client};
+use fs9_client::{FileInfo, Fs9Client, FsStats};
 
 use super::local_fs::{
     local_append_file, local_chmod, local_copy, local_mkdir, local_read_file, local_readdir,
-    local_remove, local_remove_recursive, local_rename, local_stat, local_truncate,
+    local_remove, local_remove_recursive, local_rename, local_stat, local_statfs, local_truncate,
     local_write_file, safe_resolve, LocalFileInfo,
 };
 use super::namespace::{normalize_path, MountFlags, Namespace};
@@ -17,10 +17,36 @@ pub struct RouteFileInfo {
     pub path: String,
     pub size: u64,
     pub is_dir: bool,
+    pub is_symlink: bool,
     pub mode: u32,
+    pub atime: u64,
     pub mtime: u64,
+    pub ctime: u64,
     pub uid: u32,
     pub gid: u32,
+    pub etag: String,
+    pub symlink_target: Option<String>,
+}
+
+impl RouteFileInfo {
+    /// Synthetic directory entry standing in for a child mount point.
+    fn mount_point(parent: &str, name: String) -> Self {
+        Self {
+            path: join_vfs_path(parent, &name),
+            name,
+            size: 0,
+            is_dir: true,
+            is_symlink: false,
+            mode: 0o755,
+            atime: 0,
+            mtime: 0,
+            ctime: 0,
+            uid: 0,
+            gid: 0,
+            etag: String::new(),
+            symlink_target: None,
+        }
+    }
 }
 
 impl From<LocalFileInfo> for RouteFileInfo {
@@ -30,10 +56,15 @@ impl From<LocalFileInfo> for RouteFileInfo {
             path: String::new(),
             size: value.size,
             is_dir: value.is_dir,
+            is_symlink: false,
             mode: value.mode,
+            atime: value.atime,
             mtime: value.mtime,
+            ctime: value.ctime,
             uid: value.uid,
             gid: value.gid,
+            etag: String::new(),
+            symlink_target: None,
         }
     }
 }
@@ -45,10 +76,15 @@ impl From<&FileInfo> for RouteFileInfo {
             path: value.path.clone(),
             size: value.size,
             is_dir: value.is_dir(),
+            is_symlink: value.file_type.is_symlink(),
             mode: value.mode,
+            atime: value.atime,
             mtime: value.mtime,
+            ctime: value.ctime,
             uid: value.uid,
             gid: value.gid,
+            etag: value.etag.clone(),
+            symlink_target: value.symlink_target.clone(),
         }
     }
 }
@@ -147,16 +183,7 @@ impl NamespaceRouter {
                 let child_mounts = self.namespace.child_mount_names(&normalized);
                 for name in child_mounts {
                     if seen.insert(name.clone()) {
-                        merged.push(RouteFileInfo {
-                            name: name.clone(),
-                            path: join_vfs_path(&normalized, &name),
-                            size: 0,
-                            is_dir: true,
-                            mode: 0o755,
-                            mtime: 0,
-                            uid: 0,
-                            gid: 0,
-                        });
+                        merged.push(RouteFileInfo::mount_point(&normalized, name));
                     }
                 }
                 merged.sort_by(|left, right| left.name.cmp(&right.name));
@@ -179,16 +206,7 @@ impl NamespaceRouter {
         let child_mounts = self.namespace.child_mount_names(&normalized);
         for name in child_mounts {
             if seen.insert(name.clone()) {
-                result.push(RouteFileInfo {
-                    name: name.clone(),
-                    path: join_vfs_path(&normalized, &name),
-                    size: 0,
-                    is_dir: true,
-                    mode: 0o755,
-                    mtime: 0,
-                    uid: 0,
-                    gid: 0,
-                });
+                result.push(RouteFileInfo::mount_point(&normalized, name));
             }
         }
 
@@ -378,6 +396,19 @@ impl NamespaceRouter {
         }
     }
 
+    /// Space on the filesystem serving `path`: the host filesystem behind a
+    /// local bind, otherwise whatever the remote provider reports.
+    pub async fn statfs(&self, path: &str) -> Result<FsStats, String> {
+        let target = self.resolve_existing_target(path).await?;
+        match target {
+            RouteTarget::Local { local_path, .. } => self.local_statfs_blocking(local_path).await,
+            RouteTarget::Remote { path } => {
+                let client = self.require_client()?;
+                client.statfs(&path).await.map_err(|e| e.to_string())
+            }
+        }
+    }
+
     pub fn is_local(&self, path: &str) -> bool {
         self.namespace.is_mounted(path)
     }
@@ -526,6 +557,12 @@ impl NamespaceRouter {
             .map_err(|e| e.to_string())?
     }
 
+    async fn local_statfs_blocking(&self, path: PathBuf) -> Result<FsStats, String> {
+        tokio::task::spawn_blocking(move || local_statfs(&path))
+            .await
+            .map_err(|e| e.to_string())?
+    }
+
     async fn local_readdir_blocking(&self, path: PathBuf) -> Result<Vec<LocalFileInfo>, String> {
         tokio::task::spawn_blocking(move || local_readdir(&path))
             .await
@@ -6,14 +6,15 @@ use fs9_client::OpenFlags;
 use std::future::Future;
 use std::pin::Pin;
 
-pub(crate) fn format_mtime(mtime: u64) -> String {
-    let months = [
-        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
-    ];
-    let days_since_epoch = mtime / 86400;
-    let time_of_day = mtime % 86400;
-    let hours = time_of_day / 3600;
-    let minutes = (time_of_day % 3600) / 60;
+const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+/// Splits seconds since the epoch into (year, zero-based month, day of month,
+/// seconds into the day), in UTC.
+fn civil_time(secs: u64) -> (i64, usize, i64, u64) {
+    let days_since_epoch = secs / 86400;
+    let time_of_day = secs % 86400;
 
     let mut y = 1970i64;
     let mut remaining_days = days_since_epoch as i64;
@@ -50,9 +51,70 @@ pub(crate) fn format_mtime(mtime: u64) -> String {
         remaining_days -= month_days[m] as i64;
         m += 1;
     }
-    let d = remaining_days + 1;
 
-    format!("{} {:>2} {:02}:{:02}", months[m], d, hours, minutes)
+    (y, m, remaining_days + 1, time_of_day)
+}
+
+pub(crate) fn format_mtime(mtime: u64) -> String {
+    let (_, m, d, time_of_day) = civil_time(mtime);
+    let hours = time_of_day / 3600;
+    let minutes = (time_of_day % 3600) / 60;
+
+    format!("{} {:>2} {:02}:{:02}", MONTHS[m], d, hours, minutes)
+}
+
+/// Full UTC timestamp for `stat`, e.g. `2024-03-05 14:07:09 UTC`.
+pub(crate) fn format_timestamp(secs: u64) -> String {
+    let (y, m, d, time_of_day) = civil_time(secs);
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02} UTC",
+        y,
+        m + 1,
+        d,
+        time_of_day / 3600,
+        (time_of_day % 3600) / 60,
+        time_of_day % 60
+    )
+}
+
+/// `ls -l` style mode string: a type character followed by the rwx bits.
+pub(crate) fn format_mode(type_char: char, mode: u32) -> String {
+    let mut out = String::with_capacity(10);
+    out.push(type_char);
+    for (bit, c) in [
+        (0o400, 'r'),
+        (0o200, 'w'),
+        (0o100, 'x'),
+        (0o040, 'r'),
+        (0o020, 'w'),
+        (0o010, 'x'),
+        (0o004, 'r'),
+        (0o002, 'w'),
+        (0o001, 'x'),
+    ] {
+        out.push(if mode & bit != 0 { c } else { '-' });
+    }
+    out
+}
+
+/// `du -h` style size: bytes below 1K, otherwise a power-of-1024 unit with
+/// one decimal below 10 (`1.5K`, `12M`).
+pub(crate) fn format_size_human(bytes: u64) -> String {
+    const UNITS: [&str; 6] = ["K", "M", "G", "T", "P", "E"];
+    if bytes < 1024 {
+        return bytes.to_string();
+    }
+    let mut value = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit + 1 < UNITS.len() {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if value < 10.0 {
+        format!("{value:.1}{}", UNITS[unit])
+    } else {
+        format!("{value:.0}{}", UNITS[unit])
+    }
 }
 
 pub(crate) fn interpret_escape_sequences(s: &str) -> String {
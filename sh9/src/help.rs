@@ -88,6 +88,12 @@ pub const COMMANDS: &[CommandHelp] = &[
         usage: "date [+FORMAT]",
         options: &[("+FORMAT", "Output format (e.g., +%Y-%m-%d %H:%M:%S)")],
     },
+    CommandHelp {
+        name: "df",
+        summary: "Report space on the filesystem holding each path",
+        usage: "df [-h] [PATH...]",
+        options: &[("-h", "Print sizes in human-readable units (1.5K, 12M)")],
+    },
     CommandHelp {
         name: "dirname",
         summary: "Strip last component from filename",
@@ -100,6 +106,15 @@ pub const COMMANDS: &[CommandHelp] = &[
         usage: "download [-r] FS9_PATH LOCAL_PATH",
         options: &[("-r", "Recursively download directories")],
     },
+    CommandHelp {
+        name: "du",
+        summary: "Summarize disk usage of each directory",
+        usage: "du [-h] [-s] [PATH...]",
+        options: &[
+            ("-h", "Print sizes in human-readable units (1.5K, 12M)"),
+            ("-s", "Print only the total for each PATH"),
+        ],
+    },
     CommandHelp {
         name: "echo",
         summary: "Display a line of text",
@@ -143,6 +158,16 @@ pub const COMMANDS: &[CommandHelp] = &[
         usage: "false",
         options: &[],
     },
+    CommandHelp {
+        name: "find",
+        summary: "Search a directory tree for files",
+        usage: "find [PATH...] [-name PATTERN] [-type f|d] [-maxdepth N]",
+        options: &[
+            ("-name PATTERN", "Match the file name against a glob"),
+            ("-type f|d", "Match only files or only directories"),
+            ("-maxdepth N", "Descend at most N levels below PATH"),
+        ],
+    },
     CommandHelp {
         name: "grep",
         summary: "Search for patterns in text",
@@ -302,7 +327,7 @@ pub const COMMANDS: &[CommandHelp] = &[
     CommandHelp {
         name: "set",
         summary: "Display variables or set shell options",
-        usage: "set [+-exu] [-o pipefail] [+o pipefail]",
+        usage: "set [+-exu] [-o OPTION] [+o OPTION]",
         options: &[
             ("(no args)", "Display all shell variables"),
             (
@@ -322,6 +347,14 @@ pub const COMMANDS: &[CommandHelp] = &[
                 "Pipeline returns rightmost non-zero exit status",
             ),
             ("+o pipefail", "Disable pipefail behavior"),
+            (
+                "-o nullglob",
+                "Globs matching nothing expand to nothing",
+            ),
+            (
+                "-o failglob",
+                "Globs matching nothing are an error",
+            ),
         ],
     },
     CommandHelp {
@@ -527,6 +560,10 @@ pub fn format_help_list() -> String {
     out
 }
 
-pub fn wants_help(args: &[String]) -> bool {
-    args.iter().any(|a| a == "--help" || a == "-h")
+/// `--help` always asks for help; `-h` does too unless the command uses it
+/// as an option of its own (`du -h`, `df -h`).
+pub fn wants_help(help: &CommandHelp, args: &[String]) -> bool {
+    let h_is_option = help.options.iter().any(|(flag, _)| *flag == "-h");
+    args.iter()
+        .any(|a| a == "--help" || (a == "-h" && !h_is_option))
 }
@@ -1,5 +1,4 @@
 use clap::Parser;
-use fs9_config::Fs9Config;
 use sh9::{Sh9Error, Shell};
 use std::collections::{HashMap, HashSet};
 use std::env;
@@ -32,7 +31,10 @@ struct Args {
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let args = Args::parse();
 
-    let config = fs9_config::load().unwrap_or_else(|_| Fs9Config::default());
+    let config = fs9_config::load().unwrap_or_else(|e| {
+        eprintln!("Error: Failed to load config: {e}");
+        std::process::exit(1);
+    });
 
     // Server URL priority: CLI arg > env > config > default
     let server_url = args
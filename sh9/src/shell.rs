@@ -1,7 +1,7 @@
 //! Shell state and execution engine
 
 use crate::error::{Sh9Error, Sh9Result};
-use crate::eval::namespace::Namespace;
+use crate::eval::namespace::{normalize_path, Namespace};
 use crate::eval::router::NamespaceRouter;
 use fs9_client::Fs9Client;
 use std::collections::HashMap;
@@ -38,6 +38,10 @@ pub struct ShellOptions {
     pub xtrace: bool,
     pub nounset: bool,
     pub pipefail: bool,
+    /// Unmatched globs expand to nothing instead of themselves.
+    pub nullglob: bool,
+    /// Unmatched globs are an error.
+    pub failglob: bool,
 }
 
 pub struct Shell {
@@ -304,6 +308,38 @@ impl Default for Shell {
     }
 }
 
+/// Complete the path fragment `word` for interactive tab-completion.
+///
+/// The parent directory of `word` is resolved against `cwd` and listed
+/// through `router`; matching entries are returned as replacements for the
+/// whole fragment, with `/` appended to directories. A parent that cannot be
+/// listed (missing, not a directory, unreachable) yields no completions.
+pub async fn complete_path(router: &NamespaceRouter, cwd: &str, word: &str) -> Vec<String> {
+    let (prefix, partial) = match word.rfind('/') {
+        Some(slash) => word.split_at(slash + 1),
+        None => ("", word),
+    };
+    let dir = if prefix.starts_with('/') {
+        normalize_path(prefix)
+    } else {
+        normalize_path(&format!("{cwd}/{prefix}"))
+    };
+
+    let Ok(entries) = router.readdir(&dir).await else {
+        return Vec::new();
+    };
+    let mut completions: Vec<String> = entries
+        .into_iter()
+        .filter(|entry| entry.name.starts_with(partial))
+        .map(|entry| {
+            let suffix = if entry.is_dir { "/" } else { "" };
+            format!("{prefix}{}{suffix}", entry.name)
+        })
+        .collect();
+    completions.sort();
+    completions
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -422,4 +458,114 @@ mod tests {
         let ns = shell.namespace.read().unwrap();
         assert!(ns.list_mounts().is_empty());
     }
+
+    mod completion {
+        use super::*;
+        use crate::eval::namespace::MountFlags;
+        use std::fs;
+        use std::path::{Path, PathBuf};
+        use std::time::{SystemTime, UNIX_EPOCH};
+
+        struct TempDirGuard {
+            path: PathBuf,
+        }
+
+        impl TempDirGuard {
+            fn new() -> Self {
+                let unique = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .expect("time went backwards")
+                    .as_nanos();
+                let path = std::env::temp_dir().join(format!(
+                    "sh9_complete_{}_{}",
+                    std::process::id(),
+                    unique
+                ));
+                fs::create_dir_all(&path).expect("failed to create temp test dir");
+                Self { path }
+            }
+
+            fn path(&self) -> &Path {
+                &self.path
+            }
+        }
+
+        impl Drop for TempDirGuard {
+            fn drop(&mut self) {
+                let _ = fs::remove_dir_all(&self.path);
+            }
+        }
+
+        /// /data/{notes.txt, nested/, new.log, nested/inner.txt}
+        fn fixture() -> (TempDirGuard, NamespaceRouter) {
+            let tmp = TempDirGuard::new();
+            fs::write(tmp.path().join("notes.txt"), b"n").expect("write failed");
+            fs::write(tmp.path().join("new.log"), b"l").expect("write failed");
+            fs::create_dir(tmp.path().join("nested")).expect("mkdir failed");
+            fs::write(tmp.path().join("nested/inner.txt"), b"i").expect("write failed");
+
+            let mut router = NamespaceRouter::new(None);
+            router
+                .namespace
+                .bind(tmp.path(), "/data", MountFlags::MREPL);
+            (tmp, router)
+        }
+
+        #[tokio::test]
+        async fn completes_partial_prefix_of_absolute_path() {
+            let (_tmp, router) = fixture();
+            assert_eq!(
+                complete_path(&router, "/", "/data/ne").await,
+                vec!["/data/nested/", "/data/new.log"]
+            );
+            assert_eq!(
+                complete_path(&router, "/", "/data/no").await,
+                vec!["/data/notes.txt"]
+            );
+        }
+
+        #[tokio::test]
+        async fn directories_get_a_trailing_slash() {
+            let (_tmp, router) = fixture();
+            assert_eq!(
+                complete_path(&router, "/", "/data/").await,
+                vec!["/data/nested/", "/data/new.log", "/data/notes.txt"]
+            );
+            assert_eq!(
+                complete_path(&router, "/", "/data/nested/").await,
+                vec!["/data/nested/inner.txt"]
+            );
+        }
+
+        #[tokio::test]
+        async fn relative_paths_resolve_against_cwd() {
+            let (_tmp, router) = fixture();
+            assert_eq!(
+                complete_path(&router, "/data", "no").await,
+                vec!["notes.txt"]
+            );
+            assert_eq!(
+                complete_path(&router, "/data", "nested/in").await,
+                vec!["nested/inner.txt"]
+            );
+            assert_eq!(
+                complete_path(&router, "/data/nested", "../new").await,
+                vec!["../new.log"]
+            );
+        }
+
+        #[tokio::test]
+        async fn unlistable_parent_yields_nothing() {
+            let (_tmp, router) = fixture();
+            assert!(complete_path(&router, "/", "/data/notes.txt/x")
+                .await
+                .is_empty());
+            assert!(complete_path(&router, "/", "/data/missing/")
+                .await
+                .is_empty());
+            assert!(complete_path(&router, "/", "/data/zzz").await.is_empty());
+            // No mount and no client.
+            assert!(complete_path(&router, "/", "/remote/").await.is_empty());
+        }
+    }
 }
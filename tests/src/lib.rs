@@ -6,6 +6,19 @@ use tokio::sync::OnceCell;
 
 static TEST_SERVER_URL: OnceCell<String> = OnceCell::const_new();
 
+/// A memfs mount the test server serves read-only.
+pub const READ_ONLY_MOUNT: &str = "/readonly";
+
+/// The test server's mounts: a writable memfs root and [`READ_ONLY_MOUNT`].
+const SERVER_CONFIG: &str = r#"
+mounts:
+  - path: "/"
+    provider: memfs
+  - path: "/readonly"
+    provider: memfs
+    read_only: true
+"#;
+
 struct TestServer {
     #[allow(dead_code)]
     process: Child,
@@ -18,7 +31,14 @@ impl TestServer {
         let port = find_free_port()?;
         let url = format!("http://127.0.0.1:{}", port);
 
+        // The config file has to outlive this function, so it is leaked
+        // along with the server process.
+        let config_dir = tempfile::tempdir()?.keep();
+        let config_path = config_dir.join("fs9.yaml");
+        std::fs::write(&config_path, SERVER_CONFIG)?;
+
         let process = Command::new(&server_bin)
+            .env("FS9_CONFIG", &config_path)
             .env("FS9_PORT", port.to_string())
             .env("FS9_HOST", "127.0.0.1")
             // Ensure E2E tests run with auth disabled, regardless of the parent environment.
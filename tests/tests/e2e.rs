@@ -1,5 +1,7 @@
-use fs9_client::{Fs9Client, Fs9Error, OpenFlags};
-use fs9_tests::{generate_test_path, get_server_url};
+use bytes::Bytes;
+use fs9_client::{Fs9Client, Fs9Error, OpenFlags, WalkOptions};
+use fs9_tests::{generate_test_path, get_server_url, READ_ONLY_MOUNT};
+use futures_util::{stream, StreamExt};
 
 #[tokio::test]
 async fn health_check() {
@@ -148,6 +150,38 @@ async fn not_found_error() {
     assert!(matches!(result, Err(Fs9Error::NotFound(_))));
 }
 
+#[tokio::test]
+async fn read_only_mount_write_is_permission_denied() {
+    let url = get_server_url().await;
+    let client = Fs9Client::new(&url).unwrap();
+    let path = format!("{READ_ONLY_MOUNT}{}", generate_test_path("ro"));
+
+    let err = client.write_file(&path, b"nope").await.unwrap_err();
+    assert!(matches!(err, Fs9Error::PermissionDenied(_)), "{err:?}");
+    assert_eq!(err.status(), Some(403));
+}
+
+#[tokio::test]
+async fn existing_and_non_empty_directories_are_told_apart() {
+    let url = get_server_url().await;
+    let client = Fs9Client::new(&url).unwrap();
+    let dir = generate_test_path("nonempty");
+    let file = format!("{dir}/file.txt");
+
+    client.mkdir(&dir).await.unwrap();
+    client.write_file(&file, b"x").await.unwrap();
+
+    let err = client.mkdir(&dir).await.unwrap_err();
+    assert!(matches!(err, Fs9Error::AlreadyExists(_)), "{err:?}");
+
+    let err = client.remove(&dir).await.unwrap_err();
+    assert!(matches!(err, Fs9Error::DirectoryNotEmpty(_)), "{err:?}");
+    assert!(err.to_string().starts_with("directory not empty:"));
+
+    client.remove(&file).await.unwrap();
+    client.remove(&dir).await.unwrap();
+}
+
 #[tokio::test]
 async fn list_mounts() {
     let url = get_server_url().await;
@@ -240,3 +274,125 @@ async fn append_mode() {
 
     client.remove(&path).await.unwrap();
 }
+
+#[tokio::test]
+async fn streaming_round_trip_large_file() {
+    let url = get_server_url().await;
+    let client = Fs9Client::builder(&url)
+        .chunk_size(256 * 1024)
+        .build()
+        .unwrap();
+    let path = generate_test_path("stream");
+
+    let data: Vec<u8> = (0..5 * 1024 * 1024 + 123)
+        .map(|i| (i % 251) as u8)
+        .collect();
+    let pieces: Vec<fs9_client::Result<Bytes>> = data
+        .chunks(700_000)
+        .map(|c| Ok(Bytes::copy_from_slice(c)))
+        .collect();
+
+    let written = client
+        .write_stream(&path, stream::iter(pieces))
+        .await
+        .unwrap();
+    assert_eq!(written, data.len() as u64);
+    assert_eq!(client.stat(&path).await.unwrap().size, data.len() as u64);
+
+    let mut chunks = 0;
+    let mut read_back = Vec::with_capacity(data.len());
+    let mut reader = std::pin::pin!(client.read_stream(&path));
+    while let Some(chunk) = reader.next().await {
+        let chunk = chunk.unwrap();
+        assert!(chunk.len() <= 256 * 1024);
+        read_back.extend_from_slice(&chunk);
+        chunks += 1;
+    }
+    assert_eq!(chunks, 21);
+    assert!(read_back == data);
+
+    client.remove(&path).await.unwrap();
+}
+
+#[tokio::test]
+async fn streaming_errors_are_yielded_as_items() {
+    let url = get_server_url().await;
+    let client = Fs9Client::new(&url).unwrap();
+    let path = generate_test_path("stream_missing");
+
+    let items: Vec<_> = client.read_stream(&path).collect().await;
+    assert_eq!(items.len(), 1);
+    assert!(matches!(items[0], Err(Fs9Error::NotFound(_))));
+
+    let failing = stream::iter(vec![
+        Ok(Bytes::from_static(b"partial")),
+        Err(Fs9Error::Connection("source went away".to_string())),
+    ]);
+    let err = client.write_stream(&path, failing).await.unwrap_err();
+    assert!(matches!(err, Fs9Error::Connection(_)));
+    assert_eq!(&client.read_file(&path).await.unwrap()[..], b"partial");
+
+    client.remove(&path).await.unwrap();
+}
+
+#[tokio::test]
+async fn walk_visits_tree_depth_first_in_path_order() {
+    let url = get_server_url().await;
+    let client = Fs9Client::new(&url).unwrap();
+    let base = generate_test_path("walk");
+
+    for dir in ["", "/a", "/a/deep", "/skip"] {
+        client.mkdir(&format!("{base}{dir}")).await.unwrap();
+    }
+    for file in ["/b.txt", "/a/x.txt", "/a/deep/y.txt", "/skip/z.txt"] {
+        client
+            .write_file(&format!("{base}{file}"), b"data")
+            .await
+            .unwrap();
+    }
+
+    let relative = |items: Vec<fs9_client::Result<fs9_client::FileInfo>>| -> Vec<String> {
+        items
+            .into_iter()
+            .map(|item| item.unwrap().path[base.len()..].to_string())
+            .collect()
+    };
+
+    let all = relative(client.walk(&base).collect().await);
+    assert_eq!(
+        all,
+        [
+            "/a",
+            "/a/deep",
+            "/a/deep/y.txt",
+            "/a/x.txt",
+            "/b.txt",
+            "/skip",
+            "/skip/z.txt"
+        ]
+    );
+
+    let shallow = relative(
+        client
+            .walk_with(&base, WalkOptions::new().max_depth(1))
+            .collect()
+            .await,
+    );
+    assert_eq!(shallow, ["/a", "/b.txt", "/skip"]);
+
+    let skipped = relative(
+        client
+            .walk_with(&base, WalkOptions::new().skip_dir(|d| d.name() == "skip"))
+            .collect()
+            .await,
+    );
+    assert_eq!(
+        skipped,
+        ["/a", "/a/deep", "/a/deep/y.txt", "/a/x.txt", "/b.txt"]
+    );
+
+    for path in all.iter().rev() {
+        client.remove(&format!("{base}{path}")).await.unwrap();
+    }
+    client.remove(&base).await.unwrap();
+}